@@ -1,12 +1,284 @@
+use crate::scenario::NodeId;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SimConfig {
     pub loss_rate: f64,
     pub corrupt_rate: f64,
+    /// Chance that any one bit on the wire is flipped, so a packet of `n` wire bytes
+    /// (payload plus `header_bytes`) arrives corrupted with probability
+    /// `1 - (1 - bit_error_rate)^(8n)`: large segments save headers but fail more often.
+    /// Applies on top of `corrupt_rate`.
+    pub bit_error_rate: f64,
+    /// Chance that the channel cuts a packet's payload short, keeping a random prefix
+    /// and the header as sent. Catches receivers that trust the length a header implies.
+    pub truncate_rate: f64,
+    /// Chance that the channel also delivers a copy of a data packet 1 ms after it, with
+    /// one payload byte changed and the original header and checksum. Catches receivers
+    /// that trust a seq they already validated instead of checking every arrival.
+    pub tamper_duplicate_rate: f64,
     pub min_latency: u64,
     pub max_latency: u64,
+    /// Upper bound (ms) of the random gap inserted between consecutive packets emitted
+    /// by one callback, so bursts (e.g. several ACKs) leave spread out. 0 sends them together.
+    pub burst_jitter: u64,
     pub seed: u64,
+    /// Largest payload (bytes) a protocol may put into a single packet.
+    pub mtu: usize,
+    /// What `start_timer` does when the timer ID is already armed.
+    pub duplicate_timer: DuplicateTimerPolicy,
+    /// Interleave random tag bytes into app payloads (doubling their size) and require
+    /// deliveries to carry them. Tags are stripped before delivered data is recorded,
+    /// so assertions never see them.
+    pub watermark: bool,
+    /// Header bytes counted on top of the payload for every packet in traffic statistics
+    /// (20 for a bare TCP header; 40 also counts IPv4).
+    pub header_bytes: usize,
+    /// Keep only the first N link events of each kind (sends, drops, ...) in the timeline;
+    /// later ones are only counted. `None` keeps everything.
+    pub link_event_limit: Option<usize>,
+    /// Keep only every Nth `SEND` link event in the timeline (the 1st, the N+1th, ...), for
+    /// long transfers. Drops, corruptions, deliveries and other events are all kept, and
+    /// every send is still counted. `None` keeps every send.
+    pub trace_sampling: Option<u64>,
+    /// Keep only the first N `SystemContext::log` messages of a run, both nodes together;
+    /// later ones are only counted, so a protocol logging in a tight loop cannot bloat the
+    /// trace or stall the TUI.
+    pub log_limit: usize,
+    /// Accept at most N distinct `record_metric` names per run, the engine's own metrics
+    /// not counted; values of any further name are only counted.
+    pub metric_name_limit: usize,
+    /// The first `warmup_ms` of a run (e.g. slow start) are left out of throughput and
+    /// efficiency: goodput and wire efficiency in summaries and comparisons count only the
+    /// traffic after it, over the time after it. 0 scores the whole run.
+    pub warmup_ms: u64,
+    /// Sender window (in segments) the lab asks for. Protocols can read it through
+    /// `SystemContext::config`, and `max_in_flight` assertions default to it.
+    pub window_size: Option<u32>,
+    /// Receive buffer (bytes) between the receiver and its application. Deliveries that do
+    /// not fit are rejected, and the receiver can read the free space with
+    /// `SystemContext::receive_window` to advertise it. `None` models no buffer.
+    pub receive_buffer: Option<usize>,
+    /// Bytes per second the receiving application reads out of `receive_buffer`.
+    /// `None` reads everything immediately.
+    pub app_read_rate: Option<u64>,
+    /// Flag any callback that sends more than this many packets at once (e.g. resending the
+    /// whole window on every duplicate ACK) with a warning event. `None` disables the check.
+    pub send_storm_threshold: Option<usize>,
+    /// Metric series longer than this are downsampled for the TUI and GUI charts. Reports
+    /// and trace files always keep every sample.
+    pub chart_max_points: usize,
+    /// What the applications on either end do with the data they receive.
+    pub app_model: AppModel,
+    /// ACK compression window (ms): a pure ACK arriving within this long of an earlier
+    /// pure ACK still on its way to the same node replaces it, so only the latest is
+    /// delivered. `None` delivers every ACK.
+    pub ack_compression: Option<u64>,
+    /// A middlebox on the link that rewrites headers in transit. `None` leaves them as
+    /// the protocols wrote them.
+    pub middlebox: Option<MiddleboxConfig>,
+    /// A token-bucket policer on each direction of the link. Packets that find too few
+    /// tokens are dropped as `POLICED`. `None` lets everything through.
+    pub policer: Option<PolicerConfig>,
+    /// A bottleneck queue in front of each direction of the link, optionally managed by
+    /// RED or CoDel. `None` forwards every packet without queueing.
+    pub bottleneck: Option<BottleneckConfig>,
+    /// Granularity of the simulated clock. At `us`, timers started with
+    /// `SystemContext::start_timer_us` and bottleneck transmission times keep their
+    /// sub-millisecond part and `SystemContext::now_us` reports it.
+    pub time_resolution: TimeResolution,
+    /// Which of the events due at the same instant run first, for probing code that
+    /// depends on an order. By default they run in the order they were scheduled.
+    pub tie_break: TieBreak,
+    /// Record every protocol callback in the report's `call_log`, with its arguments,
+    /// wall-clock duration and outcome, as ground truth for disputes over what a protocol
+    /// was called with.
+    pub call_log: bool,
+    /// How loss, corruption, latency and jitter are drawn from `seed`. With the default,
+    /// one stream serves every packet, so a single extra packet shifts every later draw.
+    pub channel_randomness: ChannelRandomness,
+    /// The MSS option each side carries in its SYN, for connection-mode labs. A protocol
+    /// reads the value its peer advertised with `SystemContext::peer_mss` once that SYN has
+    /// reached it. `None` carries no MSS option.
+    pub mss: Option<MssConfig>,
+}
+
+impl SimConfig {
+    /// The settings as strings (unset options omitted), for bridges that hand
+    /// protocols a plain string map instead of a typed struct.
+    pub fn to_params(&self) -> BTreeMap<String, String> {
+        let serde_json::Value::Object(fields) =
+            serde_json::to_value(self).expect("SimConfig serializes to a JSON object")
+        else {
+            unreachable!("SimConfig serializes to a JSON object");
+        };
+        fields
+            .into_iter()
+            .filter_map(|(key, value)| match value {
+                serde_json::Value::Null => None,
+                serde_json::Value::String(s) => Some((key, s)),
+                other => Some((key, other.to_string())),
+            })
+            .collect()
+    }
+}
+
+/// Behavior of `start_timer` for a timer ID that is still pending.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateTimerPolicy {
+    /// The pending expiry is cancelled and the timer restarts with the new delay.
+    #[default]
+    Replace,
+    /// The call is rejected and the pending expiry is kept.
+    Reject,
+    /// Each call schedules its own expiry; `cancel_timer` cancels all of them.
+    AllowMultiple,
+}
+
+/// Granularity of the simulated clock (`SimConfig::time_resolution`). The engine counts in
+/// microseconds either way; at `Ms` every event lands on a whole millisecond.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeResolution {
+    #[default]
+    Ms,
+    Us,
+}
+
+/// Order of events due at the same instant (`SimConfig::tie_break`). They run in three
+/// bands: the kinds set to `before_timers`, then timer expiries and every other event,
+/// then the kinds set to `after_timers`. Within a band they run in scheduling order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TieBreak {
+    /// Application sends, including the echo of `AppModel::Echo`.
+    pub app_send: TieOrder,
+    /// Packets arriving at a node, from the channel or injected by the host.
+    pub packet: TieOrder,
+}
+
+/// Where one kind of event goes among others due at the same instant; see [`TieBreak`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TieOrder {
+    /// In the order it was scheduled.
+    #[default]
+    Scheduled,
+    BeforeTimers,
+    AfterTimers,
+}
+
+/// Where the channel's random decisions come from (`SimConfig::channel_randomness`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelRandomness {
+    /// One stream drawn in the order packets are sent.
+    #[default]
+    Stream,
+    /// Each packet's draws depend only on the seed, its direction and how many packets
+    /// were sent that way before it. The Nth data segment meets the same fate in two
+    /// implementations even if one of them sends an extra ACK. The bottleneck AQM and the
+    /// byte a corruption flips still come from their own streams.
+    PerPacket,
+}
+
+/// MSS (largest data payload, in bytes) each side advertises in its SYN or SYN-ACK. Both
+/// sides must then keep their data segments within the smaller of the two.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MssConfig {
+    pub sender: u16,
+    pub receiver: u16,
+}
+
+impl MssConfig {
+    /// The MSS `node` advertises.
+    pub fn of(&self, node: NodeId) -> u16 {
+        match node {
+            NodeId::Sender => self.sender,
+            NodeId::Receiver => self.receiver,
+        }
+    }
+}
+
+/// Built-in middlebox behaviors. Rewrites are deterministic and leave the checksum as it
+/// was, like a middlebox that does not know the protocol's checksum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum MiddleboxConfig {
+    /// NAT: the sender's source port becomes `external_port` on the way out, and packets
+    /// coming back to `external_port` are sent on to the sender's original port.
+    Nat { external_port: u16 },
+    /// A sequence-randomizing firewall: adds `offset` to the seq of every sender segment
+    /// and takes it off the ACK number of every receiver segment. With `one_way = true`
+    /// the ACKs are left alone, so the sender sees ACKs for data it never sent.
+    SeqOffset {
+        offset: u32,
+        #[serde(default)]
+        one_way: bool,
+    },
+}
+
+/// Token bucket applied separately to each direction. Every packet costs its payload plus
+/// `header_bytes` in tokens. The bucket holds at most `burst` bytes, starts full and
+/// refills at `rate` bytes per second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicerConfig {
+    pub rate: u64,
+    pub burst: u64,
+}
+
+/// FIFO queue applied separately to each direction, drained at `rate` bytes per second
+/// (payload plus `header_bytes`). A packet that would wait more than `buffer_ms` is
+/// dropped as `QUEUE_DROP`; `aqm` may drop or mark packets before that.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BottleneckConfig {
+    pub rate: u64,
+    pub buffer_ms: u64,
+    #[serde(default)]
+    pub aqm: Option<AqmConfig>,
+}
+
+/// Active queue management on the bottleneck, deciding by the queueing delay a packet
+/// would see. With `ecn = true`, packets carrying data are marked with `flags::ECE`
+/// instead of dropped, and the receiver is expected to echo the mark on its ACKs.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum AqmConfig {
+    /// Random early detection: no early drops below `min_ms` of queueing delay, then a
+    /// probability rising linearly to `max_p` at `max_ms`, and every packet above it.
+    Red {
+        min_ms: u64,
+        max_ms: u64,
+        max_p: f64,
+        #[serde(default)]
+        ecn: bool,
+    },
+    /// CoDel: once the queueing delay has stayed above `target_ms` for `interval_ms`,
+    /// drop a packet, then more and more often (`interval_ms / sqrt(n)` apart) until the
+    /// delay falls below `target_ms` again.
+    Codel {
+        target_ms: u64,
+        interval_ms: u64,
+        #[serde(default)]
+        ecn: bool,
+    },
+}
+
+/// Behavior of the applications above the two protocols.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AppModel {
+    /// Data flows from the sender's application to the receiver's only.
+    #[default]
+    OneWay,
+    /// The receiver's application sends every delivery straight back through the
+    /// receiver's protocol, and the sender's application expects it returned.
+    Echo,
 }
 
 impl Default for SimConfig {
@@ -14,9 +286,37 @@ impl Default for SimConfig {
         Self {
             loss_rate: 0.0,
             corrupt_rate: 0.0,
+            bit_error_rate: 0.0,
+            truncate_rate: 0.0,
+            tamper_duplicate_rate: 0.0,
             min_latency: 10,
             max_latency: 100,
+            burst_jitter: 0,
             seed: 0,
+            mtu: 1500,
+            duplicate_timer: DuplicateTimerPolicy::default(),
+            watermark: false,
+            header_bytes: 20,
+            link_event_limit: None,
+            trace_sampling: None,
+            log_limit: 10_000,
+            metric_name_limit: 32,
+            warmup_ms: 0,
+            window_size: None,
+            receive_buffer: None,
+            app_read_rate: None,
+            send_storm_threshold: None,
+            chart_max_points: 2000,
+            app_model: AppModel::default(),
+            ack_compression: None,
+            middlebox: None,
+            policer: None,
+            bottleneck: None,
+            time_resolution: TimeResolution::default(),
+            tie_break: TieBreak::default(),
+            call_log: false,
+            channel_randomness: ChannelRandomness::default(),
+            mss: None,
         }
     }
 }
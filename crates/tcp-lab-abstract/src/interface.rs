@@ -1,4 +1,81 @@
+use crate::config::SimConfig;
 use crate::packet::Packet;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Mutex, PoisonError};
+
+/// Non-fatal problems a language bridge hit while passing a protocol's call on, reported
+/// through `SystemContext::report_warning`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BridgeWarning {
+    /// A log message, metric name or state name was not valid UTF-8.
+    InvalidUtf8,
+    /// A context method was called outside a callback, e.g. from a thread of the protocol's
+    /// own. The bridge reports it at the next callback.
+    NoContext,
+    /// An argument could not be converted, e.g. a byte array or a null pointer.
+    ConversionFailed,
+    /// A call into the protocol's language failed, e.g. with an uncaught Java exception.
+    CallFailed,
+}
+
+/// Warnings a bridge holds per kind, with their details, until the next callback; the
+/// rest are only counted.
+pub const PENDING_WARNINGS_KEPT: usize = 100;
+
+/// Warnings a language bridge raised while no context was installed, e.g. on a protocol's
+/// own thread, held for the next callback. A protocol can raise them in a loop, so only
+/// the first `PENDING_WARNINGS_KEPT` of each kind keep their details.
+pub struct PendingWarnings(Mutex<HeldWarnings>);
+
+#[derive(Default)]
+struct HeldWarnings {
+    kept: Vec<(BridgeWarning, String)>,
+    /// Warnings past the kept ones, per kind.
+    dropped: BTreeMap<BridgeWarning, u64>,
+}
+
+impl PendingWarnings {
+    pub const fn new() -> Self {
+        Self(Mutex::new(HeldWarnings {
+            kept: Vec::new(),
+            dropped: BTreeMap::new(),
+        }))
+    }
+
+    pub fn push(&self, kind: BridgeWarning, detail: String) {
+        let mut held = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let kept = held.kept.iter().filter(|(held, _)| *held == kind).count();
+        if kept < PENDING_WARNINGS_KEPT {
+            held.kept.push((kind, detail));
+        } else {
+            *held.dropped.entry(kind).or_insert(0) += 1;
+        }
+    }
+
+    /// Report the held warnings to `ctx`, those without details as one per kind.
+    pub fn flush(&self, ctx: &mut dyn SystemContext) {
+        let held = std::mem::take(&mut *self.0.lock().unwrap_or_else(PoisonError::into_inner));
+        for (kind, detail) in held.kept {
+            ctx.report_warning(kind, &detail);
+        }
+        for (kind, count) in held.dropped {
+            ctx.report_warning(kind, &format!("{count} more outside callbacks"));
+        }
+    }
+
+    /// Forget the held warnings, e.g. ones a previous run left behind.
+    pub fn clear(&self) {
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner) = HeldWarnings::default();
+    }
+}
+
+impl Default for PendingWarnings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 /// The capability provided by the simulator to the student's protocol.
 /// Students call these methods to interact with the network and application layer.
@@ -8,9 +85,10 @@ pub trait SystemContext {
 
     /// Start a timer.
     /// `timer_id` is a user-defined ID to identify this timer (e.g. matching a sequence number).
+    /// ID 0 and IDs with the high bit set (negative in Java/C++) are reserved for the engine.
     /// `delay_ms` is the duration in milliseconds.
-    /// Note: If a timer with the same ID already exists, behavior depends on implementation (usually overwrite or dual).
-    /// Recommendation: Use unique IDs or cancel before start.
+    /// If a timer with the same ID is still pending, `SimConfig::duplicate_timer` decides
+    /// whether it is restarted (default), the call is rejected, or both expiries fire.
     fn start_timer(&mut self, delay_ms: u64, timer_id: u32);
 
     /// Cancel a running timer.
@@ -19,17 +97,123 @@ pub trait SystemContext {
     /// Deliver data to the Application Layer (e.g. when a sequence is complete and valid).
     fn deliver_data(&mut self, data: &[u8]);
 
+    /// Deliver data that starts at byte `offset` of the reassembled stream (counted from 0
+    /// across every delivery), so the simulator can check the claim: the bytes must be the
+    /// ones the application sent there, and each delivery must start where the previous one
+    /// ended. Protocols that do not track offsets can keep using `deliver_data`.
+    fn deliver_data_at(&mut self, offset: u64, data: &[u8]) {
+        let _ = offset;
+        self.deliver_data(data);
+    }
+
     /// Log a message to the simulator's debug output.
     fn log(&mut self, message: &str);
 
     /// Get current simulation time in ms
     fn now(&self) -> u64;
 
+    /// Current simulation time in µs. Finer than `now() * 1000` only when
+    /// `SimConfig::time_resolution` is `us`.
+    fn now_us(&self) -> u64 {
+        self.now() * 1000
+    }
+
+    /// Start a timer that expires after `delay_us` microseconds. Unless
+    /// `SimConfig::time_resolution` is `us`, the delay is rounded up to whole ms.
+    fn start_timer_us(&mut self, delay_us: u64, timer_id: u32) {
+        self.start_timer(delay_us.div_ceil(1000), timer_id);
+    }
+
     /// Record a numeric metric for visualization / grading (e.g., cwnd, ssthresh).
     /// Implementations may aggregate these for later inspection in the TUI or grader.
     fn record_metric(&mut self, _name: &str, _value: f64) {
         // Default no-op so non-visual environments don't need to care.
     }
+
+    /// Note that the protocol's state machine entered state `name` (e.g. "WAIT_ACK_0"), so
+    /// the visualizers can draw a state timeline next to the textbook FSM diagram.
+    fn record_state(&mut self, _name: &str) {
+        // Default no-op, like `record_metric`.
+    }
+
+    /// A sandboxed bridge blocked the protocol from doing something graded runs forbid, such
+    /// as writing a file or opening a socket; `what` describes the attempt. The simulator
+    /// records it as an integrity violation.
+    fn report_violation(&mut self, _what: &str) {
+        // Default no-op, like `record_metric`.
+    }
+
+    /// The bridge had to ignore or patch up one of the protocol's calls; `detail` says
+    /// which. The simulator counts these per kind in the report.
+    fn report_warning(&mut self, _kind: BridgeWarning, _detail: &str) {
+        // Default no-op, like `record_metric`.
+    }
+
+    /// Parameters the scenario passes to this node (`[params.sender]` / `[params.receiver]`),
+    /// e.g. a window size or RTO, so one implementation can be run in several configurations.
+    /// Values are strings; numbers and booleans arrive in their TOML spelling.
+    fn init_params(&self) -> BTreeMap<String, String> {
+        BTreeMap::new()
+    }
+
+    /// Simulation settings in effect, e.g. `window_size` or `mtu`.
+    fn config(&self) -> SimConfig {
+        SimConfig::default()
+    }
+
+    /// Free bytes in the receiver's buffer (`SimConfig::receive_buffer`), already reduced by
+    /// data delivered earlier in this callback; the value to advertise in ACK windows.
+    /// `None` on the sender or when the scenario models no buffer.
+    fn receive_window(&self) -> Option<u64> {
+        None
+    }
+
+    /// MSS the peer advertised in a SYN that has reached this node (`SimConfig::mss`); keep
+    /// data segments within it and your own MSS. `None` before that SYN arrives or when the
+    /// scenario models no MSS option.
+    fn peer_mss(&self) -> Option<u16> {
+        None
+    }
+
+    /// Keep `value` under `key` in this node's scratch store, which outlives the run when
+    /// the host opted in (`tcp-lab-sim-cli --scratch-store FILE`), e.g. a parameter tuned
+    /// over several runs. Does nothing otherwise, graded runs included.
+    fn store(&mut self, _key: &str, _value: &str) {
+        // Default no-op, like `record_metric`.
+    }
+
+    /// The value last stored under `key`, in this run or an earlier one. `None` without a
+    /// scratch store.
+    fn load(&self, _key: &str) -> Option<String> {
+        None
+    }
+
+    /// Label the next packet this node sends with `note` (e.g. "fast-retransmit"), shown on
+    /// its arrow in the space-time diagram and kept in the trace. A later call before that
+    /// send replaces the note; long notes are cut short.
+    fn annotate_packet(&mut self, _note: &str) {
+        // Default no-op, like `record_metric`.
+    }
+
+    /// Ask the application to stop handing over data, e.g. while the send window is
+    /// full, instead of dropping what `on_app_data` cannot take. The simulator holds the
+    /// application's sends until `app_resume` and reports how long they waited. Sender only.
+    fn app_pause(&mut self) {
+        // Default no-op, like `record_metric`.
+    }
+
+    /// Let the application hand over data again; sends held since `app_pause` arrive in
+    /// order through `on_app_data`, right after this callback.
+    fn app_resume(&mut self) {
+        // Default no-op, like `record_metric`.
+    }
+
+    /// Turn down the data `on_app_data` is handing over, e.g. because the sender is still
+    /// waiting for an ACK, saying why. The simulator counts the send as failed rather than
+    /// silently lost (see the `no_silent_app_data_loss` assertion). Sender's `on_app_data` only.
+    fn refuse_app_data(&mut self, _reason: &str) {
+        // Default no-op, like `record_metric`.
+    }
 }
 
 /// The interface that students must implement.
@@ -46,4 +230,25 @@ pub trait TransportProtocol {
     /// Called when the Application Layer wants to send data reliably.
     /// The protocol should encapsulate this data into packets and send them.
     fn on_app_data(&mut self, ctx: &mut dyn SystemContext, data: &[u8]);
+
+    /// Called when the application closes the connection (`TestAction::Shutdown`). It
+    /// writes nothing more, but the data it already handed over must still be delivered.
+    fn on_close(&mut self, _ctx: &mut dyn SystemContext) {}
+
+    /// Diagnostic hook: the checksum this protocol computes over `data`, or `None` if it
+    /// does not expose one. Never called during a simulation; `tcp-lab-sim-cli checksum`
+    /// uses it to compare the protocol's checksum with the reference test vectors.
+    fn checksum(&mut self, _ctx: &mut dyn SystemContext, _data: &[u8]) -> Option<u16> {
+        None
+    }
+
+    /// Implementation language, used to phrase diagnostics in the student's terms.
+    /// Bridges override this; native Rust protocols keep the default.
+    fn language(&self) -> &'static str {
+        "rust"
+    }
 }
+
+/// Builds a fresh protocol instance, used to restart a node mid-run with its state lost
+/// (`TestAction::RestartNode`).
+pub type ProtocolFactory = Box<dyn FnMut() -> anyhow::Result<Box<dyn TransportProtocol>>>;
@@ -2,11 +2,21 @@ pub mod config;
 pub mod interface;
 pub mod packet;
 pub mod scenario;
+pub mod sequence;
 
-pub use interface::{SystemContext, TransportProtocol};
+pub use interface::{
+    BridgeWarning, PendingWarnings, ProtocolFactory, SystemContext, TransportProtocol,
+};
 pub use packet::{Packet, TcpHeader};
 // Re-export flags module from packet so users can access TcpHeader::Flags
 pub use packet::flags;
 
-pub use config::SimConfig;
-pub use scenario::{SimConfigOverride, TestAction, TestAssertion, TestScenario};
+pub use config::{
+    AppModel, AqmConfig, BottleneckConfig, ChannelRandomness, DuplicateTimerPolicy,
+    MiddleboxConfig, MssConfig, PolicerConfig, SimConfig, TieBreak, TieOrder, TimeResolution,
+};
+pub use scenario::{
+    AppSendCondition, AssertionPolicy, InitParams, MetricBand, NodeId, PayloadMatch, SeqUnit,
+    SimConfigOverride, SuiteInfo, TestAction, TestAssertion, TestScenario,
+};
+pub use sequence::{PacketEventKind, PacketStep};
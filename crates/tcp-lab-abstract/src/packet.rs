@@ -8,6 +8,41 @@ pub mod flags {
     pub const PSH: u8 = 0x08;
     pub const ACK: u8 = 0x10;
     pub const URG: u8 = 0x20;
+    /// Congestion experienced. Set on data by an ECN-enabled bottleneck AQM (standing in for
+    /// the IP CE codepoint); receivers echo it on their ACKs.
+    pub const ECE: u8 = 0x40;
+
+    const NAMES: [(u8, &str); 7] = [
+        (FIN, "FIN"),
+        (SYN, "SYN"),
+        (RST, "RST"),
+        (PSH, "PSH"),
+        (ACK, "ACK"),
+        (URG, "URG"),
+        (ECE, "ECE"),
+    ];
+
+    /// The flag named `name` (case-insensitive), e.g. `"syn"`.
+    pub fn from_name(name: &str) -> Option<u8> {
+        NAMES
+            .iter()
+            .find(|(_, n)| n.eq_ignore_ascii_case(name))
+            .map(|(bit, _)| *bit)
+    }
+
+    /// The flags set in `bits` joined by `|`, e.g. `SYN|ACK`, or `-` when none are.
+    pub fn names(bits: u8) -> String {
+        let names: Vec<&str> = NAMES
+            .iter()
+            .filter(|(bit, _)| bits & bit != 0)
+            .map(|(_, name)| *name)
+            .collect();
+        if names.is_empty() {
+            "-".to_string()
+        } else {
+            names.join("|")
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
@@ -82,7 +117,23 @@ impl Packet {
         }
     }
 
+    /// Payload length only; see [`Packet::wire_len`] for the size on the wire.
     pub fn len(&self) -> usize {
-        self.payload.len() // Simplified: only payload length matters for some metrics
+        self.payload.len()
+    }
+
+    /// Bytes this packet occupies on the wire given a per-packet header size.
+    pub fn wire_len(&self, header_bytes: usize) -> usize {
+        header_bytes + self.payload.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.payload.is_empty()
+    }
+
+    /// An ACK with no payload and no SYN, FIN or RST.
+    pub fn is_pure_ack(&self) -> bool {
+        self.payload.is_empty()
+            && self.header.flags & (flags::ACK | flags::SYN | flags::FIN | flags::RST) == flags::ACK
     }
 }
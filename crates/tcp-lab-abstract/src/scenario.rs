@@ -1,22 +1,99 @@
-use crate::config::SimConfig;
-use serde::Deserialize;
+use crate::config::{
+    AppModel, BottleneckConfig, ChannelRandomness, DuplicateTimerPolicy, MiddleboxConfig,
+    MssConfig, PolicerConfig, SimConfig, TieBreak, TimeResolution,
+};
+use crate::packet::flags;
+use crate::sequence::PacketStep;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
 
 #[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct TestScenario {
     pub name: String,
     pub description: String,
+    /// Labels such as `"rdt3"` or `"required"` that suite runs can select or skip
+    /// scenarios by.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// The part of the lab grade this scenario counts toward in a suite's gradebook, e.g.
+    /// `"robustness"`; scenarios without one share the `default` track.
+    #[serde(default)]
+    pub track: Option<String>,
+    /// What this scenario grades and how, in words meant for students, e.g. `"Recovers
+    /// from a lost ACK without duplicating data (10 points)"`. It is carried into the
+    /// JSON reports next to the scenario's outcome.
+    #[serde(default)]
+    pub rubric: Option<String>,
+    /// The oldest tcp-lab release that can grade this scenario, e.g. `"0.2.0"`, so an
+    /// outdated grader refuses it instead of misreading it.
+    #[serde(default)]
+    pub min_tool_version: Option<String>,
+    /// Whether the assertions after the first failed one are still checked.
+    #[serde(default)]
+    pub assertion_policy: AssertionPolicy,
     pub config: SimConfigOverride,
+    #[serde(default)]
+    pub params: InitParams,
     pub actions: Vec<TestAction>,
     pub assertions: Vec<TestAssertion>,
+    /// Ranges named metrics are expected to stay in, e.g.
+    /// `metric_bands = [{ metric = "cwnd", max = 64 }]`. The TUI draws them on the charts
+    /// and the grader warns when a metric leaves its band, without failing the scenario.
+    #[serde(default)]
+    pub metric_bands: Vec<MetricBand>,
+}
+
+impl TestScenario {
+    /// Time limit (ms) of a run: the `max_duration` assertion's, or 10 s without one.
+    pub fn max_duration(&self) -> u64 {
+        self.assertions
+            .iter()
+            .find_map(|a| match a {
+                TestAssertion::MaxDuration { ms } => Some(*ms),
+                _ => None,
+            })
+            .unwrap_or(10000)
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct SimConfigOverride {
     pub loss_rate: Option<f64>,
     pub corrupt_rate: Option<f64>,
+    pub bit_error_rate: Option<f64>,
+    pub truncate_rate: Option<f64>,
+    pub tamper_duplicate_rate: Option<f64>,
     pub min_latency: Option<u64>,
     pub max_latency: Option<u64>,
+    pub burst_jitter: Option<u64>,
     pub seed: Option<u64>,
+    pub mtu: Option<usize>,
+    pub duplicate_timer: Option<DuplicateTimerPolicy>,
+    pub watermark: Option<bool>,
+    pub header_bytes: Option<usize>,
+    pub link_event_limit: Option<usize>,
+    pub trace_sampling: Option<u64>,
+    pub log_limit: Option<usize>,
+    pub metric_name_limit: Option<usize>,
+    pub warmup_ms: Option<u64>,
+    pub window_size: Option<u32>,
+    pub receive_buffer: Option<usize>,
+    pub app_read_rate: Option<u64>,
+    pub send_storm_threshold: Option<usize>,
+    pub chart_max_points: Option<usize>,
+    pub app_model: Option<AppModel>,
+    pub ack_compression: Option<u64>,
+    pub middlebox: Option<MiddleboxConfig>,
+    pub policer: Option<PolicerConfig>,
+    pub bottleneck: Option<BottleneckConfig>,
+    pub time_resolution: Option<TimeResolution>,
+    pub tie_break: Option<TieBreak>,
+    pub call_log: Option<bool>,
+    pub channel_randomness: Option<ChannelRandomness>,
+    pub mss: Option<MssConfig>,
 }
 
 impl SimConfigOverride {
@@ -27,33 +104,434 @@ impl SimConfigOverride {
         if let Some(v) = self.corrupt_rate {
             config.corrupt_rate = v;
         }
+        if let Some(v) = self.bit_error_rate {
+            config.bit_error_rate = v;
+        }
+        if let Some(v) = self.truncate_rate {
+            config.truncate_rate = v;
+        }
+        if let Some(v) = self.tamper_duplicate_rate {
+            config.tamper_duplicate_rate = v;
+        }
         if let Some(v) = self.min_latency {
             config.min_latency = v;
         }
         if let Some(v) = self.max_latency {
             config.max_latency = v;
         }
+        if let Some(v) = self.burst_jitter {
+            config.burst_jitter = v;
+        }
         if let Some(v) = self.seed {
             config.seed = v;
         }
+        if let Some(v) = self.mtu {
+            config.mtu = v;
+        }
+        if let Some(v) = self.duplicate_timer {
+            config.duplicate_timer = v;
+        }
+        if let Some(v) = self.watermark {
+            config.watermark = v;
+        }
+        if let Some(v) = self.header_bytes {
+            config.header_bytes = v;
+        }
+        if let Some(v) = self.link_event_limit {
+            config.link_event_limit = Some(v);
+        }
+        if let Some(v) = self.trace_sampling {
+            config.trace_sampling = Some(v);
+        }
+        if let Some(v) = self.log_limit {
+            config.log_limit = v;
+        }
+        if let Some(v) = self.metric_name_limit {
+            config.metric_name_limit = v;
+        }
+        if let Some(v) = self.warmup_ms {
+            config.warmup_ms = v;
+        }
+        if let Some(v) = self.window_size {
+            config.window_size = Some(v);
+        }
+        if let Some(v) = self.receive_buffer {
+            config.receive_buffer = Some(v);
+        }
+        if let Some(v) = self.app_read_rate {
+            config.app_read_rate = Some(v);
+        }
+        if let Some(v) = self.send_storm_threshold {
+            config.send_storm_threshold = Some(v);
+        }
+        if let Some(v) = self.chart_max_points {
+            config.chart_max_points = v;
+        }
+        if let Some(v) = self.app_model {
+            config.app_model = v;
+        }
+        if let Some(v) = self.ack_compression {
+            config.ack_compression = Some(v);
+        }
+        if let Some(v) = self.middlebox {
+            config.middlebox = Some(v);
+        }
+        if let Some(v) = self.policer {
+            config.policer = Some(v);
+        }
+        if let Some(v) = self.bottleneck {
+            config.bottleneck = Some(v);
+        }
+        if let Some(v) = self.time_resolution {
+            config.time_resolution = v;
+        }
+        if let Some(v) = self.tie_break {
+            config.tie_break = v;
+        }
+        if let Some(v) = self.call_log {
+            config.call_log = v;
+        }
+        if let Some(v) = self.channel_randomness {
+            config.channel_randomness = v;
+        }
+        if let Some(v) = self.mss {
+            config.mss = Some(v);
+        }
     }
 }
 
 #[derive(Deserialize, Debug, Clone)]
-#[serde(tag = "type", rename_all = "snake_case")]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
 pub enum TestAction {
     /// Application sends data at a specific time
     AppSend { time: u64, data: String },
+    /// Application sends data as soon as `condition` holds (checked after every event)
+    AppSendWhen {
+        condition: AppSendCondition,
+        data: String,
+    },
+    /// Constant-bit-rate media: `frames` app sends of `frame_size` bytes every
+    /// `interval_ms` from `start`, each of which must be delivered within `deadline_ms`
+    /// of being sent. Late and lost frames are counted in the report's `media` section.
+    MediaStream {
+        #[serde(default)]
+        start: u64,
+        frames: u32,
+        interval_ms: u64,
+        frame_size: usize,
+        deadline_ms: u64,
+    },
     /// Deterministically drop the first packet sent by Sender with given seq number
     DropNextFromSenderSeq { seq: u32 },
     /// Deterministically corrupt the first packet sent by Sender with given seq number
     CorruptNextFromSenderSeq { seq: u32 },
+    /// Deterministically cut the payload of the first packet sent by Sender with given seq
+    /// number to its first `len` bytes (default: half of them), header unchanged
+    TruncateNextFromSenderSeq {
+        seq: u32,
+        #[serde(default)]
+        len: Option<usize>,
+    },
+    /// Deterministically deliver, 1 ms after the first data packet sent by Sender with given
+    /// seq number, a copy with its first payload byte inverted and the original checksum
+    TamperDuplicateNextFromSenderSeq { seq: u32 },
     /// Deterministically drop the first ACK sent by Receiver with given ack number
     DropNextFromReceiverAck { ack: u32 },
+    /// Deterministically drop `count` consecutive transmissions of `seq` by Sender, starting
+    /// with the `occurrence`th (1 is the first), e.g. `occurrence = 2, count = 2` drops the
+    /// first two retransmissions to exercise timeout backoff.
+    DropFromSenderSeq {
+        seq: u32,
+        #[serde(default = "one")]
+        occurrence: u32,
+        #[serde(default = "one")]
+        count: u32,
+    },
+    /// Like `drop_from_sender_seq`, for the ACKs Receiver sends with `ack`.
+    DropFromReceiverAck {
+        ack: u32,
+        #[serde(default = "one")]
+        occurrence: u32,
+        #[serde(default = "one")]
+        count: u32,
+    },
+    /// Deterministically drop the first packet `from` sends whose payload matches `payload`,
+    /// for protocols whose sequence numbers the seq-based actions cannot predict.
+    DropNextWithPayload {
+        #[serde(default = "sender")]
+        from: NodeId,
+        payload: PayloadMatch,
+    },
+    /// Hold the first packet `from` sends whose payload matches `payload` for an extra
+    /// `delay_ms` in the channel, e.g. to make it arrive after later segments.
+    DelayNextWithPayload {
+        #[serde(default = "sender")]
+        from: NodeId,
+        payload: PayloadMatch,
+        delay_ms: u64,
+    },
+    /// Re-create `node`'s protocol at `time`, losing all of its state (e.g. a receiver that
+    /// forgets the expected sequence number). Its pending timers are dropped.
+    RestartNode { node: NodeId, time: u64 },
+    /// Close both applications at `time`: each protocol gets `on_close`, and the run fails
+    /// unless everything the sender's application wrote before then is delivered by the end.
+    Shutdown { time: u64 },
+    /// Close only `node`'s application at `time` (TCP's half-close): that protocol gets
+    /// `on_close` and should send its FIN, while the peer's application keeps writing, e.g.
+    /// a client that has sent its whole request and still reads the reply. Pair with
+    /// `app_model = "echo"` and `delivers_after_half_close`.
+    HalfClose { time: u64, node: NodeId },
+    /// From `time` on, packets take `min`..=`max` ms to cross the link in either direction,
+    /// replacing `min_latency`/`max_latency` from `[config]`. Packets already in flight keep
+    /// their latency. Pair with `rto_adapts` to grade how quickly an RTO estimator follows.
+    SetLatency { time: u64, min: u64, max: u64 },
+    /// Show `text` (e.g. "watch: this ACK will be dropped") once the run reaches `time`.
+    /// The TUI and GUI pause on it, turning the scenario into a guided walkthrough.
+    Annotate { time: u64, text: String },
+    /// Hand `to` a crafted packet at `time`, bypassing the channel (no loss, corruption or
+    /// latency): a stale ACK, a spoofed RST, an overlapping retransmission. Header fields
+    /// default to 0. `flags` is a list such as `["SYN", "ACK"]` or a number, and `payload`
+    /// is hex, e.g. `"68 69"`.
+    InjectPacket {
+        time: u64,
+        to: NodeId,
+        #[serde(default)]
+        seq: u32,
+        #[serde(default)]
+        ack: u32,
+        #[serde(default, deserialize_with = "header_flags")]
+        flags: u8,
+        #[serde(default)]
+        window: u16,
+        #[serde(default)]
+        checksum: u16,
+        #[serde(default)]
+        src_port: u16,
+        #[serde(default)]
+        dst_port: u16,
+        #[serde(default)]
+        urgent_ptr: u16,
+        #[serde(default, deserialize_with = "hex_bytes")]
+        payload: Vec<u8>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FlagsValue {
+    Bits(u8),
+    Names(Vec<String>),
+}
+
+fn header_flags<'de, D>(deserializer: D) -> Result<u8, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match FlagsValue::deserialize(deserializer)? {
+        FlagsValue::Bits(bits) => Ok(bits),
+        FlagsValue::Names(names) => names.iter().try_fold(0, |bits, name| {
+            flags::from_name(name)
+                .map(|flag| bits | flag)
+                .ok_or_else(|| {
+                    serde::de::Error::custom(format!(
+                        "unknown TCP flag {name:?} (expected FIN, SYN, RST, PSH, ACK or URG)"
+                    ))
+                })
+        }),
+    }
+}
+
+fn optional_header_flags<'de, D>(deserializer: D) -> Result<Option<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    header_flags(deserializer).map(Some)
+}
+
+fn optional_hex_bytes<'de, D>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    hex_bytes(deserializer).map(Some)
+}
+
+/// Hex digits, optionally separated by whitespace, as bytes.
+fn hex_bytes<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let text = String::deserialize(deserializer)?;
+    let digits: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err(serde::de::Error::custom(format!(
+            "hex payload {text:?} has an odd number of digits"
+        )));
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let byte: String = pair.iter().collect();
+            u8::from_str_radix(&byte, 16).map_err(|_| {
+                serde::de::Error::custom(format!("invalid hex byte {byte:?} in payload"))
+            })
+        })
+        .collect()
+}
+
+/// Engine-evaluated trigger for `TestAction::AppSendWhen`,
+/// written inline, e.g. `condition = { type = "delivered", count = 1 }`.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum AppSendCondition {
+    /// At least `count` payloads have been delivered to the application
+    Delivered { count: usize },
+    /// The sender has retransmitted at least `count` data packets (default 1): sent the
+    /// same seq with the same payload again, not counting zero-window probes
+    Retransmitted {
+        #[serde(default = "one")]
+        count: u32,
+    },
+}
+
+fn one() -> u32 {
+    1
+}
+
+fn sender() -> NodeId {
+    NodeId::Sender
+}
+
+/// Which payloads a payload-matched fault applies to, written inline, e.g.
+/// `payload = { prefix = "msg-3" }`. Every given criterion must hold. Payloads are matched
+/// as the protocol sent them, so with `watermark` only `hash` of the tagged bytes works.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "PayloadMatchSpec")]
+pub struct PayloadMatch {
+    /// Leading bytes, from `prefix` (text) or `prefix_hex` (e.g. `"de ad"`).
+    pub prefix: Option<Vec<u8>>,
+    /// `payload_hash` of the whole payload, written as 16 hex digits.
+    pub hash: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PayloadMatchSpec {
+    prefix: Option<String>,
+    #[serde(default, deserialize_with = "optional_hex_bytes")]
+    prefix_hex: Option<Vec<u8>>,
+    hash: Option<String>,
+}
+
+impl TryFrom<PayloadMatchSpec> for PayloadMatch {
+    type Error = String;
+
+    fn try_from(spec: PayloadMatchSpec) -> Result<Self, Self::Error> {
+        let prefix = match (spec.prefix, spec.prefix_hex) {
+            (Some(_), Some(_)) => return Err("give either prefix or prefix_hex, not both".into()),
+            (text, hex) => text.map(String::into_bytes).or(hex),
+        };
+        let hash = spec
+            .hash
+            .map(|hash| {
+                u64::from_str_radix(&hash, 16)
+                    .map_err(|_| format!("payload hash {hash:?} is not 16 hex digits"))
+            })
+            .transpose()?;
+        if prefix.is_none() && hash.is_none() {
+            return Err("payload match needs prefix, prefix_hex or hash".into());
+        }
+        Ok(PayloadMatch { prefix, hash })
+    }
+}
+
+impl PayloadMatch {
+    pub fn matches(&self, payload: &[u8]) -> bool {
+        self.prefix
+            .as_ref()
+            .is_none_or(|prefix| payload.starts_with(prefix))
+            && self.hash.is_none_or(|hash| payload_hash(payload) == hash)
+    }
+}
+
+/// The 64-bit FNV-1a hash of `payload`, the `hash` a `PayloadMatch` compares against.
+/// Stable across runs and platforms, unlike `std`'s hasher.
+pub fn payload_hash(payload: &[u8]) -> u64 {
+    payload.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// A range a metric is expected to stay in: at least `min` and at most `max`, from
+/// `after_ms` on (e.g. past slow start). At least one bound is given.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "MetricBandSpec")]
+pub struct MetricBand {
+    pub metric: String,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub after_ms: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MetricBandSpec {
+    metric: String,
+    min: Option<f64>,
+    max: Option<f64>,
+    #[serde(default)]
+    after_ms: u64,
+}
+
+impl TryFrom<MetricBandSpec> for MetricBand {
+    type Error = String;
+
+    fn try_from(spec: MetricBandSpec) -> Result<Self, Self::Error> {
+        match (spec.min, spec.max) {
+            (None, None) => {
+                return Err(format!(
+                    "the band of {} needs min, max or both",
+                    spec.metric
+                ));
+            }
+            (Some(min), Some(max)) if min > max => {
+                return Err(format!(
+                    "the band of {} has min {min} above max {max}",
+                    spec.metric
+                ));
+            }
+            _ => {}
+        }
+        Ok(MetricBand {
+            metric: spec.metric,
+            min: spec.min,
+            max: spec.max,
+            after_ms: spec.after_ms,
+        })
+    }
+}
+
+impl MetricBand {
+    pub fn contains(&self, value: f64) -> bool {
+        self.min.is_none_or(|min| value >= min) && self.max.is_none_or(|max| value <= max)
+    }
+}
+
+impl std::fmt::Display for MetricBand {
+    /// E.g. `cwnd <= 64` or `10 <= rto <= 3000`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(min) = self.min {
+            write!(f, "{min} <= ")?;
+        }
+        write!(f, "{}", self.metric)?;
+        if let Some(max) = self.max {
+            write!(f, " <= {max}")?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
-#[serde(tag = "type", rename_all = "snake_case")]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
 pub enum TestAssertion {
     /// Assert that specific data was delivered to the application layer
     DataDelivered { data: String },
@@ -65,4 +543,276 @@ pub enum TestAssertion {
     SenderWindowDrop { from_at_least: u16, to_at_most: u16 },
     /// Assert that simulation finishes within time
     MaxDuration { ms: u64 },
+    /// Assert that the receiver never acknowledges data it did not get: with packet
+    /// numbers, every ACK names a packet that had arrived or the one after it; with byte
+    /// numbers, no ACK passes the end of the bytes that had arrived without a gap.
+    NoOptimisticAcks {
+        #[serde(default)]
+        unit: SeqUnit,
+    },
+    /// Assert that `node` logged at least one message matching the regex `pattern`,
+    /// e.g. that the sender logs a timeout before retransmitting.
+    LogContains { node: NodeId, pattern: String },
+    /// Assert that the sender never had more than `n` data segments unacknowledged at once
+    /// (default: `window_size` from `[config]`), as counted by the engine from seqs and ACKs.
+    MaxInFlight { n: Option<u32> },
+    /// Assert that no sender packet waited more than `ms` in the bottleneck queue, e.g. that
+    /// a delay-based congestion control keeps the buffer from bloating. Needs `bottleneck`
+    /// under `[config]`.
+    MaxQueueDelay { ms: u64 },
+    /// Assert that the sender sends no data while the last window the receiver advertised
+    /// to it is 0, apart from 1-byte window probes. Use with `receive_buffer` under `[config]`.
+    RespectsZeroWindow,
+    /// Assert that once a zero window reopens, the sender sends data within `within_ms`
+    /// (unless all application data had already been delivered).
+    ResumesAfterWindowUpdate { within_ms: u64 },
+    /// Assert that while the advertised window is 0 and data is pending, the sender probes it
+    /// at least every `max_interval_ms` until it reopens (or the scenario's `max_duration`).
+    ZeroWindowProbes { max_interval_ms: u64 },
+    /// Assert that the three-way handshake completes (the receiver gets the final ACK)
+    /// within `within_ms` of the sender's first SYN.
+    HandshakeCompleted { within_ms: u64 },
+    /// Assert that the sender retransmitted its SYN at most `n` times.
+    SynRetransmittedAtMost { n: u32 },
+    /// Assert that the sender sends no data before a SYN-ACK has reached it.
+    NoDataBeforeEstablished,
+    /// Assert that no data segment is larger than the MSS in effect for its sender: its own
+    /// `mss` under `[config]`, or the smaller of the two once the peer's SYN has reached it.
+    RespectsMss,
+    /// Assert that both sides sent a FIN and each FIN was acknowledged by the peer.
+    GracefulClose,
+    /// Assert that neither side sends new data (seq at or past its FIN) after its FIN.
+    NoDataAfterFin,
+    /// Assert that after every `half_close`, the direction still open keeps delivering: at
+    /// least `min_bytes` reach the closed side's application after its close, and everything
+    /// the peer's application wrote after it arrives.
+    DeliversAfterHalfClose { min_bytes: u64 },
+    /// Assert that no timer was still armed when the last packet left the network,
+    /// i.e. every retransmission timer was cancelled once its data was acknowledged.
+    NoPendingTimersAtEnd,
+    /// Assert that a node has no timer armed `grace_ms` (default 0) after its FIN was
+    /// acknowledged, so RTO and delayed-ACK timers do not leak past teardown. Checks every
+    /// node whose FIN was acknowledged; fails if neither's was.
+    TimersClearedAfterFin { grace_ms: Option<u64> },
+    /// Assert that every app send the sender's protocol was given was either delivered in
+    /// full or turned down with `refuse_app_data`, and that none was still held by
+    /// `app_pause` when the run ended, so a sender that drops data it is too busy for fails.
+    NoSilentAppDataLoss,
+    /// Assert that every timer either fired or was cancelled, so none is left armed when
+    /// the run ends. Checked on timeouts too, where it names the timers keeping the run alive.
+    TimersBalanced,
+    /// Assert that no callback sent more than `max_per_callback` packets at once
+    /// (default: `send_storm_threshold` from `[config]`).
+    NoSendStorms { max_per_callback: Option<usize> },
+    /// Assert that the sender's consecutive data segments leave at least `us` microseconds
+    /// apart, for labs that require paced transmission. The clock ticks in whole ms unless
+    /// `time_resolution = "us"`.
+    MinInterSendGap { us: u64 },
+    /// Assert that the sender never sends more than `packets` data segments back to back,
+    /// counting each segment less than `gap_us` after the one before it as part of the same
+    /// burst (default: only segments sent at the same instant).
+    MaxBurstSize { packets: usize, gap_us: Option<u64> },
+    /// Assert that the `percentile`th (1-100) `app_send` to `deliver_data` latency is at
+    /// most `max_ms`.
+    DeliveryLatency { percentile: u8, max_ms: u64 },
+    /// Assert that the `percentile`th (1-100) round-trip time, from the sender's `app_send`
+    /// to the echo's `deliver_data` at the sender, is at most `max_ms`. Needs
+    /// `app_model = "echo"` under `[config]`.
+    RoundTripLatency { percentile: u8, max_ms: u64 },
+    /// Assert that every `rto` metric value recorded from `after_ms` on (including the
+    /// value in effect at `after_ms`) lies within a factor of `factor_of_rtt` of the link's
+    /// true RTT, i.e. between `rtt / factor_of_rtt` and `rtt * factor_of_rtt`. The true RTT
+    /// is the mean channel latency of each direction over the same period, added up.
+    RtoWithin { factor_of_rtt: f64, after_ms: u64 },
+    /// Assert that after every `set_latency` action, one of the first `within_samples`
+    /// `rto` metric values recorded after the change lies within a factor of
+    /// `factor_of_rtt` of the new true RTT (measured as for `rto_within`, up to the next
+    /// change).
+    RtoAdapts {
+        within_samples: usize,
+        factor_of_rtt: f64,
+    },
+    /// Assert that pure ACKs put less than `ratio` times as many bytes on the wire as the
+    /// data segments did, both directions together, e.g. to grade delayed ACKs or
+    /// piggybacking.
+    AckOverheadBelow { ratio: f64 },
+    /// Assert that at least `min_fraction` (0-1) of the ACKs sent in both directions rode
+    /// on data segments (payload with the ACK flag) instead of going out as pure ACKs.
+    /// Meant for bidirectional runs such as `app_model = "echo"`.
+    PiggybackedAcks { min_fraction: f64 },
+    /// Assert that at least `min_fraction` (0-1) of the sender's data segments, retransmissions
+    /// included, were sent from `on_packet` as an ACK arrived rather than from a timer or
+    /// `on_app_data`, for congestion labs that require ACK-clocked transmission.
+    AckClocked { min_fraction: f64 },
+    /// Assert that at least `min_percent` of the `media_stream` frames were delivered
+    /// by their deadlines.
+    FramesOnTime { min_percent: f64 },
+    /// Assert that `node` entered `states` in this order through `record_state`. Other
+    /// states may come in between unless `exact`, which requires exactly this sequence.
+    StateSequence {
+        node: NodeId,
+        states: Vec<String>,
+        #[serde(default)]
+        exact: bool,
+    },
+    /// Assert that `node` sent a packet with the given `seq`, `ack` and `flags` (all listed
+    /// flags set; omitted fields match anything). With `after_receiving`, the packet must be
+    /// sent after `node` first received a packet with those flags, and `within_ms` of it;
+    /// otherwise `within_ms` counts from the start of the run.
+    PacketSent {
+        node: NodeId,
+        seq: Option<u32>,
+        ack: Option<u32>,
+        #[serde(default, deserialize_with = "optional_header_flags")]
+        flags: Option<u8>,
+        #[serde(default, deserialize_with = "optional_header_flags")]
+        after_receiving: Option<u8>,
+        within_ms: Option<u64>,
+    },
+    /// Assert that the packet log contains the `expect` steps in this order, e.g.
+    /// `["SEND seq=0", "DROP", "SEND seq=0 after>=3000"]`. Other events may come in between;
+    /// `node` applies to steps that do not name one.
+    PacketSequence {
+        node: Option<NodeId>,
+        expect: Vec<PacketStep>,
+    },
+}
+
+/// Course and lab details for the reports of a suite, from the `suite.toml` next to its
+/// scenarios, so that the reports can go out as feedback as they are.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SuiteInfo {
+    /// Course code or name, e.g. `"CS 305"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub course: Option<String>,
+    /// e.g. `"Fall 2026"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub term: Option<String>,
+    /// e.g. `"Lab 3"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lab: Option<String>,
+    /// e.g. `"Reliable data transfer"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+impl SuiteInfo {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl std::fmt::Display for SuiteInfo {
+    /// `CS 305 / Fall 2026 / Lab 3 / Reliable data transfer`, leaving out what is unset.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parts: Vec<&str> = [&self.course, &self.term, &self.lab, &self.title]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect();
+        write!(f, "{}", parts.join(" / "))
+    }
+}
+
+/// Which of a finished run's assertions are checked.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AssertionPolicy {
+    /// Every assertion, so the result lists all that failed.
+    #[default]
+    All,
+    /// In scenario order up to the first that fails; the rest are skipped.
+    FirstFailure,
+}
+
+impl FromStr for AssertionPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('-', "_").as_str() {
+            "all" => Ok(AssertionPolicy::All),
+            "first_failure" => Ok(AssertionPolicy::FirstFailure),
+            other => Err(format!(
+                "unknown assertion policy '{other}' (use all or first-failure)"
+            )),
+        }
+    }
+}
+
+/// How much sequence space one packet covers.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SeqUnit {
+    /// Sequence numbers count packets: a packet with seq `s` covers `[s, s + 1]`.
+    #[default]
+    Packet,
+    /// Sequence numbers count bytes, as in TCP: it covers `[s, s + payload length]`.
+    Byte,
+}
+
+/// One end of the simulated link; written `"sender"` or `"receiver"` in scenarios.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeId {
+    #[serde(alias = "sender")]
+    Sender,
+    #[serde(alias = "receiver")]
+    Receiver,
+}
+
+impl NodeId {
+    pub fn peer(&self) -> Self {
+        match self {
+            NodeId::Sender => NodeId::Receiver,
+            NodeId::Receiver => NodeId::Sender,
+        }
+    }
+}
+
+/// Per-node values handed to the protocols through `SystemContext::init_params`.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct InitParams {
+    #[serde(default, deserialize_with = "param_map")]
+    pub sender: BTreeMap<String, String>,
+    #[serde(default, deserialize_with = "param_map")]
+    pub receiver: BTreeMap<String, String>,
+}
+
+impl InitParams {
+    pub fn for_node(&self, node: NodeId) -> &BTreeMap<String, String> {
+        match node {
+            NodeId::Sender => &self.sender,
+            NodeId::Receiver => &self.receiver,
+        }
+    }
+}
+
+/// Accepts any TOML scalar so `window = 4` needs no quotes.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ParamValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+fn param_map<'de, D>(deserializer: D) -> Result<BTreeMap<String, String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let raw = BTreeMap::<String, ParamValue>::deserialize(deserializer)?;
+    Ok(raw
+        .into_iter()
+        .map(|(key, value)| {
+            let value = match value {
+                ParamValue::Bool(b) => b.to_string(),
+                ParamValue::Int(i) => i.to_string(),
+                ParamValue::Float(f) => f.to_string(),
+                ParamValue::Str(s) => s,
+            };
+            (key, value)
+        })
+        .collect())
 }
@@ -0,0 +1,154 @@
+//! Steps of a `packet_sequence` assertion, written as short text such as `"SEND seq=0"`,
+//! `"DROP"` or `"SEND seq=0 after>=3000"` and parsed when the scenario loads.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::packet::flags;
+use crate::scenario::NodeId;
+
+/// What happened to a packet in the engine's packet log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PacketEventKind {
+    /// `node` sent it (whatever the channel then did to it).
+    Sent,
+    /// The channel or a scenario fault lost the packet `node` sent.
+    Dropped,
+    /// The channel or a scenario fault corrupted the packet `node` sent.
+    Corrupted,
+    /// It reached `node`.
+    Arrived,
+    /// `Simulator::inject_packet` handed it to `node`.
+    Injected,
+}
+
+impl FromStr for PacketEventKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "SEND" | "SENT" => Ok(PacketEventKind::Sent),
+            "DROP" | "DROPPED" | "LOST" => Ok(PacketEventKind::Dropped),
+            "CORRUPT" | "CORRUPTED" => Ok(PacketEventKind::Corrupted),
+            "ARRIVE" | "ARRIVED" | "RECV" => Ok(PacketEventKind::Arrived),
+            "INJECT" | "INJECTED" => Ok(PacketEventKind::Injected),
+            _ => Err(format!(
+                "unknown packet event {s:?} (expected SEND, DROP, CORRUPT, ARRIVE or INJECT)"
+            )),
+        }
+    }
+}
+
+/// One expected packet event. Omitted fields match anything; `flags` requires every listed
+/// flag. The gap bounds count from the event matched by the previous step (from the start of
+/// the run for the first step).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct PacketStep {
+    pub kind: PacketEventKind,
+    pub node: Option<NodeId>,
+    pub seq: Option<u32>,
+    pub ack: Option<u32>,
+    pub flags: Option<u8>,
+    pub len: Option<usize>,
+    pub min_gap_ms: Option<u64>,
+    pub max_gap_ms: Option<u64>,
+    text: String,
+}
+
+impl PacketStep {
+    pub fn matches(&self, node: NodeId, seq: u32, ack: u32, bits: u8, len: usize) -> bool {
+        self.node.is_none_or(|n| n == node)
+            && self.seq.is_none_or(|s| s == seq)
+            && self.ack.is_none_or(|a| a == ack)
+            && self.flags.is_none_or(|f| bits & f == f)
+            && self.len.is_none_or(|l| l == len)
+    }
+
+    /// Whether `gap_ms` after the previous step is within this step's bounds.
+    pub fn gap_allows(&self, gap_ms: u64) -> bool {
+        self.min_gap_ms.is_none_or(|min| gap_ms >= min)
+            && self.max_gap_ms.is_none_or(|max| gap_ms <= max)
+    }
+}
+
+impl fmt::Display for PacketStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl TryFrom<String> for PacketStep {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        text.parse()
+    }
+}
+
+impl FromStr for PacketStep {
+    type Err = String;
+
+    /// `KIND [sender|receiver] [key=value]... [after>=N] [after<=N]`, where the keys are
+    /// `node`, `seq`, `ack`, `flags` (`SYN|ACK`) and `len`, and `N` is in ms.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut words = text.split_whitespace();
+        let kind = words
+            .next()
+            .ok_or_else(|| "empty packet sequence step".to_string())?
+            .parse()?;
+        let mut step = PacketStep {
+            kind,
+            node: None,
+            seq: None,
+            ack: None,
+            flags: None,
+            len: None,
+            min_gap_ms: None,
+            max_gap_ms: None,
+            text: text.trim().to_string(),
+        };
+        let invalid = |word: &str| format!("invalid {word:?} in packet sequence step {text:?}");
+        for word in words {
+            let word = word.replace('≥', ">=").replace('≤', "<=");
+            if let Some(ms) = word.strip_prefix("after>=") {
+                step.min_gap_ms = Some(parse_ms(ms).ok_or_else(|| invalid(&word))?);
+                continue;
+            }
+            if let Some(ms) = word.strip_prefix("after<=") {
+                step.max_gap_ms = Some(parse_ms(ms).ok_or_else(|| invalid(&word))?);
+                continue;
+            }
+            let (key, value) = word.split_once('=').unwrap_or(("node", &word));
+            match key {
+                "node" => step.node = Some(parse_node(value).ok_or_else(|| invalid(&word))?),
+                "seq" => step.seq = Some(value.parse().map_err(|_| invalid(&word))?),
+                "ack" => step.ack = Some(value.parse().map_err(|_| invalid(&word))?),
+                "len" => step.len = Some(value.parse().map_err(|_| invalid(&word))?),
+                "flags" => {
+                    let bits = value.split('|').try_fold(0, |bits, name| {
+                        flags::from_name(name).map(|flag| bits | flag)
+                    });
+                    step.flags = Some(bits.ok_or_else(|| invalid(&word))?);
+                }
+                _ => return Err(invalid(&word)),
+            }
+        }
+        Ok(step)
+    }
+}
+
+fn parse_ms(value: &str) -> Option<u64> {
+    value.strip_suffix("ms").unwrap_or(value).parse().ok()
+}
+
+fn parse_node(value: &str) -> Option<NodeId> {
+    match value.to_ascii_lowercase().as_str() {
+        "sender" => Some(NodeId::Sender),
+        "receiver" => Some(NodeId::Receiver),
+        _ => None,
+    }
+}
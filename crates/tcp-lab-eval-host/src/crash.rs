@@ -0,0 +1,206 @@
+//! Crash reports for C++ submissions. A segfault or abort in a student's shared library
+//! takes the whole grader down, which the queue daemon could only report as "exited
+//! abnormally". When eval-host loads C++ libraries it installs a handler for the fatal
+//! signals that records where the crash happened, symbolized as far as the library's
+//! symbols allow, prints it to stderr, sends it to the daemon over `--report-fd` to attach
+//! to the bundle's result and, with `--crash-report FILE`, also writes it there as JSON.
+//! The signal is then raised again, so the process still dies of it.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tcp_lab_simulator::i18n::Msg;
+
+/// Frames kept in a report, innermost first.
+#[cfg_attr(not(unix), allow(dead_code))]
+const MAX_FRAMES: usize = 64;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashReport {
+    /// E.g. `SIGSEGV`.
+    pub signal: String,
+    /// What went wrong, e.g. a null pointer dereference.
+    pub fault: String,
+    /// The innermost frame in a submitted library, e.g. `GbnSender::onTimer(int)`; `None`
+    /// if the crash was elsewhere or the library has no symbols.
+    pub location: Option<String>,
+    /// The stack, innermost first, as `function (object)` or `address (object)`.
+    pub frames: Vec<String>,
+}
+
+impl CrashReport {
+    /// One line for the grading result, e.g. "null pointer dereference in GbnSender::onTimer(int)".
+    pub fn summary(&self) -> String {
+        match &self.location {
+            Some(location) => Msg::CrashIn.fill(&[&self.fault, location]),
+            None => format!("{} ({})", self.fault, self.signal),
+        }
+    }
+}
+
+/// Report fatal signals from here on, looking for frames in `libraries` (the submitted C++
+/// libraries) and sending the report to the daemon and `report` as well as stderr.
+#[cfg(unix)]
+pub fn install(report: Option<PathBuf>, libraries: &[PathBuf]) {
+    imp::install(report, libraries);
+}
+
+/// Signal handlers are Unix-only; elsewhere a crash is still reported as an abnormal exit.
+#[cfg(not(unix))]
+pub fn install(_report: Option<PathBuf>, _libraries: &[PathBuf]) {}
+
+#[cfg(unix)]
+mod imp {
+    use std::ffi::{CStr, c_int, c_void};
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::OnceLock;
+    use std::{mem, ptr};
+
+    use tcp_lab_simulator::i18n::Msg;
+
+    use super::{CrashReport, MAX_FRAMES};
+    use crate::pipe::ChildReport;
+
+    const SIGNALS: [(c_int, &str); 5] = [
+        (libc::SIGSEGV, "SIGSEGV"),
+        (libc::SIGBUS, "SIGBUS"),
+        (libc::SIGABRT, "SIGABRT"),
+        (libc::SIGFPE, "SIGFPE"),
+        (libc::SIGILL, "SIGILL"),
+    ];
+
+    /// Symbolizing needs far more stack than `SIGSTKSZ`.
+    const ALT_STACK_BYTES: usize = 1 << 20;
+
+    /// Faulting addresses below this count as null pointer dereferences, e.g. a member
+    /// read through a null object pointer.
+    const NULL_PAGE: usize = 4096;
+
+    struct State {
+        report: Option<PathBuf>,
+        /// File names of the submitted libraries; reloads load copies whose names end in them.
+        libraries: Vec<String>,
+    }
+
+    static STATE: OnceLock<State> = OnceLock::new();
+
+    pub fn install(report: Option<PathBuf>, libraries: &[PathBuf]) {
+        let libraries = libraries
+            .iter()
+            .filter_map(|path| path.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect();
+        if STATE.set(State { report, libraries }).is_err() {
+            return;
+        }
+        // SAFETY: the stack is leaked, so it outlives the handler; the sigaction structs
+        // are fully initialized before use.
+        unsafe {
+            let stack = Box::leak(vec![0u8; ALT_STACK_BYTES].into_boxed_slice());
+            let stack = libc::stack_t {
+                ss_sp: stack.as_mut_ptr().cast(),
+                ss_flags: 0,
+                ss_size: ALT_STACK_BYTES,
+            };
+            // On its own stack, so a stack overflow in student code is reported too.
+            libc::sigaltstack(&stack, ptr::null_mut());
+            for (signal, _) in SIGNALS {
+                let mut action: libc::sigaction = mem::zeroed();
+                action.sa_sigaction = handle as *const () as usize;
+                action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK | libc::SA_RESETHAND;
+                libc::sigemptyset(&mut action.sa_mask);
+                libc::sigaction(signal, &action, ptr::null_mut());
+            }
+        }
+    }
+
+    /// Not async-signal-safe: symbolizing allocates and reads debug info. The process is
+    /// about to die anyway, and a report that is usually there beats none.
+    extern "C" fn handle(signal: c_int, info: *mut libc::siginfo_t, _context: *mut c_void) {
+        // SAFETY: the kernel passes a valid siginfo with SA_SIGINFO.
+        let address = unsafe { (*info).si_addr() } as usize;
+        if let Some(state) = STATE.get() {
+            let report = capture(state, signal, address);
+            eprintln!("{}", Msg::CrashReported.fill(&[&report.summary()]));
+            for frame in &report.frames {
+                eprintln!("    {frame}");
+            }
+            crate::pipe::send(&ChildReport::Crash(report.clone()));
+            if let Some(path) = &state.report
+                && let Ok(data) = serde_json::to_vec_pretty(&report)
+            {
+                let _ = fs::write(path, data);
+            }
+        }
+        // SA_RESETHAND restored the default action, which ends the process.
+        // SAFETY: raising a signal has no memory-safety preconditions.
+        unsafe {
+            libc::raise(signal);
+        }
+    }
+
+    fn capture(state: &State, signal: c_int, address: usize) -> CrashReport {
+        let name = SIGNALS
+            .iter()
+            .find(|(number, _)| *number == signal)
+            .map_or("signal", |(_, name)| name);
+        let fault = match signal {
+            libc::SIGSEGV if address < NULL_PAGE => Msg::CrashNullDeref.fill(&[]),
+            libc::SIGSEGV | libc::SIGBUS => Msg::CrashBadAccess.fill(&[&format!("{address:#x}")]),
+            libc::SIGABRT => Msg::CrashAbort.fill(&[]),
+            libc::SIGFPE => Msg::CrashArithmetic.fill(&[]),
+            _ => Msg::CrashIllegalInstruction.fill(&[]),
+        };
+        let mut frames = Vec::new();
+        let mut location = None;
+        backtrace::trace(|frame| {
+            let mut function = None;
+            backtrace::resolve_frame(frame, |symbol| {
+                if function.is_none() {
+                    function = symbol.name().map(|name| format!("{name:#}"));
+                }
+            });
+            // The handler's own frames, up to the signal trampoline, say nothing.
+            if function.as_deref().is_some_and(|function| {
+                function.starts_with("backtrace::") || function.contains("crash::imp::")
+            }) {
+                return true;
+            }
+            let object = object_of(frame.ip());
+            let submitted = object
+                .as_deref()
+                .is_some_and(|object| state.libraries.iter().any(|lib| object.ends_with(lib)));
+            if submitted && location.is_none() {
+                location = function.clone();
+            }
+            let object = object.unwrap_or_else(|| "?".to_string());
+            frames.push(match function {
+                Some(function) => format!("{function} ({object})"),
+                None => format!("{:?} ({object})", frame.ip()),
+            });
+            frames.len() < MAX_FRAMES
+        });
+        CrashReport {
+            signal: name.to_string(),
+            fault,
+            location,
+            frames,
+        }
+    }
+
+    /// File name of the executable or shared library containing `ip`.
+    fn object_of(ip: *mut c_void) -> Option<String> {
+        // SAFETY: dladdr only writes to `info`, and `dli_fname` is a C string owned by the
+        // dynamic loader while the object stays loaded.
+        unsafe {
+            let mut info: libc::Dl_info = mem::zeroed();
+            if libc::dladdr(ip, &mut info) == 0 || info.dli_fname.is_null() {
+                return None;
+            }
+            let path = CStr::from_ptr(info.dli_fname).to_string_lossy();
+            let name = path.rsplit('/').next().unwrap_or(&path);
+            Some(name.to_string())
+        }
+    }
+}
@@ -0,0 +1,785 @@
+//! `--queue DIR`: a long-running grader for a course submission portal. The portal drops
+//! each submission as a bundle directory into `DIR/incoming`; the daemon moves it to
+//! `DIR/processing`, grades it against `--scenario` in a child eval-host, writes
+//! `DIR/results/<bundle>.json` with the grader's output next to it as `<bundle>.log`, and
+//! moves the bundle on to `DIR/done`.
+//!
+//! A bundle is picked up once it holds `submission.toml`, so the portal writes that file
+//! last (or moves the finished bundle into `incoming` in one rename). The child runs in the
+//! bundle directory with a wall-clock limit and, on Unix, an address-space limit, so a
+//! submission that hangs or crashes its process only fails its own grade.
+//!
+//! The child sends its outcomes back over a pipe the daemon owns (see [`pipe`]), and a
+//! bundle passes only if the child exits with `Pass` having reported, exactly once, a
+//! passing outcome for every selected scenario. That stops a submission from passing by
+//! exiting early, and a file in `results/` counts for nothing. The outcomes are still
+//! only as trustworthy as the child: the submission runs inside it, as the daemon's user,
+//! and can write to the pipe itself or to anything that user can, including the queue.
+//! Grade with `--strict`, and run the daemon as a user (or in a container) that can write
+//! nothing else the grades depend on. Every path in a manifest must stay inside its
+//! bundle, which is what the signed `artifacts` hash covers.
+//!
+//! With `--fingerprints`, each result also carries a behavioral fingerprint per scenario,
+//! and after each round of bundles the daemon compares every pair of results so far into
+//! `DIR/similarity.json` for academic-integrity review. That file is advice for a human
+//! reviewer, not part of any grade, so it is not signed.
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus as ProcessStatus, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tcp_lab_simulator::i18n::Msg;
+use tcp_lab_simulator::{
+    ExitStatus, ScenarioOutcome, SimilarityReport, SuiteInfo, TagFilter, scenario_runner,
+};
+use tracing::{info, warn};
+
+use crate::Args;
+use crate::crash::CrashReport;
+use crate::pipe::{self, ChildReport};
+use crate::signing::{self, ReportSigner, Submission};
+
+/// How long to wait for a child's reports after it exits.
+const REPORT_GRACE: Duration = Duration::from_secs(1);
+
+/// The file that marks a bundle as ready and says how to load it.
+const MANIFEST: &str = "submission.toml";
+
+/// `submission.toml`: the protocols of a bundle, with paths relative to the bundle. A side
+/// the daemon gives a reference protocol with `--builtin-sender` or `--builtin-receiver`
+/// is left out.
+///
+/// ```toml
+/// [sender]
+/// python = "sender.Sender"
+/// [receiver]
+/// python = "receiver.Receiver"
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    sender: Option<Side>,
+    receiver: Option<Side>,
+    /// Jars and class directories for Java protocols (default: the bundle itself).
+    #[serde(default)]
+    classpath: Vec<PathBuf>,
+    /// Derives the scenario's seed for this student, as `--student-id` does.
+    student: Option<String>,
+}
+
+/// One node's protocol; exactly one field is set. There is no `builtin`: a submission
+/// naming a reference protocol would pass as its own work.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Side {
+    /// `module.Class`, imported from the bundle.
+    python: Option<String>,
+    /// Fully qualified class name, loaded from `classpath`.
+    java: Option<String>,
+    /// Shared library built from the C++ SDK.
+    cpp: Option<PathBuf>,
+}
+
+impl Manifest {
+    /// Whether either side sets `field`.
+    fn uses(&self, field: impl Fn(&Side) -> bool) -> bool {
+        [&self.sender, &self.receiver]
+            .into_iter()
+            .flatten()
+            .any(field)
+    }
+}
+
+/// `results/<bundle>.json`.
+#[derive(Debug, Serialize)]
+struct QueueResult {
+    /// What was graded; the signature binds the result to it.
+    submission: Submission,
+    bundle: String,
+    /// The course and lab of the suite, from its `suite.toml`.
+    #[serde(skip_serializing_if = "SuiteInfo::is_empty")]
+    suite: SuiteInfo,
+    /// The exit code a single eval-host run would give; see `ExitStatus`.
+    exit_code: u8,
+    /// Why the submission could not be graded or failed as a whole, e.g. a time-out;
+    /// failed scenarios carry their own errors.
+    error: Option<String>,
+    timed_out: bool,
+    duration_ms: u64,
+    /// Where a C++ submission crashed the grader, if it did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    crash: Option<CrashReport>,
+    scenarios: Vec<ScenarioOutcome>,
+}
+
+/// The part of a `results/<bundle>.json` that similarity reads back.
+#[derive(Debug, Deserialize)]
+struct StoredResult {
+    bundle: String,
+    #[serde(default)]
+    scenarios: Vec<ScenarioOutcome>,
+}
+
+/// Grade bundles as they arrive, until killed (or, with `--once`, the queue is empty).
+pub fn run(args: &Args, dir: &Path) -> Result<()> {
+    let suite = Suite::load(args)?;
+    let signer = args.report_signer()?;
+    let queue = Queue::open(dir)?;
+    queue.requeue_unfinished()?;
+    info!("Watching {} for submissions", queue.incoming.display());
+    loop {
+        let bundles = queue.ready()?;
+        if bundles.is_empty() {
+            if args.once {
+                return Ok(());
+            }
+            thread::sleep(Duration::from_millis(args.poll_ms));
+            continue;
+        }
+        for bundle in bundles {
+            let Some(path) = queue.claim(&bundle) else {
+                continue;
+            };
+            let result = grade(args, &suite, &queue, &bundle, &path);
+            info!(
+                "Graded {}: exit code {} in {} ms",
+                bundle, result.exit_code, result.duration_ms
+            );
+            queue.finish(&bundle, &result, signer.as_ref())?;
+        }
+        if args.fingerprints {
+            queue.write_similarity()?;
+        }
+    }
+}
+
+/// The scenarios every bundle is graded against.
+struct Suite {
+    /// `--scenario`, made absolute.
+    path: PathBuf,
+    info: SuiteInfo,
+    /// Names of the scenarios `--tags` and `--exclude-tags` select, which a passing child
+    /// must report an outcome for.
+    selected: BTreeSet<String>,
+    /// [`signing::digest`] of the scenario files.
+    digest: String,
+}
+
+impl Suite {
+    fn load(args: &Args) -> Result<Self> {
+        let Some(scenario) = &args.scenario else {
+            bail!(Msg::QueueNeedsScenario.text());
+        };
+        let path = fs::canonicalize(scenario)
+            .with_context(|| format!("Failed to find scenarios {scenario}"))?;
+        let (info, files) = if path.is_dir() {
+            (
+                scenario_runner::load_suite_info(&path)?,
+                scenario_runner::scenario_files(&path)?,
+            )
+        } else {
+            (SuiteInfo::default(), vec![path.clone()])
+        };
+        let filter = TagFilter {
+            tags: args.tags.clone(),
+            exclude_tags: args.exclude_tags.clone(),
+        };
+        let mut selected = BTreeSet::new();
+        for file in files {
+            if filter.matches(&scenario_runner::load_scenario(&file, args.strict)?) {
+                selected.insert(crate::stem(&file));
+            }
+        }
+        let digest = signing::digest(&[], &[&path])?;
+        Ok(Self {
+            path,
+            info,
+            selected,
+            digest,
+        })
+    }
+}
+
+struct Queue {
+    incoming: PathBuf,
+    processing: PathBuf,
+    results: PathBuf,
+    done: PathBuf,
+    similarity: PathBuf,
+}
+
+impl Queue {
+    fn open(dir: &Path) -> Result<Self> {
+        // Absolute, since each child runs in its bundle and writes its log here.
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        let dir = fs::canonicalize(dir)?;
+        let queue = Self {
+            incoming: dir.join("incoming"),
+            processing: dir.join("processing"),
+            results: dir.join("results"),
+            done: dir.join("done"),
+            similarity: dir.join("similarity.json"),
+        };
+        for sub in [
+            &queue.incoming,
+            &queue.processing,
+            &queue.results,
+            &queue.done,
+        ] {
+            fs::create_dir_all(sub)
+                .with_context(|| format!("Failed to create {}", sub.display()))?;
+        }
+        Ok(queue)
+    }
+
+    /// Put back bundles a daemon stopped grading halfway, so they are graded again.
+    fn requeue_unfinished(&self) -> Result<()> {
+        for bundle in entries(&self.processing)? {
+            warn!("Requeueing {}, whose grading was interrupted", bundle);
+            fs::rename(self.processing.join(&bundle), self.incoming.join(&bundle))
+                .with_context(|| format!("Failed to requeue {bundle}"))?;
+        }
+        Ok(())
+    }
+
+    /// Bundles in `incoming` with a manifest, oldest name first.
+    fn ready(&self) -> Result<Vec<String>> {
+        let mut ready: Vec<String> = entries(&self.incoming)?
+            .into_iter()
+            .filter(|bundle| self.incoming.join(bundle).join(MANIFEST).is_file())
+            .collect();
+        ready.sort();
+        Ok(ready)
+    }
+
+    /// Move `bundle` to `processing`; `None` if it is gone, e.g. taken by the portal back.
+    fn claim(&self, bundle: &str) -> Option<PathBuf> {
+        let path = self.processing.join(bundle);
+        fs::rename(self.incoming.join(bundle), &path).ok()?;
+        Some(path)
+    }
+
+    /// Write the result of `bundle` (signed with `--signing-key`), replacing any earlier one,
+    /// and move the bundle to `done`.
+    fn finish(
+        &self,
+        bundle: &str,
+        result: &QueueResult,
+        signer: Option<&ReportSigner>,
+    ) -> Result<()> {
+        let path = self.results.join(format!("{bundle}.json"));
+        let data = serde_json::to_vec_pretty(result).context("Failed to serialize result")?;
+        signing::write_report(&path, &data, signer)?;
+        let done = self.done.join(bundle);
+        if done.exists() {
+            fs::remove_dir_all(&done)
+                .with_context(|| format!("Failed to replace {}", done.display()))?;
+        }
+        fs::rename(self.processing.join(bundle), &done)
+            .with_context(|| format!("Failed to move {bundle} to {}", self.done.display()))
+    }
+
+    /// Compare the fingerprints of every result so far, written with `--fingerprints`,
+    /// into `similarity.json`.
+    fn write_similarity(&self) -> Result<()> {
+        let mut submissions = BTreeMap::new();
+        for entry in fs::read_dir(&self.results)
+            .with_context(|| format!("Failed to read {}", self.results.display()))?
+        {
+            let path = entry?.path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            let Some(result) = fs::read(&path)
+                .ok()
+                .and_then(|data| serde_json::from_slice::<StoredResult>(&data).ok())
+            else {
+                continue;
+            };
+            let fingerprints: BTreeMap<_, _> = result
+                .scenarios
+                .into_iter()
+                .filter_map(|outcome| Some((outcome.scenario, outcome.fingerprint?)))
+                .collect();
+            if !fingerprints.is_empty() {
+                submissions.insert(result.bundle, fingerprints);
+            }
+        }
+        let report = SimilarityReport::new(&submissions);
+        let data = serde_json::to_vec_pretty(&report).context("Failed to serialize similarity")?;
+        signing::write_report(&self.similarity, &data, None)?;
+        info!(
+            "{}",
+            Msg::SimilarityWritten.fill(&[&submissions.len(), &self.similarity.display()])
+        );
+        Ok(())
+    }
+}
+
+/// Names of the directories in `dir`.
+fn entries(dir: &Path) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    Ok(names)
+}
+
+/// Grade the bundle at `path` in a child eval-host. It passes only if the child exited
+/// with `Pass` and reported a passing outcome for every scenario the suite selects.
+fn grade(args: &Args, suite: &Suite, queue: &Queue, bundle: &str, path: &Path) -> QueueResult {
+    let started = Instant::now();
+    let log = queue.results.join(format!("{bundle}.log"));
+    let mut result = QueueResult {
+        submission: Submission::new(None, String::new(), suite.digest.clone()),
+        bundle: bundle.to_string(),
+        suite: suite.info.clone(),
+        exit_code: ExitStatus::Environment.code(),
+        error: None,
+        timed_out: false,
+        duration_ms: 0,
+        crash: None,
+        scenarios: Vec::new(),
+    };
+    let (mut outcomes, mut crash, mut repeated) = (None, None, false);
+    let run = read_manifest(path)
+        .and_then(|manifest| {
+            // Hashed before the child runs, since loading may write to the bundle.
+            result.submission.student = manifest.student.clone();
+            result.submission.artifacts = signing::digest(&[], &[path])?;
+            spawn(args, &suite.path, &manifest, path, &log)
+        })
+        .and_then(|(child, reports)| {
+            let status = wait(child, Duration::from_secs(args.time_limit));
+            // Anything the child started may still hold the pipe open; do not wait for it.
+            for report in reports.recv_timeout(REPORT_GRACE).unwrap_or_default() {
+                match report {
+                    ChildReport::Outcomes(reported) => {
+                        repeated |= outcomes.is_some();
+                        outcomes = Some(reported);
+                    }
+                    ChildReport::Crash(report) => crash = Some(report),
+                }
+            }
+            status
+        });
+    match run {
+        Ok(Some(status)) => match status.code().and_then(|code| u8::try_from(code).ok()) {
+            Some(code) if ExitStatus::from_code(code).is_some() => result.exit_code = code,
+            _ => {
+                result.exit_code = ExitStatus::StudentRuntime.code();
+                result.crash = crash;
+                result.error = Some(match &result.crash {
+                    Some(report) => Msg::QueueCrashedIn.fill(&[&report.summary()]),
+                    None => Msg::QueueCrashed.fill(&[&status]),
+                });
+            }
+        },
+        Ok(None) => {
+            result.exit_code = ExitStatus::Timeout.code();
+            result.timed_out = true;
+            result.error = Some(Msg::QueueTimedOut.fill(&[&args.time_limit]));
+        }
+        Err(err) => result.error = Some(format!("{err:#}")),
+    }
+    if repeated {
+        // The grader reports once; a second report was written by the submission, which
+        // shares the grader's process and so its end of the pipe.
+        result.exit_code = ExitStatus::StudentRuntime.code();
+        result.error = Some(Msg::QueueRepeatedOutcomes.text().to_string());
+    } else if result.exit_code == ExitStatus::Pass.code()
+        && let Some(error) = unproven(&suite.selected, outcomes.as_deref())
+    {
+        // E.g. a submission that exits the grader with 0 before it reports anything.
+        result.exit_code = ExitStatus::StudentRuntime.code();
+        result.error = Some(error);
+    }
+    result.scenarios = outcomes.unwrap_or_default();
+    if result.error.is_none() && result.scenarios.is_empty() && result.exit_code != 0 {
+        // Stopped before grading, e.g. a submission that does not load: the log says why.
+        result.error = fs::read_to_string(&log).ok().and_then(|log| {
+            log.lines()
+                .find_map(|line| line.strip_prefix("Error: "))
+                .map(str::to_string)
+        });
+    }
+    result.duration_ms = started.elapsed().as_millis() as u64;
+    result
+}
+
+/// Why `outcomes` do not show that every scenario in `selected` passed; `None` if they do.
+fn unproven(selected: &BTreeSet<String>, outcomes: Option<&[ScenarioOutcome]>) -> Option<String> {
+    let Some(outcomes) = outcomes else {
+        return Some(Msg::QueueNoOutcomes.text().to_string());
+    };
+    let reported: BTreeSet<String> = outcomes
+        .iter()
+        .map(|outcome| outcome.scenario.clone())
+        .collect();
+    if reported != *selected || reported.len() != outcomes.len() {
+        let list = |names: Vec<&String>| match names.is_empty() {
+            true => "-".to_string(),
+            false => names.into_iter().cloned().collect::<Vec<_>>().join(", "),
+        };
+        return Some(Msg::QueueOutcomesMismatch.fill(&[
+            &list(selected.difference(&reported).collect()),
+            &list(reported.difference(selected).collect()),
+        ]));
+    }
+    outcomes
+        .iter()
+        .find(|outcome| !outcome.passed())
+        .map(|outcome| Msg::QueueOutcomeFailed.fill(&[&outcome.scenario]))
+}
+
+/// Start a child eval-host grading the bundle at `path`, with everything it prints going
+/// to `log`. The child sends its reports over a pipe only the daemon reads; they arrive on
+/// the returned channel once the child closes it.
+fn spawn(
+    args: &Args,
+    scenario: &Path,
+    manifest: &Manifest,
+    path: &Path,
+    log: &Path,
+) -> Result<(Child, mpsc::Receiver<Vec<ChildReport>>)> {
+    let mut child_args: Vec<OsString> = vec!["--scenario".into(), scenario.into()];
+    child_args.extend(["--report-fd".into(), pipe::REPORT_FD.to_string().into()]);
+    child_args.extend(node_args(
+        manifest.sender.as_ref(),
+        args.builtin_sender.as_deref(),
+        "sender",
+        path,
+    )?);
+    child_args.extend(node_args(
+        manifest.receiver.as_ref(),
+        args.builtin_receiver.as_deref(),
+        "receiver",
+        path,
+    )?);
+    if let Some(student) = &manifest.student {
+        child_args.extend(["--student-id".into(), student.into()]);
+    }
+    if manifest.uses(|side| side.java.is_some()) {
+        let classpath = if manifest.classpath.is_empty() {
+            vec![path.to_path_buf()]
+        } else {
+            manifest
+                .classpath
+                .iter()
+                .map(|entry| inside(path, entry))
+                .collect::<Result<_>>()?
+        };
+        let classpath = std::env::join_paths(classpath).context("Invalid classpath")?;
+        child_args.extend(["--classpath".into(), classpath]);
+    }
+    if manifest.uses(|side| side.python.is_some()) {
+        child_args.extend(["--python-path".into(), path.into()]);
+    }
+    child_args.extend(args.queue_child_args());
+
+    let log =
+        fs::File::create(log).with_context(|| format!("Failed to create {}", log.display()))?;
+    let (reader, writer) = io::pipe().context("Failed to create the report pipe")?;
+    let mut command = Command::new(std::env::current_exe().context("Failed to find eval-host")?);
+    command
+        .args(child_args)
+        .current_dir(path)
+        .stdin(Stdio::null())
+        .stderr(log.try_clone()?);
+    pass_report_pipe(&mut command, writer, log.try_clone()?)?;
+    limit_memory(&mut command, args.memory_limit);
+    let child = command.spawn().context("Failed to start the grader");
+    // The child has its own copy of the write end now; with ours closed, the pipe ends
+    // when the child exits.
+    drop(command);
+    let child = child?;
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || sender.send(pipe::collect(reader, log)));
+    Ok((child, receiver))
+}
+
+/// Give the child the pipe's write end as [`pipe::REPORT_FD`], with `log` as its stdout.
+#[cfg(unix)]
+fn pass_report_pipe(command: &mut Command, writer: io::PipeWriter, log: fs::File) -> Result<()> {
+    use std::os::fd::{AsRawFd, OwnedFd};
+    use std::os::unix::process::CommandExt;
+
+    let writer = OwnedFd::from(writer);
+    let fd = writer.as_raw_fd();
+    command.stdout(log);
+    // SAFETY: dup2 and fcntl are async-signal-safe and only touch the child's descriptors.
+    // The closure owns `writer`, so the descriptor stays open until the command is dropped.
+    unsafe {
+        command.pre_exec(move || {
+            let _ = &writer;
+            let ok = if fd == pipe::REPORT_FD {
+                // dup2 onto itself would keep close-on-exec set.
+                libc::fcntl(fd, libc::F_SETFD, 0) != -1
+            } else {
+                libc::dup2(fd, pipe::REPORT_FD) != -1
+            };
+            if !ok {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    Ok(())
+}
+
+/// Without descriptor passing, the pipe is the child's stdout; whatever else it prints
+/// there is copied to the log.
+#[cfg(not(unix))]
+fn pass_report_pipe(command: &mut Command, writer: io::PipeWriter, _log: fs::File) -> Result<()> {
+    command.stdout(writer);
+    Ok(())
+}
+
+fn read_manifest(bundle: &Path) -> Result<Manifest> {
+    let manifest = fs::read_to_string(bundle.join(MANIFEST))
+        .with_context(|| format!("Failed to read {MANIFEST}"))?;
+    toml::from_str(&manifest).with_context(|| format!("Invalid {MANIFEST}"))
+}
+
+/// The eval-host flags loading one node's protocol: the submission's `side`, or the
+/// daemon's `reference` protocol, but not both.
+fn node_args(
+    side: Option<&Side>,
+    reference: Option<&str>,
+    node: &str,
+    bundle: &Path,
+) -> Result<Vec<OsString>> {
+    match (side, reference) {
+        (Some(side), None) => side_args(side, node, bundle),
+        (None, Some(name)) => Ok(vec![format!("--builtin-{node}").into(), name.into()]),
+        (Some(_), Some(_)) => bail!(
+            "[{node}] in {MANIFEST} is the reference protocol from --builtin-{node}; leave it out"
+        ),
+        (None, None) => bail!("{MANIFEST} needs a [{node}] section"),
+    }
+}
+
+/// `relative` within `bundle`, resolved; an error if it leads out of the bundle, e.g. to
+/// another submission's library, since only the bundle is hashed and graded as the
+/// student's work.
+fn inside(bundle: &Path, relative: &Path) -> Result<PathBuf> {
+    let root =
+        fs::canonicalize(bundle).with_context(|| format!("Failed to find {}", bundle.display()))?;
+    let path = fs::canonicalize(root.join(relative))
+        .with_context(|| format!("{} in {MANIFEST} does not exist", relative.display()))?;
+    if !path.starts_with(&root) {
+        bail!(
+            "{} in {MANIFEST} is outside the submission",
+            relative.display()
+        );
+    }
+    Ok(path)
+}
+
+/// The eval-host flags loading one node's protocol from `side`.
+fn side_args(side: &Side, node: &str, bundle: &Path) -> Result<Vec<OsString>> {
+    let mut set: Vec<(String, OsString)> = Vec::new();
+    if let Some(spec) = &side.python {
+        set.push((format!("--python-{node}"), spec.into()));
+    }
+    if let Some(class) = &side.java {
+        set.push((format!("--java-{node}"), class.into()));
+    }
+    if let Some(lib) = &side.cpp {
+        set.push((format!("--cpp-{node}-lib"), inside(bundle, lib)?.into()));
+    }
+    if set.len() != 1 {
+        bail!("[{node}] in {MANIFEST} must set exactly one of python, java or cpp");
+    }
+    Ok(set
+        .into_iter()
+        .flat_map(|(flag, value)| [flag.into(), value])
+        .collect())
+}
+
+/// Wait for `child` for at most `limit`; `None` if it had to be killed.
+fn wait(mut child: Child, limit: Duration) -> Result<Option<ProcessStatus>> {
+    let deadline = Instant::now() + limit;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[cfg(unix)]
+fn limit_memory(command: &mut Command, megabytes: Option<u64>) {
+    use std::os::unix::process::CommandExt;
+
+    let Some(megabytes) = megabytes else {
+        return;
+    };
+    let bytes = megabytes.saturating_mul(1024 * 1024) as libc::rlim_t;
+    // SAFETY: setrlimit is async-signal-safe and only touches the child being started.
+    unsafe {
+        command.pre_exec(move || {
+            let limit = libc::rlimit {
+                rlim_cur: bytes,
+                rlim_max: bytes,
+            };
+            if libc::setrlimit(libc::RLIMIT_AS, &limit) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn limit_memory(_command: &mut Command, megabytes: Option<u64>) {
+    if megabytes.is_some() {
+        warn!("--memory-limit is only enforced on Unix");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcome(scenario: &str, error: Option<&str>) -> ScenarioOutcome {
+        ScenarioOutcome {
+            scenario: scenario.to_string(),
+            track: "default".to_string(),
+            rubric: None,
+            seed: None,
+            error: error.map(str::to_string),
+            exit_code: if error.is_some() { 2 } else { 0 },
+            max_duration: 0,
+            assertions: Vec::new(),
+            warnings: Vec::new(),
+            fingerprint: None,
+        }
+    }
+
+    fn strings(args: &[OsString]) -> Vec<String> {
+        args.iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_node_args() {
+        let dir = std::env::temp_dir().join(format!("tcp-lab-daemon-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("bundle/lib")).unwrap();
+        fs::create_dir_all(dir.join("other")).unwrap();
+        fs::write(dir.join("bundle/lib/sender.so"), "").unwrap();
+        fs::write(dir.join("other/sender.so"), "").unwrap();
+        let bundle = fs::canonicalize(dir.join("bundle")).unwrap();
+        let cpp = |lib: &Path| Side {
+            cpp: Some(lib.to_path_buf()),
+            ..Side::default()
+        };
+        let python = Side {
+            python: Some("sender.Sender".into()),
+            ..Side::default()
+        };
+
+        let own = node_args(
+            Some(&cpp(Path::new("lib/sender.so"))),
+            None,
+            "sender",
+            &bundle,
+        );
+        let escaped = node_args(
+            Some(&cpp(Path::new("../other/sender.so"))),
+            None,
+            "sender",
+            &bundle,
+        );
+        let absolute = node_args(
+            Some(&cpp(&dir.join("other/sender.so"))),
+            None,
+            "sender",
+            &bundle,
+        );
+        let classpath = inside(&bundle, Path::new("lib/../.."));
+        let python_args = node_args(Some(&python), None, "sender", &bundle);
+        let reference = node_args(None, Some("rdt3"), "receiver", &bundle);
+        let both = node_args(Some(&python), Some("rdt3"), "receiver", &bundle);
+        let neither = node_args(None, None, "receiver", &bundle);
+        let two = Side {
+            python: Some("sender.Sender".into()),
+            java: Some("Sender".into()),
+            ..Side::default()
+        };
+        let ambiguous = node_args(Some(&two), None, "sender", &bundle);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            strings(&own.unwrap()),
+            [
+                "--cpp-sender-lib".to_string(),
+                bundle.join("lib/sender.so").display().to_string()
+            ]
+        );
+        assert!(escaped.is_err(), "a `..` path must not leave the bundle");
+        assert!(
+            absolute.is_err(),
+            "an absolute path must not leave the bundle"
+        );
+        assert!(classpath.is_err());
+        assert_eq!(
+            strings(&python_args.unwrap()),
+            ["--python-sender", "sender.Sender"]
+        );
+        assert_eq!(strings(&reference.unwrap()), ["--builtin-receiver", "rdt3"]);
+        assert!(both.is_err());
+        assert!(neither.is_err());
+        assert!(ambiguous.is_err());
+    }
+
+    #[test]
+    fn test_manifest_rejects_builtin() {
+        let manifest = "[sender]\nbuiltin = \"rdt3\"\n[receiver]\nbuiltin = \"rdt3\"\n";
+        assert!(toml::from_str::<Manifest>(manifest).is_err());
+        let manifest: Manifest = toml::from_str("[sender]\ncpp = \"sender.so\"\n").unwrap();
+        assert!(manifest.receiver.is_none());
+        assert!(manifest.uses(|side| side.cpp.is_some()));
+        assert!(!manifest.uses(|side| side.java.is_some()));
+    }
+
+    #[test]
+    fn test_unproven() {
+        let selected: BTreeSet<String> = ["test_gbn", "test_rdt3"].map(String::from).into();
+        let passed = [outcome("test_gbn", None), outcome("test_rdt3", None)];
+        assert_eq!(unproven(&selected, Some(&passed)), None);
+        assert_eq!(unproven(&BTreeSet::new(), Some(&[])), None);
+
+        assert!(unproven(&selected, None).is_some());
+        let missing = unproven(&selected, Some(&passed[..1])).unwrap();
+        assert!(missing.contains("test_rdt3"), "{missing}");
+        let extra = [passed[0].clone(), outcome("test_sr", None)];
+        let unexpected = unproven(&selected, Some(&extra)).unwrap();
+        assert!(unexpected.contains("test_sr"), "{unexpected}");
+        let repeated = [passed[0].clone(), passed[1].clone(), passed[1].clone()];
+        assert!(unproven(&selected, Some(&repeated)).is_some());
+        let failed = [
+            outcome("test_gbn", None),
+            outcome("test_rdt3", Some("lost")),
+        ];
+        let failed = unproven(&selected, Some(&failed)).unwrap();
+        assert!(failed.contains("test_rdt3"), "{failed}");
+    }
+}
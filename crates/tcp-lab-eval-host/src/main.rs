@@ -1,73 +1,576 @@
-use anyhow::Result;
-use clap::Parser;
-use std::path::PathBuf;
+mod crash;
+mod daemon;
+mod pipe;
+mod signing;
+
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use pipe::ChildReport;
+use serde::Serialize;
+use signing::Submission;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
 use tcp_lab_loader::spec::{builtin_by_name, parse_python_spec};
-use tcp_lab_loader::{LoaderRequest, ProtocolDescriptor, ProtocolLoader, PythonConfig};
-use tcp_lab_simulator::{SimulationReport, scenario_runner};
-use tracing::info;
+use tcp_lab_loader::{
+    LoaderError, LoaderRequest, ProtocolDescriptor, ProtocolLoader, PythonConfig,
+};
+use tcp_lab_simulator::i18n::{self, Lang, Msg};
+use tcp_lab_simulator::{
+    AssertionPolicy, ExitStatus, GradingFailed, HintCatalog, PayloadPattern, RunOptions,
+    ScenarioError, ScenarioOutcome, ScenarioResult, SimulationReport, Simulator, StudentSeed,
+    TagFilter, TestScenario, Workload, github, scenario_runner, workload,
+};
+use tracing::{info, warn};
 
 #[derive(Parser, Debug)]
-#[command(author, version, about = "Headless grader for TCP Lab scenarios")]
+#[command(author, version, about = Msg::AboutEvalHost.text())]
 struct Args {
-    /// Path to the scenario TOML file to execute.
-    #[arg(long)]
-    scenario: String,
+    #[arg(long, value_name = "LANG", help = Msg::HelpLang.text())]
+    lang: Option<Lang>,
+
+    #[arg(long, help = Msg::HelpScenarioRun.text())]
+    scenario: Option<String>,
+
+    #[arg(long, value_name = "TAG", value_delimiter = ',', help = Msg::HelpTags.text())]
+    tags: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "TAG",
+        value_delimiter = ',',
+        help = Msg::HelpExcludeTags.text()
+    )]
+    exclude_tags: Vec<String>,
+
+    #[arg(long, default_value_t = 3, help = Msg::HelpMessages.text())]
+    messages: usize,
+
+    #[arg(long, value_name = "BYTES", default_value_t = 16, help = Msg::HelpMessageSize.text())]
+    size: usize,
+
+    #[arg(long, value_name = "MS", default_value_t = 1000, help = Msg::HelpInterval.text())]
+    interval: u64,
+
+    #[arg(
+        long,
+        value_name = "PATTERN",
+        default_value = "counter",
+        help = Msg::HelpPayloadPattern.text()
+    )]
+    payload_pattern: PayloadPattern,
+
+    #[arg(long, default_value_t = false, help = Msg::HelpStrictEval.text())]
+    strict: bool,
 
-    /// Java classpath used when loading JVM-based implementations.
-    #[arg(long)]
+    #[arg(long, value_name = "FILE", help = Msg::HelpHints.text())]
+    hints: Option<PathBuf>,
+
+    #[arg(long, value_name = "POLICY", help = Msg::HelpAssertionPolicy.text())]
+    assertion_policy: Option<AssertionPolicy>,
+
+    #[arg(long, help = Msg::HelpClasspath.text())]
     classpath: Option<String>,
 
-    #[arg(long)]
+    #[arg(
+        long = "jvm-opt",
+        value_name = "OPTION",
+        allow_hyphen_values = true,
+        help = Msg::HelpJvmOpt.text()
+    )]
+    jvm_opts: Vec<String>,
+
+    #[arg(long, default_value_t = false, help = Msg::HelpDeterministicTime.text())]
+    deterministic_time: bool,
+
+    #[arg(
+        long,
+        value_name = "MS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "2000",
+        help = Msg::HelpDebugAttach.text()
+    )]
+    debug_attach: Option<u64>,
+
+    #[arg(long, value_name = "ID", help = Msg::HelpStudentId.text())]
+    student_id: Option<String>,
+    #[arg(long, value_name = "SEED", default_value_t = 0, help = Msg::HelpBaseSeed.text())]
+    base_seed: u64,
+
+    #[arg(long, help = Msg::HelpJavaSender.text())]
     java_sender: Option<String>,
-    #[arg(long)]
+    #[arg(long, help = Msg::HelpJavaReceiver.text())]
     java_receiver: Option<String>,
 
-    #[arg(long)]
+    #[arg(long, help = Msg::HelpPythonSender.text())]
     python_sender: Option<String>,
-    #[arg(long)]
+    #[arg(long, help = Msg::HelpPythonReceiver.text())]
     python_receiver: Option<String>,
 
-    /// Root directory of the uv-managed Python project.
-    #[arg(long)]
+    #[arg(long, help = Msg::HelpPythonUvProject.text())]
     python_uv_project: Option<PathBuf>,
 
-    /// Extra path to insert into Python sys.path (in addition to uv).
-    #[arg(long)]
+    #[arg(long, help = Msg::HelpPythonPath.text())]
     python_path: Option<PathBuf>,
 
-    #[arg(long)]
+    #[arg(long, help = Msg::HelpCppSenderLib.text())]
     cpp_sender_lib: Option<PathBuf>,
-    #[arg(long)]
+    #[arg(long, help = Msg::HelpCppReceiverLib.text())]
     cpp_receiver_lib: Option<PathBuf>,
 
-    #[arg(long)]
+    #[arg(long, help = Msg::HelpBuiltinSender.text())]
     builtin_sender: Option<String>,
-    #[arg(long)]
+    #[arg(long, help = Msg::HelpBuiltinReceiver.text())]
     builtin_receiver: Option<String>,
+
+    #[arg(long, default_value_t = false, help = Msg::HelpOtel.text())]
+    otel: bool,
+
+    #[arg(long, value_enum, default_value_t = Format::Text, help = Msg::HelpFormat.text())]
+    format: Format,
+
+    #[arg(long, value_name = "FILE", help = Msg::HelpEvalJsonOut.text())]
+    json_out: Option<PathBuf>,
+
+    #[arg(long, value_name = "DIR", help = Msg::HelpQueue.text())]
+    queue: Option<PathBuf>,
+
+    #[arg(long, requires = "queue", default_value_t = false, help = Msg::HelpQueueOnce.text())]
+    once: bool,
+
+    #[arg(long, value_name = "MS", default_value_t = 1000, help = Msg::HelpQueuePollMs.text())]
+    poll_ms: u64,
+
+    #[arg(long, value_name = "SECS", default_value_t = 600, help = Msg::HelpTimeLimit.text())]
+    time_limit: u64,
+
+    #[arg(long, value_name = "MB", help = Msg::HelpMemoryLimit.text())]
+    memory_limit: Option<u64>,
+
+    #[arg(long, value_name = "FILE", help = Msg::HelpCrashReport.text())]
+    crash_report: Option<PathBuf>,
+
+    #[arg(long, value_name = "FD", help = Msg::HelpReportFd.text())]
+    report_fd: Option<i32>,
+
+    #[arg(long, default_value_t = false, help = Msg::HelpFingerprints.text())]
+    fingerprints: bool,
+
+    #[arg(long, value_name = "FILE", help = Msg::HelpSigningKey.text())]
+    signing_key: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
-    tracing_subscriber::fmt::init();
+#[derive(Subcommand, Debug)]
+enum Command {
+    #[command(about = Msg::AboutKeygen.text())]
+    Keygen {
+        #[arg(value_name = "OUT", help = Msg::HelpKeygenOut.text())]
+        out: PathBuf,
+    },
+    #[command(about = Msg::AboutVerify.text())]
+    Verify {
+        #[arg(value_name = "REPORT", help = Msg::HelpVerifyReport.text())]
+        report: PathBuf,
+        #[arg(long, value_name = "FILE", help = Msg::HelpPublicKey.text())]
+        public_key: PathBuf,
+        #[arg(long, value_name = "FILE", help = Msg::HelpSignature.text())]
+        signature: Option<PathBuf>,
+        #[arg(long, value_name = "ID", help = Msg::HelpVerifyStudent.text())]
+        student: Option<String>,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Text,
+    Github,
+}
+
+fn main() -> ExitCode {
+    // Before parsing, so that `--help` and argument errors are already localized.
+    i18n::set_lang(i18n::lang_from_args(std::env::args()));
+    let args = match Args::try_parse() {
+        Ok(args) => args,
+        Err(err) => {
+            // clap would exit with 2, which means a failed assertion here.
+            let _ = err.print();
+            return if err.use_stderr() {
+                ExitStatus::Environment.into()
+            } else {
+                ExitStatus::Pass.into()
+            };
+        }
+    };
+    match run(args) {
+        Ok(()) => ExitStatus::Pass.into(),
+        Err(err) => {
+            eprintln!("Error: {err:?}");
+            exit_status(&err).into()
+        }
+    }
+}
+
+/// The exit code for `err`. A submission that raises while being loaded is a student
+/// runtime error like one that raises while running.
+fn exit_status(err: &anyhow::Error) -> ExitStatus {
+    match err
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<LoaderError>())
+    {
+        Some(LoaderError::StudentRuntime(_)) => ExitStatus::StudentRuntime,
+        _ => ExitStatus::of(err),
+    }
+}
+
+fn run(args: Args) -> Result<()> {
+    let _otel_guard = init_logging(args.otel)?;
     info!("tcp-lab-eval-host starting...");
+    match &args.command {
+        Some(Command::Keygen { out }) => return signing::keygen(out),
+        Some(Command::Verify {
+            report,
+            public_key,
+            signature,
+            student,
+        }) => {
+            let submission = signing::verify(report, public_key, signature.as_deref())?;
+            let signed_for = submission.student.as_deref().unwrap_or("-");
+            if let Some(student) = student
+                && submission.student.as_ref() != Some(student)
+            {
+                anyhow::bail!(Msg::SignatureWrongStudent.fill(&[
+                    &report.display(),
+                    &signed_for,
+                    student
+                ]));
+            }
+            println!("{}", Msg::SignatureValid.fill(&[&report.display()]));
+            println!(
+                "{}",
+                Msg::SignedSubmission.fill(&[
+                    &signed_for,
+                    &submission.graded_at,
+                    &submission.artifacts,
+                    &submission.scenarios
+                ])
+            );
+            return Ok(());
+        }
+        None => {}
+    }
+    // A bad key should fail before grading, not when the report is written.
+    args.report_signer()?;
+    if let Some(dir) = &args.queue {
+        return daemon::run(&args, dir);
+    }
 
+    if let Some(fd) = args.report_fd {
+        pipe::open(fd)?;
+    }
+    // Hashed before loading, which may write to the submission's directories.
+    let submission = match &args.json_out {
+        Some(_) => Some(args.submission()?),
+        None => None,
+    };
+    let submission = submission.as_ref();
+    let libraries: Vec<PathBuf> = [&args.cpp_sender_lib, &args.cpp_receiver_lib]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect();
+    if !libraries.is_empty() {
+        crash::install(args.crash_report.clone(), &libraries);
+    }
     let loader = build_loader(&args)?;
-    let request = LoaderRequest {
-        sender: args.sender_descriptor()?,
-        receiver: args.receiver_descriptor()?,
+    let Some(path) = &args.scenario else {
+        let (sender, receiver) = loader.load_pair(args.loader_request()?)?;
+        let mut sim = Simulator::new(workload::default_config(), sender, receiver);
+        let workload = args.workload();
+        workload.schedule(&mut sim);
+        info!(
+            "No scenario given; sending {} {} messages of {} bytes every {} ms",
+            workload.messages, workload.pattern, workload.size, workload.interval
+        );
+        sim.run_until_complete();
+        log_summary(&sim.export_report());
+        return Ok(());
+    };
+
+    let filter = TagFilter {
+        tags: args.tags.clone(),
+        exclude_tags: args.exclude_tags.clone(),
     };
+    if Path::new(path).is_dir() {
+        return run_suite(&args, &loader, Path::new(path), &filter, submission);
+    }
+    let path = Path::new(path);
+    let scenario = load(&args, path)?;
+    if !filter.matches(&scenario) {
+        info!(
+            "Skipping {}: its tags {:?} are filtered out",
+            scenario.name, scenario.tags
+        );
+        return write_outcomes(&args, submission, &[]);
+    }
+    let result = grade(&args, &loader, &scenario, args.hint_catalog()?)
+        .inspect_err(|err| annotate_error(&args, path, err))?;
+    write_outcomes(
+        &args,
+        submission,
+        &[ScenarioOutcome::new(
+            &stem(path),
+            &scenario,
+            &result,
+            args.fingerprints,
+        )],
+    )?;
+    log_assertions(&result);
+    log_hints(&result);
+    log_metric_bands(&result);
+    annotate_result(&args, &scenario.name, path, &result);
+    log_summary(&result.into_result()?);
+    Ok(())
+}
 
+/// Load the protocols afresh and grade `scenario` with them.
+fn grade(
+    args: &Args,
+    loader: &ProtocolLoader,
+    scenario: &TestScenario,
+    hints: HintCatalog,
+) -> Result<ScenarioResult> {
+    let request = args.loader_request()?;
+    // Suites grade every scenario with the same JVM or interpreter, but not the same globals.
+    loader.reset(&request)?;
+    let factories = loader.factories(&request);
     let (sender, receiver) = loader.load_pair(request)?;
-    let report = scenario_runner::run_scenario(&args.scenario, sender, receiver)?;
-    log_summary(&report);
+    let options = RunOptions {
+        strict: args.strict,
+        factories,
+        hints,
+        assertion_policy: args.assertion_policy,
+        student_seed: args.student_id.as_ref().map(|student| StudentSeed {
+            student: student.clone(),
+            base_seed: args.base_seed,
+        }),
+        ..Default::default()
+    };
+    Ok(scenario_runner::run_scenario_with(
+        scenario, sender, receiver, options,
+    ))
+}
+
+/// Load the scenario at `path`, annotating it if it does not parse.
+fn load(args: &Args, path: &Path) -> Result<TestScenario> {
+    let scenario = scenario_runner::load_scenario(path, args.strict)
+        .map_err(anyhow::Error::from)
+        .inspect_err(|err| annotate_error(args, path, err))?;
+    Ok(scenario)
+}
+
+/// With `--format github`, print `err`, which stopped the scenario at `path` from being
+/// graded, as an annotation on it.
+fn annotate_error(args: &Args, path: &Path, err: &anyhow::Error) {
+    if args.format != Format::Github {
+        return;
+    }
+    let command = match err.downcast_ref::<ScenarioError>() {
+        Some(failure) => github::failure(Some(path), None, failure),
+        None => github::error(
+            Some(path),
+            None,
+            github::title(exit_status(err)),
+            &format!("{err:#}"),
+        ),
+    };
+    println!("{command}");
+}
+
+/// With `--format github`, print the failures of `result` as annotations on the scenario
+/// at `path`.
+fn annotate_result(args: &Args, name: &str, path: &Path, result: &ScenarioResult) {
+    if args.format == Format::Github && !result.passed() {
+        let source = scenario_runner::read_scenario(path).ok();
+        println!(
+            "{}",
+            github::scenario(name, Some(path), source.as_deref(), result)
+        );
+    }
+}
+
+/// Log how many assertions passed and every failure after the first, which the caller
+/// reports itself.
+fn log_assertions(result: &ScenarioResult) {
+    if result.passed() {
+        return;
+    }
+    if let Some(tally) = result.assertion_tally() {
+        info!("{tally}");
+    }
+    for err in result.failures.iter().skip(1) {
+        info!("{}", Msg::AlsoFailed.fill(&[&format!("{err:#}")]));
+    }
+}
+
+/// Log the metric bands the run left, whether or not it passed.
+fn log_metric_bands(result: &ScenarioResult) {
+    for warning in result
+        .report
+        .iter()
+        .flat_map(|report| &report.metric_band_warnings)
+    {
+        warn!("{}", Msg::MetricBandWarning.fill(&[&warning.message()]));
+    }
+}
+
+fn log_hints(result: &ScenarioResult) {
+    for hint in result.report.iter().flat_map(|report| &report.hints) {
+        info!("{}", Msg::Hint.fill(&[&hint.text]));
+    }
+}
+
+#[cfg(feature = "otel")]
+fn init_logging(otel: bool) -> Result<Option<tcp_lab_simulator::telemetry::OtelGuard>> {
+    use tracing_subscriber::filter::LevelFilter;
+    use tracing_subscriber::prelude::*;
+
+    if !otel {
+        tracing_subscriber::fmt::init();
+        return Ok(None);
+    }
+    let (otel_layer, guard) = tcp_lab_simulator::telemetry::otel_layer("tcp-lab-eval-host")?;
+    tracing_subscriber::registry()
+        .with(otel_layer)
+        .with(tracing_subscriber::fmt::layer().with_filter(LevelFilter::INFO))
+        .init();
+    Ok(Some(guard))
+}
+
+#[cfg(not(feature = "otel"))]
+fn init_logging(otel: bool) -> Result<Option<()>> {
+    if otel {
+        anyhow::bail!("`otel` feature disabled but --otel provided");
+    }
+    tracing_subscriber::fmt::init();
+    Ok(None)
+}
+
+/// Grade every scenario in `dir` that `filter` selects, failing with the exit status of
+/// the first failed scenario if any of them fails.
+fn run_suite(
+    args: &Args,
+    loader: &ProtocolLoader,
+    dir: &Path,
+    filter: &TagFilter,
+    submission: Option<&Submission>,
+) -> Result<()> {
+    let hints = args.hint_catalog()?;
+    let info = scenario_runner::load_suite_info(dir)?;
+    if !info.is_empty() {
+        info!("{}", info);
+    }
+    let mut passed = 0;
+    let mut skipped = 0;
+    let mut failed = Vec::new();
+    let mut status = None;
+    let mut outcomes = Vec::new();
+    for path in scenario_runner::scenario_files(dir)? {
+        let scenario = load(args, &path)?;
+        let name = stem(&path);
+        if !filter.matches(&scenario) {
+            skipped += 1;
+            continue;
+        }
+        let result = grade(args, loader, &scenario, hints.clone())
+            .inspect_err(|err| annotate_error(args, &path, err))?;
+        log_metric_bands(&result);
+        outcomes.push(ScenarioOutcome::new(
+            &name,
+            &scenario,
+            &result,
+            args.fingerprints,
+        ));
+        if let Some(err) = result.failures.first() {
+            info!("FAIL {}: {:#}", name, err);
+            log_assertions(&result);
+            log_hints(&result);
+            annotate_result(args, &name, &path, &result);
+            status.get_or_insert(err.exit_status());
+            failed.push(name);
+        } else {
+            log_summary(&result.into_result()?);
+            info!("PASS {}", name);
+            passed += 1;
+        }
+    }
+    info!(
+        "{} passed, {} failed, {} skipped",
+        passed,
+        failed.len(),
+        skipped
+    );
+    write_outcomes(args, submission, &outcomes)?;
+    if let Some(status) = status {
+        return Err(GradingFailed {
+            status,
+            message: format!("Failed scenarios: {}", failed.join(", ")),
+        }
+        .into());
+    }
     Ok(())
 }
 
+fn stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// `--json-out`: the outcomes with the submission they graded, which a signature binds
+/// them to.
+#[derive(Serialize)]
+struct OutcomeReport<'a> {
+    submission: &'a Submission,
+    scenarios: &'a [ScenarioOutcome],
+}
+
+/// With `--json-out`, write `outcomes` of `submission` there; with `--report-fd`, send
+/// them to the daemon.
+fn write_outcomes(
+    args: &Args,
+    submission: Option<&Submission>,
+    outcomes: &[ScenarioOutcome],
+) -> Result<()> {
+    if args.report_fd.is_some() && !pipe::send(&ChildReport::Outcomes(outcomes.to_vec())) {
+        anyhow::bail!("Failed to send the outcomes to the daemon");
+    }
+    let (Some(path), Some(submission)) = (&args.json_out, submission) else {
+        return Ok(());
+    };
+    let report = OutcomeReport {
+        submission,
+        scenarios: outcomes,
+    };
+    let data = serde_json::to_vec_pretty(&report).context("Failed to serialize outcomes")?;
+    signing::write_report(path, &data, args.report_signer()?.as_ref())
+}
+
 fn build_loader(args: &Args) -> Result<ProtocolLoader> {
-    let mut builder = ProtocolLoader::builder();
+    let mut builder = ProtocolLoader::builder()
+        .sandbox(args.strict)
+        .deterministic_time(args.deterministic_time)
+        .debug_attach(args.debug_attach.map(Duration::from_millis));
     if let Some(cp) = &args.classpath {
         builder = builder.java_classpath(cp.clone());
     }
+    for option in &args.jvm_opts {
+        builder = builder.jvm_option(option.clone());
+    }
 
     if args.python_uv_project.is_some() || args.python_path.is_some() {
         let mut cfg = PythonConfig::default();
@@ -80,19 +583,266 @@ fn build_loader(args: &Args) -> Result<ProtocolLoader> {
         builder = builder.python_config(cfg);
     }
 
-    builder.build()
+    Ok(builder.build()?)
 }
 
 fn log_summary(report: &SimulationReport) {
+    let summary = report.summary();
     info!(
-        "Simulation duration: {} ms | packets sent: {} | deliveries: {}",
+        "Simulation duration: {} ms | packets sent: {} ({} retransmitted, {} ambiguous ACKs) | deliveries: {}",
         report.duration_ms,
-        report.sender_packet_count,
-        report.delivered_data.len()
+        summary.sender_packets,
+        summary.retransmissions,
+        summary.ambiguous_acks,
+        summary.deliveries
+    );
+    if let (Some(optimal), Some(efficiency)) =
+        (summary.optimal_completion_ms, summary.efficiency_vs_optimal)
+    {
+        info!(
+            "Optimal completion: {} ms | efficiency vs optimal: {:.1}%",
+            optimal,
+            efficiency * 100.0
+        );
+    }
+    info!(
+        "Channel: {} drops ({} injected) | {} corruptions ({} injected) | {} truncations | {} tampered duplicates | {} ACK-side packets",
+        summary.drops(),
+        summary.injected_drops,
+        summary.corruptions(),
+        summary.injected_corruptions,
+        summary.truncations,
+        summary.tampered_duplicates,
+        summary.receiver_packets
+    );
+    if let Some(fraction) = summary.ack_clocked_fraction {
+        let clocking = &report.clocking;
+        info!(
+            "Sender clocking: {} | {} data segments sent on ACKs, {} on timers, {} otherwise ({:.1}% ACK-clocked)",
+            if clocking.ack >= clocking.timer {
+                "ACK-clocked"
+            } else {
+                "timer-driven"
+            },
+            clocking.ack,
+            clocking.timer,
+            clocking.other,
+            fraction * 100.0
+        );
+    }
+    let backpressure = &report.app_backpressure;
+    if backpressure.pauses > 0 {
+        info!(
+            "App backpressure: {} pauses, {} ms paused | {} sends held, waiting {:.1} ms on average and {} ms at most | {} still waiting",
+            backpressure.pauses,
+            backpressure.paused_ms,
+            backpressure.held_sends,
+            backpressure.mean_wait_ms().unwrap_or(0.0),
+            backpressure.max_wait_ms,
+            backpressure.left_sends
+        );
+    }
+    for (node, bytes) in [
+        ("Sender", summary.from_sender),
+        ("Receiver", summary.from_receiver),
+    ] {
+        info!(
+            "{} sent: {} data segments ({} wire bytes, {} carrying an ACK) | {} pure ACKs ({} wire bytes)",
+            node,
+            bytes.data_packets,
+            bytes.data_wire_bytes,
+            bytes.piggybacked_acks,
+            bytes.pure_ack_packets,
+            bytes.pure_ack_wire_bytes
+        );
+    }
+    for (node, timers) in [
+        ("Sender", report.timers.sender),
+        ("Receiver", report.timers.receiver),
+    ] {
+        info!(
+            "{} timers: {} started, {} fired, {} cancelled, {} still armed",
+            node, timers.started, timers.fired, timers.cancelled, timers.armed
+        );
+    }
+    for (node, callbacks) in [
+        ("Sender", report.callbacks.sender),
+        ("Receiver", report.callbacks.receiver),
+    ] {
+        info!(
+            "{} callbacks: init {} | on_packet {} | on_timer {} | on_app_data {} | {:.1} ms inside",
+            node,
+            callbacks.init.calls,
+            callbacks.on_packet.calls,
+            callbacks.on_timer.calls,
+            callbacks.on_app_data.calls,
+            callbacks.total_us() as f64 / 1000.0
+        );
+    }
+    for (kind, latency) in [
+        ("Channel", &summary.channel_latency),
+        ("Delivery", &summary.delivery_latency),
+        ("Round-trip", &summary.round_trip_latency),
+    ] {
+        if let Some(latency) = latency {
+            info!(
+                "{} latency: p50 {} ms | p95 {} ms | p99 {} ms",
+                kind, latency.p50, latency.p95, latency.p99
+            );
+        }
+    }
+    if let Some(media) = &report.media {
+        info!(
+            "Media: {} frames | {:.1}% on time | {:.1}% late | {:.1}% lost",
+            media.frames,
+            media.on_time_percent(),
+            media.late_percent,
+            media.lost_percent
+        );
+    }
+    if summary.dropped_logs > 0 || summary.dropped_metric_values > 0 {
+        info!(
+            "Over budget: {} log messages past the first {} | {} metric values under {} names past the first {}",
+            summary.dropped_logs,
+            report.config.log_limit,
+            summary.dropped_metric_values,
+            report.dropped_metrics.len(),
+            report.config.metric_name_limit
+        );
+    }
+    if !report.bridge_warning_counts.is_empty() {
+        let counts: Vec<String> = report
+            .bridge_warning_counts
+            .iter()
+            .map(|(kind, count)| format!("{kind:?} {count}"))
+            .collect();
+        info!("Bridge warnings: {}", counts.join(" | "));
+    }
+    info!(
+        "Wire bytes: {} ({} B headers) | efficiency: {:.1}% | goodput: {:.0} bit/s",
+        report.traffic.wire_bytes(),
+        report.config.header_bytes,
+        report.efficiency() * 100.0,
+        report.goodput_bps()
     );
 }
 
 impl Args {
+    fn workload(&self) -> Workload {
+        Workload {
+            messages: self.messages,
+            size: self.size,
+            interval: self.interval,
+            pattern: self.payload_pattern,
+        }
+    }
+
+    fn hint_catalog(&self) -> Result<HintCatalog> {
+        match &self.hints {
+            Some(path) => HintCatalog::load(path),
+            None => Ok(HintCatalog::default()),
+        }
+    }
+
+    /// The grading flags a `--queue` daemon hands on to each child eval-host. Paths are made
+    /// absolute since the child runs inside the bundle.
+    fn queue_child_args(&self) -> Vec<std::ffi::OsString> {
+        let absolute = |path: &PathBuf| fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+        let mut out: Vec<std::ffi::OsString> = Vec::new();
+        if let Some(lang) = self.lang {
+            out.extend(["--lang".into(), lang.to_string().into()]);
+        }
+        if !self.tags.is_empty() {
+            out.extend(["--tags".into(), self.tags.join(",").into()]);
+        }
+        if !self.exclude_tags.is_empty() {
+            out.extend(["--exclude-tags".into(), self.exclude_tags.join(",").into()]);
+        }
+        if self.strict {
+            out.push("--strict".into());
+        }
+        if let Some(hints) = &self.hints {
+            out.extend(["--hints".into(), absolute(hints).into()]);
+        }
+        if let Some(policy) = self.assertion_policy {
+            let policy = match policy {
+                AssertionPolicy::All => "all",
+                AssertionPolicy::FirstFailure => "first-failure",
+            };
+            out.extend(["--assertion-policy".into(), policy.into()]);
+        }
+        for opt in &self.jvm_opts {
+            out.extend(["--jvm-opt".into(), opt.into()]);
+        }
+        if self.deterministic_time {
+            out.push("--deterministic-time".into());
+        }
+        if self.fingerprints {
+            out.push("--fingerprints".into());
+        }
+        // The student ID comes from each bundle's manifest.
+        if self.base_seed != 0 {
+            out.extend(["--base-seed".into(), self.base_seed.to_string().into()]);
+        }
+        if let Some(project) = &self.python_uv_project {
+            out.extend(["--python-uv-project".into(), absolute(project).into()]);
+        }
+        out
+    }
+
+    /// What this run grades, for the `--json-out` report.
+    fn submission(&self) -> Result<Submission> {
+        let labels: Vec<String> = [
+            ("java-sender", &self.java_sender),
+            ("java-receiver", &self.java_receiver),
+            ("python-sender", &self.python_sender),
+            ("python-receiver", &self.python_receiver),
+            ("builtin-sender", &self.builtin_sender),
+            ("builtin-receiver", &self.builtin_receiver),
+        ]
+        .into_iter()
+        .filter_map(|(flag, value)| Some(format!("{flag}={}", value.as_ref()?)))
+        .collect();
+        // Java tolerates classpath entries that do not exist; they hold nothing to hash.
+        let classpath: Vec<PathBuf> = self
+            .classpath
+            .iter()
+            .flat_map(std::env::split_paths)
+            .filter(|entry| entry.exists())
+            .collect();
+        let paths: Vec<&Path> = [
+            &self.cpp_sender_lib,
+            &self.cpp_receiver_lib,
+            &self.python_path,
+            &self.python_uv_project,
+        ]
+        .into_iter()
+        .flatten()
+        .chain(&classpath)
+        .map(PathBuf::as_path)
+        .collect();
+        let scenarios: Vec<&Path> = self.scenario.iter().map(Path::new).collect();
+        Ok(Submission::new(
+            self.student_id.clone(),
+            signing::digest(&labels, &paths)?,
+            signing::digest(&[], &scenarios)?,
+        ))
+    }
+
+    fn report_signer(&self) -> Result<Option<signing::ReportSigner>> {
+        self.signing_key
+            .as_deref()
+            .map(signing::ReportSigner::load)
+            .transpose()
+    }
+
+    fn loader_request(&self) -> Result<LoaderRequest> {
+        Ok(LoaderRequest {
+            sender: self.sender_descriptor()?,
+            receiver: self.receiver_descriptor()?,
+        })
+    }
+
     fn sender_descriptor(&self) -> Result<Option<ProtocolDescriptor>> {
         self.resolve_descriptor(
             &self.java_sender,
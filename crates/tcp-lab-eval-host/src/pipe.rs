@@ -0,0 +1,133 @@
+//! The channel from a `--queue` child back to its daemon. The daemon owns a pipe and
+//! hands its write end to the child as `--report-fd`; the child sends its outcomes (and,
+//! if a C++ submission crashes it, the crash report) as one JSON line each. The daemon
+//! reads its grades from here, never from files in `results/`.
+//!
+//! The pipe does not authenticate what comes over it. The submission runs in the child
+//! and can write to the same descriptor: Python with `os.write(3, ...)`, which raises no
+//! audit event for the sandbox to refuse, C++ with `write(3, ...)`, and elsewhere than
+//! Unix any `print` of a JSON line. The daemon rejects a run that reports outcomes more
+//! than once, which catches a submission that writes alongside the grader. One that
+//! writes the only report and then exits cleanly looks just like the grader, so a signed
+//! result attests what the child reported, not that the grader's code wrote it.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use tcp_lab_simulator::ScenarioOutcome;
+
+use crate::crash::CrashReport;
+
+/// Where the daemon puts the pipe in the child. Elsewhere than Unix, the child's stdout
+/// (1) is the pipe instead, and lines that are not reports go to the log.
+#[cfg(unix)]
+pub const REPORT_FD: i32 = 3;
+#[cfg(not(unix))]
+pub const REPORT_FD: i32 = 1;
+
+/// One line on the pipe.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChildReport {
+    Outcomes(Vec<ScenarioOutcome>),
+    Crash(CrashReport),
+}
+
+static PIPE: OnceLock<Mutex<Box<dyn Write + Send>>> = OnceLock::new();
+
+/// Send reports over `fd` from here on (`--report-fd`).
+pub fn open(fd: i32) -> Result<()> {
+    let pipe: Box<dyn Write + Send> = if fd == 1 {
+        Box::new(io::stdout())
+    } else {
+        Box::new(inherited(fd)?)
+    };
+    if PIPE.set(Mutex::new(pipe)).is_err() {
+        bail!("--report-fd was opened twice");
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn inherited(fd: i32) -> Result<File> {
+    use std::os::fd::FromRawFd;
+
+    // SAFETY: fcntl only inspects and flags the descriptor; it is taken over only once it
+    // is known to be open, and `open` takes it over at most once.
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFD);
+        if flags == -1 {
+            bail!("--report-fd {fd} is not an open file descriptor");
+        }
+        // Not passed on to anything the submission starts.
+        libc::fcntl(fd, libc::F_SETFD, flags | libc::FD_CLOEXEC);
+        Ok(File::from_raw_fd(fd))
+    }
+}
+
+#[cfg(not(unix))]
+fn inherited(fd: i32) -> Result<File> {
+    bail!("--report-fd {fd}: only stdout (1) can be inherited outside Unix")
+}
+
+/// Send `report` to the daemon; `false` without `--report-fd`. Only tries the lock, since
+/// a crash report may be sent from a signal handler that interrupted another send.
+pub fn send(report: &ChildReport) -> bool {
+    let Some(pipe) = PIPE.get() else {
+        return false;
+    };
+    let Ok(mut pipe) = pipe.try_lock() else {
+        return false;
+    };
+    let Ok(mut line) = serde_json::to_vec(report) else {
+        return false;
+    };
+    line.push(b'\n');
+    pipe.write_all(&line).and_then(|()| pipe.flush()).is_ok()
+}
+
+/// Read the reports from `pipe` until the child closes it, copying any other line to
+/// `log`.
+pub fn collect(pipe: impl Read, mut log: File) -> Vec<ChildReport> {
+    let mut reports = Vec::new();
+    let mut pipe = BufReader::new(pipe);
+    let mut line = Vec::new();
+    while matches!(pipe.read_until(b'\n', &mut line), Ok(n) if n > 0) {
+        match serde_json::from_slice(&line) {
+            Ok(report) => reports.push(report),
+            Err(_) => {
+                let _ = log.write_all(&line);
+            }
+        }
+        line.clear();
+    }
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_collect() {
+        let log = std::env::temp_dir().join(format!("tcp-lab-pipe-{}.log", std::process::id()));
+        let outcomes = r#"{"outcomes":[{"scenario":"test_rdt3","error":null,"exit_code":0}]}"#;
+        let crash = r#"{"crash":{"signal":"SIGSEGV","fault":"null pointer dereference","location":null,"frames":[]}}"#;
+        let stream = format!("{outcomes}\nhello from the submission\n{crash}\n{{\"outcomes\":");
+        let reports = collect(stream.as_bytes(), File::create(&log).unwrap());
+        let logged = fs::read_to_string(&log).unwrap();
+        fs::remove_file(&log).unwrap();
+
+        assert_eq!(reports.len(), 2);
+        assert!(
+            matches!(&reports[0], ChildReport::Outcomes(outcomes) if outcomes.len() == 1 && outcomes[0].passed())
+        );
+        assert!(matches!(&reports[1], ChildReport::Crash(report) if report.signal == "SIGSEGV"));
+        // Other lines, and a report cut off by the child's exit, go to the log.
+        assert_eq!(logged, "hello from the submission\n{\"outcomes\":");
+    }
+}
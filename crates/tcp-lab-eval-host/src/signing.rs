@@ -0,0 +1,274 @@
+//! Detached ed25519 signatures for the JSON reports eval-host writes, so staff can tell a
+//! report from the grading host from one a student edited before forwarding it.
+//!
+//! Keys and signatures are stored as one line of base64: a 32-byte secret key in the
+//! `keygen` output, its 32-byte public key in `<output>.pub`, and the 64-byte signature of
+//! a report's exact bytes in `<report>.sig`.
+//!
+//! Every signed report starts with a [`Submission`]: hashes of the protocols and scenarios
+//! that were graded, the student and the time. The signature covers it with the outcomes,
+//! so a passing report cannot be passed off as another student's or another lab's.
+
+use anyhow::{Context, Result, anyhow, bail};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tcp_lab_simulator::i18n::Msg;
+
+/// The grading host's secret key, from `--signing-key`.
+pub struct ReportSigner(SigningKey);
+
+impl ReportSigner {
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes = read_key(path)?;
+        Ok(Self(SigningKey::from_bytes(&bytes)))
+    }
+}
+
+/// What a report graded, at the top of every report eval-host writes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Submission {
+    /// `--student-id`, or `student` in a bundle's manifest.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub student: Option<String>,
+    /// [`digest`] of the sender and receiver: the files they were loaded from and how.
+    pub artifacts: String,
+    /// [`digest`] of the scenario files graded against.
+    pub scenarios: String,
+    /// When grading started, in seconds since the Unix epoch.
+    pub graded_at: u64,
+}
+
+impl Submission {
+    pub fn new(student: Option<String>, artifacts: String, scenarios: String) -> Self {
+        Self {
+            student,
+            artifacts,
+            scenarios,
+            graded_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs()),
+        }
+    }
+}
+
+/// SHA-256, in hex, of `labels` and of every file under `paths`, each path being a file or
+/// a directory walked in name order. Files are hashed with their path relative to the one
+/// given, so the same submission hashes the same wherever it is unpacked. Python's
+/// `__pycache__` is left out, as loading the submission writes it; symbolic links are
+/// hashed as their target's name and not followed.
+pub fn digest(labels: &[String], paths: &[&Path]) -> Result<String> {
+    let mut hasher = Sha256::new();
+    for label in labels {
+        field(&mut hasher, label.as_bytes());
+    }
+    for path in paths {
+        digest_tree(&mut hasher, path, path)?;
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+fn digest_tree(hasher: &mut Sha256, root: &Path, path: &Path) -> Result<()> {
+    let meta =
+        fs::symlink_metadata(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    if meta.is_dir() {
+        if path.file_name().is_some_and(|name| name == "__pycache__") {
+            return Ok(());
+        }
+        let mut entries = fs::read_dir(path)
+            .and_then(|entries| {
+                entries
+                    .map(|entry| entry.map(|entry| entry.path()))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        entries.sort();
+        for entry in entries {
+            digest_tree(hasher, root, &entry)?;
+        }
+        return Ok(());
+    }
+    field(hasher, relative.to_string_lossy().as_bytes());
+    if meta.is_symlink() {
+        let target =
+            fs::read_link(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        field(hasher, target.to_string_lossy().as_bytes());
+    } else {
+        let data = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        field(hasher, &data);
+    }
+    Ok(())
+}
+
+/// `bytes`, prefixed with their length so that no two sequences of fields hash alike.
+fn field(hasher: &mut Sha256, bytes: &[u8]) {
+    hasher.update((bytes.len() as u64).to_le_bytes());
+    hasher.update(bytes);
+}
+
+/// Create a key pair: the secret key in `out`, readable only by its owner on Unix, and the
+/// public key to hand to staff in `out.pub`.
+pub fn keygen(out: &Path) -> Result<()> {
+    let mut secret = [0u8; 32];
+    getrandom::fill(&mut secret).map_err(|err| anyhow!("Failed to generate a key: {err}"))?;
+    let key = SigningKey::from_bytes(&secret);
+    write_private(out, &format!("{}\n", STANDARD.encode(secret)))?;
+    let public = sibling(out, "pub");
+    let encoded = format!("{}\n", STANDARD.encode(key.verifying_key().as_bytes()));
+    fs::write(&public, encoded).with_context(|| format!("Failed to write {}", public.display()))
+}
+
+/// Check `report` against its signature (by default `<report>.sig`) and `public_key`, and
+/// return the submission it graded.
+pub fn verify(report: &Path, public_key: &Path, signature: Option<&Path>) -> Result<Submission> {
+    let key = VerifyingKey::from_bytes(&read_key(public_key)?)
+        .with_context(|| format!("Invalid public key {}", public_key.display()))?;
+    let signature = signature.map_or_else(|| sibling(report, "sig"), Path::to_path_buf);
+    let encoded = fs::read_to_string(&signature)
+        .with_context(|| format!("Failed to read {}", signature.display()))?;
+    let bytes = STANDARD
+        .decode(encoded.trim())
+        .ok()
+        .and_then(|bytes| <[u8; 64]>::try_from(bytes).ok())
+        .with_context(|| format!("Invalid signature file {}", signature.display()))?;
+    let data = fs::read(report).with_context(|| format!("Failed to read {}", report.display()))?;
+    if key
+        .verify_strict(&data, &Signature::from_bytes(&bytes))
+        .is_err()
+    {
+        bail!(Msg::SignatureInvalid.fill(&[&report.display()]));
+    }
+    #[derive(Deserialize)]
+    struct Signed {
+        submission: Submission,
+    }
+    serde_json::from_slice::<Signed>(&data)
+        .map(|signed| signed.submission)
+        .map_err(|_| anyhow!(Msg::SignatureNoSubmission.fill(&[&report.display()])))
+}
+
+/// Write the report `data` to `path`, and with a signer its signature to `<path>.sig`
+/// first. Each file is replaced in a single rename, so a reader never sees half of one.
+pub fn write_report(path: &Path, data: &[u8], signer: Option<&ReportSigner>) -> Result<()> {
+    if let Some(signer) = signer {
+        let signature = STANDARD.encode(signer.0.sign(data).to_bytes());
+        replace(&sibling(path, "sig"), format!("{signature}\n").as_bytes())?;
+    }
+    replace(path, data)
+}
+
+fn replace(path: &Path, data: &[u8]) -> Result<()> {
+    let partial = sibling(path, "partial");
+    fs::write(&partial, data).with_context(|| format!("Failed to write {}", partial.display()))?;
+    fs::rename(&partial, path).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// `path` with `.extension` appended, e.g. `b1.json.sig`.
+fn sibling(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(extension);
+    PathBuf::from(name)
+}
+
+fn read_key(path: &Path) -> Result<[u8; 32]> {
+    let encoded =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    STANDARD
+        .decode(encoded.trim())
+        .ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .with_context(|| format!("Invalid key file {}", path.display()))
+}
+
+/// Write a new file only its owner can read on Unix; an existing key is never overwritten.
+fn write_private(path: &Path, contents: &str) -> Result<()> {
+    use std::io::Write;
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signed_report_round_trip() {
+        let dir = std::env::temp_dir().join(format!("tcp-lab-signing-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("bundle")).unwrap();
+        fs::write(dir.join("bundle/sender.py"), "class Sender: pass\n").unwrap();
+        let key = dir.join("grader.key");
+        keygen(&key).unwrap();
+        let signer = ReportSigner::load(&key).unwrap();
+        let submission = Submission::new(
+            Some("2024001".into()),
+            digest(&[], &[&dir.join("bundle")]).unwrap(),
+            digest(&["test_rdt3".into()], &[]).unwrap(),
+        );
+        let report = dir.join("report.json");
+        let data = serde_json::to_vec(&serde_json::json!({
+            "submission": submission,
+            "scenarios": [{ "scenario": "test_rdt3", "error": null, "exit_code": 0 }],
+        }))
+        .unwrap();
+        write_report(&report, &data, Some(&signer)).unwrap();
+        let public = sibling(&key, "pub");
+        let verified = verify(&report, &public, None);
+
+        // Handing the passing report to another student breaks the signature.
+        let forwarded = String::from_utf8(data.clone())
+            .unwrap()
+            .replace("2024001", "2024002");
+        fs::write(&report, forwarded).unwrap();
+        let tampered = verify(&report, &public, None);
+        // So does signing with another key.
+        fs::write(&report, &data).unwrap();
+        keygen(&dir.join("other.key")).unwrap();
+        let other = verify(&report, &dir.join("other.key.pub"), None);
+        let overwrite = keygen(&key);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(verified.unwrap(), submission);
+        assert!(tampered.is_err());
+        assert!(other.is_err());
+        assert!(overwrite.is_err(), "keygen must not overwrite a key");
+    }
+
+    #[test]
+    fn test_digest() {
+        let dir = std::env::temp_dir().join(format!("tcp-lab-digest-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for copy in ["a", "b"] {
+            fs::create_dir_all(dir.join(copy).join("__pycache__")).unwrap();
+            fs::write(dir.join(copy).join("sender.py"), "x = 1\n").unwrap();
+        }
+        fs::write(dir.join("b/__pycache__/sender.pyc"), "compiled").unwrap();
+        let a = digest(&[], &[&dir.join("a")]).unwrap();
+        let b = digest(&[], &[&dir.join("b")]).unwrap();
+        let labelled = digest(&["builtin-receiver=rdt2".into()], &[&dir.join("a")]).unwrap();
+        fs::write(dir.join("b/sender.py"), "x = 2\n").unwrap();
+        let changed = digest(&[], &[&dir.join("b")]).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(a, b, "location and __pycache__ must not matter");
+        assert_ne!(a, labelled);
+        assert_ne!(a, changed);
+    }
+}
@@ -1,17 +1,29 @@
 use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::ptr;
 use std::slice;
+use std::sync::atomic::{AtomicU64, Ordering};
 
-use tcp_lab_abstract::{Packet, SystemContext, TcpHeader};
-use tracing::error;
+use tcp_lab_abstract::{BridgeWarning, Packet, PendingWarnings, SystemContext, TcpHeader};
 
 // ==========================================
 // TLS Context Management (same pattern as JNI)
 // ==========================================
 
+/// Version of the C ABI between the host and protocol libraries; must match
+/// `TCP_LAB_ABI_VERSION` in `sdk/cpp/include/tcp_lab/sdk.hpp`.
+pub const ABI_VERSION: u32 = 2;
+
+/// Simulated time of the latest callback, what `tcp_lab_now` returns without a context.
+static LAST_NOW_MS: AtomicU64 = AtomicU64::new(0);
+
+/// Warnings raised while no context was installed, reported at the next callback.
+static PENDING_WARNINGS: PendingWarnings = PendingWarnings::new();
+
 thread_local! {
     static CURRENT_CONTEXT: RefCell<Option<*mut (dyn SystemContext + 'static)>> =
         RefCell::new(None);
+    static SELFTEST_RESULT: RefCell<Option<Result<(), String>>> = const { RefCell::new(None) };
 }
 
 /// Ensure that the C ABI symbols remain linked/exported when the host application
@@ -21,14 +33,31 @@ pub fn ensure_linked() {
     unsafe {
         ptr::read_volatile(
             &(tcp_lab_send_packet
-                as unsafe extern "C" fn(u32, u32, u8, u16, u16, *const u8, usize)),
+                as unsafe extern "C" fn(u16, u16, u32, u32, u8, u16, u16, u16, *const u8, usize)),
         );
         ptr::read_volatile(&(tcp_lab_start_timer as unsafe extern "C" fn(u64, i32)));
         ptr::read_volatile(&(tcp_lab_cancel_timer as unsafe extern "C" fn(i32)));
         ptr::read_volatile(&(tcp_lab_deliver_data as unsafe extern "C" fn(*const u8, usize)));
+        ptr::read_volatile(
+            &(tcp_lab_deliver_data_at as unsafe extern "C" fn(u64, *const u8, usize)),
+        );
         ptr::read_volatile(&(tcp_lab_log as unsafe extern "C" fn(*const i8)));
         ptr::read_volatile(&(tcp_lab_now as unsafe extern "C" fn() -> u64));
         ptr::read_volatile(&(tcp_lab_record_metric as unsafe extern "C" fn(*const i8, f64)));
+        ptr::read_volatile(&(tcp_lab_record_state as unsafe extern "C" fn(*const i8)));
+        ptr::read_volatile(&(tcp_lab_init_params as unsafe extern "C" fn(*mut i8, usize) -> usize));
+        ptr::read_volatile(&(tcp_lab_config as unsafe extern "C" fn(*mut i8, usize) -> usize));
+        ptr::read_volatile(&(tcp_lab_receive_window as unsafe extern "C" fn() -> i64));
+        ptr::read_volatile(&(tcp_lab_peer_mss as unsafe extern "C" fn() -> i32));
+        ptr::read_volatile(&(tcp_lab_store as unsafe extern "C" fn(*const i8, *const i8)));
+        ptr::read_volatile(
+            &(tcp_lab_load as unsafe extern "C" fn(*const i8, *mut i8, usize) -> i64),
+        );
+        ptr::read_volatile(&(tcp_lab_selftest as unsafe extern "C" fn(*const SelftestProbe)));
+        ptr::read_volatile(&(tcp_lab_annotate_packet as unsafe extern "C" fn(*const i8)));
+        ptr::read_volatile(&(tcp_lab_app_pause as extern "C" fn()));
+        ptr::read_volatile(&(tcp_lab_app_resume as extern "C" fn()));
+        ptr::read_volatile(&(tcp_lab_refuse_app_data as unsafe extern "C" fn(*const i8)));
     }
 }
 
@@ -38,6 +67,8 @@ pub fn with_context<F, R>(ctx: &mut dyn SystemContext, f: F) -> R
 where
     F: FnOnce() -> R,
 {
+    LAST_NOW_MS.store(ctx.now(), Ordering::Relaxed);
+    PENDING_WARNINGS.flush(ctx);
     let ptr = ctx as *mut dyn SystemContext;
     // Extend lifetime to 'static for storage in TLS
     let static_ptr: *mut (dyn SystemContext + 'static) = unsafe { std::mem::transmute(ptr) };
@@ -64,27 +95,52 @@ where
             let ctx = unsafe { &mut *ptr };
             f(ctx);
         } else {
-            error!("tcp-lab-ffi: called without active SystemContext!");
+            PENDING_WARNINGS.push(
+                BridgeWarning::NoContext,
+                "a tcp_lab_* function was called outside a callback".to_string(),
+            );
         }
     });
 }
 
+/// Drop warnings held from an earlier run, before a new one starts.
+pub fn clear_pending_warnings() {
+    PENDING_WARNINGS.clear();
+}
+
+/// Report a call the bridge had to ignore or patch up. Outside a callback there is no
+/// context to report to, so it waits for the next one.
+fn warn(kind: BridgeWarning, detail: &str) {
+    CURRENT_CONTEXT.with(|c| match *c.borrow() {
+        Some(ptr) => unsafe { &mut *ptr }.report_warning(kind, detail),
+        None => PENDING_WARNINGS.push(kind, detail.to_string()),
+    });
+}
+
 // ==========================================
 // C ABI functions used by C++ SDK (NativeBridge.hpp)
 // ==========================================
 
+/// # Safety
+/// `payload` must point to `payload_len` readable bytes (or be null when the length is 0).
 #[unsafe(no_mangle)]
-pub extern "C" fn tcp_lab_send_packet(
+pub unsafe extern "C" fn tcp_lab_send_packet(
+    src_port: u16,
+    dst_port: u16,
     seq: u32,
     ack: u32,
     flags: u8,
     window: u16,
     checksum: u16,
+    urgent_ptr: u16,
     payload: *const u8,
     payload_len: usize,
 ) {
     if payload.is_null() && payload_len > 0 {
-        error!("tcp_lab_send_packet called with null payload pointer");
+        warn(
+            BridgeWarning::ConversionFailed,
+            "tcp_lab_send_packet called with null payload pointer",
+        );
         return;
     }
 
@@ -96,12 +152,14 @@ pub extern "C" fn tcp_lab_send_packet(
 
     use_context(|ctx| {
         let header = TcpHeader {
+            src_port,
+            dst_port,
             seq_num: seq,
             ack_num: ack,
             flags,
             window_size: window,
             checksum,
-            ..Default::default()
+            urgent_ptr,
         };
         let packet = Packet::new(header, data);
         ctx.send_packet(packet);
@@ -122,11 +180,16 @@ pub extern "C" fn tcp_lab_cancel_timer(timer_id: i32) {
     });
 }
 
+/// # Safety
+/// `data` must point to `len` readable bytes (or be null when the length is 0).
 #[unsafe(no_mangle)]
-pub extern "C" fn tcp_lab_deliver_data(data: *const u8, len: usize) {
+pub unsafe extern "C" fn tcp_lab_deliver_data(data: *const u8, len: usize) {
     if data.is_null() {
         if len > 0 {
-            error!("tcp_lab_deliver_data called with null data pointer");
+            warn(
+                BridgeWarning::ConversionFailed,
+                "tcp_lab_deliver_data called with null data pointer",
+            );
         }
         use_context(|ctx| {
             ctx.deliver_data(&[]);
@@ -141,8 +204,34 @@ pub extern "C" fn tcp_lab_deliver_data(data: *const u8, len: usize) {
     });
 }
 
+/// # Safety
+/// `data` must point to `len` readable bytes (or be null when the length is 0).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tcp_lab_deliver_data_at(offset: u64, data: *const u8, len: usize) {
+    if data.is_null() {
+        if len > 0 {
+            warn(
+                BridgeWarning::ConversionFailed,
+                "tcp_lab_deliver_data_at called with null data pointer",
+            );
+        }
+        use_context(|ctx| {
+            ctx.deliver_data_at(offset, &[]);
+        });
+        return;
+    }
+
+    let slice = unsafe { slice::from_raw_parts(data, len) };
+
+    use_context(|ctx| {
+        ctx.deliver_data_at(offset, slice);
+    });
+}
+
+/// # Safety
+/// `msg` must be null or a valid NUL-terminated C string.
 #[unsafe(no_mangle)]
-pub extern "C" fn tcp_lab_log(msg: *const i8) {
+pub unsafe extern "C" fn tcp_lab_log(msg: *const i8) {
     if msg.is_null() {
         return;
     }
@@ -153,22 +242,27 @@ pub extern "C" fn tcp_lab_log(msg: *const i8) {
                 ctx.log(s);
             });
         } else {
-            error!("tcp_lab_log received invalid UTF-8");
+            warn(
+                BridgeWarning::InvalidUtf8,
+                "tcp_lab_log received invalid UTF-8",
+            );
         }
     }
 }
 
 #[unsafe(no_mangle)]
 pub extern "C" fn tcp_lab_now() -> u64 {
-    let mut time = 0u64;
+    let mut time = LAST_NOW_MS.load(Ordering::Relaxed);
     use_context(|ctx| {
         time = ctx.now();
     });
     time
 }
 
+/// # Safety
+/// `name` must be null or a valid NUL-terminated C string.
 #[unsafe(no_mangle)]
-pub extern "C" fn tcp_lab_record_metric(name: *const i8, value: f64) {
+pub unsafe extern "C" fn tcp_lab_record_metric(name: *const i8, value: f64) {
     if name.is_null() {
         return;
     }
@@ -179,7 +273,335 @@ pub extern "C" fn tcp_lab_record_metric(name: *const i8, value: f64) {
                 ctx.record_metric(s, value);
             });
         } else {
-            error!("tcp_lab_record_metric received invalid UTF-8 name");
+            warn(
+                BridgeWarning::InvalidUtf8,
+                "tcp_lab_record_metric received invalid UTF-8 name",
+            );
+        }
+    }
+}
+
+/// # Safety
+/// `name` must be null or a valid NUL-terminated C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tcp_lab_record_state(name: *const i8) {
+    if name.is_null() {
+        return;
+    }
+    unsafe {
+        let cstr = std::ffi::CStr::from_ptr(name);
+        if let Ok(s) = cstr.to_str() {
+            use_context(|ctx| {
+                ctx.record_state(s);
+            });
+        } else {
+            warn(
+                BridgeWarning::InvalidUtf8,
+                "tcp_lab_record_state received invalid UTF-8 name",
+            );
+        }
+    }
+}
+
+/// Free receive buffer in bytes, or -1 when none is modeled (and on the sender).
+#[unsafe(no_mangle)]
+pub extern "C" fn tcp_lab_receive_window() -> i64 {
+    let mut window = -1;
+    use_context(|ctx| {
+        window = ctx.receive_window().map_or(-1, |free| free as i64);
+    });
+    window
+}
+
+/// MSS the peer advertised in its SYN, or -1 before that SYN arrives (or without one).
+#[unsafe(no_mangle)]
+pub extern "C" fn tcp_lab_peer_mss() -> i32 {
+    let mut mss = -1;
+    use_context(|ctx| {
+        mss = ctx.peer_mss().map_or(-1, i32::from);
+    });
+    mss
+}
+
+/// # Safety
+/// `key` and `value` must be null or valid NUL-terminated C strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tcp_lab_store(key: *const i8, value: *const i8) {
+    if key.is_null() || value.is_null() {
+        return;
+    }
+    let (key, value) = unsafe {
+        (
+            std::ffi::CStr::from_ptr(key).to_str(),
+            std::ffi::CStr::from_ptr(value).to_str(),
+        )
+    };
+    if let (Ok(key), Ok(value)) = (key, value) {
+        use_context(|ctx| {
+            ctx.store(key, value);
+        });
+    } else {
+        warn(
+            BridgeWarning::InvalidUtf8,
+            "tcp_lab_store received an invalid UTF-8 key or value",
+        );
+    }
+}
+
+/// Writes the value stored under `key` to `buf` (without a NUL) and returns its length,
+/// or -1 when nothing is stored. Like `tcp_lab_init_params`, nothing is written unless
+/// `buf_len` is at least that long.
+///
+/// # Safety
+/// `key` must be null or a valid NUL-terminated C string, and `buf` null or point to
+/// `buf_len` writable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tcp_lab_load(key: *const i8, buf: *mut i8, buf_len: usize) -> i64 {
+    if key.is_null() {
+        return -1;
+    }
+    let Ok(key) = unsafe { std::ffi::CStr::from_ptr(key) }.to_str() else {
+        warn(
+            BridgeWarning::InvalidUtf8,
+            "tcp_lab_load received an invalid UTF-8 key",
+        );
+        return -1;
+    };
+    let mut value = None;
+    use_context(|ctx| {
+        value = ctx.load(key);
+    });
+    let Some(value) = value else {
+        return -1;
+    };
+    if !buf.is_null() && buf_len >= value.len() {
+        unsafe { ptr::copy_nonoverlapping(value.as_ptr(), buf.cast::<u8>(), value.len()) };
+    }
+    value.len() as i64
+}
+
+/// # Safety
+/// `note` must be null or a valid NUL-terminated C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tcp_lab_annotate_packet(note: *const i8) {
+    if note.is_null() {
+        return;
+    }
+    if let Ok(note) = unsafe { std::ffi::CStr::from_ptr(note) }.to_str() {
+        use_context(|ctx| {
+            ctx.annotate_packet(note);
+        });
+    } else {
+        warn(
+            BridgeWarning::InvalidUtf8,
+            "tcp_lab_annotate_packet received an invalid UTF-8 note",
+        );
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn tcp_lab_app_pause() {
+    use_context(|ctx| {
+        ctx.app_pause();
+    });
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn tcp_lab_app_resume() {
+    use_context(|ctx| {
+        ctx.app_resume();
+    });
+}
+
+/// # Safety
+/// `reason` must be null or a valid NUL-terminated C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tcp_lab_refuse_app_data(reason: *const i8) {
+    if reason.is_null() {
+        use_context(|ctx| {
+            ctx.refuse_app_data("");
+        });
+        return;
+    }
+    if let Ok(reason) = unsafe { std::ffi::CStr::from_ptr(reason) }.to_str() {
+        use_context(|ctx| {
+            ctx.refuse_app_data(reason);
+        });
+    } else {
+        warn(
+            BridgeWarning::InvalidUtf8,
+            "tcp_lab_refuse_app_data received an invalid UTF-8 reason",
+        );
+    }
+}
+
+/// Writes the node's init params to `buf` as `key\0value\0key\0value\0...` and returns
+/// the number of bytes required. Nothing is written unless `buf_len` is at least that
+/// large, so callers can ask with a null buffer first.
+///
+/// # Safety
+/// `buf` must be null or point to `buf_len` writable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tcp_lab_init_params(buf: *mut i8, buf_len: usize) -> usize {
+    let mut params = BTreeMap::new();
+    use_context(|ctx| {
+        params = ctx.init_params();
+    });
+    unsafe { write_pairs(&params, buf, buf_len) }
+}
+
+/// Writes the simulation settings to `buf` in the format of `tcp_lab_init_params`.
+///
+/// # Safety
+/// `buf` must be null or point to `buf_len` writable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tcp_lab_config(buf: *mut i8, buf_len: usize) -> usize {
+    let mut params = BTreeMap::new();
+    use_context(|ctx| {
+        params = ctx.config().to_params();
+    });
+    unsafe { write_pairs(&params, buf, buf_len) }
+}
+
+unsafe fn write_pairs(pairs: &BTreeMap<String, String>, buf: *mut i8, buf_len: usize) -> usize {
+    let mut encoded = Vec::new();
+    for (key, value) in pairs {
+        encoded.extend_from_slice(key.as_bytes());
+        encoded.push(0);
+        encoded.extend_from_slice(value.as_bytes());
+        encoded.push(0);
+    }
+    if !buf.is_null() && buf_len >= encoded.len() {
+        unsafe { ptr::copy_nonoverlapping(encoded.as_ptr(), buf.cast::<u8>(), encoded.len()) };
+    }
+    encoded.len()
+}
+
+// ==========================================
+// ABI self-test
+// ==========================================
+
+/// Mirrors `tcp_lab_selftest_probe` in `sdk.hpp`. A library fills one in with the values
+/// below and hands it to `tcp_lab_selftest` when loaded, so a build whose layout differs
+/// from the host's is refused before it can corrupt headers mid-run. The first four
+/// fields are `u32`s, which sit at the same offsets under any packing; the rest need
+/// padding under the default layout.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SelftestProbe {
+    pub magic: u32,
+    pub struct_size: u32,
+    pub pointer_size: u32,
+    pub wchar_size: u32,
+    pub flags: u8,
+    pub window: u16,
+    pub seq: u32,
+    pub offset: u64,
+    pub value: f64,
+    pub timer_id: i32,
+    pub len: usize,
+}
+
+impl SelftestProbe {
+    /// What a library built with the host's settings sends.
+    pub fn expected() -> Self {
+        Self {
+            magic: 0x0102_0304,
+            struct_size: size_of::<Self>() as u32,
+            pointer_size: size_of::<*const u8>() as u32,
+            // `wchar_t` is UTF-16 on Windows and UTF-32 elsewhere.
+            wchar_size: if cfg!(windows) { 2 } else { 4 },
+            flags: 0xA5,
+            window: 0xBEEF,
+            seq: 0xDEAD_BEEF,
+            offset: 0x0123_4567_89AB_CDEF,
+            value: 1.5,
+            timer_id: -2,
+            len: 0x5A5A,
+        }
+    }
+
+    /// Why `self`, as sent by a library, differs from [`SelftestProbe::expected`], most
+    /// telling difference first.
+    pub fn mismatch(&self) -> Option<String> {
+        let expected = Self::expected();
+        if self.magic == expected.magic.swap_bytes() {
+            return Some("the library uses the opposite byte order".to_string());
+        }
+        if self.magic != expected.magic {
+            return Some(format!(
+                "the library sent a garbled probe (magic {:#010x})",
+                self.magic
+            ));
+        }
+        if self.pointer_size != expected.pointer_size {
+            return Some(format!(
+                "the library is a {}-bit build, but the host is {}-bit",
+                self.pointer_size * 8,
+                expected.pointer_size * 8
+            ));
+        }
+        if self.wchar_size != expected.wchar_size {
+            return Some(format!(
+                "wchar_t is {} bytes in the library but {} in the host (-fshort-wchar?)",
+                self.wchar_size, expected.wchar_size
+            ));
+        }
+        if self.struct_size != expected.struct_size {
+            return Some(format!(
+                "the probe struct is {} bytes in the library but {} in the host \
+                 (#pragma pack or -fpack-struct?)",
+                self.struct_size, expected.struct_size
+            ));
         }
+        let fields = [
+            ("flags", self.flags == expected.flags),
+            ("window", self.window == expected.window),
+            ("seq", self.seq == expected.seq),
+            ("offset", self.offset == expected.offset),
+            ("value", self.value == expected.value),
+            ("timer_id", self.timer_id == expected.timer_id),
+            ("len", self.len == expected.len),
+        ];
+        fields
+            .iter()
+            .find(|(_, same)| !same)
+            .map(|(field, _)| format!("field `{field}` of the probe came through corrupted"))
     }
 }
+
+/// Run `f`, which calls the library's `protocol_selftest`, and return what the library
+/// sent to `tcp_lab_selftest`: `None` if it never called it, else whether its probe
+/// matched the host's layout.
+pub fn selftest<F: FnOnce()>(f: F) -> Option<Result<(), String>> {
+    SELFTEST_RESULT.with(|result| *result.borrow_mut() = None);
+    f();
+    SELFTEST_RESULT.with(|result| result.borrow_mut().take())
+}
+
+/// # Safety
+/// `probe` must be null or point to a readable `tcp_lab_selftest_probe`. Only its first
+/// four fields are read unless they show it has the host's layout.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tcp_lab_selftest(probe: *const SelftestProbe) {
+    let outcome = if probe.is_null() {
+        Err("the library sent a null probe".to_string())
+    } else {
+        // The header fields are read one by one so a shorter struct isn't overrun.
+        let header = probe.cast::<u32>();
+        let head = unsafe { [0, 1, 2, 3].map(|i| ptr::read_unaligned(header.add(i))) };
+        let mut received = SelftestProbe {
+            magic: head[0],
+            struct_size: head[1],
+            pointer_size: head[2],
+            wchar_size: head[3],
+            ..SelftestProbe::expected()
+        };
+        // With the header wrong the fields aren't worth reading; it explains the mismatch.
+        if received.mismatch().is_none() {
+            received = unsafe { ptr::read_unaligned(probe) };
+        }
+        received.mismatch().map_or(Ok(()), Err)
+    };
+    SELFTEST_RESULT.with(|result| *result.borrow_mut() = Some(outcome));
+}
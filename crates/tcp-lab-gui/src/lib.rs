@@ -0,0 +1,508 @@
+//! Native desktop visualizer for the simulator.
+//!
+//! A windowed counterpart to `tcp_lab_simulator::tui::TuiApp` for machines where
+//! terminals mangle the TUI (notably Windows lab machines). It renders a
+//! zoomable space-time diagram with hover tooltips, cwnd/ssthresh charts and a
+//! packet inspector for the selected link event.
+
+use anyhow::anyhow;
+use eframe::egui::{self, Color32, RichText};
+use egui_plot::{Legend, Line, MarkerShape, Plot, PlotPoints, Points};
+use tcp_lab_simulator::report::series::downsample;
+use tcp_lab_simulator::{LinkDirection, LinkEventKind, LinkEventSummary, NodeId, Simulator};
+
+/// Vertical positions of the three lanes in the space-time diagram.
+const SENDER_Y: f64 = 0.0;
+const CHANNEL_Y: f64 = 1.0;
+const RECEIVER_Y: f64 = 2.0;
+
+pub struct GuiApp {
+    simulator: Simulator,
+    scenario_name: Option<String>,
+    paused: bool,
+    /// Simulator events processed per wall-clock second while running.
+    events_per_second: f32,
+    /// Fractional steps carried over between frames.
+    step_budget: f32,
+    /// Index into `simulator.link_events` shown in the inspector.
+    selected: Option<usize>,
+}
+
+impl GuiApp {
+    pub fn new(simulator: Simulator, scenario_name: Option<String>) -> Self {
+        Self {
+            simulator,
+            scenario_name,
+            paused: true, // Start paused
+            events_per_second: 10.0,
+            step_budget: 0.0,
+            selected: None,
+        }
+    }
+
+    /// Open the window and block until it is closed.
+    pub fn run(&mut self) -> anyhow::Result<()> {
+        self.simulator.init();
+
+        let title = format!(
+            "TCP Lab — {}",
+            self.scenario_name.as_deref().unwrap_or("Ad-hoc Simulation")
+        );
+        let options = eframe::NativeOptions {
+            viewport: egui::ViewportBuilder::default().with_inner_size([1280.0, 800.0]),
+            ..Default::default()
+        };
+        eframe::run_native(
+            &title,
+            options,
+            Box::new(|_cc| Ok(Box::new(GuiView { app: self }))),
+        )
+        .map_err(|e| anyhow!("GUI visualizer failed: {}", e))
+    }
+
+    pub fn into_simulator(self) -> Simulator {
+        self.simulator
+    }
+
+    fn advance(&mut self, dt: f32) {
+        if self.paused {
+            return;
+        }
+        self.step_budget += dt * self.events_per_second;
+        while self.step_budget >= 1.0 {
+            self.step_budget -= 1.0;
+            let notes = self.simulator.annotations.len();
+            if !self.simulator.step() {
+                // Simulation finished
+                self.paused = true;
+                self.step_budget = 0.0;
+                break;
+            }
+            if self.simulator.annotations.len() > notes {
+                // Stop on each annotation so it can be read before the run moves on.
+                self.paused = true;
+                self.step_budget = 0.0;
+                break;
+            }
+        }
+    }
+
+    fn render_control(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let scenario = self.scenario_name.as_deref().unwrap_or("Ad-hoc Simulation");
+            ui.strong(scenario);
+            ui.separator();
+            let label = if self.paused { "▶ Run" } else { "⏸ Pause" };
+            if ui.button(label).clicked() {
+                self.paused = !self.paused;
+            }
+            if ui.button("Step").clicked() {
+                self.simulator.step();
+            }
+            if ui.button("Run to end").clicked() {
+                while self.simulator.step() {}
+                self.paused = true;
+            }
+            ui.add(
+                egui::Slider::new(&mut self.events_per_second, 1.0..=1000.0)
+                    .logarithmic(true)
+                    .text("events/s"),
+            );
+            ui.separator();
+            ui.label(format!(
+                "Time: {} ms | Status: {} | Events Pending: {}",
+                self.simulator.current_time(),
+                if self.paused { "PAUSED" } else { "RUNNING" },
+                self.simulator.remaining_events()
+            ));
+        });
+        if let Some(note) = self.simulator.latest_annotation() {
+            ui.horizontal(|ui| {
+                ui.colored_label(
+                    Color32::from_rgb(120, 200, 255),
+                    egui::RichText::new(format!("Note @ {} ms: {}", note.time, note.text))
+                        .strong()
+                        .size(16.0),
+                );
+            });
+        }
+    }
+
+    fn render_dashboard(&self, ui: &mut egui::Ui) {
+        let sim = &self.simulator;
+        let cfg = sim.config();
+        let win_current = sim.sender_window_sizes.last().copied().unwrap_or(0);
+        let win_max = sim.sender_window_sizes.iter().copied().max().unwrap_or(0);
+
+        ui.heading("Dashboard");
+        egui::Grid::new("stats").num_columns(2).show(ui, |ui| {
+            ui.label("Delivered messages");
+            ui.label(sim.delivered_data.len().to_string());
+            ui.end_row();
+            ui.label("Sender packets");
+            ui.label(sim.sender_packet_count.to_string());
+            ui.end_row();
+            ui.label("Wire bytes");
+            ui.label(format!(
+                "{} (efficiency {:.1}%)",
+                sim.traffic.wire_bytes(),
+                sim.traffic.efficiency() * 100.0
+            ));
+            ui.end_row();
+            ui.label("Sender window");
+            ui.label(format!("current={} max={}", win_current, win_max));
+            ui.end_row();
+            ui.label("Rejected actions");
+            ui.label(sim.rejected_actions.len().to_string());
+            ui.end_row();
+            ui.label("Channel");
+            ui.label(format!(
+                "loss={:.2} corrupt={:.2} latency={}..{} ms",
+                cfg.loss_rate, cfg.corrupt_rate, cfg.min_latency, cfg.max_latency
+            ));
+            ui.end_row();
+        });
+    }
+
+    /// Each node's `record_state` timeline, the current state highlighted.
+    fn render_states(&self, ui: &mut egui::Ui) {
+        ui.heading("States");
+        let log = &self.simulator.state_log;
+        if log.is_empty() {
+            ui.label("No states recorded");
+            return;
+        }
+        egui::Grid::new("states").num_columns(2).show(ui, |ui| {
+            for node in [NodeId::Sender, NodeId::Receiver] {
+                ui.label(format!("{:?}", node));
+                ui.horizontal_wrapped(|ui| {
+                    let mut transitions = log.iter().filter(|t| t.node == node).peekable();
+                    while let Some(t) = transitions.next() {
+                        let text = format!("{} @{}", t.to, t.time);
+                        if transitions.peek().is_some() {
+                            ui.label(text);
+                            ui.label("→");
+                        } else {
+                            ui.colored_label(Color32::LIGHT_GREEN, text);
+                        }
+                    }
+                });
+                ui.end_row();
+            }
+        });
+    }
+
+    fn render_window_history(&self, ui: &mut egui::Ui) {
+        ui.heading("Window");
+        let sim = &self.simulator;
+        let cwnd: Option<Vec<[f64; 2]>> = sim
+            .chart_series("cwnd")
+            .filter(|s| !s.is_empty())
+            .map(|s| s.iter().map(|(t, v)| [*t as f64, *v]).collect());
+        let ssthresh: Option<Vec<[f64; 2]>> = sim
+            .chart_series("ssthresh")
+            .filter(|s| !s.is_empty())
+            .map(|s| s.iter().map(|(t, v)| [*t as f64, *v]).collect());
+
+        // Without a cwnd metric, fall back to advertised windows in send order.
+        let (x_label, cwnd) = match cwnd {
+            Some(points) => ("time (ms)", points),
+            None => (
+                "packet #",
+                downsample(
+                    &sim.sender_window_sizes
+                        .iter()
+                        .enumerate()
+                        .map(|(i, w)| (i as u64, *w as f64))
+                        .collect::<Vec<_>>(),
+                    sim.config().chart_max_points,
+                )
+                .iter()
+                .map(|(i, w)| [*i as f64, *w])
+                .collect(),
+            ),
+        };
+
+        Plot::new("window_history")
+            .legend(Legend::default())
+            .x_axis_label(x_label)
+            .height(ui.available_height())
+            .show(ui, |plot_ui| {
+                plot_ui.line(Line::new("cwnd", PlotPoints::new(cwnd)).color(Color32::LIGHT_GREEN));
+                if let Some(points) = ssthresh {
+                    plot_ui
+                        .line(Line::new("ssthresh", PlotPoints::new(points)).color(Color32::GRAY));
+                }
+            });
+    }
+
+    fn render_link_space_time(&mut self, ui: &mut egui::Ui) {
+        let events = &self.simulator.link_events;
+        if events.is_empty() {
+            ui.label("No link activity yet");
+            return;
+        }
+
+        let response = Plot::new("space_time")
+            .x_axis_label("time (ms)")
+            .y_axis_formatter(|mark, _| lane_name(mark.value).to_string())
+            .include_y(SENDER_Y - 0.5)
+            .include_y(RECEIVER_Y + 0.5)
+            .allow_zoom([true, false])
+            .allow_scroll([true, false])
+            .allow_drag([true, false])
+            .show_grid([true, false])
+            .label_formatter(|_| None)
+            .show(ui, |plot_ui| {
+                let (t_min, t_max) = (
+                    events.first().map(|e| e.time as f64).unwrap_or(0.0),
+                    self.simulator.current_time().max(1) as f64,
+                );
+                for (y, color) in [
+                    (SENDER_Y, Color32::LIGHT_BLUE),
+                    (CHANNEL_Y, Color32::DARK_GRAY),
+                    (RECEIVER_Y, Color32::YELLOW),
+                ] {
+                    plot_ui.line(
+                        Line::new("", PlotPoints::new(vec![[t_min, y], [t_max, y]]))
+                            .color(color)
+                            .allow_hover(false),
+                    );
+                }
+
+                for (idx, e) in events.iter().enumerate() {
+                    let t0 = e.time as f64;
+                    let highlight = self.selected == Some(idx);
+                    let (kind, color) = event_kind(e);
+                    match kind {
+                        "SEND" | "PROBE" => {
+                            let (y_src, y_dst) = lanes(e.direction());
+                            let t1 = t0 + e.latency().unwrap_or(1) as f64;
+                            plot_ui.line(
+                                Line::new("", PlotPoints::new(vec![[t0, y_src], [t1, y_dst]]))
+                                    .color(color)
+                                    .width(if highlight { 3.0 } else { 1.0 })
+                                    .allow_hover(false),
+                            );
+                        }
+                        "NOTE" => {
+                            plot_ui.line(
+                                Line::new(
+                                    "",
+                                    PlotPoints::new(vec![
+                                        [t0, SENDER_Y - 0.4],
+                                        [t0, RECEIVER_Y + 0.4],
+                                    ]),
+                                )
+                                .color(color)
+                                .width(if highlight { 3.0 } else { 1.5 })
+                                .allow_hover(false),
+                            );
+                        }
+                        "DROP" | "CORRUPT" => {
+                            let shape = if kind == "DROP" {
+                                MarkerShape::Cross
+                            } else {
+                                MarkerShape::Diamond
+                            };
+                            plot_ui.points(
+                                Points::new("", vec![[t0, CHANNEL_Y]])
+                                    .shape(shape)
+                                    .color(color)
+                                    .radius(if highlight { 7.0 } else { 4.0 })
+                                    .allow_hover(false),
+                            );
+                        }
+                        _ => {
+                            let y = if e.description.starts_with("[Sender]")
+                                || e.description.starts_with("[Host->Sender]")
+                            {
+                                SENDER_Y
+                            } else {
+                                RECEIVER_Y
+                            };
+                            plot_ui.points(
+                                Points::new("", vec![[t0, y]])
+                                    .color(color)
+                                    .radius(if highlight { 5.0 } else { 2.5 })
+                                    .allow_hover(false),
+                            );
+                        }
+                    }
+                }
+                plot_ui.pointer_coordinate()
+            });
+
+        // Tooltip and selection for the event nearest to the pointer.
+        let Some(pointer) = response.inner else {
+            return;
+        };
+        let ms_per_px = response.transform.dvalue_dpos()[0].abs();
+        let nearest = events
+            .iter()
+            .enumerate()
+            .map(|(idx, e)| (idx, (e.time as f64 - pointer.x).abs()))
+            .filter(|(_, dist)| *dist <= ms_per_px * 6.0)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(idx, _)| idx);
+        if let Some(idx) = nearest {
+            let e = &events[idx];
+            let tooltip = format!("[{} ms] {}", e.time, e.description);
+            if response.response.clicked() {
+                self.selected = Some(idx);
+            }
+            response.response.on_hover_text(tooltip);
+        }
+    }
+
+    fn render_inspector(&self, ui: &mut egui::Ui) {
+        ui.heading("Packet Inspector");
+        let Some(e) = self
+            .selected
+            .and_then(|i| self.simulator.link_events.get(i))
+        else {
+            ui.label("Click an event in the diagram or list to inspect it.");
+            return;
+        };
+        let (kind, color) = event_kind(e);
+        egui::Grid::new("inspector").num_columns(2).show(ui, |ui| {
+            ui.label("Event");
+            ui.label(RichText::new(kind).color(color).strong());
+            ui.end_row();
+            ui.label("Time");
+            ui.label(format!("{} ms", e.time));
+            ui.end_row();
+            ui.label("Direction");
+            ui.label(match e.direction() {
+                LinkDirection::SenderToReceiver => "Sender → Receiver",
+                LinkDirection::ReceiverToSender => "Receiver → Sender",
+                LinkDirection::Unknown => "—",
+            });
+            ui.end_row();
+            if let Some(packet) = e.packet {
+                for (label, value) in [("Seq", packet.seq), ("Ack", packet.ack)] {
+                    ui.label(label);
+                    ui.label(value.to_string());
+                    ui.end_row();
+                }
+            }
+            if let Some(latency) = e.latency() {
+                ui.label("Latency");
+                ui.label(format!(
+                    "{} ms (arrives at {} ms)",
+                    latency,
+                    e.time + latency
+                ));
+                ui.end_row();
+            }
+        });
+        ui.separator();
+        ui.label(RichText::new(&e.description).monospace());
+    }
+
+    fn render_link_events(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Link Events");
+        let events = &self.simulator.link_events;
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .stick_to_bottom(true)
+            .show_rows(ui, row_height, events.len(), |ui, range| {
+                for idx in range {
+                    let e = &events[idx];
+                    let (_, color) = event_kind(e);
+                    let text = RichText::new(format!("[{:>5} ms] {}", e.time, e.description))
+                        .monospace()
+                        .color(color);
+                    if ui
+                        .selectable_label(self.selected == Some(idx), text)
+                        .clicked()
+                    {
+                        self.selected = Some(idx);
+                    }
+                }
+            });
+    }
+}
+
+/// Borrows the app for the lifetime of the native event loop so the caller
+/// gets the simulator back once the window closes.
+struct GuiView<'a> {
+    app: &'a mut GuiApp,
+}
+
+impl eframe::App for GuiView<'_> {
+    fn ui(&mut self, ui: &mut egui::Ui, _frame: &mut eframe::Frame) {
+        let dt = ui.input(|i| i.stable_dt);
+        self.app.advance(dt);
+        if !self.app.paused {
+            ui.ctx().request_repaint();
+        }
+
+        egui::Panel::top("control").show(ui, |ui| self.app.render_control(ui));
+        egui::Panel::bottom("link_events")
+            .resizable(true)
+            .default_size(200.0)
+            .show(ui, |ui| self.app.render_link_events(ui));
+        egui::Panel::right("side")
+            .resizable(true)
+            .default_size(380.0)
+            .show(ui, |ui| {
+                self.app.render_dashboard(ui);
+                ui.separator();
+                self.app.render_states(ui);
+                ui.separator();
+                self.app.render_inspector(ui);
+                ui.separator();
+                self.app.render_window_history(ui);
+            });
+        egui::CentralPanel::default().show(ui, |ui| self.app.render_link_space_time(ui));
+    }
+}
+
+/// Short kind label and display color for a link event.
+fn event_kind(e: &LinkEventSummary) -> (&'static str, Color32) {
+    match e.kind {
+        LinkEventKind::Rejected => ("REJECTED", Color32::from_rgb(255, 140, 0)),
+        LinkEventKind::IntegrityViolation => ("INTEGRITY", Color32::from_rgb(255, 140, 0)),
+        LinkEventKind::Drop | LinkEventKind::InjectedDrop => ("DROP", Color32::RED),
+        LinkEventKind::Policed => ("POLICED", Color32::from_rgb(255, 80, 80)),
+        LinkEventKind::QueueDrop => ("QUEUE DROP", Color32::from_rgb(255, 80, 80)),
+        LinkEventKind::Mark => ("MARK", Color32::from_rgb(255, 200, 80)),
+        LinkEventKind::Corrupt | LinkEventKind::InjectedCorrupt => ("CORRUPT", Color32::YELLOW),
+        LinkEventKind::Truncate => ("TRUNCATE", Color32::YELLOW),
+        LinkEventKind::TamperedDuplicate => ("TAMPERED", Color32::YELLOW),
+        LinkEventKind::Deliver => ("DELIVERED", Color32::GREEN),
+        LinkEventKind::Send => ("SEND", Color32::WHITE),
+        LinkEventKind::Probe => ("PROBE", Color32::LIGHT_BLUE),
+        LinkEventKind::Suppressed => ("EVENT", Color32::LIGHT_GRAY),
+        LinkEventKind::Restart => ("RESTART", Color32::from_rgb(200, 120, 255)),
+        LinkEventKind::Close => ("CLOSE", Color32::from_rgb(200, 120, 255)),
+        LinkEventKind::AppFlow => ("APP", Color32::from_rgb(200, 120, 255)),
+        LinkEventKind::SendStorm => ("STORM", Color32::from_rgb(255, 140, 0)),
+        LinkEventKind::Annotation => ("NOTE", Color32::from_rgb(120, 200, 255)),
+        LinkEventKind::Injected => ("INJECT", Color32::from_rgb(255, 105, 180)),
+        LinkEventKind::AckCompressed => ("COMPRESSED", Color32::from_rgb(200, 180, 120)),
+        LinkEventKind::Rewrite => ("REWRITE", Color32::from_rgb(180, 160, 255)),
+        LinkEventKind::LatencyChange => ("LATENCY", Color32::from_rgb(120, 200, 255)),
+    }
+}
+
+fn lanes(direction: LinkDirection) -> (f64, f64) {
+    match direction {
+        LinkDirection::ReceiverToSender => (RECEIVER_Y, SENDER_Y),
+        LinkDirection::SenderToReceiver | LinkDirection::Unknown => (SENDER_Y, RECEIVER_Y),
+    }
+}
+
+fn lane_name(y: f64) -> &'static str {
+    if (y - SENDER_Y).abs() < f64::EPSILON {
+        "Sender"
+    } else if (y - CHANNEL_Y).abs() < f64::EPSILON {
+        "Channel"
+    } else if (y - RECEIVER_Y).abs() < f64::EPSILON {
+        "Receiver"
+    } else {
+        ""
+    }
+}
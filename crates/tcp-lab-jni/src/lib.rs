@@ -1,10 +1,14 @@
 use jni::JNIEnv;
-use jni::objects::{JByteArray, JClass, JObject, JString, JValue};
-use jni::sys::{jbyte, jbyteArray, jdouble, jint, jlong};
+use jni::objects::{JByteArray, JClass, JObject, JObjectArray, JString, JValue};
+use jni::sys::{jbyte, jdouble, jint, jlong};
 use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::sync::Arc;
-use tcp_lab_abstract::{Packet, SystemContext, TcpHeader, TransportProtocol};
-use tracing::error;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tcp_lab_abstract::{
+    BridgeWarning, Packet, PendingWarnings, SystemContext, TcpHeader, TransportProtocol,
+};
+use tracing::debug_span;
 
 // ==========================================
 // TLS Context Management
@@ -15,10 +19,18 @@ thread_local! {
     static CURRENT_CONTEXT_PTR: RefCell<usize> = const { RefCell::new(0) };
 }
 
+/// Simulated time of the latest callback, what the patched `System` clocks return.
+static SIM_NOW_MS: AtomicU64 = AtomicU64::new(0);
+
+/// Warnings raised while no context was installed, reported at the next callback.
+static PENDING_WARNINGS: PendingWarnings = PendingWarnings::new();
+
 fn with_context<F, R>(ctx: &mut dyn SystemContext, f: F) -> R
 where
     F: FnOnce() -> R,
 {
+    SIM_NOW_MS.store(ctx.now(), Ordering::Relaxed);
+    PENDING_WARNINGS.flush(ctx);
     let ptr = ctx as *mut dyn SystemContext;
     let static_ptr: *mut (dyn SystemContext + 'static) = unsafe { std::mem::transmute(ptr) };
 
@@ -47,11 +59,28 @@ where
             let ctx = unsafe { &mut *ptr };
             f(ctx);
         } else {
-            error!("Java called native method without active SystemContext!");
+            PENDING_WARNINGS.push(
+                BridgeWarning::NoContext,
+                "a NativeBridge method was called outside a callback".to_string(),
+            );
         }
     });
 }
 
+/// Drop warnings held from an earlier run, before a new one starts.
+pub fn clear_pending_warnings() {
+    PENDING_WARNINGS.clear();
+}
+
+/// Report a call the bridge had to ignore or patch up. Outside a callback there is no
+/// context to report to, so it waits for the next one.
+fn warn(kind: BridgeWarning, detail: String) {
+    CURRENT_CONTEXT.with(|c| match *c.borrow() {
+        Some(ptr) => unsafe { &mut *ptr }.report_warning(kind, &detail),
+        None => PENDING_WARNINGS.push(kind, detail),
+    });
+}
+
 // ==========================================
 // Native Methods Implementation
 // ==========================================
@@ -60,31 +89,37 @@ where
 pub extern "system" fn Java_com_ouc_tcp_sdk_NativeBridge_sendPacket(
     env: JNIEnv,
     _class: JClass,
+    src_port: jint,
+    dst_port: jint,
     seq: jlong,
     ack: jlong,
     flags: jbyte,
     window: jint,
     checksum: jint,
     urgent: jint,
-    payload: jbyteArray,
+    payload: JByteArray,
 ) {
-    let payload_vec = match env.convert_byte_array(unsafe { JByteArray::from_raw(payload) }) {
+    let payload_vec = match env.convert_byte_array(payload) {
         Ok(v) => v,
         Err(e) => {
-            error!("Failed to convert byte array: {:?}", e);
+            warn(
+                BridgeWarning::ConversionFailed,
+                format!("failed to convert a byte array: {:?}", e),
+            );
             return;
         }
     };
 
     use_context(|ctx| {
         let header = TcpHeader {
+            src_port: src_port as u16,
+            dst_port: dst_port as u16,
             seq_num: seq as u32,
             ack_num: ack as u32,
             flags: flags as u8,
             window_size: window as u16,
             checksum: checksum as u16,
             urgent_ptr: urgent as u16,
-            ..Default::default()
         };
         let packet = Packet::new(header, payload_vec);
         ctx.send_packet(packet);
@@ -118,12 +153,15 @@ pub extern "system" fn Java_com_ouc_tcp_sdk_NativeBridge_cancelTimer(
 pub extern "system" fn Java_com_ouc_tcp_sdk_NativeBridge_deliverData(
     env: JNIEnv,
     _class: JClass,
-    data: jbyteArray,
+    data: JByteArray,
 ) {
-    let data_vec = match env.convert_byte_array(unsafe { JByteArray::from_raw(data) }) {
+    let data_vec = match env.convert_byte_array(data) {
         Ok(v) => v,
         Err(e) => {
-            error!("Failed to convert byte array: {:?}", e);
+            warn(
+                BridgeWarning::ConversionFailed,
+                format!("failed to convert a byte array: {:?}", e),
+            );
             return;
         }
     };
@@ -133,6 +171,29 @@ pub extern "system" fn Java_com_ouc_tcp_sdk_NativeBridge_deliverData(
     });
 }
 
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_ouc_tcp_sdk_NativeBridge_deliverDataAt(
+    env: JNIEnv,
+    _class: JClass,
+    offset: jlong,
+    data: JByteArray,
+) {
+    let data_vec = match env.convert_byte_array(data) {
+        Ok(v) => v,
+        Err(e) => {
+            warn(
+                BridgeWarning::ConversionFailed,
+                format!("failed to convert a byte array: {:?}", e),
+            );
+            return;
+        }
+    };
+
+    use_context(|ctx| {
+        ctx.deliver_data_at(offset as u64, &data_vec);
+    });
+}
+
 #[unsafe(no_mangle)]
 pub extern "system" fn Java_com_ouc_tcp_sdk_NativeBridge_log(
     mut env: JNIEnv,
@@ -141,7 +202,13 @@ pub extern "system" fn Java_com_ouc_tcp_sdk_NativeBridge_log(
 ) {
     let msg_str: String = match env.get_string(&msg) {
         Ok(s) => s.into(),
-        Err(_) => "Invalid UTF-8 string in log".into(),
+        Err(_) => {
+            warn(
+                BridgeWarning::InvalidUtf8,
+                "log message is not valid UTF-8".to_string(),
+            );
+            "Invalid UTF-8 string in log".into()
+        }
     };
 
     use_context(|ctx| {
@@ -149,6 +216,22 @@ pub extern "system" fn Java_com_ouc_tcp_sdk_NativeBridge_log(
     });
 }
 
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_ouc_tcp_sdk_NativeBridge_reportViolation(
+    mut env: JNIEnv,
+    _class: JClass,
+    what: JString,
+) {
+    let what: String = match env.get_string(&what) {
+        Ok(s) => s.into(),
+        Err(_) => "a forbidden operation".into(),
+    };
+
+    use_context(|ctx| {
+        ctx.report_violation(&what);
+    });
+}
+
 #[unsafe(no_mangle)]
 pub extern "system" fn Java_com_ouc_tcp_sdk_NativeBridge_recordMetric(
     mut env: JNIEnv,
@@ -159,7 +242,10 @@ pub extern "system" fn Java_com_ouc_tcp_sdk_NativeBridge_recordMetric(
     let name_str: String = match env.get_string(&name) {
         Ok(s) => s.into(),
         Err(_) => {
-            error!("Invalid UTF-8 in metric name");
+            warn(
+                BridgeWarning::InvalidUtf8,
+                "metric name is not valid UTF-8".to_string(),
+            );
             return;
         }
     };
@@ -169,18 +255,231 @@ pub extern "system" fn Java_com_ouc_tcp_sdk_NativeBridge_recordMetric(
     });
 }
 
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_ouc_tcp_sdk_NativeBridge_recordState(
+    mut env: JNIEnv,
+    _class: JClass,
+    name: JString,
+) {
+    let name_str: String = match env.get_string(&name) {
+        Ok(s) => s.into(),
+        Err(_) => {
+            warn(
+                BridgeWarning::InvalidUtf8,
+                "state name is not valid UTF-8".to_string(),
+            );
+            return;
+        }
+    };
+
+    use_context(|ctx| {
+        ctx.record_state(&name_str);
+    });
+}
+
 #[unsafe(no_mangle)]
 pub extern "system" fn Java_com_ouc_tcp_sdk_NativeBridge_now(
     _env: JNIEnv,
     _class: JClass,
 ) -> jlong {
-    let mut time = 0;
+    // Without a context, the time of the latest callback, rather than 0, keeps the
+    // clock the protocol sees from jumping back to the start of the run.
+    let mut time = SIM_NOW_MS.load(Ordering::Relaxed) as i64;
     use_context(|ctx| {
         time = ctx.now() as i64;
     });
     time
 }
 
+/// Free receive buffer in bytes, or -1 when none is modeled.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_ouc_tcp_sdk_NativeBridge_receiveWindow(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jlong {
+    let mut window = -1;
+    use_context(|ctx| {
+        window = ctx.receive_window().map_or(-1, |free| free as i64);
+    });
+    window
+}
+
+/// MSS the peer advertised in its SYN, or -1 before that SYN arrives (or without one).
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_ouc_tcp_sdk_NativeBridge_peerMss(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jint {
+    let mut mss = -1;
+    use_context(|ctx| {
+        mss = ctx.peer_mss().map_or(-1, jint::from);
+    });
+    mss
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_ouc_tcp_sdk_NativeBridge_store(
+    mut env: JNIEnv,
+    _class: JClass,
+    key: JString,
+    value: JString,
+) {
+    let (Ok(key), Ok(value)) = (env.get_string(&key), env.get_string(&value)) else {
+        warn(
+            BridgeWarning::InvalidUtf8,
+            "scratch store key or value is not valid UTF-8".to_string(),
+        );
+        return;
+    };
+    let (key, value): (String, String) = (key.into(), value.into());
+    use_context(|ctx| {
+        ctx.store(&key, &value);
+    });
+}
+
+/// The value stored under `key`, or null when nothing is.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_ouc_tcp_sdk_NativeBridge_load<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    key: JString<'local>,
+) -> JString<'local> {
+    let key: String = match env.get_string(&key) {
+        Ok(s) => s.into(),
+        Err(_) => {
+            warn(
+                BridgeWarning::InvalidUtf8,
+                "scratch store key is not valid UTF-8".to_string(),
+            );
+            return JString::default();
+        }
+    };
+    let mut value = None;
+    use_context(|ctx| {
+        value = ctx.load(&key);
+    });
+    let Some(value) = value else {
+        return JString::default();
+    };
+    env.new_string(value).unwrap_or_else(|e| {
+        warn(
+            BridgeWarning::ConversionFailed,
+            format!("failed to build the loaded string: {:?}", e),
+        );
+        JString::default()
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_ouc_tcp_sdk_NativeBridge_annotatePacket(
+    mut env: JNIEnv,
+    _class: JClass,
+    note: JString,
+) {
+    let note: String = match env.get_string(&note) {
+        Ok(s) => s.into(),
+        Err(_) => {
+            warn(
+                BridgeWarning::InvalidUtf8,
+                "packet note is not valid UTF-8".to_string(),
+            );
+            return;
+        }
+    };
+    use_context(|ctx| {
+        ctx.annotate_packet(&note);
+    });
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_ouc_tcp_sdk_NativeBridge_appPause(_env: JNIEnv, _class: JClass) {
+    use_context(|ctx| {
+        ctx.app_pause();
+    });
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_ouc_tcp_sdk_NativeBridge_appResume(_env: JNIEnv, _class: JClass) {
+    use_context(|ctx| {
+        ctx.app_resume();
+    });
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_ouc_tcp_sdk_NativeBridge_refuseAppData(
+    mut env: JNIEnv,
+    _class: JClass,
+    reason: JString,
+) {
+    let reason: String = match env.get_string(&reason) {
+        Ok(s) => s.into(),
+        Err(_) => {
+            warn(
+                BridgeWarning::InvalidUtf8,
+                "refusal reason is not valid UTF-8".to_string(),
+            );
+            return;
+        }
+    };
+    use_context(|ctx| {
+        ctx.refuse_app_data(&reason);
+    });
+}
+
+/// Returns the node's init params as a flat `[key, value, key, value, ...]` array,
+/// which `SystemContextImpl.initParams()` turns into a map.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_ouc_tcp_sdk_NativeBridge_initParams<'local>(
+    env: JNIEnv<'local>,
+    _class: JClass<'local>,
+) -> JObjectArray<'local> {
+    let mut params = BTreeMap::new();
+    use_context(|ctx| {
+        params = ctx.init_params();
+    });
+    string_pairs(env, &params)
+}
+
+/// Returns the simulation settings flattened like `initParams`.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_ouc_tcp_sdk_NativeBridge_config<'local>(
+    env: JNIEnv<'local>,
+    _class: JClass<'local>,
+) -> JObjectArray<'local> {
+    let mut params = BTreeMap::new();
+    use_context(|ctx| {
+        params = ctx.config().to_params();
+    });
+    string_pairs(env, &params)
+}
+
+fn string_pairs<'local>(
+    mut env: JNIEnv<'local>,
+    pairs: &BTreeMap<String, String>,
+) -> JObjectArray<'local> {
+    let array = (|| {
+        let array = env.new_object_array(
+            (pairs.len() * 2) as i32,
+            "java/lang/String",
+            JObject::null(),
+        )?;
+        for (i, (key, value)) in pairs.iter().enumerate() {
+            let key = env.new_string(key)?;
+            env.set_object_array_element(&array, (i * 2) as i32, key)?;
+            let value = env.new_string(value)?;
+            env.set_object_array_element(&array, (i * 2 + 1) as i32, value)?;
+        }
+        jni::errors::Result::Ok(array)
+    })();
+    array.unwrap_or_else(|e| {
+        warn(
+            BridgeWarning::ConversionFailed,
+            format!("failed to build a string pair array: {:?}", e),
+        );
+        JObjectArray::default()
+    })
+}
+
 // ==========================================
 // Native Registration
 // ==========================================
@@ -190,7 +489,7 @@ pub fn register_native_methods(env: &mut JNIEnv) -> jni::errors::Result<()> {
     let methods = [
         jni::NativeMethod {
             name: "sendPacket".into(),
-            sig: "(JJBIII[B)V".into(),
+            sig: "(IIJJBIII[B)V".into(),
             fn_ptr: Java_com_ouc_tcp_sdk_NativeBridge_sendPacket as *mut _,
         },
         jni::NativeMethod {
@@ -208,6 +507,11 @@ pub fn register_native_methods(env: &mut JNIEnv) -> jni::errors::Result<()> {
             sig: "([B)V".into(),
             fn_ptr: Java_com_ouc_tcp_sdk_NativeBridge_deliverData as *mut _,
         },
+        jni::NativeMethod {
+            name: "deliverDataAt".into(),
+            sig: "(J[B)V".into(),
+            fn_ptr: Java_com_ouc_tcp_sdk_NativeBridge_deliverDataAt as *mut _,
+        },
         jni::NativeMethod {
             name: "log".into(),
             sig: "(Ljava/lang/String;)V".into(),
@@ -223,6 +527,114 @@ pub fn register_native_methods(env: &mut JNIEnv) -> jni::errors::Result<()> {
             sig: "(Ljava/lang/String;D)V".into(),
             fn_ptr: Java_com_ouc_tcp_sdk_NativeBridge_recordMetric as *mut _,
         },
+        jni::NativeMethod {
+            name: "recordState".into(),
+            sig: "(Ljava/lang/String;)V".into(),
+            fn_ptr: Java_com_ouc_tcp_sdk_NativeBridge_recordState as *mut _,
+        },
+        jni::NativeMethod {
+            name: "initParams".into(),
+            sig: "()[Ljava/lang/String;".into(),
+            fn_ptr: Java_com_ouc_tcp_sdk_NativeBridge_initParams as *mut _,
+        },
+        jni::NativeMethod {
+            name: "receiveWindow".into(),
+            sig: "()J".into(),
+            fn_ptr: Java_com_ouc_tcp_sdk_NativeBridge_receiveWindow as *mut _,
+        },
+        jni::NativeMethod {
+            name: "peerMss".into(),
+            sig: "()I".into(),
+            fn_ptr: Java_com_ouc_tcp_sdk_NativeBridge_peerMss as *mut _,
+        },
+        jni::NativeMethod {
+            name: "config".into(),
+            sig: "()[Ljava/lang/String;".into(),
+            fn_ptr: Java_com_ouc_tcp_sdk_NativeBridge_config as *mut _,
+        },
+    ];
+    env.register_native_methods(&class, &methods)?;
+
+    // Registered on its own so an SDK jar from before the grading sandbox still loads.
+    let sandbox = [jni::NativeMethod {
+        name: "reportViolation".into(),
+        sig: "(Ljava/lang/String;)V".into(),
+        fn_ptr: Java_com_ouc_tcp_sdk_NativeBridge_reportViolation as *mut _,
+    }];
+    if env.register_native_methods(&class, &sandbox).is_err() {
+        env.exception_clear()?;
+    }
+    // Likewise for SDK jars from before the scratch store.
+    let scratch = [
+        jni::NativeMethod {
+            name: "store".into(),
+            sig: "(Ljava/lang/String;Ljava/lang/String;)V".into(),
+            fn_ptr: Java_com_ouc_tcp_sdk_NativeBridge_store as *mut _,
+        },
+        jni::NativeMethod {
+            name: "load".into(),
+            sig: "(Ljava/lang/String;)Ljava/lang/String;".into(),
+            fn_ptr: Java_com_ouc_tcp_sdk_NativeBridge_load as *mut _,
+        },
+    ];
+    if env.register_native_methods(&class, &scratch).is_err() {
+        env.exception_clear()?;
+    }
+    let annotate = [jni::NativeMethod {
+        name: "annotatePacket".into(),
+        sig: "(Ljava/lang/String;)V".into(),
+        fn_ptr: Java_com_ouc_tcp_sdk_NativeBridge_annotatePacket as *mut _,
+    }];
+    if env.register_native_methods(&class, &annotate).is_err() {
+        env.exception_clear()?;
+    }
+    let app_flow = [
+        jni::NativeMethod {
+            name: "appPause".into(),
+            sig: "()V".into(),
+            fn_ptr: Java_com_ouc_tcp_sdk_NativeBridge_appPause as *mut _,
+        },
+        jni::NativeMethod {
+            name: "appResume".into(),
+            sig: "()V".into(),
+            fn_ptr: Java_com_ouc_tcp_sdk_NativeBridge_appResume as *mut _,
+        },
+        jni::NativeMethod {
+            name: "refuseAppData".into(),
+            sig: "(Ljava/lang/String;)V".into(),
+            fn_ptr: Java_com_ouc_tcp_sdk_NativeBridge_refuseAppData as *mut _,
+        },
+    ];
+    if env.register_native_methods(&class, &app_flow).is_err() {
+        env.exception_clear()?;
+    }
+    Ok(())
+}
+
+extern "system" fn sim_current_time_millis(_env: JNIEnv, _class: JClass) -> jlong {
+    SIM_NOW_MS.load(Ordering::Relaxed) as jlong
+}
+
+extern "system" fn sim_nano_time(_env: JNIEnv, _class: JClass) -> jlong {
+    SIM_NOW_MS.load(Ordering::Relaxed).saturating_mul(1_000_000) as jlong
+}
+
+/// Rebind `System.currentTimeMillis` and `System.nanoTime` to the simulated clock, which
+/// stands still within a callback. Only takes effect when their JIT intrinsics are disabled
+/// (`-XX:DisableIntrinsic=_currentTimeMillis,_nanoTime`).
+pub fn register_sim_clock(env: &mut JNIEnv) -> jni::errors::Result<()> {
+    let class = env.find_class("java/lang/System")?;
+    let methods = [
+        jni::NativeMethod {
+            name: "currentTimeMillis".into(),
+            sig: "()J".into(),
+            fn_ptr: sim_current_time_millis as *mut _,
+        },
+        jni::NativeMethod {
+            name: "nanoTime".into(),
+            sig: "()J".into(),
+            fn_ptr: sim_nano_time as *mut _,
+        },
     ];
     env.register_native_methods(class, &methods)
 }
@@ -260,14 +672,18 @@ impl JavaTransportProtocol {
         }
     }
 
-    fn call_java<F>(&mut self, ctx: &mut dyn SystemContext, op: F)
+    fn call_java<F>(&mut self, ctx: &mut dyn SystemContext, callback: &'static str, op: F)
     where
         F: FnOnce(&mut JNIEnv, &JObject, &JObject) -> jni::errors::Result<()>,
     {
+        let _span = debug_span!("bridge_call", language = "java", callback).entered();
         let mut env = match self.jvm.attach_current_thread() {
             Ok(e) => e,
             Err(e) => {
-                error!("Failed to attach JNI thread: {:?}", e);
+                ctx.report_warning(
+                    BridgeWarning::CallFailed,
+                    &format!("{callback}: failed to attach the JNI thread: {:?}", e),
+                );
                 return;
             }
         };
@@ -277,7 +693,10 @@ impl JavaTransportProtocol {
             let ctx_obj = self.context_impl.as_ref().unwrap().as_obj();
 
             if let Err(e) = op(&mut env, obj, ctx_obj) {
-                error!("Java exception or JNI error: {:?}", e);
+                warn(
+                    BridgeWarning::CallFailed,
+                    format!("{callback}: Java exception or JNI error: {:?}", e),
+                );
                 if env.exception_check().unwrap_or(false) {
                     env.exception_describe().unwrap_or(());
                     env.exception_clear().unwrap_or(());
@@ -287,6 +706,22 @@ impl JavaTransportProtocol {
     }
 }
 
+/// The checksum a Java hook returned, `None` for the default -1 or (with a warning) any
+/// other value that does not fit 16 bits.
+fn checked_checksum(ctx: &mut dyn SystemContext, value: i32) -> Option<u16> {
+    match u16::try_from(value) {
+        Ok(checksum) => Some(checksum),
+        Err(_) if value == -1 => None,
+        Err(_) => {
+            ctx.report_warning(
+                BridgeWarning::ConversionFailed,
+                &format!("checksum returned {value}, which is not a 16-bit value"),
+            );
+            None
+        }
+    }
+}
+
 impl Drop for JavaTransportProtocol {
     fn drop(&mut self) {
         // Attach current thread to JVM to safely drop GlobalRefs
@@ -300,7 +735,7 @@ impl Drop for JavaTransportProtocol {
 
 impl TransportProtocol for JavaTransportProtocol {
     fn init(&mut self, ctx: &mut dyn SystemContext) {
-        self.call_java(ctx, |env, obj, ctx_obj| {
+        self.call_java(ctx, "init", |env, obj, ctx_obj| {
             env.call_method(
                 obj,
                 "init",
@@ -312,10 +747,22 @@ impl TransportProtocol for JavaTransportProtocol {
     }
 
     fn on_packet(&mut self, ctx: &mut dyn SystemContext, packet: Packet) {
-        self.call_java(ctx, |env, obj, ctx_obj| {
+        self.call_java(ctx, "on_packet", |env, obj, ctx_obj| {
             let header_cls = env.find_class("com/ouc/tcp/sdk/TcpHeader")?;
             let header_obj = env.new_object(header_cls, "()V", &[])?;
 
+            env.call_method(
+                &header_obj,
+                "setSrcPort",
+                "(I)V",
+                &[JValue::Int(packet.header.src_port as i32)],
+            )?;
+            env.call_method(
+                &header_obj,
+                "setDstPort",
+                "(I)V",
+                &[JValue::Int(packet.header.dst_port as i32)],
+            )?;
             env.call_method(
                 &header_obj,
                 "setSeqNum",
@@ -346,6 +793,12 @@ impl TransportProtocol for JavaTransportProtocol {
                 "(I)V",
                 &[JValue::Int(packet.header.checksum as i32)],
             )?;
+            env.call_method(
+                &header_obj,
+                "setUrgentPointer",
+                "(I)V",
+                &[JValue::Int(packet.header.urgent_ptr as i32)],
+            )?;
 
             let payload_arr = env.byte_array_from_slice(&packet.payload)?;
 
@@ -367,7 +820,7 @@ impl TransportProtocol for JavaTransportProtocol {
     }
 
     fn on_timer(&mut self, ctx: &mut dyn SystemContext, timer_id: u32) {
-        self.call_java(ctx, |env, obj, ctx_obj| {
+        self.call_java(ctx, "on_timer", |env, obj, ctx_obj| {
             env.call_method(
                 obj,
                 "onTimer",
@@ -379,7 +832,7 @@ impl TransportProtocol for JavaTransportProtocol {
     }
 
     fn on_app_data(&mut self, ctx: &mut dyn SystemContext, data: &[u8]) {
-        self.call_java(ctx, |env, obj, ctx_obj| {
+        self.call_java(ctx, "on_app_data", |env, obj, ctx_obj| {
             let data_arr = env.byte_array_from_slice(data)?;
             env.call_method(
                 obj,
@@ -390,4 +843,37 @@ impl TransportProtocol for JavaTransportProtocol {
             Ok(())
         });
     }
+
+    fn on_close(&mut self, ctx: &mut dyn SystemContext) {
+        self.call_java(ctx, "on_close", |env, obj, ctx_obj| {
+            env.call_method(
+                obj,
+                "onClose",
+                "(Lcom/ouc/tcp/sdk/SystemContext;)V",
+                &[JValue::Object(ctx_obj)],
+            )?;
+            Ok(())
+        });
+    }
+
+    fn checksum(&mut self, ctx: &mut dyn SystemContext, data: &[u8]) -> Option<u16> {
+        let mut value = -1;
+        self.call_java(ctx, "checksum", |env, obj, ctx_obj| {
+            let data_arr = env.byte_array_from_slice(data)?;
+            value = env
+                .call_method(
+                    obj,
+                    "checksum",
+                    "(Lcom/ouc/tcp/sdk/SystemContext;[B)I",
+                    &[JValue::Object(ctx_obj), JValue::Object(&data_arr)],
+                )?
+                .i()?;
+            Ok(())
+        });
+        checked_checksum(ctx, value)
+    }
+
+    fn language(&self) -> &'static str {
+        "java"
+    }
 }
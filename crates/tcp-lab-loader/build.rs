@@ -0,0 +1,17 @@
+use std::env;
+use std::path::PathBuf;
+
+fn main() {
+    // OUT_DIR is <target>/<profile>/build/<pkg>-<hash>/out; the JNI shim cdylib is
+    // emitted into <target>/<profile>, so remember it as a discovery fallback.
+    if let Some(profile_dir) = env::var_os("OUT_DIR")
+        .map(PathBuf::from)
+        .and_then(|out| out.ancestors().nth(3).map(PathBuf::from))
+    {
+        println!(
+            "cargo:rustc-env=TCP_LAB_BUILD_PROFILE_DIR={}",
+            profile_dir.display()
+        );
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -0,0 +1,106 @@
+//! Micro-benchmarks for the language bridges.
+//!
+//! Each loaded protocol is driven directly (no simulator, no channel) through a
+//! fixed synthetic workload so the measured time is dominated by the callback
+//! dispatch itself: JNI attach/marshalling, PyO3 conversions or FFI calls.
+
+use std::time::{Duration, Instant};
+use tcp_lab_abstract::{Packet, SystemContext, TransportProtocol};
+
+const PAYLOAD_SIZE: usize = 64;
+
+/// Average wall-clock cost of each callback for one backend.
+#[derive(Debug, Clone)]
+pub struct BridgeBenchResult {
+    pub backend: String,
+    pub iterations: u32,
+    pub init: Duration,
+    pub on_app_data: Duration,
+    pub on_packet: Duration,
+    pub on_timer: Duration,
+}
+
+impl BridgeBenchResult {
+    /// Mean cost of a callback, averaged over the three per-event callbacks.
+    pub fn mean_callback(&self) -> Duration {
+        (self.on_app_data + self.on_packet + self.on_timer) / 3
+    }
+}
+
+/// Context that accepts every action and discards it.
+#[derive(Default)]
+struct BenchContext {
+    now: u64,
+    actions: u64,
+}
+
+impl SystemContext for BenchContext {
+    fn send_packet(&mut self, _packet: Packet) {
+        self.actions += 1;
+    }
+
+    fn start_timer(&mut self, _delay_ms: u64, _timer_id: u32) {
+        self.actions += 1;
+    }
+
+    fn cancel_timer(&mut self, _timer_id: u32) {
+        self.actions += 1;
+    }
+
+    fn deliver_data(&mut self, _data: &[u8]) {
+        self.actions += 1;
+    }
+
+    fn log(&mut self, _message: &str) {
+        self.actions += 1;
+    }
+
+    fn now(&self) -> u64 {
+        self.now
+    }
+}
+
+/// Run the synthetic workload `iterations` times per callback against `protocol`.
+pub fn bench_protocol(
+    backend: impl Into<String>,
+    protocol: &mut dyn TransportProtocol,
+    iterations: u32,
+) -> BridgeBenchResult {
+    let iterations = iterations.max(1);
+    let mut ctx = BenchContext::default();
+    let payload = vec![0x5Au8; PAYLOAD_SIZE];
+
+    let start = Instant::now();
+    protocol.init(&mut ctx);
+    let init = start.elapsed();
+
+    let start = Instant::now();
+    for _ in 0..iterations {
+        ctx.now += 1;
+        protocol.on_app_data(&mut ctx, &payload);
+    }
+    let on_app_data = start.elapsed() / iterations;
+
+    let start = Instant::now();
+    for i in 0..iterations {
+        ctx.now += 1;
+        protocol.on_packet(&mut ctx, Packet::new_ack(0, i & 1, 0));
+    }
+    let on_packet = start.elapsed() / iterations;
+
+    let start = Instant::now();
+    for _ in 0..iterations {
+        ctx.now += 1;
+        protocol.on_timer(&mut ctx, 1);
+    }
+    let on_timer = start.elapsed() / iterations;
+
+    BridgeBenchResult {
+        backend: backend.into(),
+        iterations,
+        init,
+        on_app_data,
+        on_packet,
+        on_timer,
+    }
+}
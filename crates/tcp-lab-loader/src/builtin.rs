@@ -1,5 +1,8 @@
 use std::collections::VecDeque;
 use tcp_lab_abstract::{Packet, SystemContext, TransportProtocol, flags};
+use tcp_lab_rust_sdk::rdt1;
+
+use crate::BuiltinProtocol;
 
 const DATA_TIMER: u32 = 1;
 const DATA_TIMEOUT_MS: u64 = 1000;
@@ -90,6 +93,9 @@ impl TransportProtocol for Rdt2Sender {
         self.pending.push_back(data.to_vec());
         self.try_send(ctx);
     }
+    fn checksum(&mut self, _ctx: &mut dyn SystemContext, data: &[u8]) -> Option<u16> {
+        Some(checksum(data))
+    }
 }
 
 #[derive(Default)]
@@ -144,6 +150,19 @@ impl TransportProtocol for Rdt2Receiver {
     fn on_timer(&mut self, _ctx: &mut dyn SystemContext, _timer_id: u32) {}
 
     fn on_app_data(&mut self, _ctx: &mut dyn SystemContext, _data: &[u8]) {}
+
+    fn checksum(&mut self, _ctx: &mut dyn SystemContext, data: &[u8]) -> Option<u16> {
+        Some(checksum(data))
+    }
+}
+
+pub fn instantiate(builtin: BuiltinProtocol) -> Box<dyn TransportProtocol> {
+    match builtin {
+        BuiltinProtocol::Rdt1Sender => rdt1::sender(),
+        BuiltinProtocol::Rdt1Receiver => rdt1::receiver(),
+        BuiltinProtocol::Rdt2Sender => rdt2_sender(),
+        BuiltinProtocol::Rdt2Receiver => rdt2_receiver(),
+    }
 }
 
 pub fn rdt2_sender() -> Box<dyn TransportProtocol> {
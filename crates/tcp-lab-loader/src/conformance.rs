@@ -0,0 +1,299 @@
+//! Round-trip checks for the language bridges.
+//!
+//! A reflector written with each SDK (`com.ouc.tcp.sdk.conformance.Reflector`,
+//! `tcp_lab_sdk.reflector.Reflector`, `sdk/cpp/src/reflector.cpp`) sends every packet it
+//! is given straight back. Driving it with edge-case packets shows whether every header
+//! field and payload byte survives the trip into the language and out again, so the
+//! bridges can be shown to agree bit for bit.
+//!
+//! The same way, [`check_checksums`] runs a protocol's own checksum, through the
+//! `checksum` diagnostic hook, over the reference [`test_vectors`].
+
+use std::fmt::Write;
+
+use tcp_lab_abstract::{BridgeWarning, Packet, SystemContext, TcpHeader, TransportProtocol, flags};
+use tcp_lab_rust_sdk::checksum::test_vectors;
+
+/// One packet of the matrix.
+#[derive(Debug, Clone)]
+pub struct EdgeCase {
+    pub name: &'static str,
+    pub packet: Packet,
+}
+
+/// The packets every bridge must hand back unchanged.
+pub fn edge_cases() -> Vec<EdgeCase> {
+    let header =
+        |src_port, dst_port, seq_num, ack_num, flags, window_size, checksum, urgent_ptr| {
+            TcpHeader {
+                src_port,
+                dst_port,
+                seq_num,
+                ack_num,
+                flags,
+                window_size,
+                checksum,
+                urgent_ptr,
+            }
+        };
+    let case = |name, header, payload| EdgeCase {
+        name,
+        packet: Packet::new(header, payload),
+    };
+    vec![
+        case("empty", TcpHeader::default(), Vec::new()),
+        case(
+            "max_fields",
+            header(
+                u16::MAX,
+                u16::MAX,
+                u32::MAX,
+                u32::MAX,
+                u8::MAX,
+                u16::MAX,
+                u16::MAX,
+                u16::MAX,
+            ),
+            b"max".to_vec(),
+        ),
+        // Values with the top bit set, which a signed type in the language would flip.
+        case(
+            "sign_bits",
+            header(
+                0x8001,
+                0x8002,
+                0x8000_0003,
+                0x8000_0004,
+                0x80,
+                0x8005,
+                0x8006,
+                0x8007,
+            ),
+            vec![0x80],
+        ),
+        // No two fields alike, so a swap between them shows.
+        case(
+            "distinct_fields",
+            header(1, 2, 3, 4, flags::ACK, 5, 6, 7),
+            b"distinct".to_vec(),
+        ),
+        case("all_flags", header(0, 0, 0, 0, 0xFF, 0, 0, 0), Vec::new()),
+        case(
+            "payload_64k",
+            header(1000, 2000, 1, 0, flags::ACK | flags::PSH, 65535, 0, 0),
+            (0..64 * 1024).map(|i| (i % 251) as u8).collect(),
+        ),
+        case(
+            "non_utf8",
+            TcpHeader::new(1, 0, 0, 0),
+            vec![
+                0xFF, 0xFE, 0xC3, 0x28, 0xA0, 0xA1, 0xED, 0xA0, 0x80, 0xC0, 0x80,
+            ],
+        ),
+        case("nul_bytes", TcpHeader::new(1, 0, 0, 0), vec![0; 16]),
+    ]
+}
+
+/// How one edge case came back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseOutcome {
+    pub case: &'static str,
+    /// What differed, `None` if the packet came back bit for bit.
+    pub mismatch: Option<String>,
+}
+
+/// Every edge case sent through one backend.
+#[derive(Debug, Clone)]
+pub struct RoundTripReport {
+    pub backend: String,
+    pub cases: Vec<CaseOutcome>,
+}
+
+impl RoundTripReport {
+    pub fn passed(&self) -> bool {
+        self.cases.iter().all(|case| case.mismatch.is_none())
+    }
+}
+
+/// Context that keeps what the protocol sends and the warnings of its bridge.
+#[derive(Default)]
+struct CheckContext {
+    sent: Vec<Packet>,
+    warnings: Vec<String>,
+}
+
+impl SystemContext for CheckContext {
+    fn send_packet(&mut self, packet: Packet) {
+        self.sent.push(packet);
+    }
+
+    fn start_timer(&mut self, _delay_ms: u64, _timer_id: u32) {}
+
+    fn cancel_timer(&mut self, _timer_id: u32) {}
+
+    fn deliver_data(&mut self, _data: &[u8]) {}
+
+    fn log(&mut self, _message: &str) {}
+
+    fn now(&self) -> u64 {
+        0
+    }
+
+    fn report_warning(&mut self, _kind: BridgeWarning, detail: &str) {
+        self.warnings.push(detail.to_string());
+    }
+}
+
+/// Hand each edge case to `reflector` and compare what it sends back.
+pub fn check_round_trip(
+    backend: impl Into<String>,
+    reflector: &mut dyn TransportProtocol,
+) -> RoundTripReport {
+    let mut ctx = CheckContext::default();
+    reflector.init(&mut ctx);
+    let cases = edge_cases()
+        .into_iter()
+        .map(|case| {
+            ctx.sent.clear();
+            reflector.on_packet(&mut ctx, case.packet.clone());
+            let mismatch = match ctx.sent.as_slice() {
+                [back] => compare(&case.packet, back),
+                sent => Some(format!("sent back {} packets instead of 1", sent.len())),
+            };
+            CaseOutcome {
+                case: case.name,
+                mismatch,
+            }
+        })
+        .collect();
+    RoundTripReport {
+        backend: backend.into(),
+        cases,
+    }
+}
+
+/// The fields of `back` that differ from `sent`, e.g. `src_port 65535 came back as 0`.
+fn compare(sent: &Packet, back: &Packet) -> Option<String> {
+    let (a, b) = (&sent.header, &back.header);
+    let fields = [
+        ("src_port", u64::from(a.src_port), u64::from(b.src_port)),
+        ("dst_port", u64::from(a.dst_port), u64::from(b.dst_port)),
+        ("seq_num", u64::from(a.seq_num), u64::from(b.seq_num)),
+        ("ack_num", u64::from(a.ack_num), u64::from(b.ack_num)),
+        ("flags", u64::from(a.flags), u64::from(b.flags)),
+        (
+            "window_size",
+            u64::from(a.window_size),
+            u64::from(b.window_size),
+        ),
+        ("checksum", u64::from(a.checksum), u64::from(b.checksum)),
+        (
+            "urgent_ptr",
+            u64::from(a.urgent_ptr),
+            u64::from(b.urgent_ptr),
+        ),
+    ];
+    let mut diffs: Vec<String> = fields
+        .iter()
+        .filter(|(_, sent, back)| sent != back)
+        .map(|(name, sent, back)| format!("{name} {sent} came back as {back}"))
+        .collect();
+    if sent.payload != back.payload {
+        let mut diff = String::from("payload");
+        match sent
+            .payload
+            .iter()
+            .zip(&back.payload)
+            .position(|(x, y)| x != y)
+        {
+            Some(at) => {
+                let _ = write!(
+                    diff,
+                    " differs at byte {at} ({:#04x} came back as {:#04x})",
+                    sent.payload[at], back.payload[at]
+                );
+            }
+            None => {
+                let _ = write!(
+                    diff,
+                    " of {} bytes came back with {}",
+                    sent.payload.len(),
+                    back.payload.len()
+                );
+            }
+        }
+        diffs.push(diff);
+    }
+    (!diffs.is_empty()).then(|| diffs.join(", "))
+}
+
+/// What a protocol's checksum gave for one test vector.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VectorOutcome {
+    pub vector: &'static str,
+    pub expected: u16,
+    /// `None` if the protocol gave no checksum.
+    pub got: Option<u16>,
+}
+
+impl VectorOutcome {
+    pub fn passed(&self) -> bool {
+        self.got == Some(self.expected)
+    }
+}
+
+/// Every test vector run through one backend's checksum.
+#[derive(Debug, Clone)]
+pub struct ChecksumReport {
+    pub backend: String,
+    pub vectors: Vec<VectorOutcome>,
+    /// Bridge warnings raised on the way, e.g. a hook that returned a value over 16 bits.
+    pub warnings: Vec<String>,
+}
+
+impl ChecksumReport {
+    pub fn passed(&self) -> bool {
+        self.vectors.iter().all(VectorOutcome::passed)
+    }
+
+    /// Whether the protocol answered at all, i.e. implements the `checksum` hook.
+    pub fn has_hook(&self) -> bool {
+        self.vectors.iter().any(|vector| vector.got.is_some())
+    }
+}
+
+/// Ask `protocol` for its checksum of every test vector.
+pub fn check_checksums(
+    backend: impl Into<String>,
+    protocol: &mut dyn TransportProtocol,
+) -> ChecksumReport {
+    let mut ctx = CheckContext::default();
+    // A checksum may rely on state set up in `init`, e.g. a lookup table.
+    protocol.init(&mut ctx);
+    let vectors = test_vectors()
+        .into_iter()
+        .map(|vector| VectorOutcome {
+            vector: vector.name,
+            expected: vector.checksum,
+            got: protocol.checksum(&mut ctx, &vector.data),
+        })
+        .collect();
+    ChecksumReport {
+        backend: backend.into(),
+        vectors,
+        warnings: ctx.warnings,
+    }
+}
+
+/// The reflector in Rust, which crosses no bridge; a control for the harness itself.
+pub struct Reflector;
+
+impl TransportProtocol for Reflector {
+    fn on_packet(&mut self, ctx: &mut dyn SystemContext, packet: Packet) {
+        ctx.send_packet(packet);
+    }
+
+    fn on_timer(&mut self, _ctx: &mut dyn SystemContext, _timer_id: u32) {}
+
+    fn on_app_data(&mut self, _ctx: &mut dyn SystemContext, _data: &[u8]) {}
+}
@@ -1,10 +1,16 @@
 use std::ffi::c_void;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
 
-use anyhow::Context;
-use libloading::{Library, Symbol};
+use libloading::Library;
 use tcp_lab_abstract::{Packet, SystemContext, TransportProtocol};
 use tcp_lab_ffi::with_context;
+use tracing::debug_span;
+
+use crate::{LoaderError, check_sdk_version};
 
 /// C function types exported by a C++ protocol library.
 ///
@@ -15,78 +21,222 @@ use tcp_lab_ffi::with_context;
 /// extern "C" void protocol_init(TransportProtocol*);
 /// extern "C" void protocol_on_app_data(TransportProtocol*, const uint8_t* data, size_t len);
 /// extern "C" void protocol_on_packet(TransportProtocol*,
+///                                  uint16_t src_port, uint16_t dst_port,
 ///                                  uint32_t seq, uint32_t ack, uint8_t flags,
-///                                  uint16_t window, uint16_t checksum,
+///                                  uint16_t window, uint16_t checksum, uint16_t urgent_ptr,
 ///                                  const uint8_t* payload, size_t len);
 /// extern "C" void protocol_on_timer(TransportProtocol*, int timerId);
+/// extern "C" void protocol_on_close(TransportProtocol*); // optional
+/// extern "C" int32_t protocol_checksum(TransportProtocol*, const uint8_t* data, size_t len); // optional
+/// extern "C" uint32_t tcp_lab_sdk_version();
 /// ```
-
 type CreateFn = unsafe extern "C" fn() -> *mut c_void;
 type DestroyFn = unsafe extern "C" fn(*mut c_void);
 type InitFn = unsafe extern "C" fn(*mut c_void);
 type OnAppDataFn = unsafe extern "C" fn(*mut c_void, *const u8, usize);
-type OnPacketFn = unsafe extern "C" fn(*mut c_void, u32, u32, u8, u16, u16, *const u8, usize);
+type OnPacketFn =
+    unsafe extern "C" fn(*mut c_void, u16, u16, u32, u32, u8, u16, u16, u16, *const u8, usize);
 type OnTimerFn = unsafe extern "C" fn(*mut c_void, i32);
+type OnCloseFn = unsafe extern "C" fn(*mut c_void);
+type ChecksumFn = unsafe extern "C" fn(*mut c_void, *const u8, usize) -> i32;
+/// `extern "C" uint32_t tcp_lab_abi_version();`, exported by `TCP_LAB_REGISTER_PROTOCOL`.
+type AbiVersionFn = unsafe extern "C" fn() -> u32;
+/// `extern "C" uint32_t tcp_lab_sdk_version();`, also exported by the macro.
+type SdkVersionFn = unsafe extern "C" fn() -> u32;
+/// `extern "C" void protocol_selftest();`, which passes a probe to `tcp_lab_selftest`.
+/// Libraries built before the macro exported it skip the self-test.
+type SelftestFn = unsafe extern "C" fn();
+
+/// Pauses before every callback into a library so a debugger can be attached to the
+/// running host (`--debug-attach`): each pause names the process, the callback and the
+/// packet or timer it is about to handle.
+#[derive(Debug, Clone)]
+pub struct DebugAttach {
+    pub pause: Duration,
+    /// A shell command started (not waited for) before each pause, from
+    /// [`DebugAttach::NOTIFY_ENV`]; it gets `TCP_LAB_PID`, `TCP_LAB_CALLBACK`,
+    /// `TCP_LAB_DETAIL` and `TCP_LAB_LIBRARY` in its environment, e.g. to raise a desktop
+    /// notification or open a terminal running `gdb -p "$TCP_LAB_PID"`.
+    pub notify: Option<String>,
+}
+
+impl DebugAttach {
+    pub const NOTIFY_ENV: &'static str = "TCP_LAB_DEBUG_ATTACH_NOTIFY";
+
+    /// Pause for `pause`, notifying through the command in [`DebugAttach::NOTIFY_ENV`] if set.
+    pub fn from_env(pause: Duration) -> Self {
+        Self {
+            pause,
+            notify: std::env::var(Self::NOTIFY_ENV)
+                .ok()
+                .filter(|command| !command.is_empty()),
+        }
+    }
+
+    fn before(&self, library: &Path, callback: &str, detail: &str) {
+        let pid = std::process::id();
+        eprintln!(
+            "tcp-lab: pid {pid} is about to call {callback}({detail}) in {}; pausing {} ms \
+             (attach with `gdb -p {pid}` or `lldb -p {pid}`)",
+            library.display(),
+            self.pause.as_millis()
+        );
+        if let Some(command) = &self.notify {
+            let mut shell = if cfg!(windows) {
+                let mut shell = Command::new("cmd");
+                shell.args(["/C", command]);
+                shell
+            } else {
+                let mut shell = Command::new("sh");
+                shell.args(["-c", command]);
+                shell
+            };
+            let spawned = shell
+                .env("TCP_LAB_PID", pid.to_string())
+                .env("TCP_LAB_CALLBACK", callback)
+                .env("TCP_LAB_DETAIL", detail)
+                .env("TCP_LAB_LIBRARY", library)
+                .spawn();
+            if let Err(err) = spawned {
+                eprintln!("tcp-lab: failed to run {}: {err}", Self::NOTIFY_ENV);
+            }
+        }
+        thread::sleep(self.pause);
+    }
+}
 
 pub struct CppTransportProtocol {
     _lib: Library,
+    library: PathBuf,
+    debug_attach: Option<DebugAttach>,
     instance: *mut c_void,
     destroy: DestroyFn,
     init_fn: InitFn,
     on_app_data_fn: OnAppDataFn,
     on_packet_fn: OnPacketFn,
     on_timer_fn: OnTimerFn,
+    on_close_fn: Option<OnCloseFn>,
+    checksum_fn: Option<ChecksumFn>,
 }
 
 unsafe impl Send for CppTransportProtocol {}
 unsafe impl Sync for CppTransportProtocol {}
 
-impl CppTransportProtocol {
-    fn new(lib: Library) -> anyhow::Result<Self> {
+/// The functions a protocol library exports, looked up but not yet called.
+struct Exports {
+    create: CreateFn,
+    destroy: DestroyFn,
+    init_fn: InitFn,
+    on_app_data_fn: OnAppDataFn,
+    on_packet_fn: OnPacketFn,
+    on_timer_fn: OnTimerFn,
+    /// Libraries built before the close hook existed do not export it.
+    on_close_fn: Option<OnCloseFn>,
+    /// Nor the checksum hook.
+    checksum_fn: Option<ChecksumFn>,
+}
+
+impl Exports {
+    /// Look up every required symbol and check the library was built against this host's
+    /// ABI and SDK, with a data layout matching the host's. Libraries from before the ABI
+    /// version symbol existed are taken to be ABI version 1.
+    fn resolve(lib: &Library) -> Result<Self, LoaderError> {
         unsafe {
-            let create: Symbol<CreateFn> = lib
-                .get(b"create_protocol\0")
-                .context("missing create_protocol")?;
-            let destroy_sym: Symbol<DestroyFn> = lib
-                .get(b"destroy_protocol\0")
-                .context("missing destroy_protocol")?;
-            let init_sym: Symbol<InitFn> = lib
-                .get(b"protocol_init\0")
-                .context("missing protocol_init")?;
-            let on_app_data_sym: Symbol<OnAppDataFn> = lib
-                .get(b"protocol_on_app_data\0")
-                .context("missing protocol_on_app_data")?;
-            let on_packet_sym: Symbol<OnPacketFn> = lib
-                .get(b"protocol_on_packet\0")
-                .context("missing protocol_on_packet")?;
-            let on_timer_sym: Symbol<OnTimerFn> = lib
-                .get(b"protocol_on_timer\0")
-                .context("missing protocol_on_timer")?;
-
-            let destroy = *destroy_sym;
-            let init_fn = *init_sym;
-            let on_app_data_fn = *on_app_data_sym;
-            let on_packet_fn = *on_packet_sym;
-            let on_timer_fn = *on_timer_sym;
-
-            let instance = create();
-            if instance.is_null() {
-                anyhow::bail!("create_protocol returned null");
+            if let Ok(version) = lib.get::<AbiVersionFn>(b"tcp_lab_abi_version\0") {
+                let version = version();
+                if version != tcp_lab_ffi::ABI_VERSION {
+                    return Err(LoaderError::Unloadable(format!(
+                        "library was built against tcp-lab C ABI version {}, but this host \
+                         expects version {}; rebuild it with the current sdk/cpp headers",
+                        version,
+                        tcp_lab_ffi::ABI_VERSION
+                    )));
+                }
+            }
+            let sdk_version = lib
+                .get::<SdkVersionFn>(b"tcp_lab_sdk_version\0")
+                .ok()
+                .map(|version| version());
+            check_sdk_version("sdk/cpp", sdk_version)?;
+            if let Ok(selftest) = lib.get::<SelftestFn>(b"protocol_selftest\0") {
+                let outcome = tcp_lab_ffi::selftest(|| selftest())
+                    .unwrap_or_else(|| Err("protocol_selftest sent no probe".to_string()));
+                if let Err(problem) = outcome {
+                    return Err(LoaderError::Unloadable(format!(
+                        "library failed the C ABI self-test: {problem}; rebuild it with \
+                         the compiler and flags of sdk/cpp"
+                    )));
+                }
             }
-
             Ok(Self {
-                _lib: lib,
-                instance,
-                destroy,
-                init_fn,
-                on_app_data_fn,
-                on_packet_fn,
-                on_timer_fn,
+                create: *lib
+                    .get::<CreateFn>(b"create_protocol\0")
+                    .map_err(missing("create_protocol"))?,
+                destroy: *lib
+                    .get::<DestroyFn>(b"destroy_protocol\0")
+                    .map_err(missing("destroy_protocol"))?,
+                init_fn: *lib
+                    .get::<InitFn>(b"protocol_init\0")
+                    .map_err(missing("protocol_init"))?,
+                on_app_data_fn: *lib
+                    .get::<OnAppDataFn>(b"protocol_on_app_data\0")
+                    .map_err(missing("protocol_on_app_data"))?,
+                on_packet_fn: *lib
+                    .get::<OnPacketFn>(b"protocol_on_packet\0")
+                    .map_err(missing("protocol_on_packet"))?,
+                on_timer_fn: *lib
+                    .get::<OnTimerFn>(b"protocol_on_timer\0")
+                    .map_err(missing("protocol_on_timer"))?,
+                on_close_fn: lib
+                    .get::<OnCloseFn>(b"protocol_on_close\0")
+                    .ok()
+                    .map(|symbol| *symbol),
+                checksum_fn: lib
+                    .get::<ChecksumFn>(b"protocol_checksum\0")
+                    .ok()
+                    .map(|symbol| *symbol),
             })
         }
     }
 }
 
+impl CppTransportProtocol {
+    fn new(
+        lib: Library,
+        library: PathBuf,
+        debug_attach: Option<DebugAttach>,
+    ) -> Result<Self, LoaderError> {
+        let exports = Exports::resolve(&lib)?;
+        let instance = unsafe { (exports.create)() };
+        if instance.is_null() {
+            return Err(LoaderError::StudentRuntime(
+                "create_protocol returned null".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            _lib: lib,
+            library,
+            debug_attach,
+            instance,
+            destroy: exports.destroy,
+            init_fn: exports.init_fn,
+            on_app_data_fn: exports.on_app_data_fn,
+            on_packet_fn: exports.on_packet_fn,
+            on_timer_fn: exports.on_timer_fn,
+            on_close_fn: exports.on_close_fn,
+            checksum_fn: exports.checksum_fn,
+        })
+    }
+
+    /// Give a debugger the chance to attach before `callback` runs, if asked to.
+    fn pause(&self, callback: &str, detail: impl FnOnce() -> String) {
+        if let Some(debug_attach) = &self.debug_attach {
+            debug_attach.before(&self.library, callback, &detail());
+        }
+    }
+}
+
 impl Drop for CppTransportProtocol {
     fn drop(&mut self) {
         unsafe {
@@ -97,6 +247,8 @@ impl Drop for CppTransportProtocol {
 
 impl TransportProtocol for CppTransportProtocol {
     fn init(&mut self, ctx: &mut dyn SystemContext) {
+        let _span = debug_span!("bridge_call", language = "cpp", callback = "init").entered();
+        self.pause("protocol_init", String::new);
         unsafe {
             with_context(ctx, || {
                 (self.init_fn)(self.instance);
@@ -105,17 +257,30 @@ impl TransportProtocol for CppTransportProtocol {
     }
 
     fn on_packet(&mut self, ctx: &mut dyn SystemContext, packet: Packet) {
+        let _span = debug_span!("bridge_call", language = "cpp", callback = "on_packet").entered();
+        self.pause("protocol_on_packet", || {
+            format!(
+                "seq={}, ack={}, flags={:#04x}, len={}",
+                packet.header.seq_num,
+                packet.header.ack_num,
+                packet.header.flags,
+                packet.payload.len()
+            )
+        });
         unsafe {
             let header = packet.header;
             let payload = packet.payload;
             with_context(ctx, || {
                 (self.on_packet_fn)(
                     self.instance,
+                    header.src_port,
+                    header.dst_port,
                     header.seq_num,
                     header.ack_num,
                     header.flags,
                     header.window_size,
                     header.checksum,
+                    header.urgent_ptr,
                     payload.as_ptr(),
                     payload.len(),
                 );
@@ -124,6 +289,8 @@ impl TransportProtocol for CppTransportProtocol {
     }
 
     fn on_timer(&mut self, ctx: &mut dyn SystemContext, timer_id: u32) {
+        let _span = debug_span!("bridge_call", language = "cpp", callback = "on_timer").entered();
+        self.pause("protocol_on_timer", || format!("timerId={timer_id}"));
         unsafe {
             with_context(ctx, || {
                 (self.on_timer_fn)(self.instance, timer_id as i32);
@@ -132,19 +299,99 @@ impl TransportProtocol for CppTransportProtocol {
     }
 
     fn on_app_data(&mut self, ctx: &mut dyn SystemContext, data: &[u8]) {
+        let _span =
+            debug_span!("bridge_call", language = "cpp", callback = "on_app_data").entered();
+        self.pause("protocol_on_app_data", || format!("len={}", data.len()));
         unsafe {
             with_context(ctx, || {
                 (self.on_app_data_fn)(self.instance, data.as_ptr(), data.len());
             });
         }
     }
+
+    fn on_close(&mut self, ctx: &mut dyn SystemContext) {
+        let Some(on_close_fn) = self.on_close_fn else {
+            return;
+        };
+        let _span = debug_span!("bridge_call", language = "cpp", callback = "on_close").entered();
+        self.pause("protocol_on_close", String::new);
+        unsafe {
+            with_context(ctx, || {
+                on_close_fn(self.instance);
+            });
+        }
+    }
+
+    fn checksum(&mut self, ctx: &mut dyn SystemContext, data: &[u8]) -> Option<u16> {
+        let checksum_fn = self.checksum_fn?;
+        let _span = debug_span!("bridge_call", language = "cpp", callback = "checksum").entered();
+        self.pause("protocol_checksum", || format!("len={}", data.len()));
+        let value = unsafe {
+            with_context(ctx, || {
+                checksum_fn(self.instance, data.as_ptr(), data.len())
+            })
+        };
+        // The macro returns -1 for an empty optional and the checksum otherwise.
+        u16::try_from(value).ok()
+    }
+
+    fn language(&self) -> &'static str {
+        "cpp"
+    }
+}
+
+/// The error for a library that does not export the symbol `name`.
+fn missing(name: &'static str) -> impl Fn(libloading::Error) -> LoaderError {
+    move |err| LoaderError::Unloadable(format!("missing {name}: {err}"))
 }
 
-/// Load a C++ protocol library from the given path and wrap it as a Rust TransportProtocol.
-pub fn load_protocol<P: AsRef<Path>>(path: P) -> anyhow::Result<Box<dyn TransportProtocol>> {
+fn open_library(path: &Path) -> Result<Library, LoaderError> {
     tcp_lab_ffi::ensure_linked();
-    let lib = unsafe { Library::new(path.as_ref()) }
-        .with_context(|| format!("failed to load C++ protocol library {:?}", path.as_ref()))?;
-    let cpp = CppTransportProtocol::new(lib)?;
+    unsafe { Library::new(path) }.map_err(|err| {
+        LoaderError::Unloadable(format!(
+            "failed to load C++ protocol library {:?}: {}",
+            path, err
+        ))
+    })
+}
+
+/// Load a C++ protocol library from the given path and wrap it as a Rust TransportProtocol,
+/// pausing before each callback if `debug_attach` is set.
+pub fn load_protocol<P: AsRef<Path>>(
+    path: P,
+    debug_attach: Option<DebugAttach>,
+) -> Result<Box<dyn TransportProtocol>, LoaderError> {
+    let path = path.as_ref();
+    let lib = open_library(path)?;
+    let cpp = CppTransportProtocol::new(lib, path.to_path_buf(), debug_attach)?;
     Ok(Box::new(cpp))
 }
+
+/// Copy the library at `path` to a new file in the temp directory. The dynamic loader hands
+/// out an already open library again for the same path, so loading the copy is the only way
+/// to get a rebuilt library while the old one is still in use.
+pub fn fresh_copy(path: &Path) -> Result<PathBuf, LoaderError> {
+    static COPIES: AtomicU64 = AtomicU64::new(0);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let copy = std::env::temp_dir().join(format!(
+        "tcp-lab-{}-{}-{}",
+        std::process::id(),
+        COPIES.fetch_add(1, Ordering::Relaxed),
+        name
+    ));
+    std::fs::copy(path, &copy).map_err(|err| {
+        LoaderError::Unloadable(format!(
+            "failed to copy C++ protocol library {:?}: {}",
+            path, err
+        ))
+    })?;
+    Ok(copy)
+}
+
+/// Check that the library at `path` opens, exports every protocol function and matches this
+/// host's ABI version, without calling `create_protocol`. Opening the library still runs its
+/// static initializers.
+pub fn check_protocol<P: AsRef<Path>>(path: P) -> Result<(), LoaderError> {
+    let lib = open_library(path.as_ref())?;
+    Exports::resolve(&lib).map(|_| ())
+}
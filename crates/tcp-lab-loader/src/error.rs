@@ -0,0 +1,40 @@
+use std::fmt::Display;
+
+/// Why a protocol could not be loaded, by whose problem it is: graders report an
+/// `Unloadable` or `StudentRuntime` submission to the student, an `Environment` error to
+/// whoever runs the host.
+#[derive(Debug, thiserror::Error)]
+pub enum LoaderError {
+    /// A protocol spec or builtin name could not be understood.
+    #[error("{0}")]
+    Parse(String),
+    /// The class, module or library is missing, or does not have the expected shape.
+    #[error("{0}")]
+    Unloadable(String),
+    /// The submission's own code failed while it was being constructed, e.g. a constructor
+    /// threw or a Python module raised on import.
+    #[error("{0}")]
+    StudentRuntime(String),
+    /// The host lacks something the backend needs: a JVM or CPython, the JNI shim, or the
+    /// cargo feature for that language.
+    #[error("{0}")]
+    Environment(String),
+}
+
+impl LoaderError {
+    pub fn parse(err: impl Display) -> Self {
+        Self::Parse(format!("{err:#}"))
+    }
+
+    pub fn unloadable(err: impl Display) -> Self {
+        Self::Unloadable(format!("{err:#}"))
+    }
+
+    pub fn student_runtime(err: impl Display) -> Self {
+        Self::StudentRuntime(format!("{err:#}"))
+    }
+
+    pub fn environment(err: impl Display) -> Self {
+        Self::Environment(format!("{err:#}"))
+    }
+}
@@ -0,0 +1,66 @@
+//! The reference protocols built into the loader, in lab order, so that frontends list and
+//! construct them from here instead of keeping their own lists.
+
+use tcp_lab_abstract::TransportProtocol;
+
+use crate::{BuiltinProtocol, builtin};
+
+/// A built-in sender/receiver pair and what it demonstrates.
+#[derive(Debug, Clone, Copy)]
+pub struct Example {
+    /// Name accepted by `--builtin-sender`/`--builtin-receiver`, e.g. `rdt2`.
+    pub name: &'static str,
+    /// The lab stage, spelled like the scenario tags in `tests/`.
+    pub stage: &'static str,
+    pub description: &'static str,
+    pub sender: BuiltinProtocol,
+    pub receiver: BuiltinProtocol,
+}
+
+impl Example {
+    /// The sender or the receiver of the pair.
+    pub fn builtin(&self, is_sender: bool) -> BuiltinProtocol {
+        if is_sender {
+            self.sender
+        } else {
+            self.receiver
+        }
+    }
+
+    pub fn new_sender(&self) -> Box<dyn TransportProtocol> {
+        builtin::instantiate(self.sender)
+    }
+
+    pub fn new_receiver(&self) -> Box<dyn TransportProtocol> {
+        builtin::instantiate(self.receiver)
+    }
+}
+
+const CATALOG: &[Example] = &[
+    Example {
+        name: "rdt1",
+        stage: "rdt1",
+        description: "Sends each message as one packet and delivers whatever arrives; \
+                      correct only on a perfect channel",
+        sender: BuiltinProtocol::Rdt1Sender,
+        receiver: BuiltinProtocol::Rdt1Receiver,
+    },
+    Example {
+        name: "rdt2",
+        stage: "rdt2",
+        description: "Stop-and-wait with a checksum, alternating sequence numbers, \
+                      duplicate ACKs for corrupt packets and a retransmission timeout",
+        sender: BuiltinProtocol::Rdt2Sender,
+        receiver: BuiltinProtocol::Rdt2Receiver,
+    },
+];
+
+/// Every built-in pair, earliest lab stage first.
+pub fn catalog() -> &'static [Example] {
+    CATALOG
+}
+
+/// The built-in pair called `name`.
+pub fn find(name: &str) -> Option<&'static Example> {
+    CATALOG.iter().find(|example| example.name == name)
+}
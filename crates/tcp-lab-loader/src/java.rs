@@ -1,20 +1,154 @@
-use jni::{InitArgsBuilder, JavaVM};
-use std::sync::Arc;
+use anyhow::{Context, bail};
+use jni::objects::{JClass, JObject};
+use jni::{InitArgsBuilder, JNIEnv, JavaVM};
+use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use tcp_lab_abstract::TransportProtocol;
 use tcp_lab_jni::JavaTransportProtocol;
+use tracing::{debug, warn};
 
-pub fn create_jvm(classpath: &str) -> anyhow::Result<Arc<JavaVM>> {
-    // Detect library path (where libtcp_lab_jni.dylib/so is)
-    // Assuming we run from cargo run, it is in target/debug/
-    let lib_path = std::env::current_dir()?.join("target/debug");
+use crate::{LoaderError, check_sdk_version};
 
-    let jvm_args = InitArgsBuilder::new()
+/// Overrides where the JNI shim (`tcp_lab_jni`) is looked up; a directory or the library itself.
+const JNI_PATH_ENV: &str = "TCP_LAB_JNI_PATH";
+
+/// Interface every Java-side protocol implements.
+const PROTOCOL_INTERFACE: &str = "com/ouc/tcp/sdk/TransportProtocol";
+
+/// Heap cap applied unless the caller passes its own, so one memory-hungry submission cannot
+/// exhaust a grading host running many JVMs.
+pub const DEFAULT_MAX_HEAP: &str = "-Xmx512m";
+
+/// Target profile directory this crate was built into, derived from OUT_DIR by build.rs.
+const BUILD_PROFILE_DIR: Option<&str> = option_env!("TCP_LAB_BUILD_PROFILE_DIR");
+
+/// How the JVM is started, from `LoaderBuilder`.
+pub struct JvmSettings<'a> {
+    /// Passed through after tcp-lab's own options (so they win), e.g. `-Xmx256m` or `-ea`.
+    /// [`DEFAULT_MAX_HEAP`] is added unless they already limit the heap.
+    pub options: &'a [String],
+    /// Install the SDK's `GradingSandbox` security manager before any protocol class loads.
+    pub sandbox: bool,
+    /// Bind `System.currentTimeMillis` and `System.nanoTime` to simulated time.
+    pub deterministic_time: bool,
+}
+
+/// What a running JVM was started with; a process can only ever start one.
+#[derive(PartialEq, Eq)]
+struct JvmKey {
+    classpath: String,
+    options: Vec<String>,
+    sandbox: bool,
+    deterministic_time: bool,
+}
+
+/// The JVM of this process, shared by every loader asking for the same settings, or why
+/// it could not be set up.
+type SharedJvm = (JvmKey, Result<Arc<JavaVM>, String>);
+static JVM: Mutex<Option<SharedJvm>> = Mutex::new(None);
+
+/// The process's JVM, started on first use. Later loaders with the same classpath and
+/// settings share it instead of paying for another startup, which JNI would refuse anyway.
+pub fn create_jvm(classpath: &str, settings: &JvmSettings) -> Result<Arc<JavaVM>, LoaderError> {
+    let key = JvmKey {
+        classpath: classpath.to_string(),
+        options: settings.options.to_vec(),
+        sandbox: settings.sandbox,
+        deterministic_time: settings.deterministic_time,
+    };
+    let mut running = JVM.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some((started, jvm)) = running.as_ref() {
+        if *started != key {
+            return Err(LoaderError::Environment(format!(
+                "A JVM with classpath {:?} is already running in this process and cannot be \
+                 restarted; use the same classpath and Java settings for every loader",
+                started.classpath
+            )));
+        }
+        debug!("Reusing the running JVM");
+        let jvm = jvm.clone().map_err(LoaderError::Environment)?;
+        check_sdk(&jvm)?;
+        return Ok(jvm);
+    }
+    let jvm = start_jvm(classpath, settings).map_err(LoaderError::environment)?;
+    let configured = configure_jvm(&jvm, settings);
+    let shared = match &configured {
+        Ok(()) => Ok(jvm.clone()),
+        Err(err) => Err(err.to_string()),
+    };
+    *running = Some((key, shared));
+    configured.map(|()| jvm)
+}
+
+/// Check the SDK and install the sandbox and simulated clocks, which must happen before any
+/// protocol class loads.
+fn configure_jvm(jvm: &JavaVM, settings: &JvmSettings) -> Result<(), LoaderError> {
+    check_sdk(jvm)?;
+    if settings.sandbox {
+        install_sandbox(jvm)?;
+    }
+    if settings.deterministic_time {
+        let mut env = jvm
+            .attach_current_thread()
+            .map_err(LoaderError::environment)?;
+        tcp_lab_jni::register_sim_clock(&mut env).map_err(|err| {
+            LoaderError::Environment(format!(
+                "Failed to bind the Java clocks to simulated time: {}",
+                err
+            ))
+        })?;
+    }
+    Ok(())
+}
+
+fn start_jvm(classpath: &str, settings: &JvmSettings) -> anyhow::Result<Arc<JavaVM>> {
+    let classpath = expand_classpath(classpath)?;
+    debug!("JVM classpath: {}", classpath);
+    let shim_dir = locate_jni_shim()?;
+    let libjvm = locate_libjvm()?;
+    prepare_dll_search_path(&libjvm);
+
+    let mut jvm_args = InitArgsBuilder::new()
         .version(jni::JNIVersion::V8)
         .option(format!("-Djava.class.path={}", classpath))
-        .option(format!("-Djava.library.path={}", lib_path.display()))
-        .build()?;
+        .option(format!("-Djava.library.path={}", shim_dir.display()));
+    let options = settings.options;
+    let major = java_major_version(&libjvm);
+    if !options.iter().any(|option| limits_heap(option)) {
+        jvm_args = jvm_args.option(DEFAULT_MAX_HEAP);
+    }
+    // JDK 18+ refuses System.setSecurityManager unless allowed at startup, while JDK 8-11
+    // would read `allow` as a class name; only JDK 12+ understands the value, and JDK 24+
+    // refuses to start with it.
+    if settings.sandbox && major.is_some_and(|major| (12..24).contains(&major)) {
+        jvm_args = jvm_args.option("-Djava.security.manager=allow");
+    }
+    if settings.deterministic_time {
+        // Compiled code would otherwise read the OS clock directly instead of the natives.
+        jvm_args = jvm_args
+            .option("-XX:+UnlockDiagnosticVMOptions")
+            .option("-XX:DisableIntrinsic=_currentTimeMillis,_nanoTime");
+        if major.is_some_and(|major| major >= 9) {
+            // Silences the warning about re-registering platform natives.
+            jvm_args = jvm_args.option("-Xlog:jni+resolve=error");
+        }
+    }
+    for option in options {
+        jvm_args = jvm_args.option(option.as_str());
+    }
+    debug!("JVM options: {:?}", options);
+    let jvm_args = jvm_args
+        .build()
+        .with_context(|| format!("Invalid JVM options {:?}", options))?;
 
-    let jvm = JavaVM::new(jvm_args)?;
+    let jvm = JavaVM::with_libjvm(jvm_args, || Ok(&libjvm)).with_context(|| {
+        format!(
+            "Failed to start the JVM from {}. Make sure JAVA_HOME points to a 64-bit JDK 8 or newer",
+            libjvm.display()
+        )
+    })?;
     {
         let mut env = jvm.attach_current_thread()?;
         tcp_lab_jni::register_native_methods(&mut env)?;
@@ -22,16 +156,585 @@ pub fn create_jvm(classpath: &str) -> anyhow::Result<Arc<JavaVM>> {
     Ok(Arc::new(jvm))
 }
 
+/// Check the Java SDK on the classpath against [`crate::SDK_VERSION`]. A classpath without
+/// the SDK is left to the protocol lookup to report.
+fn check_sdk(jvm: &JavaVM) -> Result<(), LoaderError> {
+    let mut env = jvm
+        .attach_current_thread()
+        .map_err(LoaderError::environment)?;
+    let Some(cls) = attempt(&mut env, |env| env.find_class(PROTOCOL_INTERFACE)) else {
+        return Ok(());
+    };
+    let version = attempt(&mut env, |env| {
+        env.get_static_field(&cls, "SDK_VERSION", "I")?.i()
+    });
+    check_sdk_version(
+        "sdk/java",
+        version.and_then(|version| u32::try_from(version).ok()),
+    )
+}
+
+fn install_sandbox(jvm: &JavaVM) -> Result<(), LoaderError> {
+    let mut env = jvm
+        .attach_current_thread()
+        .map_err(LoaderError::environment)?;
+    let Some(cls) = attempt(&mut env, |env| {
+        env.find_class("com/ouc/tcp/sdk/GradingSandbox")
+    }) else {
+        return Err(LoaderError::Environment(
+            "The tcp-lab Java SDK on the classpath has no GradingSandbox; rebuild the \
+             submission against the current sdk/java to grade it sandboxed"
+                .to_string(),
+        ));
+    };
+    if env.call_static_method(&cls, "install", "()V", &[]).is_err() {
+        let reason = pending_exception(&mut env).unwrap_or_default();
+        if reason.starts_with("java.lang.UnsupportedOperationException") {
+            // JDK 24 removed the security manager; grading still works, only unsandboxed.
+            warn!(
+                "The JVM cannot install the Java grading sandbox ({}), so Java submissions \
+                 run unsandboxed. JDK 24 removed the security manager; grade with JDK 8 to 23 \
+                 to sandbox them",
+                reason
+            );
+            return Ok(());
+        }
+        return Err(LoaderError::Environment(format!(
+            "Failed to install the Java grading sandbox: {}",
+            reason
+        )));
+    }
+    debug!("Java grading sandbox installed");
+    Ok(())
+}
+
+/// Clear the pending Java exception and return its `toString()`.
+fn pending_exception(env: &mut JNIEnv) -> Option<String> {
+    let throwable = env.exception_occurred().ok()?;
+    env.exception_clear().ok()?;
+    let text = env
+        .call_method(&throwable, "toString", "()Ljava/lang/String;", &[])
+        .and_then(|value| value.l())
+        .ok()?;
+    env.get_string(&text.into()).ok().map(Into::into)
+}
+
+/// Major version from the `release` file of the JDK holding `libjvm` (`1.8.0_392` is 8).
+fn java_major_version(libjvm: &Path) -> Option<u32> {
+    let release = libjvm
+        .ancestors()
+        .skip(1)
+        .take(4)
+        .map(|dir| dir.join("release"))
+        .find(|path| path.is_file())?;
+    let content = std::fs::read_to_string(release).ok()?;
+    let version = content
+        .lines()
+        .find_map(|line| line.strip_prefix("JAVA_VERSION="))?
+        .trim_matches('"');
+    let mut parts = version.split(['.', '_', '-', '+']);
+    match parts.next()? {
+        "1" => parts.next()?.parse().ok(),
+        major => major.parse().ok(),
+    }
+}
+
+fn limits_heap(option: &str) -> bool {
+    [
+        "-Xmx",
+        "-XX:MaxHeapSize=",
+        "-XX:MaxRAM=",
+        "-XX:MaxRAMPercentage=",
+    ]
+    .iter()
+    .any(|prefix| option.starts_with(prefix))
+}
+
 pub fn load_protocol(
     jvm: &Arc<JavaVM>,
     class_name: &str,
-) -> anyhow::Result<Box<dyn TransportProtocol>> {
-    let mut env = jvm.attach_current_thread()?;
+) -> Result<Box<dyn TransportProtocol>, LoaderError> {
+    let mut env = jvm
+        .attach_current_thread()
+        .map_err(LoaderError::environment)?;
 
-    let class_path = class_name.replace(".", "/");
-    let cls = env.find_class(&class_path)?;
-    let obj = env.new_object(cls, "()V", &[])?;
-    let global = env.new_global_ref(obj)?;
+    let (cls, internal_name) =
+        find_protocol_class(&mut env, class_name).map_err(LoaderError::unloadable)?;
+    let obj = instantiate(&mut env, &cls, &internal_name)?;
+    if !env
+        .is_instance_of(&obj, PROTOCOL_INTERFACE)
+        .map_err(LoaderError::environment)?
+    {
+        return Err(not_a_protocol(class_name));
+    }
+    let global = env.new_global_ref(obj).map_err(LoaderError::environment)?;
 
     Ok(Box::new(JavaTransportProtocol::new(jvm.clone(), global)))
 }
+
+/// Check that `class_name` could be loaded without calling its constructor or factory: the
+/// class exists, one of the ways `instantiate` obtains an instance applies, and the instance's
+/// type implements the SDK interface. Looking up a static field still initializes its class,
+/// so a Kotlin or Scala `object` is constructed by its static initializer.
+pub fn check_protocol(jvm: &Arc<JavaVM>, class_name: &str) -> Result<(), LoaderError> {
+    let mut env = jvm
+        .attach_current_thread()
+        .map_err(LoaderError::environment)?;
+
+    let (cls, internal_name) =
+        find_protocol_class(&mut env, class_name).map_err(LoaderError::unloadable)?;
+    let instance_class = instance_class(&mut env, &cls, &internal_name)
+        .ok_or_else(|| no_instance_source(&internal_name))?;
+    if !env
+        .is_assignable_from(&instance_class, PROTOCOL_INTERFACE)
+        .map_err(LoaderError::environment)?
+    {
+        return Err(not_a_protocol(class_name));
+    }
+    Ok(())
+}
+
+/// The class of the instance `instantiate` would return, found through the same fields and
+/// methods but without reading or calling them.
+fn instance_class<'local>(
+    env: &mut JNIEnv<'local>,
+    cls: &JClass<'local>,
+    internal_name: &str,
+) -> Option<JClass<'local>> {
+    let self_sig = format!("L{};", internal_name);
+    let own_class = |env: &mut JNIEnv<'local>| env.new_local_ref(cls).ok().map(JClass::from);
+
+    for field in ["INSTANCE", "MODULE$"] {
+        if attempt(env, |env| env.get_static_field_id(cls, field, &self_sig)).is_some() {
+            return own_class(env);
+        }
+    }
+
+    let module_name = format!("{}$", internal_name);
+    if let Some(module) = attempt(env, |env| env.find_class(module_name.as_str())) {
+        let module_sig = format!("L{};", module_name);
+        if attempt(env, |env| {
+            env.get_static_field_id(&module, "MODULE$", &module_sig)
+        })
+        .is_some()
+        {
+            return Some(module);
+        }
+    }
+
+    if attempt(env, |env| env.get_method_id(cls, "<init>", "()V")).is_some() {
+        return own_class(env);
+    }
+
+    let self_factory = format!("()L{};", internal_name);
+    let interface_factory = format!("()L{};", PROTOCOL_INTERFACE);
+    if attempt(env, |env| {
+        env.get_static_method_id(cls, "create", &self_factory)
+    })
+    .is_some()
+    {
+        return own_class(env);
+    }
+    if attempt(env, |env| {
+        env.get_static_method_id(cls, "create", &interface_factory)
+    })
+    .is_some()
+    {
+        return attempt(env, |env| env.find_class(PROTOCOL_INTERFACE));
+    }
+
+    let companion_name = format!("{}$Companion", internal_name);
+    let companion_sig = format!("L{};", companion_name);
+    if attempt(env, |env| {
+        env.get_static_field_id(cls, "Companion", &companion_sig)
+    })
+    .is_some()
+    {
+        let companion = attempt(env, |env| env.find_class(companion_name.as_str()))?;
+        if attempt(env, |env| {
+            env.get_method_id(&companion, "create", &self_factory)
+        })
+        .is_some()
+        {
+            return own_class(env);
+        }
+        if attempt(env, |env| {
+            env.get_method_id(&companion, "create", &interface_factory)
+        })
+        .is_some()
+        {
+            return attempt(env, |env| env.find_class(PROTOCOL_INTERFACE));
+        }
+    }
+    None
+}
+
+/// Resolve a class name as written in source. Nested classes are compiled to `Outer$Inner`,
+/// so when `a.b.Outer.Inner` is not found the trailing dots are retried as `$`.
+fn find_protocol_class<'local>(
+    env: &mut JNIEnv<'local>,
+    class_name: &str,
+) -> anyhow::Result<(JClass<'local>, String)> {
+    let mut candidates = vec![class_name.replace('.', "/")];
+    let mut nested = candidates[0].clone();
+    while let Some(pos) = nested.rfind('/') {
+        nested.replace_range(pos..=pos, "$");
+        candidates.push(nested.clone());
+    }
+
+    for candidate in &candidates {
+        if let Some(cls) = attempt(env, |env| env.find_class(candidate.as_str())) {
+            return Ok((cls, candidate.clone()));
+        }
+    }
+    bail!(
+        "Class {} not found on the classpath (tried {}). Check --classpath and the package name",
+        class_name,
+        candidates.join(", ")
+    )
+}
+
+/// Obtain a protocol instance the way each JVM language exposes one: a Kotlin `object`
+/// through its static `INSTANCE`, a Scala `object` through `Name$.MODULE$`, a plain class
+/// through its no-arg constructor, and otherwise a static or companion `create()` factory.
+fn instantiate<'local>(
+    env: &mut JNIEnv<'local>,
+    cls: &JClass<'local>,
+    internal_name: &str,
+) -> Result<JObject<'local>, LoaderError> {
+    let self_sig = format!("L{};", internal_name);
+
+    // Kotlin `object Foo` / Scala `Foo$` module class named directly.
+    for field in ["INSTANCE", "MODULE$"] {
+        if let Some(obj) = static_object(env, cls, field, &self_sig) {
+            debug!("Using {}.{} as protocol instance", internal_name, field);
+            return Ok(obj);
+        }
+    }
+
+    // Scala `object Foo`: `Foo` is only a mirror class of static forwarders.
+    let module_name = format!("{}$", internal_name);
+    if let Some(module) = attempt(env, |env| env.find_class(module_name.as_str())) {
+        let module_sig = format!("L{};", module_name);
+        if let Some(obj) = static_object(env, &module, "MODULE$", &module_sig) {
+            debug!("Using {}.MODULE$ as protocol instance", module_name);
+            return Ok(obj);
+        }
+    }
+
+    if attempt(env, |env| env.get_method_id(cls, "<init>", "()V")).is_some() {
+        return match env.new_object(cls, "()V", &[]) {
+            Ok(obj) => Ok(obj),
+            Err(err) => {
+                describe_exception(env);
+                Err(LoaderError::StudentRuntime(format!(
+                    "Failed to instantiate Java protocol {}: the no-argument constructor threw: {}",
+                    internal_name.replace('/', "."),
+                    err
+                )))
+            }
+        };
+    }
+
+    let factory_sigs = [
+        format!("()L{};", internal_name),
+        format!("()L{};", PROTOCOL_INTERFACE),
+    ];
+    for sig in &factory_sigs {
+        if let Some(obj) = attempt(env, |env| {
+            env.call_static_method(cls, "create", sig.as_str(), &[])?
+                .l()
+        })
+        .filter(|obj| !obj.is_null())
+        {
+            return Ok(obj);
+        }
+    }
+
+    // Kotlin `companion object { fun create() }` without @JvmStatic.
+    let companion_sig = format!("L{}$Companion;", internal_name);
+    if let Some(companion) = static_object(env, cls, "Companion", &companion_sig) {
+        for sig in &factory_sigs {
+            if let Some(obj) = attempt(env, |env| {
+                env.call_method(&companion, "create", sig.as_str(), &[])?
+                    .l()
+            })
+            .filter(|obj| !obj.is_null())
+            {
+                return Ok(obj);
+            }
+        }
+    }
+
+    Err(no_instance_source(internal_name))
+}
+
+fn no_instance_source(internal_name: &str) -> LoaderError {
+    LoaderError::Unloadable(format!(
+        "{} has no public no-argument constructor, Kotlin `object` INSTANCE, Scala `object` \
+         MODULE$ or static/companion `create()` factory. Give the class a constructor without \
+         parameters (in Kotlin, default every constructor parameter or add `constructor() : this(...)`)",
+        internal_name.replace('/', ".")
+    ))
+}
+
+fn not_a_protocol(class_name: &str) -> LoaderError {
+    LoaderError::Unloadable(format!(
+        "{} does not implement com.ouc.tcp.sdk.TransportProtocol. For a Scala `object`, \
+         make the object itself extend TransportProtocol",
+        class_name
+    ))
+}
+
+/// Read a non-null static object field, treating a missing field as `None`.
+fn static_object<'local>(
+    env: &mut JNIEnv<'local>,
+    cls: &JClass<'local>,
+    field: &str,
+    sig: &str,
+) -> Option<JObject<'local>> {
+    attempt(env, |env| env.get_static_field(cls, field, sig)?.l()).filter(|obj| !obj.is_null())
+}
+
+/// Run a lookup whose failure is expected while probing, clearing the pending Java exception.
+fn attempt<'local, T>(
+    env: &mut JNIEnv<'local>,
+    op: impl FnOnce(&mut JNIEnv<'local>) -> jni::errors::Result<T>,
+) -> Option<T> {
+    match op(env) {
+        Ok(value) => Some(value),
+        Err(_) => {
+            let _ = env.exception_clear();
+            None
+        }
+    }
+}
+
+fn describe_exception(env: &mut JNIEnv) {
+    if env.exception_check().unwrap_or(false) {
+        let _ = env.exception_describe();
+        let _ = env.exception_clear();
+    }
+}
+
+/// Expand classpath entries the way the `java` launcher does, since the JVM itself
+/// takes `java.class.path` literally: `dir/*` becomes every jar in `dir`, and a plain
+/// directory additionally contributes the jars directly inside it (e.g. Maven's
+/// `target/dependency`). Entries use the platform separator (`;` on Windows, `:` elsewhere).
+fn expand_classpath(classpath: &str) -> anyhow::Result<String> {
+    let mut entries: Vec<PathBuf> = Vec::new();
+    for entry in std::env::split_paths(classpath) {
+        if entry.as_os_str().is_empty() {
+            continue;
+        }
+        if entry.file_name().is_some_and(|name| name == "*") {
+            let dir = entry.parent().unwrap_or(Path::new("."));
+            let jars = jars_in(dir)
+                .with_context(|| format!("Failed to expand classpath entry {}", entry.display()))?;
+            if jars.is_empty() {
+                warn!("Classpath entry {} matched no jars", entry.display());
+            }
+            entries.extend(jars);
+        } else if entry.is_dir() {
+            let jars = jars_in(&entry).unwrap_or_default();
+            entries.push(entry);
+            entries.extend(jars);
+        } else {
+            if !entry.exists() {
+                warn!("Classpath entry {} does not exist", entry.display());
+            }
+            entries.push(entry);
+        }
+    }
+
+    let joined: OsString = std::env::join_paths(&entries).context("Invalid classpath entry")?;
+    joined
+        .into_string()
+        .map_err(|cp| anyhow::anyhow!("Classpath is not valid UTF-8: {:?}", cp))
+}
+
+/// Jar files directly inside `dir`, sorted for a deterministic classpath.
+fn jars_in(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut jars: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("jar"))
+        })
+        .collect();
+    jars.sort();
+    Ok(jars)
+}
+
+/// Find the directory holding the JNI shim so the SDK's `System.loadLibrary` succeeds.
+fn locate_jni_shim() -> anyhow::Result<PathBuf> {
+    let file_name = format!("{}tcp_lab_jni{}", DLL_PREFIX, DLL_SUFFIX);
+
+    let mut candidates = Vec::new();
+    if let Some(path) = std::env::var_os(JNI_PATH_ENV) {
+        let path = PathBuf::from(path);
+        if path.is_file() {
+            candidates.push(path);
+        } else {
+            candidates.push(path.join(&file_name));
+        }
+    }
+    if let Some(exe_dir) = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+    {
+        candidates.push(exe_dir.join(&file_name));
+    }
+    if let Some(dir) = BUILD_PROFILE_DIR {
+        candidates.push(Path::new(dir).join(&file_name));
+    }
+    if let Ok(cwd) = std::env::current_dir() {
+        candidates.push(cwd.join("target").join("debug").join(&file_name));
+        candidates.push(cwd.join("target").join("release").join(&file_name));
+    }
+
+    if let Some(found) = candidates.iter().find(|p| p.is_file()) {
+        let dir = found.parent().unwrap_or(Path::new("."));
+        return Ok(dir.to_path_buf());
+    }
+
+    let searched: Vec<String> = candidates
+        .iter()
+        .map(|p| format!("  {}", p.display()))
+        .collect();
+    bail!(
+        "Could not find the JNI shim {}. Build it with `cargo build -p tcp-lab-jni` or set {} \
+         to the directory containing it. Searched:\n{}",
+        file_name,
+        JNI_PATH_ENV,
+        searched.join("\n")
+    )
+}
+
+/// Find the JVM shared library, preferring the layout under JAVA_HOME.
+fn locate_libjvm() -> anyhow::Result<PathBuf> {
+    let file_name = java_locator::get_jvm_dyn_lib_file_name();
+    let java_home = match std::env::var_os("JAVA_HOME").filter(|v| !v.is_empty()) {
+        Some(home) => Some(PathBuf::from(home)),
+        // Falls back to resolving the `java` executable on PATH.
+        None => java_locator::locate_java_home().ok().map(PathBuf::from),
+    };
+
+    if let Some(home) = &java_home {
+        // JDK 9+ first, then the JDK 8 `jre/` layouts.
+        let relative: &[&str] = if cfg!(windows) {
+            &[
+                "bin/server",
+                "bin/client",
+                "jre/bin/server",
+                "jre/bin/client",
+            ]
+        } else {
+            &[
+                "lib/server",
+                "jre/lib/server",
+                "jre/lib/amd64/server",
+                "jre/lib/aarch64/server",
+                "lib/amd64/server",
+            ]
+        };
+        if let Some(found) = relative
+            .iter()
+            .map(|dir| home.join(dir).join(file_name))
+            .find(|p| p.is_file())
+        {
+            return Ok(found);
+        }
+    }
+
+    // Non-standard layouts: let java-locator search the whole installation.
+    if let Ok(dir) = java_locator::locate_jvm_dyn_library() {
+        return Ok(Path::new(&dir).join(file_name));
+    }
+
+    match java_home {
+        Some(home) => bail!(
+            "Could not find {} under {}. Point JAVA_HOME at a JDK 8+ installation \
+             (the directory containing `bin/java`), not at its `bin` folder",
+            file_name,
+            home.display()
+        ),
+        None => bail!(
+            "Could not find a Java installation: JAVA_HOME is not set and `java` is not on PATH. \
+             Install a JDK 8+ and set JAVA_HOME to its root directory"
+        ),
+    }
+}
+
+/// `jvm.dll` depends on DLLs that live in `%JAVA_HOME%\bin`, which Windows only finds via PATH.
+#[cfg(windows)]
+fn prepare_dll_search_path(libjvm: &Path) {
+    // <home>\bin\server\jvm.dll or <home>\jre\bin\server\jvm.dll
+    let Some(bin_dir) = libjvm.parent().and_then(Path::parent) else {
+        return;
+    };
+    let mut paths = vec![bin_dir.to_path_buf()];
+    if let Some(path) = std::env::var_os("PATH") {
+        paths.extend(std::env::split_paths(&path));
+    }
+    if let Ok(joined) = std::env::join_paths(paths) {
+        // SAFETY: called while the loader is still single-threaded, before the JVM starts.
+        unsafe { std::env::set_var("PATH", joined) };
+    }
+}
+
+#[cfg(not(windows))]
+fn prepare_dll_search_path(_libjvm: &Path) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_expand_classpath() {
+        let dir = std::env::temp_dir().join(format!("tcp-lab-classpath-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("libs/nested.jar")).unwrap();
+        fs::create_dir_all(dir.join("classes")).unwrap();
+        for file in [
+            "libs/b.jar",
+            "libs/a.JAR",
+            "libs/notes.txt",
+            "classes/c.jar",
+            "extra.zip",
+        ] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        let classpath = std::env::join_paths([
+            dir.join("libs/*"),
+            PathBuf::new(),
+            dir.join("classes"),
+            dir.join("extra.zip"),
+        ])
+        .unwrap()
+        .into_string()
+        .unwrap();
+        let expanded = expand_classpath(&classpath);
+        let missing = expand_classpath(dir.join("missing/*").to_str().unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+
+        // `libs/*` keeps only the jar files, a directory also brings its own jars, and
+        // anything else passes through; the empty entry is dropped.
+        let entries: Vec<PathBuf> = std::env::split_paths(&expanded.unwrap()).collect();
+        assert_eq!(
+            entries,
+            [
+                dir.join("libs/a.JAR"),
+                dir.join("libs/b.jar"),
+                dir.join("classes"),
+                dir.join("classes/c.jar"),
+                dir.join("extra.zip"),
+            ]
+        );
+        let err = missing.unwrap_err();
+        assert!(format!("{err:#}").contains("missing"), "{err:#}");
+    }
+}
@@ -1,18 +1,22 @@
+pub mod bench;
 mod builtin;
+pub mod conformance;
 #[cfg(feature = "cpp")]
 pub mod cpp;
+mod error;
+pub mod examples;
 #[cfg(feature = "java")]
 mod java;
 #[cfg(feature = "python")]
 pub mod python;
 pub mod spec;
 
+pub use error::LoaderError;
+
 use anyhow::Result;
-use std::path::PathBuf;
-use tcp_lab_abstract::TransportProtocol;
+use std::path::{Path, PathBuf};
+use tcp_lab_abstract::{NodeId, ProtocolFactory, TransportProtocol};
 
-#[cfg(feature = "java")]
-use anyhow::Context;
 #[cfg(feature = "java")]
 use java::create_jvm;
 #[cfg(feature = "java")]
@@ -32,9 +36,37 @@ type JavaVmHandle = ();
 #[cfg(feature = "java")]
 type JavaVmHandle = Arc<JavaVM>;
 
-/// Built-in Rust implementations that can be used without loading external code.
+/// Version of the language SDKs in `sdk/` that this loader's bridges are written against.
+/// Bumped with `SDK_VERSION` in `sdk/python/tcp_lab`, `TransportProtocol.SDK_VERSION` in
+/// `sdk/java` and `TCP_LAB_SDK_VERSION` in `sdk/cpp` whenever an SDK changes in a way the
+/// bridges rely on, such as a new callback.
+pub const SDK_VERSION: u32 = 2;
+
+/// Check the SDK version a submission declares, `None` if its SDK copy predates the
+/// version constant. `sdk` names the SDK directory, e.g. `sdk/java`.
+pub fn check_sdk_version(sdk: &str, found: Option<u32>) -> Result<(), LoaderError> {
+    match found {
+        Some(version) if version == SDK_VERSION => Ok(()),
+        Some(version) if version > SDK_VERSION => Err(LoaderError::Environment(format!(
+            "the submission uses {sdk} version {version}, but this grader only supports \
+             version {SDK_VERSION}; update tcp-lab"
+        ))),
+        found => Err(LoaderError::Unloadable(format!(
+            "the submission's copy of {sdk} is {}, but this grader needs version \
+             {SDK_VERSION}; update it from the course repository",
+            found.map_or("older than version 1".to_string(), |version| format!(
+                "version {version}"
+            ))
+        ))),
+    }
+}
+
+/// Built-in Rust implementations that can be used without loading external code. They
+/// are listed with descriptions by [`examples::catalog`].
 #[derive(Clone, Copy, Debug)]
 pub enum BuiltinProtocol {
+    Rdt1Sender,
+    Rdt1Receiver,
     Rdt2Sender,
     Rdt2Receiver,
 }
@@ -48,6 +80,26 @@ pub enum ProtocolDescriptor {
     Rust(Box<dyn TransportProtocol>),
 }
 
+impl ProtocolDescriptor {
+    /// A copy of this descriptor, or `None` for a `Rust` instance, which cannot be re-created.
+    pub fn try_clone(&self) -> Option<Self> {
+        Some(match self {
+            Self::BuiltIn(builtin) => Self::BuiltIn(*builtin),
+            Self::Java { class_name } => Self::Java {
+                class_name: class_name.clone(),
+            },
+            Self::Python { module, class_name } => Self::Python {
+                module: module.clone(),
+                class_name: class_name.clone(),
+            },
+            Self::Cpp { library_path } => Self::Cpp {
+                library_path: library_path.clone(),
+            },
+            Self::Rust(_) => return None,
+        })
+    }
+}
+
 /// Pair of protocol descriptors used by the loader.
 #[derive(Default)]
 pub struct LoaderRequest {
@@ -55,6 +107,23 @@ pub struct LoaderRequest {
     pub receiver: Option<ProtocolDescriptor>,
 }
 
+impl LoaderRequest {
+    /// A copy for loading the pair again, or `None` if either side is a `Rust` instance.
+    pub fn try_clone(&self) -> Option<Self> {
+        let copy = |descriptor: &Option<ProtocolDescriptor>| match descriptor {
+            Some(descriptor) => descriptor.try_clone().map(Some),
+            None => Some(None),
+        };
+        Some(Self {
+            sender: copy(&self.sender)?,
+            receiver: copy(&self.receiver)?,
+        })
+    }
+}
+
+/// Sender and receiver instances returned by [`ProtocolLoader::load_pair`].
+pub type LoadedPair = (Box<dyn TransportProtocol>, Box<dyn TransportProtocol>);
+
 /// Python environment configuration. Allows pointing to a uv-managed project
 /// as well as adding ad-hoc search paths.
 #[derive(Default, Clone)]
@@ -78,7 +147,11 @@ impl PythonConfig {
 /// Builder for the loader. Allows configuring shared state (e.g. JVM, uv env).
 pub struct LoaderBuilder {
     java_classpath: Option<String>,
+    jvm_options: Vec<String>,
     python: Option<PythonConfig>,
+    sandbox: bool,
+    deterministic_time: bool,
+    debug_attach: Option<std::time::Duration>,
 }
 
 impl Default for LoaderBuilder {
@@ -91,7 +164,11 @@ impl LoaderBuilder {
     pub fn new() -> Self {
         Self {
             java_classpath: None,
+            jvm_options: Vec::new(),
             python: None,
+            sandbox: false,
+            deterministic_time: false,
+            debug_attach: None,
         }
     }
 
@@ -100,24 +177,65 @@ impl LoaderBuilder {
         self
     }
 
+    /// Pass an option such as `-Xmx256m` or `-ea` to the JVM. Without a heap limit among
+    /// them the JVM is capped at 512 MiB.
+    pub fn jvm_option(mut self, option: impl Into<String>) -> Self {
+        self.jvm_options.push(option.into());
+        self
+    }
+
+    /// Restrict student code for graded runs. Java protocols run under the SDK's
+    /// `GradingSandbox` security manager (JDK 8 to 23) and Python protocols under an audit
+    /// hook: writing files, network access, starting processes and (in Java) `System.exit`
+    /// are blocked and reported to the simulator as integrity violations.
+    pub fn sandbox(mut self, sandbox: bool) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
+    /// Bind student code's clocks to simulated time, so a protocol that reads the wall clock
+    /// instead of `ctx.now()` still behaves the same on every run: Python's `time.time`,
+    /// `time.monotonic` and `time.perf_counter` (and their `_ns` forms), and Java's
+    /// `System.currentTimeMillis` and `System.nanoTime` (HotSpot). The clocks stand still
+    /// within a callback. `datetime.now()` and `java.time.Instant.now()` still read the
+    /// real clock.
+    pub fn deterministic_time(mut self, deterministic: bool) -> Self {
+        self.deterministic_time = deterministic;
+        self
+    }
+
+    /// Pause for `pause` before every callback into a C++ library, printing the process ID
+    /// and the packet or timer about to be handled, so students can attach gdb or lldb to
+    /// their library at the moment they care about. Set `TCP_LAB_DEBUG_ATTACH_NOTIFY` to a
+    /// shell command to be notified as well (see `cpp::loader::DebugAttach`).
+    pub fn debug_attach(mut self, pause: Option<std::time::Duration>) -> Self {
+        self.debug_attach = pause;
+        self
+    }
+
     pub fn python_config(mut self, config: PythonConfig) -> Self {
         self.python = Some(config);
         self
     }
 
-    pub fn build(self) -> Result<ProtocolLoader> {
+    pub fn build(self) -> Result<ProtocolLoader, LoaderError> {
         #[cfg(feature = "java")]
-        let java_vm = init_java(self.java_classpath)?;
+        let java_vm = init_java(&self)?;
         #[cfg(not(feature = "java"))]
         {
-            let _ = init_java(self.java_classpath)?;
+            let _ = init_java(&self)?;
         }
 
         #[cfg(feature = "python")]
-        let python_env = init_python(self.python)?;
+        let python_env = init_python(self.python).map_err(LoaderError::environment)?;
         #[cfg(not(feature = "python"))]
         {
-            let _ = init_python(self.python)?;
+            let _ = init_python(self.python).map_err(LoaderError::environment)?;
+        }
+        // Only C++ callbacks pause, so the option means nothing without the bridge.
+        #[cfg(not(feature = "cpp"))]
+        if self.debug_attach.is_some() {
+            return Err(cpp_disabled());
         }
 
         Ok(ProtocolLoader {
@@ -125,24 +243,45 @@ impl LoaderBuilder {
             java_vm,
             #[cfg(feature = "python")]
             python_env,
+            #[cfg(feature = "python")]
+            python_hooks: python::loader::RuntimeHooks {
+                sandbox: self.sandbox,
+                deterministic_time: self.deterministic_time,
+            },
+            #[cfg(feature = "cpp")]
+            cpp_copies: Default::default(),
+            #[cfg(feature = "cpp")]
+            cpp_debug_attach: self.debug_attach.map(cpp::loader::DebugAttach::from_env),
         })
     }
 }
 
-fn init_java(classpath: Option<String>) -> Result<Option<JavaVmHandle>> {
+fn init_java(builder: &LoaderBuilder) -> Result<Option<JavaVmHandle>, LoaderError> {
     #[cfg(feature = "java")]
     {
-        if let Some(cp) = classpath {
-            let vm = create_jvm(&cp)?;
+        if let Some(cp) = &builder.java_classpath {
+            let settings = java::JvmSettings {
+                options: &builder.jvm_options,
+                sandbox: builder.sandbox,
+                deterministic_time: builder.deterministic_time,
+            };
+            let vm = create_jvm(cp, &settings)?;
             Ok(Some(vm))
         } else {
+            if !builder.jvm_options.is_empty() {
+                return Err(LoaderError::Environment(
+                    "JVM options provided without a Java classpath".to_string(),
+                ));
+            }
             Ok(None)
         }
     }
     #[cfg(not(feature = "java"))]
     {
-        if classpath.is_some() {
-            anyhow::bail!("`java` feature disabled but Java classpath provided");
+        if builder.java_classpath.is_some() || !builder.jvm_options.is_empty() {
+            return Err(LoaderError::Environment(
+                "`java` feature disabled but Java classpath or JVM options provided".to_string(),
+            ));
         }
         Ok(None)
     }
@@ -160,6 +299,8 @@ fn init_python(config: Option<PythonConfig>) -> Result<Option<PythonEnvironment>
                 return Ok(None);
             };
 
+            python::environment::preflight(env.python_home().map(PathBuf::as_path))?;
+
             // Set PYTHONHOME environment variable if available
             if let Some(python_home) = env.python_home() {
                 unsafe {
@@ -182,11 +323,19 @@ fn init_python(config: Option<PythonConfig>) -> Result<Option<PythonEnvironment>
 }
 
 /// Loader capable of instantiating sender/receiver implementations across languages.
+#[derive(Clone)]
 pub struct ProtocolLoader {
     #[cfg(feature = "java")]
     java_vm: Option<JavaVmHandle>,
     #[cfg(feature = "python")]
     python_env: Option<PythonEnvironment>,
+    #[cfg(feature = "python")]
+    python_hooks: python::loader::RuntimeHooks,
+    /// The fresh copy `reload` made of each C++ library, loaded in its place.
+    #[cfg(feature = "cpp")]
+    cpp_copies: std::sync::Arc<std::sync::Mutex<std::collections::HashMap<PathBuf, PathBuf>>>,
+    #[cfg(feature = "cpp")]
+    cpp_debug_attach: Option<cpp::loader::DebugAttach>,
 }
 
 impl ProtocolLoader {
@@ -194,10 +343,15 @@ impl ProtocolLoader {
         LoaderBuilder::new()
     }
 
-    pub fn load_pair(
-        &self,
-        request: LoaderRequest,
-    ) -> Result<(Box<dyn TransportProtocol>, Box<dyn TransportProtocol>)> {
+    /// Load both protocols of a new run. Warnings the bridges still hold from an earlier
+    /// run (see `PendingWarnings`) are dropped, so they do not end up in this run's report.
+    pub fn load_pair(&self, request: LoaderRequest) -> Result<LoadedPair, LoaderError> {
+        #[cfg(feature = "java")]
+        tcp_lab_jni::clear_pending_warnings();
+        #[cfg(feature = "python")]
+        python::context::clear_pending_warnings();
+        #[cfg(feature = "cpp")]
+        tcp_lab_ffi::clear_pending_warnings();
         let sender = match request.sender {
             Some(desc) => self.load(desc)?,
             None => builtin::default_sender(),
@@ -209,12 +363,47 @@ impl ProtocolLoader {
         Ok((sender, receiver))
     }
 
-    pub fn load(&self, descriptor: ProtocolDescriptor) -> Result<Box<dyn TransportProtocol>> {
+    /// A factory that loads a fresh instance on every call, for simulating node restarts.
+    /// `None` picks the built-in default like `load_pair`; `Rust` instances yield `None`.
+    pub fn factory(
+        &self,
+        descriptor: Option<&ProtocolDescriptor>,
+        is_sender: bool,
+    ) -> Option<ProtocolFactory> {
+        let descriptor = match descriptor {
+            Some(desc) => Some(desc.try_clone()?),
+            None => None,
+        };
+        let loader = self.clone();
+        Some(Box::new(move || match &descriptor {
+            Some(desc) => {
+                Ok(loader.load(desc.try_clone().expect("Rust descriptors are excluded"))?)
+            }
+            None if is_sender => Ok(builtin::default_sender()),
+            None => Ok(builtin::default_receiver()),
+        }))
+    }
+
+    /// Factories for both nodes of `request`, skipping any that cannot be re-created.
+    pub fn factories(&self, request: &LoaderRequest) -> Vec<(NodeId, ProtocolFactory)> {
+        [
+            (NodeId::Sender, self.factory(request.sender.as_ref(), true)),
+            (
+                NodeId::Receiver,
+                self.factory(request.receiver.as_ref(), false),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(node, factory)| Some((node, factory?)))
+        .collect()
+    }
+
+    pub fn load(
+        &self,
+        descriptor: ProtocolDescriptor,
+    ) -> Result<Box<dyn TransportProtocol>, LoaderError> {
         match descriptor {
-            ProtocolDescriptor::BuiltIn(builtin) => Ok(match builtin {
-                BuiltinProtocol::Rdt2Sender => builtin::rdt2_sender(),
-                BuiltinProtocol::Rdt2Receiver => builtin::rdt2_receiver(),
-            }),
+            ProtocolDescriptor::BuiltIn(builtin) => Ok(builtin::instantiate(builtin)),
             ProtocolDescriptor::Java { class_name } => self.load_java(&class_name),
             ProtocolDescriptor::Python { module, class_name } => {
                 self.load_python(&module, &class_name)
@@ -224,37 +413,200 @@ impl ProtocolLoader {
         }
     }
 
+    /// Check that `descriptor` would load without constructing a protocol: the class, module
+    /// or library is found, has the callbacks and factory the bridge needs, and (for C++)
+    /// matches this host's ABI version. Lets batch graders report an unloadable submission
+    /// apart from one that loads but fails its tests. Static initializers and Python
+    /// module-level code still run.
+    pub fn check(&self, descriptor: &ProtocolDescriptor) -> Result<(), LoaderError> {
+        match descriptor {
+            ProtocolDescriptor::BuiltIn(_) | ProtocolDescriptor::Rust(_) => Ok(()),
+            ProtocolDescriptor::Java { class_name } => self.check_java(class_name),
+            ProtocolDescriptor::Python { module, class_name } => {
+                self.check_python(module, class_name)
+            }
+            ProtocolDescriptor::Cpp { library_path } => self.check_cpp(library_path),
+        }
+    }
+
+    /// Pick up changes on disk to the implementations of `request`, so that the next `load`
+    /// and the factories run the new code: Python modules are imported again and C++
+    /// libraries are loaded from a fresh copy. The JVM cannot unload classes, so Java
+    /// protocols keep the version it loaded first.
+    pub fn reload(&self, request: &LoaderRequest) -> Result<(), LoaderError> {
+        for descriptor in [&request.sender, &request.receiver].into_iter().flatten() {
+            match descriptor {
+                ProtocolDescriptor::Python { module, .. } => self.reload_python(module)?,
+                ProtocolDescriptor::Cpp { library_path } => self.reload_cpp(library_path)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Let the next run of `request` start from a clean slate while keeping the JVM and the
+    /// Python interpreter, so a grader can run a whole suite in one process. Python modules
+    /// the submission imported run their top-level code again, so module globals start
+    /// over, and C++ libraries are loaded from a fresh copy, which resets their static
+    /// variables. The JVM cannot unload classes, so Java static fields and Kotlin or Scala
+    /// `object`s keep their state from run to run.
+    pub fn reset(&self, request: &LoaderRequest) -> Result<(), LoaderError> {
+        let descriptors = [&request.sender, &request.receiver];
+        let descriptors = descriptors.into_iter().flatten();
+        if descriptors
+            .clone()
+            .any(|descriptor| matches!(descriptor, ProtocolDescriptor::Python { .. }))
+        {
+            self.reset_python()?;
+        }
+        for descriptor in descriptors {
+            if let ProtocolDescriptor::Cpp { library_path } = descriptor {
+                self.reload_cpp(library_path)?;
+            }
+        }
+        Ok(())
+    }
+
     #[cfg(feature = "java")]
-    fn load_java(&self, class_name: &str) -> Result<Box<dyn TransportProtocol>> {
-        let vm = self
-            .java_vm
-            .as_ref()
-            .context("JVM not configured; call LoaderBuilder::java_classpath first")?;
-        java::load_protocol(vm, class_name)
+    fn java_vm(&self) -> Result<&JavaVmHandle, LoaderError> {
+        self.java_vm.as_ref().ok_or_else(|| {
+            LoaderError::Environment(
+                "JVM not configured; call LoaderBuilder::java_classpath first".to_string(),
+            )
+        })
+    }
+
+    #[cfg(feature = "java")]
+    fn load_java(&self, class_name: &str) -> Result<Box<dyn TransportProtocol>, LoaderError> {
+        java::load_protocol(self.java_vm()?, class_name)
+    }
+
+    #[cfg(feature = "java")]
+    fn check_java(&self, class_name: &str) -> Result<(), LoaderError> {
+        java::check_protocol(self.java_vm()?, class_name)
+    }
+
+    #[cfg(not(feature = "java"))]
+    fn load_java(&self, _class_name: &str) -> Result<Box<dyn TransportProtocol>, LoaderError> {
+        Err(java_disabled())
     }
 
     #[cfg(not(feature = "java"))]
-    fn load_java(&self, _class_name: &str) -> Result<Box<dyn TransportProtocol>> {
-        anyhow::bail!("Java support disabled at compile time");
+    fn check_java(&self, _class_name: &str) -> Result<(), LoaderError> {
+        Err(java_disabled())
+    }
+
+    #[cfg(feature = "python")]
+    fn load_python(
+        &self,
+        module: &str,
+        class_name: &str,
+    ) -> Result<Box<dyn TransportProtocol>, LoaderError> {
+        python::loader::load_protocol(
+            module,
+            class_name,
+            self.python_env.as_ref(),
+            self.python_hooks,
+        )
+    }
+
+    #[cfg(feature = "python")]
+    fn check_python(&self, module: &str, class_name: &str) -> Result<(), LoaderError> {
+        python::loader::check_protocol(
+            module,
+            class_name,
+            self.python_env.as_ref(),
+            self.python_hooks,
+        )
+    }
+
+    #[cfg(not(feature = "python"))]
+    fn load_python(
+        &self,
+        _module: &str,
+        _class_name: &str,
+    ) -> Result<Box<dyn TransportProtocol>, LoaderError> {
+        Err(python_disabled())
+    }
+
+    #[cfg(not(feature = "python"))]
+    fn check_python(&self, _module: &str, _class_name: &str) -> Result<(), LoaderError> {
+        Err(python_disabled())
     }
 
     #[cfg(feature = "python")]
-    fn load_python(&self, module: &str, class_name: &str) -> Result<Box<dyn TransportProtocol>> {
-        python::loader::load_protocol(module, class_name, self.python_env.as_ref())
+    fn reload_python(&self, module: &str) -> Result<(), LoaderError> {
+        python::loader::reload_module(module)
     }
 
     #[cfg(not(feature = "python"))]
-    fn load_python(&self, _module: &str, _class_name: &str) -> Result<Box<dyn TransportProtocol>> {
-        anyhow::bail!("Python support disabled at compile time");
+    fn reload_python(&self, _module: &str) -> Result<(), LoaderError> {
+        Err(python_disabled())
+    }
+
+    #[cfg(feature = "python")]
+    fn reset_python(&self) -> Result<(), LoaderError> {
+        python::loader::reset_modules()
+    }
+
+    #[cfg(not(feature = "python"))]
+    fn reset_python(&self) -> Result<(), LoaderError> {
+        Err(python_disabled())
+    }
+
+    #[cfg(feature = "cpp")]
+    fn load_cpp(&self, path: &PathBuf) -> Result<Box<dyn TransportProtocol>, LoaderError> {
+        let copy = self.cpp_copies.lock().unwrap().get(path).cloned();
+        cpp::loader::load_protocol(copy.as_ref().unwrap_or(path), self.cpp_debug_attach.clone())
+    }
+
+    #[cfg(feature = "cpp")]
+    fn reload_cpp(&self, path: &Path) -> Result<(), LoaderError> {
+        let copy = cpp::loader::fresh_copy(path)?;
+        if let Some(previous) = self
+            .cpp_copies
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), copy)
+        {
+            // Still mapped by the protocols loaded from it; only the file goes away.
+            let _ = std::fs::remove_file(previous);
+        }
+        Ok(())
     }
 
     #[cfg(feature = "cpp")]
-    fn load_cpp(&self, path: &PathBuf) -> Result<Box<dyn TransportProtocol>> {
-        cpp::loader::load_protocol(path)
+    fn check_cpp(&self, path: &PathBuf) -> Result<(), LoaderError> {
+        cpp::loader::check_protocol(path)
     }
 
     #[cfg(not(feature = "cpp"))]
-    fn load_cpp(&self, _path: &PathBuf) -> Result<Box<dyn TransportProtocol>> {
-        anyhow::bail!("C++ support disabled at compile time");
+    fn load_cpp(&self, _path: &PathBuf) -> Result<Box<dyn TransportProtocol>, LoaderError> {
+        Err(cpp_disabled())
     }
+
+    #[cfg(not(feature = "cpp"))]
+    fn check_cpp(&self, _path: &PathBuf) -> Result<(), LoaderError> {
+        Err(cpp_disabled())
+    }
+
+    #[cfg(not(feature = "cpp"))]
+    fn reload_cpp(&self, _path: &Path) -> Result<(), LoaderError> {
+        Err(cpp_disabled())
+    }
+}
+
+#[cfg(not(feature = "java"))]
+fn java_disabled() -> LoaderError {
+    LoaderError::Environment("Java support disabled at compile time".to_string())
+}
+
+#[cfg(not(feature = "python"))]
+fn python_disabled() -> LoaderError {
+    LoaderError::Environment("Python support disabled at compile time".to_string())
+}
+
+#[cfg(not(feature = "cpp"))]
+fn cpp_disabled() -> LoaderError {
+    LoaderError::Environment("C++ support disabled at compile time".to_string())
 }
@@ -10,17 +10,16 @@ pub fn to_py_packet<'py>(py: Python<'py>, packet: Packet) -> PyResult<Bound<'py,
     let packet_cls = structs_mod.getattr("Packet")?;
 
     let h = packet.header;
-    let py_header = header_cls.call(
-        (
-            h.seq_num,
-            h.ack_num,
-            h.flags,
-            h.window_size,
-            h.checksum,
-            h.urgent_ptr,
-        ),
-        None,
-    )?;
+    let py_header = header_cls.call1((
+        h.seq_num,
+        h.ack_num,
+        h.flags,
+        h.window_size,
+        h.checksum,
+        h.urgent_ptr,
+        h.src_port,
+        h.dst_port,
+    ))?;
 
     let py_payload = PyBytes::new(py, &packet.payload);
 
@@ -32,6 +31,8 @@ pub fn from_py_packet(obj: &Bound<'_, PyAny>) -> PyResult<Packet> {
     let header_obj = obj.getattr("header")?;
     let payload_obj = obj.getattr("payload")?;
 
+    let src_port: u16 = header_obj.getattr("src_port")?.extract()?;
+    let dst_port: u16 = header_obj.getattr("dst_port")?.extract()?;
     let seq_num: u32 = header_obj.getattr("seq_num")?.extract()?;
     let ack_num: u32 = header_obj.getattr("ack_num")?.extract()?;
     let flags: u8 = header_obj.getattr("flags")?.extract()?;
@@ -42,8 +43,8 @@ pub fn from_py_packet(obj: &Bound<'_, PyAny>) -> PyResult<Packet> {
     let payload: Vec<u8> = payload_obj.extract()?;
 
     let header = TcpHeader {
-        src_port: 0, // Not used in this lab model usually
-        dst_port: 0,
+        src_port,
+        dst_port,
         seq_num,
         ack_num,
         flags,
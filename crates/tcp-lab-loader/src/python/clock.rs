@@ -0,0 +1,36 @@
+//! Simulated wall clock for `LoaderBuilder::deterministic_time`: the `time` module's clocks
+//! are replaced by ones that read the simulation time of the current callback.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use pyo3::prelude::*;
+
+use super::context;
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Patch `time` once per process, before any student module imports it.
+pub fn install(py: Python<'_>) -> PyResult<()> {
+    if INSTALLED.load(Ordering::Acquire) {
+        return Ok(());
+    }
+    let time = py.import("time")?;
+    let seconds = wrap_pyfunction!(sim_seconds, py)?;
+    let nanos = wrap_pyfunction!(sim_nanos, py)?;
+    for name in ["time", "monotonic", "perf_counter"] {
+        time.setattr(name, &seconds)?;
+        time.setattr(format!("{name}_ns"), &nanos)?;
+    }
+    INSTALLED.store(true, Ordering::Release);
+    Ok(())
+}
+
+#[pyfunction]
+fn sim_seconds() -> f64 {
+    context::sim_now_ms() as f64 / 1000.0
+}
+
+#[pyfunction]
+fn sim_nanos() -> u64 {
+    context::sim_now_ms().saturating_mul(1_000_000)
+}
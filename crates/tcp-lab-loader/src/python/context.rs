@@ -1,7 +1,9 @@
 use pyo3::exceptions::PyRuntimeError;
 use pyo3::prelude::*;
 use std::cell::RefCell;
-use tcp_lab_abstract::SystemContext;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tcp_lab_abstract::{BridgeWarning, PendingWarnings, SystemContext};
 
 use super::adapter;
 
@@ -11,11 +13,28 @@ thread_local! {
     static CURRENT_CONTEXT: RefCell<Option<*mut (dyn SystemContext + 'static)>> = RefCell::new(None);
 }
 
+/// Simulated time of the latest callback, for the patched `time` clocks.
+static SIM_NOW_MS: AtomicU64 = AtomicU64::new(0);
+
+/// Warnings raised while no context was installed, reported at the next callback.
+static PENDING_WARNINGS: PendingWarnings = PendingWarnings::new();
+
+/// Drop warnings held from an earlier run, before a new one starts.
+pub(crate) fn clear_pending_warnings() {
+    PENDING_WARNINGS.clear();
+}
+
+pub(crate) fn sim_now_ms() -> u64 {
+    SIM_NOW_MS.load(Ordering::Relaxed)
+}
+
 /// Execute the given closure with the SystemContext active in TLS.
 pub fn with_context<F, R>(ctx: &mut dyn SystemContext, f: F) -> R
 where
     F: FnOnce() -> R,
 {
+    SIM_NOW_MS.store(ctx.now(), Ordering::Relaxed);
+    PENDING_WARNINGS.flush(ctx);
     let ptr = ctx as *mut dyn SystemContext;
     // Transmute to extend lifetime to 'static for storage in TLS.
     // SAFETY: We guarantee that `ptr` is valid for the duration of `f()`
@@ -48,6 +67,10 @@ where
             let ctx = unsafe { &mut *ptr };
             f(ctx)
         } else {
+            PENDING_WARNINGS.push(
+                BridgeWarning::NoContext,
+                "a ctx method was called outside a callback".to_string(),
+            );
             Err(PyRuntimeError::new_err(
                 "SystemContext not active (called outside callback?)",
             ))
@@ -55,9 +78,32 @@ where
     })
 }
 
+/// Pass a sandbox violation to the active context; outside a callback (e.g. while the module
+/// is imported) the `PermissionError` alone reports it.
+pub(crate) fn report_violation(what: &str) {
+    CURRENT_CONTEXT.with(|c| {
+        if let Some(ptr) = *c.borrow() {
+            // SAFETY: as in `use_context`.
+            let ctx = unsafe { &mut *ptr };
+            ctx.report_violation(what);
+        }
+    });
+}
+
+/// Report a call the bridge had to ignore or patch up; outside a callback it waits for
+/// the next one.
+pub(crate) fn report_warning(kind: BridgeWarning, detail: &str) {
+    CURRENT_CONTEXT.with(|c| match *c.borrow() {
+        // SAFETY: as in `use_context`.
+        Some(ptr) => unsafe { &mut *ptr }.report_warning(kind, detail),
+        None => PENDING_WARNINGS.push(kind, detail.to_string()),
+    });
+}
+
 /// The SystemContext implementation exposed to Python.
 /// This class has no state; it proxies calls to the TLS context.
 #[pyclass(name = "SystemContextImpl")]
+#[derive(Default)]
 pub struct PySystemContext;
 
 #[pymethods]
@@ -82,6 +128,13 @@ impl PySystemContext {
         })
     }
 
+    fn start_timer_us(&self, delay_us: u64, timer_id: u32) -> PyResult<()> {
+        use_context(|ctx| {
+            ctx.start_timer_us(delay_us, timer_id);
+            Ok(())
+        })
+    }
+
     fn cancel_timer(&self, timer_id: u32) -> PyResult<()> {
         use_context(|ctx| {
             ctx.cancel_timer(timer_id);
@@ -96,6 +149,13 @@ impl PySystemContext {
         })
     }
 
+    fn deliver_data_at(&self, offset: u64, data: &[u8]) -> PyResult<()> {
+        use_context(|ctx| {
+            ctx.deliver_data_at(offset, data);
+            Ok(())
+        })
+    }
+
     fn log(&self, message: &str) -> PyResult<()> {
         use_context(|ctx| {
             ctx.log(message);
@@ -107,10 +167,80 @@ impl PySystemContext {
         use_context(|ctx| Ok(ctx.now()))
     }
 
+    fn now_us(&self) -> PyResult<u64> {
+        use_context(|ctx| Ok(ctx.now_us()))
+    }
+
     fn record_metric(&self, name: &str, value: f64) -> PyResult<()> {
         use_context(|ctx| {
             ctx.record_metric(name, value);
             Ok(())
         })
     }
+
+    fn record_state(&self, name: &str) -> PyResult<()> {
+        use_context(|ctx| {
+            ctx.record_state(name);
+            Ok(())
+        })
+    }
+
+    fn init_params(&self) -> PyResult<BTreeMap<String, String>> {
+        use_context(|ctx| Ok(ctx.init_params()))
+    }
+
+    fn receive_window(&self) -> PyResult<Option<u64>> {
+        use_context(|ctx| Ok(ctx.receive_window()))
+    }
+
+    fn peer_mss(&self) -> PyResult<Option<u16>> {
+        use_context(|ctx| Ok(ctx.peer_mss()))
+    }
+
+    fn store(&self, key: &str, value: &str) -> PyResult<()> {
+        use_context(|ctx| {
+            ctx.store(key, value);
+            Ok(())
+        })
+    }
+
+    fn load(&self, key: &str) -> PyResult<Option<String>> {
+        use_context(|ctx| Ok(ctx.load(key)))
+    }
+
+    fn annotate_packet(&self, note: &str) -> PyResult<()> {
+        use_context(|ctx| {
+            ctx.annotate_packet(note);
+            Ok(())
+        })
+    }
+
+    fn app_pause(&self) -> PyResult<()> {
+        use_context(|ctx| {
+            ctx.app_pause();
+            Ok(())
+        })
+    }
+
+    fn app_resume(&self) -> PyResult<()> {
+        use_context(|ctx| {
+            ctx.app_resume();
+            Ok(())
+        })
+    }
+
+    fn refuse_app_data(&self, reason: &str) -> PyResult<()> {
+        use_context(|ctx| {
+            ctx.refuse_app_data(reason);
+            Ok(())
+        })
+    }
+
+    /// Simulation settings as a dict with their natural types (`None` for unset options).
+    fn config<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let config = use_context(|ctx| Ok(ctx.config()))?;
+        let json = serde_json::to_string(&config)
+            .map_err(|e| PyRuntimeError::new_err(format!("failed to encode config: {e}")))?;
+        py.import("json")?.call_method1("loads", (json,))
+    }
 }
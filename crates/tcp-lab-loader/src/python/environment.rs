@@ -2,7 +2,8 @@ use anyhow::{Context, Result};
 use pyo3::prelude::*;
 use pyo3::types::PyList;
 use serde::Deserialize;
-use std::path::PathBuf;
+use std::ffi::CStr;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 #[derive(Clone, Debug)]
@@ -91,3 +92,41 @@ print(json.dumps({
         Ok(())
     }
 }
+
+/// Check that CPython can start before the first `Python::attach`, which aborts the whole
+/// process when the standard library cannot be found. Uses `home`, else `PYTHONHOME`; with
+/// neither, CPython finds its own installation and there is nothing to check.
+pub fn preflight(home: Option<&Path>) -> Result<()> {
+    // SAFETY: both are documented as callable before the interpreter is initialized.
+    if unsafe { pyo3::ffi::Py_IsInitialized() } != 0 {
+        return Ok(());
+    }
+    let version = unsafe { CStr::from_ptr(pyo3::ffi::Py_GetVersion()) }
+        .to_string_lossy()
+        .into_owned();
+    let version = version.split('.').take(2).collect::<Vec<_>>().join(".");
+
+    let from_env = std::env::var_os("PYTHONHOME").filter(|home| !home.is_empty());
+    let home = match (home, &from_env) {
+        (Some(home), _) => home.to_path_buf(),
+        // `prefix:exec_prefix` on Unix; the standard library lives under the prefix.
+        (None, Some(env)) => std::env::split_paths(env).next().unwrap_or_default(),
+        (None, None) => return Ok(()),
+    };
+    let stdlib = if cfg!(windows) {
+        home.join("Lib")
+    } else {
+        home.join("lib").join(format!("python{version}"))
+    };
+    if !stdlib.join("encodings").is_dir() {
+        anyhow::bail!(
+            "Python {version} cannot start: {} has no standard library ({} is missing). \
+             Point PYTHONHOME (or the uv project's interpreter) at a Python {version} \
+             installation, unset PYTHONHOME to use the one tcp-lab was built against, or \
+             install it with `uv python install {version}`",
+            home.display(),
+            stdlib.join("encodings").display()
+        );
+    }
+    Ok(())
+}
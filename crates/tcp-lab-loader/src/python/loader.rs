@@ -1,10 +1,17 @@
-use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+use pyo3::exceptions::PyModuleNotFoundError;
 use pyo3::prelude::*;
-use tcp_lab_abstract::{Packet, SystemContext, TransportProtocol};
+use pyo3::types::PyDict;
+use tcp_lab_abstract::{BridgeWarning, Packet, SystemContext, TransportProtocol};
+use tracing::debug_span;
 
 use super::adapter;
-use super::context::{PySystemContext, with_context};
-use super::environment::PythonEnvironment;
+use super::context::{self, PySystemContext, with_context};
+use super::environment::{self, PythonEnvironment};
+use super::{clock, sandbox};
+use crate::{LoaderError, check_sdk_version};
 
 pub struct PythonTransportProtocol {
     instance: Py<PyAny>,
@@ -15,28 +22,17 @@ impl PythonTransportProtocol {
         module_name: &str,
         class_name: &str,
         env: Option<&PythonEnvironment>,
-    ) -> Result<Self> {
+    ) -> Result<Self, LoaderError> {
         Python::attach(|py| {
-            if let Some(env) = env {
-                env.inject(py)
-                    .map_err(|e| anyhow::anyhow!("Failed to activate Python environment: {}", e))?;
-            }
-
-            let module = py
-                .import(module_name)
-                .with_context(|| format!("Failed to import Python module '{}'", module_name))?;
+            let cls = import_class(py, module_name, class_name, env)?;
 
-            let cls = module.getattr(class_name).with_context(|| {
-                format!(
-                    "Failed to find class '{}' in module '{}'",
-                    class_name, module_name
-                )
+            let instance = cls.call0().map_err(|e| {
+                LoaderError::StudentRuntime(format!(
+                    "Failed to instantiate class '{}': {}",
+                    class_name, e
+                ))
             })?;
 
-            let instance = cls
-                .call0()
-                .with_context(|| format!("Failed to instantiate class '{}'", class_name))?;
-
             Ok(Self {
                 instance: instance.into(),
             })
@@ -46,18 +42,22 @@ impl PythonTransportProtocol {
 
 impl TransportProtocol for PythonTransportProtocol {
     fn init(&mut self, ctx: &mut dyn SystemContext) {
+        let _span = debug_span!("bridge_call", language = "python", callback = "init").entered();
         with_context(ctx, || {
             Python::attach(|py| {
                 let py_ctx = PySystemContext::new();
                 if let Err(e) = self.instance.call_method1(py, "init", (py_ctx,)) {
                     eprintln!("Python init failed: {}", e);
                     e.print(py);
+                    context::report_warning(BridgeWarning::CallFailed, &format!("init raised {e}"));
                 }
             })
         })
     }
 
     fn on_packet(&mut self, ctx: &mut dyn SystemContext, packet: Packet) {
+        let _span =
+            debug_span!("bridge_call", language = "python", callback = "on_packet").entered();
         with_context(ctx, || {
             Python::attach(|py| {
                 let py_ctx = PySystemContext::new();
@@ -65,6 +65,10 @@ impl TransportProtocol for PythonTransportProtocol {
                     Ok(p) => p,
                     Err(e) => {
                         eprintln!("Failed to convert packet to Python: {}", e);
+                        context::report_warning(
+                            BridgeWarning::ConversionFailed,
+                            &format!("failed to convert a packet to Python: {e}"),
+                        );
                         return;
                     }
                 };
@@ -75,12 +79,18 @@ impl TransportProtocol for PythonTransportProtocol {
                 {
                     eprintln!("Python on_packet failed: {}", e);
                     e.print(py);
+                    context::report_warning(
+                        BridgeWarning::CallFailed,
+                        &format!("on_packet raised {e}"),
+                    );
                 }
             })
         })
     }
 
     fn on_timer(&mut self, ctx: &mut dyn SystemContext, timer_id: u32) {
+        let _span =
+            debug_span!("bridge_call", language = "python", callback = "on_timer").entered();
         with_context(ctx, || {
             Python::attach(|py| {
                 let py_ctx = PySystemContext::new();
@@ -90,12 +100,18 @@ impl TransportProtocol for PythonTransportProtocol {
                 {
                     eprintln!("Python on_timer failed: {}", e);
                     e.print(py);
+                    context::report_warning(
+                        BridgeWarning::CallFailed,
+                        &format!("on_timer raised {e}"),
+                    );
                 }
             })
         })
     }
 
     fn on_app_data(&mut self, ctx: &mut dyn SystemContext, data: &[u8]) {
+        let _span =
+            debug_span!("bridge_call", language = "python", callback = "on_app_data").entered();
         with_context(ctx, || {
             Python::attach(|py| {
                 let py_ctx = PySystemContext::new();
@@ -106,17 +122,270 @@ impl TransportProtocol for PythonTransportProtocol {
                 {
                     eprintln!("Python on_app_data failed: {}", e);
                     e.print(py);
+                    context::report_warning(
+                        BridgeWarning::CallFailed,
+                        &format!("on_app_data raised {e}"),
+                    );
                 }
             })
         })
     }
+
+    /// Optional in Python: classes that predate the hook and do not inherit it are skipped.
+    fn on_close(&mut self, ctx: &mut dyn SystemContext) {
+        let _span =
+            debug_span!("bridge_call", language = "python", callback = "on_close").entered();
+        with_context(ctx, || {
+            Python::attach(|py| {
+                if !self.instance.bind(py).hasattr("on_close").unwrap_or(false) {
+                    return;
+                }
+                let py_ctx = PySystemContext::new();
+                if let Err(e) = self.instance.call_method1(py, "on_close", (py_ctx,)) {
+                    eprintln!("Python on_close failed: {}", e);
+                    e.print(py);
+                    context::report_warning(
+                        BridgeWarning::CallFailed,
+                        &format!("on_close raised {e}"),
+                    );
+                }
+            })
+        })
+    }
+
+    /// Optional in Python like `on_close`; a result that is not a 16-bit int is reported.
+    fn checksum(&mut self, ctx: &mut dyn SystemContext, data: &[u8]) -> Option<u16> {
+        let _span =
+            debug_span!("bridge_call", language = "python", callback = "checksum").entered();
+        with_context(ctx, || {
+            Python::attach(|py| {
+                if !self.instance.bind(py).hasattr("checksum").unwrap_or(false) {
+                    return None;
+                }
+                let py_ctx = PySystemContext::new();
+                let py_data = pyo3::types::PyBytes::new(py, data);
+                let value = match self
+                    .instance
+                    .call_method1(py, "checksum", (py_ctx, py_data))
+                {
+                    Ok(value) => value,
+                    Err(e) => {
+                        e.print(py);
+                        context::report_warning(
+                            BridgeWarning::CallFailed,
+                            &format!("checksum raised {e}"),
+                        );
+                        return None;
+                    }
+                };
+                let value = value.bind(py);
+                if value.is_none() {
+                    return None;
+                }
+                match value.extract::<u16>() {
+                    Ok(checksum) => Some(checksum),
+                    Err(_) => {
+                        context::report_warning(
+                            BridgeWarning::ConversionFailed,
+                            &format!("checksum returned {value}, which is not a 16-bit value"),
+                        );
+                        None
+                    }
+                }
+            })
+        })
+    }
+
+    fn language(&self) -> &'static str {
+        "python"
+    }
+}
+
+/// Import `module` (running its top-level code) and look up `class`, mapping failures the
+/// way graders report them.
+fn import_class<'py>(
+    py: Python<'py>,
+    module_name: &str,
+    class_name: &str,
+    env: Option<&PythonEnvironment>,
+) -> Result<Bound<'py, PyAny>, LoaderError> {
+    if let Some(env) = env {
+        env.inject(py).map_err(|e| {
+            LoaderError::Environment(format!("Failed to activate Python environment: {}", e))
+        })?;
+    }
+
+    check_sdk_version("sdk/python", sdk_version(py))?;
+    if BASELINE.get().is_none() {
+        let loaded = loaded_modules(py).map_err(LoaderError::environment)?;
+        let _ = BASELINE.set(loaded.into_iter().map(|(name, _)| name).collect());
+    }
+
+    let module = py.import(module_name).map_err(|e| {
+        let message = format!("Failed to import Python module '{}': {}", module_name, e);
+        // Anything but a missing module was raised by the module's own code.
+        if e.is_instance_of::<PyModuleNotFoundError>(py) {
+            LoaderError::Unloadable(message)
+        } else {
+            LoaderError::StudentRuntime(message)
+        }
+    })?;
+
+    module.getattr(class_name).map_err(|e| {
+        LoaderError::Unloadable(format!(
+            "Failed to find class '{}' in module '{}': {}",
+            class_name, module_name, e
+        ))
+    })
+}
+
+/// `tcp_lab.SDK_VERSION`, or `None` if the SDK predates it or cannot be imported.
+fn sdk_version(py: Python<'_>) -> Option<u32> {
+    py.import("tcp_lab")
+        .ok()?
+        .getattr("SDK_VERSION")
+        .ok()?
+        .extract()
+        .ok()
+}
+
+/// Grading hooks installed into the interpreter before a student module is imported.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RuntimeHooks {
+    /// The audit-hook sandbox blocking sockets, subprocesses and file writes.
+    pub sandbox: bool,
+    /// `time.time` and friends return simulated time.
+    pub deterministic_time: bool,
+}
+
+fn prepare(hooks: RuntimeHooks) -> Result<(), LoaderError> {
+    environment::preflight(None).map_err(LoaderError::environment)?;
+    Python::attach(|py| {
+        if hooks.sandbox {
+            sandbox::install(py).map_err(|e| {
+                LoaderError::Environment(format!("Failed to install the Python sandbox: {}", e))
+            })?;
+        }
+        if hooks.deterministic_time {
+            clock::install(py).map_err(|e| {
+                LoaderError::Environment(format!("Failed to patch the Python clocks: {}", e))
+            })?;
+        }
+        Ok(())
+    })
+}
+
+/// Check that `class` could be loaded from `module` without instantiating it: the module
+/// imports, and the class is callable and defines every callback the bridge invokes.
+/// Importing still runs the module's top-level code.
+pub fn check_protocol(
+    module: &str,
+    class: &str,
+    env: Option<&PythonEnvironment>,
+    hooks: RuntimeHooks,
+) -> Result<(), LoaderError> {
+    prepare(hooks)?;
+    Python::attach(|py| {
+        let cls = import_class(py, module, class, env)?;
+        if !cls.is_callable() {
+            return Err(LoaderError::Unloadable(format!(
+                "'{}' in module '{}' is not a class",
+                class, module
+            )));
+        }
+        let missing: Vec<&str> = ["init", "on_packet", "on_timer", "on_app_data"]
+            .into_iter()
+            .filter(|method| !cls.hasattr(*method).unwrap_or(false))
+            .collect();
+        if !missing.is_empty() {
+            return Err(LoaderError::Unloadable(format!(
+                "Python class '{}' does not define {}",
+                class,
+                missing.join(", ")
+            )));
+        }
+        Ok(())
+    })
+}
+
+/// The modules imported before the first submission module, which `reset_modules` leaves
+/// alone: the standard library, the SDK and the bridge's own.
+static BASELINE: OnceLock<HashSet<String>> = OnceLock::new();
+
+/// `sys.modules` by name, in the order the modules finished importing.
+fn loaded_modules(py: Python<'_>) -> PyResult<Vec<(String, Bound<'_, PyAny>)>> {
+    let modules: Bound<'_, PyDict> = py.import("sys")?.getattr("modules")?.cast_into()?;
+    modules
+        .iter()
+        .map(|(name, module)| Ok((name.extract()?, module)))
+        .collect()
+}
+
+/// Run the top-level code of every module imported since the first submission module loaded
+/// again, dependencies first, so that their globals start over for the next run. Installed
+/// packages and extension modules keep their state, since re-importing those is not safe.
+pub fn reset_modules() -> Result<(), LoaderError> {
+    let Some(baseline) = BASELINE.get() else {
+        return Ok(());
+    };
+    Python::attach(|py| {
+        let importlib = py.import("importlib").map_err(LoaderError::environment)?;
+        let modules = loaded_modules(py).map_err(LoaderError::environment)?;
+        for (name, module) in modules {
+            let source = module
+                .getattr("__file__")
+                .and_then(|file| file.extract::<String>())
+                .unwrap_or_default();
+            let installed = ["site-packages", "dist-packages"]
+                .iter()
+                .any(|dir| source.contains(dir));
+            if baseline.contains(&name) || !source.ends_with(".py") || installed {
+                continue;
+            }
+            importlib.call_method1("reload", (module,)).map_err(|e| {
+                LoaderError::StudentRuntime(format!(
+                    "Failed to re-import Python module '{}': {}",
+                    name, e
+                ))
+            })?;
+        }
+        Ok(())
+    })
+}
+
+/// Import `module` again if it was imported before, so the next load runs its code as it
+/// is on disk now.
+pub fn reload_module(module: &str) -> Result<(), LoaderError> {
+    Python::attach(|py| {
+        let Ok(loaded) = py
+            .import("sys")
+            .and_then(|sys| sys.getattr("modules"))
+            .and_then(|modules| modules.get_item(module))
+        else {
+            return Ok(());
+        };
+        py.import("importlib")
+            .and_then(|importlib| {
+                importlib.call_method0("invalidate_caches")?;
+                importlib.call_method1("reload", (loaded,))
+            })
+            .map(|_| ())
+            .map_err(|e| {
+                LoaderError::StudentRuntime(format!(
+                    "Failed to reload Python module '{}': {}",
+                    module, e
+                ))
+            })
+    })
 }
 
 pub fn load_protocol(
     module: &str,
     class: &str,
     env: Option<&PythonEnvironment>,
-) -> Result<Box<dyn TransportProtocol>> {
+    hooks: RuntimeHooks,
+) -> Result<Box<dyn TransportProtocol>, LoaderError> {
+    prepare(hooks)?;
     let protocol = PythonTransportProtocol::new(module, class, env)?;
     Ok(Box::new(protocol))
 }
@@ -1,4 +1,6 @@
 pub mod adapter;
+mod clock;
 pub mod context;
 pub mod environment;
 pub mod loader;
+mod sandbox;
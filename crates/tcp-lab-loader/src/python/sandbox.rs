@@ -0,0 +1,79 @@
+//! Audit hook for graded runs (`LoaderBuilder::sandbox`). Opening sockets, starting
+//! processes and changing files fail with `PermissionError`, and each attempt is reported to
+//! the simulator as an integrity violation. Audit hooks are not a security boundary (ctypes
+//! can get around them); they catch submissions that reach outside the simulator, not a
+//! determined attacker. A hook cannot be removed, so it stays for the rest of the process.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use pyo3::exceptions::PyPermissionError;
+use pyo3::prelude::*;
+use pyo3::types::PyTuple;
+
+use super::context;
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Install the hook once per process.
+pub fn install(py: Python<'_>) -> PyResult<()> {
+    if INSTALLED.load(Ordering::Acquire) {
+        return Ok(());
+    }
+    let sys = py.import("sys")?;
+    // Bytecode caching would otherwise show up as file writes on every later import.
+    sys.setattr("dont_write_bytecode", true)?;
+    sys.call_method1("addaudithook", (wrap_pyfunction!(audit, py)?,))?;
+    INSTALLED.store(true, Ordering::Release);
+    Ok(())
+}
+
+#[pyfunction]
+fn audit(event: &str, args: &Bound<'_, PyTuple>) -> PyResult<()> {
+    let Some(what) = forbidden(event, args)? else {
+        return Ok(());
+    };
+    context::report_violation(&what);
+    Err(PyPermissionError::new_err(format!(
+        "{what} is not allowed in graded runs"
+    )))
+}
+
+/// What a forbidden event would do, or `None` when it is allowed.
+fn forbidden(event: &str, args: &Bound<'_, PyTuple>) -> PyResult<Option<String>> {
+    let arg = |index: usize| -> PyResult<String> {
+        Ok(match args.get_item(index) {
+            Ok(value) => value.str()?.to_string(),
+            Err(_) => String::new(),
+        })
+    };
+    Ok(Some(match event {
+        "socket.__new__"
+        | "socket.bind"
+        | "socket.connect"
+        | "socket.sendto"
+        | "socket.getaddrinfo"
+        | "socket.gethostbyname" => "network access".to_string(),
+        "subprocess.Popen" => format!("starting process {}", arg(0)?),
+        "os.system" | "os.exec" | "os.posix_spawn" | "os.spawn" | "os.fork" | "os.forkpty"
+        | "pty.spawn" => format!("starting a process ({event})"),
+        "open" => {
+            // (path, mode, flags); `os.open` passes no mode, only flags.
+            let mode = args.get_item(1)?;
+            let writes = if mode.is_none() {
+                // O_WRONLY and O_RDWR are 1 and 2 on every platform.
+                args.get_item(2)?.extract::<i64>().unwrap_or(0) & 3 != 0
+            } else {
+                mode.str()?.to_string().contains(['w', 'a', 'x', '+'])
+            };
+            if !writes {
+                return Ok(None);
+            }
+            format!("writing {}", arg(0)?)
+        }
+        "os.remove" | "os.rename" | "os.mkdir" | "os.rmdir" | "os.truncate" | "os.symlink"
+        | "os.link" | "os.chmod" | "os.chown" | "shutil.rmtree" => {
+            format!("modifying {} ({event})", arg(0)?)
+        }
+        _ => return Ok(None),
+    }))
+}
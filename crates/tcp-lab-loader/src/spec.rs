@@ -1,22 +1,27 @@
-use anyhow::{Context, Result};
-
-use crate::BuiltinProtocol;
+use crate::{BuiltinProtocol, LoaderError, examples};
 
 /// Parse a Python implementation spec of the form `module.Class`.
-pub fn parse_python_spec(spec: &str) -> Result<(String, String)> {
+pub fn parse_python_spec(spec: &str) -> Result<(String, String), LoaderError> {
     spec.rsplit_once('.')
         .map(|(module, class)| (module.to_string(), class.to_string()))
-        .context("Python class should be provided as module.Class")
+        .ok_or_else(|| {
+            LoaderError::Parse(format!(
+                "Python class should be provided as module.Class, got {spec:?}"
+            ))
+        })
 }
 
-/// Map a user-visible builtin name to the enum used by the loader.
-pub fn builtin_by_name(name: &str, is_sender: bool) -> Result<BuiltinProtocol> {
-    match name {
-        "rdt2" => Ok(if is_sender {
-            BuiltinProtocol::Rdt2Sender
-        } else {
-            BuiltinProtocol::Rdt2Receiver
-        }),
-        other => anyhow::bail!("Unknown builtin '{other}'. Try 'rdt2'."),
+/// Map a user-visible builtin name, one of [`examples::catalog`], to the enum used by the
+/// loader.
+pub fn builtin_by_name(name: &str, is_sender: bool) -> Result<BuiltinProtocol, LoaderError> {
+    match examples::find(name) {
+        Some(example) => Ok(example.builtin(is_sender)),
+        None => {
+            let names: Vec<&str> = examples::catalog().iter().map(|e| e.name).collect();
+            Err(LoaderError::Parse(format!(
+                "Unknown builtin '{name}'. Try one of: {}.",
+                names.join(", ")
+            )))
+        }
     }
 }
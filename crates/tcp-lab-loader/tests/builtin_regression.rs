@@ -0,0 +1,42 @@
+//! Grades every builtin pair against the workspace scenarios with several seeds and compares
+//! the outcomes with `tests/expected/builtins.toml`, so engine changes that alter what a
+//! scenario accepts show up here. After an intended change, update that file.
+
+use std::path::Path;
+use std::rc::Rc;
+
+use tcp_lab_loader::{ProtocolDescriptor, ProtocolLoader, examples};
+use tcp_lab_simulator::TagFilter;
+use tcp_lab_simulator::selftest::{Expectations, ProtocolPair, SharedFactory, grade_pairs};
+
+const SEEDS: [u64; 3] = [1, 7, 2024];
+
+fn builtin(loader: &ProtocolLoader, example: &examples::Example, is_sender: bool) -> SharedFactory {
+    let loader = loader.clone();
+    let builtin = example.builtin(is_sender);
+    Rc::new(move || Ok(loader.load(ProtocolDescriptor::BuiltIn(builtin))?))
+}
+
+#[test]
+fn builtin_pairs_match_recorded_outcomes() {
+    let workspace = Path::new(env!("CARGO_MANIFEST_DIR")).join("../..");
+    let loader = ProtocolLoader::builder().build().unwrap();
+    let pairs: Vec<ProtocolPair> = examples::catalog()
+        .iter()
+        .map(|example| ProtocolPair {
+            name: example.name.to_string(),
+            sender: builtin(&loader, example, true),
+            receiver: builtin(&loader, example, false),
+        })
+        .collect();
+    let outcomes = grade_pairs(
+        &pairs,
+        workspace.join("tests"),
+        &SEEDS,
+        &TagFilter::default(),
+    )
+    .unwrap();
+    let expectations = Expectations::load(workspace.join("tests/expected/builtins.toml")).unwrap();
+    let regressions = expectations.regressions(&outcomes);
+    assert!(regressions.is_empty(), "{}", regressions.join("\n"));
+}
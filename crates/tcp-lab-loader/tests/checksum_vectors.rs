@@ -0,0 +1,45 @@
+//! The checksum test vectors of `conformance`, checked through the `checksum` hook.
+
+use tcp_lab_abstract::{Packet, SystemContext, TransportProtocol};
+use tcp_lab_loader::conformance::{Reflector, check_checksums};
+use tcp_lab_loader::{BuiltinProtocol, ProtocolDescriptor, ProtocolLoader};
+use tcp_lab_rust_sdk::checksum::internet_checksum;
+
+/// Sums the words little-endian, a common slip when porting the helper.
+struct SwappedChecksum;
+
+impl TransportProtocol for SwappedChecksum {
+    fn on_packet(&mut self, _ctx: &mut dyn SystemContext, _packet: Packet) {}
+
+    fn on_timer(&mut self, _ctx: &mut dyn SystemContext, _timer_id: u32) {}
+
+    fn on_app_data(&mut self, _ctx: &mut dyn SystemContext, _data: &[u8]) {}
+
+    fn checksum(&mut self, _ctx: &mut dyn SystemContext, data: &[u8]) -> Option<u16> {
+        Some(internet_checksum(data).swap_bytes())
+    }
+}
+
+#[test]
+fn compares_the_hook_with_every_vector() {
+    let loader = ProtocolLoader::builder().build().unwrap();
+    let mut builtin = loader
+        .load(ProtocolDescriptor::BuiltIn(BuiltinProtocol::Rdt2Sender))
+        .unwrap();
+    assert!(check_checksums("rust", builtin.as_mut()).passed());
+
+    let report = check_checksums("swapped", &mut SwappedChecksum);
+    let failed: Vec<&str> = report
+        .vectors
+        .iter()
+        .filter(|vector| !vector.passed())
+        .map(|vector| vector.vector)
+        .collect();
+    assert!(failed.contains(&"rfc1071"));
+    // Symmetric inputs come out the same either way.
+    assert!(!failed.contains(&"zeros"));
+
+    let report = check_checksums("reflector", &mut Reflector);
+    assert!(!report.has_hook());
+    assert!(!report.passed());
+}
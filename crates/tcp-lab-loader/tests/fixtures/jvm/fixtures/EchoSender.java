@@ -0,0 +1,25 @@
+package fixtures;
+
+import com.ouc.tcp.sdk.Packet;
+import com.ouc.tcp.sdk.SystemContext;
+import com.ouc.tcp.sdk.TcpHeader;
+import com.ouc.tcp.sdk.TransportProtocol;
+
+/** Shared behaviour: every fixture sends one packet per application message. */
+public abstract class EchoSender implements TransportProtocol {
+    @Override
+    public void init(SystemContext ctx) {
+        ctx.log(getClass().getName() + " ready");
+    }
+
+    @Override
+    public void onPacket(SystemContext ctx, Packet packet) {}
+
+    @Override
+    public void onTimer(SystemContext ctx, int timerId) {}
+
+    @Override
+    public void onAppData(SystemContext ctx, byte[] data) {
+        ctx.sendPacket(new Packet(new TcpHeader(), data));
+    }
+}
@@ -0,0 +1,35 @@
+package fixtures;
+
+import com.ouc.tcp.sdk.SystemContext;
+import java.net.URL;
+import java.net.URLClassLoader;
+
+/** Tries the ways out of the grading sandbox short of writing files, each of which it blocks. */
+public class EscapesSandbox extends EchoSender {
+    private int field;
+
+    @Override
+    public void onAppData(SystemContext ctx, byte[] data) {
+        attempt(() -> System.loadLibrary("sandbox_escape"));
+        attempt(() -> Class.forName("sun.misc.Unsafe"));
+        attempt(() -> String.class.getDeclaredField("value"));
+        attempt(() -> EscapesSandbox.class.getDeclaredField("field").setAccessible(true));
+        attempt(() -> System.setOut(System.out));
+        attempt(() -> new URLClassLoader(new URL[0]).close());
+        super.onAppData(ctx, data);
+    }
+
+    private interface Attempt {
+        void run() throws Exception;
+    }
+
+    private static void attempt(Attempt attempt) {
+        try {
+            attempt.run();
+        } catch (SecurityException expected) {
+            // Reported to the simulator already.
+        } catch (Exception e) {
+            throw new RuntimeException(e);
+        }
+    }
+}
@@ -0,0 +1,27 @@
+package fixtures;
+
+/**
+ * Bytecode shape of
+ * `class KotlinCompanion(val window: Int) : TransportProtocol { companion object { fun create() = KotlinCompanion(4) } }`.
+ */
+public final class KotlinCompanion extends EchoSender {
+    public static final Companion Companion = new Companion();
+
+    private final int window;
+
+    public KotlinCompanion(int window) {
+        this.window = window;
+    }
+
+    public int getWindow() {
+        return window;
+    }
+
+    public static final class Companion {
+        private Companion() {}
+
+        public KotlinCompanion create() {
+            return new KotlinCompanion(4);
+        }
+    }
+}
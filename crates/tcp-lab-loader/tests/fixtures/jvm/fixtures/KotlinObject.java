@@ -0,0 +1,8 @@
+package fixtures;
+
+/** Bytecode shape of Kotlin `object KotlinObject : TransportProtocol`. */
+public final class KotlinObject extends EchoSender {
+    public static final KotlinObject INSTANCE = new KotlinObject();
+
+    private KotlinObject() {}
+}
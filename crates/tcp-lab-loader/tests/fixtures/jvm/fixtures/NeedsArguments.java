@@ -0,0 +1,6 @@
+package fixtures;
+
+/** Bytecode shape of Kotlin `class NeedsArguments(val window: Int)` with no way to build it. */
+public final class NeedsArguments extends EchoSender {
+    public NeedsArguments(int window) {}
+}
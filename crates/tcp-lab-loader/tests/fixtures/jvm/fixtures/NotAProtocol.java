@@ -0,0 +1,3 @@
+package fixtures;
+
+public final class NotAProtocol {}
@@ -0,0 +1,8 @@
+package fixtures;
+
+/** A protocol declared as a nested class, referred to as `fixtures.Outer.Nested`. */
+public final class Outer {
+    private Outer() {}
+
+    public static final class Nested extends EchoSender {}
+}
@@ -0,0 +1,16 @@
+package fixtures;
+
+import com.ouc.tcp.sdk.Packet;
+import com.ouc.tcp.sdk.SystemContext;
+import com.ouc.tcp.sdk.TcpHeader;
+
+/** Stamps packets with the wall clock instead of ctx.now(). */
+public class ReadsClock extends EchoSender {
+    @Override
+    public void onAppData(SystemContext ctx, byte[] data) {
+        TcpHeader header = new TcpHeader();
+        header.setSeqNum(System.currentTimeMillis());
+        header.setAckNum(System.nanoTime() / 1_000_000);
+        ctx.sendPacket(new Packet(header, data));
+    }
+}
@@ -0,0 +1,8 @@
+package fixtures;
+
+/** Bytecode shape of the module class scalac emits for `object ScalaObject`. */
+public final class ScalaObject$ extends EchoSender {
+    public static final ScalaObject$ MODULE$ = new ScalaObject$();
+
+    private ScalaObject$() {}
+}
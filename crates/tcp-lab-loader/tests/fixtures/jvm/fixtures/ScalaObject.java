@@ -0,0 +1,13 @@
+package fixtures;
+
+/**
+ * Bytecode shape of the mirror class scalac emits for `object ScalaObject extends TransportProtocol`:
+ * only static forwarders, and it does not implement the interface itself.
+ */
+public final class ScalaObject {
+    private ScalaObject() {}
+
+    public static void onAppData(com.ouc.tcp.sdk.SystemContext ctx, byte[] data) {
+        ScalaObject$.MODULE$.onAppData(ctx, data);
+    }
+}
@@ -0,0 +1,17 @@
+package fixtures;
+
+import com.ouc.tcp.sdk.SystemContext;
+import java.io.FileOutputStream;
+import java.io.IOException;
+
+/** Tries to leave a file behind, which the grading sandbox blocks. */
+public class WritesFile extends EchoSender {
+    @Override
+    public void onAppData(SystemContext ctx, byte[] data) {
+        try (FileOutputStream out = new FileOutputStream("sandbox-escape.txt")) {
+            out.write(data);
+        } catch (IOException e) {
+            throw new RuntimeException(e);
+        }
+    }
+}
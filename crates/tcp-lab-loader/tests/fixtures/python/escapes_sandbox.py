@@ -0,0 +1,28 @@
+"""Reaches outside the simulator from a callback; the grading sandbox blocks each attempt."""
+
+import socket
+
+from tcp_lab.structs import Packet, TcpHeader
+from tcp_lab_sdk.protocol import BaseTransportProtocol
+
+
+class EscapesSandbox(BaseTransportProtocol):
+    def on_packet(self, ctx, packet):
+        pass
+
+    def on_app_data(self, ctx, data):
+        # One packet per attempt, naming the exception it raised.
+        for attempt in (self.write_file, self.open_socket):
+            try:
+                attempt(data)
+                outcome = b"allowed"
+            except PermissionError:
+                outcome = b"PermissionError"
+            ctx.send_packet(Packet(TcpHeader(), outcome))
+
+    def write_file(self, data):
+        with open("sandbox-escape-py.txt", "w") as out:
+            out.write(data.decode())
+
+    def open_socket(self, _data):
+        socket.socket().close()
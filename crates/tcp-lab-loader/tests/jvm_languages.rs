@@ -0,0 +1,200 @@
+//! Loads protocols shaped like Kotlin and Scala output through the JNI bridge.
+//!
+//! The fixtures under `tests/fixtures/jvm` are Java sources that reproduce the bytecode
+//! kotlinc and scalac emit (`object` singletons, companion factories, Scala mirror classes),
+//! so the test only needs a JDK. Everything runs in one test because a process can host a
+//! single JVM, which is started with the grading sandbox and simulated clocks.
+#![cfg(feature = "java")]
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use tcp_lab_abstract::{Packet, SystemContext};
+use tcp_lab_loader::{ProtocolDescriptor, ProtocolLoader};
+
+#[derive(Default)]
+struct RecordingContext {
+    sent: Vec<Packet>,
+    violations: Vec<String>,
+    now: u64,
+}
+
+impl SystemContext for RecordingContext {
+    fn send_packet(&mut self, packet: Packet) {
+        self.sent.push(packet);
+    }
+
+    fn start_timer(&mut self, _delay_ms: u64, _timer_id: u32) {}
+
+    fn cancel_timer(&mut self, _timer_id: u32) {}
+
+    fn deliver_data(&mut self, _data: &[u8]) {}
+
+    fn log(&mut self, _message: &str) {}
+
+    fn now(&self) -> u64 {
+        self.now
+    }
+
+    fn report_violation(&mut self, what: &str) {
+        self.violations.push(what.to_string());
+    }
+}
+
+fn javac() -> Option<PathBuf> {
+    let from_home = std::env::var_os("JAVA_HOME")
+        .map(|home| Path::new(&home).join("bin").join("javac"))
+        .filter(|javac| {
+            javac
+                .with_extension(std::env::consts::EXE_EXTENSION)
+                .is_file()
+        });
+    let javac = from_home.unwrap_or_else(|| PathBuf::from("javac"));
+    Command::new(&javac)
+        .arg("-version")
+        .output()
+        .ok()
+        .filter(|out| out.status.success())
+        .map(|_| javac)
+}
+
+fn java_sources(dir: &Path, out: &mut Vec<PathBuf>) {
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            java_sources(&path, out);
+        } else if path.extension().is_some_and(|ext| ext == "java") {
+            out.push(path);
+        }
+    }
+}
+
+fn compile_fixtures(javac: &Path) -> PathBuf {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let classes = Path::new(env!("CARGO_TARGET_TMPDIR")).join("jvm-language-fixtures");
+    let _ = std::fs::remove_dir_all(&classes);
+
+    let mut sources = Vec::new();
+    java_sources(&manifest.join("../../sdk/java/src/main/java"), &mut sources);
+    java_sources(&manifest.join("tests/fixtures/jvm"), &mut sources);
+
+    let status = Command::new(javac)
+        .arg("-d")
+        .arg(&classes)
+        .args(&sources)
+        .status()
+        .expect("failed to run javac");
+    assert!(status.success(), "javac failed");
+    classes
+}
+
+#[test]
+fn loads_kotlin_and_scala_shaped_classes() {
+    let Some(javac) = javac() else {
+        eprintln!("skipping: javac not found (set JAVA_HOME to a JDK)");
+        return;
+    };
+    let classes = compile_fixtures(&javac);
+    let loader = ProtocolLoader::builder()
+        .java_classpath(classes.to_string_lossy())
+        .jvm_option("-Xmx128m")
+        .jvm_option("-ea")
+        .sandbox(true)
+        .deterministic_time(true)
+        .build()
+        .expect("JVM should start");
+
+    for class_name in [
+        "fixtures.KotlinObject",
+        "fixtures.KotlinCompanion",
+        "fixtures.ScalaObject",
+        "fixtures.ScalaObject$",
+        "fixtures.Outer.Nested",
+        "fixtures.Outer$Nested",
+    ] {
+        let descriptor = ProtocolDescriptor::Java {
+            class_name: class_name.to_string(),
+        };
+        loader
+            .check(&descriptor)
+            .unwrap_or_else(|e| panic!("check {class_name}: {e:#}"));
+        let mut protocol = loader
+            .load(descriptor)
+            .unwrap_or_else(|e| panic!("{class_name}: {e:#}"));
+        let mut ctx = RecordingContext::default();
+        protocol.init(&mut ctx);
+        protocol.on_app_data(&mut ctx, b"hi");
+        assert_eq!(ctx.sent.len(), 1, "{class_name} should send one packet");
+        assert_eq!(ctx.sent[0].payload, b"hi");
+    }
+
+    let mut writer = loader
+        .load(ProtocolDescriptor::Java {
+            class_name: "fixtures.WritesFile".to_string(),
+        })
+        .expect("WritesFile should load");
+    let mut ctx = RecordingContext::default();
+    writer.on_app_data(&mut ctx, b"answers");
+    assert_eq!(ctx.violations.len(), 1, "{:?}", ctx.violations);
+    assert!(
+        ctx.violations[0].starts_with("writing ") && ctx.violations[0].contains("sandbox-escape"),
+        "{:?}",
+        ctx.violations
+    );
+    assert!(!Path::new("sandbox-escape.txt").exists());
+
+    let mut escaper = loader
+        .load(ProtocolDescriptor::Java {
+            class_name: "fixtures.EscapesSandbox".to_string(),
+        })
+        .expect("EscapesSandbox should load");
+    let mut ctx = RecordingContext::default();
+    escaper.on_app_data(&mut ctx, b"out");
+    let expected = [
+        "loading the native library sandbox_escape",
+        "using the internal package sun.misc",
+        "reflecting on the private members of another class",
+        "bypassing access checks with reflection",
+        "replacing System.in, System.out or System.err",
+        "creating a class loader",
+    ];
+    assert_eq!(ctx.violations, expected);
+    // Blocked attempts do not stop the protocol; the JDK's own privileged work still runs.
+    assert_eq!(ctx.sent.len(), 1);
+
+    let mut clock_reader = loader
+        .load(ProtocolDescriptor::Java {
+            class_name: "fixtures.ReadsClock".to_string(),
+        })
+        .expect("ReadsClock should load");
+    let mut ctx = RecordingContext {
+        now: 1234,
+        ..Default::default()
+    };
+    clock_reader.on_app_data(&mut ctx, b"tick");
+    assert_eq!(ctx.sent[0].header.seq_num, 1234, "currentTimeMillis");
+    assert_eq!(ctx.sent[0].header.ack_num, 1234, "nanoTime");
+
+    for (class_name, expected) in [
+        (
+            "fixtures.NeedsArguments",
+            "no public no-argument constructor",
+        ),
+        ("fixtures.NotAProtocol", "does not implement"),
+        ("fixtures.Missing", "not found on the classpath"),
+    ] {
+        let descriptor = ProtocolDescriptor::Java {
+            class_name: class_name.to_string(),
+        };
+        let checked = match loader.check(&descriptor) {
+            Ok(()) => panic!("{class_name} should not pass the check"),
+            Err(e) => format!("{e:#}"),
+        };
+        assert!(checked.contains(expected), "check {class_name}: {checked}");
+        let err = match loader.load(descriptor) {
+            Ok(_) => panic!("{class_name} should not load"),
+            Err(e) => format!("{e:#}"),
+        };
+        assert!(err.contains(expected), "{class_name}: {err}");
+    }
+}
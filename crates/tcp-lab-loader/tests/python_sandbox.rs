@@ -0,0 +1,65 @@
+//! The audit-hook sandbox of graded Python runs. Everything runs in one test because the
+//! hook, once installed, stays for the rest of the process.
+#![cfg(feature = "python")]
+
+use std::path::Path;
+
+use tcp_lab_abstract::{Packet, SystemContext};
+use tcp_lab_loader::{ProtocolDescriptor, ProtocolLoader, PythonConfig};
+
+#[derive(Default)]
+struct RecordingContext {
+    sent: Vec<Packet>,
+    violations: Vec<String>,
+}
+
+impl SystemContext for RecordingContext {
+    fn send_packet(&mut self, packet: Packet) {
+        self.sent.push(packet);
+    }
+
+    fn start_timer(&mut self, _delay_ms: u64, _timer_id: u32) {}
+
+    fn cancel_timer(&mut self, _timer_id: u32) {}
+
+    fn deliver_data(&mut self, _data: &[u8]) {}
+
+    fn log(&mut self, _message: &str) {}
+
+    fn now(&self) -> u64 {
+        0
+    }
+
+    fn report_violation(&mut self, what: &str) {
+        self.violations.push(what.to_string());
+    }
+}
+
+#[test]
+fn blocks_file_writes_and_sockets_in_callbacks() {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let config = PythonConfig::default()
+        .add_sys_path(manifest.join("../../sdk/python"))
+        .add_sys_path(manifest.join("tests/fixtures/python"));
+    let loader = ProtocolLoader::builder()
+        .python_config(config)
+        .sandbox(true)
+        .build()
+        .expect("Python should start");
+    let mut protocol = loader
+        .load(ProtocolDescriptor::Python {
+            module: "escapes_sandbox".to_string(),
+            class_name: "EscapesSandbox".to_string(),
+        })
+        .expect("EscapesSandbox should load");
+
+    let mut ctx = RecordingContext::default();
+    protocol.on_app_data(&mut ctx, b"answers");
+    let outcomes: Vec<&[u8]> = ctx.sent.iter().map(|packet| &packet.payload[..]).collect();
+    assert_eq!(outcomes, [&b"PermissionError"[..], b"PermissionError"]);
+    assert_eq!(
+        ctx.violations,
+        ["writing sandbox-escape-py.txt", "network access"]
+    );
+    assert!(!Path::new("sandbox-escape-py.txt").exists());
+}
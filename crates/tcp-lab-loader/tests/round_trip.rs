@@ -0,0 +1,38 @@
+//! The round-trip harness of `conformance` on reflectors that need no bridge.
+
+use tcp_lab_abstract::{Packet, SystemContext, TransportProtocol};
+use tcp_lab_loader::conformance::{Reflector, check_round_trip};
+
+/// Clears the source port and drops the last payload byte, like an incomplete bridge.
+struct Lossy;
+
+impl TransportProtocol for Lossy {
+    fn on_packet(&mut self, ctx: &mut dyn SystemContext, mut packet: Packet) {
+        packet.header.src_port = 0;
+        packet.payload.pop();
+        ctx.send_packet(packet);
+    }
+
+    fn on_timer(&mut self, _ctx: &mut dyn SystemContext, _timer_id: u32) {}
+
+    fn on_app_data(&mut self, _ctx: &mut dyn SystemContext, _data: &[u8]) {}
+}
+
+#[test]
+fn reports_fields_that_do_not_survive() {
+    assert!(check_round_trip("rust", &mut Reflector).passed());
+
+    let report = check_round_trip("lossy", &mut Lossy);
+    let mismatch = |name: &str| {
+        report
+            .cases
+            .iter()
+            .find(|case| case.case == name)
+            .and_then(|case| case.mismatch.clone())
+    };
+    assert_eq!(
+        mismatch("max_fields").as_deref(),
+        Some("src_port 65535 came back as 0, payload of 3 bytes came back with 2")
+    );
+    assert_eq!(mismatch("empty"), None);
+}
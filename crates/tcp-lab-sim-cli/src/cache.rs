@@ -0,0 +1,115 @@
+//! Suite outcomes remembered between runs, so scenarios whose inputs did not change
+//! are not graded again.
+//!
+//! An entry is keyed by the scenario file's content, the implementation under test (the
+//! student's files, the grader binary and the protocol options) and the seed. Hashes use
+//! the standard library's hasher, so a new toolchain only costs one full rerun.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use tcp_lab_abstract::{SimConfig, TestScenario};
+use tcp_lab_simulator::AssertionOutcome;
+
+use crate::watch::files_under;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheKey {
+    pub scenario: u64,
+    pub artifact: u64,
+    pub seed: u64,
+}
+
+impl CacheKey {
+    /// The key of `scenario`, loaded from `path`, run against `artifact`.
+    pub fn new(path: &Path, scenario: &TestScenario, artifact: u64) -> Result<Self> {
+        let data = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Ok(Self {
+            scenario: hash_bytes(&data),
+            artifact,
+            seed: scenario.config.seed.unwrap_or(SimConfig::default().seed),
+        })
+    }
+}
+
+impl fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:016x}-{:016x}-{}",
+            self.scenario, self.artifact, self.seed
+        )
+    }
+}
+
+/// A remembered outcome: the grader's error (`None` for a pass), the process exit code
+/// it maps to, and for a pass when each assertion was met.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedOutcome {
+    pub error: Option<String>,
+    pub exit_code: u8,
+    #[serde(default)]
+    pub assertions: Vec<AssertionOutcome>,
+}
+
+/// Outcomes by `CacheKey`, stored as JSON.
+pub struct GradeCache {
+    path: PathBuf,
+    entries: BTreeMap<String, CachedOutcome>,
+}
+
+impl GradeCache {
+    /// Read the cache at `path`; a missing or unreadable file starts an empty one.
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let entries = fs::read(&path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        Self { path, entries }
+    }
+
+    pub fn get(&self, key: &CacheKey) -> Option<&CachedOutcome> {
+        self.entries.get(&key.to_string())
+    }
+
+    pub fn insert(&mut self, key: &CacheKey, outcome: CachedOutcome) {
+        self.entries.insert(key.to_string(), outcome);
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let data = serde_json::to_vec_pretty(&self.entries).context("Failed to serialize cache")?;
+        fs::write(&self.path, data)
+            .with_context(|| format!("Failed to write cache file {}", self.path.display()))
+    }
+}
+
+pub fn hash_bytes(data: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Hash of the implementation: every file under `paths` (names and contents), the
+/// grader binary's size and modification time, which cover the built-in protocols and
+/// the engine, and `options`, the settings that choose and configure the protocols.
+pub fn hash_artifact(paths: &[PathBuf], exe: &Path, options: &str) -> Result<u64> {
+    let mut hasher = DefaultHasher::new();
+    for path in files_under(paths) {
+        let data = fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        path.hash(&mut hasher);
+        data.hash(&mut hasher);
+    }
+    let meta = fs::metadata(exe)
+        .with_context(|| format!("Failed to read the metadata of {}", exe.display()))?;
+    meta.len().hash(&mut hasher);
+    meta.modified().ok().hash(&mut hasher);
+    options.hash(&mut hasher);
+    Ok(hasher.finish())
+}
@@ -1,105 +1,709 @@
+mod cache;
+mod soak;
+mod watch;
+
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use std::ffi::OsString;
 use std::fs;
+use std::io::{IsTerminal, Read, Seek};
 use std::path::{Path, PathBuf};
-use tracing::info;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use cache::{CacheKey, CachedOutcome, GradeCache};
+use soak::{Soak, SoakOptions};
 
-use tcp_lab_abstract::{SimConfig, TestAction, TestScenario, TransportProtocol};
+use tcp_lab_abstract::{
+    MetricBand, NodeId, ProtocolFactory, SimConfig, TestScenario, TransportProtocol,
+};
 use tcp_lab_loader::spec::{builtin_by_name, parse_python_spec};
-use tcp_lab_loader::{LoaderRequest, ProtocolDescriptor, ProtocolLoader, PythonConfig};
-use tcp_lab_simulator::tui::{MemoryLogBuffer, TuiApp};
-use tcp_lab_simulator::{SimulationReport, Simulator, encda, scenario_runner};
+use tcp_lab_loader::{
+    LoaderError, LoaderRequest, ProtocolDescriptor, ProtocolLoader, PythonConfig, bench,
+    conformance, examples,
+};
+use tcp_lab_simulator::capture::{self, Capture};
+use tcp_lab_simulator::i18n::{self, Lang, Msg};
+use tcp_lab_simulator::progress::{Progression, ScenarioStatus};
+use tcp_lab_simulator::report::diagram;
+use tcp_lab_simulator::report::sweep::{ParamPoint, ParamSweep, SeedRun, SweepSummary};
+use tcp_lab_simulator::report::table::{self, RunRow};
+use tcp_lab_simulator::trace::binary::{self, TraceReader, TraceRecord};
+use tcp_lab_simulator::trace::bundle::{self, Bundle, BundleMetadata};
+use tcp_lab_simulator::tui::{MemoryLogBuffer, Reload, RenderMode, TuiApp};
+use tcp_lab_simulator::{
+    AssertionOutcome, AssertionPolicy, ExitStatus, Gradebook, GradingFailed, HintCatalog,
+    PayloadPattern, RunOptions, ScenarioError, ScenarioOutcome, ScenarioResult, ScratchStore,
+    SimulationReport, Simulator, StudentSeed, TagFilter, TrackWeights, Workload, encda, gradebook,
+    live::LiveInput, realtime, repro, scenario_runner, trace, workload,
+};
 
 #[derive(Parser, Debug)]
-#[command(author, version, about = "Interactive TCP Lab simulator")]
+#[command(author, version, about = Msg::AboutSimCli.text())]
 struct Args {
-    /// Load a scenario from disk.
-    #[arg(long)]
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(long, value_name = "LANG", help = Msg::HelpLang.text())]
+    lang: Option<Lang>,
+
+    #[arg(long, help = Msg::HelpScenarioLoad.text())]
     scenario: Option<PathBuf>,
 
-    /// Launch the terminal UI visualizer.
-    #[arg(long, default_value_t = false)]
+    #[arg(long, default_value_t = false, help = Msg::HelpStrictSim.text())]
+    strict: bool,
+
+    #[arg(long, value_name = "FILE", help = Msg::HelpHints.text())]
+    hints: Option<PathBuf>,
+
+    #[arg(long, value_name = "POLICY", help = Msg::HelpAssertionPolicy.text())]
+    assertion_policy: Option<AssertionPolicy>,
+
+    #[arg(long, default_value_t = false, help = Msg::HelpTui.text())]
     tui: bool,
 
-    /// JVM classpath used when loading Java implementations.
-    #[arg(long)]
+    #[arg(
+        long,
+        value_name = "MODE",
+        default_value = "color",
+        help = Msg::HelpRenderMode.text()
+    )]
+    render_mode: RenderMode,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        requires = "tui",
+        conflicts_with_all = ["stdin_data", "encda"],
+        help = Msg::HelpSideBySide.text()
+    )]
+    side_by_side: bool,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        requires = "tui",
+        conflicts_with_all = ["side_by_side", "encda"],
+        help = Msg::HelpCapture.text()
+    )]
+    capture: Option<PathBuf>,
+
+    #[arg(long, help = Msg::HelpClasspath.text())]
     classpath: Option<String>,
 
-    #[arg(long)]
+    #[arg(
+        long = "jvm-opt",
+        value_name = "OPTION",
+        allow_hyphen_values = true,
+        help = Msg::HelpJvmOpt.text()
+    )]
+    jvm_opts: Vec<String>,
+
+    #[arg(long, default_value_t = false, help = Msg::HelpDeterministicTime.text())]
+    deterministic_time: bool,
+
+    #[arg(
+        long,
+        value_name = "MS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "2000",
+        help = Msg::HelpDebugAttach.text()
+    )]
+    debug_attach: Option<u64>,
+
+    #[arg(long, value_name = "ID", help = Msg::HelpStudentId.text())]
+    student_id: Option<String>,
+    #[arg(long, value_name = "SEED", default_value_t = 0, help = Msg::HelpBaseSeed.text())]
+    base_seed: u64,
+
+    #[arg(long, help = Msg::HelpJavaSender.text())]
     java_sender: Option<String>,
-    #[arg(long)]
+    #[arg(long, help = Msg::HelpJavaReceiver.text())]
     java_receiver: Option<String>,
 
-    #[arg(long)]
+    #[arg(long, help = Msg::HelpPythonSender.text())]
     python_sender: Option<String>,
-    #[arg(long)]
+    #[arg(long, help = Msg::HelpPythonReceiver.text())]
     python_receiver: Option<String>,
 
-    /// Root directory of the uv-managed Python project.
-    #[arg(long)]
+    #[arg(long, help = Msg::HelpPythonUvProject.text())]
     python_uv_project: Option<PathBuf>,
 
-    /// Extra path added to Python sys.path (in addition to uv).
-    #[arg(long)]
+    #[arg(long, help = Msg::HelpPythonPath.text())]
     python_path: Option<PathBuf>,
 
-    #[arg(long)]
+    #[arg(long, help = Msg::HelpCppSenderLib.text())]
     cpp_sender_lib: Option<PathBuf>,
-    #[arg(long)]
+    #[arg(long, help = Msg::HelpCppReceiverLib.text())]
     cpp_receiver_lib: Option<PathBuf>,
 
-    #[arg(long)]
+    #[arg(long, help = Msg::HelpBuiltinSender.text())]
     builtin_sender: Option<String>,
-    #[arg(long)]
+    #[arg(long, help = Msg::HelpBuiltinReceiver.text())]
     builtin_receiver: Option<String>,
 
-    /// Write a JSON trace of the finished simulation.
-    #[arg(long)]
+    #[arg(long, help = Msg::HelpTraceOut.text())]
     trace_out: Option<PathBuf>,
 
-    /// Play an encrypted ENCDA.tcp trace (mutually exclusive with --scenario).
-    #[arg(long)]
+    #[arg(long, value_name = "FILE", help = Msg::HelpBinaryTraceOut.text())]
+    binary_trace_out: Option<PathBuf>,
+
+    #[arg(long, value_name = "FILE", help = Msg::HelpBundleOut.text())]
+    bundle_out: Option<PathBuf>,
+
+    #[arg(long, value_name = "FILE", help = Msg::HelpDotOut.text())]
+    dot_out: Option<PathBuf>,
+
+    #[arg(long, value_name = "MS", requires = "dot_out", help = Msg::HelpDotFrom.text())]
+    dot_from: Option<u64>,
+
+    #[arg(long, value_name = "MS", requires = "dot_out", help = Msg::HelpDotUntil.text())]
+    dot_until: Option<u64>,
+
+    #[arg(long, help = Msg::HelpDebugEvents.text())]
+    debug_events: bool,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with = "strict",
+        help = Msg::HelpScratchStore.text()
+    )]
+    scratch_store: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        num_args = 0..=1,
+        help = Msg::HelpAsciiDiagram.text()
+    )]
+    ascii_diagram: Option<Option<PathBuf>>,
+
+    #[arg(long, default_value_t = 3, help = Msg::HelpMessages.text())]
+    messages: usize,
+
+    #[arg(long, value_name = "BYTES", default_value_t = 16, help = Msg::HelpMessageSize.text())]
+    size: usize,
+
+    #[arg(long, value_name = "MS", default_value_t = 1000, help = Msg::HelpInterval.text())]
+    interval: u64,
+
+    #[arg(
+        long,
+        value_name = "PATTERN",
+        default_value = "counter",
+        help = Msg::HelpPayloadPattern.text()
+    )]
+    payload_pattern: PayloadPattern,
+
+    #[arg(long, default_value_t = false, help = Msg::HelpStdinData.text())]
+    stdin_data: bool,
+
+    #[arg(long, help = Msg::HelpEncda.text())]
     encda: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "SCALE",
+        conflicts_with = "tui",
+        help = Msg::HelpRealtime.text()
+    )]
+    realtime: Option<f64>,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with_all = ["tui", "realtime"],
+        help = Msg::HelpGui.text()
+    )]
+    gui: bool,
+
+    #[arg(long, default_value_t = false, help = Msg::HelpOtel.text())]
+    otel: bool,
+
+    #[arg(
+        long,
+        value_name = "HOURS",
+        conflicts_with_all = ["tui", "gui", "realtime", "encda"],
+        help = Msg::HelpSoak.text()
+    )]
+    soak: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        default_value = "soak-reports",
+        help = Msg::HelpSoakDir.text()
+    )]
+    soak_dir: PathBuf,
+
+    #[arg(long, value_name = "N", default_value_t = 100, help = Msg::HelpSoakKeep.text())]
+    soak_keep: usize,
+
+    #[arg(long, requires = "soak", help = Msg::HelpSoakNoShrink.text())]
+    soak_no_shrink: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    #[command(about = Msg::AboutBenchBridge.text())]
+    BenchBridge {
+        #[arg(long, default_value_t = 10_000, help = Msg::HelpIterations.text())]
+        iterations: u32,
+    },
+    #[command(about = Msg::AboutRoundTrip.text())]
+    RoundTrip,
+    #[command(about = Msg::AboutChecksum.text())]
+    Checksum,
+    #[command(about = Msg::AboutCompare.text())]
+    Compare {
+        #[arg(long, help = Msg::HelpJsonOut.text())]
+        json_out: Option<PathBuf>,
+    },
+    #[command(about = Msg::AboutSweep.text())]
+    Sweep {
+        #[arg(
+            long,
+            value_delimiter = ',',
+            required_unless_present = "param",
+            help = Msg::HelpSeeds.text()
+        )]
+        seeds: Vec<u64>,
+        #[arg(long, value_name = "NAME", requires = "values", help = Msg::HelpSweepParam.text())]
+        param: Option<String>,
+        #[arg(
+            long,
+            value_delimiter = ',',
+            requires = "param",
+            help = Msg::HelpSweepValues.text()
+        )]
+        values: Vec<String>,
+        #[arg(long, value_name = "FILE", help = Msg::HelpSweepCsvOut.text())]
+        csv_out: Option<PathBuf>,
+        #[arg(long, value_name = "FILE", help = Msg::HelpSweepSvgOut.text())]
+        svg_out: Option<PathBuf>,
+        #[arg(long, help = Msg::HelpSweepJsonOut.text())]
+        json_out: Option<PathBuf>,
+    },
+    #[command(about = Msg::AboutSuite.text())]
+    Suite {
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+        #[command(flatten)]
+        filter: TagArgs,
+        #[arg(long, help = Msg::HelpSuiteJsonOut.text())]
+        json_out: Option<PathBuf>,
+        #[arg(long, value_name = "FILE", help = Msg::HelpCache.text())]
+        cache: Option<PathBuf>,
+        #[arg(long, value_name = "TRACK=WEIGHT,...", help = Msg::HelpTrackWeights.text())]
+        weights: Option<TrackWeights>,
+        #[arg(long, value_name = "FILE", help = Msg::HelpGradebook.text())]
+        gradebook: Option<PathBuf>,
+    },
+    #[command(about = Msg::AboutWatch.text())]
+    Watch {
+        #[arg(long, value_name = "DIR", help = Msg::HelpWatchSuite.text())]
+        suite: PathBuf,
+        #[arg(long, value_name = "COMMAND", help = Msg::HelpBuild.text())]
+        build: Option<String>,
+        #[arg(long = "watch", value_name = "PATH", help = Msg::HelpWatchPath.text())]
+        paths: Vec<PathBuf>,
+        #[command(flatten)]
+        filter: TagArgs,
+        #[arg(long, default_value_t = false, help = Msg::HelpNotify.text())]
+        notify: bool,
+        #[arg(long, value_name = "MS", default_value_t = 500, help = Msg::HelpPollMs.text())]
+        poll_ms: u64,
+    },
+    #[command(about = Msg::AboutTrace.text())]
+    Trace {
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+        #[arg(long, value_name = "MS", help = Msg::HelpTraceFrom.text())]
+        from: Option<u64>,
+        #[arg(long, value_name = "MS", help = Msg::HelpTraceUntil.text())]
+        until: Option<u64>,
+    },
+    #[command(about = Msg::AboutRepro.text())]
+    Repro {
+        #[arg(long, value_name = "FILE", help = Msg::HelpReproOut.text())]
+        out: Option<PathBuf>,
+    },
+    #[command(about = Msg::AboutBuiltins.text())]
+    Builtins,
+    #[command(about = Msg::AboutMigrate.text())]
+    Migrate {
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+        #[arg(long, value_name = "FILE", help = Msg::HelpMigrateOut.text())]
+        out: Option<PathBuf>,
+    },
+    #[command(about = Msg::AboutProgress.text())]
+    Progress {
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+        #[arg(long, value_name = "FILE", help = Msg::HelpProgressCache.text())]
+        cache: PathBuf,
+    },
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct TagArgs {
+    #[arg(long, value_name = "TAG", value_delimiter = ',', help = Msg::HelpTags.text())]
+    tags: Vec<String>,
+    #[arg(
+        long,
+        value_name = "TAG",
+        value_delimiter = ',',
+        help = Msg::HelpExcludeTags.text()
+    )]
+    exclude_tags: Vec<String>,
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
-    let _log_guard = init_logging(args.tui);
+impl TagArgs {
+    fn filter(&self) -> TagFilter {
+        TagFilter {
+            tags: self.tags.clone(),
+            exclude_tags: self.exclude_tags.clone(),
+        }
+    }
+
+    /// The same flags again, for a child process.
+    fn to_args(&self) -> Vec<OsString> {
+        let mut args = Vec::new();
+        for (flag, tags) in [
+            ("--tags", &self.tags),
+            ("--exclude-tags", &self.exclude_tags),
+        ] {
+            if !tags.is_empty() {
+                args.push(flag.into());
+                args.push(tags.join(",").into());
+            }
+        }
+        args
+    }
+}
+
+fn main() -> ExitCode {
+    // Before parsing, so that `--help` and argument errors are already localized.
+    i18n::set_lang(i18n::lang_from_args(std::env::args()));
+    let args = match Args::try_parse() {
+        Ok(args) => args,
+        Err(err) => {
+            // clap would exit with 2, which means a failed assertion here.
+            let _ = err.print();
+            return if err.use_stderr() {
+                ExitStatus::Environment.into()
+            } else {
+                ExitStatus::Pass.into()
+            };
+        }
+    };
+    match run(args) {
+        Ok(()) => ExitStatus::Pass.into(),
+        Err(err) => {
+            eprintln!("Error: {err:?}");
+            exit_status(&err).into()
+        }
+    }
+}
+
+/// The exit code for `err`. A submission that raises while being loaded is a student
+/// runtime error like one that raises while running.
+fn exit_status(err: &anyhow::Error) -> ExitStatus {
+    match err
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<LoaderError>())
+    {
+        Some(LoaderError::StudentRuntime(_)) => ExitStatus::StudentRuntime,
+        _ => ExitStatus::of(err),
+    }
+}
+
+fn run(args: Args) -> Result<()> {
+    let _log_guard = init_logging(args.tui, args.otel)?;
     info!("tcp-lab-sim-cli starting…");
 
+    if let Some(Command::Watch {
+        suite,
+        build,
+        paths,
+        filter,
+        notify,
+        poll_ms,
+    }) = &args.command
+    {
+        // Everything before the subcommand configures the protocols, so each round
+        // grades with the same options.
+        let global: Vec<OsString> = std::env::args_os()
+            .skip(1)
+            .take_while(|arg| arg != "watch")
+            .collect();
+        let mut suite_args = global;
+        suite_args.push("suite".into());
+        suite_args.push(suite.into());
+        suite_args.extend(filter.to_args());
+        suite_args.push("--cache".into());
+        suite_args.push(
+            std::env::temp_dir()
+                .join(format!("tcp-lab-watch-{}-cache.json", std::process::id()))
+                .into(),
+        );
+        let mut watched = vec![suite.clone()];
+        watched.extend(args.implementation_paths());
+        watched.extend(paths.iter().cloned());
+        return watch::run(watch::WatchOptions {
+            suite_args,
+            build: build.clone(),
+            paths: watched,
+            notify: *notify,
+            poll: Duration::from_millis(*poll_ms),
+        });
+    }
+
+    if let Some(Command::Trace { file, from, until }) = &args.command {
+        return print_trace(file, *from, *until);
+    }
+    if let Some(Command::Builtins) = &args.command {
+        print_builtins();
+        return Ok(());
+    }
+    if let Some(Command::Migrate { file, out }) = &args.command {
+        return migrate(file, out.as_deref());
+    }
+    if let Some(Command::Progress { file, cache }) = &args.command {
+        return print_progress(&args, file, cache);
+    }
+
     let loader = args.build_loader()?;
+    match &args.command {
+        Some(Command::BenchBridge { iterations }) => {
+            return run_bench_bridge(&args, &loader, *iterations);
+        }
+        Some(Command::RoundTrip) => {
+            return run_round_trip(&args, &loader);
+        }
+        Some(Command::Checksum) => {
+            return run_checksum(&args, &loader);
+        }
+        Some(Command::Compare { json_out }) => {
+            return run_compare(&args, &loader, json_out.as_deref());
+        }
+        Some(Command::Sweep {
+            seeds,
+            param: Some(param),
+            values,
+            csv_out,
+            svg_out,
+            json_out,
+        }) => {
+            let outputs = SweepOutputs {
+                csv: csv_out.clone(),
+                svg: svg_out.clone(),
+                json: json_out.clone(),
+            };
+            return run_param_sweep(&args, &loader, seeds, param, values, &outputs);
+        }
+        Some(Command::Sweep {
+            seeds, json_out, ..
+        }) => {
+            return run_sweep(&args, &loader, seeds, json_out.as_deref());
+        }
+        Some(Command::Repro { out }) => {
+            return run_repro(&args, &loader, out.as_deref());
+        }
+        Some(Command::Suite {
+            dir,
+            filter,
+            json_out,
+            cache,
+            weights,
+            gradebook,
+        }) => {
+            let cache = cache.as_ref().map(GradeCache::load);
+            let output = SuiteOutput {
+                json_out: json_out.as_deref(),
+                weights: weights.as_ref(),
+                gradebook: gradebook.as_deref(),
+            };
+            return run_suite(&args, &loader, dir, &filter.filter(), output, cache);
+        }
+        Some(
+            Command::Watch { .. }
+            | Command::Trace { .. }
+            | Command::Builtins
+            | Command::Migrate { .. }
+            | Command::Progress { .. },
+        ) => {
+            unreachable!("handled before loading protocols")
+        }
+        None => {}
+    }
+    if let Some(hours) = args.soak {
+        return run_soak(&args, &loader, hours);
+    }
+
     let request = args.loader_request()?;
+    let factories = loader.factories(&request);
+    let reload_request = request.try_clone();
     let (sender, receiver) = loader.load_pair(request)?;
 
     if args.scenario.is_some() && args.encda.is_some() {
         anyhow::bail!("--scenario and --encda cannot be used together");
     }
 
+    let frontend = args.frontend()?;
+    let sim_options = args.sim_options()?;
+    // The scenario's name and text, for `--bundle-out`.
+    let mut bundled_scenario = None;
     let report = if let Some(path) = &args.encda {
         let dataset = encda::load_from_file(path)?;
-        run_encda_sim(args.tui, dataset, sender, receiver)?
+        run_encda_sim(frontend, dataset, sender, receiver, sim_options)?
     } else if let Some(path) = &args.scenario {
-        if args.tui {
-            let scenario = load_scenario(path)?;
-            run_scenario_tui(scenario, sender, receiver)?
+        let mut scenario = scenario_runner::load_scenario(path, args.strict)?;
+        if args.bundle_out.is_some() {
+            let text = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read scenario {}", path.display()))?;
+            bundled_scenario = Some((scenario.name.clone(), text));
+        }
+        if let Frontend::Headless = frontend {
+            let options = RunOptions {
+                strict: args.strict,
+                factories,
+                hints: args.hint_catalog()?,
+                assertion_policy: args.assertion_policy,
+                student_seed: args.student_seed(),
+                debug_events: sim_options.debug_events,
+                scratch_store: sim_options.scratch_store.map(|(_, store)| store),
+                ..Default::default()
+            };
+            let scratch_path = args.scratch_store.as_deref();
+            grade(&scenario, sender, receiver, options, scratch_path)?
         } else {
-            let scenario_path = path
-                .to_str()
-                .context("Scenario path contains invalid UTF-8")?;
-            scenario_runner::run_scenario(scenario_path, sender, receiver)?
+            if let Some(seed) = args.student_seed() {
+                seed.apply(&mut scenario);
+            }
+            let extras = TuiExtras {
+                reload: reload_request.map(|request| scenario_reload(&loader, request, &scenario)),
+                reference: args.reference_run(&loader, Some(&scenario))?,
+                capture: args.capture()?,
+                metric_bands: scenario.metric_bands.clone(),
+            };
+            run_scenario_interactive(
+                frontend,
+                scenario,
+                sender,
+                receiver,
+                factories,
+                extras,
+                sim_options,
+            )?
         }
     } else {
-        run_default_sim(args.tui, sender, receiver)?
+        // Live runs send only what is typed or piped in.
+        let workload = (!args.stdin_data).then(|| args.workload());
+        let extras = TuiExtras {
+            reload: None,
+            reference: args.reference_run(&loader, None)?,
+            capture: args.capture()?,
+            metric_bands: Vec::new(),
+        };
+        run_default_sim(
+            frontend,
+            workload.as_ref(),
+            sender,
+            receiver,
+            extras,
+            sim_options,
+        )?
     };
 
     if let Some(trace_path) = &args.trace_out {
         write_trace(trace_path, &report)?;
     }
+    if let Some(path) = &args.binary_trace_out {
+        binary::write_report(path, &report)?;
+    }
+    if let Some(path) = &args.bundle_out {
+        let (name, text) = bundled_scenario.unzip();
+        let mut metadata = BundleMetadata::new(&report, name);
+        metadata.sender = args.protocol_label(true);
+        metadata.receiver = args.protocol_label(false);
+        bundle::write(path, &metadata, &report, text.as_deref())?;
+    }
+    if let Some(path) = &args.dot_out {
+        let dot = trace::export_dot(&report, args.dot_from, args.dot_until);
+        fs::write(path, dot)
+            .with_context(|| format!("Failed to write causal graph {}", path.display()))?;
+    }
+    if let Some(target) = &args.ascii_diagram {
+        let text = diagram::space_time(&report.link_events);
+        match target {
+            Some(path) => fs::write(path, text)
+                .with_context(|| format!("Failed to write diagram {}", path.display()))?,
+            None => print!("{text}"),
+        }
+    }
 
     Ok(())
 }
 
+/// How a simulation is driven once it has been built.
+#[derive(Clone, Copy, Debug)]
+enum Frontend {
+    /// Run to completion as fast as possible.
+    Headless,
+    /// Run to completion paced against the wall clock at the given time scale.
+    /// With `live`, stdin lines are sent as they arrive.
+    Paced {
+        scale: f64,
+        live: bool,
+    },
+    Tui {
+        mode: RenderMode,
+        live: bool,
+    },
+    #[cfg(feature = "gui")]
+    Gui,
+}
+
 impl Args {
+    fn frontend(&self) -> Result<Frontend> {
+        if self.gui {
+            if self.stdin_data {
+                anyhow::bail!("--stdin-data needs --tui or --realtime");
+            }
+            #[cfg(feature = "gui")]
+            return Ok(Frontend::Gui);
+            #[cfg(not(feature = "gui"))]
+            anyhow::bail!("`gui` feature disabled but --gui provided");
+        }
+        let live = self.stdin_data;
+        Ok(if self.tui {
+            Frontend::Tui {
+                mode: self.render_mode,
+                live,
+            }
+        } else if let Some(scale) = self.realtime {
+            Frontend::Paced { scale, live }
+        } else if live {
+            anyhow::bail!("--stdin-data needs --tui or --realtime");
+        } else {
+            Frontend::Headless
+        })
+    }
+
+    fn workload(&self) -> Workload {
+        Workload {
+            messages: self.messages,
+            size: self.size,
+            interval: self.interval,
+            pattern: self.payload_pattern,
+        }
+    }
+
     fn loader_request(&self) -> Result<LoaderRequest> {
         Ok(LoaderRequest {
             sender: self.resolve_descriptor(
@@ -119,11 +723,112 @@ impl Args {
         })
     }
 
+    /// Where the student's protocols come from: classpath entries, Python roots and
+    /// C++ libraries.
+    /// The built-in protocols the student's run is shown next to with `--side-by-side`.
+    fn reference_request(&self) -> Result<LoaderRequest> {
+        Ok(LoaderRequest {
+            sender: self.resolve_descriptor(
+                &None,
+                &None,
+                None,
+                self.builtin_sender.as_deref(),
+                true,
+            )?,
+            receiver: self.resolve_descriptor(
+                &None,
+                &None,
+                None,
+                self.builtin_receiver.as_deref(),
+                false,
+            )?,
+        })
+    }
+
+    /// With `--side-by-side`, builds the reference run on `scenario`, or on the default
+    /// simulation with the generated messages, with the same seed as the student's run.
+    fn reference_run(
+        &self,
+        loader: &ProtocolLoader,
+        scenario: Option<&TestScenario>,
+    ) -> Result<Option<Reload>> {
+        if !self.side_by_side {
+            return Ok(None);
+        }
+        let request = self.reference_request()?;
+        let loader = loader.clone();
+        let scenario = scenario.cloned();
+        let workload = self.workload();
+        Ok(Some(Box::new(move || {
+            let pair = request.try_clone().expect("built-in descriptors clone");
+            let factories = loader.factories(&request);
+            let (sender, receiver) = loader.load_pair(pair)?;
+            Ok(match &scenario {
+                Some(scenario) => build_scenario_sim(scenario, sender, receiver, factories)?,
+                None => {
+                    let mut sim = build_default_sim(sender, receiver);
+                    workload.schedule(&mut sim);
+                    sim
+                }
+            })
+        })))
+    }
+
+    /// The `--capture` export, read before the TUI takes over the terminal.
+    fn capture(&self) -> Result<Option<Capture>> {
+        self.capture
+            .as_deref()
+            .map(capture::load_from_file)
+            .transpose()
+    }
+
+    fn implementation_paths(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self
+            .classpath
+            .iter()
+            .flat_map(std::env::split_paths)
+            .collect();
+        paths.extend(self.python_uv_project.iter().cloned());
+        paths.extend(self.python_path.iter().cloned());
+        paths.extend(self.cpp_sender_lib.iter().cloned());
+        paths.extend(self.cpp_receiver_lib.iter().cloned());
+        paths
+    }
+
+    /// The settings that choose and configure the protocols, as one string for
+    /// `cache::hash_artifact`.
+    fn protocol_options(&self) -> String {
+        format!(
+            "{:?}",
+            (
+                (&self.java_sender, &self.java_receiver),
+                (&self.python_sender, &self.python_receiver),
+                (&self.cpp_sender_lib, &self.cpp_receiver_lib),
+                (&self.builtin_sender, &self.builtin_receiver),
+                (&self.classpath, &self.jvm_opts),
+                (self.strict, self.deterministic_time),
+            )
+        )
+    }
+
+    fn hint_catalog(&self) -> Result<HintCatalog> {
+        match &self.hints {
+            Some(path) => HintCatalog::load(path),
+            None => Ok(HintCatalog::default()),
+        }
+    }
+
     fn build_loader(&self) -> Result<ProtocolLoader> {
-        let mut builder = ProtocolLoader::builder();
+        let mut builder = ProtocolLoader::builder()
+            .sandbox(self.strict)
+            .deterministic_time(self.deterministic_time)
+            .debug_attach(self.debug_attach.map(Duration::from_millis));
         if let Some(cp) = &self.classpath {
             builder = builder.java_classpath(cp.clone());
         }
+        for option in &self.jvm_opts {
+            builder = builder.jvm_option(option.clone());
+        }
 
         if self.python_uv_project.is_some() || self.python_path.is_some() {
             let mut cfg = PythonConfig::default();
@@ -136,7 +841,53 @@ impl Args {
             builder = builder.python_config(cfg);
         }
 
-        builder.build()
+        Ok(builder.build()?)
+    }
+
+    fn sim_options(&self) -> Result<SimOptions> {
+        let scratch_store = match &self.scratch_store {
+            Some(path) => Some((path.clone(), ScratchStore::load(path)?)),
+            None => None,
+        };
+        Ok(SimOptions {
+            debug_events: self.debug_events,
+            scratch_store,
+        })
+    }
+
+    fn student_seed(&self) -> Option<StudentSeed> {
+        self.student_id.as_ref().map(|student| StudentSeed {
+            student: student.clone(),
+            base_seed: self.base_seed,
+        })
+    }
+
+    /// Where one side's protocol comes from, e.g. `java:Sender`, for bundle metadata.
+    fn protocol_label(&self, is_sender: bool) -> Option<String> {
+        let (java, python, cpp, builtin) = if is_sender {
+            (
+                &self.java_sender,
+                &self.python_sender,
+                &self.cpp_sender_lib,
+                &self.builtin_sender,
+            )
+        } else {
+            (
+                &self.java_receiver,
+                &self.python_receiver,
+                &self.cpp_receiver_lib,
+                &self.builtin_receiver,
+            )
+        };
+        let cpp = cpp.as_ref().map(|path| path.display().to_string());
+        [
+            ("java", java),
+            ("python", python),
+            ("cpp", &cpp),
+            ("builtin", builtin),
+        ]
+        .into_iter()
+        .find_map(|(kind, name)| name.as_ref().map(|name| format!("{kind}:{name}")))
     }
 
     fn resolve_descriptor(
@@ -173,7 +924,759 @@ impl Args {
     }
 }
 
-fn init_logging(use_tui: bool) -> Option<MemoryLogBuffer> {
+/// The built-in sender (`--builtin-sender`, else rdt2) followed by every sender
+/// passed for another language, labelled by backend.
+fn sender_backends(args: &Args) -> Result<Vec<(String, ProtocolDescriptor)>> {
+    let builtin = args.builtin_sender.as_deref().unwrap_or("rdt2");
+    let mut backends = vec![(
+        "rust".to_string(),
+        ProtocolDescriptor::BuiltIn(builtin_by_name(builtin, true)?),
+    )];
+    if let Some(class_name) = &args.java_sender {
+        backends.push((
+            "java".to_string(),
+            ProtocolDescriptor::Java {
+                class_name: class_name.clone(),
+            },
+        ));
+    }
+    if let Some(spec) = &args.python_sender {
+        let (module, class_name) = parse_python_spec(spec)?;
+        backends.push((
+            "python".to_string(),
+            ProtocolDescriptor::Python { module, class_name },
+        ));
+    }
+    if let Some(path) = &args.cpp_sender_lib {
+        backends.push((
+            "cpp".to_string(),
+            ProtocolDescriptor::Cpp {
+                library_path: path.clone(),
+            },
+        ));
+    }
+    Ok(backends)
+}
+
+fn run_compare(args: &Args, loader: &ProtocolLoader, json_out: Option<&Path>) -> Result<()> {
+    if args.encda.is_some() {
+        anyhow::bail!("compare runs --scenario or the default simulation, not --encda");
+    }
+    let scenario = args
+        .scenario
+        .as_ref()
+        .map(|path| scenario_runner::load_scenario(path, args.strict))
+        .transpose()?;
+
+    let mut rows = Vec::new();
+    for (name, descriptor) in sender_backends(args)? {
+        info!("Running {} sender…", name);
+        let mut request = args.loader_request()?;
+        request.sender = Some(descriptor);
+        let factories = loader.factories(&request);
+        let (sender, receiver) = loader
+            .load_pair(request)
+            .with_context(|| format!("Failed to load {name} protocol for comparison"))?;
+        let sim = match &scenario {
+            Some(scenario) => build_scenario_sim(scenario, sender, receiver, factories)?,
+            None => {
+                let mut sim = build_default_sim(sender, receiver);
+                args.workload().schedule(&mut sim);
+                sim
+            }
+        };
+        let report = drive(sim, None, Frontend::Headless, TuiExtras::default())?;
+        rows.push(RunRow::from_report(name, &report));
+    }
+
+    print!("{}", table::comparison(&rows));
+    if let Some(path) = json_out {
+        let data = serde_json::to_vec_pretty(&rows).context("Failed to serialize comparison")?;
+        fs::write(path, &data)
+            .with_context(|| format!("Failed to write comparison file {}", path.display()))?;
+    }
+    Ok(())
+}
+
+fn run_sweep(
+    args: &Args,
+    loader: &ProtocolLoader,
+    seeds: &[u64],
+    json_out: Option<&Path>,
+) -> Result<()> {
+    if args.encda.is_some() {
+        anyhow::bail!("sweep runs --scenario or the default simulation, not --encda");
+    }
+    let scenario = args
+        .scenario
+        .as_ref()
+        .map(|path| scenario_runner::load_scenario(path, args.strict))
+        .transpose()?;
+    let summary = run_seeds(
+        args,
+        loader,
+        scenario.as_ref(),
+        &workload::default_config(),
+        seeds,
+    )?;
+    print!("{}", summary.table());
+    if let Some(path) = json_out {
+        let data = serde_json::to_vec_pretty(&summary).context("Failed to serialize sweep")?;
+        fs::write(path, &data)
+            .with_context(|| format!("Failed to write sweep file {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Runs `scenario`, or the default workload under `config`, headless once per seed. No
+/// seeds means one run with the scenario's (or config's) own seed.
+fn run_seeds(
+    args: &Args,
+    loader: &ProtocolLoader,
+    scenario: Option<&TestScenario>,
+    config: &SimConfig,
+    seeds: &[u64],
+) -> Result<SweepSummary> {
+    let seeds: Vec<Option<u64>> = match seeds {
+        [] => vec![None],
+        seeds => seeds.iter().copied().map(Some).collect(),
+    };
+    let mut runs = Vec::new();
+    for seed in seeds {
+        if let Some(seed) = seed {
+            info!("Running seed {}…", seed);
+        }
+        let request = args.loader_request()?;
+        loader.reset(&request)?;
+        let factories = loader.factories(&request);
+        let (sender, receiver) = loader.load_pair(request)?;
+        let sim = match scenario {
+            Some(scenario) => {
+                let mut scenario = scenario.clone();
+                if seed.is_some() {
+                    scenario.config.seed = seed;
+                }
+                build_scenario_sim(&scenario, sender, receiver, factories)?
+            }
+            None => {
+                let config = SimConfig {
+                    seed: seed.unwrap_or(config.seed),
+                    ..config.clone()
+                };
+                let mut sim = Simulator::new(config, sender, receiver);
+                args.workload().schedule(&mut sim);
+                sim
+            }
+        };
+        let report = drive(sim, None, Frontend::Headless, TuiExtras::default())?;
+        runs.push(SeedRun::from_report(report.config.seed, &report));
+    }
+    SweepSummary::new(runs).context("No seeds to run")
+}
+
+/// Where `sweep --param` writes its results besides the table.
+struct SweepOutputs {
+    csv: Option<PathBuf>,
+    svg: Option<PathBuf>,
+    json: Option<PathBuf>,
+}
+
+/// `sweep --param`: the seed sweep repeated with one `[config]` setting at each value.
+fn run_param_sweep(
+    args: &Args,
+    loader: &ProtocolLoader,
+    seeds: &[u64],
+    param: &str,
+    values: &[String],
+    outputs: &SweepOutputs,
+) -> Result<()> {
+    if args.encda.is_some() {
+        anyhow::bail!("sweep runs --scenario or the default simulation, not --encda");
+    }
+    let content = args
+        .scenario
+        .as_ref()
+        .map(scenario_runner::read_scenario)
+        .transpose()?;
+
+    let mut points = Vec::new();
+    for value in values {
+        info!("Running {} = {}…", param, value);
+        let summary = match &content {
+            Some(content) => {
+                let content = scenario_runner::set_config_value(content, param, value)?;
+                let scenario = scenario_runner::parse_scenario(&content, args.strict)?;
+                run_seeds(args, loader, Some(&scenario), &SimConfig::default(), seeds)?
+            }
+            None => {
+                let config =
+                    scenario_runner::with_config_value(&workload::default_config(), param, value)?;
+                run_seeds(args, loader, None, &config, seeds)?
+            }
+        };
+        points.push(ParamPoint {
+            value: value.clone(),
+            summary,
+        });
+    }
+
+    let sweep = ParamSweep {
+        param: param.to_string(),
+        points,
+    };
+    print!("{}", sweep.table());
+    let csv = outputs
+        .csv
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(format!("sweep-{param}.csv")));
+    let write = |path: &Path, data: &[u8]| {
+        fs::write(path, data)
+            .with_context(|| format!("Failed to write sweep file {}", path.display()))
+    };
+    write(&csv, sweep.csv().as_bytes())?;
+    println!("{}", Msg::SweepWrote.fill(&[&csv.display()]));
+    if let Some(path) = &outputs.svg {
+        write(path, sweep.svg().as_bytes())?;
+        println!("{}", Msg::SweepWrote.fill(&[&path.display()]));
+    }
+    if let Some(path) = &outputs.json {
+        let data = serde_json::to_vec_pretty(&sweep).context("Failed to serialize sweep")?;
+        write(path, &data)?;
+    }
+    Ok(())
+}
+
+/// `repro`: shrink the failing `--scenario` into a small one that fails the same way.
+fn run_repro(args: &Args, loader: &ProtocolLoader, out: Option<&Path>) -> Result<()> {
+    let Some(path) = &args.scenario else {
+        anyhow::bail!(Msg::ReproNeedsScenario.text());
+    };
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read scenario file {}", path.display()))?;
+    let run = |scenario: &TestScenario| run_fresh(args, loader, scenario);
+    let Some(repro) = repro::minimize(&content, args.strict, run)? else {
+        anyhow::bail!(Msg::ReproPasses.text());
+    };
+    for step in &repro.steps {
+        eprintln!("{step}");
+    }
+    info!("Shrunk in {} runs", repro.runs);
+    match out {
+        Some(out) => fs::write(out, &repro.content)
+            .with_context(|| format!("Failed to write {}", out.display()))?,
+        None => print!("{}", repro.content),
+    }
+    Ok(())
+}
+
+/// Run `scenario` with freshly loaded protocols; a loader error is an environment failure.
+fn run_fresh(args: &Args, loader: &ProtocolLoader, scenario: &TestScenario) -> ScenarioResult {
+    let pair = args.loader_request().and_then(|request| {
+        loader.reset(&request)?;
+        let factories = loader.factories(&request);
+        Ok((loader.load_pair(request)?, factories))
+    });
+    match pair {
+        Ok(((sender, receiver), factories)) => {
+            let options = RunOptions {
+                strict: args.strict,
+                factories,
+                ..Default::default()
+            };
+            scenario_runner::run_scenario_with(scenario, sender, receiver, options)
+        }
+        Err(e) => ScenarioResult {
+            report: None,
+            failures: vec![ScenarioError::Environment {
+                details: format!("{e:#}"),
+            }],
+        },
+    }
+}
+
+/// Grade random cases, or `--scenario` with random seeds, until `hours` have passed.
+fn run_soak(args: &Args, loader: &ProtocolLoader, hours: f64) -> Result<()> {
+    if !(hours.is_finite() && hours > 0.0) {
+        anyhow::bail!("--soak needs a positive number of hours, got {hours}");
+    }
+    if args.soak_keep == 0 {
+        anyhow::bail!(
+            "--soak-keep needs at least 1, or every failure report is deleted as it is written"
+        );
+    }
+    let base = args
+        .scenario
+        .as_ref()
+        .map(|path| -> Result<_> {
+            let content = scenario_runner::read_scenario(path)?;
+            let scenario = scenario_runner::parse_scenario(&content, args.strict)?;
+            Ok((content, scenario))
+        })
+        .transpose()?;
+    let mut soak = Soak::new(SoakOptions {
+        duration: Duration::from_secs_f64(hours * 3600.0),
+        dir: args.soak_dir.clone(),
+        keep: args.soak_keep,
+        shrink: !args.soak_no_shrink,
+    })?;
+    let mut rng = soak::fresh_rng();
+    info!(
+        "Soaking for {} h, reports in {}",
+        hours,
+        args.soak_dir.display()
+    );
+    while soak.running() {
+        let (case, scenario) = match &base {
+            Some((content, base)) => {
+                let case = soak::seeded_case(&mut rng, content)?;
+                let mut scenario = base.clone();
+                scenario.config.seed = Some(case.seed);
+                (case, scenario)
+            }
+            None => {
+                let case = soak::random_case(&mut rng);
+                let scenario = scenario_runner::parse_scenario(&case.toml, true)?;
+                (case, scenario)
+            }
+        };
+        let request = args.loader_request()?;
+        loader.reset(&request)?;
+        let factories = loader.factories(&request);
+        let (sender, receiver) = loader.load_pair(request)?;
+        let options = RunOptions {
+            strict: args.strict,
+            factories,
+            ..Default::default()
+        };
+        let started = Instant::now();
+        let result = scenario_runner::run_scenario_with(&scenario, sender, receiver, options);
+        let shrink = |toml: &str| {
+            let run = |scenario: &TestScenario| run_fresh(args, loader, scenario);
+            match repro::minimize(toml, args.strict, run) {
+                Ok(repro) => repro,
+                Err(e) => {
+                    warn!("Could not shrink seed {}: {e}", case.seed);
+                    None
+                }
+            }
+        };
+        soak.record(&case, &result, started.elapsed(), shrink)?;
+    }
+
+    soak.write_summary()?;
+    println!("{}", soak.progress());
+    let summary = soak.summary();
+    for (kind, count) in &summary.by_kind {
+        println!("  {kind}: {count}");
+    }
+    if summary.failed > 0 {
+        return Err(GradingFailed {
+            status: ExitStatus::AssertionFailed,
+            message: format!("{} of {} soak runs failed", summary.failed, summary.runs),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// What a `suite` run reports besides its PASS and FAIL lines.
+struct SuiteOutput<'a> {
+    json_out: Option<&'a Path>,
+    weights: Option<&'a TrackWeights>,
+    gradebook: Option<&'a Path>,
+}
+
+/// Grade every scenario in `dir` that `filter` selects, printing a PASS or FAIL line
+/// for each, and fail if any of them failed. Suites with tracks or weights also get the
+/// per-track scores and the weighted grade.
+fn run_suite(
+    args: &Args,
+    loader: &ProtocolLoader,
+    dir: &Path,
+    filter: &TagFilter,
+    output: SuiteOutput,
+    mut cache: Option<GradeCache>,
+) -> Result<()> {
+    let hints = args.hint_catalog()?;
+    let artifact = match &cache {
+        Some(_) => suite_artifact(args, &hints)?,
+        None => 0,
+    };
+    let info = scenario_runner::load_suite_info(dir)?;
+    if !info.is_empty() {
+        println!("{info}");
+    }
+    let mut outcomes = Vec::new();
+    for path in scenario_runner::scenario_files(dir)? {
+        let mut scenario = scenario_runner::load_scenario(&path, args.strict)?;
+        if !filter.matches(&scenario) {
+            continue;
+        }
+        let seed = args.student_seed().map(|seed| seed.apply(&mut scenario));
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let track = scenario
+            .track
+            .clone()
+            .unwrap_or_else(|| gradebook::DEFAULT_TRACK.to_string());
+        let key = CacheKey::new(&path, &scenario, artifact)?;
+        let max_duration = scenario.max_duration();
+        if let Some(cached) = cache.as_ref().and_then(|cache| cache.get(&key)) {
+            match &cached.error {
+                None => println!(
+                    "{}",
+                    Msg::SuitePassCached
+                        .fill(&[&name, &time_budget(&cached.assertions, max_duration)])
+                ),
+                Some(error) => println!("{}", Msg::SuiteFailCached.fill(&[&name, error])),
+            }
+            outcomes.push(ScenarioOutcome {
+                scenario: name,
+                track,
+                rubric: scenario.rubric,
+                seed,
+                error: cached.error.clone(),
+                exit_code: cached.exit_code,
+                max_duration,
+                assertions: cached.assertions.clone(),
+                warnings: Vec::new(),
+                fingerprint: None,
+            });
+            continue;
+        }
+        let request = args.loader_request()?;
+        loader.reset(&request)?;
+        let factories = loader.factories(&request);
+        let (sender, receiver) = loader.load_pair(request)?;
+        let options = RunOptions {
+            strict: args.strict,
+            factories,
+            hints: hints.clone(),
+            assertion_policy: args.assertion_policy,
+            ..Default::default()
+        };
+        let (assertions, failure) = match grade(&scenario, sender, receiver, options, None) {
+            Ok(report) => {
+                println!(
+                    "{}",
+                    Msg::SuitePass.fill(&[&name, &time_budget(&report.assertions, max_duration)])
+                );
+                (report.assertions, None)
+            }
+            Err(failure) => {
+                println!("{}", Msg::SuiteFail.fill(&[&name, &failure]));
+                (Vec::new(), Some(failure))
+            }
+        };
+        let outcome = CachedOutcome {
+            exit_code: failure
+                .as_ref()
+                .map_or(ExitStatus::Pass, |failure| failure.status)
+                .code(),
+            error: failure.map(|failure| failure.message),
+            assertions,
+        };
+        if let Some(cache) = &mut cache {
+            cache.insert(&key, outcome.clone());
+        }
+        outcomes.push(ScenarioOutcome {
+            scenario: name,
+            track,
+            rubric: scenario.rubric.clone(),
+            seed,
+            error: outcome.error,
+            exit_code: outcome.exit_code,
+            max_duration,
+            assertions: outcome.assertions,
+            warnings: Vec::new(),
+            fingerprint: None,
+        });
+    }
+
+    if let Some(cache) = &cache {
+        cache.save()?;
+    }
+    if let Some(path) = output.json_out {
+        let data = serde_json::to_vec_pretty(&outcomes).context("Failed to serialize suite")?;
+        fs::write(path, &data)
+            .with_context(|| format!("Failed to write suite file {}", path.display()))?;
+    }
+    let tracked = outcomes.iter().any(|o| o.track != gradebook::DEFAULT_TRACK);
+    if tracked || output.weights.is_some() || output.gradebook.is_some() {
+        let book = Gradebook {
+            suite: info,
+            ..Gradebook::new(
+                outcomes
+                    .iter()
+                    .map(|o| (o.scenario.as_str(), o.track.as_str(), o.error.is_none())),
+                output.weights.unwrap_or(&TrackWeights::default()),
+            )?
+        };
+        println!("{book}");
+        if let Some(path) = output.gradebook {
+            let data = serde_json::to_vec_pretty(&book).context("Failed to serialize gradebook")?;
+            fs::write(path, &data)
+                .with_context(|| format!("Failed to write gradebook {}", path.display()))?;
+        }
+    }
+    // The suite exits with the status of its first failed scenario.
+    if let Some(first) = outcomes.iter().find(|o| o.error.is_some()) {
+        let failed = outcomes.iter().filter(|o| o.error.is_some()).count();
+        return Err(GradingFailed {
+            status: ExitStatus::from_code(first.exit_code).unwrap_or(ExitStatus::Environment),
+            message: format!("{} of {} scenarios failed", failed, outcomes.len()),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// ` (met by 4230 of 10000 ms)` after a PASS line: the latest time one of the assertions was
+/// met against the scenario's limit, so passes that barely made it stand out. Empty when
+/// no assertion recorded a time.
+fn time_budget(assertions: &[AssertionOutcome], max_duration: u64) -> String {
+    assertions
+        .iter()
+        .filter_map(|outcome| outcome.satisfied_at)
+        .max()
+        .map(|met| Msg::SuiteMetBy.fill(&[&met, &max_duration]))
+        .unwrap_or_default()
+}
+
+/// Grade `scenario` headless. A failure's message lists the hints the run matched
+/// after the failure itself.
+fn grade(
+    scenario: &TestScenario,
+    sender: Box<dyn TransportProtocol>,
+    receiver: Box<dyn TransportProtocol>,
+    options: RunOptions,
+    scratch_path: Option<&Path>,
+) -> Result<SimulationReport, GradingFailed> {
+    let result = scenario_runner::run_scenario_with(scenario, sender, receiver, options);
+    // Saved whether or not the run passed, since practice runs fail all the time.
+    if let Some(path) = scratch_path
+        && let Some(store) = result
+            .report
+            .as_ref()
+            .and_then(|r| r.scratch_store.as_ref())
+        && let Err(err) = store.save(path)
+    {
+        warn!("{err:#}");
+    }
+    for warning in result
+        .report
+        .iter()
+        .flat_map(|report| &report.metric_band_warnings)
+    {
+        warn!("{}", Msg::MetricBandWarning.fill(&[&warning.message()]));
+    }
+    let tally: String = result
+        .assertion_tally()
+        .filter(|_| !result.passed())
+        .map(|tally| format!("\n  {tally}"))
+        .unwrap_or_default();
+    let hints: String = result
+        .report
+        .iter()
+        .flat_map(|report| &report.hints)
+        .map(|hint| format!("\n  {}", Msg::Hint.fill(&[&hint.text])))
+        .collect();
+    result.into_result().map_err(|err| GradingFailed {
+        status: err.exit_status(),
+        message: format!("{err:#}{tally}{hints}"),
+    })
+}
+
+fn run_bench_bridge(args: &Args, loader: &ProtocolLoader, iterations: u32) -> Result<()> {
+    let mut results = Vec::new();
+    for (name, descriptor) in sender_backends(args)? {
+        info!("Benchmarking {} bridge ({} iterations)…", name, iterations);
+        let mut protocol = loader
+            .load(descriptor)
+            .with_context(|| format!("Failed to load {name} protocol for benchmarking"))?;
+        results.push(bench::bench_protocol(name, protocol.as_mut(), iterations));
+    }
+
+    let baseline = results[0].mean_callback();
+    println!(
+        "{:<8} {:>12} {:>14} {:>12} {:>12} {:>14}",
+        "backend", "init(us)", "app_data(us)", "packet(us)", "timer(us)", "overhead(us)"
+    );
+    for r in &results {
+        let overhead = r.mean_callback().saturating_sub(baseline);
+        println!(
+            "{:<8} {:>12.2} {:>14.3} {:>12.3} {:>12.3} {:>14.3}",
+            r.backend,
+            micros(r.init),
+            micros(r.on_app_data),
+            micros(r.on_packet),
+            micros(r.on_timer),
+            micros(overhead)
+        );
+    }
+    Ok(())
+}
+
+fn run_round_trip(args: &Args, loader: &ProtocolLoader) -> Result<()> {
+    let mut reports = vec![conformance::check_round_trip(
+        "rust",
+        &mut conformance::Reflector,
+    )];
+    // The first backend is the built-in sender, which is no reflector.
+    for (name, descriptor) in sender_backends(args)?.into_iter().skip(1) {
+        info!("Sending edge-case packets through the {} bridge…", name);
+        let mut protocol = loader
+            .load(descriptor)
+            .with_context(|| format!("Failed to load {name} reflector"))?;
+        reports.push(conformance::check_round_trip(name, protocol.as_mut()));
+    }
+
+    println!("{:<8} {:<16} result", "backend", "case");
+    for report in &reports {
+        for case in &report.cases {
+            match &case.mismatch {
+                None => println!("{:<8} {:<16} ok", report.backend, case.case),
+                Some(mismatch) => {
+                    println!("{:<8} {:<16} {}", report.backend, case.case, mismatch)
+                }
+            }
+        }
+    }
+    let failed: Vec<&str> = reports
+        .iter()
+        .filter(|report| !report.passed())
+        .map(|report| report.backend.as_str())
+        .collect();
+    if !failed.is_empty() {
+        return Err(GradingFailed {
+            status: ExitStatus::AssertionFailed,
+            message: format!("packets changed on the way through {}", failed.join(", ")),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+fn run_checksum(args: &Args, loader: &ProtocolLoader) -> Result<()> {
+    // The built-in sender computes the reference checksum itself.
+    let backends: Vec<_> = sender_backends(args)?.into_iter().skip(1).collect();
+    if backends.is_empty() {
+        anyhow::bail!(
+            "checksum needs a protocol to check: pass --java-sender, --python-sender or \
+             --cpp-sender-lib"
+        );
+    }
+    let mut reports = Vec::new();
+    for (name, descriptor) in backends {
+        let mut protocol = loader
+            .load(descriptor)
+            .with_context(|| format!("Failed to load {name} protocol"))?;
+        reports.push(conformance::check_checksums(name, protocol.as_mut()));
+    }
+
+    println!("{:<8} {:<18} result", "backend", "vector");
+    for report in &reports {
+        if !report.has_hook() {
+            println!(
+                "{:<8} {:<18} no checksum hook; override `checksum` in the protocol",
+                report.backend, "-"
+            );
+        }
+        for vector in report.vectors.iter().filter(|_| report.has_hook()) {
+            match vector.got {
+                _ if vector.passed() => {
+                    println!("{:<8} {:<18} ok", report.backend, vector.vector)
+                }
+                Some(got) => println!(
+                    "{:<8} {:<18} expected {:#06x}, got {:#06x}",
+                    report.backend, vector.vector, vector.expected, got
+                ),
+                None => println!(
+                    "{:<8} {:<18} expected {:#06x}, got nothing",
+                    report.backend, vector.vector, vector.expected
+                ),
+            }
+        }
+        for warning in &report.warnings {
+            println!("{:<8} {:<18} warning: {}", report.backend, "-", warning);
+        }
+    }
+    let failed: Vec<&str> = reports
+        .iter()
+        .filter(|report| !report.passed())
+        .map(|report| report.backend.as_str())
+        .collect();
+    if !failed.is_empty() {
+        return Err(GradingFailed {
+            status: ExitStatus::AssertionFailed,
+            message: format!(
+                "the checksum of {} does not match the test vectors",
+                failed.join(", ")
+            ),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+fn micros(d: std::time::Duration) -> f64 {
+    d.as_secs_f64() * 1e6
+}
+
+struct LogGuard {
+    _tui_buffer: Option<MemoryLogBuffer>,
+    #[cfg(feature = "otel")]
+    _otel: Option<tcp_lab_simulator::telemetry::OtelGuard>,
+}
+
+#[cfg(feature = "otel")]
+fn init_logging(use_tui: bool, otel: bool) -> Result<LogGuard> {
+    use tracing_subscriber::filter::LevelFilter;
+    use tracing_subscriber::prelude::*;
+
+    if !otel {
+        return Ok(LogGuard {
+            _tui_buffer: init_fmt_logging(use_tui),
+            _otel: None,
+        });
+    }
+
+    let (otel_layer, otel_guard) = tcp_lab_simulator::telemetry::otel_layer("tcp-lab-sim-cli")?;
+    let registry = tracing_subscriber::registry().with(otel_layer);
+    let buffer = if use_tui {
+        let buffer = MemoryLogBuffer::new();
+        let writer = buffer.clone();
+        let fmt_layer = tracing_subscriber::fmt::layer()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .with_filter(LevelFilter::INFO);
+        registry.with(fmt_layer).init();
+        Some(buffer)
+    } else {
+        registry
+            .with(tracing_subscriber::fmt::layer().with_filter(LevelFilter::INFO))
+            .init();
+        None
+    };
+    Ok(LogGuard {
+        _tui_buffer: buffer,
+        _otel: Some(otel_guard),
+    })
+}
+
+#[cfg(not(feature = "otel"))]
+fn init_logging(use_tui: bool, otel: bool) -> Result<LogGuard> {
+    if otel {
+        anyhow::bail!("`otel` feature disabled but --otel provided");
+    }
+    Ok(LogGuard {
+        _tui_buffer: init_fmt_logging(use_tui),
+    })
+}
+
+fn init_fmt_logging(use_tui: bool) -> Option<MemoryLogBuffer> {
     if use_tui {
         let buffer = MemoryLogBuffer::new();
         let writer = buffer.clone();
@@ -189,109 +1692,319 @@ fn init_logging(use_tui: bool) -> Option<MemoryLogBuffer> {
 }
 
 fn run_default_sim(
-    use_tui: bool,
+    frontend: Frontend,
+    workload: Option<&Workload>,
     sender: Box<dyn TransportProtocol>,
     receiver: Box<dyn TransportProtocol>,
+    extras: TuiExtras,
+    options: SimOptions,
 ) -> Result<SimulationReport> {
     let mut sim = build_default_sim(sender, receiver);
-    if use_tui {
-        let mut app = TuiApp::new(sim, None);
-        app.run()?;
-        let sim = app.into_simulator();
-        Ok(sim.export_report())
-    } else {
-        info!("Starting default headless simulation…");
-        sim.run_until_complete();
-        info!("Simulation complete.");
-        Ok(sim.export_report())
+    options.apply(&mut sim);
+    match workload {
+        Some(workload) => {
+            workload.schedule(&mut sim);
+            info!(
+                "Starting default simulation: {} {} messages of {} bytes every {} ms…",
+                workload.messages, workload.pattern, workload.size, workload.interval
+            );
+        }
+        None => info!("Starting default simulation…"),
     }
+    let report = drive(sim, None, frontend, extras)?;
+    info!("Simulation complete.");
+    options.save(&report)?;
+    Ok(report)
 }
 
 fn build_default_sim(
     sender: Box<dyn TransportProtocol>,
     receiver: Box<dyn TransportProtocol>,
 ) -> Simulator {
-    let config = SimConfig {
-        loss_rate: 0.1,
-        min_latency: 100,
-        max_latency: 500,
-        seed: 42,
-        ..Default::default()
-    };
-    let mut sim = Simulator::new(config, sender, receiver);
-    sim.schedule_app_send(1000, b"Packet 1".to_vec());
-    sim.schedule_app_send(2000, b"Packet 2".to_vec());
-    sim.schedule_app_send(3000, b"Packet 3".to_vec());
-    sim
+    Simulator::new(workload::default_config(), sender, receiver)
 }
 
-fn run_scenario_tui(
+/// Replay a scenario in a visualizer or paced run. Assertions are only checked
+/// by the headless scenario runner.
+fn run_scenario_interactive(
+    frontend: Frontend,
     scenario: TestScenario,
     sender: Box<dyn TransportProtocol>,
     receiver: Box<dyn TransportProtocol>,
+    factories: Vec<(NodeId, ProtocolFactory)>,
+    extras: TuiExtras,
+    options: SimOptions,
 ) -> Result<SimulationReport> {
+    let mut sim = build_scenario_sim(&scenario, sender, receiver, factories)?;
+    options.apply(&mut sim);
+    info!("Replaying scenario {}", scenario.name);
+    let report = drive(sim, Some(scenario.name), frontend, extras)?;
+    options.save(&report)?;
+    Ok(report)
+}
+
+/// Rebuild `scenario`'s run with the implementation reloaded from disk, for the TUI's
+/// `r` key.
+fn scenario_reload(
+    loader: &ProtocolLoader,
+    request: LoaderRequest,
+    scenario: &TestScenario,
+) -> Reload {
+    let loader = loader.clone();
+    let scenario = scenario.clone();
+    Box::new(move || {
+        loader.reload(&request)?;
+        let pair = request.try_clone().expect("Rust descriptors are excluded");
+        let (sender, receiver) = loader.load_pair(pair)?;
+        let factories = loader.factories(&request);
+        build_scenario_sim(&scenario, sender, receiver, factories)
+    })
+}
+
+fn build_scenario_sim(
+    scenario: &TestScenario,
+    sender: Box<dyn TransportProtocol>,
+    receiver: Box<dyn TransportProtocol>,
+    factories: Vec<(NodeId, ProtocolFactory)>,
+) -> Result<Simulator> {
     let mut config = SimConfig::default();
     scenario.config.apply_to(&mut config);
-    let mut sim = Simulator::new(config, sender, receiver);
-    configure_actions(&mut sim, &scenario.actions);
-
-    let mut app = TuiApp::new(sim, Some(scenario.name.clone()));
-    app.run()?;
-    let sim = app.into_simulator();
-    Ok(sim.export_report())
+    let mut sim = Simulator::builder(config, sender, receiver)
+        .init_params(scenario.params.clone())
+        .factories(factories)
+        .build()
+        .map_err(|err| anyhow::anyhow!(Msg::InvalidConfig.fill(&[&err])))?;
+    scenario_runner::configure_actions(&mut sim, &scenario.actions);
+    Ok(sim)
 }
 
 fn run_encda_sim(
-    use_tui: bool,
+    frontend: Frontend,
     dataset: encda::EncdaDataset,
     sender: Box<dyn TransportProtocol>,
     receiver: Box<dyn TransportProtocol>,
+    options: SimOptions,
 ) -> Result<SimulationReport> {
     let mut sim = build_default_sim(sender, receiver);
+    options.apply(&mut sim);
     for (idx, chunk) in dataset.groups.iter().enumerate() {
         let time = (idx as u64) * 10;
         sim.schedule_app_send(time, chunk.clone());
     }
-    if use_tui {
-        let mut app = TuiApp::new(sim, Some("ENCDA Trace".to_string()));
-        app.run()?;
-        Ok(app.into_simulator().export_report())
-    } else {
-        info!(
-            "Running ENCDA trace with {} groups of {} bytes",
-            dataset.groups.len(),
-            dataset.group_size
-        );
-        sim.run_until_complete();
-        Ok(sim.export_report())
+    info!(
+        "Running ENCDA trace with {} groups of {} bytes",
+        dataset.groups.len(),
+        dataset.group_size
+    );
+    let report = drive(
+        sim,
+        Some("ENCDA Trace".to_string()),
+        frontend,
+        TuiExtras::default(),
+    )?;
+    options.save(&report)?;
+    Ok(report)
+}
+
+/// Switches for the simulator of a single run, whichever frontend drives it.
+struct SimOptions {
+    debug_events: bool,
+    /// `--scratch-store` and what it held before the run.
+    scratch_store: Option<(PathBuf, ScratchStore)>,
+}
+
+impl SimOptions {
+    fn apply(&self, sim: &mut Simulator) {
+        sim.set_debug_events(self.debug_events);
+        if let Some((_, store)) = &self.scratch_store {
+            sim.set_scratch_store(store.clone());
+        }
+    }
+
+    /// Keep what the protocols stored for the next run.
+    fn save(&self, report: &SimulationReport) -> Result<()> {
+        if let Some((path, _)) = &self.scratch_store
+            && let Some(store) = &report.scratch_store
+        {
+            store.save(path)?;
+        }
+        Ok(())
     }
 }
 
-fn configure_actions(sim: &mut Simulator, actions: &[TestAction]) {
-    for action in actions {
-        match action {
-            TestAction::AppSend { time, data } => {
-                sim.schedule_app_send(*time, data.as_bytes().to_vec());
+/// What the TUI offers beyond replaying the run; other frontends ignore it.
+#[derive(Default)]
+struct TuiExtras {
+    /// Restarts the run with the implementation reloaded, for the `r` key.
+    reload: Option<Reload>,
+    /// Builds the reference run drawn side by side with it.
+    reference: Option<Reload>,
+    /// A real transfer drawn next to it.
+    capture: Option<Capture>,
+    /// The scenario's `metric_bands`, drawn on the charts.
+    metric_bands: Vec<MetricBand>,
+}
+
+/// Run `sim` through the selected frontend and export the final report.
+fn drive(
+    mut sim: Simulator,
+    title: Option<String>,
+    frontend: Frontend,
+    extras: TuiExtras,
+) -> Result<SimulationReport> {
+    match frontend {
+        Frontend::Headless => sim.run_until_complete(),
+        Frontend::Paced { scale, live } => {
+            info!("Pacing at {}x real time", scale);
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_time()
+                .build()
+                .context("Failed to start the pacing runtime")?;
+            if live {
+                info!("Sending each line read from stdin; end the input to finish");
+                let mut input = LiveInput::stdin();
+                runtime.block_on(realtime::run_paced_live(&mut sim, scale, &mut input))?;
+            } else {
+                runtime.block_on(realtime::run_paced(&mut sim, scale))?;
             }
-            TestAction::DropNextFromSenderSeq { seq } => {
-                sim.add_drop_sender_seq_once(*seq);
+        }
+        Frontend::Tui { mode, live } => {
+            let mut app = TuiApp::new(sim, title);
+            app.set_render_mode(mode);
+            if let Some(reload) = extras.reload {
+                app.set_reload(reload);
             }
-            TestAction::CorruptNextFromSenderSeq { seq } => {
-                sim.add_corrupt_sender_seq_once(*seq);
+            if let Some(reference) = extras.reference {
+                app.set_reference(reference)?;
             }
-            TestAction::DropNextFromReceiverAck { ack } => {
-                sim.add_drop_receiver_ack_once(*ack);
+            if let Some(capture) = extras.capture {
+                app.set_capture(capture);
             }
+            app.set_metric_bands(extras.metric_bands);
+            if live {
+                // The TUI reads keys from the terminal, so typed input goes
+                // through its prompt and only piped stdin is read directly.
+                if std::io::stdin().is_terminal() {
+                    app.set_typed_input(true);
+                } else {
+                    app.set_live_input(LiveInput::stdin());
+                }
+            }
+            app.run()?;
+            sim = app.into_simulator();
+        }
+        #[cfg(feature = "gui")]
+        Frontend::Gui => {
+            let mut app = tcp_lab_gui::GuiApp::new(sim, title);
+            app.run()?;
+            sim = app.into_simulator();
+        }
+    }
+    Ok(sim.export_report())
+}
+
+/// `trace`: print the records of a binary trace between `from` and `until`.
+fn print_trace(path: &Path, from: Option<u64>, until: Option<u64>) -> Result<()> {
+    if bundle::is_bundle(path) {
+        let mut bundle = Bundle::open(path)?;
+        let reader = bundle
+            .trace()
+            .with_context(|| format!("In {}", path.display()))?;
+        return print_records(reader, from, until);
+    }
+    print_records(TraceReader::open(path)?, from, until)
+}
+
+fn print_records<R: Read + Seek>(
+    mut reader: TraceReader<R>,
+    from: Option<u64>,
+    until: Option<u64>,
+) -> Result<()> {
+    if let Some(from) = from {
+        reader.seek_to_time(from)?;
+    }
+    for record in reader {
+        let record = record?;
+        if until.is_some_and(|until| record.time() > until) {
+            break;
+        }
+        match record {
+            TraceRecord::Link(event) => println!("{:>8}  {}", event.time, event.description),
+            TraceRecord::Packet(event) => println!(
+                "{:>8}  {:?} {:?} seq={} ack={} flags={:#04x} len={}{}",
+                event.time,
+                event.kind,
+                event.node,
+                event.seq,
+                event.ack,
+                event.flags,
+                event.len,
+                if event.ambiguous { " ambiguous" } else { "" }
+            ),
         }
     }
+    Ok(())
 }
 
-fn load_scenario(path: &Path) -> Result<TestScenario> {
+/// `builtins`: list the protocols `--builtin-sender`/`--builtin-receiver` accept.
+fn print_builtins() {
+    for example in examples::catalog() {
+        println!(
+            "{:<8}  {:<6}  {}",
+            example.name, example.stage, example.description
+        );
+    }
+}
+
+/// `progress`: the stages of the course progression in `file` that the suite results in
+/// `cache` complete, and what the next one still needs. Only results for the
+/// implementation as it is now count, so a change since the last suite run shows its
+/// scenarios as not graded.
+fn print_progress(args: &Args, file: &Path, cache: &Path) -> Result<()> {
+    let progression = Progression::load(file)?;
+    let cache = GradeCache::load(cache);
+    let artifact = suite_artifact(args, &args.hint_catalog()?)?;
+    let progress = progression.progress(|path| {
+        let mut scenario = scenario_runner::load_scenario(path, args.strict)?;
+        if let Some(seed) = args.student_seed() {
+            seed.apply(&mut scenario);
+        }
+        let key = CacheKey::new(path, &scenario, artifact)?;
+        Ok(match cache.get(&key) {
+            Some(outcome) if outcome.error.is_none() => ScenarioStatus::Passed,
+            Some(_) => ScenarioStatus::Failed,
+            None => ScenarioStatus::NotGraded,
+        })
+    })?;
+    println!("{progress}");
+    Ok(())
+}
+
+/// The `CacheKey::artifact` of the implementation `args` selects.
+fn suite_artifact(args: &Args, hints: &HintCatalog) -> Result<u64> {
+    let exe = std::env::current_exe().context("Failed to locate the grader binary")?;
+    // The hints are part of each failure's message.
+    let options = format!("{}{:?}", args.protocol_options(), hints);
+    cache::hash_artifact(&args.implementation_paths(), &exe, &options)
+}
+
+/// `migrate`: upgrade an older scenario file, printing each change as a warning.
+fn migrate(path: &Path, out: Option<&Path>) -> Result<()> {
     let content = fs::read_to_string(path)
         .with_context(|| format!("Failed to read scenario file {}", path.display()))?;
-    let scenario: TestScenario =
-        toml::from_str(&content).context("Failed to parse scenario file")?;
-    Ok(scenario)
+    let migration = scenario_runner::migrate_scenario(&content)?;
+    if migration.changes.is_empty() {
+        eprintln!("{}", Msg::MigrateUpToDate.text());
+    }
+    for change in &migration.changes {
+        eprintln!("warning: {change}");
+    }
+    match out {
+        Some(out) => fs::write(out, &migration.content)
+            .with_context(|| format!("Failed to write {}", out.display()))?,
+        None => print!("{}", migration.content),
+    }
+    Ok(())
 }
 
 fn write_trace(path: &Path, report: &SimulationReport) -> Result<()> {
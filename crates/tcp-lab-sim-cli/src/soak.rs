@@ -0,0 +1,338 @@
+//! `--soak`: grade an implementation on randomized scenarios for hours, so staff can
+//! shake out rare failures of the reference implementations and the engine before a
+//! semester starts.
+//!
+//! Without `--scenario`, every run draws a new link (loss, corruption, latency) and
+//! workload, and the scenario only asks that everything sent before a final shutdown is
+//! delivered. With `--scenario`, the scenario runs with a new seed each time. A failed
+//! run's report is written gzip-compressed, next to the scenario it ran and that scenario
+//! shrunk by `repro::minimize`, and only the newest ones are kept. `summary.json` is
+//! rewritten every minute with the failure rates.
+
+use anyhow::{Context, Result};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use rand::distr::Alphanumeric;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use tcp_lab_simulator::ScenarioResult;
+use tcp_lab_simulator::i18n::Msg;
+use tcp_lab_simulator::repro::Repro;
+
+/// How often progress is printed and `summary.json` rewritten.
+const SUMMARY_EVERY: Duration = Duration::from_secs(60);
+
+pub struct SoakOptions {
+    pub duration: Duration,
+    /// Where failure reports and `summary.json` go.
+    pub dir: PathBuf,
+    /// Failed runs whose reports are kept; older ones are deleted. At least 1, since the
+    /// newest report is written before the oldest are deleted.
+    pub keep: usize,
+    /// Whether failed runs are shrunk into a small scenario that fails the same way.
+    pub shrink: bool,
+}
+
+/// One run to grade: the seed, and the scenario with that seed as TOML.
+pub struct SoakCase {
+    pub seed: u64,
+    pub toml: String,
+}
+
+/// A run of the scenario `content` (`--scenario`) with a random seed.
+pub fn seeded_case(rng: &mut StdRng, content: &str) -> Result<SoakCase> {
+    let seed = random_seed(rng);
+    let mut table: toml::Table = toml::from_str(content).context("Failed to parse scenario")?;
+    let config = table
+        .entry("config")
+        .or_insert_with(|| toml::Table::new().into());
+    let config = config
+        .as_table_mut()
+        .context("Scenario `config` is not a table")?;
+    config.insert("seed".into(), (seed as i64).into());
+    Ok(SoakCase {
+        seed,
+        toml: toml::to_string(&table).context("Failed to serialize scenario")?,
+    })
+}
+
+/// A scenario with a random link and workload, as TOML so a failure can be replayed with
+/// `--scenario`. It fails if data written before the final shutdown is never delivered.
+pub fn random_case(rng: &mut StdRng) -> SoakCase {
+    let seed = random_seed(rng);
+    let loss = f64::from(rng.random_range(0..=30u8)) / 100.0;
+    let corrupt = f64::from(rng.random_range(0..=20u8)) / 100.0;
+    let min_latency = rng.random_range(1..=100u64);
+    let max_latency = min_latency + rng.random_range(0..=200u64);
+    let messages = rng.random_range(1..=10u64);
+    let interval = rng.random_range(10..=1000u64);
+
+    let mut toml = String::new();
+    let _ = writeln!(toml, "name = \"soak {seed}\"");
+    let _ = writeln!(
+        toml,
+        "description = \"{messages} messages every {interval} ms over {loss:.2} loss, \
+         {corrupt:.2} corruption, {min_latency}-{max_latency} ms latency\"\n"
+    );
+    let _ = writeln!(toml, "[config]");
+    let _ = writeln!(toml, "seed = {seed}");
+    let _ = writeln!(toml, "loss_rate = {loss:.2}");
+    let _ = writeln!(toml, "corrupt_rate = {corrupt:.2}");
+    let _ = writeln!(toml, "min_latency = {min_latency}");
+    let _ = writeln!(toml, "max_latency = {max_latency}");
+    for i in 0..messages {
+        let size = rng.random_range(1..=64);
+        let data: String = (&mut *rng)
+            .sample_iter(Alphanumeric)
+            .take(size)
+            .map(char::from)
+            .collect();
+        let _ = writeln!(
+            toml,
+            "\n[[actions]]\ntype = \"app_send\"\ntime = {}\ndata = \"{data}\"",
+            i * interval
+        );
+    }
+    let last_send = (messages - 1) * interval;
+    let _ = writeln!(
+        toml,
+        "\n[[actions]]\ntype = \"shutdown\"\ntime = {}",
+        last_send + 1
+    );
+    // Long enough for every message to survive a run of bad luck on a slow link.
+    let _ = writeln!(
+        toml,
+        "\n[[assertions]]\ntype = \"max_duration\"\nms = {}",
+        last_send + 200 * max_latency + 60_000
+    );
+    SoakCase { seed, toml }
+}
+
+/// A seed a scenario file can hold: TOML integers are signed.
+fn random_seed(rng: &mut StdRng) -> u64 {
+    rng.random_range(0..=i64::MAX as u64)
+}
+
+/// Failure rates of a soak so far, as written to `summary.json`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SoakSummary {
+    pub elapsed_s: u64,
+    pub runs: u64,
+    pub failed: u64,
+    /// Percentage of runs that failed.
+    pub failure_rate: f64,
+    /// Failed runs per kind of failure, e.g. `timeout` or `deadlock`.
+    pub by_kind: BTreeMap<String, u64>,
+    /// Seed of the run that took the longest wall-clock time, and how long (ms).
+    pub slowest_seed: Option<u64>,
+    pub slowest_ms: u64,
+}
+
+/// What a failure report holds besides the run's own report.
+#[derive(Serialize)]
+struct FailureReport<'a> {
+    seed: u64,
+    scenario: &'a str,
+    failures: Vec<String>,
+    report: Option<&'a tcp_lab_simulator::SimulationReport>,
+}
+
+/// Records runs, writes and rotates failure reports, and keeps the summary.
+pub struct Soak {
+    options: SoakOptions,
+    started: Instant,
+    last_summary: Instant,
+    summary: SoakSummary,
+    /// Files of the kept failure reports, oldest first.
+    kept: VecDeque<Vec<PathBuf>>,
+}
+
+impl Soak {
+    pub fn new(options: SoakOptions) -> Result<Self> {
+        fs::create_dir_all(&options.dir)
+            .with_context(|| format!("Failed to create {}", options.dir.display()))?;
+        let now = Instant::now();
+        Ok(Self {
+            options,
+            started: now,
+            last_summary: now,
+            summary: SoakSummary::default(),
+            kept: VecDeque::new(),
+        })
+    }
+
+    /// Whether the soak should start another run.
+    pub fn running(&self) -> bool {
+        self.started.elapsed() < self.options.duration
+    }
+
+    pub fn summary(&self) -> &SoakSummary {
+        &self.summary
+    }
+
+    /// Count a finished run, and write its report if it failed, shrinking its scenario
+    /// with `shrink` unless the options turned that off.
+    pub fn record(
+        &mut self,
+        case: &SoakCase,
+        result: &ScenarioResult,
+        took: Duration,
+        shrink: impl FnOnce(&str) -> Option<Repro>,
+    ) -> Result<()> {
+        let summary = &mut self.summary;
+        summary.runs += 1;
+        let took_ms = took.as_millis() as u64;
+        if summary.slowest_seed.is_none() || took_ms > summary.slowest_ms {
+            summary.slowest_seed = Some(case.seed);
+            summary.slowest_ms = took_ms;
+        }
+        if let Some(first) = result.failures.first() {
+            summary.failed += 1;
+            *summary.by_kind.entry(first.kind().to_string()).or_default() += 1;
+            println!("{}", Msg::SoakFail.fill(&[&case.seed, first]));
+            self.write_failure(case, result, shrink)?;
+        }
+        if self.last_summary.elapsed() >= SUMMARY_EVERY {
+            self.last_summary = Instant::now();
+            println!("{}", self.progress());
+            self.write_summary()?;
+        }
+        Ok(())
+    }
+
+    /// `soak 1:05:00: 5230 runs, 2 failed (0.04%)`.
+    pub fn progress(&self) -> String {
+        let s = &self.summary;
+        Msg::SoakProgress.fill(&[
+            &clock(self.started.elapsed()),
+            &s.runs,
+            &s.failed,
+            &format!("{:.2}", rate(s.failed, s.runs)),
+        ])
+    }
+
+    /// Bring the summary up to date and write it to `summary.json`.
+    pub fn write_summary(&mut self) -> Result<()> {
+        self.summary.elapsed_s = self.started.elapsed().as_secs();
+        self.summary.failure_rate = rate(self.summary.failed, self.summary.runs);
+        let path = self.options.dir.join("summary.json");
+        let data =
+            serde_json::to_vec_pretty(&self.summary).context("Failed to serialize soak summary")?;
+        fs::write(&path, data).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    fn write_failure(
+        &mut self,
+        case: &SoakCase,
+        result: &ScenarioResult,
+        shrink: impl FnOnce(&str) -> Option<Repro>,
+    ) -> Result<()> {
+        let stem = format!("failure-{:06}-{}", self.summary.runs, case.seed);
+        let path = self.options.dir.join(format!("{stem}.toml"));
+        fs::write(&path, &case.toml)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        let mut files = vec![path];
+        if self.options.shrink
+            && let Some(repro) = shrink(&case.toml)
+        {
+            let path = self.options.dir.join(format!("{stem}.min.toml"));
+            fs::write(&path, &repro.content)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            println!("{}", Msg::SoakShrunk.fill(&[&repro.runs, &path.display()]));
+            files.push(path);
+        }
+        let report = FailureReport {
+            seed: case.seed,
+            scenario: &case.toml,
+            failures: result.failures.iter().map(|f| format!("{f:#}")).collect(),
+            report: result.report.as_ref(),
+        };
+        let path = self.options.dir.join(format!("{stem}.json.gz"));
+        write_gzip_json(&path, &report)?;
+        files.push(path);
+
+        self.kept.push_back(files);
+        while self.kept.len() > self.options.keep {
+            for path in self.kept.pop_front().into_iter().flatten() {
+                let _ = fs::remove_file(path);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_gzip_json(path: &Path, value: &impl Serialize) -> Result<()> {
+    let file =
+        fs::File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut encoder = GzEncoder::new(file, Compression::default());
+    serde_json::to_writer(&mut encoder, value).context("Failed to serialize failure report")?;
+    encoder
+        .finish()
+        .and_then(|mut file| file.flush())
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn rate(failed: u64, runs: u64) -> f64 {
+    if runs == 0 {
+        0.0
+    } else {
+        failed as f64 * 100.0 / runs as f64
+    }
+}
+
+/// `h:mm:ss`.
+fn clock(elapsed: Duration) -> String {
+    let s = elapsed.as_secs();
+    format!("{}:{:02}:{:02}", s / 3600, s / 60 % 60, s % 60)
+}
+
+/// The seed the random cases are drawn from, different for every soak.
+pub fn fresh_rng() -> StdRng {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64);
+    StdRng::seed_from_u64(nanos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tcp_lab_loader::{ProtocolDescriptor, ProtocolLoader, spec};
+    use tcp_lab_simulator::scenario_runner;
+    use tcp_lab_simulator::{ScenarioError, SimulationReport};
+
+    /// Parse `toml` as the soak does and grade rdt2 on it.
+    fn run(loader: &ProtocolLoader, toml: &str) -> Result<SimulationReport, ScenarioError> {
+        let scenario = scenario_runner::parse_scenario(toml, true).unwrap();
+        let load = |is_sender| {
+            let builtin = spec::builtin_by_name("rdt2", is_sender).unwrap();
+            loader.load(ProtocolDescriptor::BuiltIn(builtin)).unwrap()
+        };
+        scenario_runner::run_scenario(&scenario, load(true), load(false), true)
+    }
+
+    #[test]
+    fn test_cases_run() {
+        let loader = ProtocolLoader::builder().build().unwrap();
+        let mut rng = StdRng::seed_from_u64(5054);
+
+        let case = random_case(&mut rng);
+        let report = run(&loader, &case.toml).unwrap();
+        assert_eq!(report.config.seed, case.seed);
+
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../tests/test_rdt3.toml");
+        let content = fs::read_to_string(path).unwrap();
+        let case = seeded_case(&mut rng, &content).unwrap();
+        let scenario = scenario_runner::parse_scenario(&case.toml, true).unwrap();
+        assert_eq!(scenario.config.seed, Some(case.seed));
+        run(&loader, &case.toml).unwrap();
+    }
+}
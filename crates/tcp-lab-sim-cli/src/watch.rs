@@ -0,0 +1,201 @@
+//! `watch`: rebuild and re-grade a suite every time a watched file changes.
+//!
+//! Each round runs the `suite` subcommand in a fresh process, so Java classes, Python
+//! modules and C++ libraries are loaded again instead of the copies from the first round.
+
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use tcp_lab_simulator::ScenarioOutcome;
+use tcp_lab_simulator::i18n::Msg;
+
+pub struct WatchOptions {
+    /// Arguments that start the child's `suite` run, after the executable.
+    pub suite_args: Vec<OsString>,
+    /// Shell command run before every round; a failure skips the round.
+    pub build: Option<String>,
+    /// Files and directories whose changes start a new round.
+    pub paths: Vec<PathBuf>,
+    pub notify: bool,
+    pub poll: Duration,
+}
+
+/// Grade, wait for a change, repeat; only returns if the binary cannot be found.
+pub fn run(options: WatchOptions) -> Result<()> {
+    let exe = std::env::current_exe().context("Failed to locate the tcp-lab-sim-cli binary")?;
+    let results = std::env::temp_dir().join(format!("tcp-lab-watch-{}.json", std::process::id()));
+    let mut previous: Option<BTreeMap<String, bool>> = None;
+    let mut round = 0;
+    loop {
+        round += 1;
+        println!("{}", Msg::WatchRound.fill(&[&round]));
+        let summary = match build(options.build.as_deref()) {
+            Err(failure) => {
+                println!("{failure}");
+                Msg::WatchBuildFailed.text().to_string()
+            }
+            Ok(()) => match grade(&exe, &options.suite_args, &results) {
+                Ok(outcomes) => {
+                    let (summary, passed) = report(&outcomes, previous.as_ref());
+                    previous = Some(passed);
+                    summary
+                }
+                Err(failure) => {
+                    println!("{failure:#}");
+                    Msg::WatchGradingFailed.text().to_string()
+                }
+            },
+        };
+        if options.notify {
+            notify(&summary);
+        }
+
+        // Taken after the round, so files the build or the protocols wrote do not
+        // start another one.
+        let seen = snapshot(&options.paths);
+        println!("{}", Msg::WatchWaiting.fill(&[&seen.len()]));
+        while snapshot(&options.paths) == seen {
+            thread::sleep(options.poll);
+        }
+    }
+}
+
+fn build(command: Option<&str>) -> Result<(), String> {
+    let Some(command) = command else {
+        return Ok(());
+    };
+    println!("$ {command}");
+    let output = if cfg!(windows) {
+        Command::new("cmd").args(["/C", command]).output()
+    } else {
+        Command::new("sh").args(["-c", command]).output()
+    };
+    match output {
+        Ok(output) if output.status.success() => Ok(()),
+        Ok(output) => Err(format!(
+            "Build failed ({}):\n{}",
+            output.status,
+            tail(&output)
+        )),
+        Err(err) => Err(format!("Failed to run the build command: {err}")),
+    }
+}
+
+fn grade(exe: &Path, suite_args: &[OsString], results: &Path) -> Result<Vec<ScenarioOutcome>> {
+    let _ = fs::remove_file(results);
+    let output = Command::new(exe)
+        .args(suite_args)
+        .arg("--json-out")
+        .arg(results)
+        .output()
+        .context("Failed to start the suite run")?;
+    // Failing scenarios make the run exit with an error after writing their results;
+    // without the file, the suite could not run at all.
+    let data = fs::read(results)
+        .map_err(|_| anyhow::anyhow!("Suite run failed ({}):\n{}", output.status, tail(&output)))?;
+    serde_json::from_slice(&data).context("Failed to read the suite results")
+}
+
+/// Print the failures and what changed since the last round; returns a one-line summary
+/// and which scenarios passed.
+fn report(
+    outcomes: &[ScenarioOutcome],
+    previous: Option<&BTreeMap<String, bool>>,
+) -> (String, BTreeMap<String, bool>) {
+    let passed: BTreeMap<String, bool> = outcomes
+        .iter()
+        .map(|outcome| (outcome.scenario.clone(), outcome.error.is_none()))
+        .collect();
+    let count = passed.values().filter(|passed| **passed).count();
+    let mut summary = Msg::WatchPassed.fill(&[&count, &outcomes.len()]);
+    if let Some(previous) = previous {
+        let changed = |now: bool| {
+            passed
+                .iter()
+                .filter(|(name, passed)| **passed == now && previous.get(*name) == Some(&!now))
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>()
+        };
+        let (fixed, broken) = (changed(true), changed(false));
+        if !fixed.is_empty() {
+            summary.push_str(&Msg::WatchNowPassing.fill(&[&fixed.join(", ")]));
+        }
+        if !broken.is_empty() {
+            summary.push_str(&Msg::WatchNowFailing.fill(&[&broken.join(", ")]));
+        }
+    }
+    for outcome in outcomes {
+        if let Some(error) = &outcome.error {
+            let first = error.lines().next().unwrap_or_default();
+            println!("{}", Msg::WatchFail.fill(&[&outcome.scenario, &first]));
+        }
+    }
+    println!("{summary}");
+    (summary, passed)
+}
+
+/// Last lines of a command's stderr (or stdout if that is empty).
+fn tail(output: &Output) -> String {
+    let text = if output.stderr.is_empty() {
+        String::from_utf8_lossy(&output.stdout)
+    } else {
+        String::from_utf8_lossy(&output.stderr)
+    };
+    let lines: Vec<&str> = text.lines().collect();
+    lines[lines.len().saturating_sub(20)..].join("\n")
+}
+
+/// Size and modification time of every file under `paths`.
+fn snapshot(paths: &[PathBuf]) -> BTreeMap<PathBuf, (u64, Option<SystemTime>)> {
+    files_under(paths)
+        .into_iter()
+        .filter_map(|path| {
+            let meta = fs::metadata(&path).ok()?;
+            Some((path, (meta.len(), meta.modified().ok())))
+        })
+        .collect()
+}
+
+/// Every file in `paths` or below them, skipping hidden entries, in path order.
+pub fn files_under(paths: &[PathBuf]) -> BTreeSet<PathBuf> {
+    let mut files = BTreeSet::new();
+    let mut pending: Vec<PathBuf> = paths.to_vec();
+    while let Some(path) = pending.pop() {
+        let Ok(meta) = fs::metadata(&path) else {
+            continue;
+        };
+        if !meta.is_dir() {
+            files.insert(path);
+        } else if let Ok(entries) = fs::read_dir(&path) {
+            pending.extend(
+                entries
+                    .flatten()
+                    .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+                    .map(|entry| entry.path()),
+            );
+        }
+    }
+    files
+}
+
+/// Best-effort desktop notification; missing notifiers are ignored.
+fn notify(summary: &str) {
+    let result = if cfg!(target_os = "macos") {
+        let script = format!(
+            "display notification \"{}\" with title \"tcp-lab\"",
+            summary.replace('"', "'")
+        );
+        Command::new("osascript").args(["-e", &script]).output()
+    } else {
+        Command::new("notify-send")
+            .args(["tcp-lab", summary])
+            .output()
+    };
+    let _ = result;
+}
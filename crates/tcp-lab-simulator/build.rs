@@ -0,0 +1,42 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn main() {
+    // Embed the official scenarios under the workspace's `tests/` as `builtin:` scenarios.
+    // Each is named by its path under `tests/` without `.toml`, e.g. `lab3/gbn_basic`.
+    let root = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR").unwrap()).join("../../tests");
+    let mut scenarios = Vec::new();
+    collect(&root, "", &mut scenarios);
+    scenarios.sort();
+
+    let mut out = String::from("&[\n");
+    for (name, path) in &scenarios {
+        out.push_str(&format!("    ({name:?}, include_str!({path:?})),\n"));
+    }
+    out.push_str("]\n");
+    let dest = PathBuf::from(env::var_os("OUT_DIR").unwrap()).join("builtin_scenarios.rs");
+    fs::write(dest, out).unwrap();
+    println!("cargo:rerun-if-changed=build.rs");
+}
+
+fn collect(dir: &Path, prefix: &str, scenarios: &mut Vec<(String, String)>) {
+    println!("cargo:rerun-if-changed={}", dir.display());
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if path.is_dir() {
+            // `expected/` holds regression baselines, not scenarios.
+            if !(prefix.is_empty() && name == "expected") {
+                collect(&path, &format!("{prefix}{name}/"), scenarios);
+            }
+        } else if let Some(stem) = name.strip_suffix(".toml") {
+            let path = fs::canonicalize(&path).unwrap_or(path);
+            println!("cargo:rerun-if-changed={}", path.display());
+            scenarios.push((format!("{prefix}{stem}"), path.display().to_string()));
+        }
+    }
+}
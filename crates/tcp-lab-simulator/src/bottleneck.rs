@@ -0,0 +1,208 @@
+//! The bottleneck queue of `SimConfig::bottleneck`, one per direction of the link. Packets
+//! wait behind the bytes queued before them, so a sender that overruns `rate` builds up
+//! queueing delay until the buffer overflows or the AQM starts dropping or marking.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tcp_lab_abstract::{AqmConfig, BottleneckConfig};
+
+/// What the bottleneck does with one packet. `queued_ms` is how long it waits (or would
+/// have waited) behind the packets queued before it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verdict {
+    /// Sent on after `delay_ms` (fractional) of queueing and transmission, with ECE set if
+    /// `marked`.
+    Forward {
+        queued_ms: u64,
+        delay_ms: f64,
+        marked: bool,
+    },
+    /// The buffer had no room for it.
+    TailDrop { queued_ms: u64 },
+    /// The AQM dropped it before the buffer filled up.
+    EarlyDrop { queued_ms: u64 },
+}
+
+#[derive(Debug)]
+pub struct Bottleneck {
+    config: BottleneckConfig,
+    /// When everything queued so far has been sent, in fractional ms.
+    busy_until: f64,
+    codel: CodelState,
+    /// RED's drop decisions; separate from the engine's stream so loss and latency draws
+    /// stay unchanged.
+    rng: StdRng,
+}
+
+#[derive(Debug, Default)]
+struct CodelState {
+    /// When the delay, above target since then, will have stayed there for an interval.
+    /// `None` while it is below target.
+    above_until: Option<f64>,
+    /// When the next drop is due; `None` while not dropping.
+    drop_next: Option<f64>,
+    /// Drops since CoDel started dropping.
+    count: u32,
+}
+
+impl Bottleneck {
+    pub fn new(config: BottleneckConfig, seed: u64) -> Self {
+        Self {
+            config,
+            busy_until: 0.0,
+            codel: CodelState::default(),
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Queue `bytes` arriving at `time_us`. Only `markable` packets (those carrying data)
+    /// can be marked instead of dropped by an ECN-enabled AQM.
+    pub fn enqueue(&mut self, time_us: u64, bytes: usize, markable: bool) -> Verdict {
+        let now = time_us as f64 / 1000.0;
+        let start = self.busy_until.max(now);
+        let queued = start - now;
+        let queued_ms = queued.round() as u64;
+        if queued > self.config.buffer_ms as f64 {
+            return Verdict::TailDrop { queued_ms };
+        }
+        let (signal, ecn) = match self.config.aqm {
+            None => (false, false),
+            Some(AqmConfig::Red {
+                min_ms,
+                max_ms,
+                max_p,
+                ecn,
+            }) => (self.red(queued, min_ms, max_ms, max_p), ecn),
+            Some(AqmConfig::Codel {
+                target_ms,
+                interval_ms,
+                ecn,
+            }) => (self.codel(now, queued, target_ms, interval_ms), ecn),
+        };
+        let marked = signal && ecn && markable;
+        if signal && !marked {
+            return Verdict::EarlyDrop { queued_ms };
+        }
+        self.busy_until = start + bytes as f64 * 1000.0 / self.config.rate.max(1) as f64;
+        Verdict::Forward {
+            queued_ms,
+            delay_ms: self.busy_until - now,
+            marked,
+        }
+    }
+
+    /// Bytes waiting to be sent at `time_us`.
+    pub fn backlog_bytes(&self, time_us: u64) -> u64 {
+        let waiting_ms = (self.busy_until - time_us as f64 / 1000.0).max(0.0);
+        (waiting_ms * self.config.rate as f64 / 1000.0).round() as u64
+    }
+
+    /// Name of the AQM for link events, `"full"` for a plain drop-tail queue.
+    pub fn aqm_name(&self) -> &'static str {
+        match self.config.aqm {
+            None => "full",
+            Some(AqmConfig::Red { .. }) => "red",
+            Some(AqmConfig::Codel { .. }) => "codel",
+        }
+    }
+
+    fn red(&mut self, queued: f64, min_ms: u64, max_ms: u64, max_p: f64) -> bool {
+        let (min, max) = (min_ms as f64, max_ms as f64);
+        let p = if queued < min {
+            0.0
+        } else if queued >= max {
+            1.0
+        } else {
+            max_p * (queued - min) / (max - min)
+        };
+        p > 0.0 && self.rng.random::<f64>() < p
+    }
+
+    fn codel(&mut self, now: f64, queued: f64, target_ms: u64, interval_ms: u64) -> bool {
+        let interval = interval_ms as f64;
+        let state = &mut self.codel;
+        if queued < target_ms as f64 {
+            *state = CodelState::default();
+            return false;
+        }
+        let Some(above_until) = state.above_until else {
+            state.above_until = Some(now + interval);
+            return false;
+        };
+        match state.drop_next {
+            None if now >= above_until => {
+                state.count = 1;
+                state.drop_next = Some(now + interval);
+                true
+            }
+            Some(next) if now >= next => {
+                state.count += 1;
+                state.drop_next = Some(next + interval / f64::from(state.count).sqrt());
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(aqm: Option<AqmConfig>) -> Bottleneck {
+        // 1000 B/s: a 100-byte packet takes 100 ms to send.
+        let config = BottleneckConfig {
+            rate: 1000,
+            buffer_ms: 500,
+            aqm,
+        };
+        Bottleneck::new(config, 7)
+    }
+
+    #[test]
+    fn queues_then_drops_tail_red_and_codel() {
+        let mut fifo = queue(None);
+        let verdicts: Vec<Verdict> = (0..7).map(|_| fifo.enqueue(0, 100, true)).collect();
+        assert_eq!(
+            verdicts[1],
+            Verdict::Forward {
+                queued_ms: 100,
+                delay_ms: 200.0,
+                marked: false
+            }
+        );
+        assert_eq!(verdicts[6], Verdict::TailDrop { queued_ms: 600 });
+
+        // Above max_ms RED signals every packet; with ECN data is marked, ACKs dropped.
+        let red = AqmConfig::Red {
+            min_ms: 50,
+            max_ms: 150,
+            max_p: 0.1,
+            ecn: true,
+        };
+        let mut red = queue(Some(red));
+        red.enqueue(0, 100, true);
+        red.enqueue(0, 100, true);
+        assert!(matches!(
+            red.enqueue(0, 100, true),
+            Verdict::Forward { marked: true, .. }
+        ));
+        assert_eq!(
+            red.enqueue(0, 0, false),
+            Verdict::EarlyDrop { queued_ms: 300 }
+        );
+
+        // CoDel drops once the delay has stayed above target for an interval.
+        let codel = AqmConfig::Codel {
+            target_ms: 50,
+            interval_ms: 200,
+            ecn: false,
+        };
+        let mut codel = queue(Some(codel));
+        let dropped: Vec<u64> = (0..10)
+            .map(|i| i * 50_000)
+            .filter(|&t| matches!(codel.enqueue(t, 100, true), Verdict::EarlyDrop { .. }))
+            .collect();
+        assert_eq!(dropped, [250_000, 450_000]);
+    }
+}
@@ -0,0 +1,293 @@
+//! `SimulatorBuilder` collects what a run needs besides the two protocols (restart
+//! factories, checkers, a middlebox, init params and the time limit) and checks that the
+//! `SimConfig` makes sense before any protocol is called, so a typo in a scenario fails
+//! with a `ConfigError` instead of a run that silently sends nothing.
+
+use tcp_lab_abstract::{
+    AqmConfig, InitParams, NodeId, ProtocolFactory, SimConfig, TransportProtocol,
+};
+
+use crate::checker::Checker;
+use crate::engine::Simulator;
+use crate::error::ConfigError;
+use crate::middlebox::Middlebox;
+use crate::scratch::ScratchStore;
+
+/// Builds a `Simulator` from `Simulator::builder(config, sender, receiver)`.
+///
+/// ```ignore
+/// let sim = Simulator::builder(config, sender, receiver)
+///     .factory(NodeId::Receiver, factory)
+///     .checker(checker)
+///     .end_time(10_000)
+///     .build()?;
+/// ```
+pub struct SimulatorBuilder {
+    config: SimConfig,
+    sender: Box<dyn TransportProtocol>,
+    receiver: Box<dyn TransportProtocol>,
+    factories: Vec<(NodeId, ProtocolFactory)>,
+    checkers: Vec<Checker>,
+    middlebox: Option<Box<dyn Middlebox>>,
+    init_params: InitParams,
+    end_time: Option<u64>,
+    strict_integrity: bool,
+    debug_events: bool,
+    scratch_store: Option<ScratchStore>,
+}
+
+impl SimulatorBuilder {
+    pub fn new(
+        config: SimConfig,
+        sender: Box<dyn TransportProtocol>,
+        receiver: Box<dyn TransportProtocol>,
+    ) -> Self {
+        Self {
+            config,
+            sender,
+            receiver,
+            factories: Vec::new(),
+            checkers: Vec::new(),
+            middlebox: None,
+            init_params: InitParams::default(),
+            end_time: None,
+            strict_integrity: false,
+            debug_events: false,
+            scratch_store: None,
+        }
+    }
+
+    /// How to re-create `node`'s protocol for scheduled restarts.
+    pub fn factory(mut self, node: NodeId, factory: ProtocolFactory) -> Self {
+        self.factories.push((node, factory));
+        self
+    }
+
+    pub fn factories(
+        mut self,
+        factories: impl IntoIterator<Item = (NodeId, ProtocolFactory)>,
+    ) -> Self {
+        self.factories.extend(factories);
+        self
+    }
+
+    /// Show `checker` every packet and link event of the run.
+    pub fn checker(mut self, checker: Checker) -> Self {
+        self.checkers.push(checker);
+        self
+    }
+
+    pub fn checkers(mut self, checkers: impl IntoIterator<Item = Checker>) -> Self {
+        self.checkers.extend(checkers);
+        self
+    }
+
+    /// Rewrite headers in transit with `middlebox` instead of the one `SimConfig::middlebox`
+    /// describes.
+    pub fn middlebox(mut self, middlebox: Box<dyn Middlebox>) -> Self {
+        self.middlebox = Some(middlebox);
+        self
+    }
+
+    /// Values returned by `SystemContext::init_params` for each node.
+    pub fn init_params(mut self, params: InitParams) -> Self {
+        self.init_params = params;
+        self
+    }
+
+    /// Treat the simulation as over after `time` ms: later sends are rejected.
+    pub fn end_time(mut self, time: u64) -> Self {
+        self.end_time = Some(time);
+        self
+    }
+
+    /// See `Simulator::set_strict_integrity`.
+    pub fn strict_integrity(mut self, strict: bool) -> Self {
+        self.strict_integrity = strict;
+        self
+    }
+
+    /// See `Simulator::set_debug_events`.
+    pub fn debug_events(mut self, on: bool) -> Self {
+        self.debug_events = on;
+        self
+    }
+
+    /// See `Simulator::set_scratch_store`.
+    pub fn scratch_store(mut self, store: ScratchStore) -> Self {
+        self.scratch_store = Some(store);
+        self
+    }
+
+    /// The simulator, or the first inconsistency `validate` finds in the config.
+    pub fn build(self) -> Result<Simulator, ConfigError> {
+        validate(&self.config)?;
+        let mut sim = Simulator::new(self.config, self.sender, self.receiver);
+        for (node, factory) in self.factories {
+            sim.set_protocol_factory(node, factory);
+        }
+        for checker in self.checkers {
+            sim.add_checker(checker);
+        }
+        if let Some(middlebox) = self.middlebox {
+            sim.set_middlebox(middlebox);
+        }
+        sim.set_init_params(self.init_params);
+        if let Some(time) = self.end_time {
+            sim.set_end_time(time);
+        }
+        sim.set_strict_integrity(self.strict_integrity);
+        sim.set_debug_events(self.debug_events);
+        if let Some(store) = self.scratch_store {
+            sim.set_scratch_store(store);
+        }
+        Ok(sim)
+    }
+}
+
+/// Check `config` for values no run can use: rates outside 0..=1, an inverted latency
+/// range, or zero where something has to flow.
+pub fn validate(config: &SimConfig) -> Result<(), ConfigError> {
+    for (key, value) in [
+        ("loss_rate", config.loss_rate),
+        ("corrupt_rate", config.corrupt_rate),
+        ("bit_error_rate", config.bit_error_rate),
+        ("truncate_rate", config.truncate_rate),
+        ("tamper_duplicate_rate", config.tamper_duplicate_rate),
+    ] {
+        if !(0.0..=1.0).contains(&value) {
+            return Err(ConfigError::Probability { key, value });
+        }
+    }
+    if config.min_latency > config.max_latency {
+        return Err(ConfigError::LatencyRange {
+            min: config.min_latency,
+            max: config.max_latency,
+        });
+    }
+    if config.mtu == 0 && (config.policer.is_some() || config.bottleneck.is_some()) {
+        return Err(ConfigError::ZeroMtu);
+    }
+    let zero = [
+        ("window_size", config.window_size.map(u64::from)),
+        ("trace_sampling", config.trace_sampling),
+        ("app_read_rate", config.app_read_rate),
+        ("policer.rate", config.policer.map(|p| p.rate)),
+        ("policer.burst", config.policer.map(|p| p.burst)),
+        ("bottleneck.rate", config.bottleneck.map(|b| b.rate)),
+        (
+            "bottleneck.aqm.interval_ms",
+            match config.bottleneck.and_then(|b| b.aqm) {
+                Some(AqmConfig::Codel { interval_ms, .. }) => Some(interval_ms),
+                _ => None,
+            },
+        ),
+    ];
+    if let Some((key, _)) = zero.into_iter().find(|(_, value)| *value == Some(0)) {
+        return Err(ConfigError::Zero { key });
+    }
+    if let Some(AqmConfig::Red {
+        min_ms,
+        max_ms,
+        max_p,
+        ..
+    }) = config.bottleneck.and_then(|b| b.aqm)
+    {
+        if min_ms >= max_ms {
+            return Err(ConfigError::RedThresholds { min_ms, max_ms });
+        }
+        if !(0.0..=1.0).contains(&max_p) {
+            return Err(ConfigError::Probability {
+                key: "bottleneck.aqm.max_p",
+                value: max_p,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tcp_lab_abstract::{BottleneckConfig, Packet, PolicerConfig, SystemContext};
+
+    struct Idle;
+
+    impl TransportProtocol for Idle {
+        fn on_packet(&mut self, _ctx: &mut dyn SystemContext, _packet: Packet) {}
+
+        fn on_timer(&mut self, _ctx: &mut dyn SystemContext, _timer_id: u32) {}
+
+        fn on_app_data(&mut self, _ctx: &mut dyn SystemContext, _data: &[u8]) {}
+    }
+
+    fn build(config: SimConfig) -> Result<Simulator, ConfigError> {
+        SimulatorBuilder::new(config, Box::new(Idle), Box::new(Idle)).build()
+    }
+
+    #[test]
+    fn rejects_inconsistent_configs() {
+        assert!(build(SimConfig::default()).is_ok());
+
+        let bottleneck = BottleneckConfig {
+            rate: 10_000,
+            buffer_ms: 100,
+            aqm: None,
+        };
+        let cases = [
+            (
+                SimConfig {
+                    loss_rate: 1.5,
+                    ..SimConfig::default()
+                },
+                ConfigError::Probability {
+                    key: "loss_rate",
+                    value: 1.5,
+                },
+            ),
+            (
+                SimConfig {
+                    min_latency: 50,
+                    max_latency: 10,
+                    ..SimConfig::default()
+                },
+                ConfigError::LatencyRange { min: 50, max: 10 },
+            ),
+            (
+                SimConfig {
+                    mtu: 0,
+                    bottleneck: Some(bottleneck),
+                    ..SimConfig::default()
+                },
+                ConfigError::ZeroMtu,
+            ),
+            (
+                SimConfig {
+                    bottleneck: Some(BottleneckConfig {
+                        rate: 0,
+                        ..bottleneck
+                    }),
+                    ..SimConfig::default()
+                },
+                ConfigError::Zero {
+                    key: "bottleneck.rate",
+                },
+            ),
+            (
+                SimConfig {
+                    policer: Some(PolicerConfig {
+                        rate: 1000,
+                        burst: 0,
+                    }),
+                    ..SimConfig::default()
+                },
+                ConfigError::Zero {
+                    key: "policer.burst",
+                },
+            ),
+        ];
+        for (config, expected) in cases {
+            assert_eq!(build(config).err(), Some(expected));
+        }
+    }
+}
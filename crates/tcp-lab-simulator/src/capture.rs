@@ -0,0 +1,273 @@
+//! Real TCP transfers captured with Wireshark, imported from `tshark -T json` so the TUI can
+//! draw them next to a simulated run. A capture point sees each packet once, so arrivals
+//! are placed half a round trip after the send, with the round trip taken from the
+//! handshake.
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::engine::{
+    LinkEventKind, LinkEventSummary, LinkPacket, NodeId, PacketEvent, PacketEventKind,
+};
+
+/// A single-flow capture in the simulator's terms. The endpoint that sent more payload
+/// bytes is the sender.
+#[derive(Debug, Clone)]
+pub struct Capture {
+    /// `address:port` of the sending endpoint.
+    pub sender: String,
+    /// `address:port` of the receiving endpoint.
+    pub receiver: String,
+    /// One-way delay the arrivals are drawn with.
+    pub latency_ms: u64,
+    /// A send per packet, as the space-time diagram draws them.
+    pub link_events: Vec<LinkEventSummary>,
+    /// The send and the arrival of every packet, as the time-sequence graph reads them.
+    pub packets: Vec<PacketEvent>,
+}
+
+/// One TCP segment of the export.
+#[derive(Debug, Clone)]
+struct Segment {
+    time_ms: u64,
+    src: String,
+    dst: String,
+    seq: u32,
+    ack: u32,
+    flags: u8,
+    window: u16,
+    len: usize,
+}
+
+pub fn load_from_file(path: &Path) -> Result<Capture> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read capture {}", path.display()))?;
+    parse_tshark_json(&text).with_context(|| format!("Failed to import {}", path.display()))
+}
+
+/// Parse the output of `tshark -r FILE -T json`. Frames without a TCP layer are skipped;
+/// TCP frames of more than one connection are refused.
+pub fn parse_tshark_json(text: &str) -> Result<Capture> {
+    let frames: Vec<Value> =
+        serde_json::from_str(text).context("not a JSON array as written by `tshark -T json`")?;
+    let mut segments = Vec::new();
+    for (index, frame) in frames.iter().enumerate() {
+        let layers = frame
+            .pointer("/_source/layers")
+            .ok_or_else(|| anyhow!("frame {} has no `_source.layers`", index + 1))?;
+        if layers.get("tcp").is_none() {
+            continue;
+        }
+        segments.push(segment(layers).with_context(|| format!("frame {}", index + 1))?);
+    }
+    let Some(first) = segments.first() else {
+        bail!("the capture has no TCP segments");
+    };
+    let start = first.time_ms;
+
+    let mut payload: BTreeMap<&str, usize> = BTreeMap::new();
+    for segment in &segments {
+        *payload.entry(&segment.src).or_default() += segment.len;
+        payload.entry(&segment.dst).or_default();
+    }
+    if payload.len() != 2 {
+        bail!(
+            "the capture holds {} TCP endpoints, not one connection; export a single flow, \
+             e.g. with `tshark -r FILE -Y tcp.stream==0 -T json`",
+            payload.len()
+        );
+    }
+    let (sender, _) = payload
+        .iter()
+        .max_by_key(|(_, bytes)| **bytes)
+        .expect("two endpoints");
+    let sender = sender.to_string();
+    let receiver = segments
+        .iter()
+        .flat_map(|segment| [&segment.src, &segment.dst])
+        .find(|endpoint| **endpoint != sender)
+        .expect("two endpoints")
+        .clone();
+
+    let latency_ms = (handshake_rtt(&segments) / 2).max(1);
+    let mut link_events = Vec::new();
+    let mut packets = Vec::new();
+    for segment in &segments {
+        let time = segment.time_ms.saturating_sub(start);
+        let (from, to) = if segment.src == sender {
+            (NodeId::Sender, NodeId::Receiver)
+        } else {
+            (NodeId::Receiver, NodeId::Sender)
+        };
+        link_events.push(LinkEventSummary {
+            time,
+            kind: LinkEventKind::Send,
+            description: format!(
+                "[{:?}->{:?}] SEND seq={} ack={} (latency={}ms)",
+                from, to, segment.seq, segment.ack, latency_ms
+            ),
+            packet: Some(LinkPacket {
+                from,
+                seq: segment.seq,
+                ack: segment.ack,
+                latency: Some(latency_ms),
+            }),
+        });
+        let event = |time, kind, node| PacketEvent {
+            time,
+            kind,
+            node,
+            seq: segment.seq,
+            ack: segment.ack,
+            flags: segment.flags,
+            window: segment.window,
+            len: segment.len,
+            ambiguous: false,
+            cause: None,
+            arrival: None,
+            note: None,
+        };
+        packets.push(event(time, PacketEventKind::Sent, from));
+        packets.push(event(time + latency_ms, PacketEventKind::Arrived, to));
+    }
+    // Arrivals follow later sends on a long path; the graphs expect time order.
+    packets.sort_by_key(|packet| packet.time);
+
+    Ok(Capture {
+        sender,
+        receiver,
+        latency_ms,
+        link_events,
+        packets,
+    })
+}
+
+fn segment(layers: &Value) -> Result<Segment> {
+    let time: f64 = field(layers, "frame", "frame.time_relative")?
+        .parse()
+        .context("frame.time_relative is not a number")?;
+    let ip = if layers.get("ip").is_some() {
+        "ip"
+    } else {
+        "ipv6"
+    };
+    let tcp_number = |name: &str| -> Result<u64> {
+        let value = field(layers, "tcp", name)?;
+        match value.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16),
+            None => value.parse(),
+        }
+        .with_context(|| format!("{name} is not a number: {value:?}"))
+    };
+    let endpoint = |end: &str| -> Result<String> {
+        Ok(format!(
+            "{}:{}",
+            field(layers, ip, &format!("{ip}.{end}"))?,
+            field(layers, "tcp", &format!("tcp.{end}port"))?
+        ))
+    };
+    Ok(Segment {
+        time_ms: (time * 1000.0).round() as u64,
+        src: endpoint("src")?,
+        dst: endpoint("dst")?,
+        // Wireshark's relative numbers, so the transfer starts at 0 like a simulated one.
+        seq: tcp_number("tcp.seq")? as u32,
+        ack: tcp_number("tcp.ack")? as u32,
+        flags: tcp_number("tcp.flags")? as u8,
+        window: tcp_number("tcp.window_size_value")? as u16,
+        len: tcp_number("tcp.len")? as usize,
+    })
+}
+
+/// `layers[layer][name]` as a string, the way tshark writes every value.
+fn field<'a>(layers: &'a Value, layer: &str, name: &str) -> Result<&'a str> {
+    layers
+        .get(layer)
+        .and_then(|layer| layer.get(name))
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("missing {name}"))
+}
+
+/// From the SYN to the first segment its sender sends after the SYN-ACK, which is one
+/// round trip wherever the capture was taken; 0 without a complete handshake.
+fn handshake_rtt(segments: &[Segment]) -> u64 {
+    use tcp_lab_abstract::flags::{ACK, SYN};
+    let Some(syn) = segments.iter().position(|s| s.flags & (SYN | ACK) == SYN) else {
+        return 0;
+    };
+    let client = &segments[syn].src;
+    let Some(syn_ack) = segments[syn..]
+        .iter()
+        .position(|s| s.flags & (SYN | ACK) == SYN | ACK && s.dst == *client)
+    else {
+        return 0;
+    };
+    segments[syn + syn_ack..]
+        .iter()
+        .find(|s| s.src == *client)
+        .map_or(0, |third| third.time_ms - segments[syn].time_ms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(time: &str, from_client: bool, seq: u32, ack: u32, flags: &str, len: u32) -> String {
+        let (src, dst, sport, dport) = if from_client {
+            ("10.0.0.1", "10.0.0.2", "50000", "80")
+        } else {
+            ("10.0.0.2", "10.0.0.1", "80", "50000")
+        };
+        format!(
+            r#"{{"_index": "packets", "_source": {{"layers": {{
+                "frame": {{"frame.time_relative": "{time}"}},
+                "ip": {{"ip.src": "{src}", "ip.dst": "{dst}"}},
+                "tcp": {{"tcp.srcport": "{sport}", "tcp.dstport": "{dport}",
+                         "tcp.seq": "{seq}", "tcp.ack": "{ack}", "tcp.flags": "{flags}",
+                         "tcp.window_size_value": "502", "tcp.len": "{len}"}}}}}}}}"#
+        )
+    }
+
+    #[test]
+    fn imports_a_transfer_from_the_host_that_sends_the_data() {
+        let arp =
+            r#"{"_source": {"layers": {"frame": {"frame.time_relative": "0.0"}, "arp": {}}}}"#;
+        let frames = [
+            arp.to_string(),
+            // Captured at the server, so the handshake's round trip is between its
+            // SYN-ACK and the client's ACK.
+            frame("1.000", true, 0, 0, "0x0002", 0),
+            frame("1.0004", false, 0, 1, "0x0012", 0),
+            frame("1.040", true, 1, 1, "0x0010", 0),
+            frame("1.041", true, 1, 1, "0x0018", 1000),
+            frame("1.0412", false, 1, 1001, "0x0010", 0),
+        ];
+        let capture = parse_tshark_json(&format!("[{}]", frames.join(","))).unwrap();
+
+        assert_eq!(capture.sender, "10.0.0.1:50000");
+        assert_eq!(capture.receiver, "10.0.0.2:80");
+        assert_eq!(capture.latency_ms, 20);
+        assert_eq!(capture.link_events.len(), 5);
+        assert_eq!(capture.link_events[0].time, 0);
+        assert_eq!(
+            capture.link_events[3].description,
+            "[Sender->Receiver] SEND seq=1 ack=1 (latency=20ms)"
+        );
+        let data = &capture.packets.iter().find(|p| p.len == 1000).unwrap();
+        assert_eq!(
+            (data.time, data.node, data.flags),
+            (41, NodeId::Sender, 0x18)
+        );
+        assert!(capture.packets.is_sorted_by_key(|p| p.time));
+    }
+
+    #[test]
+    fn refuses_several_connections() {
+        let other = frame("0.5", true, 0, 0, "0x0002", 0).replace("50000", "50001");
+        let frames = [frame("0.1", true, 0, 0, "0x0002", 0), other];
+        let err = parse_tshark_json(&format!("[{}]", frames.join(","))).unwrap_err();
+        assert!(format!("{err:#}").contains("tcp.stream==0"));
+    }
+}
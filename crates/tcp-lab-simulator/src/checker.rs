@@ -0,0 +1,88 @@
+//! Rust-side checks for what the declarative assertions cannot express. A grader written in
+//! Rust registers checkers through `RunOptions::checkers` (or `Simulator::add_checker`);
+//! each sees every packet and link event of the run as it happens and can fail the run on
+//! the spot with its own message.
+
+use serde::Serialize;
+
+use crate::engine::{LinkEventSummary, PacketEvent};
+
+/// An event a checker observes.
+#[derive(Debug, Clone, Copy)]
+pub enum RunEvent<'a> {
+    /// A step in a packet's life, shown at the end of the step that logged it so that
+    /// `ambiguous` is already set.
+    Packet(&'a PacketEvent),
+    /// A link event, also one that `trace_sampling` or `link_event_limit` keeps out of
+    /// the report.
+    Link(&'a LinkEventSummary),
+}
+
+impl RunEvent<'_> {
+    pub fn time(&self) -> u64 {
+        match self {
+            RunEvent::Packet(event) => event.time,
+            RunEvent::Link(event) => event.time,
+        }
+    }
+}
+
+/// What a checker runs on each event.
+type Check = Box<dyn FnMut(RunEvent<'_>) -> Result<(), String>>;
+
+/// A named closure called with every event of a run. Returning `Err(message)` stops the
+/// run at that event and fails it with `message`.
+///
+/// ```ignore
+/// let checker = Checker::new("no_zero_window", |event| match event {
+///     RunEvent::Packet(p) if p.node == NodeId::Receiver && p.kind == PacketEventKind::Sent
+///         && p.window == 0 => Err(format!("the receiver closed its window at {} ms", p.time)),
+///     _ => Ok(()),
+/// });
+/// ```
+pub struct Checker {
+    pub name: String,
+    check: Check,
+}
+
+impl Checker {
+    pub fn new(
+        name: impl Into<String>,
+        check: impl FnMut(RunEvent<'_>) -> Result<(), String> + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            check: Box::new(check),
+        }
+    }
+}
+
+/// The verdict of the checker that stopped a run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CheckFailure {
+    pub time: u64,
+    pub checker: String,
+    pub message: String,
+}
+
+/// Show `event` to each of `checkers` in turn until one fails, unless an earlier event
+/// already failed the run.
+pub(crate) fn observe(
+    checkers: &mut [Checker],
+    failure: &mut Option<CheckFailure>,
+    event: RunEvent<'_>,
+) {
+    if failure.is_some() {
+        return;
+    }
+    for checker in checkers {
+        if let Err(message) = (checker.check)(event) {
+            *failure = Some(CheckFailure {
+                time: event.time(),
+                checker: checker.name.clone(),
+                message,
+            });
+            return;
+        }
+    }
+}
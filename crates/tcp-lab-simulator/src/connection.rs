@@ -0,0 +1,196 @@
+//! Connection-state tracking for the handshake and teardown labs, inferred from the flags of
+//! the packets each node sends and receives. Sequence numbers are only compared with the
+//! FIN's own, so packet- and byte-numbered labs are graded alike.
+
+use serde::Serialize;
+use tcp_lab_abstract::{MssConfig, NodeId, TcpHeader};
+
+/// Where the three-way handshake stands, from the receiver's (server's) point of view.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionState {
+    #[default]
+    Closed,
+    /// The sender has sent a SYN.
+    SynSent,
+    /// The receiver has answered with a SYN-ACK.
+    SynReceived,
+    /// The receiver got the sender's final ACK.
+    Established,
+}
+
+/// One side's FIN.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct FinTrace {
+    /// When this side first sent a FIN.
+    pub sent_at: Option<u64>,
+    /// Sequence number of that FIN.
+    pub seq: u32,
+    /// When a FIN from this side first reached the peer.
+    pub received_at: Option<u64>,
+    /// When an ACK covering the FIN (`ack >= seq`) first came back after it had arrived.
+    pub acked_at: Option<u64>,
+}
+
+/// A data segment larger than the MSS in effect for the node that sent it.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct MssViolation {
+    pub node: NodeId,
+    pub time: u64,
+    pub len: usize,
+    pub mss: u16,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConnectionTrace {
+    pub state: ConnectionState,
+    /// When the sender sent its first SYN.
+    pub syn_sent_at: Option<u64>,
+    /// SYNs sent by the sender, retransmissions included.
+    pub syn_sends: u32,
+    /// When a SYN-ACK first reached the sender.
+    pub syn_ack_received_at: Option<u64>,
+    /// When the receiver got the ACK completing the handshake.
+    pub established_at: Option<u64>,
+    /// First sender data packet sent before a SYN-ACK had reached the sender.
+    pub early_data_at: Option<u64>,
+    pub sender_fin: FinTrace,
+    pub receiver_fin: FinTrace,
+    /// First packet carrying new data (seq at or past the node's own FIN) after that FIN.
+    pub data_after_fin: Option<(NodeId, u64)>,
+    /// The MSS options the two sides' SYNs carry (`SimConfig::mss`).
+    pub mss: Option<MssConfig>,
+    /// Peer MSS the sender and the receiver have learned from a SYN reaching them.
+    pub sender_peer_mss: Option<u16>,
+    pub receiver_peer_mss: Option<u16>,
+    /// First data segment larger than the MSS in effect for its sender.
+    pub mss_violation: Option<MssViolation>,
+}
+
+impl ConnectionTrace {
+    pub fn new(mss: Option<MssConfig>) -> Self {
+        Self {
+            mss,
+            ..Self::default()
+        }
+    }
+
+    /// Note a packet `node` hands to the channel (lost or not).
+    pub fn on_send(&mut self, node: NodeId, header: &TcpHeader, payload_len: usize, time: u64) {
+        let has_payload = payload_len > 0;
+        if let Some(mss) = self.mss_limit(node)
+            && payload_len > usize::from(mss)
+        {
+            self.mss_violation.get_or_insert(MssViolation {
+                node,
+                time,
+                len: payload_len,
+                mss,
+            });
+        }
+        let fin = self.fin_mut(node);
+        if header.is_fin() && fin.sent_at.is_none() {
+            fin.sent_at = Some(time);
+            fin.seq = header.seq_num;
+        } else if has_payload
+            && !header.is_fin()
+            && fin.sent_at.is_some()
+            && header.seq_num >= fin.seq
+        {
+            self.data_after_fin.get_or_insert((node, time));
+        }
+        match node {
+            NodeId::Sender => {
+                if header.is_syn() && !header.is_ack() {
+                    self.syn_sends += 1;
+                    self.syn_sent_at.get_or_insert(time);
+                    if self.state == ConnectionState::Closed {
+                        self.state = ConnectionState::SynSent;
+                    }
+                }
+                if has_payload && self.syn_ack_received_at.is_none() {
+                    self.early_data_at.get_or_insert(time);
+                }
+            }
+            NodeId::Receiver => {
+                if header.is_syn() && header.is_ack() && self.state == ConnectionState::SynSent {
+                    self.state = ConnectionState::SynReceived;
+                }
+            }
+        }
+    }
+
+    /// Note a packet arriving at `node`.
+    pub fn on_arrival(&mut self, node: NodeId, header: &TcpHeader, time: u64) {
+        if header.is_syn()
+            && let Some(mss) = self.mss
+        {
+            *self.peer_mss_mut(node) = Some(mss.of(node.peer()));
+        }
+        if header.is_fin() {
+            let peer_fin = self.fin_mut(node.peer());
+            if peer_fin.sent_at.is_some() {
+                peer_fin.received_at.get_or_insert(time);
+            }
+        }
+        let own_fin = self.fin_mut(node);
+        if header.is_ack() && own_fin.received_at.is_some() && header.ack_num >= own_fin.seq {
+            own_fin.acked_at.get_or_insert(time);
+        }
+        match node {
+            NodeId::Sender => {
+                if header.is_syn() && header.is_ack() {
+                    self.syn_ack_received_at.get_or_insert(time);
+                }
+            }
+            NodeId::Receiver => {
+                if header.is_ack() && !header.is_syn() && self.state == ConnectionState::SynReceived
+                {
+                    self.state = ConnectionState::Established;
+                    self.established_at = Some(time);
+                }
+            }
+        }
+    }
+
+    pub fn fin(&self, node: NodeId) -> &FinTrace {
+        match node {
+            NodeId::Sender => &self.sender_fin,
+            NodeId::Receiver => &self.receiver_fin,
+        }
+    }
+
+    fn fin_mut(&mut self, node: NodeId) -> &mut FinTrace {
+        match node {
+            NodeId::Sender => &mut self.sender_fin,
+            NodeId::Receiver => &mut self.receiver_fin,
+        }
+    }
+
+    /// The MSS `node` learned from its peer's SYN, if one has reached it.
+    pub fn peer_mss(&self, node: NodeId) -> Option<u16> {
+        match node {
+            NodeId::Sender => self.sender_peer_mss,
+            NodeId::Receiver => self.receiver_peer_mss,
+        }
+    }
+
+    fn peer_mss_mut(&mut self, node: NodeId) -> &mut Option<u16> {
+        match node {
+            NodeId::Sender => &mut self.sender_peer_mss,
+            NodeId::Receiver => &mut self.receiver_peer_mss,
+        }
+    }
+
+    /// Largest data segment `node` may send: its own MSS, or the peer's if it has learned
+    /// a smaller one. `None` without `SimConfig::mss`.
+    pub fn mss_limit(&self, node: NodeId) -> Option<u16> {
+        let own = self.mss?.of(node);
+        Some(self.peer_mss(node).map_or(own, |peer| peer.min(own)))
+    }
+
+    /// SYNs sent after the first one.
+    pub fn syn_retransmissions(&self) -> u32 {
+        self.syn_sends.saturating_sub(1)
+    }
+}
@@ -0,0 +1,245 @@
+//! Failure classes of the scenario API, so hosts and autograders can tell a broken scenario
+//! from a failing submission without matching on message text. Each displays as the
+//! grader's message.
+
+use tcp_lab_abstract::{NodeId, TestAssertion};
+
+use crate::engine::PacketEvent;
+use crate::explain::Clue;
+use crate::i18n::Msg;
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum ScenarioError {
+    /// The scenario file could not be read or is not a valid scenario.
+    #[error("{details}")]
+    Parse { details: String },
+    /// `scenario.assertions[which]` did not hold. `explanation` lists what the trace shows
+    /// about the failure, one line each after `details`.
+    #[error("{details}{}", why(explanation))]
+    AssertionFailed {
+        which: usize,
+        assertion: Box<TestAssertion>,
+        details: String,
+        explanation: Vec<Clue>,
+    },
+    /// A protocol panicked, so the run was abandoned.
+    #[error("{details}")]
+    StudentRuntime { details: String },
+    /// Events were still pending after `limit_ms` (the `max_duration` assertion, or 10 s).
+    #[error("{details}{}", why(explanation))]
+    Timeout {
+        limit_ms: u64,
+        details: String,
+        explanation: Vec<Clue>,
+    },
+    /// The run ran out of events with application data still undelivered, so nothing could
+    /// have delivered it; the last packet moved at `time`. `waiting_node` is the one left waiting: the sender
+    /// if some of its data never reached the receiver, else the receiver holding it.
+    /// `last_event` is the last entry of the packet log, if any. Replaces the delivery
+    /// assertions that failed because of it.
+    #[error("{details}{}", why(explanation))]
+    Deadlock {
+        waiting_node: NodeId,
+        last_event: Option<Box<PacketEvent>>,
+        time: u64,
+        details: String,
+        explanation: Vec<Clue>,
+    },
+    /// The applications closed the connection at `time` (a `shutdown` action) after writing
+    /// `written` bytes, but only `delivered` of them reached the receiver's application.
+    #[error("{details}")]
+    NotFlushed {
+        time: u64,
+        written: u64,
+        delivered: u64,
+        details: String,
+    },
+    /// In strict mode, `node` delivered data that never arrived at it.
+    #[error("{details}")]
+    Integrity {
+        time: u64,
+        node: NodeId,
+        details: String,
+    },
+    /// The Rust-side `checker` (see `RunOptions::checkers`) failed the run at `time`,
+    /// which ended it there.
+    #[error("{details}")]
+    CheckFailed {
+        time: u64,
+        checker: String,
+        details: String,
+    },
+    /// `node`'s protocol saw simulated time go backwards at `time`, so the run cannot be
+    /// trusted. This is a bug in the engine, a language bridge or the host driving it.
+    #[error("{details}")]
+    ClockAnomaly {
+        time: u64,
+        node: NodeId,
+        details: String,
+    },
+    /// The host did not provide what the scenario needs, e.g. a factory for a node that
+    /// `restart_node` restarts.
+    #[error("{details}")]
+    Environment { details: String },
+}
+
+impl ScenarioError {
+    /// Whether the submission is at fault, as opposed to the scenario or the host.
+    pub fn is_student_failure(&self) -> bool {
+        matches!(
+            self,
+            ScenarioError::AssertionFailed { .. }
+                | ScenarioError::StudentRuntime { .. }
+                | ScenarioError::Timeout { .. }
+                | ScenarioError::Deadlock { .. }
+                | ScenarioError::NotFlushed { .. }
+                | ScenarioError::Integrity { .. }
+                | ScenarioError::CheckFailed { .. }
+        )
+    }
+
+    /// Short name of the failure class, e.g. `timeout` or `not_flushed`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ScenarioError::Parse { .. } => "parse",
+            ScenarioError::AssertionFailed { .. } => "assertion_failed",
+            ScenarioError::StudentRuntime { .. } => "panic",
+            ScenarioError::Timeout { .. } => "timeout",
+            ScenarioError::Deadlock { .. } => "deadlock",
+            ScenarioError::NotFlushed { .. } => "not_flushed",
+            ScenarioError::Integrity { .. } => "integrity",
+            ScenarioError::CheckFailed { .. } => "check_failed",
+            ScenarioError::ClockAnomaly { .. } => "clock_anomaly",
+            ScenarioError::Environment { .. } => "environment",
+        }
+    }
+
+    /// The process exit code a grading run that failed with this error ends with.
+    pub fn exit_status(&self) -> ExitStatus {
+        match self {
+            ScenarioError::AssertionFailed { .. }
+            | ScenarioError::Deadlock { .. }
+            | ScenarioError::NotFlushed { .. }
+            | ScenarioError::Integrity { .. }
+            | ScenarioError::CheckFailed { .. } => ExitStatus::AssertionFailed,
+            ScenarioError::StudentRuntime { .. } => ExitStatus::StudentRuntime,
+            ScenarioError::Timeout { .. } => ExitStatus::Timeout,
+            ScenarioError::Parse { .. }
+            | ScenarioError::ClockAnomaly { .. }
+            | ScenarioError::Environment { .. } => ExitStatus::Environment,
+        }
+    }
+}
+
+/// A `SimConfig` that `SimulatorBuilder::build` refuses, because no run could use it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    /// `key` must be a probability.
+    Probability { key: &'static str, value: f64 },
+    /// `min_latency` is above `max_latency`.
+    LatencyRange { min: u64, max: u64 },
+    /// `mtu` is 0 on a link with a policer or bottleneck, so no data could ever pass.
+    ZeroMtu,
+    /// `key` is set to 0, which would stop everything it limits.
+    Zero { key: &'static str },
+    /// RED's `min_ms` is not below its `max_ms`.
+    RedThresholds { min_ms: u64, max_ms: u64 },
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&match self {
+            ConfigError::Probability { key, value } => Msg::ConfigProbability.fill(&[key, value]),
+            ConfigError::LatencyRange { min, max } => Msg::ConfigLatencyRange.fill(&[min, max]),
+            ConfigError::ZeroMtu => Msg::ConfigZeroMtu.text().to_string(),
+            ConfigError::Zero { key } => Msg::ConfigZero.fill(&[key]),
+            ConfigError::RedThresholds { min_ms, max_ms } => {
+                Msg::ConfigRedThresholds.fill(&[min_ms, max_ms])
+            }
+        })
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Why `Simulator::push_external_event` refused an event.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ExternalEventError {
+    #[error("event at {time} ms is before the current time ({now} ms)")]
+    InThePast { time: u64, now: u64 },
+    #[error("cannot restart the {node:?}: it has no protocol factory")]
+    NoFactory { node: NodeId },
+}
+
+/// Process exit codes of sim-cli and eval-host, so CI scripts can tell how a grading run
+/// ended without parsing its output. 1 is never used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    Pass = 0,
+    /// An assertion or a Rust-side checker did not hold, or the run deadlocked, left data
+    /// unsent at a shutdown or delivered data that never arrived.
+    AssertionFailed = 2,
+    /// The submission panicked or raised, while running or while being loaded.
+    StudentRuntime = 3,
+    /// Not the submission's fault: bad arguments, an invalid scenario, a missing runtime or
+    /// library, or a bug in the engine.
+    Environment = 4,
+    Timeout = 5,
+}
+
+impl ExitStatus {
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        [
+            Self::Pass,
+            Self::AssertionFailed,
+            Self::StudentRuntime,
+            Self::Environment,
+            Self::Timeout,
+        ]
+        .into_iter()
+        .find(|status| status.code() == code)
+    }
+
+    /// How the run that returned `err` ended: the status of the `ScenarioError` or
+    /// `GradingFailed` in its chain, `Environment` for anything else.
+    pub fn of(err: &anyhow::Error) -> Self {
+        err.chain()
+            .find_map(|cause| {
+                cause
+                    .downcast_ref::<GradingFailed>()
+                    .map(|failed| failed.status)
+                    .or_else(|| {
+                        cause
+                            .downcast_ref::<ScenarioError>()
+                            .map(ScenarioError::exit_status)
+                    })
+            })
+            .unwrap_or(ExitStatus::Environment)
+    }
+}
+
+impl From<ExitStatus> for std::process::ExitCode {
+    fn from(status: ExitStatus) -> Self {
+        Self::from(status.code())
+    }
+}
+
+/// A failed grading run whose message was already rendered, e.g. with hints appended or
+/// summarizing a suite, that keeps the exit status of the failure behind it.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{message}")]
+pub struct GradingFailed {
+    pub status: ExitStatus,
+    pub message: String,
+}
+
+fn why(explanation: &[Clue]) -> String {
+    explanation
+        .iter()
+        .map(|clue| format!("\n  {}", Msg::Why.fill(&[clue])))
+        .collect()
+}
@@ -0,0 +1,167 @@
+//! Student-facing explanations of a failed run, built from the packet and timer logs:
+//! "seq=2 was dropped at t=3210ms; your sender never retransmitted it; timer 2 was
+//! cancelled at t=3300ms" instead of only the assertion that did not hold.
+
+use std::fmt::{self, Display};
+
+use tcp_lab_abstract::{NodeId, TestAssertion};
+
+use crate::engine::{PacketEventKind, Simulator, TimerRecord};
+use crate::i18n::Msg;
+use crate::stream::StreamReport;
+
+/// Lost segments listed at most; the first ones are usually the cause of the rest.
+const MAX_LOSSES: usize = 3;
+
+/// One fact from the trace that helps explain a failure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Clue {
+    /// A data segment the sender sent was lost and never sent again.
+    Unrecovered {
+        seq: u32,
+        time: u64,
+        corrupted: bool,
+        timer: TimerFate,
+    },
+    /// The receiver delivered fewer application bytes than the sender was handed.
+    Undelivered { sent: u64, delivered: u64 },
+    /// The receiver's deliveries left gaps in the stream, repeated or reordered it.
+    Stream(StreamReport),
+}
+
+/// What became of the sender timer that should have recovered a loss: the one armed when
+/// the segment was lost, else the next one started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerFate {
+    NoTimer,
+    Cancelled { timer_id: u32, time: u64 },
+    Fired { timer_id: u32, time: u64 },
+    StillArmed { timer_id: u32 },
+}
+
+impl Display for Clue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Clue::Unrecovered {
+                seq,
+                time,
+                corrupted,
+                timer,
+            } => {
+                let (seq, time, timer) = (*seq, *time, *timer);
+                let lost = if *corrupted {
+                    Msg::ClueCorrupted
+                } else {
+                    Msg::ClueDropped
+                };
+                let timer = match timer {
+                    TimerFate::NoTimer => Msg::ClueNoTimer.text().to_string(),
+                    TimerFate::Cancelled { timer_id, time } => {
+                        Msg::ClueTimerCancelled.fill(&[&timer_id, &time])
+                    }
+                    TimerFate::Fired { timer_id, time } => {
+                        Msg::ClueTimerFired.fill(&[&timer_id, &time])
+                    }
+                    TimerFate::StillArmed { timer_id } => Msg::ClueTimerArmed.fill(&[&timer_id]),
+                };
+                write!(
+                    f,
+                    "{}; {}; {}",
+                    lost.fill(&[&seq, &time]),
+                    Msg::ClueNeverResent.text(),
+                    timer
+                )
+            }
+            Clue::Undelivered { sent, delivered } => {
+                f.write_str(&Msg::ClueUndelivered.fill(&[sent, delivered]))
+            }
+            Clue::Stream(report) => report.fmt(f),
+        }
+    }
+}
+
+/// Clues for a failed `assertion`, or for a timeout when `None`. Only failures that lost
+/// or stalled data get any; a wrong window or state sequence is not explained by losses.
+pub fn explain(sim: &Simulator, assertion: Option<&TestAssertion>) -> Vec<Clue> {
+    if !assertion.is_none_or(about_delivery) {
+        return Vec::new();
+    }
+
+    let mut clues: Vec<Clue> = unrecovered_losses(sim).take(MAX_LOSSES).collect();
+    let sent = sim.app_bytes_sent();
+    let delivered = sim.delivered_data.iter().map(|d| d.len() as u64).sum();
+    if delivered < sent {
+        clues.push(Clue::Undelivered { sent, delivered });
+    }
+    let stream = sim.stream_report();
+    if !stream.is_clean() {
+        clues.push(Clue::Stream(stream));
+    }
+    clues
+}
+
+/// Whether `assertion` fails when data is lost or stalls.
+pub(crate) fn about_delivery(assertion: &TestAssertion) -> bool {
+    matches!(
+        assertion,
+        TestAssertion::DataDelivered { .. }
+            | TestAssertion::MaxDuration { .. }
+            | TestAssertion::DeliveryLatency { .. }
+            | TestAssertion::RoundTripLatency { .. }
+            | TestAssertion::FramesOnTime { .. }
+            | TestAssertion::GracefulClose
+            | TestAssertion::DeliversAfterHalfClose { .. }
+    )
+}
+
+/// Sender data segments that were dropped or corrupted with no later copy of the same
+/// seq and length, in the order they were lost.
+fn unrecovered_losses(sim: &Simulator) -> impl Iterator<Item = Clue> + '_ {
+    let log = &sim.packet_log;
+    log.iter().enumerate().filter_map(move |(index, event)| {
+        let corrupted = match event.kind {
+            PacketEventKind::Dropped => false,
+            PacketEventKind::Corrupted => true,
+            _ => return None,
+        };
+        if event.node != NodeId::Sender || event.len == 0 {
+            return None;
+        }
+        let resent = log[index + 1..].iter().any(|later| {
+            later.kind == PacketEventKind::Sent
+                && later.node == NodeId::Sender
+                && later.seq == event.seq
+                && later.len == event.len
+        });
+        (!resent).then(|| Clue::Unrecovered {
+            seq: event.seq,
+            time: event.time,
+            corrupted,
+            timer: timer_fate(&sim.timer_log, event.time),
+        })
+    })
+}
+
+fn timer_fate(timers: &[TimerRecord], lost_at: u64) -> TimerFate {
+    let sender = || timers.iter().filter(|t| t.node == NodeId::Sender);
+    let Some(timer) = sender()
+        .filter(|t| t.armed_at(lost_at))
+        .max_by_key(|t| t.started)
+        .or_else(|| sender().find(|t| t.started > lost_at))
+    else {
+        return TimerFate::NoTimer;
+    };
+    match timer.ended {
+        None => TimerFate::StillArmed {
+            timer_id: timer.timer_id,
+        },
+        Some(time) if timer.fired => TimerFate::Fired {
+            timer_id: timer.timer_id,
+            time,
+        },
+        Some(time) => TimerFate::Cancelled {
+            timer_id: timer.timer_id,
+            time,
+        },
+    }
+}
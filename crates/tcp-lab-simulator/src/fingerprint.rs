@@ -0,0 +1,326 @@
+//! Behavioural fingerprints of graded runs, compared across submissions as an advisory
+//! signal for academic-integrity review. Two protocols written independently tend to
+//! differ in how they space their sends, which timeouts they pick, what makes them
+//! retransmit and how their metrics evolve, even when both pass; copies (renamed,
+//! reformatted or translated to another language) keep behaving alike. A high score is
+//! a reason to read both submissions, never a verdict: protocols that follow the lab's
+//! reference design closely score high too, so scores only stand out against the
+//! cohort's mean.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use tcp_lab_abstract::{NodeId, PacketEventKind};
+
+use crate::trace::SimulationReport;
+
+/// Log2 buckets of the gap and delay histograms; the last one takes everything longer.
+const BUCKETS: usize = 16;
+
+/// Sends kept in `Fingerprint::decisions`.
+const MAX_DECISIONS: usize = 4096;
+
+/// Points each metric trajectory is resampled to.
+const TRAJECTORY_POINTS: usize = 32;
+
+/// What one run of a submission did, reduced to what can be compared with another run
+/// of the same scenario and seed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    /// The run's seed; runs under different seeds are not compared.
+    pub seed: u64,
+    /// Share of the gaps between the sender's consecutive sends in each log2 bucket of
+    /// milliseconds (0, 1, 2-3, 4-7, ...).
+    pub send_gaps: Vec<f64>,
+    /// Share of the delays of the sender's timers in each log2 bucket.
+    pub timer_delays: Vec<f64>,
+    /// The sender's sends in order, one letter each for what caused it: `t` a timer,
+    /// `a` an arriving packet, `n` anything else (app data, `init`). Uppercase for a
+    /// retransmission, `c` for a segment without payload.
+    pub decisions: String,
+    /// Each metric the sender recorded, resampled over the run and scaled to 0..=1.
+    pub metrics: BTreeMap<String, Vec<f64>>,
+}
+
+impl Fingerprint {
+    pub fn of(report: &SimulationReport) -> Self {
+        let expiries: HashSet<u64> = report
+            .timer_log
+            .iter()
+            .filter(|timer| timer.node == NodeId::Sender && timer.fired)
+            .map(|timer| timer.expiry)
+            .collect();
+        let arrivals: HashSet<u64> = report
+            .packets
+            .iter()
+            .filter(|event| {
+                event.node == NodeId::Sender
+                    && matches!(
+                        event.kind,
+                        PacketEventKind::Arrived | PacketEventKind::Injected
+                    )
+            })
+            .filter_map(|event| event.cause)
+            .collect();
+
+        let mut sent = HashSet::new();
+        let mut decisions = String::new();
+        let mut send_times = Vec::new();
+        for event in report
+            .packets
+            .iter()
+            .filter(|event| event.node == NodeId::Sender && event.kind == PacketEventKind::Sent)
+        {
+            send_times.push(event.time);
+            if decisions.len() >= MAX_DECISIONS {
+                continue;
+            }
+            let letter = match event.cause {
+                Some(cause) if expiries.contains(&cause) => 't',
+                Some(cause) if arrivals.contains(&cause) => 'a',
+                _ => 'n',
+            };
+            decisions.push(if event.len == 0 {
+                'c'
+            } else if !sent.insert(event.seq) {
+                letter.to_ascii_uppercase()
+            } else {
+                letter
+            });
+        }
+
+        let gaps = send_times.windows(2).map(|pair| pair[1] - pair[0]);
+        let delays = report
+            .timer_log
+            .iter()
+            .filter(|timer| timer.node == NodeId::Sender)
+            .map(|timer| timer.expires - timer.started);
+        Self {
+            seed: report.config.seed,
+            send_gaps: histogram(gaps),
+            timer_delays: histogram(delays),
+            decisions,
+            metrics: trajectories(&report.metrics, report.duration_ms),
+        }
+    }
+
+    /// How alike two runs behaved, from 0 to 1; `None` if they ran under different seeds
+    /// or have nothing in common to compare.
+    pub fn similarity(&self, other: &Self) -> Option<f64> {
+        if self.seed != other.seed {
+            return None;
+        }
+        let shared: Vec<f64> = self
+            .metrics
+            .iter()
+            .filter_map(|(name, points)| {
+                let theirs = other.metrics.get(name)?;
+                let distance: f64 = points.iter().zip(theirs).map(|(a, b)| (a - b).abs()).sum();
+                Some(1.0 - distance / points.len().max(1) as f64)
+            })
+            .collect();
+        let parts = [
+            cosine(&self.send_gaps, &other.send_gaps),
+            cosine(&self.timer_delays, &other.timer_delays),
+            trigram_similarity(&self.decisions, &other.decisions),
+            mean(&shared),
+        ];
+        mean(&parts.into_iter().flatten().collect::<Vec<_>>())
+    }
+}
+
+/// One pair of submissions in a [`SimilarityReport`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PairSimilarity {
+    pub first: String,
+    pub second: String,
+    /// Mean of `scenarios`, from 0 to 1.
+    pub score: f64,
+    /// Similarity per scenario both ran under the same seed.
+    pub scenarios: BTreeMap<String, f64>,
+}
+
+/// Pairwise similarity of a cohort's submissions, most alike first.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SimilarityReport {
+    pub submissions: usize,
+    /// Mean score over every compared pair: what submissions of this lab share anyway.
+    pub cohort_mean: Option<f64>,
+    pub pairs: Vec<PairSimilarity>,
+}
+
+impl SimilarityReport {
+    /// Compare every pair of `submissions`, given as submission name to the fingerprint
+    /// of each scenario it ran. Pairs without a comparable scenario are left out.
+    pub fn new(submissions: &BTreeMap<String, BTreeMap<String, Fingerprint>>) -> Self {
+        let names: Vec<&String> = submissions.keys().collect();
+        let mut pairs = Vec::new();
+        for (i, first) in names.iter().enumerate() {
+            for second in &names[i + 1..] {
+                let theirs = &submissions[*second];
+                let scenarios: BTreeMap<String, f64> = submissions[*first]
+                    .iter()
+                    .filter_map(|(scenario, fingerprint)| {
+                        let score = fingerprint.similarity(theirs.get(scenario)?)?;
+                        Some((scenario.clone(), score))
+                    })
+                    .collect();
+                let Some(score) = mean(&scenarios.values().copied().collect::<Vec<_>>()) else {
+                    continue;
+                };
+                pairs.push(PairSimilarity {
+                    first: (*first).clone(),
+                    second: (*second).clone(),
+                    score,
+                    scenarios,
+                });
+            }
+        }
+        pairs.sort_by(|a, b| b.score.total_cmp(&a.score));
+        let scores: Vec<f64> = pairs.iter().map(|pair| pair.score).collect();
+        Self {
+            submissions: submissions.len(),
+            cohort_mean: mean(&scores),
+            pairs,
+        }
+    }
+}
+
+/// Share of `values` in each log2 bucket; empty without values.
+fn histogram(values: impl Iterator<Item = u64>) -> Vec<f64> {
+    let mut counts = vec![0u64; BUCKETS];
+    for value in values {
+        let bucket = (u64::BITS - value.leading_zeros()) as usize;
+        counts[bucket.min(BUCKETS - 1)] += 1;
+    }
+    let total: u64 = counts.iter().sum();
+    if total == 0 {
+        return Vec::new();
+    }
+    counts
+        .into_iter()
+        .map(|count| count as f64 / total as f64)
+        .collect()
+}
+
+/// Each metric's value at evenly spaced times over `duration_ms`, holding the latest
+/// sample, scaled so its smallest value is 0 and its largest 1.
+fn trajectories(
+    metrics: &HashMap<String, Vec<(u64, f64)>>,
+    duration_ms: u64,
+) -> BTreeMap<String, Vec<f64>> {
+    metrics
+        .iter()
+        .filter(|(_, samples)| !samples.is_empty())
+        .map(|(name, samples)| {
+            let mut next = 0;
+            let points: Vec<f64> = (0..TRAJECTORY_POINTS)
+                .map(|i| {
+                    let time = duration_ms * i as u64 / (TRAJECTORY_POINTS as u64 - 1);
+                    while next + 1 < samples.len() && samples[next + 1].0 <= time {
+                        next += 1;
+                    }
+                    samples[next].1
+                })
+                .collect();
+            let low = points.iter().copied().fold(f64::INFINITY, f64::min);
+            let high = points.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            let range = high - low;
+            let scaled = points
+                .into_iter()
+                .map(|value| {
+                    if range > 0.0 {
+                        (value - low) / range
+                    } else {
+                        0.0
+                    }
+                })
+                .collect();
+            (name.clone(), scaled)
+        })
+        .collect()
+}
+
+fn cosine(a: &[f64], b: &[f64]) -> Option<f64> {
+    let dot: f64 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+    let norms =
+        a.iter().map(|a| a * a).sum::<f64>().sqrt() * b.iter().map(|b| b * b).sum::<f64>().sqrt();
+    (norms > 0.0).then(|| dot / norms)
+}
+
+/// Cosine similarity of how often each run of three decisions occurs.
+fn trigram_similarity(a: &str, b: &str) -> Option<f64> {
+    let (a, b) = (trigram_counts(a), trigram_counts(b));
+    let dot: f64 = a
+        .iter()
+        .filter_map(|(trigram, count)| Some(count * b.get(trigram)?))
+        .sum();
+    let norm = |counts: &HashMap<&[u8], f64>| counts.values().map(|c| c * c).sum::<f64>().sqrt();
+    let norms = norm(&a) * norm(&b);
+    (norms > 0.0).then(|| dot / norms)
+}
+
+fn trigram_counts(decisions: &str) -> HashMap<&[u8], f64> {
+    let mut counts: HashMap<&[u8], f64> = HashMap::new();
+    for trigram in decisions.as_bytes().windows(3) {
+        *counts.entry(trigram).or_default() += 1.0;
+    }
+    counts
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fingerprint(seed: u64, decisions: &str, cwnd: &[f64]) -> Fingerprint {
+        Fingerprint {
+            seed,
+            send_gaps: histogram([0, 0, 5, 100].into_iter()),
+            timer_delays: histogram([1000, 1000].into_iter()),
+            decisions: decisions.to_string(),
+            metrics: BTreeMap::from([("cwnd".to_string(), cwnd.to_vec())]),
+        }
+    }
+
+    #[test]
+    fn test_similarity() {
+        let a = fingerprint(1, "nnnnaaaaTTaaaa", &[0.0, 0.5, 1.0]);
+        assert!((a.similarity(&a).unwrap() - 1.0).abs() < 1e-9);
+        let b = fingerprint(1, "nnnnaTaTaTaTaT", &[1.0, 0.5, 0.0]);
+        assert!(a.similarity(&b).unwrap() < 0.8);
+        assert_eq!(a.similarity(&fingerprint(2, "nnnnaaaaTTaaaa", &[])), None);
+    }
+
+    #[test]
+    fn test_report_ranks_pairs() {
+        let run = |decisions: &str, cwnd: &[f64]| {
+            BTreeMap::from([("test_gbn".to_string(), fingerprint(7, decisions, cwnd))])
+        };
+        let submissions = BTreeMap::from([
+            ("alice".to_string(), run("nnnnaaaaTTaaaa", &[0.0, 0.5, 1.0])),
+            ("bob".to_string(), run("nnnnaTaTaTaTaT", &[1.0, 0.0, 1.0])),
+            ("carol".to_string(), run("nnnnaaaaTTaaaa", &[0.0, 0.5, 1.0])),
+        ]);
+        let report = SimilarityReport::new(&submissions);
+        assert_eq!(report.pairs.len(), 3);
+        assert_eq!(
+            (
+                report.pairs[0].first.as_str(),
+                report.pairs[0].second.as_str()
+            ),
+            ("alice", "carol")
+        );
+        assert!(report.pairs[0].score > report.cohort_mean.unwrap());
+    }
+
+    #[test]
+    fn test_histogram_buckets() {
+        assert_eq!(histogram(std::iter::empty()), Vec::<f64>::new());
+        let shares = histogram([0, 1, 2, 3].into_iter());
+        assert_eq!(&shares[..3], &[0.25, 0.25, 0.5]);
+    }
+}
@@ -0,0 +1,153 @@
+//! GitHub Actions workflow commands for grading results, so that an autograding workflow
+//! shows failed assertions and student exceptions as annotations on the scenario file,
+//! see <https://docs.github.com/actions/reference/workflow-commands-for-github-actions>.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::error::{ExitStatus, ScenarioError};
+use crate::i18n::Msg;
+use crate::scenario_runner::ScenarioResult;
+
+/// The annotation title of a failure that ends the run with `status`.
+pub fn title(status: ExitStatus) -> &'static str {
+    match status {
+        ExitStatus::Pass | ExitStatus::AssertionFailed => Msg::AnnotateAssertion,
+        ExitStatus::StudentRuntime => Msg::AnnotateStudentRuntime,
+        ExitStatus::Environment => Msg::AnnotateEnvironment,
+        ExitStatus::Timeout => Msg::AnnotateTimeout,
+    }
+    .text()
+}
+
+/// An `::error` command, on `file` at `line` when they are known.
+pub fn error(file: Option<&Path>, line: Option<usize>, title: &str, message: &str) -> String {
+    command("error", file, line, Some(title), message)
+}
+
+/// The `::error` command for `failure` of the scenario in `file`. `source` is the file's
+/// text, which places a failed assertion on its `[[assertions]]` table.
+pub fn failure(file: Option<&Path>, source: Option<&str>, failure: &ScenarioError) -> String {
+    let line = match failure {
+        ScenarioError::AssertionFailed { which, .. } => {
+            source.and_then(|source| assertion_line(source, *which))
+        }
+        ScenarioError::Parse { details } => parse_error_line(details),
+        _ => None,
+    };
+    error(
+        file,
+        line,
+        title(failure.exit_status()),
+        &failure.to_string(),
+    )
+}
+
+/// The commands for a failed scenario `name`: an error per failure and a notice per hint
+/// the run matched, in a collapsible group. Empty if the scenario passed.
+pub fn scenario(
+    name: &str,
+    file: Option<&Path>,
+    source: Option<&str>,
+    result: &ScenarioResult,
+) -> String {
+    if result.passed() {
+        return String::new();
+    }
+    let mut out = format!("::group::{}\n", escape_data(name));
+    for err in &result.failures {
+        let _ = writeln!(out, "{}", failure(file, source, err));
+    }
+    for hint in result.report.iter().flat_map(|report| &report.hints) {
+        let notice = command("notice", file, None, None, &Msg::Hint.fill(&[&hint.text]));
+        let _ = writeln!(out, "{notice}");
+    }
+    out.push_str("::endgroup::");
+    out
+}
+
+fn command(
+    name: &str,
+    file: Option<&Path>,
+    line: Option<usize>,
+    title: Option<&str>,
+    message: &str,
+) -> String {
+    let mut properties = Vec::new();
+    if let Some(file) = file {
+        properties.push(format!(
+            "file={}",
+            escape_property(&file.display().to_string())
+        ));
+    }
+    if let Some(line) = line {
+        properties.push(format!("line={line}"));
+    }
+    if let Some(title) = title {
+        properties.push(format!("title={}", escape_property(title)));
+    }
+    let separator = if properties.is_empty() { "" } else { " " };
+    format!(
+        "::{name}{separator}{}::{}",
+        properties.join(","),
+        escape_data(message)
+    )
+}
+
+/// The 1-based line of the header of `scenario.assertions[which]`. `None` when the
+/// assertions are not written as `[[assertions]]` tables.
+fn assertion_line(source: &str, which: usize) -> Option<usize> {
+    source
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.trim_start().starts_with("[[assertions]]"))
+        .nth(which)
+        .map(|(index, _)| index + 1)
+}
+
+/// The line a TOML syntax error points at, from its `at line N` message.
+fn parse_error_line(details: &str) -> Option<usize> {
+    let (_, rest) = details.split_once("at line ")?;
+    let digits = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    rest[..digits].parse().ok()
+}
+
+fn escape_data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenario_runner::parse_scenario;
+    use tcp_lab_abstract::TestAssertion;
+
+    #[test]
+    fn annotates_failures_on_their_lines() {
+        let source = "[[assertions]]\ntype = \"max_duration\"\nms = 1\n\n[[assertions]]\ntype = \"respects_zero_window\"\n";
+        let failure = ScenarioError::AssertionFailed {
+            which: 1,
+            assertion: Box::new(TestAssertion::RespectsZeroWindow),
+            details: "50% sent,\nthen stalled".into(),
+            explanation: Vec::new(),
+        };
+        assert_eq!(
+            super::failure(Some(Path::new("tests/a,b.toml")), Some(source), &failure),
+            format!(
+                "::error file=tests/a%2Cb.toml,line=5,title={}::50%25 sent,%0Athen stalled",
+                Msg::AnnotateAssertion.text()
+            )
+        );
+
+        let err = parse_scenario("name = \n", false).unwrap_err();
+        assert!(super::failure(None, None, &err).starts_with("::error line=1,"));
+    }
+}
@@ -0,0 +1,189 @@
+//! Per-track scores of a suite run. Scenarios name their `track` (e.g. `"correctness"`,
+//! `"robustness"`, `"performance"`), each track scores the share of its scenarios that
+//! passed, and the lab grade is the weighted mean of the track scores.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::bail;
+use serde::Serialize;
+use tcp_lab_abstract::SuiteInfo;
+
+/// The track of scenarios that do not name one.
+pub const DEFAULT_TRACK: &str = "default";
+
+/// Relative weight of each track, e.g. `correctness=60,robustness=20,performance=20`.
+/// The weights need not add up to 100.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrackWeights(pub BTreeMap<String, f64>);
+
+impl FromStr for TrackWeights {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = BTreeMap::new();
+        for entry in s
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let Some((track, weight)) = entry.split_once('=') else {
+                return Err(format!("expected TRACK=WEIGHT, got '{entry}'"));
+            };
+            let weight: f64 = weight
+                .trim()
+                .parse()
+                .ok()
+                .filter(|weight: &f64| weight.is_finite() && *weight >= 0.0)
+                .ok_or_else(|| format!("invalid weight '{}' for track {track}", weight.trim()))?;
+            if weights.insert(track.trim().to_string(), weight).is_some() {
+                return Err(format!("track {} is weighted twice", track.trim()));
+            }
+        }
+        Ok(Self(weights))
+    }
+}
+
+/// How one track of a suite went.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TrackScore {
+    pub track: String,
+    pub weight: f64,
+    pub passed: usize,
+    pub total: usize,
+    /// Percentage of the track's scenarios that passed.
+    pub score: f64,
+    /// File stems of the track's failed scenarios.
+    pub failed: Vec<String>,
+}
+
+/// The per-track scores of a suite run and the grade they add up to.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Gradebook {
+    /// The course and lab the suite grades, from its `suite.toml`.
+    #[serde(skip_serializing_if = "SuiteInfo::is_empty")]
+    pub suite: SuiteInfo,
+    pub tracks: Vec<TrackScore>,
+    /// Weighted mean of the track scores, 0 to 100.
+    pub grade: f64,
+}
+
+impl Gradebook {
+    /// Score `outcomes`, given as `(scenario, track, passed)`. Without weights every track
+    /// counts the same. With weights, every track that ran must have one, and every
+    /// weighted track must have run, so a typo cannot silently drop part of the grade.
+    pub fn new<'a>(
+        outcomes: impl IntoIterator<Item = (&'a str, &'a str, bool)>,
+        weights: &TrackWeights,
+    ) -> anyhow::Result<Self> {
+        let mut by_track: BTreeMap<&str, (usize, Vec<String>)> = BTreeMap::new();
+        for (scenario, track, passed) in outcomes {
+            let (total, failed) = by_track.entry(track).or_default();
+            *total += 1;
+            if !passed {
+                failed.push(scenario.to_string());
+            }
+        }
+        if !weights.0.is_empty() {
+            if let Some(track) = by_track
+                .keys()
+                .find(|track| !weights.0.contains_key(**track))
+            {
+                bail!("track {track} has no weight");
+            }
+            if let Some(track) = weights
+                .0
+                .keys()
+                .find(|track| !by_track.contains_key(track.as_str()))
+            {
+                bail!("weighted track {track} has no scenarios");
+            }
+        }
+
+        let tracks: Vec<TrackScore> = by_track
+            .into_iter()
+            .map(|(track, (total, failed))| {
+                let passed = total - failed.len();
+                TrackScore {
+                    track: track.to_string(),
+                    weight: weights.0.get(track).copied().unwrap_or(1.0),
+                    passed,
+                    total,
+                    score: 100.0 * passed as f64 / total as f64,
+                    failed,
+                }
+            })
+            .collect();
+        let total_weight: f64 = tracks.iter().map(|track| track.weight).sum();
+        let grade = if total_weight > 0.0 {
+            tracks.iter().map(|t| t.weight * t.score).sum::<f64>() / total_weight
+        } else {
+            0.0
+        };
+        Ok(Self {
+            suite: SuiteInfo::default(),
+            tracks,
+            grade,
+        })
+    }
+}
+
+impl fmt::Display for Gradebook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for track in &self.tracks {
+            writeln!(
+                f,
+                "{}: {}/{} passed, {:.1}% (weight {})",
+                track.track, track.passed, track.total, track.score, track.weight
+            )?;
+        }
+        write!(f, "Grade: {:.1}%", self.grade)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weights_track_scores() {
+        let outcomes = [
+            ("test_rdt3", "correctness", true),
+            ("test_sr", "correctness", true),
+            ("test_loss", "robustness", true),
+            ("test_reorder", "robustness", false),
+            ("test_goodput", "performance", false),
+        ];
+        let weights: TrackWeights = "correctness=60, robustness=20, performance=20"
+            .parse()
+            .unwrap();
+        let book = Gradebook::new(outcomes, &weights).unwrap();
+        let scores: Vec<(&str, f64)> = book
+            .tracks
+            .iter()
+            .map(|t| (t.track.as_str(), t.score))
+            .collect();
+        assert_eq!(
+            scores,
+            [
+                ("correctness", 100.0),
+                ("performance", 0.0),
+                ("robustness", 50.0)
+            ]
+        );
+        assert_eq!(book.tracks[2].failed, ["test_reorder"]);
+        assert_eq!(book.grade, 70.0);
+
+        // Unweighted tracks count the same.
+        let book = Gradebook::new(outcomes, &TrackWeights::default()).unwrap();
+        assert_eq!(book.grade, 50.0);
+
+        let typo: TrackWeights = "correctness=60,robustnes=20,performance=20"
+            .parse()
+            .unwrap();
+        let err = Gradebook::new(outcomes, &typo).unwrap_err();
+        assert_eq!(err.to_string(), "track robustness has no weight");
+        assert!("correctness".parse::<TrackWeights>().is_err());
+    }
+}
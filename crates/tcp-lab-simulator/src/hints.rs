@@ -0,0 +1,269 @@
+//! Hints for failed runs: the trace is matched against bug signatures that labs see again
+//! and again, and each match adds the course's advice for it to the report.
+//!
+//! The built-in advice can be replaced per course with a hints file:
+//!
+//! ```toml
+//! [hints]
+//! timer_never_cancelled = "Stop the retransmission timer once the ACK you wait for arrives."
+//! window_never_grows = ""   # an empty text turns a signature off
+//! ```
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use crate::engine::{NodeId, PacketEventKind};
+use crate::i18n::Msg;
+use crate::trace::SimulationReport;
+use tcp_lab_abstract::flags;
+
+/// A recognizable bug pattern in a trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Signature {
+    /// The sender got ACKs back but never cancelled a timer, and its timers kept firing.
+    TimerNeverCancelled,
+    /// The receiver acknowledged a number that matches no data segment it received.
+    WrongAckNumber,
+    /// Corrupted data reached the receiver, and its protocol then delivered bytes the
+    /// application never sent.
+    DeliversCorrupted,
+    /// The sender recorded a `cwnd` metric that never rose above its first value.
+    WindowNeverGrows,
+}
+
+impl Signature {
+    pub const ALL: [Signature; 4] = [
+        Signature::TimerNeverCancelled,
+        Signature::WrongAckNumber,
+        Signature::DeliversCorrupted,
+        Signature::WindowNeverGrows,
+    ];
+
+    fn default_text(self) -> &'static str {
+        match self {
+            Signature::TimerNeverCancelled => Msg::HintTimerNeverCancelled.text(),
+            Signature::WrongAckNumber => Msg::HintWrongAckNumber.text(),
+            Signature::DeliversCorrupted => Msg::HintDeliversCorrupted.text(),
+            Signature::WindowNeverGrows => Msg::HintWindowNeverGrows.text(),
+        }
+    }
+}
+
+/// A signature the run matched, with the advice for it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Hint {
+    pub signature: Signature,
+    pub text: String,
+}
+
+/// Advice per signature; signatures without an entry give no hint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HintCatalog {
+    texts: BTreeMap<Signature, String>,
+}
+
+impl Default for HintCatalog {
+    /// The built-in advice, in the selected language.
+    fn default() -> Self {
+        Self {
+            texts: Signature::ALL
+                .into_iter()
+                .map(|signature| (signature, signature.default_text().to_string()))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HintFile {
+    #[serde(default)]
+    hints: BTreeMap<Signature, String>,
+}
+
+impl HintCatalog {
+    /// The built-in advice with the entries of a hints file (see the module docs) in place
+    /// of it.
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let file: HintFile = toml::from_str(text).context("Invalid hints file")?;
+        let mut catalog = Self::default();
+        for (signature, text) in file.hints {
+            if text.trim().is_empty() {
+                catalog.texts.remove(&signature);
+            } else {
+                catalog.texts.insert(signature, text);
+            }
+        }
+        Ok(catalog)
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read hints file {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("In {}", path.display()))
+    }
+
+    /// The advice for every signature `report` matches.
+    pub fn hints_for(&self, report: &SimulationReport) -> Vec<Hint> {
+        detect(report)
+            .into_iter()
+            .filter_map(|signature| {
+                Some(Hint {
+                    signature,
+                    text: self.texts.get(&signature)?.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// The signatures `report` matches, in `Signature::ALL` order.
+pub fn detect(report: &SimulationReport) -> Vec<Signature> {
+    Signature::ALL
+        .into_iter()
+        .filter(|signature| match signature {
+            Signature::TimerNeverCancelled => timer_never_cancelled(report),
+            Signature::WrongAckNumber => wrong_ack_number(report),
+            Signature::DeliversCorrupted => delivers_corrupted(report),
+            Signature::WindowNeverGrows => window_never_grows(report),
+        })
+        .collect()
+}
+
+fn timer_never_cancelled(report: &SimulationReport) -> bool {
+    let timers = report.timers.sender;
+    let got_acks = report.packets.iter().any(|event| {
+        event.kind == PacketEventKind::Arrived
+            && event.node == NodeId::Sender
+            && event.flags & flags::ACK != 0
+    });
+    got_acks && timers.cancelled == 0 && timers.fired > 0
+}
+
+/// An ACK is plausible if it names a received segment's seq, the one after it (packet
+/// numbering) or its end (byte numbering). ACKs before any data arrived are not judged.
+fn wrong_ack_number(report: &SimulationReport) -> bool {
+    let mut plausible = BTreeSet::new();
+    for event in &report.packets {
+        match event.kind {
+            PacketEventKind::Arrived if event.node == NodeId::Receiver && event.len > 0 => {
+                plausible.insert(event.seq);
+                plausible.insert(event.seq.wrapping_add(1));
+                plausible.insert(event.seq.wrapping_add(event.len as u32));
+            }
+            PacketEventKind::Sent
+                if event.node == NodeId::Receiver
+                    && event.flags & flags::ACK != 0
+                    && event.flags & (flags::SYN | flags::FIN) == 0
+                    && !plausible.is_empty()
+                    && !plausible.contains(&event.ack) =>
+            {
+                return true;
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+fn delivers_corrupted(report: &SimulationReport) -> bool {
+    let Some(first_corruption) = report
+        .packets
+        .iter()
+        .find(|event| {
+            event.kind == PacketEventKind::Corrupted
+                && event.node == NodeId::Sender
+                && event.len > 0
+        })
+        .map(|event| event.time)
+    else {
+        return false;
+    };
+    // The engine rejects deliveries of bytes the application never sent.
+    report.rejected_actions.iter().any(|rejected| {
+        rejected.node == NodeId::Receiver
+            && rejected.time > first_corruption
+            && rejected.message.contains("never sent")
+    })
+}
+
+fn window_never_grows(report: &SimulationReport) -> bool {
+    let Some(samples) = report.metrics.get("cwnd") else {
+        return false;
+    };
+    match samples.split_first() {
+        Some(((_, first), rest)) if !rest.is_empty() => {
+            rest.iter().all(|(_, value)| value <= first)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Simulator;
+    use tcp_lab_abstract::{Packet, SimConfig, SystemContext, TransportProtocol};
+
+    /// Sends each message once under timer 1 and ignores ACKs.
+    struct Forgetful;
+
+    impl TransportProtocol for Forgetful {
+        fn on_packet(&mut self, _ctx: &mut dyn SystemContext, _packet: Packet) {}
+
+        fn on_timer(&mut self, _ctx: &mut dyn SystemContext, _timer_id: u32) {}
+
+        fn on_app_data(&mut self, ctx: &mut dyn SystemContext, data: &[u8]) {
+            ctx.send_packet(Packet::new_simple(0, 0, 0, data.to_vec()));
+            ctx.start_timer(300, 1);
+        }
+    }
+
+    /// ACKs every segment with a number ten past its seq.
+    struct OffByTen;
+
+    impl TransportProtocol for OffByTen {
+        fn on_packet(&mut self, ctx: &mut dyn SystemContext, packet: Packet) {
+            let ack = packet.header.seq_num + 10;
+            ctx.send_packet(Packet::new_simple(0, ack, flags::ACK, Vec::new()));
+        }
+
+        fn on_timer(&mut self, _ctx: &mut dyn SystemContext, _timer_id: u32) {}
+
+        fn on_app_data(&mut self, _ctx: &mut dyn SystemContext, _data: &[u8]) {}
+    }
+
+    #[test]
+    fn test_detects_signatures() {
+        let config = SimConfig {
+            loss_rate: 0.0,
+            corrupt_rate: 0.0,
+            ..Default::default()
+        };
+        let mut sim = Simulator::new(config, Box::new(Forgetful), Box::new(OffByTen));
+        sim.schedule_app_send(0, b"hello".to_vec());
+        sim.run_until_complete();
+        let report = sim.export_report();
+        assert_eq!(
+            detect(&report),
+            [Signature::TimerNeverCancelled, Signature::WrongAckNumber]
+        );
+
+        let catalog = HintCatalog::parse(
+            "[hints]\ntimer_never_cancelled = \"Call cancel_timer.\"\nwrong_ack_number = \"\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            catalog.hints_for(&report),
+            [Hint {
+                signature: Signature::TimerNeverCancelled,
+                text: "Call cancel_timer.".to_string(),
+            }]
+        );
+        assert!(HintCatalog::parse("[hints]\nnever_heard_of = \"x\"\n").is_err());
+    }
+}
@@ -0,0 +1,742 @@
+//! Message catalog for user-facing text: TUI labels, CLI help and grader failures, in
+//! English and Chinese. The language is picked once at startup (`--lang zh-CN` or
+//! `TCP_LAB_LANG`); log lines and link event descriptions stay English.
+
+use std::fmt::{self, Display};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use tcp_lab_abstract::NodeId;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Lang {
+    #[default]
+    En,
+    ZhCn,
+}
+
+impl FromStr for Lang {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('_', "-").as_str() {
+            "en" | "en-us" | "en-gb" => Ok(Lang::En),
+            "zh" | "zh-cn" | "zh-hans" => Ok(Lang::ZhCn),
+            other => Err(format!("unsupported language '{other}' (use en or zh-CN)")),
+        }
+    }
+}
+
+impl Display for Lang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Lang::En => "en",
+            Lang::ZhCn => "zh-CN",
+        })
+    }
+}
+
+static LANG: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_lang(lang: Lang) {
+    LANG.store(lang as u8, Ordering::Relaxed);
+}
+
+pub fn lang() -> Lang {
+    match LANG.load(Ordering::Relaxed) {
+        1 => Lang::ZhCn,
+        _ => Lang::En,
+    }
+}
+
+/// The `--lang` value among `args`, else `TCP_LAB_LANG`, else English. Read before clap
+/// parses the command line so that `--help` is already localized.
+pub fn lang_from_args(args: impl IntoIterator<Item = String>) -> Lang {
+    let mut args = args.into_iter();
+    let mut requested = None;
+    while let Some(arg) = args.next() {
+        if arg == "--lang" {
+            requested = args.next();
+        } else if let Some(value) = arg.strip_prefix("--lang=") {
+            requested = Some(value.to_string());
+        }
+    }
+    requested
+        .or_else(|| std::env::var("TCP_LAB_LANG").ok())
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_default()
+}
+
+macro_rules! catalog {
+    ($($key:ident => $en:literal, $zh:literal;)*) => {
+        /// Every translatable message. `{}` marks where `fill` inserts an argument; both
+        /// languages take the arguments in the same order.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum Msg {
+            $($key,)*
+        }
+
+        impl Msg {
+            pub const ALL: &[Msg] = &[$(Msg::$key,)*];
+
+            pub fn text_in(self, lang: Lang) -> &'static str {
+                match (self, lang) {
+                    $(
+                        (Msg::$key, Lang::En) => $en,
+                        (Msg::$key, Lang::ZhCn) => $zh,
+                    )*
+                }
+            }
+        }
+    };
+}
+
+catalog! {
+    Sender => "Sender", "发送方";
+    Receiver => "Receiver", "接收方";
+
+    // Grader
+    AssertionFailed => "Assertion Failed: {}", "断言失败：{}";
+    ScenarioPassed => "Test Scenario Passed!", "测试场景通过！";
+    UnknownScenarioKeys => "Unknown scenario keys: {}", "未知的场景配置项：{}";
+    ToolTooOld => "This scenario needs tcp-lab {} or newer, but this grader is {}; update tcp-lab",
+        "该场景需要 tcp-lab {} 或更新版本，而当前评测器为 {}；请更新 tcp-lab";
+    InvalidToolVersion => "Invalid min_tool_version {}, expected e.g. \"0.2.0\"",
+        "min_tool_version {} 无效，应形如 \"0.2.0\"";
+    UnknownBuiltinScenario => "No built-in scenario {}; available: {}",
+        "没有名为 {} 的内置场景；可用的有：{}";
+    IgnoringUnknownKeys => "Ignoring unknown scenario keys (check for typos): {}",
+        "忽略未知的场景配置项（请检查拼写）：{}";
+    MigrateMovedToConfig => "moved {} into [config]", "已将 {} 移入 [config]";
+    MigrateAddedKey => "added the missing key {} with an empty value", "已补上缺失的 {}（值为空）";
+    MigrateDroppedKey => "dropped the unknown key {}", "已删除未知的配置项 {}";
+    MigrateConfigNotTable => "config must be a table", "config 必须是表";
+    UnknownConfigSetting => "`{}` is not a [config] setting", "`{}` 不是 [config] 中的设置";
+    InvalidConfig => "Invalid config: {}", "配置无效：{}";
+    ConfigProbability => "{} must be between 0 and 1, got {}", "{} 必须在 0 到 1 之间，实际为 {}";
+    ConfigLatencyRange =>
+        "min_latency ({} ms) is above max_latency ({} ms)",
+        "min_latency（{} ms）大于 max_latency（{} ms）";
+    ConfigZeroMtu =>
+        "mtu is 0, so no data can pass the policer or bottleneck",
+        "mtu 为 0，任何数据都无法通过限速器或瓶颈队列";
+    ConfigZero => "{} must be greater than 0", "{} 必须大于 0";
+    ConfigRedThresholds =>
+        "RED min_ms ({}) must be below max_ms ({})",
+        "RED 的 min_ms（{}）必须小于 max_ms（{}）";
+    RestartNeedsFactory =>
+        "restart_node needs a way to re-create the {} protocol, which this loader cannot provide",
+        "restart_node 需要重新创建{}协议，但当前的加载方式不支持";
+    TimedOut => "Test timed out after {} ms", "测试在 {} ms 后超时";
+    TimedOutUnbalanced => "Run timed out after {} ms with timers unbalanced: {}",
+        "运行在 {} ms 后超时，且定时器未配平：{}";
+    ProtocolPanicked => "A protocol panicked at {} ms: {}", "协议在 {} ms 时发生 panic：{}";
+    Deadlock =>
+        "Deadlock: no packet moved after {} ms and the run ran out of events, but only {} of {} bytes were delivered; the {} was left waiting. Last packet event: {}",
+        "死锁：{} ms 之后再没有分组传输，运行的事件已耗尽，但只交付了 {} 字节（共 {} 字节）；{}一直在等待。最后一个分组事件：{}";
+    NotFlushed =>
+        "The applications closed the connection at {} ms after writing {} bytes, but only {} of them were delivered by the end of the run; on_close must finish sending what the sender still holds",
+        "应用在 {} ms 关闭连接，此前共写入 {} 字节，但运行结束时只交付了其中 {} 字节；on_close 必须把发送方仍持有的数据发送完";
+    LastPacketEvent => "{} {} seq={} ack={} at {} ms", "{} {} seq={} ack={}（{} ms）";
+    NoPacketEvent => "none", "无";
+    IntegrityViolation => "Integrity violation at {} ms ({}): {}", "完整性违规（{} ms，{}）：{}";
+    CheckFailed => "Check {} failed at {} ms: {}", "检查 {} 在 {} ms 未通过：{}";
+    ClockAnomaly =>
+        "Simulated time went backwards at {} ms ({}): {}. This is a bug in the engine or a language bridge, not in the submission",
+        "仿真时间在 {} ms 发生倒退（{}）：{}。这是引擎或语言桥接层的缺陷，而非提交代码的问题";
+    NotDelivered => "Data {} was not delivered", "数据 {} 未被交付";
+    SenderPacketsBelow => "Sender sent {} packets, expected min {}",
+        "发送方发送了 {} 个分组，期望至少 {} 个";
+    SenderPacketsAbove => "Sender sent {} packets, expected max {}",
+        "发送方发送了 {} 个分组，期望至多 {} 个";
+    WindowMaxBelow => "Sender window max {} < expected min {}",
+        "发送方窗口最大值 {} 小于期望最小值 {}";
+    WindowMaxAbove => "Sender window max {} > expected max {}",
+        "发送方窗口最大值 {} 大于期望最大值 {}";
+    WindowNoDrop => "Sender window did not drop from >= {} down to <= {}",
+        "发送方窗口没有从 >= {} 降到 <= {}";
+    InvalidLogPattern => "Invalid log_contains pattern {}", "log_contains 的正则表达式无效：{}";
+    NeverLogged => "{} never logged a message matching {}", "{}从未输出匹配 {} 的日志";
+    MaxInFlightNeedsWindow => "max_in_flight needs `n` or `window_size` under [config]",
+        "max_in_flight 需要设置 `n` 或 [config] 中的 `window_size`";
+    TooManyInFlight => "Sender had {} segments in flight, window is {}",
+        "发送方有 {} 个报文段在途，窗口为 {}";
+    MaxQueueDelayNeedsBottleneck => "max_queue_delay needs `bottleneck` under [config]",
+        "max_queue_delay 需要 [config] 中的 `bottleneck`";
+    QueueDelayTooHigh => "A sender packet waited {} ms in the bottleneck queue at {} ms, limit is {} ms",
+        "发送方报文在瓶颈队列中等待了 {} ms（{} ms 时），上限为 {} ms";
+    ZeroWindowSend => "Sender sent {} bytes at {} ms into a zero window",
+        "发送方向零窗口发送了 {} 字节（{} ms）";
+    NoResumeAfterWindow => "Window reopened at {} ms but the sender sent no data within {} ms",
+        "窗口在 {} ms 重新打开，但发送方在 {} ms 内没有发送数据";
+    ZeroWindowUnprobed => "Zero window left unprobed for over {} ms from {} ms",
+        "零窗口超过 {} ms 未被探测（自 {} ms 起）";
+    NoSyn => "Sender never sent a SYN", "发送方从未发送 SYN";
+    HandshakeSlow => "Handshake took {} ms, limit is {} ms", "握手耗时 {} ms，上限为 {} ms";
+    HandshakeStuck => "Handshake never completed (stuck in {})", "握手未完成（停留在 {}）";
+    SynRetransmits => "SYN retransmitted {} times, at most {} allowed",
+        "SYN 重传了 {} 次，最多允许 {} 次";
+    DataBeforeEstablished => "Sender sent data at {} ms before the handshake completed",
+        "发送方在握手完成前发送了数据（{} ms）";
+    RespectsMssNeedsMss => "respects_mss needs `mss` under [config]",
+        "respects_mss 需要设置 [config] 中的 `mss`";
+    MssExceeded => "{} sent a {}-byte segment at {} ms, over the MSS of {} bytes",
+        "{}发送了 {} 字节的报文段（{} ms），超过了 {} 字节的 MSS";
+    ReproName => "{} (repro)", "{}（复现）";
+    ReproDescription => "Shrunk from a failing run; fails with: {}", "由失败的运行精简而来，失败原因：{}";
+    ReproAssertions => "kept {} of {} assertions, the ones that failed",
+        "保留了失败的 {} 条断言（共 {} 条）";
+    ReproDeterministicLink => "replaced random loss and corruption with {} deterministic faults",
+        "将随机丢包和损坏替换为 {} 个确定性故障";
+    ReproFixedLatency => "fixed the latency at {} ms", "将延迟固定为 {} ms";
+    ReproActions => "kept {} of {} actions", "保留了 {} 个动作（共 {} 个）";
+    ReproTrimmedData => "shortened an app_send from {} to {} characters",
+        "将一次 app_send 的数据从 {} 个字符缩短为 {} 个";
+    NoFin => "{} never sent a FIN", "{}从未发送 FIN";
+    FinNotAcked => "{}'s FIN was never acknowledged by its peer", "{}的 FIN 从未被对端确认";
+    DataAfterFin => "{} sent new data at {} ms after its FIN", "{}在发送 FIN 后又发送了新数据（{} ms）";
+    NoHalfClose => "delivers_after_half_close needs a half_close action",
+        "delivers_after_half_close 需要一个 half_close 动作";
+    HalfOpenStalled =>
+        "{} half-closed at {} ms, then got only {} bytes through the direction still open ({}'s application wrote {} after the close); expected {}",
+        "{}在 {} ms 半关闭后，仍开放的方向只交付了 {} 字节（此后{}的应用层写入了 {} 字节），期望 {} 字节";
+    TimerArmedAtEnd =>
+        "{} timer {} (started at {} ms) was still armed when traffic ended at {} ms",
+        "{}的定时器 {}（{} ms 启动）在流量结束时（{} ms）仍未停止";
+    TimersAfterFin =>
+        "{} still had {} timer(s) armed {} ms after its FIN was acknowledged at {} ms (ids {})",
+        "{}有 {} 个定时器在其 FIN 被确认 {} ms 后仍未停止（FIN 于 {} ms 被确认，编号 {}）";
+    NoFinAcked => "Neither side's FIN was acknowledged", "双方的 FIN 都未被确认";
+    TimersUnbalanced => "Timers unbalanced: {}", "定时器未配平：{}";
+    SilentAppLoss =>
+        "{} app sends ({} bytes) were neither delivered nor refused by the sender, the first made at {} ms (stream bytes {}); call refuse_app_data instead of dropping data",
+        "{} 次应用层发送（{} 字节）既未交付也未被发送方拒绝，第一次发生在 {} ms（流字节 {}）；请调用 refuse_app_data 而不要直接丢弃数据";
+    AppSendsStillHeld => "{} app sends were still held by app_pause when the run ended",
+        "运行结束时仍有 {} 次应用层发送因 app_pause 而未交给协议";
+    TimerCounts => "{} started {} timers, {} fired, {} cancelled, {} still armed",
+        "{}启动了 {} 个定时器，{} 个触发，{} 个取消，{} 个仍在运行";
+    SendStormNeedsLimit =>
+        "no_send_storms needs `max_per_callback` or `send_storm_threshold` under [config]",
+        "no_send_storms 需要设置 `max_per_callback` 或 [config] 中的 `send_storm_threshold`";
+    SendStorm => "{} sent {} packets in one callback at {} ms, at most {} allowed",
+        "{}在一次回调中发送了 {} 个分组（{} ms），最多允许 {} 个";
+    SendGapTooShort =>
+        "The sender sent a data segment {} µs after the previous one at {} ms, at least {} µs required",
+        "发送方的数据段与前一个仅间隔 {} µs（{} ms），至少需要 {} µs";
+    BurstTooLarge => "The sender sent {} data segments back to back from {} ms, at most {} allowed",
+        "发送方连续发送了 {} 个数据段（自 {} ms 起），最多允许 {} 个";
+    PercentileRange => "{} percentile must be 1-100, got {}",
+        "{} 的 percentile 必须在 1-100 之间，实际为 {}";
+    NoDeliveries => "No deliveries to measure latency from", "没有可用于计算时延的交付";
+    LatencyTooHigh => "p{} delivery latency is {} ms, at most {} ms allowed",
+        "p{} 交付时延为 {} ms，最多允许 {} ms";
+    RoundTripNeedsEcho => "round_trip_latency needs app_model = \"echo\" under [config]",
+        "round_trip_latency 需要在 [config] 中设置 app_model = \"echo\"";
+    NoMediaFrames => "frames_on_time needs a media_stream action", "frames_on_time 需要 media_stream 动作";
+    FramesLate => "{}% of frames on time ({} late, {} lost of {}), at least {}% required",
+        "按时到达的帧占 {}%（迟到 {} 帧、丢失 {} 帧，共 {} 帧），至少需要 {}%";
+    NoEchoes => "No echo made it back to the sender's application", "没有回显数据返回到发送方应用层";
+    RoundTripTooHigh => "p{} round-trip time is {} ms, at most {} ms allowed",
+        "p{} 往返时间为 {} ms，最多允许 {} ms";
+    RtoFactorRange => "{} factor_of_rtt must be at least 1, got {}",
+        "{} 的 factor_of_rtt 必须至少为 1，实际为 {}";
+    NoRttSamples => "No packets crossed the link in both directions after {} ms",
+        "{} ms 之后没有双向经过链路的分组";
+    NoRtoMetric => "No `rto` metric was recorded", "没有记录 `rto` 指标";
+    RtoOutOfRange => "rto was {} ms at {} ms, outside {}-{} ms around the true RTT of {} ms",
+        "rto 为 {} ms（{} ms 时），超出范围 {}-{} ms（真实 RTT 为 {} ms）";
+    RtoAdaptsNeedsChange => "rto_adapts needs a set_latency action",
+        "rto_adapts 需要 set_latency 动作";
+    RtoSamplesRange => "rto_adapts within_samples must be at least 1",
+        "rto_adapts 的 within_samples 必须至少为 1";
+    RtoDidNotAdapt =>
+        "rto did not adapt to the latency change at {} ms within {} samples: [{}] ms, none within {}-{} ms around the new true RTT of {} ms",
+        "rto 未能在 {} ms 的时延变化后 {} 个样本内适应：[{}] ms，均不在 {}-{} ms 范围内（新的真实 RTT 为 {} ms）";
+    AckRatioRange => "ack_overhead_below ratio must be positive, got {}",
+        "ack_overhead_below 的 ratio 必须为正数，实际为 {}";
+    NoDataSent => "No data segments were sent", "没有发送任何数据分组";
+    AckOverheadTooHigh =>
+        "Pure ACKs took {} wire bytes against {} for data (ratio {}), expected below {}",
+        "纯 ACK 占用 {} 线路字节，数据占用 {}（比例 {}），期望低于 {}";
+    PiggybackFractionRange => "piggybacked_acks min_fraction must be between 0 and 1, got {}",
+        "piggybacked_acks 的 min_fraction 必须在 0 到 1 之间，实际为 {}";
+    NoAcksSent => "No ACKs were sent", "没有发送任何 ACK";
+    TooFewPiggybacked =>
+        "{} of {} ACKs were piggybacked on data ({}%), at least {}% required",
+        "有 {} 个 ACK 捎带在数据上（共 {} 个，{}%），至少需要 {}%";
+    AckClockedFractionRange => "ack_clocked min_fraction must be between 0 and 1, got {}",
+        "ack_clocked 的 min_fraction 必须在 0 到 1 之间，实际为 {}";
+    TooFewAckClocked =>
+        "{} of {} data segments were sent as an ACK arrived ({}%; {} from timers), at least {}% required",
+        "{} 个数据分组在 ACK 到达时发送（共 {} 个，{}%；{} 个由定时器触发），至少需要 {}%";
+    NoStatesRecorded => "no states recorded", "没有记录状态";
+    StatesNotExact => "{} went through {}, expected {}", "{}的状态序列为 {}，期望为 {}";
+    StateMissing =>
+        "{} never entered {} (state {} of the expected sequence); it went through {}",
+        "{}从未进入 {}（期望序列中的第 {} 个状态）；实际经过 {}";
+    OptimisticAck => "Receiver sent ACK {} at {} ms for data that had not arrived",
+        "接收方发送了 ACK {}（{} ms），但对应的数据尚未到达";
+    PacketNeverSent => "{} never sent a packet with {}", "{}从未发送 {} 的分组";
+    PacketSentLate => "{} first sent a packet with {} {} ms after {}, at most {} ms allowed",
+        "{}首次发送 {} 的分组用了 {} ms（从{}算起），最多允许 {} ms";
+    NeverReceivedFlags => "{} never received a packet with flags {}", "{}从未收到标志为 {} 的分组";
+    RunStart => "the start of the run", "运行开始";
+    ReceivingFlags => "receiving {}", "收到 {}";
+    AnyHeader => "any header", "任意首部";
+    PacketStepMissing => "packet sequence step {} `{}` has no matching packet event after {} ms",
+        "分组序列第 {} 步 `{}` 在 {} ms 之后没有匹配的分组事件";
+    PacketStepTiming => "packet sequence step {} `{}`: the next matching packet event came {} ms after the previous step",
+        "分组序列第 {} 步 `{}`：下一个匹配的分组事件出现在上一步之后 {} ms";
+    Why => "Why: {}", "原因：{}";
+    ClueDropped => "seq={} was dropped at t={}ms", "seq={} 在 t={}ms 时被丢弃";
+    ClueCorrupted => "seq={} was corrupted at t={}ms", "seq={} 在 t={}ms 时损坏";
+    ClueNeverResent => "your sender never retransmitted it", "你的发送方从未重传它";
+    ClueNoTimer => "no sender timer was running", "发送方没有运行任何定时器";
+    ClueTimerCancelled => "timer {} was cancelled at t={}ms", "定时器 {} 在 t={}ms 时被取消";
+    ClueTimerFired => "timer {} fired at t={}ms", "定时器 {} 在 t={}ms 时超时";
+    ClueTimerArmed => "timer {} was still armed when the run ended",
+        "运行结束时定时器 {} 仍在计时";
+    StreamMissing => "bytes {} missing", "字节 {} 缺失";
+    StreamDuplicated => "bytes {} delivered more than once", "字节 {} 被重复交付";
+    StreamReordered => "bytes {} delivered out of order", "字节 {} 乱序交付";
+    StreamMore => "{} more", "另有 {} 段";
+    ClueUndelivered => "the application sent {} bytes and the receiver delivered {}",
+        "应用层发送了 {} 字节，接收方交付了 {} 字节";
+    Hint => "Hint: {}", "提示：{}";
+    MetricLeftBand => "{} left its target band ({}) at {} ms: {} of {} samples outside, furthest at {}",
+        "{} 超出目标范围（{}），始于 {} ms：{} / {} 个采样在范围外，最远为 {}";
+    MetricBandWarning => "Warning: {}", "警告：{}";
+    AssertionTally => "{} of {} assertions passed", "通过了 {} 个断言（共 {} 个）";
+    AssertionTallyFailed => "; failed: {}", "；失败：{}";
+    AssertionTallySkipped => "; skipped: {}", "；跳过：{}";
+    AlsoFailed => "Also failed: {}", "另一处失败：{}";
+    AnnotateAssertion => "Assertion failed", "断言失败";
+    AnnotateStudentRuntime => "Student code raised an exception", "学生代码抛出异常";
+    AnnotateEnvironment => "Grading environment error", "评测环境错误";
+    AnnotateTimeout => "Scenario timed out", "场景超时";
+    HintTimerNeverCancelled =>
+        "ACKs reached your sender, but it never cancelled a timer, so its timers kept firing. Cancel the retransmission timer once everything it guards is acknowledged.",
+        "ACK 已到达你的发送方，但它从未取消定时器，导致定时器不断超时。被保护的数据全部确认后，请取消重传定时器。";
+    HintWrongAckNumber =>
+        "Your receiver sent an ACK number that matches no segment it received. Acknowledge the seq you received (or the next one you expect), not a counter of your own.",
+        "你的接收方发送的 ACK 号与收到的任何分组都不对应。请确认收到的 seq（或期望的下一个），而不是自己维护的计数。";
+    HintDeliversCorrupted =>
+        "Your receiver delivered bytes that were corrupted on the way. Verify the checksum and discard corrupted segments before calling deliver_data.",
+        "你的接收方交付了传输中损坏的数据。调用 deliver_data 之前请校验检验和并丢弃损坏的分组。";
+    HintWindowNeverGrows =>
+        "Your sender's window never grew past its first value. Increase cwnd as ACKs arrive (slow start, then congestion avoidance).",
+        "你的发送方窗口从未超过初始值。请在收到 ACK 时增大 cwnd（先慢启动，再拥塞避免）。";
+
+    // TUI
+    AdHocSimulation => "Ad-hoc Simulation", "临时仿真";
+    ControlStatus =>
+        "Scenario: {} | Time: {} ms | Status: {} | Events Pending: {} | (q)uit (space)pause/resume (s)tep",
+        "场景：{} | 时间：{} ms | 状态：{} | 待处理事件：{} | (q)退出 (空格)暂停/继续 (s)单步";
+    ControlTypeHint => " (i)nput message", " (i)输入消息";
+    ControlReloadHint => " (r)eload", " (r)重新加载";
+    ReloadFailed => "Reload failed: {} | (r)etry", "重新加载失败：{} | (r)重试";
+    ControlDraft =>
+        "Message for the sender's app: {}_ | (enter)send (esc)cancel",
+        "发送方应用层消息：{}_ | (回车)发送 (esc)取消";
+    Paused => "PAUSED", "已暂停";
+    Running => "RUNNING", "运行中";
+    Control => "Control", "控制";
+    NoteAt => "Note @ {} ms", "提示 @ {} ms";
+    SimulationStats => "Simulation Stats:", "仿真统计：";
+    StatDelivered => "  Delivered messages: {}", "  已交付消息：{}";
+    StatSenderPackets => "  Sender packets:     {}", "  发送方分组：{}";
+    StatWireBytes => "  Wire bytes:         {} (efficiency {}%)", "  线路字节：{}（效率 {}%）";
+    StatSenderWindow => "  Sender window:      current={} max={}", "  发送方窗口：当前={} 最大={}";
+    StatTimers => "  Armed timers:       sender {} receiver {}", "  未停止的定时器：发送方 {} 接收方 {}";
+    StatChannel => "  Channel: loss={}, corrupt={}, latency={}..{} ms",
+        "  信道：丢包率={}，出错率={}，时延={}..{} ms";
+    ControlsHeading => "Controls:", "操作：";
+    ControlSpace => "  Space: Pause/Resume", "  空格：暂停/继续";
+    ControlStep => "  s:     Step one event", "  s：   单步执行一个事件";
+    ControlQuit => "  q:     Quit", "  q：   退出";
+    ControlType => "  i:     Type app data to send", "  i：   输入要发送的应用数据";
+    ControlReload =>
+        "  r:     Reload the implementation and restart",
+        "  r：   重新加载实现并重新开始";
+    ControlTimeSequence =>
+        "  t:     In-flight / time-sequence chart",
+        "  t：   切换在途图 / 时间-序号图";
+    Dashboard => "Dashboard", "仪表盘";
+    States => "States", "状态";
+    NoWindowMetrics => "No window metrics yet", "暂无窗口指标";
+    Window => "Window", "窗口";
+    WindowChartTitle => "Sender Window / ssthresh", "发送方窗口 / ssthresh";
+    AxisTime => "time", "时间";
+    AxisSize => "size", "大小";
+    InFlight => "In Flight", "在途";
+    QueueChartTitle => "Bottleneck Queue ({} drops)", "瓶颈队列（{} 次丢弃）";
+    QueueIdle => "No packet has reached the bottleneck yet", "尚无报文到达瓶颈";
+    AxisBytes => "bytes", "字节";
+    NoDataSegments => "No data segments sent yet", "尚未发送数据报文段";
+    AxisSegments => "segs", "报文段";
+    TimeSequenceTitle =>
+        "Time-Sequence (sent, retransmitted, lost, ACKed)",
+        "时间-序号图（发送、重传、丢失、确认）";
+    AxisSeq => "seq", "序号";
+    NoLinkActivity => "No link activity yet", "暂无链路活动";
+    Link => "Link", "链路";
+    SpaceTimeTitle => "Link Space-Time Diagram", "链路时空图";
+    SpaceTimeYours => "Your Run", "你的运行";
+    SpaceTimeDiverged =>
+        "Your Run (differs from the reference at {} ms)",
+        "你的运行（在 {} ms 处与参考实现不同）";
+    SpaceTimeReference => "Reference", "参考实现";
+    Capture => "Capture", "抓包";
+    CaptureTitle => "Capture {} -> {}", "抓包 {} -> {}";
+    NoLinkEvents => "No link events yet", "暂无链路事件";
+    LinkEvents => "Link Events", "链路事件";
+
+    // CLI help
+    AboutSimCli => "Interactive TCP Lab simulator", "TCP Lab 交互式仿真器";
+    AboutEvalHost => "Headless grader for TCP Lab scenarios", "TCP Lab 无界面场景评测器";
+    HelpLang =>
+        "Language of UI labels, help and grader messages: en or zh-CN (default: TCP_LAB_LANG, else en)",
+        "界面文字、帮助和评测信息的语言：en 或 zh-CN（默认取 TCP_LAB_LANG，否则为 en）";
+    HelpScenarioLoad => "Load a scenario from disk", "从磁盘加载场景";
+    HelpScenarioRun =>
+        "Path to the scenario TOML file to execute, or a directory to grade every scenario in it. Without it, the generated workload (--messages etc.) runs on a lossy default link",
+        "要执行的场景 TOML 文件路径，或目录（评测其中所有场景）。不指定时，在默认的有损链路上运行生成的负载（--messages 等）";
+    HelpTags => "Only run scenarios tagged with one of these, e.g. rdt3,required",
+        "只运行带有其中某个标签的场景，如 rdt3,required";
+    HelpExcludeTags => "Skip scenarios tagged with any of these, e.g. bonus",
+        "跳过带有其中任一标签的场景，如 bonus";
+    HelpStrictSim =>
+        "Grading mode: fail on unknown scenario keys and on delivered bytes that never arrived in any packet (headless scenario runs); Java and Python protocols run sandboxed",
+        "评测模式：遇到未知的场景配置项，或交付了从未随分组到达的数据时判为失败（仅无界面运行）；Java 和 Python 协议在沙箱中运行";
+    HelpHints =>
+        "Hints file (TOML) whose advice replaces the built-in hints shown for failed scenarios",
+        "提示文件（TOML），其中的建议替换场景失败时显示的内置提示";
+    HelpAssertionPolicy =>
+        "Overrides each scenario's assertion_policy: all checks every assertion and reports each failure, first-failure stops at the first one that fails",
+        "覆盖各场景的 assertion_policy：all 检查全部断言并报告每个失败，first-failure 在第一个失败的断言处停止";
+    HelpStrictEval =>
+        "Grading mode: fail on unknown scenario keys instead of ignoring them with a warning, and on delivered bytes that never arrived at the delivering node in any packet. Java and Python protocols run sandboxed: writing files, network access, starting processes and System.exit fail the run",
+        "评测模式：未知的场景配置项直接判为失败而不是警告后忽略；交付了从未随分组到达该节点的数据时也判为失败。Java 和 Python 协议在沙箱中运行：写文件、访问网络、启动进程和 System.exit 都会使评测失败";
+    HelpTui => "Launch the terminal UI visualizer", "启动终端可视化界面";
+    HelpRenderMode =>
+        "How the TUI draws: color, color-blind (Okabe-Ito palette) or ascii (text charts, no Braille or box drawing)",
+        "终端界面的绘制方式：color、color-blind（Okabe-Ito 色盲友好配色）或 ascii（纯文本图表，不使用盲文点阵和制表符）";
+    HelpClasspath =>
+        "Java classpath used when loading JVM-based implementations. Accepts jars, class directories (their jars are added too) and `dir/*` wildcards",
+        "加载 JVM 实现时使用的类路径。可以是 jar、class 目录（其中的 jar 也会加入）以及 `dir/*` 通配符";
+    HelpJvmOpt =>
+        "Option passed to the JVM, repeatable (e.g. --jvm-opt -Xmx256m --jvm-opt -ea). The heap is capped at 512m unless an option sets it",
+        "传给 JVM 的选项，可重复使用（例如 --jvm-opt -Xmx256m --jvm-opt -ea）。未指定堆大小时上限为 512m";
+    HelpDeterministicTime =>
+        "Make Python's time.time()/monotonic()/perf_counter() and Java's System.currentTimeMillis()/nanoTime() return simulated time, so protocols that read the wall clock behave the same on every run",
+        "让 Python 的 time.time()/monotonic()/perf_counter() 和 Java 的 System.currentTimeMillis()/nanoTime() 返回模拟时间，使读取真实时钟的协议每次运行结果一致";
+    HelpDebugAttach =>
+        "Pause this many ms (default 2000) before every callback into a C++ library, printing the process ID and the packet or timer about to be handled, so gdb/lldb can be attached; set TCP_LAB_DEBUG_ATTACH_NOTIFY to a shell command to be notified too",
+        "每次调用 C++ 库的回调前暂停指定毫秒数（默认 2000），并打印进程号和即将处理的分组或定时器，以便挂接 gdb/lldb；设置 TCP_LAB_DEBUG_ATTACH_NOTIFY 为一条 shell 命令可同时收到通知";
+    HelpStudentId =>
+        "Derive each scenario's seed from its name, this student ID and --base-seed, so every student gets different but equivalent randomness; the report records the derivation",
+        "由场景名、该学生 ID 和 --base-seed 派生每个场景的种子，使每位学生面对不同但等价的随机性；报告中记录派生过程";
+    HelpBaseSeed => "The course-wide part of seeds derived with --student-id", "使用 --student-id 派生种子时全课程共用的部分";
+    HelpJavaSender => "Fully qualified JVM class of the sender", "发送方的 JVM 类全名";
+    HelpJavaReceiver => "Fully qualified JVM class of the receiver", "接收方的 JVM 类全名";
+    HelpPythonSender => "Python sender as module.Class", "Python 发送方，格式为 module.Class";
+    HelpPythonReceiver => "Python receiver as module.Class", "Python 接收方，格式为 module.Class";
+    HelpPythonUvProject => "Root directory of the uv-managed Python project",
+        "由 uv 管理的 Python 项目根目录";
+    HelpPythonPath => "Extra path to insert into Python sys.path (in addition to uv)",
+        "额外加入 Python sys.path 的路径（uv 之外）";
+    HelpCppSenderLib => "Shared library with the C++ sender", "包含 C++ 发送方的动态库";
+    HelpCppReceiverLib => "Shared library with the C++ receiver", "包含 C++ 接收方的动态库";
+    HelpSideBySide =>
+        "With --tui, run the built-in protocols (--builtin-sender/--builtin-receiver, else rdt2) on the same scenario and seed next to yours, stepping both together and marking where their link events first differ",
+        "配合 --tui 使用：在同一场景和随机种子下同时运行内置协议（--builtin-sender/--builtin-receiver，默认 rdt2），两者同步单步推进，并标出链路事件首次出现差异的位置";
+    HelpCapture =>
+        "With --tui, draw a real TCP transfer exported with `tshark -r FILE -T json` (one connection) next to your run, in the space-time diagram and the time-sequence graph",
+        "配合 --tui 使用：把用 `tshark -r FILE -T json` 导出的真实 TCP 传输（单个连接）画在你的运行旁边，显示在时空图和时间-序号图中";
+    HelpBuiltinSender => "Built-in sender by name (e.g. rdt2; see the builtins command)",
+        "按名称选择内置发送方（如 rdt2；可用 builtins 子命令查看）";
+    HelpBuiltinReceiver => "Built-in receiver by name (e.g. rdt2; see the builtins command)",
+        "按名称选择内置接收方（如 rdt2；可用 builtins 子命令查看）";
+    HelpTraceOut => "Write a JSON trace of the finished simulation", "将仿真结果写入 JSON 跟踪文件";
+    HelpBinaryTraceOut =>
+        "Write the link and packet events as a compact binary trace that `trace` can read from any time",
+        "将链路事件和报文事件写入紧凑的二进制跟踪文件，`trace` 可从任意时刻读取";
+    HelpBundleOut =>
+        "Write a .tcplabrun bundle: the JSON and binary traces, per-node logs, the scenario and run metadata in one zip",
+        "写入 .tcplabrun 运行包：将 JSON 与二进制跟踪、各节点日志、场景文件和运行元数据打包为一个 zip";
+    HelpDotOut =>
+        "Write the causal graph of the run (which arrival or timer each packet was sent from) as Graphviz DOT",
+        "将运行的因果图（每个报文由哪次到达或哪个定时器触发发送）写入 Graphviz DOT 文件";
+    HelpDotFrom => "First simulated time (ms) in the --dot-out graph", "--dot-out 图的起始仿真时间（毫秒）";
+    HelpDotUntil => "Last simulated time (ms) in the --dot-out graph", "--dot-out 图的结束仿真时间（毫秒）";
+    HelpDebugEvents =>
+        "Record low-level engine decisions (random draws, queueing, skipped timers) in the trace, for debugging the simulator",
+        "在 trace 中记录引擎底层决策（随机抽取、排队、跳过的定时器），用于调试仿真器本身";
+    HelpScratchStore =>
+        "Keep the protocols' ctx.store values in FILE (JSON) between practice runs; not allowed with --strict",
+        "在练习运行之间把协议通过 ctx.store 保存的值保留在 FILE（JSON）中；不能与 --strict 同用";
+    HelpAsciiDiagram =>
+        "After the run, print the link space-time diagram as ASCII art, or write it to FILE",
+        "运行结束后以 ASCII 字符画输出链路时空图，或写入 FILE";
+    HelpMessages =>
+        "Number of messages the application sends in runs without a scenario",
+        "无场景运行时应用层发送的消息数";
+    HelpMessageSize => "Size of each generated message in bytes", "每条生成消息的字节数";
+    HelpInterval =>
+        "Milliseconds between generated messages; the first is sent after one interval",
+        "生成消息之间的间隔（毫秒），第一条在一个间隔后发送";
+    HelpPayloadPattern =>
+        "Content of generated messages: counter (\"Packet N\" padded with dots), zeros, alphabet (A-Z across the stream) or random (from the seed)",
+        "生成消息的内容：counter（用点号补齐的 \"Packet N\"）、zeros、alphabet（整个数据流依次为 A-Z）或 random（由随机种子生成）";
+    HelpStdinData =>
+        "With --tui or --realtime, send each line read from stdin as sender app data when it arrives, instead of the generated messages. In the TUI, press i to type a line when stdin is the terminal",
+        "配合 --tui 或 --realtime 使用：从标准输入读到的每一行在到达时作为发送方应用数据发送，取代生成的消息。若标准输入是终端，在 TUI 中按 i 输入一行";
+    HelpEncda => "Play an encrypted ENCDA.tcp trace (mutually exclusive with --scenario)",
+        "回放加密的 ENCDA.tcp 跟踪（不能与 --scenario 同时使用）";
+    HelpRealtime =>
+        "Pace a headless run against the wall clock, SCALE simulated ms per real ms (e.g. 10 plays ten times faster than real time). Scenarios are replayed without assertion checks, as in the TUI",
+        "按真实时间推进无界面运行，每真实毫秒推进 SCALE 个仿真毫秒（如 10 表示十倍速）。与 TUI 一样，场景只回放、不检查断言";
+    HelpGui => "Open the native desktop visualizer (requires the `gui` feature)",
+        "打开桌面可视化窗口（需要 `gui` 特性）";
+    HelpOtel => "Export per-callback latency spans over OTLP (requires the `otel` feature)",
+        "通过 OTLP 导出每次回调的耗时 span（需要 `otel` 特性）";
+    HelpFormat =>
+        "Output format: `github` also prints failures as GitHub Actions annotations on the scenario files",
+        "输出格式：`github` 会把失败另外输出为标注在场景文件上的 GitHub Actions 注释";
+    HelpEvalJsonOut => "Also write how each scenario went as JSON",
+        "同时把每个场景的评测结果写成 JSON";
+    HelpQueue =>
+        "Run as a daemon grading the submission bundles dropped into DIR/incoming against --scenario, writing DIR/results/<bundle>.json",
+        "以守护进程方式运行：用 --scenario 评测放入 DIR/incoming 的提交包，并写出 DIR/results/<提交包>.json";
+    HelpQueueOnce => "With --queue, grade the bundles already queued, then exit",
+        "配合 --queue：评测队列中已有的提交包后退出";
+    HelpQueuePollMs => "How often --queue looks for new bundles", "--queue 检查新提交包的间隔";
+    HelpTimeLimit => "Wall-clock seconds a --queue submission may take before it is stopped and graded as timed out",
+        "--queue 中每份提交允许的墙钟时间（秒），超时即终止并判为超时";
+    HelpMemoryLimit => "Address space (MB) a --queue submission may use (Unix only)",
+        "--queue 中每份提交可使用的地址空间（MB，仅 Unix）";
+    QueueNeedsScenario => "--queue needs the scenarios to grade with as --scenario",
+        "--queue 需要用 --scenario 指定评测所用的场景";
+    QueueTimedOut => "The submission did not finish grading within {} s", "该提交未能在 {} 秒内完成评测";
+    QueueCrashed => "The grader exited abnormally ({}), e.g. because the submission crashed it",
+        "评测进程异常退出（{}），例如提交的代码使其崩溃";
+    QueueCrashedIn => "The submission crashed the grader: {}", "提交的代码使评测进程崩溃：{}";
+    QueueNoOutcomes => "The grader exited successfully without reporting any outcomes",
+        "评测进程正常退出，但没有报告任何评测结果";
+    QueueOutcomesMismatch =>
+        "The grader's outcomes do not match the selected scenarios (missing: {}; unexpected: {})",
+        "评测结果与所选场景不符（缺少：{}；多出：{}）";
+    QueueOutcomeFailed => "The grader exited successfully although {} failed",
+        "评测进程正常退出，但 {} 未通过";
+    QueueRepeatedOutcomes =>
+        "The grader reported its outcomes more than once, so the submission wrote to its report pipe",
+        "评测进程不止一次报告评测结果，说明提交的代码写入了其报告管道";
+    CrashReported => "Fatal: {}", "致命错误：{}";
+    CrashIn => "{} in {}", "{}（位于 {}）";
+    CrashNullDeref => "null pointer dereference", "空指针解引用";
+    CrashBadAccess => "invalid memory access at {}", "非法内存访问（地址 {}）";
+    CrashAbort => "abort (a failed assert or an uncaught C++ exception)",
+        "abort（断言失败或未捕获的 C++ 异常）";
+    CrashArithmetic => "arithmetic error (e.g. integer division by zero)",
+        "算术错误（例如整数除以零）";
+    CrashIllegalInstruction => "illegal instruction", "非法指令";
+    HelpCrashReport =>
+        "When a C++ submission crashes the grader, also write the symbolized crash report to this JSON file",
+        "C++ 提交使评测进程崩溃时，将符号化的崩溃报告同时写入此 JSON 文件";
+    HelpReportFd =>
+        "Send the outcomes, and any crash report, as JSON lines over this inherited file descriptor (the --queue daemon sets this for each bundle)",
+        "通过继承的此文件描述符以 JSON 行发送评测结果及崩溃报告（--queue 守护进程会为每个提交设置）";
+    HelpFingerprints =>
+        "Add a behavioral fingerprint of each run to --json-out; with --queue, also rank every pair of graded submissions by how alike they behaved in DIR/similarity.json, an advisory signal for academic-integrity review",
+        "在 --json-out 中附上每次运行的行为指纹；配合 --queue 时，还会在 DIR/similarity.json 中按行为相似度为已评测提交两两排序，仅作学术诚信审查的参考";
+    SimilarityWritten => "Compared {} submissions in {}", "已比较 {} 份提交，结果写入 {}";
+    HelpSigningKey =>
+        "Sign every JSON report written (--json-out, --queue results) with this ed25519 key from `keygen`, into <report>.sig",
+        "用 `keygen` 生成的 ed25519 密钥为写出的每份 JSON 报告（--json-out、--queue 结果）签名，签名写入 <报告>.sig";
+    AboutKeygen => "Create an ed25519 key pair for --signing-key: the secret key in OUT, the public key in OUT.pub",
+        "为 --signing-key 生成 ed25519 密钥对：私钥写入 OUT，公钥写入 OUT.pub";
+    HelpKeygenOut => "Where to write the secret key", "私钥的写入路径";
+    AboutVerify => "Check that a JSON report was signed by the grading host and not changed since",
+        "检查 JSON 报告确由评测主机签名，且签名后未被修改";
+    HelpVerifyReport => "The report to check", "要检查的报告";
+    HelpPublicKey => "The grading host's public key (the .pub file from `keygen`)",
+        "评测主机的公钥（`keygen` 生成的 .pub 文件）";
+    HelpSignature => "The signature file (default: <report>.sig)", "签名文件（默认为 <报告>.sig）";
+    SignatureValid => "{} was signed by the grading host and is unchanged", "{} 确由评测主机签名且未被修改";
+    SignatureInvalid => "{} does not match its signature: it was changed or signed with another key",
+        "{} 与其签名不符：报告已被修改，或由其他密钥签名";
+    SignatureNoSubmission => "{} does not say which submission it graded, so its signature proves nothing about one",
+        "{} 未注明所评测的提交，其签名无法证明与任何提交相关";
+    SignedSubmission => "Student {}, graded at {} (Unix time); protocols {}, scenarios {}",
+        "学生 {}，评测于 {}（Unix 时间）；协议 {}，场景 {}";
+    SignatureWrongStudent => "{} was graded for student {}, not {}", "{} 评测的是学生 {}，而不是 {}";
+    HelpVerifyStudent => "Also require the report to be for this student ID",
+        "同时要求报告属于此学号";
+    AboutBenchBridge =>
+        "Measure per-callback overhead of the built-in Rust protocol and every sender passed via --java-sender/--python-sender/--cpp-sender-lib",
+        "测量内置 Rust 协议以及 --java-sender/--python-sender/--cpp-sender-lib 指定的各发送方的单次回调开销";
+    HelpIterations => "Calls per callback type", "每种回调的调用次数";
+    AboutRoundTrip =>
+        "Send edge-case packets (maximal fields, empty and 64 KiB payloads, all flags, non-UTF-8 bytes) through every --java-sender/--python-sender/--cpp-sender-lib, which must be the SDK's Reflector, and check that they come back bit for bit",
+        "把边界情况的数据包（字段取最大值、空载荷与 64 KiB 载荷、全部标志位、非 UTF-8 字节）送入 --java-sender/--python-sender/--cpp-sender-lib 指定的各发送方（须为 SDK 中的 Reflector），检查它们是否逐位原样返回";
+    AboutChecksum =>
+        "Check the checksum of every --java-sender/--python-sender/--cpp-sender-lib against the reference test vectors, through the protocol's `checksum` diagnostic hook",
+        "通过协议的 `checksum` 诊断钩子，用参考测试向量检查 --java-sender/--python-sender/--cpp-sender-lib 指定的各发送方的校验和";
+    AboutCompare =>
+        "Run the scenario (or the default simulation) headless once per sender: the built-in one plus every --java-sender/--python-sender/--cpp-sender-lib, then print their key aggregates side by side",
+        "对每个发送方（内置发送方以及 --java-sender/--python-sender/--cpp-sender-lib 指定的发送方）各无界面运行一次场景（或默认仿真），并排列出主要统计";
+    HelpJsonOut => "Also write the rows as JSON", "同时把结果行写成 JSON";
+    AboutSweep =>
+        "Run the scenario (or the default simulation) headless once per seed and print the mean, standard deviation, minimum and maximum of completion time, retransmissions and goodput; with --param, do so at each of several values of a [config] setting and write the results as CSV",
+        "对每个种子无界面运行一次场景（或默认仿真），输出完成时间、重传次数和有效吞吐量的均值、标准差、最小值和最大值；使用 --param 时在某个 [config] 设置的多个取值下分别运行，并把结果写成 CSV";
+    HelpSeeds => "Seeds to run, e.g. 1,2,3 (overrides the scenario's seed); optional with --param",
+        "要运行的种子，如 1,2,3（覆盖场景中的种子）；使用 --param 时可省略";
+    AboutSuite =>
+        "Grade every scenario TOML in a directory, printing PASS or FAIL for each; fails if any scenario fails",
+        "评测目录中的每个场景 TOML，逐个输出 PASS 或 FAIL；有场景失败时返回失败";
+    HelpSuiteJsonOut => "Also write each scenario's outcome as JSON", "同时把每个场景的结果写成 JSON";
+    HelpTrackWeights =>
+        "Weight of each scenario track in the lab grade, e.g. correctness=60,robustness=20,performance=20",
+        "各场景轨道在实验成绩中的权重，例如 correctness=60,robustness=20,performance=20";
+    HelpGradebook =>
+        "Also write the per-track scores and the weighted grade as JSON",
+        "同时把各轨道得分和加权成绩写成 JSON";
+    HelpSoak =>
+        "Grade the implementation on randomized scenarios (or on --scenario with a new seed each run) for this many hours, and summarize the failure rates",
+        "在随机生成的场景上（或每次换一个种子运行 --scenario）持续评测实现这么多小时，并汇总失败率";
+    HelpSoakDir =>
+        "Directory for the soak's summary.json and the compressed reports of failed runs",
+        "存放 soak 的 summary.json 和失败运行的压缩报告的目录";
+    HelpSoakKeep =>
+        "Keep the reports of only this many of the latest failed runs (at least 1)",
+        "只保留最近这么多次失败运行的报告（至少 1 次）";
+    HelpSoakNoShrink =>
+        "Do not shrink failed runs into small .min.toml scenarios that fail the same way",
+        "不把失败的运行精简为以同样方式失败的小型 .min.toml 场景";
+    HelpCache =>
+        "Reuse outcomes stored in this file when the scenario, the protocol files and the seed are unchanged, and store new ones",
+        "当场景、协议文件和种子都未改变时复用该文件中保存的结果，并保存新的结果";
+    AboutWatch =>
+        "Re-run the build command and re-grade a scenario directory whenever a watched file changes, printing what started or stopped passing",
+        "每当被监视的文件发生变化时，重新执行构建命令并评测场景目录，输出哪些场景开始通过或不再通过";
+    HelpWatchSuite => "Scenario directory to grade", "要评测的场景目录";
+    HelpBuild => "Shell command to run before each grading round, e.g. \"javac -d out src/*.java\"",
+        "每轮评测前执行的 shell 命令，如 \"javac -d out src/*.java\"";
+    HelpWatchPath =>
+        "Extra file or directory to watch (the suite, --classpath, Python paths and C++ libraries are always watched)",
+        "额外监视的文件或目录（场景目录、--classpath、Python 路径和 C++ 库总会被监视）";
+    HelpNotify => "Show a desktop notification with each round's result", "每轮评测后显示桌面通知";
+    HelpPollMs => "How often to check the watched files", "检查被监视文件的间隔";
+    AboutTrace => "Print the events of a binary trace or run bundle, seeking straight to --from",
+        "输出二进制跟踪文件或运行包中的事件，直接定位到 --from 指定的时刻";
+    HelpTraceFrom => "First simulated time (ms) to print", "输出的起始仿真时间（毫秒）";
+    HelpTraceUntil => "Last simulated time (ms) to print", "输出的结束仿真时间（毫秒）";
+    AboutMigrate => "Upgrade a scenario written for an older tcp-lab to the current schema",
+        "将为旧版 tcp-lab 编写的场景升级到当前格式";
+    HelpMigrateOut => "Write the upgraded scenario here instead of to stdout (comments are not kept)",
+        "将升级后的场景写入此文件而非标准输出（注释不会保留）";
+    AboutProgress =>
+        "Show which lab stages the cached suite results complete and which scenarios the next one needs",
+        "根据缓存的评测结果显示已完成的实验阶段，以及下一阶段还需通过的场景";
+    HelpProgressCache => "The --cache file the suite runs wrote", "suite 运行时 --cache 写入的缓存文件";
+    MigrateUpToDate => "The scenario is already up to date", "该场景已是最新格式";
+    AboutRepro => "Shrink the failing --scenario into a small scenario that fails the same way, to hand out with a grade",
+        "将失败的 --scenario 精简为以同样方式失败的小场景，随成绩一起发给学生";
+    HelpReproOut => "Write the repro scenario here instead of to stdout",
+        "将复现场景写入此文件而非标准输出";
+    ReproNeedsScenario => "repro needs the failing scenario as --scenario",
+        "repro 需要用 --scenario 指定失败的场景";
+    ReproPasses => "The scenario passes, so there is nothing to reproduce",
+        "该场景已通过，无需复现";
+    AboutBuiltins => "List the built-in reference protocols with their lab stage",
+        "列出内置参考协议及其所属实验阶段";
+    HelpSweepJsonOut => "Also write every run and the statistics as JSON",
+        "同时把每次运行及统计结果写成 JSON";
+    HelpSweepParam => "[config] setting to sweep, e.g. loss_rate; runs the seeds (or the scenario's own seed) at each of --values",
+        "要扫描的 [config] 设置，如 loss_rate；在 --values 的每个取值下运行这些种子（或场景自身的种子）";
+    HelpSweepValues => "Values of --param to run, e.g. 0,0.05,0.1,0.2",
+        "--param 要取的值，如 0,0.05,0.1,0.2";
+    HelpSweepCsvOut => "Where to write the --param results as CSV [default: sweep-<param>.csv]",
+        "--param 结果的 CSV 输出文件 [默认：sweep-<param>.csv]";
+    HelpSweepSvgOut => "Also chart goodput and retransmissions against --values as SVG",
+        "同时把有效吞吐量和重传次数随 --values 变化的图表写成 SVG";
+    SweepWrote => "Wrote {}", "已写入 {}";
+    SuitePass => "PASS {}{}", "PASS {}{}";
+    SuitePassCached => "PASS {} (cached){}", "PASS {}（缓存）{}";
+    SuiteFail => "FAIL {}: {}", "FAIL {}：{}";
+    SuiteFailCached => "FAIL {} (cached): {}", "FAIL {}（缓存）：{}";
+    SuiteMetBy => " (met by {} of {} ms)", "（在 {} / {} 毫秒时满足）";
+    WatchRound => "── round {} ──", "── 第 {} 轮 ──";
+    WatchWaiting => "Watching {} files for changes…", "正在监视 {} 个文件的变化…";
+    WatchFail => "  FAIL {}: {}", "  FAIL {}：{}";
+    WatchPassed => "{}/{} passed", "{}/{} 通过";
+    WatchNowPassing => " | now passing: {}", " | 新通过：{}";
+    WatchNowFailing => " | now failing: {}", " | 新失败：{}";
+    WatchBuildFailed => "build failed", "构建失败";
+    WatchGradingFailed => "grading failed", "评测失败";
+    SoakFail => "FAIL seed {}: {}", "FAIL 种子 {}：{}";
+    SoakShrunk => "  shrunk in {} runs: {}", "  经 {} 次运行精简为：{}";
+    SoakProgress => "soak {}: {} runs, {} failed ({}%)", "soak {}：共 {} 次运行，{} 次失败（{}%）";
+}
+
+impl Msg {
+    /// The message in the selected language.
+    pub fn text(self) -> &'static str {
+        self.text_in(lang())
+    }
+
+    /// The message with each `{}` replaced by the next of `args`.
+    pub fn fill(self, args: &[&dyn Display]) -> String {
+        let mut out = String::new();
+        let mut args = args.iter();
+        let mut parts = self.text().split("{}");
+        out.push_str(parts.next().unwrap_or_default());
+        for part in parts {
+            if let Some(arg) = args.next() {
+                out.push_str(&arg.to_string());
+            }
+            out.push_str(part);
+        }
+        out
+    }
+
+    /// `AssertionFailed` wrapped around this message.
+    pub fn failed(self, args: &[&dyn Display]) -> String {
+        Msg::AssertionFailed.fill(&[&self.fill(args)])
+    }
+}
+
+pub fn node_name(node: NodeId) -> &'static str {
+    match node {
+        NodeId::Sender => Msg::Sender.text(),
+        NodeId::Receiver => Msg::Receiver.text(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Lang, Msg, lang_from_args};
+
+    #[test]
+    fn translations_take_the_same_arguments() {
+        for msg in Msg::ALL {
+            assert_eq!(
+                msg.text_in(Lang::En).matches("{}").count(),
+                msg.text_in(Lang::ZhCn).matches("{}").count(),
+                "{msg:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn parses_lang_flag() {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            lang_from_args(args(&["bin", "--lang", "zh-CN"])),
+            Lang::ZhCn
+        );
+        assert_eq!(lang_from_args(args(&["bin", "--lang=zh_cn"])), Lang::ZhCn);
+        assert_eq!("EN".parse(), Ok(Lang::En));
+        assert!("fr".parse::<Lang>().is_err());
+    }
+}
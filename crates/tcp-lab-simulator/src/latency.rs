@@ -0,0 +1,112 @@
+//! Latency samples for the report: how long packets spent in the channel, and how long
+//! application bytes took from `app_send` to `deliver_data`.
+
+use serde::Serialize;
+
+/// Equal-width buckets in `LatencyStats::histogram`.
+const HISTOGRAM_BUCKETS: u64 = 10;
+
+/// Every sample of one kind of latency, in milliseconds.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyHistogram {
+    samples: Vec<u64>,
+}
+
+impl LatencyHistogram {
+    pub fn record(&mut self, ms: u64) {
+        self.samples.push(ms);
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// The nearest-rank `p`th percentile (`p` in 0..=100), or `None` without samples.
+    pub fn percentile(&self, p: u8) -> Option<u64> {
+        let mut sorted = self.samples.clone();
+        sorted.sort_unstable();
+        nearest_rank(&sorted, p)
+    }
+
+    pub fn stats(&self) -> Option<LatencyStats> {
+        let mut sorted = self.samples.clone();
+        sorted.sort_unstable();
+        let (&min, &max) = (sorted.first()?, sorted.last()?);
+        let width = (max - min) / HISTOGRAM_BUCKETS + 1;
+        let mut histogram: Vec<LatencyBucket> = (0..HISTOGRAM_BUCKETS)
+            .map(|i| LatencyBucket {
+                from_ms: min + i * width,
+                to_ms: min + (i + 1) * width,
+                count: 0,
+            })
+            .take_while(|bucket| bucket.from_ms <= max)
+            .collect();
+        for &ms in &sorted {
+            histogram[((ms - min) / width) as usize].count += 1;
+        }
+        Some(LatencyStats {
+            samples: sorted.len() as u64,
+            min,
+            p50: nearest_rank(&sorted, 50)?,
+            p95: nearest_rank(&sorted, 95)?,
+            p99: nearest_rank(&sorted, 99)?,
+            max,
+            histogram,
+        })
+    }
+}
+
+fn nearest_rank(sorted: &[u64], p: u8) -> Option<u64> {
+    let rank = (usize::from(p.min(100)) * sorted.len()).div_ceil(100);
+    sorted.get(rank.saturating_sub(1)).copied()
+}
+
+/// Samples in `[from_ms, to_ms)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LatencyBucket {
+    pub from_ms: u64,
+    pub to_ms: u64,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct LatencyPercentiles {
+    pub p50: u64,
+    pub p95: u64,
+    pub p99: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LatencyStats {
+    pub samples: u64,
+    pub min: u64,
+    pub p50: u64,
+    pub p95: u64,
+    pub p99: u64,
+    pub max: u64,
+    pub histogram: Vec<LatencyBucket>,
+}
+
+impl LatencyStats {
+    pub fn percentiles(&self) -> LatencyPercentiles {
+        LatencyPercentiles {
+            p50: self.p50,
+            p95: self.p95,
+            p99: self.p99,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct LatencyReport {
+    /// Time from a packet leaving its node to its arrival, for packets that were not lost.
+    pub channel: Option<LatencyStats>,
+    /// Time from the `app_send` that first carried a delivered byte to its `deliver_data`.
+    pub delivery: Option<LatencyStats>,
+    /// Time from an `app_send` to the sender's `deliver_data` of its echo (`app_model = "echo"`).
+    pub round_trip: Option<LatencyStats>,
+}
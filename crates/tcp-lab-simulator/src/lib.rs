@@ -1,11 +1,73 @@
+pub mod bottleneck;
+pub mod builder;
+pub mod capture;
+pub mod checker;
+pub mod connection;
 pub mod engine;
+pub mod error;
+pub mod i18n;
 
 #[cfg(feature = "tui")]
 pub mod tui;
 
+#[cfg(feature = "otel")]
+pub mod telemetry;
+
+#[cfg(feature = "realtime")]
+pub mod realtime;
+
 pub mod encda;
+pub mod explain;
+pub mod fingerprint;
+pub mod github;
+pub mod gradebook;
+pub mod hints;
+pub mod latency;
+pub mod live;
+pub mod media;
+pub mod middlebox;
+pub mod optimal;
+pub mod outcome;
+pub mod progress;
+pub mod report;
+pub mod repro;
 pub mod scenario_runner;
+pub mod scratch;
+pub mod selftest;
+pub mod stream;
+#[cfg(test)]
+mod test_protocol;
 pub mod trace;
+pub mod workload;
 
-pub use engine::{LinkEventSummary, NodeId, Simulator};
-pub use trace::SimulationReport;
+pub use builder::SimulatorBuilder;
+pub use checker::{CheckFailure, Checker, RunEvent};
+pub use connection::{ConnectionState, ConnectionTrace, FinTrace, MssViolation};
+pub use engine::{
+    APP_BACKLOG_METRIC, AckTraceEntry, Annotation, AppBackpressureStats, BridgeWarning,
+    BridgeWarningEvent, CallRecord, CallStatus, CallbackReport, CallbackStats, DirectionBytes,
+    DropEvent, DropReason, ExternalEvent, FlowTraceEntry, IN_FLIGHT_METRIC, LinkDirection,
+    LinkEventKind, LinkEventSummary, LogEntry, NodeCallbacks, NodeId, PacingStats, PacketEvent,
+    PacketEventKind, ProtocolFactory, QUEUE_DELAY_METRIC, QUEUE_DROPS_METRIC, QUEUE_LENGTH_METRIC,
+    RTO_METRIC, RefusedAppSend, RejectedAction, SendStorm, SilentAppLoss, Simulator,
+    StateTransition, TamperedDuplicate, TimerRecord, TimerReport, TimerStats, TrafficStats,
+    TruncatedPacket,
+};
+pub use error::{ConfigError, ExitStatus, ExternalEventError, GradingFailed, ScenarioError};
+pub use explain::{Clue, TimerFate};
+pub use fingerprint::{Fingerprint, PairSimilarity, SimilarityReport};
+pub use gradebook::{Gradebook, TrackScore, TrackWeights};
+pub use hints::{Hint, HintCatalog, Signature};
+pub use latency::{LatencyHistogram, LatencyPercentiles, LatencyReport, LatencyStats};
+pub use media::{MediaReport, MediaTracker};
+pub use middlebox::Middlebox;
+pub use outcome::ScenarioOutcome;
+pub use scenario_runner::{
+    AssertionOutcome, AssertionStatus, MetricBandWarning, RunOptions, ScenarioResult,
+    SeedDerivation, StudentSeed, TagFilter, run_scenario_with,
+};
+pub use scratch::ScratchStore;
+pub use stream::StreamReport;
+pub use tcp_lab_abstract::{AssertionPolicy, SuiteInfo, TestScenario};
+pub use trace::{ReportSummary, SimulationReport};
+pub use workload::{PayloadPattern, Workload};
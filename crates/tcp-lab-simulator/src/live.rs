@@ -0,0 +1,71 @@
+//! Application data supplied while a run is on screen.
+//!
+//! Each line read from stdin becomes one app send at the simulated time it
+//! arrives, so a lecture demo can chat over the protocol under test.
+
+use crate::engine::Simulator;
+use std::io::{self, BufRead};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+
+/// A stream of app messages fed into a running simulation.
+pub struct LiveInput {
+    rx: Receiver<Vec<u8>>,
+    open: bool,
+}
+
+impl LiveInput {
+    /// Read stdin line by line on a background thread. Line endings are
+    /// stripped and empty lines skipped. The input closes at end of file.
+    pub fn stdin() -> Self {
+        let (tx, input) = Self::channel();
+        thread::spawn(move || {
+            let mut stdin = io::stdin().lock();
+            let mut line = Vec::new();
+            loop {
+                line.clear();
+                match stdin.read_until(b'\n', &mut line) {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {}
+                }
+                while matches!(line.last(), Some(b'\n' | b'\r')) {
+                    line.pop();
+                }
+                if !line.is_empty() && tx.send(line.clone()).is_err() {
+                    break;
+                }
+            }
+        });
+        input
+    }
+
+    /// An input fed through the returned sender. It closes once every sender
+    /// has been dropped.
+    pub fn channel() -> (Sender<Vec<u8>>, Self) {
+        let (tx, rx) = mpsc::channel();
+        (tx, Self { rx, open: true })
+    }
+
+    /// Whether more messages may still arrive.
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Schedule every message received so far as an app send at `time`, or at
+    /// the simulator's current time if that is later. Returns how many there were.
+    pub fn feed(&mut self, sim: &mut Simulator, time: u64) -> usize {
+        let time = time.max(sim.current_time());
+        let mut count = 0;
+        while self.open {
+            match self.rx.try_recv() {
+                Ok(data) => {
+                    sim.schedule_app_send(time, data);
+                    count += 1;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => self.open = false,
+            }
+        }
+        count
+    }
+}
@@ -0,0 +1,126 @@
+//! Deadline tracking for streamed media frames.
+//!
+//! A `media_stream` action sends constant-bit-rate frames that are only useful
+//! if they reach the receiving application before their deadline. The report
+//! then shows what a fully reliable protocol costs in late frames, next to a
+//! protocol that gives up on old data.
+
+use serde::Serialize;
+
+#[derive(Debug, Clone)]
+struct MediaFrame {
+    /// Offset of the frame's first byte in the application stream.
+    start: usize,
+    deadline: u64,
+    /// Which of the frame's bytes have been delivered.
+    delivered: Vec<bool>,
+    remaining: usize,
+    completed_at: Option<u64>,
+}
+
+/// Every frame sent so far and how much of it has been delivered.
+#[derive(Debug, Clone, Default)]
+pub struct MediaTracker {
+    frames: Vec<MediaFrame>,
+}
+
+impl MediaTracker {
+    /// Track a frame of `len` bytes starting at `start` in the app stream.
+    pub fn add_frame(&mut self, start: usize, len: usize, deadline: u64) {
+        self.frames.push(MediaFrame {
+            start,
+            deadline,
+            delivered: vec![false; len],
+            remaining: len,
+            completed_at: None,
+        });
+    }
+
+    /// Mark `len` bytes from `offset` in the app stream as delivered at `now`.
+    pub fn record_delivery(&mut self, offset: usize, len: usize, now: u64) {
+        let end = offset + len;
+        let first = self
+            .frames
+            .partition_point(|frame| frame.start + frame.delivered.len() <= offset);
+        for frame in &mut self.frames[first..] {
+            if frame.start >= end {
+                break;
+            }
+            let from = offset.saturating_sub(frame.start);
+            let to = (end - frame.start).min(frame.delivered.len());
+            for byte in &mut frame.delivered[from..to] {
+                if !*byte {
+                    *byte = true;
+                    frame.remaining -= 1;
+                }
+            }
+            if frame.remaining == 0 && frame.completed_at.is_none() {
+                frame.completed_at = Some(now);
+            }
+        }
+    }
+
+    /// Counts over all frames, or `None` if no frame was sent.
+    pub fn report(&self) -> Option<MediaReport> {
+        if self.frames.is_empty() {
+            return None;
+        }
+        let mut report = MediaReport {
+            frames: self.frames.len() as u64,
+            ..Default::default()
+        };
+        for frame in &self.frames {
+            match frame.completed_at {
+                Some(at) if at <= frame.deadline => report.on_time += 1,
+                Some(_) => report.late += 1,
+                None => report.lost += 1,
+            }
+        }
+        let percent = |n: u64| n as f64 * 100.0 / report.frames as f64;
+        report.late_percent = percent(report.late);
+        report.lost_percent = percent(report.lost);
+        Some(report)
+    }
+}
+
+/// How the frames of a `media_stream` fared. A frame is on time once its last
+/// byte is delivered by its deadline, late if that happens after it, and lost
+/// if some of its bytes never arrive.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MediaReport {
+    pub frames: u64,
+    pub on_time: u64,
+    pub late: u64,
+    pub lost: u64,
+    pub late_percent: f64,
+    pub lost_percent: f64,
+}
+
+impl MediaReport {
+    pub fn on_time_percent(&self) -> f64 {
+        100.0 - self.late_percent - self.lost_percent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_deadlines() {
+        let mut media = MediaTracker::default();
+        media.add_frame(0, 4, 100);
+        media.add_frame(4, 4, 200);
+        media.add_frame(8, 4, 300);
+        // One delivery spans the first two frames; the second completes late.
+        media.record_delivery(0, 6, 50);
+        media.record_delivery(6, 2, 250);
+        // A repeated delivery must not complete the third frame.
+        media.record_delivery(8, 2, 260);
+        media.record_delivery(8, 2, 270);
+
+        let report = media.report().unwrap();
+        assert_eq!((report.on_time, report.late, report.lost), (1, 1, 1));
+        assert!((report.on_time_percent() - 100.0 / 3.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,129 @@
+//! Middleboxes that rewrite headers on the link, for labs on why end-to-end assumptions
+//! break. The engine has a single link, so a middlebox sits on it rather than on a hop of
+//! its own: it adds no latency and sees every packet that is not lost, after corruption.
+
+use tcp_lab_abstract::{MiddleboxConfig, NodeId, Packet};
+
+pub trait Middlebox {
+    /// Short name shown in `REWRITE` link events.
+    fn name(&self) -> &'static str;
+
+    /// Rewrite `packet`, which `from` sent, in place. Returns what changed, or `None` if
+    /// the packet passed untouched.
+    fn forward(&mut self, from: NodeId, packet: &mut Packet) -> Option<String>;
+}
+
+/// The built-in middlebox `config` describes.
+pub fn from_config(config: MiddleboxConfig) -> Box<dyn Middlebox> {
+    match config {
+        MiddleboxConfig::Nat { external_port } => Box::new(Nat {
+            external_port,
+            internal_port: None,
+        }),
+        MiddleboxConfig::SeqOffset { offset, one_way } => Box::new(SeqOffset { offset, one_way }),
+    }
+}
+
+/// Maps the sender's source port to `external_port`; the first outgoing packet sets up
+/// the mapping.
+pub struct Nat {
+    pub external_port: u16,
+    pub internal_port: Option<u16>,
+}
+
+impl Middlebox for Nat {
+    fn name(&self) -> &'static str {
+        "nat"
+    }
+
+    fn forward(&mut self, from: NodeId, packet: &mut Packet) -> Option<String> {
+        let header = &mut packet.header;
+        match from {
+            NodeId::Sender => {
+                let internal = *self.internal_port.get_or_insert(header.src_port);
+                if header.src_port != internal || internal == self.external_port {
+                    return None;
+                }
+                header.src_port = self.external_port;
+                Some(format!("src_port {}->{}", internal, self.external_port))
+            }
+            NodeId::Receiver => {
+                let internal = self.internal_port?;
+                if header.dst_port != self.external_port || internal == self.external_port {
+                    return None;
+                }
+                header.dst_port = internal;
+                Some(format!("dst_port {}->{}", self.external_port, internal))
+            }
+        }
+    }
+}
+
+/// Shifts sender sequence numbers by `offset` and, unless `one_way`, shifts the
+/// receiver's ACK numbers back.
+pub struct SeqOffset {
+    pub offset: u32,
+    pub one_way: bool,
+}
+
+impl Middlebox for SeqOffset {
+    fn name(&self) -> &'static str {
+        "seq_offset"
+    }
+
+    fn forward(&mut self, from: NodeId, packet: &mut Packet) -> Option<String> {
+        let header = &mut packet.header;
+        if self.offset == 0 {
+            return None;
+        }
+        match from {
+            NodeId::Sender => {
+                let seq = header.seq_num.wrapping_add(self.offset);
+                let change = format!("seq {}->{}", header.seq_num, seq);
+                header.seq_num = seq;
+                Some(change)
+            }
+            NodeId::Receiver if !self.one_way => {
+                let ack = header.ack_num.wrapping_sub(self.offset);
+                let change = format!("ack {}->{}", header.ack_num, ack);
+                header.ack_num = ack;
+                Some(change)
+            }
+            NodeId::Receiver => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tcp_lab_abstract::flags;
+
+    #[test]
+    fn test_builtin_rewrites() {
+        let mut nat = from_config(MiddleboxConfig::Nat {
+            external_port: 40000,
+        });
+        let mut out = Packet::new_simple(0, 0, 0, vec![1]);
+        out.header.src_port = 5000;
+        assert_eq!(
+            nat.forward(NodeId::Sender, &mut out).as_deref(),
+            Some("src_port 5000->40000")
+        );
+        let mut back = Packet::new_ack(0, 1, 0);
+        back.header.dst_port = 40000;
+        nat.forward(NodeId::Receiver, &mut back);
+        assert_eq!(back.header.dst_port, 5000);
+
+        let mut firewall = from_config(MiddleboxConfig::SeqOffset {
+            offset: 1000,
+            one_way: true,
+        });
+        let mut data = Packet::new_simple(1, 0, 0, vec![1]);
+        firewall.forward(NodeId::Sender, &mut data);
+        assert_eq!(data.header.seq_num, 1001);
+        let mut ack = Packet::new_simple(0, 1001, flags::ACK, Vec::new());
+        assert_eq!(firewall.forward(NodeId::Receiver, &mut ack), None);
+        assert_eq!(ack.header.ack_num, 1001);
+    }
+}
@@ -0,0 +1,53 @@
+//! The fastest any protocol could finish a run, as a target to measure against.
+//!
+//! The bound gives every segment the link's minimum latency and loses none. Each
+//! `app_send` leaves as soon as it happens, cut into `mtu`-sized segments, and with a
+//! `window_size` at most that many segments are outstanding per round trip. Random and
+//! injected losses are left out, so on a lossy link the gap to it also shows what
+//! recovery cost.
+
+use tcp_lab_abstract::SimConfig;
+
+/// When the last byte of `sends` (each `(time, bytes)`, in time order) could reach the
+/// receiver at the earliest, or `None` if nothing was sent.
+pub fn optimal_completion(sends: &[(u64, usize)], config: &SimConfig) -> Option<u64> {
+    let one_way = config.min_latency;
+    let window = config.window_size.map(|w| w.max(1) as usize);
+    let mut departures: Vec<u64> = Vec::new();
+    for &(time, bytes) in sends {
+        for _ in 0..bytes.div_ceil(config.mtu.max(1)) {
+            let mut departure = time;
+            // The segment `window` places back must have been acknowledged first.
+            if let Some(window) = window
+                && let Some(earlier) = departures.len().checked_sub(window)
+            {
+                departure = departure.max(departures[earlier] + 2 * one_way);
+            }
+            departures.push(departure);
+        }
+    }
+    departures.last().map(|departure| departure + one_way)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_limits_rounds() {
+        let config = SimConfig {
+            min_latency: 50,
+            mtu: 100,
+            window_size: Some(2),
+            ..Default::default()
+        };
+        // Five segments in windows of two: rounds leave at 0, 100 and 200 ms.
+        assert_eq!(optimal_completion(&[(0, 500)], &config), Some(250));
+        // A late send is bound by its own time instead.
+        assert_eq!(
+            optimal_completion(&[(0, 100), (1000, 1)], &config),
+            Some(1050)
+        );
+        assert_eq!(optimal_completion(&[], &config), None);
+    }
+}
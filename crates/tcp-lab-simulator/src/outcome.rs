@@ -0,0 +1,93 @@
+//! How one scenario of a graded run went, as `sim-cli suite --json-out` and
+//! `eval-host --json-out` write it and the grading daemon and `watch` read it back.
+
+use serde::{Deserialize, Serialize};
+use tcp_lab_abstract::TestScenario;
+
+use crate::error::ExitStatus;
+use crate::fingerprint::Fingerprint;
+use crate::gradebook::DEFAULT_TRACK;
+use crate::scenario_runner::{AssertionOutcome, ScenarioResult, SeedDerivation};
+
+/// How one scenario went.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScenarioOutcome {
+    /// File stem of the scenario, e.g. `test_rdt3`.
+    pub scenario: String,
+    /// The scenario's gradebook track.
+    #[serde(default = "default_track")]
+    pub track: String,
+    /// What the scenario grades, from its `rubric`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rubric: Option<String>,
+    /// How the seed was derived under `--student-id`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<SeedDerivation>,
+    /// The grader's error, `None` when every assertion held.
+    pub error: Option<String>,
+    /// The process exit code this outcome alone would give; see `ExitStatus`.
+    pub exit_code: u8,
+    /// The scenario's time limit (ms).
+    #[serde(default)]
+    pub max_duration: u64,
+    /// How each assertion went and, for a pass, when it was met.
+    #[serde(default)]
+    pub assertions: Vec<AssertionOutcome>,
+    /// The scenario's `metric_bands` the run left, which do not fail it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// How the run behaved, with `--fingerprints`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<Fingerprint>,
+}
+
+fn default_track() -> String {
+    DEFAULT_TRACK.to_string()
+}
+
+impl ScenarioOutcome {
+    /// The outcome of running `scenario`, named `name`; with `fingerprint`, it also
+    /// carries the run's [`Fingerprint`].
+    pub fn new(
+        name: &str,
+        scenario: &TestScenario,
+        result: &ScenarioResult,
+        fingerprint: bool,
+    ) -> Self {
+        let failure = result.failures.first();
+        Self {
+            scenario: name.to_string(),
+            track: scenario.track.clone().unwrap_or_else(default_track),
+            rubric: scenario.rubric.clone(),
+            seed: result
+                .report
+                .as_ref()
+                .and_then(|report| report.seed_derivation.clone()),
+            error: failure.map(|err| format!("{err:#}")),
+            exit_code: failure
+                .map_or(ExitStatus::Pass, |err| err.exit_status())
+                .code(),
+            max_duration: scenario.max_duration(),
+            assertions: result
+                .report
+                .as_ref()
+                .map(|report| report.assertions.clone())
+                .unwrap_or_default(),
+            warnings: result
+                .report
+                .iter()
+                .flat_map(|report| &report.metric_band_warnings)
+                .map(|warning| warning.message())
+                .collect(),
+            fingerprint: result
+                .report
+                .as_ref()
+                .filter(|_| fingerprint)
+                .map(Fingerprint::of),
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+    }
+}
@@ -0,0 +1,234 @@
+//! A course progression: the lab stages in the order students work through them (e.g.
+//! RDT 1.0 through Reno), each a set of scenarios. A stage is complete once all of its
+//! scenarios pass; the first incomplete one is the student's next target and the ones
+//! after it stay locked until it is done.
+//!
+//! ```toml
+//! [[stage]]
+//! name = "RDT 2.0"
+//! description = "Checksums and NAKs over a corrupting channel"
+//! scenarios = ["suites/rdt2", "tests/test_rdt20.toml"]
+//! ```
+//!
+//! Scenario entries are files or suite directories, relative to the progression file.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, ensure};
+use serde::{Deserialize, Serialize};
+
+use crate::scenario_runner::scenario_files;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Progression {
+    #[serde(rename = "stage")]
+    pub stages: Vec<Stage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Stage {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Scenario files and suite directories; after `Progression::load`, only files.
+    pub scenarios: Vec<PathBuf>,
+}
+
+impl Progression {
+    /// Read the progression at `path`, resolving each stage's entries against its
+    /// directory and expanding suite directories into their scenario files.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read progression {}", path.display()))?;
+        let mut progression: Progression = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse progression {}", path.display()))?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        for stage in &mut progression.stages {
+            let mut files = Vec::new();
+            for entry in &stage.scenarios {
+                let entry = base.join(entry);
+                if entry.is_dir() {
+                    files.extend(scenario_files(&entry)?);
+                } else {
+                    files.push(entry);
+                }
+            }
+            ensure!(
+                !files.is_empty(),
+                "Stage {:?} of {} has no scenarios",
+                stage.name,
+                path.display()
+            );
+            stage.scenarios = files;
+        }
+        Ok(progression)
+    }
+
+    /// Where a student stands, given each scenario's last result.
+    pub fn progress(
+        &self,
+        mut status: impl FnMut(&Path) -> Result<ScenarioStatus>,
+    ) -> Result<Progress> {
+        let mut stages = Vec::new();
+        for stage in &self.stages {
+            let scenarios = stage
+                .scenarios
+                .iter()
+                .map(|path| Ok((scenario_name(path), status(path)?)))
+                .collect::<Result<_>>()?;
+            stages.push(StageProgress {
+                name: stage.name.clone(),
+                description: stage.description.clone(),
+                scenarios,
+            });
+        }
+        Ok(Progress { stages })
+    }
+}
+
+/// A scenario's last result for the implementation as it is now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScenarioStatus {
+    Passed,
+    Failed,
+    /// No result yet, or only for an older version of the implementation.
+    NotGraded,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StageProgress {
+    pub name: String,
+    pub description: Option<String>,
+    pub scenarios: Vec<(String, ScenarioStatus)>,
+}
+
+impl StageProgress {
+    pub fn passed(&self) -> usize {
+        self.scenarios
+            .iter()
+            .filter(|(_, status)| *status == ScenarioStatus::Passed)
+            .count()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.passed() == self.scenarios.len()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Progress {
+    pub stages: Vec<StageProgress>,
+}
+
+impl Progress {
+    /// The index of the first incomplete stage; `None` once every stage is complete.
+    pub fn current(&self) -> Option<usize> {
+        self.stages.iter().position(|stage| !stage.is_complete())
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let current = self.current().unwrap_or(self.stages.len());
+        for (i, stage) in self.stages.iter().enumerate() {
+            let state = match i.cmp(&current) {
+                std::cmp::Ordering::Less => "complete",
+                std::cmp::Ordering::Equal => "next",
+                std::cmp::Ordering::Greater => "locked",
+            };
+            writeln!(
+                f,
+                "[{state:>8}] {}: {}/{} passed",
+                stage.name,
+                stage.passed(),
+                stage.scenarios.len()
+            )?;
+        }
+        let Some(stage) = self.stages.get(current) else {
+            return write!(f, "All stages complete");
+        };
+        write!(f, "Next: {}", stage.name)?;
+        if let Some(description) = &stage.description {
+            write!(f, " ({description})")?;
+        }
+        for (name, status) in &stage.scenarios {
+            let status = match status {
+                ScenarioStatus::Passed => continue,
+                ScenarioStatus::Failed => "failing",
+                ScenarioStatus::NotGraded => "not graded",
+            };
+            write!(f, "\n  {name}: {status}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A scenario's name as `suite` prints it: its file stem.
+fn scenario_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_incomplete_stage_is_next() {
+        let dir = std::env::temp_dir().join(format!("tcp-lab-progress-{}", std::process::id()));
+        fs::create_dir_all(dir.join("rdt2")).unwrap();
+        for file in ["rdt1.toml", "rdt2/a.toml", "rdt2/b.toml", "reno.toml"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        fs::write(
+            dir.join("course.toml"),
+            r#"
+[[stage]]
+name = "RDT 1.0"
+scenarios = ["rdt1.toml"]
+
+[[stage]]
+name = "RDT 2.0"
+description = "Checksums and NAKs"
+scenarios = ["rdt2"]
+
+[[stage]]
+name = "Reno"
+scenarios = ["reno.toml"]
+"#,
+        )
+        .unwrap();
+        let progression = Progression::load(dir.join("course.toml")).unwrap();
+        assert_eq!(progression.stages[1].scenarios.len(), 2);
+
+        let progress = progression
+            .progress(|path| {
+                Ok(match scenario_name(path).as_str() {
+                    "rdt1" | "a" => ScenarioStatus::Passed,
+                    "b" => ScenarioStatus::Failed,
+                    _ => ScenarioStatus::NotGraded,
+                })
+            })
+            .unwrap();
+        assert_eq!(progress.current(), Some(1));
+        let text = progress.to_string();
+        assert!(text.contains("[complete] RDT 1.0: 1/1 passed"));
+        assert!(text.contains("[    next] RDT 2.0: 1/2 passed"));
+        assert!(text.contains("[  locked] Reno: 0/1 passed"));
+        assert!(text.ends_with("Next: RDT 2.0 (Checksums and NAKs)\n  b: failing"));
+
+        let done = progression
+            .progress(|_| Ok(ScenarioStatus::Passed))
+            .unwrap();
+        assert_eq!(done.current(), None);
+        assert!(done.to_string().ends_with("All stages complete"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
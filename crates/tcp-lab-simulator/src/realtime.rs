@@ -0,0 +1,97 @@
+//! Wall-clock paced execution.
+//!
+//! Instead of draining the event queue as fast as possible, each event is held
+//! back until its simulated timestamp is due on the wall clock, scaled by a
+//! configurable factor. Demos then unfold like a live network and frontends
+//! observing the simulator between steps see steady motion.
+
+use crate::engine::Simulator;
+use crate::live::LiveInput;
+use anyhow::{Result, ensure};
+use std::time::Duration;
+use tokio::time::{Instant, sleep, sleep_until};
+
+/// How often a live run checks for new input while no event is due.
+const LIVE_POLL: Duration = Duration::from_millis(10);
+
+/// Steps a simulator in sync with wall-clock time.
+pub struct Pacer {
+    time_scale: f64,
+    /// Wall-clock instant and simulated time of the first paced step.
+    origin: Option<(Instant, u64)>,
+}
+
+impl Pacer {
+    /// `time_scale` is simulated time per unit of real time: `10.0` plays ten
+    /// simulated seconds per second, `0.5` plays at half speed.
+    pub fn new(time_scale: f64) -> Result<Self> {
+        ensure!(
+            time_scale.is_finite() && time_scale > 0.0,
+            "time scale must be a positive number, got {}",
+            time_scale
+        );
+        Ok(Self {
+            time_scale,
+            origin: None,
+        })
+    }
+
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    /// The simulated time matching the wall clock now, never behind `sim`.
+    pub fn sim_time_now(&mut self, sim: &Simulator) -> u64 {
+        let (wall, sim_start) = self.origin(sim);
+        let elapsed = wall.elapsed().as_secs_f64() * 1000.0 * self.time_scale;
+        (sim_start + elapsed as u64).max(sim.current_time())
+    }
+
+    fn origin(&mut self, sim: &Simulator) -> (Instant, u64) {
+        *self
+            .origin
+            .get_or_insert_with(|| (Instant::now(), sim.current_time()))
+    }
+
+    /// Wait until the next event is due, then process it.
+    /// Returns false once the event queue is empty.
+    pub async fn step(&mut self, sim: &mut Simulator) -> bool {
+        let Some(next) = sim.peek_next_event_time() else {
+            return false;
+        };
+        let (wall, sim_start) = self.origin(sim);
+        let elapsed_ms = next.saturating_sub(sim_start) as f64 / self.time_scale;
+        sleep_until(wall + Duration::from_secs_f64(elapsed_ms / 1000.0)).await;
+        sim.step()
+    }
+}
+
+/// Initialize `sim` and run it to completion at `time_scale` times real time.
+pub async fn run_paced(sim: &mut Simulator, time_scale: f64) -> Result<()> {
+    let mut pacer = Pacer::new(time_scale)?;
+    sim.init();
+    while pacer.step(sim).await {}
+    Ok(())
+}
+
+/// Like [`run_paced`], but each message from `input` is sent at the simulated
+/// time it arrives. Runs until the input has closed and the queue is empty.
+pub async fn run_paced_live(
+    sim: &mut Simulator,
+    time_scale: f64,
+    input: &mut LiveInput,
+) -> Result<()> {
+    let mut pacer = Pacer::new(time_scale)?;
+    sim.init();
+    loop {
+        let now = pacer.sim_time_now(sim);
+        input.feed(sim, now);
+        match sim.peek_next_event_time() {
+            Some(next) if next <= now => {
+                sim.step();
+            }
+            None if !input.is_open() => return Ok(()),
+            _ => sleep(LIVE_POLL).await,
+        }
+    }
+}
@@ -0,0 +1,7 @@
+//! Presentation helpers for finished runs.
+
+pub mod diagram;
+pub mod series;
+pub mod sweep;
+pub mod table;
+pub mod time_sequence;
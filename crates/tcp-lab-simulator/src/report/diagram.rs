@@ -0,0 +1,308 @@
+//! The link space-time diagram as plain ASCII, one row per link event, for terminals where
+//! the TUI cannot run. Time runs down the page; packets cross between the two node columns.
+
+use crate::engine::{LinkDirection, LinkEventKind, LinkEventSummary};
+
+/// Characters between the Sender and Receiver columns.
+const LANE: usize = 44;
+/// Where on the lane a lost packet ends.
+const DROP_AT: usize = LANE * 2 / 3;
+/// Where on the lane a corrupted packet is marked.
+const CORRUPT_AT: usize = LANE * 3 / 4;
+
+/// Renders `events` (e.g. `SimulationReport::link_events`) as an aligned text diagram:
+///
+/// ```text
+///  time ms  Sender                                       Receiver
+///        0  |-- seq=0 ack=0 ---------------------------->|  arrives after 45 ms
+///       12  |-- seq=5 ack=0 -------------X               |  lost (random loss)
+///       45  |                                            *  DELIVERED 5 bytes to application
+///       45  |<---------------------------- seq=0 ack=5 --|  arrives after 20 ms
+/// ```
+///
+/// A corruption is merged into the send it belongs to and marked `~` on the lane.
+pub fn space_time(events: &[LinkEventSummary]) -> String {
+    let mut out = format!(
+        "{:>8}  {:<width$}{}\n",
+        "time ms",
+        "Sender",
+        "Receiver",
+        width = LANE + 1
+    );
+    let mut corrupted: Option<&LinkEventSummary> = None;
+    for event in events {
+        if matches!(
+            event.kind,
+            LinkEventKind::Corrupt | LinkEventKind::InjectedCorrupt
+        ) {
+            if let Some(pending) = corrupted.replace(event) {
+                out.push_str(&row(pending.time, &corrupt_lane(pending), "corrupted"));
+            }
+            continue;
+        }
+        let merged = corrupted.take_if(|c| {
+            matches!(event.kind, LinkEventKind::Send | LinkEventKind::Probe)
+                && c.time == event.time
+                && c.direction() == event.direction()
+        });
+        if let Some(pending) = corrupted.take() {
+            out.push_str(&row(pending.time, &corrupt_lane(pending), "corrupted"));
+        }
+        let (lane, note) = match event.kind {
+            LinkEventKind::Send | LinkEventKind::Probe => {
+                let mut lane = arrow(event, LANE);
+                let mut note = match event.latency() {
+                    Some(ms) => format!("arrives after {ms} ms"),
+                    None => String::new(),
+                };
+                if event.kind == LinkEventKind::Probe {
+                    note.insert_str(0, "window probe, ");
+                }
+                if merged.is_some() {
+                    mark(&mut lane, event.direction(), CORRUPT_AT, '~');
+                    note.insert_str(0, "corrupted, ");
+                }
+                (lane, note)
+            }
+            LinkEventKind::Drop
+            | LinkEventKind::InjectedDrop
+            | LinkEventKind::Policed
+            | LinkEventKind::QueueDrop => {
+                let mut lane = arrow(event, DROP_AT);
+                mark(&mut lane, event.direction(), DROP_AT, 'X');
+                let cause = match event.kind {
+                    LinkEventKind::Drop => "random loss",
+                    LinkEventKind::Policed => "policed",
+                    LinkEventKind::QueueDrop => "queue",
+                    _ => "injected",
+                };
+                (lane, format!("lost ({cause})"))
+            }
+            _ => node_note(event),
+        };
+        out.push_str(&row(event.time, &lane, &note));
+    }
+    if let Some(pending) = corrupted {
+        out.push_str(&row(pending.time, &corrupt_lane(pending), "corrupted"));
+    }
+    out
+}
+
+/// The time of the first link event where two runs of the same scenario part ways, e.g. a
+/// student's run and the reference. Only events before `settled` count, since a run still
+/// in progress may not have logged all of its events at later times yet.
+pub fn first_divergence(
+    ours: &[LinkEventSummary],
+    theirs: &[LinkEventSummary],
+    settled: u64,
+) -> Option<u64> {
+    let settled = |events: &[LinkEventSummary]| {
+        events
+            .iter()
+            .position(|e| e.time >= settled)
+            .unwrap_or(events.len())
+    };
+    let (ours, theirs) = (&ours[..settled(ours)], &theirs[..settled(theirs)]);
+    ours.iter()
+        .zip(theirs)
+        .find(|(a, b)| a.time != b.time || a.kind != b.kind || a.description != b.description)
+        .map(|(a, b)| a.time.min(b.time))
+        .or_else(|| {
+            let longer = if ours.len() > theirs.len() {
+                ours
+            } else {
+                theirs
+            };
+            longer.get(ours.len().min(theirs.len())).map(|e| e.time)
+        })
+}
+
+fn row(time: u64, lane: &str, note: &str) -> String {
+    format!("{time:>8}  {lane}  {note}").trim_end().to_string() + "\n"
+}
+
+/// `|`, the lane, `|`, with an arrow from the sending column that stops after `len` cells.
+fn arrow(event: &LinkEventSummary, len: usize) -> String {
+    let back = event.direction() == LinkDirection::ReceiverToSender;
+    let label: Vec<char> = format!(" {} ", header_label(event)).chars().collect();
+    let mut cells = vec![' '; LANE];
+    // Index of the cell `i` cells away from the sending column.
+    let from_sender = |i: usize| if back { LANE - 1 - i } else { i };
+    for i in 0..len {
+        cells[from_sender(i)] = '-';
+    }
+    if len == LANE {
+        cells[from_sender(LANE - 1)] = if back { '<' } else { '>' };
+    }
+    if label.len() + 4 <= len {
+        let start = if back { LANE - 2 - label.len() } else { 2 };
+        cells[start..start + label.len()].copy_from_slice(&label);
+    }
+    format!("|{}|", cells.into_iter().collect::<String>())
+}
+
+fn header_label(event: &LinkEventSummary) -> String {
+    event
+        .packet
+        .map(|packet| format!("seq={} ack={}", packet.seq, packet.ack))
+        .unwrap_or_default()
+}
+
+/// Overwrite the lane cell `at` cells from the sending column with `ch`.
+fn mark(lane: &mut String, direction: LinkDirection, at: usize, ch: char) {
+    let mut cells: Vec<char> = lane.chars().collect();
+    let at = at.clamp(1, LANE);
+    let index = if direction == LinkDirection::ReceiverToSender {
+        LANE + 1 - at
+    } else {
+        at
+    };
+    cells[index] = ch;
+    *lane = cells.into_iter().collect();
+}
+
+/// A corruption whose send was not recorded (e.g. past `link_event_limit`).
+fn corrupt_lane(event: &LinkEventSummary) -> String {
+    let mut lane = arrow(event, CORRUPT_AT);
+    mark(&mut lane, event.direction(), CORRUPT_AT, '~');
+    lane
+}
+
+/// Events at one node (deliveries, restarts, injected packets...) or the whole run (notes): a `*`
+/// on the node's column and the description without its `[Node]` prefix.
+fn node_note(event: &LinkEventSummary) -> (String, String) {
+    let blank = " ".repeat(LANE);
+    let description = event.description.as_str();
+    let (lane, text) = if let Some(text) = description
+        .strip_prefix("[Sender] ")
+        .or_else(|| description.strip_prefix("[Host->Sender] "))
+    {
+        (format!("*{blank}|"), text)
+    } else if let Some(text) = description
+        .strip_prefix("[Receiver] ")
+        .or_else(|| description.strip_prefix("[Host->Receiver] "))
+    {
+        (format!("|{blank}*"), text)
+    } else {
+        (format!("|{blank}|"), description)
+    };
+    (lane, text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{first_divergence, space_time};
+    use crate::engine::{LinkEventKind, LinkEventSummary, LinkPacket, NodeId};
+
+    fn event(time: u64, kind: LinkEventKind, description: &str) -> LinkEventSummary {
+        LinkEventSummary {
+            time,
+            kind,
+            description: description.to_string(),
+            packet: None,
+        }
+    }
+
+    /// A link event about the packet `from` sent, arriving after `latency` ms for a send.
+    fn channel(
+        time: u64,
+        kind: LinkEventKind,
+        (from, seq, ack): (NodeId, u32, u32),
+        latency: Option<u64>,
+        description: &str,
+    ) -> LinkEventSummary {
+        LinkEventSummary {
+            packet: Some(LinkPacket {
+                from,
+                seq,
+                ack,
+                latency,
+            }),
+            ..event(time, kind, description)
+        }
+    }
+
+    #[test]
+    fn draws_aligned_rows() {
+        let events = [
+            channel(
+                0,
+                LinkEventKind::Send,
+                (NodeId::Sender, 0, 0),
+                Some(45),
+                "[Sender->Receiver] SEND seq=0 ack=0 (latency=45ms)",
+            ),
+            channel(
+                12,
+                LinkEventKind::Drop,
+                (NodeId::Sender, 5, 0),
+                None,
+                "[Sender->Receiver] DROP (random loss) seq=5 ack=0",
+            ),
+            channel(
+                20,
+                LinkEventKind::Corrupt,
+                (NodeId::Sender, 5, 0),
+                None,
+                "[Sender->Receiver] CORRUPT seq=5 ack=0",
+            ),
+            channel(
+                20,
+                LinkEventKind::Send,
+                (NodeId::Sender, 5, 0),
+                Some(30),
+                "[Sender->Receiver] SEND seq=5 ack=0 (latency=30ms)",
+            ),
+            event(
+                45,
+                LinkEventKind::Deliver,
+                "[Receiver] DELIVERED 5 bytes to application",
+            ),
+            channel(
+                45,
+                LinkEventKind::Send,
+                (NodeId::Receiver, 0, 5),
+                Some(20),
+                "[Receiver->Sender] SEND seq=0 ack=5 (latency=20ms)",
+            ),
+            event(60, LinkEventKind::Annotation, "NOTE watch the ACK"),
+        ];
+        let diagram = space_time(&events);
+        let lines: Vec<&str> = diagram.lines().collect();
+        assert_eq!(lines.len(), 7, "{diagram}");
+        // Both node columns line up on every row.
+        let sender_col = lines[1].find('|').unwrap();
+        let receiver_col = sender_col + super::LANE + 1;
+        for line in &lines[1..] {
+            let cells: Vec<char> = line.chars().collect();
+            assert!(matches!(cells[sender_col], '|' | '*'), "{line}");
+            assert!(matches!(cells[receiver_col], '|' | '*'), "{line}");
+        }
+        assert_eq!(lines[0].find("Receiver"), Some(receiver_col));
+        assert!(lines[1].contains("seq=0 ack=0") && lines[1].contains(">|  arrives after 45 ms"));
+        assert!(lines[2].contains('X') && lines[2].ends_with("lost (random loss)"));
+        assert!(lines[3].contains('~') && lines[3].contains("corrupted, arrives after 30 ms"));
+        assert!(lines[4].ends_with("*  DELIVERED 5 bytes to application"));
+        assert!(lines[5].contains("|<") && lines[5].contains(" seq=0 ack=5 "));
+        assert!(lines[6].ends_with("NOTE watch the ACK"));
+    }
+
+    #[test]
+    fn finds_first_divergence() {
+        let send = |time, seq| {
+            channel(
+                time,
+                LinkEventKind::Send,
+                (NodeId::Sender, seq, 0),
+                Some(10),
+                &format!("[Sender->Receiver] SEND seq={seq} ack=0 (latency=10ms)"),
+            )
+        };
+        let ours = [send(0, 0), send(10, 1), send(20, 3)];
+        let theirs = [send(0, 0), send(10, 1), send(20, 2), send(30, 3)];
+        assert_eq!(first_divergence(&ours, &theirs, u64::MAX), Some(20));
+        // Events not yet settled in both runs are not compared.
+        assert_eq!(first_divergence(&ours, &theirs, 20), None);
+        assert_eq!(first_divergence(&ours[..2], &theirs, u64::MAX), Some(20));
+    }
+}
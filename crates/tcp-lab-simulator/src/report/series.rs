@@ -0,0 +1,53 @@
+//! Downsampling of metric series for charts. Long runs record far more samples than a chart
+//! has pixels; the raw series (and the trace file) keep every sample.
+
+use std::borrow::Cow;
+
+/// At most `max_points` samples of `series` for plotting. Samples are split into equal
+/// strides, and each stride keeps its lowest and highest sample in time order, so spikes
+/// such as a cwnd collapse survive. The last sample is always kept.
+pub fn downsample(series: &[(u64, f64)], max_points: usize) -> Cow<'_, [(u64, f64)]> {
+    if series.len() <= max_points || max_points < 3 {
+        return Cow::Borrowed(series);
+    }
+    // Two points per stride, one slot reserved for the last sample.
+    let strides = (max_points - 1) / 2;
+    let stride = (series.len() - 1).div_ceil(strides);
+    let mut points = Vec::with_capacity(max_points);
+    for chunk in series[..series.len() - 1].chunks(stride) {
+        let (mut lo, mut hi) = (0, 0);
+        for (i, (_, v)) in chunk.iter().enumerate() {
+            if *v < chunk[lo].1 {
+                lo = i;
+            }
+            if *v > chunk[hi].1 {
+                hi = i;
+            }
+        }
+        points.push(chunk[lo.min(hi)]);
+        if lo != hi {
+            points.push(chunk[lo.max(hi)]);
+        }
+    }
+    points.push(series[series.len() - 1]);
+    Cow::Owned(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::downsample;
+
+    #[test]
+    fn keeps_extremes_and_last_sample() {
+        let mut series: Vec<(u64, f64)> = (0..1000).map(|t| (t, 10.0)).collect();
+        series[500].1 = 1.0;
+        series[700].1 = 50.0;
+        let points = downsample(&series, 21);
+        assert!(points.len() <= 21);
+        assert!(points.contains(&(500, 1.0)));
+        assert!(points.contains(&(700, 50.0)));
+        assert_eq!(points.last(), Some(&(999, 10.0)));
+        assert!(points.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(downsample(&series[..20], 21).len(), 20);
+    }
+}
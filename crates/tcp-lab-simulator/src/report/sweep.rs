@@ -0,0 +1,370 @@
+//! Statistics over the same run repeated with different seeds, and over a config setting
+//! swept across several values, for the quantitative part of a lab report.
+
+use std::fmt::Write;
+
+use serde::Serialize;
+
+use crate::SimulationReport;
+use crate::report::table::Table;
+
+/// Spread of one aggregate across the runs of a sweep. `stddev` is the sample
+/// standard deviation (0 for a single run).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Spread {
+    pub mean: f64,
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Spread {
+    /// `None` if `values` is empty.
+    pub fn of(values: &[f64]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let variance = match values.len() {
+            1 => 0.0,
+            _ => values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0),
+        };
+        Some(Self {
+            mean,
+            stddev: variance.sqrt(),
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        })
+    }
+}
+
+/// Key aggregates of one seed's run.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SeedRun {
+    pub seed: u64,
+    pub completion_ms: u64,
+    pub retransmissions: u64,
+    pub goodput_bps: f64,
+}
+
+impl SeedRun {
+    pub fn from_report(seed: u64, report: &SimulationReport) -> Self {
+        Self {
+            seed,
+            completion_ms: report.duration_ms,
+            retransmissions: report.summary().retransmissions,
+            goodput_bps: report.goodput_bps(),
+        }
+    }
+}
+
+/// Every run of a seed sweep plus the spread of each aggregate over them.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SweepSummary {
+    pub runs: Vec<SeedRun>,
+    pub completion_ms: Spread,
+    pub retransmissions: Spread,
+    pub goodput_bps: Spread,
+}
+
+impl SweepSummary {
+    /// `None` if there are no runs.
+    pub fn new(runs: Vec<SeedRun>) -> Option<Self> {
+        let spread =
+            |value: fn(&SeedRun) -> f64| Spread::of(&runs.iter().map(value).collect::<Vec<_>>());
+        Some(Self {
+            completion_ms: spread(|run| run.completion_ms as f64)?,
+            retransmissions: spread(|run| run.retransmissions as f64)?,
+            goodput_bps: spread(|run| run.goodput_bps)?,
+            runs,
+        })
+    }
+
+    /// One row per aggregate with its mean, standard deviation, minimum and maximum.
+    pub fn table(&self) -> Table {
+        let mut table = Table::new(["metric", "mean", "stddev", "min", "max"]);
+        for (name, spread) in [
+            ("time(ms)", self.completion_ms),
+            ("retransmits", self.retransmissions),
+            ("goodput(bit/s)", self.goodput_bps),
+        ] {
+            table.push_row([
+                name.to_string(),
+                format!("{:.1}", spread.mean),
+                format!("{:.1}", spread.stddev),
+                format!("{:.0}", spread.min),
+                format!("{:.0}", spread.max),
+            ]);
+        }
+        table
+    }
+}
+
+/// The seed sweep run at one value of a swept setting.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ParamPoint {
+    /// The value as given, e.g. `0.05`.
+    pub value: String,
+    pub summary: SweepSummary,
+}
+
+/// A config setting swept across several values.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ParamSweep {
+    pub param: String,
+    pub points: Vec<ParamPoint>,
+}
+
+/// Width and height of one chart panel in the SVG, and its margin for the axes.
+const PANEL: (f64, f64) = (420.0, 280.0);
+const MARGIN: f64 = 50.0;
+
+impl ParamSweep {
+    /// One row per value with the mean goodput, retransmissions and completion time.
+    pub fn table(&self) -> Table {
+        let mut table = Table::new([
+            self.param.as_str(),
+            "runs",
+            "goodput(bit/s)",
+            "retransmits",
+            "time(ms)",
+        ]);
+        for point in &self.points {
+            let summary = &point.summary;
+            table.push_row([
+                point.value.clone(),
+                summary.runs.len().to_string(),
+                format!("{:.0}", summary.goodput_bps.mean),
+                format!("{:.1}", summary.retransmissions.mean),
+                format!("{:.1}", summary.completion_ms.mean),
+            ]);
+        }
+        table
+    }
+
+    /// One line per value with the mean and standard deviation of each aggregate.
+    pub fn csv(&self) -> String {
+        let mut out = format!(
+            "{},runs,goodput_bps_mean,goodput_bps_stddev,retransmissions_mean,\
+             retransmissions_stddev,completion_ms_mean,completion_ms_stddev\n",
+            csv_field(&self.param)
+        );
+        for point in &self.points {
+            let summary = &point.summary;
+            let _ = writeln!(
+                out,
+                "{},{},{},{},{},{},{},{}",
+                csv_field(&point.value),
+                summary.runs.len(),
+                summary.goodput_bps.mean,
+                summary.goodput_bps.stddev,
+                summary.retransmissions.mean,
+                summary.retransmissions.stddev,
+                summary.completion_ms.mean,
+                summary.completion_ms.stddev
+            );
+        }
+        out
+    }
+
+    /// Goodput and retransmissions against the swept values, side by side, with a bar of
+    /// one standard deviation either way when a value ran several seeds. Numeric values
+    /// are placed to scale; anything else is spaced evenly in the given order.
+    pub fn svg(&self) -> String {
+        let (width, height) = (PANEL.0 * 2.0, PANEL.1);
+        let mut out = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+             font-family=\"sans-serif\" font-size=\"11\">\n\
+             <rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n"
+        );
+        let xs = self.positions();
+        for (panel, (title, spread)) in [
+            (
+                "goodput (bit/s)",
+                (|s: &SweepSummary| s.goodput_bps) as fn(&SweepSummary) -> Spread,
+            ),
+            ("retransmissions", |s: &SweepSummary| s.retransmissions),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let spreads: Vec<Spread> = self.points.iter().map(|p| spread(&p.summary)).collect();
+            self.panel(&mut out, panel as f64 * PANEL.0, title, &xs, &spreads);
+        }
+        out.push_str("</svg>\n");
+        out
+    }
+
+    /// Where each value goes on a 0-1 axis.
+    fn positions(&self) -> Vec<f64> {
+        let numeric: Option<Vec<f64>> = self.points.iter().map(|p| p.value.parse().ok()).collect();
+        let evenly = |i: usize| match self.points.len() {
+            1 => 0.5,
+            n => i as f64 / (n - 1) as f64,
+        };
+        match numeric {
+            Some(values) => {
+                let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+                let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                values
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| match max - min {
+                        span if span > 0.0 => (v - min) / span,
+                        _ => evenly(i),
+                    })
+                    .collect()
+            }
+            None => (0..self.points.len()).map(evenly).collect(),
+        }
+    }
+
+    fn panel(&self, out: &mut String, left: f64, title: &str, xs: &[f64], spreads: &[Spread]) {
+        let (x0, x1) = (left + MARGIN, left + PANEL.0 - MARGIN / 2.0);
+        let (y0, y1) = (PANEL.1 - MARGIN, MARGIN / 2.0);
+        let top = spreads
+            .iter()
+            .map(|s| s.mean + s.stddev)
+            .fold(0.0, f64::max)
+            .max(1.0)
+            * 1.1;
+        let x = |at: f64| x0 + at * (x1 - x0);
+        let y = |value: f64| y0 - value / top * (y0 - y1);
+        let _ = writeln!(
+            out,
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\" font-size=\"13\">{}</text>",
+            (x0 + x1) / 2.0,
+            y1 - 8.0,
+            title
+        );
+        let _ = writeln!(
+            out,
+            "<polyline points=\"{x0},{y1} {x0},{y0} {x1},{y0}\" fill=\"none\" stroke=\"black\"/>"
+        );
+        for tick in 0..=4 {
+            let value = top * tick as f64 / 4.0;
+            let _ = writeln!(
+                out,
+                "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>",
+                x0 - 4.0,
+                y(value) + 4.0,
+                short(value)
+            );
+        }
+        for (point, &at) in self.points.iter().zip(xs) {
+            let _ = writeln!(
+                out,
+                "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+                x(at),
+                y0 + 14.0,
+                escape(&point.value)
+            );
+        }
+        let _ = writeln!(
+            out,
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+            (x0 + x1) / 2.0,
+            y0 + 32.0,
+            escape(&self.param)
+        );
+        let line: Vec<String> = xs
+            .iter()
+            .zip(spreads)
+            .map(|(&at, s)| format!("{},{}", x(at), y(s.mean)))
+            .collect();
+        let _ = writeln!(
+            out,
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"steelblue\" stroke-width=\"2\"/>",
+            line.join(" ")
+        );
+        for (&at, s) in xs.iter().zip(spreads) {
+            if s.stddev > 0.0 {
+                let _ = writeln!(
+                    out,
+                    "<line x1=\"{0}\" y1=\"{1}\" x2=\"{0}\" y2=\"{2}\" stroke=\"steelblue\"/>",
+                    x(at),
+                    y(s.mean - s.stddev),
+                    y(s.mean + s.stddev)
+                );
+            }
+            let _ = writeln!(
+                out,
+                "<circle cx=\"{}\" cy=\"{}\" r=\"3\" fill=\"steelblue\"/>",
+                x(at),
+                y(s.mean)
+            );
+        }
+    }
+}
+
+/// `field` quoted for CSV if it needs to be.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// An axis label, e.g. `1.5k` or `2M`.
+fn short(value: f64) -> String {
+    match value {
+        v if v >= 1e6 => format!("{:.1}M", v / 1e6),
+        v if v >= 1e3 => format!("{:.1}k", v / 1e3),
+        v => format!("{v:.1}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ParamPoint, ParamSweep, SeedRun, Spread, SweepSummary};
+
+    #[test]
+    fn spread_uses_sample_stddev() {
+        let spread = Spread::of(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]).unwrap();
+        assert_eq!((spread.mean, spread.min, spread.max), (5.0, 2.0, 9.0));
+        assert!((spread.stddev - (32.0f64 / 7.0).sqrt()).abs() < 1e-12);
+        assert_eq!(Spread::of(&[3.0]).unwrap().stddev, 0.0);
+        assert!(Spread::of(&[]).is_none());
+    }
+
+    #[test]
+    fn param_sweep_csv_and_svg() {
+        let point = |value: &str, goodputs: &[f64]| ParamPoint {
+            value: value.to_string(),
+            summary: SweepSummary::new(
+                goodputs
+                    .iter()
+                    .enumerate()
+                    .map(|(seed, &goodput_bps)| SeedRun {
+                        seed: seed as u64,
+                        completion_ms: 1000,
+                        retransmissions: 2,
+                        goodput_bps,
+                    })
+                    .collect(),
+            )
+            .unwrap(),
+        };
+        let sweep = ParamSweep {
+            param: "loss_rate".to_string(),
+            points: vec![point("0", &[800.0]), point("0.1", &[400.0, 600.0])],
+        };
+        let csv = sweep.csv();
+        let lines: Vec<_> = csv.lines().collect();
+        assert!(lines[0].starts_with("loss_rate,runs,goodput_bps_mean,"));
+        assert_eq!(lines[1], "0,1,800,0,2,0,1000,0");
+        assert!(lines[2].starts_with("0.1,2,500,141.42"), "{csv}");
+        let svg = sweep.svg();
+        assert!(svg.starts_with("<svg") && svg.trim_end().ends_with("</svg>"));
+        // A bar only where the seeds disagree: goodput at 0.1, not its retransmissions.
+        assert_eq!(svg.matches("<line ").count(), 1);
+    }
+}
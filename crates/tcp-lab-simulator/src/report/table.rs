@@ -0,0 +1,143 @@
+//! Aligned plain-text tables of run aggregates, for comparing implementations side by side.
+
+use serde::Serialize;
+use std::fmt;
+
+use crate::SimulationReport;
+
+/// Key aggregates of one run; one row of a comparison table.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RunRow {
+    pub label: String,
+    pub completion_ms: u64,
+    pub packets_sent: u64,
+    pub retransmissions: u64,
+    pub goodput_bps: f64,
+    /// Largest `cwnd` metric the sender reported, if it reported any.
+    pub max_cwnd: Option<f64>,
+    /// See `ReportSummary::efficiency_vs_optimal`.
+    pub efficiency_vs_optimal: Option<f64>,
+}
+
+impl RunRow {
+    pub fn from_report(label: impl Into<String>, report: &SimulationReport) -> Self {
+        let summary = report.summary();
+        Self {
+            label: label.into(),
+            completion_ms: report.duration_ms,
+            packets_sent: summary.sender_packets,
+            retransmissions: summary.retransmissions,
+            goodput_bps: report.goodput_bps(),
+            max_cwnd: report
+                .metrics
+                .get("cwnd")
+                .and_then(|series| series.iter().map(|(_, v)| *v).reduce(f64::max)),
+            efficiency_vs_optimal: summary.efficiency_vs_optimal,
+        }
+    }
+}
+
+/// Renders `rows` with the standard comparison columns.
+pub fn comparison(rows: &[RunRow]) -> Table {
+    let mut table = Table::new([
+        "implementation",
+        "time(ms)",
+        "packets",
+        "retransmits",
+        "goodput(bit/s)",
+        "max cwnd",
+        "vs optimal",
+    ]);
+    for row in rows {
+        table.push_row([
+            row.label.clone(),
+            row.completion_ms.to_string(),
+            row.packets_sent.to_string(),
+            row.retransmissions.to_string(),
+            format!("{:.0}", row.goodput_bps),
+            row.max_cwnd
+                .map_or_else(|| "-".to_string(), |c| format!("{c:.1}")),
+            row.efficiency_vs_optimal
+                .map_or_else(|| "-".to_string(), |e| format!("{:.0}%", e * 100.0)),
+        ]);
+    }
+    table
+}
+
+/// A text table whose first column is left-aligned and the rest right-aligned.
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new<I, S>(headers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            headers: headers.into_iter().map(Into::into).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Appends a row; missing cells render empty and extra cells are dropped.
+    pub fn push_row<I, S>(&mut self, cells: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut row: Vec<String> = cells.into_iter().map(Into::into).collect();
+        row.resize(self.headers.len(), String::new());
+        self.rows.push(row);
+    }
+
+    fn widths(&self) -> Vec<usize> {
+        self.headers
+            .iter()
+            .enumerate()
+            .map(|(col, header)| {
+                self.rows
+                    .iter()
+                    .map(|row| row[col].chars().count())
+                    .fold(header.chars().count(), usize::max)
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let widths = self.widths();
+        for row in std::iter::once(&self.headers).chain(&self.rows) {
+            let mut line = String::new();
+            for (col, (cell, width)) in row.iter().zip(&widths).enumerate() {
+                if col == 0 {
+                    line.push_str(&format!("{cell:<width$}"));
+                } else {
+                    line.push_str(&format!("  {cell:>width$}"));
+                }
+            }
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Table;
+
+    #[test]
+    fn aligns_columns() {
+        let mut table = Table::new(["name", "packets"]);
+        table.push_row(["reno", "12"]);
+        table.push_row(["tahoe-variant", "7"]);
+        assert_eq!(
+            table.to_string(),
+            "name           packets\nreno                12\ntahoe-variant        7\n"
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! The time-sequence graph known from tcptrace, built from the packet log. It shows which
+//! sequence range the sender put on the wire and when, which of those segments were
+//! retransmissions or lost, and how far the ACKs reaching the sender had advanced.
+
+use std::collections::HashSet;
+
+use crate::engine::{NodeId, PacketEvent, PacketEventKind};
+
+/// One data segment the sender sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeqSegment {
+    pub time: u64,
+    pub seq: u32,
+    /// One past the last sequence number covered: `seq + len` when the protocol numbers
+    /// bytes, `seq + 1` when it numbers segments.
+    pub end: u32,
+    /// The sender had sent this `seq` before.
+    pub retransmit: bool,
+    /// The channel dropped or corrupted it.
+    pub lost: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TimeSequence {
+    pub segments: Vec<SeqSegment>,
+    /// `(time, ack)` of every packet that reached the sender.
+    pub acks: Vec<(u64, u32)>,
+}
+
+impl TimeSequence {
+    /// Builds the graph from `packets` (e.g. `Simulator::packet_log`). Sequence numbers are
+    /// taken as byte offsets when every new segment starts at or past the end of the one
+    /// before it, and as segment numbers otherwise. Alternating-bit protocols reuse 0 and 1,
+    /// so from the third segment on everything they send counts as a retransmission.
+    pub fn from_packets(packets: &[PacketEvent]) -> Self {
+        let mut graph = Self::default();
+        let mut seen = HashSet::new();
+        let mut byte_numbered = true;
+        let mut next_new: Option<u32> = None;
+        for packet in packets {
+            match (packet.kind, packet.node) {
+                (PacketEventKind::Sent, NodeId::Sender) if packet.len > 0 => {
+                    let retransmit = !seen.insert(packet.seq);
+                    let end = packet.seq.saturating_add(packet.len as u32);
+                    if !retransmit {
+                        byte_numbered &= next_new.is_none_or(|next| packet.seq >= next);
+                        next_new = Some(end);
+                    }
+                    graph.segments.push(SeqSegment {
+                        time: packet.time,
+                        seq: packet.seq,
+                        end,
+                        retransmit,
+                        lost: false,
+                    });
+                }
+                (PacketEventKind::Dropped | PacketEventKind::Corrupted, NodeId::Sender) => {
+                    // Logged right after the send it belongs to.
+                    if let Some(last) = graph.segments.last_mut()
+                        && (last.time, last.seq) == (packet.time, packet.seq)
+                    {
+                        last.lost = true;
+                    }
+                }
+                (PacketEventKind::Arrived, NodeId::Sender) => {
+                    graph.acks.push((packet.time, packet.ack));
+                }
+                _ => {}
+            }
+        }
+        if !byte_numbered {
+            for segment in &mut graph.segments {
+                segment.end = segment.seq.saturating_add(1);
+            }
+        }
+        graph
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// The highest sequence or ACK number on the graph.
+    pub fn max_seq(&self) -> u32 {
+        self.segments
+            .iter()
+            .map(|s| s.end)
+            .chain(self.acks.iter().map(|&(_, ack)| ack))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(time: u64, kind: PacketEventKind, node: NodeId, seq: u32, ack: u32) -> PacketEvent {
+        PacketEvent {
+            time,
+            kind,
+            node,
+            seq,
+            ack,
+            flags: 0,
+            window: 0,
+            len: if node == NodeId::Sender { 100 } else { 0 },
+            ambiguous: false,
+            cause: None,
+            arrival: None,
+            note: None,
+        }
+    }
+
+    #[test]
+    fn marks_retransmissions_losses_and_acks() {
+        use NodeId::*;
+        use PacketEventKind::*;
+        let log = [
+            event(0, Sent, Sender, 0, 0),
+            event(0, Sent, Sender, 100, 0),
+            event(0, Dropped, Sender, 100, 0),
+            event(40, Arrived, Receiver, 0, 0),
+            event(60, Sent, Receiver, 0, 100),
+            event(100, Arrived, Sender, 0, 100),
+            event(300, Sent, Sender, 100, 0),
+        ];
+        let graph = TimeSequence::from_packets(&log);
+        let summary: Vec<_> = graph
+            .segments
+            .iter()
+            .map(|s| (s.time, s.seq, s.end, s.retransmit, s.lost))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (0, 0, 100, false, false),
+                (0, 100, 200, false, true),
+                (300, 100, 200, true, false),
+            ]
+        );
+        assert_eq!(graph.acks, [(100, 100)]);
+        assert_eq!(graph.max_seq(), 200);
+
+        // Segment numbers: each segment is one unit tall.
+        let log = [event(0, Sent, Sender, 0, 0), event(0, Sent, Sender, 1, 0)];
+        let graph = TimeSequence::from_packets(&log);
+        assert_eq!(graph.segments[1].end, 2);
+    }
+}
@@ -0,0 +1,598 @@
+//! Shrink a failing scenario into a small standalone one that fails the same way, so a
+//! student gets a repro case with their grade instead of a trace of thousands of events.
+//!
+//! The random losses and corruptions of the failing run become deterministic drop actions
+//! on a fixed-latency link, assertions that held are left out, and actions and `app_send`
+//! data are cut down for as long as the run still fails. `data_delivered` assertions follow
+//! the `app_send`s they expect. If the original passes once its faults are taken away, every
+//! smaller scenario must too, so the repro still fails because of the exchange rather than
+//! because, say, the data an assertion expects is no longer sent. Every step is checked by
+//! running the protocols again, so the result depends on them behaving deterministically.
+
+use std::collections::HashMap;
+
+use tcp_lab_abstract::{NodeId, PacketEventKind, TestScenario};
+
+use crate::engine::DropReason;
+use crate::error::ScenarioError;
+use crate::i18n::Msg;
+use crate::scenario_runner::{self, AssertionStatus, ScenarioResult};
+use crate::trace::SimulationReport;
+
+/// A shrunk scenario that fails like the original.
+#[derive(Debug, Clone)]
+pub struct Repro {
+    /// The scenario as TOML.
+    pub content: String,
+    /// What was cut, one line per step that kept the failure.
+    pub steps: Vec<String>,
+    /// How often the protocols were run, the original run included.
+    pub runs: usize,
+}
+
+/// Shrink the scenario `content` against `run`, which runs a scenario with fresh
+/// protocols. `None` if the scenario passes, so there is nothing to reproduce; a failure
+/// that is not the submission's, such as a protocol that would not load, is returned.
+pub fn minimize(
+    content: &str,
+    strict: bool,
+    mut run: impl FnMut(&TestScenario) -> ScenarioResult,
+) -> Result<Option<Repro>, ScenarioError> {
+    let table: toml::Table = toml::from_str(content).map_err(|e| ScenarioError::Parse {
+        details: e.to_string(),
+    })?;
+    let scenario = scenario_runner::parse_scenario(content, strict)?;
+    let result = run(&scenario);
+    let Some(failure) = result.failures.first() else {
+        return Ok(None);
+    };
+    if !failure.is_student_failure() {
+        return Err(failure.clone());
+    }
+    let mut shrinker = Shrinker {
+        table,
+        strict,
+        signature: signature(failure),
+        guarded: false,
+        run: &mut run,
+        runs: 1,
+        steps: Vec::new(),
+    };
+    shrinker.guarded = shrinker.passes(&without_faults(&shrinker.table));
+    if let Some(report) = &result.report {
+        shrinker.keep_failed_assertions(report);
+        shrinker.determinize_link(report);
+    }
+    shrinker.shrink_actions();
+    shrinker.trim_app_data();
+
+    // Describe the failure as the repro shows it, not as the original run did.
+    let table = shrinker.table.clone();
+    let shown = shrinker.run(&table);
+    let failure = shown
+        .as_ref()
+        .and_then(|result| result.failures.first())
+        .unwrap_or(failure);
+    let description = Msg::ReproDescription.fill(&[&first_line(failure)]);
+    let mut table = shrinker.table;
+    let name = table
+        .get("name")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+    let name = Msg::ReproName.fill(&[&name]);
+    table.insert("name".into(), name.into());
+    table.insert("description".into(), description.into());
+    Ok(Some(Repro {
+        content: toml::to_string(&table).expect("scenario tables serialize"),
+        steps: shrinker.steps,
+        runs: shrinker.runs,
+    }))
+}
+
+struct Shrinker<'a, F> {
+    table: toml::Table,
+    strict: bool,
+    /// What the first failure must still be; see [`signature`].
+    signature: String,
+    /// Whether a candidate must also pass without its faults, as the original does.
+    guarded: bool,
+    run: &'a mut F,
+    runs: usize,
+    steps: Vec<String>,
+}
+
+impl<F: FnMut(&TestScenario) -> ScenarioResult> Shrinker<'_, F> {
+    /// Run `table`; `None` if it is not a valid scenario.
+    fn run(&mut self, table: &toml::Table) -> Option<ScenarioResult> {
+        let content = toml::to_string(table).ok()?;
+        let scenario = scenario_runner::parse_scenario(&content, self.strict).ok()?;
+        self.runs += 1;
+        Some((self.run)(&scenario))
+    }
+
+    fn passes(&mut self, table: &toml::Table) -> bool {
+        self.run(table).is_some_and(|result| result.passed())
+    }
+
+    /// Whether `table` still fails the way the original did.
+    fn fails(&mut self, table: &toml::Table) -> bool {
+        let same = self.run(table).is_some_and(|result| {
+            result
+                .failures
+                .first()
+                .is_some_and(|failure| signature(failure) == self.signature)
+        });
+        same && (!self.guarded || self.passes(&without_faults(table)))
+    }
+
+    /// Take `candidate` if it still fails, noting `step`.
+    fn try_table(&mut self, candidate: toml::Table, step: impl FnOnce() -> String) -> bool {
+        if !self.fails(&candidate) {
+            return false;
+        }
+        self.table = candidate;
+        self.steps.push(step());
+        true
+    }
+
+    fn list(&self, key: &str) -> Vec<toml::Value> {
+        self.table
+            .get(key)
+            .and_then(|v| v.as_array())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn with_list(&self, key: &str, items: Vec<toml::Value>) -> toml::Table {
+        let mut table = self.table.clone();
+        table.insert(key.into(), toml::Value::Array(items));
+        table
+    }
+
+    /// Leave out the assertions that held, keeping `max_duration`, which bounds the run.
+    fn keep_failed_assertions(&mut self, report: &SimulationReport) {
+        let assertions = self.list("assertions");
+        let kept: Vec<toml::Value> = assertions
+            .iter()
+            .enumerate()
+            .filter(|(index, assertion)| {
+                action_type(assertion) == Some("max_duration")
+                    || report
+                        .assertions
+                        .iter()
+                        .any(|a| a.index == *index && a.status == AssertionStatus::Failed)
+            })
+            .map(|(_, assertion)| assertion.clone())
+            .collect();
+        if kept.len() < assertions.len() {
+            let (kept_len, total) = (kept.len(), assertions.len());
+            let candidate = self.with_list("assertions", kept);
+            self.try_table(candidate, || {
+                Msg::ReproAssertions.fill(&[&kept_len, &total])
+            });
+        }
+    }
+
+    /// Replace `loss_rate`, `corrupt_rate`, `bit_error_rate`, `truncate_rate` and
+    /// `tamper_duplicate_rate` with actions repeating what the channel did in the failing run, and pin the latency to the middle of its range.
+    fn determinize_link(&mut self, report: &SimulationReport) {
+        let config = &report.config;
+        let random = config.loss_rate > 0.0
+            || config.corrupt_rate > 0.0
+            || config.bit_error_rate > 0.0
+            || config.truncate_rate > 0.0
+            || config.tamper_duplicate_rate > 0.0;
+        let latency = config.min_latency.midpoint(config.max_latency);
+        let fixed = |table: &mut toml::Table| {
+            let config = config_table(table);
+            config.insert("min_latency".into(), to_value(latency));
+            config.insert("max_latency".into(), to_value(latency));
+        };
+        if random {
+            let faults = channel_faults(report);
+            let mut candidate = self.table.clone();
+            let settings = config_table(&mut candidate);
+            settings.insert("loss_rate".into(), 0.0.into());
+            settings.insert("corrupt_rate".into(), 0.0.into());
+            settings.insert("bit_error_rate".into(), 0.0.into());
+            settings.insert("truncate_rate".into(), 0.0.into());
+            settings.insert("tamper_duplicate_rate".into(), 0.0.into());
+            let mut actions = candidate
+                .get("actions")
+                .and_then(|v| v.as_array())
+                .cloned()
+                .unwrap_or_default();
+            actions.extend(faults.iter().cloned().map(toml::Value::Table));
+            candidate.insert("actions".into(), toml::Value::Array(actions));
+            let step = || Msg::ReproDeterministicLink.fill(&[&faults.len()]);
+            let mut pinned = candidate.clone();
+            fixed(&mut pinned);
+            if self.try_table(pinned, step) {
+                self.steps.push(Msg::ReproFixedLatency.fill(&[&latency]));
+                return;
+            }
+            self.try_table(candidate, step);
+        }
+        if config.min_latency != config.max_latency {
+            let mut candidate = self.table.clone();
+            fixed(&mut candidate);
+            self.try_table(candidate, || Msg::ReproFixedLatency.fill(&[&latency]));
+        }
+    }
+
+    /// `table` with only the actions in `kept`, and without the `data_delivered`
+    /// assertions that expected data only the `removed` actions sent.
+    fn with_actions(&self, kept: Vec<toml::Value>, removed: &[toml::Value]) -> toml::Table {
+        let gone: Vec<&str> = removed
+            .iter()
+            .filter_map(app_send_data)
+            .filter(|data| !kept.iter().any(|a| app_send_data(a) == Some(data)))
+            .collect();
+        let mut table = self.with_list("actions", kept);
+        if !gone.is_empty() {
+            let assertions = self
+                .list("assertions")
+                .into_iter()
+                .filter(|a| !delivery_data(a).is_some_and(|data| gone.contains(&data)))
+                .collect();
+            table.insert("assertions".into(), toml::Value::Array(assertions));
+        }
+        table
+    }
+
+    /// Delta debugging over the actions: leave out ever smaller runs of them for as long
+    /// as the failure stays.
+    fn shrink_actions(&mut self) {
+        let mut actions = self.list("actions");
+        let total = actions.len();
+        let mut chunks = 2;
+        while !actions.is_empty() {
+            let size = actions.len().div_ceil(chunks);
+            let mut shrunk = false;
+            for start in (0..actions.len()).step_by(size) {
+                let mut kept = actions.clone();
+                let removed: Vec<toml::Value> = kept
+                    .drain(start..(start + size).min(actions.len()))
+                    .collect();
+                let candidate = self.with_actions(kept.clone(), &removed);
+                if self.fails(&candidate) {
+                    self.table = candidate;
+                    actions = kept;
+                    chunks = (chunks - 1).max(2);
+                    shrunk = true;
+                    break;
+                }
+            }
+            if !shrunk {
+                if chunks >= actions.len() {
+                    break;
+                }
+                chunks = (chunks * 2).min(actions.len());
+            }
+        }
+        if actions.len() < total {
+            self.steps
+                .push(Msg::ReproActions.fill(&[&actions.len(), &total]));
+        }
+    }
+
+    /// Halve each remaining `app_send`'s data for as long as the failure stays.
+    fn trim_app_data(&mut self) {
+        let actions = self.list("actions");
+        for (index, action) in actions.iter().enumerate() {
+            let Some(data) = app_send_data(action) else {
+                continue;
+            };
+            let original = data.chars().count();
+            let mut kept = original;
+            while kept > 1 {
+                let current: String = data.chars().take(kept).collect();
+                let half: String = data.chars().take(kept / 2).collect();
+                let mut trimmed = self.list("actions");
+                trimmed[index]
+                    .as_table_mut()
+                    .expect("actions are tables")
+                    .insert("data".into(), half.clone().into());
+                let mut candidate = self.with_list("actions", trimmed);
+                let assertions = self
+                    .list("assertions")
+                    .into_iter()
+                    .map(|mut assertion| {
+                        if delivery_data(&assertion) == Some(current.as_str()) {
+                            let table = assertion.as_table_mut().expect("assertions are tables");
+                            table.insert("data".into(), half.clone().into());
+                        }
+                        assertion
+                    })
+                    .collect();
+                candidate.insert("assertions".into(), toml::Value::Array(assertions));
+                if !self.fails(&candidate) {
+                    break;
+                }
+                self.table = candidate;
+                kept /= 2;
+            }
+            if kept < original {
+                self.steps
+                    .push(Msg::ReproTrimmedData.fill(&[&original, &kept]));
+            }
+        }
+    }
+}
+
+/// What a failure must stay for a shrunk scenario to count as reproducing it: its kind,
+/// and for a failed assertion, which type of assertion (its data may have been trimmed).
+fn signature(failure: &ScenarioError) -> String {
+    match failure {
+        ScenarioError::AssertionFailed { assertion, .. } => {
+            let debug = format!("{assertion:?}");
+            let end = debug.find([' ', '{', '(']).unwrap_or(debug.len());
+            debug[..end].to_string()
+        }
+        other => other.kind().to_string(),
+    }
+}
+
+/// Actions that lose or damage packets on purpose.
+const FAULT_ACTIONS: [&str; 8] = [
+    "drop_next_from_sender_seq",
+    "corrupt_next_from_sender_seq",
+    "truncate_next_from_sender_seq",
+    "tamper_duplicate_next_from_sender_seq",
+    "drop_next_from_receiver_ack",
+    "drop_from_sender_seq",
+    "drop_from_receiver_ack",
+    "drop_next_with_payload",
+];
+
+/// `table` on a link that loses and damages nothing.
+fn without_faults(table: &toml::Table) -> toml::Table {
+    let mut table = table.clone();
+    let config = config_table(&mut table);
+    config.insert("loss_rate".into(), 0.0.into());
+    config.insert("corrupt_rate".into(), 0.0.into());
+    config.insert("bit_error_rate".into(), 0.0.into());
+    config.insert("truncate_rate".into(), 0.0.into());
+    config.insert("tamper_duplicate_rate".into(), 0.0.into());
+    if let Some(actions) = table.get_mut("actions").and_then(|v| v.as_array_mut()) {
+        actions.retain(|a| !action_type(a).is_some_and(|t| FAULT_ACTIONS.contains(&t)));
+    }
+    table
+}
+
+/// Drop actions for the packets the channel lost at random in `report`, found by counting
+/// each node's sends of a seq (sender) or ACK number (receiver). Back-to-back losses of
+/// the same one become a single action with a `count`. A random corruption of a sender
+/// segment's first transmission becomes `corrupt_next_from_sender_seq`, and a random
+/// truncation of one becomes `truncate_next_from_sender_seq` to the same length, and a
+/// random tampered duplicate of one becomes `tamper_duplicate_next_from_sender_seq`; other
+/// corruptions, truncations and duplicates cannot be expressed and are left out.
+fn channel_faults(report: &SimulationReport) -> Vec<toml::Table> {
+    let mut losses: Vec<(u64, NodeId, u32, u32)> = report
+        .drops
+        .iter()
+        .filter(|drop| drop.reason == DropReason::RandomLoss)
+        .map(|drop| (drop.time, drop.from, drop.seq, drop.ack))
+        .collect();
+    let mut corruptions: Vec<(u64, NodeId, u32, u32)> = report
+        .corrupted_packets
+        .iter()
+        .filter(|packet| !packet.injected)
+        .map(|packet| (packet.time, packet.from, packet.seq, packet.ack))
+        .collect();
+    let mut truncations: Vec<((u64, NodeId, u32, u32), usize)> = report
+        .truncated_packets
+        .iter()
+        .filter(|packet| !packet.injected)
+        .map(|packet| {
+            (
+                (packet.time, packet.from, packet.seq, packet.ack),
+                packet.len,
+            )
+        })
+        .collect();
+    let mut duplicates: Vec<(u64, NodeId, u32, u32)> = report
+        .tampered_duplicates
+        .iter()
+        .filter(|copy| !copy.injected)
+        .map(|copy| (copy.time, copy.from, copy.seq, copy.ack))
+        .collect();
+    let take = |list: &mut Vec<(u64, NodeId, u32, u32)>, key| {
+        list.iter()
+            .position(|entry| *entry == key)
+            .map(|i| list.remove(i))
+            .is_some()
+    };
+
+    let mut sends: HashMap<(NodeId, u32), u32> = HashMap::new();
+    let mut faults: Vec<toml::Table> = Vec::new();
+    // The last drop action and the occurrence it ends at, to extend its `count`.
+    let mut last_drop: Option<(NodeId, u32, u32)> = None;
+    for event in &report.packets {
+        let number = match event.node {
+            NodeId::Sender => event.seq,
+            NodeId::Receiver => event.ack,
+        };
+        let occurrence = sends.get(&(event.node, number)).copied().unwrap_or(0);
+        let key = (event.time, event.node, event.seq, event.ack);
+        match event.kind {
+            PacketEventKind::Sent => {
+                sends.insert((event.node, number), occurrence + 1);
+                if take(&mut duplicates, key) && event.node == NodeId::Sender && occurrence == 0 {
+                    let mut action = toml::Table::new();
+                    action.insert(
+                        "type".into(),
+                        "tamper_duplicate_next_from_sender_seq".into(),
+                    );
+                    action.insert("seq".into(), to_value(number));
+                    faults.push(action);
+                    last_drop = None;
+                }
+            }
+            PacketEventKind::Dropped if take(&mut losses, key) => {
+                if last_drop == Some((event.node, number, occurrence - 1))
+                    && let Some(count) = faults.last_mut().and_then(|f| f.get_mut("count"))
+                {
+                    *count = to_value(count.as_integer().unwrap_or(1) + 1);
+                } else {
+                    let (kind, field) = match event.node {
+                        NodeId::Sender => ("drop_from_sender_seq", "seq"),
+                        NodeId::Receiver => ("drop_from_receiver_ack", "ack"),
+                    };
+                    let mut action = toml::Table::new();
+                    action.insert("type".into(), kind.into());
+                    action.insert(field.into(), to_value(number));
+                    action.insert("occurrence".into(), to_value(occurrence));
+                    action.insert("count".into(), to_value(1));
+                    faults.push(action);
+                }
+                last_drop = Some((event.node, number, occurrence));
+            }
+            // A packet both corrupted and truncated has an event for each, in that order.
+            PacketEventKind::Corrupted => {
+                let (kind, len) = if take(&mut corruptions, key) {
+                    ("corrupt_next_from_sender_seq", None)
+                } else if let Some(i) = truncations.iter().position(|(k, _)| *k == key) {
+                    (
+                        "truncate_next_from_sender_seq",
+                        Some(truncations.remove(i).1),
+                    )
+                } else {
+                    continue;
+                };
+                if event.node == NodeId::Sender && occurrence == 1 {
+                    let mut action = toml::Table::new();
+                    action.insert("type".into(), kind.into());
+                    action.insert("seq".into(), to_value(number));
+                    if let Some(len) = len {
+                        action.insert("len".into(), to_value(len as u64));
+                    }
+                    faults.push(action);
+                    last_drop = None;
+                }
+            }
+            _ => {}
+        }
+    }
+    faults
+}
+
+/// The scenario's `[config]` table, created if missing.
+fn config_table(table: &mut toml::Table) -> &mut toml::Table {
+    table
+        .entry("config")
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+        .as_table_mut()
+        .expect("a parsed scenario's config is a table")
+}
+
+fn action_type(value: &toml::Value) -> Option<&str> {
+    value.get("type").and_then(|v| v.as_str())
+}
+
+fn app_send_data(action: &toml::Value) -> Option<&str> {
+    (action_type(action) == Some("app_send"))
+        .then(|| action.get("data")?.as_str())
+        .flatten()
+}
+
+fn delivery_data(assertion: &toml::Value) -> Option<&str> {
+    (action_type(assertion) == Some("data_delivered"))
+        .then(|| assertion.get("data")?.as_str())
+        .flatten()
+}
+
+fn to_value(n: impl Into<i128>) -> toml::Value {
+    toml::Value::Integer(n.into().clamp(0, i64::MAX.into()) as i64)
+}
+
+fn first_line(failure: &ScenarioError) -> String {
+    failure
+        .to_string()
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tcp_lab_abstract::{Packet, SystemContext, TransportProtocol};
+
+    use crate::scenario_runner::{RunOptions, run_scenario_with};
+
+    /// Sends each message once and never retransmits.
+    struct OneShot {
+        next: u32,
+    }
+
+    impl TransportProtocol for OneShot {
+        fn on_packet(&mut self, _ctx: &mut dyn SystemContext, _packet: Packet) {}
+
+        fn on_timer(&mut self, _ctx: &mut dyn SystemContext, _timer_id: u32) {}
+
+        fn on_app_data(&mut self, ctx: &mut dyn SystemContext, data: &[u8]) {
+            ctx.send_packet(Packet::new_simple(self.next, 0, 0, data.to_vec()));
+            self.next += 1;
+        }
+    }
+
+    struct Deliverer;
+
+    impl TransportProtocol for Deliverer {
+        fn on_packet(&mut self, ctx: &mut dyn SystemContext, packet: Packet) {
+            ctx.deliver_data(&packet.payload);
+        }
+
+        fn on_timer(&mut self, _ctx: &mut dyn SystemContext, _timer_id: u32) {}
+
+        fn on_app_data(&mut self, _ctx: &mut dyn SystemContext, _data: &[u8]) {}
+    }
+
+    fn run(scenario: &TestScenario) -> ScenarioResult {
+        run_scenario_with(
+            scenario,
+            Box::new(OneShot { next: 0 }),
+            Box::new(Deliverer),
+            RunOptions::default(),
+        )
+    }
+
+    #[test]
+    fn test_minimize() {
+        let mut content = String::from(
+            "name = \"lossy\"\ndescription = \"\"\n\n[config]\nloss_rate = 0.3\nseed = 7\n\
+             min_latency = 10\nmax_latency = 90\n",
+        );
+        for i in 0..20 {
+            content.push_str(&format!(
+                "\n[[actions]]\ntype = \"app_send\"\ntime = {}\ndata = \"message-{i:02}\"\n\n\
+                 [[assertions]]\ntype = \"data_delivered\"\ndata = \"message-{i:02}\"\n",
+                i * 10
+            ));
+        }
+
+        let repro = minimize(&content, false, run)
+            .unwrap()
+            .expect("the run fails");
+        let table: toml::Table = toml::from_str(&repro.content).unwrap();
+        let config = table["config"].as_table().unwrap();
+        assert_eq!(config["loss_rate"].as_float(), Some(0.0));
+        assert_eq!(config["min_latency"].as_integer(), Some(50));
+        // One message, the drop that loses it and the assertion that expects it.
+        let actions = table["actions"].as_array().unwrap();
+        assert_eq!(actions.len(), 2, "{}", repro.content);
+        assert_eq!(table["assertions"].as_array().unwrap().len(), 1);
+        assert!(repro.steps.len() >= 4, "{:?}", repro.steps);
+
+        let scenario = scenario_runner::parse_scenario(&repro.content, false).unwrap();
+        assert!(!run(&scenario).passed());
+        let clean = toml::to_string(&without_faults(&table)).unwrap();
+        let scenario = scenario_runner::parse_scenario(&clean, false).unwrap();
+        assert!(run(&scenario).passed());
+
+        let passing = content.replace("loss_rate = 0.3", "loss_rate = 0.0");
+        assert!(minimize(&passing, false, run).unwrap().is_none());
+    }
+}
@@ -1,142 +1,3263 @@
-use crate::engine::Simulator;
+use crate::builder::SimulatorBuilder;
+use crate::checker::Checker;
+use crate::engine::{
+    AckTraceEntry, FlowTraceEntry, PacketEvent, PacketEventKind, ProtocolFactory,
+    QUEUE_DELAY_METRIC, RTO_METRIC, Simulator,
+};
+use crate::error::ScenarioError;
+use crate::explain::{about_delivery, explain};
+use crate::hints::HintCatalog;
+use crate::i18n::{Msg, node_name};
+use crate::scratch::ScratchStore;
 use crate::trace::SimulationReport;
-use anyhow::{Context, anyhow};
+use crate::workload;
+use anyhow::{Context, bail};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_path_to_error::Segment;
+use serde_value::{DeserializerError, ValueDeserializer};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
-use tcp_lab_abstract::{SimConfig, TestAction, TestAssertion, TestScenario, TransportProtocol};
-use tracing::info;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use tcp_lab_abstract::{
+    AppModel, AssertionPolicy, MetricBand, NodeId, Packet, PacketStep, SeqUnit, SimConfig,
+    SuiteInfo, TcpHeader, TestAction, TestAssertion, TestScenario, TransportProtocol, flags,
+};
+use tracing::{info, warn};
 
-pub fn run_scenario(
-    scenario_path: &str,
-    sender: Box<dyn TransportProtocol>,
-    receiver: Box<dyn TransportProtocol>,
-) -> anyhow::Result<SimulationReport> {
-    let content = fs::read_to_string(scenario_path).context("Failed to read scenario file")?;
-    let scenario: TestScenario = toml::from_str(&content).context("Failed to parse scenario")?;
+/// The tcp-lab release this grader belongs to, compared with a scenario's
+/// `min_tool_version`.
+pub const TOOL_VERSION: &str = env!("CARGO_PKG_VERSION");
 
-    info!("Running Scenario: {}", scenario.name);
-    info!("Description: {}", scenario.description);
+/// Prefix naming one of [`BUILTIN_SCENARIOS`] instead of a file, e.g. `builtin:test_gbn`.
+pub const BUILTIN_PREFIX: &str = "builtin:";
 
-    let mut config = SimConfig::default();
-    scenario.config.apply_to(&mut config);
+/// The official scenarios from the repository's `tests/`, embedded at build time so that
+/// they can be run without a checkout. Each is `(name, TOML)`, in name order; the name is
+/// the file's path under `tests/` without `.toml`, e.g. `lab3/gbn_basic`.
+pub const BUILTIN_SCENARIOS: &[(&str, &str)] =
+    include!(concat!(env!("OUT_DIR"), "/builtin_scenarios.rs"));
+
+/// The text of the scenario at `path`. A `builtin:NAME` path reads the embedded scenario
+/// NAME, unless a file of that name exists.
+pub fn read_scenario(path: impl AsRef<Path>) -> anyhow::Result<String> {
+    let path = path.as_ref();
+    if let Some(name) = path
+        .to_str()
+        .and_then(|path| path.strip_prefix(BUILTIN_PREFIX))
+        .filter(|_| !path.is_file())
+    {
+        return match BUILTIN_SCENARIOS
+            .iter()
+            .find(|(builtin, _)| *builtin == name)
+        {
+            Some((_, content)) => Ok(content.to_string()),
+            None => {
+                let names: Vec<&str> = BUILTIN_SCENARIOS.iter().map(|(name, _)| *name).collect();
+                bail!(Msg::UnknownBuiltinScenario.fill(&[&name, &names.join(", ")]))
+            }
+        };
+    }
+    fs::read_to_string(path)
+        .with_context(|| format!("Failed to read scenario file {}", path.display()))
+}
+
+/// Read and parse a scenario file, or a built-in scenario; see [`read_scenario`].
+///
+/// Unknown keys (usually misspelled fields) are an error when `strict` is set, which graders
+/// should always do; otherwise they are dropped with a warning listing each one.
+pub fn load_scenario(path: impl AsRef<Path>, strict: bool) -> Result<TestScenario, ScenarioError> {
+    let path = path.as_ref();
+    let parse = || {
+        let content = read_scenario(path)?;
+        parse_toml(&content, strict)
+            .with_context(|| format!("Failed to parse scenario {}", path.display()))
+    };
+    parse().map_err(parse_error)
+}
 
-    let mut sim = Simulator::new(config, sender, receiver);
+/// Parse scenario TOML; see [`load_scenario`] for how unknown keys are handled.
+pub fn parse_scenario(content: &str, strict: bool) -> Result<TestScenario, ScenarioError> {
+    parse_toml(content, strict).map_err(parse_error)
+}
 
-    // Configure actions (App sends, deterministic faults, etc.)
-    for action in &scenario.actions {
+/// The file in a suite directory that holds its [`SuiteInfo`] rather than a scenario.
+pub const SUITE_FILE: &str = "suite.toml";
+
+/// The `*.toml` files in `dir` other than [`SUITE_FILE`], in file name order.
+pub fn scenario_files(dir: impl AsRef<Path>) -> anyhow::Result<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read scenario directory {}", dir.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    paths.retain(|path| {
+        path.extension().is_some_and(|ext| ext == "toml")
+            && path.file_name().is_some_and(|name| name != SUITE_FILE)
+    });
+    paths.sort();
+    Ok(paths)
+}
+
+/// The course and lab details in the [`SUITE_FILE`] of `dir`; empty when it has none.
+pub fn load_suite_info(dir: impl AsRef<Path>) -> anyhow::Result<SuiteInfo> {
+    let path = dir.as_ref().join(SUITE_FILE);
+    if !path.is_file() {
+        return Ok(SuiteInfo::default());
+    }
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read suite file {}", path.display()))?;
+    toml::from_str(&content)
+        .with_context(|| format!("Failed to parse suite file {}", path.display()))
+}
+
+/// Which scenarios of a suite to run, by their `tags`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagFilter {
+    /// Run only scenarios with at least one of these tags; empty runs all of them.
+    pub tags: Vec<String>,
+    /// Skip scenarios with any of these tags, even if `tags` selects them.
+    pub exclude_tags: Vec<String>,
+}
+
+impl TagFilter {
+    pub fn matches(&self, scenario: &TestScenario) -> bool {
+        let has = |wanted: &Vec<String>| scenario.tags.iter().any(|tag| wanted.contains(tag));
+        (self.tags.is_empty() || has(&self.tags)) && !has(&self.exclude_tags)
+    }
+}
+
+fn parse_error(err: anyhow::Error) -> ScenarioError {
+    ScenarioError::Parse {
+        details: format!("{err:#}"),
+    }
+}
+
+fn parse_toml(content: &str, strict: bool) -> anyhow::Result<TestScenario> {
+    let mut table: toml::Table = toml::from_str(content)?;
+    let ignored = strip_unknown_keys(&mut table)?;
+    let scenario = if ignored.is_empty() {
+        // Parse the original text so errors keep their line numbers.
+        toml::from_str(content)?
+    } else {
+        if strict {
+            bail!(Msg::UnknownScenarioKeys.fill(&[&ignored.join(", ")]));
+        }
+        warn!("{}", Msg::IgnoringUnknownKeys.fill(&[&ignored.join(", ")]));
+        TestScenario::deserialize(table)?
+    };
+    check_tool_version(&scenario)?;
+    Ok(scenario)
+}
+
+/// `value` as the TOML value of the `[config]` setting `param`: `0.05`, `true` or `"reno"`
+/// as written, and a bare word as a string. Fails if `SimConfig` has no such setting.
+fn config_value(param: &str, value: &str) -> anyhow::Result<toml::Value> {
+    let serde_value::Value::Map(settings) = serde_value::to_value(SimConfig::default())? else {
+        unreachable!("SimConfig serializes to a map");
+    };
+    if !settings.contains_key(&serde_value::Value::String(param.to_string())) {
+        bail!(Msg::UnknownConfigSetting.fill(&[&param]));
+    }
+    let parsed = toml::from_str::<toml::Table>(&format!("value = {value}"))
+        .ok()
+        .and_then(|mut table| table.remove("value"));
+    Ok(parsed.unwrap_or_else(|| toml::Value::String(value.to_string())))
+}
+
+/// Scenario TOML `content` with its `[config]` setting `param` set to `value`, which is
+/// read as in a scenario file; a bare word is taken as a string.
+pub fn set_config_value(content: &str, param: &str, value: &str) -> anyhow::Result<String> {
+    let value = config_value(param, value)?;
+    let mut table: toml::Table = toml::from_str(content)?;
+    let config = table
+        .entry("config")
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    let Some(config) = config.as_table_mut() else {
+        bail!(Msg::MigrateConfigNotTable.text());
+    };
+    config.insert(param.to_string(), value);
+    Ok(toml::to_string(&table)?)
+}
+
+/// `config` with its setting `param` set to `value`, read as by [`set_config_value`].
+pub fn with_config_value(
+    config: &SimConfig,
+    param: &str,
+    value: &str,
+) -> anyhow::Result<SimConfig> {
+    let value = config_value(param, value)?;
+    let serde_value::Value::Map(mut settings) = serde_value::to_value(config)? else {
+        unreachable!("SimConfig serializes to a map");
+    };
+    settings.insert(
+        serde_value::Value::String(param.to_string()),
+        serde_value::to_value(value)?,
+    );
+    SimConfig::deserialize(serde_value::Value::Map(settings))
+        .with_context(|| Msg::InvalidConfig.fill(&[&param]))
+}
+
+/// A scenario upgraded by [`migrate_scenario`].
+#[derive(Debug, Clone)]
+pub struct Migration {
+    /// The upgraded TOML, which parses strictly.
+    pub content: String,
+    /// What was changed, one line each; empty if the scenario was already current.
+    pub changes: Vec<String>,
+}
+
+/// The keys a scenario must have; an older file without them gets an empty value.
+const REQUIRED_KEYS: [&str; 4] = ["description", "config", "actions", "assertions"];
+
+/// Upgrade scenario TOML written for an older tcp-lab to the current schema: link
+/// settings at the top level move under `[config]`, missing required keys get empty values
+/// and unknown keys are dropped. Comments and formatting are not kept. Fails if the result
+/// still does not parse, e.g. because an assertion lacks a field.
+pub fn migrate_scenario(content: &str) -> Result<Migration, ScenarioError> {
+    let migrate = || {
+        let mut table: toml::Table = toml::from_str(content)?;
+        let mut changes = Vec::new();
+
+        let serde_value::Value::Map(settings) = serde_value::to_value(SimConfig::default())? else {
+            unreachable!("SimConfig serializes to a map");
+        };
+        let moved: Vec<(String, toml::Value)> = settings
+            .into_keys()
+            .filter_map(|key| match key {
+                serde_value::Value::String(key) => Some((key.clone(), table.remove(&key)?)),
+                _ => None,
+            })
+            .collect();
+        if !moved.is_empty() {
+            let config = table
+                .entry("config")
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            let Some(config) = config.as_table_mut() else {
+                bail!(Msg::MigrateConfigNotTable.text());
+            };
+            for (key, value) in moved {
+                changes.push(Msg::MigrateMovedToConfig.fill(&[&key]));
+                config.entry(key).or_insert(value);
+            }
+        }
+        for key in REQUIRED_KEYS {
+            if !table.contains_key(key) {
+                changes.push(Msg::MigrateAddedKey.fill(&[&key]));
+                let empty = match key {
+                    "description" => toml::Value::String(String::new()),
+                    "config" => toml::Value::Table(toml::Table::new()),
+                    _ => toml::Value::Array(Vec::new()),
+                };
+                table.insert(key.to_string(), empty);
+            }
+        }
+        for key in strip_unknown_keys(&mut table)? {
+            changes.push(Msg::MigrateDroppedKey.fill(&[&key]));
+        }
+
+        let content = toml::to_string(&table)?;
+        parse_toml(&content, true)?;
+        Ok(Migration { content, changes })
+    };
+    migrate().map_err(parse_error)
+}
+
+/// Refuse a scenario whose `min_tool_version` is newer than [`TOOL_VERSION`].
+fn check_tool_version(scenario: &TestScenario) -> anyhow::Result<()> {
+    let Some(needed) = &scenario.min_tool_version else {
+        return Ok(());
+    };
+    let Some(parsed) = version_parts(needed) else {
+        bail!(Msg::InvalidToolVersion.fill(&[needed]));
+    };
+    if version_parts(TOOL_VERSION).is_some_and(|current| parsed > current) {
+        bail!(Msg::ToolTooOld.fill(&[needed, &TOOL_VERSION]));
+    }
+    Ok(())
+}
+
+/// The numeric parts of a version such as `0.2.1`, ignoring a `-beta` style suffix.
+fn version_parts(version: &str) -> Option<Vec<u32>> {
+    let release = version
+        .split_once('-')
+        .map_or(version, |(release, _)| release);
+    release
+        .split('.')
+        .map(|part| part.trim().parse().ok())
+        .collect()
+}
+
+/// Remove every key the scenario types reject as unknown, returning their dotted paths.
+///
+/// The types use `deny_unknown_fields`, so this repeatedly deserializes and removes the key
+/// reported by each `UnknownField` error until a pass no longer trips over one.
+fn strip_unknown_keys(table: &mut toml::Table) -> anyhow::Result<Vec<String>> {
+    let mut ignored = Vec::new();
+    loop {
+        let value = serde_value::to_value(&*table)?;
+        let result: Result<TestScenario, _> =
+            serde_path_to_error::deserialize(ValueDeserializer::<DeserializerError>::new(value));
+        let err = match result {
+            Err(err) => err,
+            Ok(_) => return Ok(ignored),
+        };
+        let DeserializerError::UnknownField(field, _) = err.inner() else {
+            // Any other problem is reported when the scenario is parsed for real.
+            return Ok(ignored);
+        };
+
+        // Struct fields end the path; fields of tagged enum variants stop at the element.
+        let mut segments: Vec<&Segment> = err.path().iter().collect();
+        let location = if matches!(segments.last(), Some(Segment::Map { key }) if key == field) {
+            segments.pop();
+            err.path().to_string()
+        } else {
+            format!("{}.{}", err.path(), field)
+        };
+        let removed = table_at(table, &segments).and_then(|container| container.remove(field));
+        if removed.is_none() {
+            return Ok(ignored);
+        }
+        ignored.push(location);
+    }
+}
+
+fn table_at<'a>(table: &'a mut toml::Table, path: &[&Segment]) -> Option<&'a mut toml::Table> {
+    let Some((Segment::Map { key }, rest)) = path.split_first() else {
+        return path.is_empty().then_some(table);
+    };
+    let mut value = table.get_mut(key.as_str())?;
+    for segment in rest {
+        value = match (segment, value) {
+            (Segment::Map { key }, toml::Value::Table(t)) => t.get_mut(key.as_str())?,
+            (Segment::Seq { index }, toml::Value::Array(items)) => items.get_mut(*index)?,
+            _ => return None,
+        };
+    }
+    value.as_table_mut()
+}
+
+/// Find the first ACK for data that had not arrived by then. Packet numbers are
+/// acknowledged one by one, as SR does, so an ACK must name a packet that arrived or the
+/// one after it. Byte numbers are cumulative, so an ACK must not pass the end of the bytes
+/// that arrived without a gap from the lowest one.
+fn first_optimistic_ack(trace: &[AckTraceEntry], unit: SeqUnit) -> Option<(u64, u32)> {
+    let mut packets = HashSet::new();
+    // Arrived byte ranges, merged: start -> end.
+    let mut ranges: BTreeMap<u64, u64> = BTreeMap::new();
+    for entry in trace {
+        match *entry {
+            AckTraceEntry::Arrived { seq, len, .. } => match unit {
+                SeqUnit::Packet => {
+                    packets.insert(u64::from(seq));
+                }
+                SeqUnit::Byte => {
+                    merge_range(&mut ranges, u64::from(seq), u64::from(seq) + u64::from(len))
+                }
+            },
+            AckTraceEntry::Acked { time, ack } => {
+                let ack64 = u64::from(ack);
+                let honest = match unit {
+                    SeqUnit::Packet => {
+                        packets.contains(&ack64)
+                            || ack64
+                                .checked_sub(1)
+                                .is_some_and(|seq| packets.contains(&seq))
+                    }
+                    SeqUnit::Byte => ranges
+                        .first_key_value()
+                        .is_some_and(|(&start, &end)| start <= ack64 && ack64 <= end),
+                };
+                if !honest {
+                    return Some((time, ack));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Add `[start, end)` to the disjoint, non-adjacent `ranges`, merging what it touches.
+fn merge_range(ranges: &mut BTreeMap<u64, u64>, mut start: u64, mut end: u64) {
+    if let Some((&before, &before_end)) = ranges.range(..=start).next_back()
+        && before_end >= start
+    {
+        start = before;
+        end = end.max(before_end);
+    }
+    while let Some((&next, &next_end)) = ranges.range(start..).next() {
+        if next > end {
+            break;
+        }
+        end = end.max(next_end);
+        ranges.remove(&next);
+    }
+    ranges.insert(start, end);
+}
+
+/// Find the first data packet over one byte sent while the sender's last advertised window was 0.
+fn first_zero_window_send(trace: &[FlowTraceEntry]) -> Option<(u64, usize)> {
+    let mut window = None;
+    for entry in trace {
+        match *entry {
+            FlowTraceEntry::Window { window: w, .. } => window = Some(w),
+            FlowTraceEntry::Data { time, len } => {
+                if window == Some(0) && len > 1 {
+                    return Some((time, len));
+                }
+            }
+            FlowTraceEntry::Probe { .. } => {}
+        }
+    }
+    None
+}
+
+/// Find the first time a zero window reopened while data was pending and the sender then
+/// stayed silent for more than `within_ms` (counting up to `end`).
+fn first_stalled_reopen(trace: &[FlowTraceEntry], within_ms: u64, end: u64) -> Option<u64> {
+    let mut window = None;
+    let mut reopened_at = None;
+    for entry in trace {
+        match *entry {
+            FlowTraceEntry::Window {
+                time,
+                window: w,
+                app_data_pending,
+            } => {
+                if window == Some(0) && w > 0 && app_data_pending && reopened_at.is_none() {
+                    reopened_at = Some(time);
+                }
+                window = Some(w);
+            }
+            FlowTraceEntry::Data { time, .. } => {
+                if let Some(reopened) = reopened_at.take()
+                    && time - reopened > within_ms
+                {
+                    return Some(reopened);
+                }
+            }
+            FlowTraceEntry::Probe { .. } => {}
+        }
+    }
+    reopened_at.filter(|reopened| end - reopened > within_ms)
+}
+
+/// Find the start of the first gap longer than `max_interval_ms` in which the sender left a
+/// zero window unprobed while data was pending. Gaps run from the window closing or the
+/// previous probe to the next probe, the window reopening, or `end`.
+fn first_probe_gap(trace: &[FlowTraceEntry], max_interval_ms: u64, end: u64) -> Option<u64> {
+    let mut silent_since = None;
+    for entry in trace {
+        match *entry {
+            FlowTraceEntry::Window {
+                time,
+                window,
+                app_data_pending,
+            } => {
+                if window == 0 && app_data_pending {
+                    silent_since.get_or_insert(time);
+                } else if let Some(since) = silent_since.take()
+                    && time - since > max_interval_ms
+                {
+                    return Some(since);
+                }
+            }
+            FlowTraceEntry::Probe { time } => {
+                if let Some(since) = silent_since.replace(time)
+                    && time - since > max_interval_ms
+                {
+                    return Some(since);
+                }
+            }
+            FlowTraceEntry::Data { .. } => {}
+        }
+    }
+    silent_since.filter(|since| end.saturating_sub(*since) > max_interval_ms)
+}
+
+/// Describe the first node that still has armed timers.
+fn unbalanced_timers(sim: &Simulator) -> Option<String> {
+    [NodeId::Sender, NodeId::Receiver]
+        .into_iter()
+        .find_map(|node| {
+            let stats = sim.timer_stats(node);
+            (stats.armed > 0).then(|| {
+                Msg::TimerCounts.fill(&[
+                    &node_name(node),
+                    &stats.started,
+                    &stats.fired,
+                    &stats.cancelled,
+                    &stats.armed,
+                ])
+            })
+        })
+}
+
+/// Register a scenario's app sends and deterministic faults with the simulator.
+pub fn configure_actions(sim: &mut Simulator, actions: &[TestAction]) {
+    for action in actions {
         match action {
             TestAction::AppSend { time, data } => {
                 sim.schedule_app_send(*time, data.as_bytes().to_vec());
             }
+            TestAction::AppSendWhen { condition, data } => {
+                sim.schedule_app_send_when(*condition, data.as_bytes().to_vec());
+            }
+            TestAction::MediaStream {
+                start,
+                frames,
+                interval_ms,
+                frame_size,
+                deadline_ms,
+            } => {
+                for index in 0..*frames {
+                    let time = start + u64::from(index) * interval_ms;
+                    let data = workload::media_frame(index, *frame_size);
+                    sim.schedule_frame(time, data, time + deadline_ms);
+                }
+            }
             TestAction::DropNextFromSenderSeq { seq } => {
                 sim.add_drop_sender_seq_once(*seq);
             }
             TestAction::CorruptNextFromSenderSeq { seq } => {
                 sim.add_corrupt_sender_seq_once(*seq);
             }
+            TestAction::TruncateNextFromSenderSeq { seq, len } => {
+                sim.add_truncate_sender_seq_once(*seq, *len);
+            }
+            TestAction::TamperDuplicateNextFromSenderSeq { seq } => {
+                sim.add_tamper_duplicate_sender_seq_once(*seq);
+            }
             TestAction::DropNextFromReceiverAck { ack } => {
                 sim.add_drop_receiver_ack_once(*ack);
             }
+            TestAction::DropFromSenderSeq {
+                seq,
+                occurrence,
+                count,
+            } => {
+                sim.add_drop_sender_seq(*seq, *occurrence, *count);
+            }
+            TestAction::DropFromReceiverAck {
+                ack,
+                occurrence,
+                count,
+            } => {
+                sim.add_drop_receiver_ack(*ack, *occurrence, *count);
+            }
+            TestAction::DropNextWithPayload { from, payload } => {
+                sim.add_drop_payload_once(*from, payload.clone());
+            }
+            TestAction::DelayNextWithPayload {
+                from,
+                payload,
+                delay_ms,
+            } => {
+                sim.add_delay_payload_once(*from, payload.clone(), *delay_ms);
+            }
+            TestAction::RestartNode { node, time } => {
+                sim.schedule_restart(*time, *node);
+            }
+            TestAction::SetLatency { time, min, max } => {
+                sim.schedule_latency_change(*time, *min, *max);
+            }
+            TestAction::Shutdown { time } => {
+                sim.schedule_shutdown(*time);
+            }
+            TestAction::HalfClose { time, node } => {
+                sim.schedule_half_close(*time, *node);
+            }
+            TestAction::Annotate { time, text } => {
+                sim.add_annotation(*time, text.clone());
+            }
+            TestAction::InjectPacket {
+                time,
+                to,
+                seq,
+                ack,
+                flags,
+                window,
+                checksum,
+                src_port,
+                dst_port,
+                urgent_ptr,
+                payload,
+            } => {
+                let header = TcpHeader {
+                    src_port: *src_port,
+                    dst_port: *dst_port,
+                    seq_num: *seq,
+                    ack_num: *ack,
+                    flags: *flags,
+                    window_size: *window,
+                    checksum: *checksum,
+                    urgent_ptr: *urgent_ptr,
+                };
+                sim.inject_packet(*time, *to, Packet::new(header, payload.clone()));
+            }
         }
     }
+}
 
-    // Call init after we've configured the simulator
-    sim.init();
+/// Run a scenario and check its assertions.
+///
+/// With `strict` (grading mode), deliveries must also match a payload that arrived at the
+/// delivering node, and any mismatch fails the run as an integrity violation.
+pub fn run_scenario(
+    scenario: &TestScenario,
+    sender: Box<dyn TransportProtocol>,
+    receiver: Box<dyn TransportProtocol>,
+    strict: bool,
+) -> Result<SimulationReport, ScenarioError> {
+    run_scenario_with_factories(scenario, sender, receiver, strict, Vec::new())
+}
 
-    // Max duration check
-    let max_duration = scenario
-        .assertions
-        .iter()
-        .find_map(|a| {
-            if let TestAssertion::MaxDuration { ms } = a {
-                Some(*ms)
-            } else {
-                None
+/// Like [`run_scenario`], with the factories that `restart_node` actions use to re-create
+/// a node's protocol. A scenario that restarts a node without a factory fails up front.
+pub fn run_scenario_with_factories(
+    scenario: &TestScenario,
+    sender: Box<dyn TransportProtocol>,
+    receiver: Box<dyn TransportProtocol>,
+    strict: bool,
+    factories: Vec<(NodeId, ProtocolFactory)>,
+) -> Result<SimulationReport, ScenarioError> {
+    let options = RunOptions {
+        strict,
+        factories,
+        ..Default::default()
+    };
+    run_scenario_with(scenario, sender, receiver, options).into_result()
+}
+
+/// How [`run_scenario_with`] runs a scenario.
+#[derive(Default)]
+pub struct RunOptions {
+    /// Grading mode; see [`run_scenario`].
+    pub strict: bool,
+    /// Factories for `restart_node` actions; see [`run_scenario_with_factories`].
+    pub factories: Vec<(NodeId, ProtocolFactory)>,
+    /// Advice added to the report of a failed run; the built-in one by default.
+    pub hints: HintCatalog,
+    /// Rust-side checks of every event; the first that fails ends the run as a
+    /// `CheckFailed`.
+    pub checkers: Vec<Checker>,
+    /// Overrides the scenario's `assertion_policy`, e.g. for a whole suite.
+    pub assertion_policy: Option<AssertionPolicy>,
+    /// Replaces the scenario's seed with one derived for a student.
+    pub student_seed: Option<StudentSeed>,
+    /// Record the engine's low-level decisions; see `Simulator::set_debug_events`.
+    pub debug_events: bool,
+    /// Values the protocols carry between practice runs; ignored in grading mode
+    /// (`strict`). The updated store comes back in the report.
+    pub scratch_store: Option<ScratchStore>,
+}
+
+/// A per-student seed: a run's seed becomes a hash of the scenario's name, `student` and
+/// `base_seed`, so each student faces different but statistically equivalent randomness
+/// and a grader can rerun exactly what one of them saw.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StudentSeed {
+    pub student: String,
+    pub base_seed: u64,
+}
+
+impl StudentSeed {
+    /// The derivation of the seed for the scenario named `scenario`. The hash is FNV-1a
+    /// with a SplitMix64 finish, so the seed is the same on every platform and release.
+    pub fn derive(&self, scenario: &str) -> SeedDerivation {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut feed = |bytes: &[u8]| {
+            for byte in bytes {
+                hash = (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3);
             }
+        };
+        for field in [scenario.as_bytes(), self.student.as_bytes()] {
+            feed(&(field.len() as u64).to_le_bytes());
+            feed(field);
+        }
+        feed(&self.base_seed.to_le_bytes());
+        let mut seed = hash.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        seed = (seed ^ (seed >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        seed = (seed ^ (seed >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        SeedDerivation {
+            scenario: scenario.to_string(),
+            student: self.student.clone(),
+            base_seed: self.base_seed,
+            seed: seed ^ (seed >> 31),
+        }
+    }
+
+    /// Give `scenario` its derived seed.
+    pub fn apply(&self, scenario: &mut TestScenario) -> SeedDerivation {
+        let derivation = self.derive(&scenario.name);
+        scenario.config.seed = Some(derivation.seed);
+        derivation
+    }
+}
+
+/// How a run's seed was derived from a [`StudentSeed`], in
+/// `SimulationReport::seed_derivation`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeedDerivation {
+    pub scenario: String,
+    pub student: String,
+    pub base_seed: u64,
+    pub seed: u64,
+}
+
+/// A metric that left its band from the scenario's `metric_bands`, in
+/// `SimulationReport::metric_band_warnings`. A warning, not a failure.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MetricBandWarning {
+    pub band: MetricBand,
+    /// When the metric was first outside the band.
+    pub first_at: u64,
+    /// The value furthest outside it.
+    pub worst: f64,
+    /// Samples from `after_ms` on that were outside the band, of `samples`.
+    pub outside: usize,
+    pub samples: usize,
+}
+
+impl MetricBandWarning {
+    /// E.g. "cwnd left its target band (cwnd <= 64) at 1200 ms: 14 of 80 samples outside,
+    /// furthest at 96".
+    pub fn message(&self) -> String {
+        Msg::MetricLeftBand.fill(&[
+            &self.band.metric,
+            &self.band,
+            &self.first_at,
+            &self.outside,
+            &self.samples,
+            &self.worst,
+        ])
+    }
+}
+
+/// The `bands` the recorded `metrics` left. Metrics never recorded are not warned about.
+pub fn metric_band_warnings(
+    metrics: &HashMap<String, Vec<(u64, f64)>>,
+    bands: &[MetricBand],
+) -> Vec<MetricBandWarning> {
+    bands
+        .iter()
+        .filter_map(|band| {
+            let samples: Vec<(u64, f64)> = metrics
+                .get(&band.metric)?
+                .iter()
+                .copied()
+                .filter(|&(time, _)| time >= band.after_ms)
+                .collect();
+            let outside: Vec<(u64, f64)> = samples
+                .iter()
+                .copied()
+                .filter(|&(_, value)| !band.contains(value))
+                .collect();
+            let &(first_at, _) = outside.first()?;
+            // How far a value lies past the bound it broke.
+            let excess = |value: f64| {
+                let below = band.min.map_or(0.0, |min| min - value);
+                let above = band.max.map_or(0.0, |max| value - max);
+                below.max(above)
+            };
+            let worst = outside
+                .iter()
+                .map(|&(_, value)| value)
+                .max_by(|a, b| excess(*a).total_cmp(&excess(*b)))?;
+            Some(MetricBandWarning {
+                band: band.clone(),
+                first_at,
+                worst,
+                outside: outside.len(),
+                samples: samples.len(),
+            })
         })
-        .unwrap_or(10000); // Default 10s
+        .collect()
+}
+
+/// How one of a scenario's assertions went, in `SimulationReport::assertions`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssertionOutcome {
+    /// Position in the scenario's `assertions`, from 0.
+    pub index: usize,
+    pub status: AssertionStatus,
+    /// Why it failed; `None` unless `status` is `Failed`.
+    pub details: Option<String>,
+    /// Sim time (ms) at which a passed assertion first held, e.g. when the data it expects
+    /// was delivered; for `max_duration`, when the run ended. `None` for assertions about
+    /// the whole run, such as `max_in_flight`, and for ones that did not pass.
+    #[serde(default)]
+    pub satisfied_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AssertionStatus {
+    Passed,
+    Failed,
+    /// Not checked because an earlier one failed under `assertion_policy = "first_failure"`.
+    Skipped,
+}
+
+/// Outcome of [`run_scenario_with`].
+#[derive(Debug, Clone)]
+pub struct ScenarioResult {
+    /// The run's report, also when it failed. `None` only for an `Environment` failure.
+    pub report: Option<SimulationReport>,
+    /// Empty when the scenario passed. A timeout or integrity violation is the only failure;
+    /// otherwise every assertion that did not hold is listed, in scenario order, except that
+    /// a deadlock comes first in place of the delivery assertions it made fail. A shutdown
+    /// that left data unsent comes before the assertions. Under `first_failure`, only the
+    /// first failed assertion is checked and listed; `report.assertions` has every outcome.
+    pub failures: Vec<ScenarioError>,
+}
+
+impl ScenarioResult {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// How many of the scenario's assertions passed, with the failed and skipped ones by
+    /// index, e.g. `3 of 5 assertions passed; failed: assertions[1], assertions[4]`.
+    /// `None` if the run ended before they were checked.
+    pub fn assertion_tally(&self) -> Option<String> {
+        let outcomes = &self.report.as_ref()?.assertions;
+        if outcomes.is_empty() {
+            return None;
+        }
+        let with = |status| {
+            let which: Vec<String> = outcomes
+                .iter()
+                .filter(|outcome| outcome.status == status)
+                .map(|outcome| format!("assertions[{}]", outcome.index))
+                .collect();
+            which.join(", ")
+        };
+        let passed = outcomes
+            .iter()
+            .filter(|outcome| outcome.status == AssertionStatus::Passed)
+            .count();
+        let mut tally = Msg::AssertionTally.fill(&[&passed, &outcomes.len()]);
+        for (status, msg) in [
+            (AssertionStatus::Failed, Msg::AssertionTallyFailed),
+            (AssertionStatus::Skipped, Msg::AssertionTallySkipped),
+        ] {
+            let which = with(status);
+            if !which.is_empty() {
+                tally.push_str(&msg.fill(&[&which]));
+            }
+        }
+        Some(tally)
+    }
 
-    // Run loop
-    while sim.step() {
-        if sim.current_time() > max_duration {
-            return Err(anyhow!("Test timed out after {} ms", max_duration));
+    /// The report if the scenario passed, otherwise its first failure.
+    pub fn into_result(self) -> Result<SimulationReport, ScenarioError> {
+        match (self.failures.into_iter().next(), self.report) {
+            (Some(failure), _) => Err(failure),
+            (None, Some(report)) => Ok(report),
+            (None, None) => unreachable!("only environment failures have no report"),
         }
     }
+}
 
-    // Final assertions
-    for assertion in &scenario.assertions {
-        match assertion {
-            TestAssertion::DataDelivered { data } => {
-                let found = sim.delivered_data.iter().any(|d| d == data.as_bytes());
-                if !found {
-                    return Err(anyhow!(
-                        "Assertion Failed: Data {:?} was not delivered",
-                        data
-                    ));
+/// Run a scenario with the given protocols and check every assertion, returning the report
+/// and all failures instead of stopping at the first, e.g. for a `#[test]` that grades a
+/// protocol:
+///
+/// ```ignore
+/// let scenario = scenario_runner::load_scenario("tests/test_rdt3.toml", true)?;
+/// let result = run_scenario_with(&scenario, Box::new(MySender::default()),
+///     Box::new(MyReceiver::default()), RunOptions { strict: true, ..Default::default() });
+/// assert!(result.passed(), "{:?}", result.failures);
+/// ```
+pub fn run_scenario_with(
+    scenario: &TestScenario,
+    sender: Box<dyn TransportProtocol>,
+    receiver: Box<dyn TransportProtocol>,
+    options: RunOptions,
+) -> ScenarioResult {
+    let RunOptions {
+        strict,
+        factories,
+        hints,
+        checkers,
+        assertion_policy,
+        student_seed,
+        debug_events,
+        scratch_store,
+    } = options;
+    let mut seeded = None;
+    let derivation = student_seed.map(|seed| {
+        let scenario = seeded.insert(scenario.clone());
+        seed.apply(scenario)
+    });
+    let scenario = seeded.as_ref().unwrap_or(scenario);
+    let policy = assertion_policy.unwrap_or(scenario.assertion_policy);
+    let setup = |builder: SimulatorBuilder| {
+        let builder = builder
+            .strict_integrity(strict)
+            .debug_events(debug_events)
+            .factories(factories)
+            .checkers(checkers);
+        match scratch_store.filter(|_| !strict) {
+            Some(store) => builder.scratch_store(store),
+            None => builder,
+        }
+    };
+    let mut result = run_and_check(scenario, sender, receiver, setup, policy);
+    let passed = result.passed();
+    if let Some(report) = &mut result.report {
+        report.seed_derivation = derivation;
+        if !passed {
+            report.hints = hints.hints_for(report);
+        }
+    }
+    result
+}
+
+fn run_and_check(
+    scenario: &TestScenario,
+    sender: Box<dyn TransportProtocol>,
+    receiver: Box<dyn TransportProtocol>,
+    setup: impl FnOnce(SimulatorBuilder) -> SimulatorBuilder,
+    policy: AssertionPolicy,
+) -> ScenarioResult {
+    info!("Running Scenario: {}", scenario.name);
+    info!("Description: {}", scenario.description);
+
+    let mut config = SimConfig::default();
+    scenario.config.apply_to(&mut config);
+
+    let built = setup(Simulator::builder(config, sender, receiver))
+        .init_params(scenario.params.clone())
+        .build();
+    let mut sim = match built {
+        Ok(sim) => sim,
+        Err(err) => {
+            return ScenarioResult {
+                report: None,
+                failures: vec![ScenarioError::Parse {
+                    details: Msg::InvalidConfig.fill(&[&err]),
+                }],
+            };
+        }
+    };
+    for action in &scenario.actions {
+        if let TestAction::RestartNode { node, .. } = action
+            && !sim.has_protocol_factory(*node)
+        {
+            return ScenarioResult {
+                report: None,
+                failures: vec![ScenarioError::Environment {
+                    details: Msg::RestartNeedsFactory.fill(&[&node_name(*node)]),
+                }],
+            };
+        }
+    }
+
+    // Configure actions (App sends, deterministic faults, etc.)
+    configure_actions(&mut sim, &scenario.actions);
+
+    let max_duration = scenario.max_duration();
+    sim.set_end_time(max_duration);
+
+    // Call init after we've configured the simulator, then run until the events run out
+    // or the time limit passes. A panicking protocol abandons the run.
+    let finished = panic::catch_unwind(AssertUnwindSafe(|| {
+        sim.init();
+        while sim.step() {
+            if sim.current_time() > max_duration {
+                return false;
+            }
+        }
+        true
+    }));
+    match finished {
+        Ok(true) => {}
+        Ok(false) => {
+            let balanced_asserted = scenario
+                .assertions
+                .iter()
+                .any(|a| matches!(a, TestAssertion::TimersBalanced));
+            let details = match unbalanced_timers(&sim) {
+                Some(problem) if balanced_asserted => {
+                    Msg::TimedOutUnbalanced.failed(&[&max_duration, &problem])
                 }
+                _ => Msg::TimedOut.fill(&[&max_duration]),
+            };
+            return ScenarioResult {
+                report: Some(sim.export_report()),
+                failures: vec![ScenarioError::Timeout {
+                    limit_ms: max_duration,
+                    details,
+                    explanation: explain(&sim, None),
+                }],
+            };
+        }
+        Err(payload) => {
+            let reason = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            return ScenarioResult {
+                report: Some(sim.export_report()),
+                failures: vec![ScenarioError::StudentRuntime {
+                    details: Msg::ProtocolPanicked.fill(&[&sim.current_time(), &reason]),
+                }],
+            };
+        }
+    }
+
+    if sim.pending_conditional_sends() > 0 {
+        warn!(
+            "{} app_send_when action(s) never triggered",
+            sim.pending_conditional_sends()
+        );
+    }
+
+    if let Some(anomaly) = sim.clock_anomalies.first() {
+        return ScenarioResult {
+            report: Some(sim.export_report()),
+            failures: vec![ScenarioError::ClockAnomaly {
+                time: anomaly.time,
+                node: anomaly.node,
+                details: Msg::ClockAnomaly.fill(&[
+                    &anomaly.time,
+                    &node_name(anomaly.node),
+                    &anomaly.message,
+                ]),
+            }],
+        };
+    }
+
+    if let Some(failure) = &sim.check_failure {
+        return ScenarioResult {
+            report: Some(sim.export_report()),
+            failures: vec![ScenarioError::CheckFailed {
+                time: failure.time,
+                checker: failure.checker.clone(),
+                details: Msg::CheckFailed.fill(&[
+                    &failure.checker,
+                    &failure.time,
+                    &failure.message,
+                ]),
+            }],
+        };
+    }
+
+    if let Some(violation) = sim.integrity_violations.first() {
+        return ScenarioResult {
+            report: Some(sim.export_report()),
+            failures: vec![ScenarioError::Integrity {
+                time: violation.time,
+                node: violation.node,
+                details: Msg::IntegrityViolation.fill(&[
+                    &violation.time,
+                    &node_name(violation.node),
+                    &violation.message,
+                ]),
+            }],
+        };
+    }
+
+    let mut outcomes = Vec::with_capacity(scenario.assertions.len());
+    let mut failed = Vec::new();
+    for (index, assertion) in scenario.assertions.iter().enumerate() {
+        let mut outcome = AssertionOutcome {
+            index,
+            status: AssertionStatus::Skipped,
+            details: None,
+            satisfied_at: None,
+        };
+        if policy == AssertionPolicy::All || failed.is_empty() {
+            match check_assertion(&sim, assertion, max_duration) {
+                Ok(satisfied_at) => {
+                    outcome.status = AssertionStatus::Passed;
+                    outcome.satisfied_at = satisfied_at;
+                }
+                Err(err) => {
+                    let details = format!("{err:#}");
+                    outcome.status = AssertionStatus::Failed;
+                    outcome.details = Some(details.clone());
+                    failed.push(ScenarioError::AssertionFailed {
+                        which: index,
+                        assertion: Box::new(assertion.clone()),
+                        details,
+                        explanation: explain(&sim, Some(assertion)),
+                    });
+                }
+            }
+        }
+        outcomes.push(outcome);
+    }
+    let mut failures: Vec<ScenarioError> = not_flushed(&sim).into_iter().chain(failed).collect();
+    let stalled = |failure: &ScenarioError| matches!(failure, ScenarioError::AssertionFailed { assertion, .. } if about_delivery(assertion));
+    if failures.iter().any(stalled)
+        && let Some(deadlock) = deadlock(&sim)
+    {
+        failures.retain(|failure| !stalled(failure));
+        failures.insert(0, deadlock);
+    }
+    if failures.is_empty() {
+        info!("{}", Msg::ScenarioPassed.text());
+    }
+    let mut report = sim.export_report();
+    report.assertions = outcomes;
+    report.metric_band_warnings = metric_band_warnings(&report.metrics, &scenario.metric_bands);
+    ScenarioResult {
+        report: Some(report),
+        failures,
+    }
+}
+
+/// A `NotFlushed` if the run reached a shutdown but did not deliver everything written before.
+fn not_flushed(sim: &Simulator) -> Option<ScenarioError> {
+    let shutdown = sim.shutdown?;
+    let delivered: u64 = sim.delivered_data.iter().map(|d| d.len() as u64).sum();
+    let delivered = delivered.min(shutdown.app_bytes);
+    (delivered < shutdown.app_bytes).then(|| ScenarioError::NotFlushed {
+        time: shutdown.time,
+        written: shutdown.app_bytes,
+        delivered,
+        details: Msg::NotFlushed.fill(&[&shutdown.time, &shutdown.app_bytes, &delivered]),
+    })
+}
+
+/// A `Deadlock` if the finished run left application data undelivered.
+fn deadlock(sim: &Simulator) -> Option<ScenarioError> {
+    let sent = sim.app_bytes_sent();
+    let delivered: u64 = sim.delivered_data.iter().map(|d| d.len() as u64).sum();
+    if delivered >= sent {
+        return None;
+    }
+    let segments = |kind: PacketEventKind, node: NodeId| {
+        sim.packet_log
+            .iter()
+            .filter(move |e| e.kind == kind && e.node == node && e.len > 0)
+    };
+    // The receiver holds the data if every segment the sender sent reached it and they
+    // add up to at least what the application sent.
+    let arrived: BTreeSet<(u32, usize)> = segments(PacketEventKind::Arrived, NodeId::Receiver)
+        .map(|e| (e.seq, e.len))
+        .collect();
+    let arrived_bytes: u64 = segments(PacketEventKind::Arrived, NodeId::Receiver)
+        .map(|e| e.len as u64)
+        .sum();
+    let all_arrived =
+        segments(PacketEventKind::Sent, NodeId::Sender).all(|e| arrived.contains(&(e.seq, e.len)));
+    let waiting_node = if all_arrived && arrived_bytes >= sent {
+        NodeId::Receiver
+    } else {
+        NodeId::Sender
+    };
+    let last_event = sim.packet_log.last().cloned().map(Box::new);
+    let last = match &last_event {
+        Some(e) => Msg::LastPacketEvent.fill(&[
+            &node_name(e.node),
+            &event_label(e.kind),
+            &e.seq,
+            &e.ack,
+            &e.time,
+        ]),
+        None => Msg::NoPacketEvent.text().to_string(),
+    };
+    Some(ScenarioError::Deadlock {
+        waiting_node,
+        last_event,
+        time: sim.last_packet_time,
+        details: Msg::Deadlock.fill(&[
+            &sim.last_packet_time,
+            &delivered,
+            &sent,
+            &node_name(waiting_node),
+            &last,
+        ]),
+        explanation: explain(sim, None),
+    })
+}
+
+/// `kind` as written in a `packet_sequence` step.
+fn event_label(kind: PacketEventKind) -> &'static str {
+    match kind {
+        PacketEventKind::Sent => "SEND",
+        PacketEventKind::Dropped => "DROP",
+        PacketEventKind::Corrupted => "CORRUPT",
+        PacketEventKind::Arrived => "ARRIVE",
+        PacketEventKind::Injected => "INJECT",
+    }
+}
+
+/// Check one of a scenario's final assertions against a finished run.
+fn check_assertion(
+    sim: &Simulator,
+    assertion: &TestAssertion,
+    max_duration: u64,
+) -> anyhow::Result<Option<u64>> {
+    let satisfied_at = match assertion {
+        TestAssertion::DataDelivered { data } => {
+            let Some(found) = sim.delivered_data.iter().position(|d| d == data.as_bytes()) else {
+                bail!(Msg::NotDelivered.failed(&[&format!("{data:?}")]));
+            };
+            Some(sim.delivery_times[found])
+        }
+        TestAssertion::SenderPacketCount { min, max } => {
+            if sim.sender_packet_count < *min {
+                bail!(Msg::SenderPacketsBelow.failed(&[&sim.sender_packet_count, min]));
+            }
+            if let Some(max) = max
+                && sim.sender_packet_count > *max
+            {
+                bail!(Msg::SenderPacketsAbove.failed(&[&sim.sender_packet_count, max]));
+            }
+            // Met with the `min`th packet the sender sent.
+            let sends = sim
+                .packet_log
+                .iter()
+                .filter(|p| p.kind == PacketEventKind::Sent && p.node == NodeId::Sender);
+            min.checked_sub(1)
+                .and_then(|nth| sends.clone().nth(nth as usize))
+                .map(|p| p.time)
+        }
+        TestAssertion::SenderWindowMax { min, max } => {
+            let max_win = sim.sender_window_sizes.iter().copied().max().unwrap_or(0);
+            if max_win < *min {
+                bail!(Msg::WindowMaxBelow.failed(&[&max_win, min]));
             }
-            TestAssertion::SenderPacketCount { min, max } => {
-                if sim.sender_packet_count < *min {
-                    return Err(anyhow!(
-                        "Assertion Failed: Sender sent {} packets, expected min {}",
-                        sim.sender_packet_count,
-                        min
-                    ));
+            if let Some(m) = max
+                && max_win > *m
+            {
+                bail!(Msg::WindowMaxAbove.failed(&[&max_win, m]));
+            }
+            None
+        }
+        TestAssertion::SenderWindowDrop {
+            from_at_least,
+            to_at_most,
+        } => {
+            let mut seen_high = false;
+            let mut seen_drop = false;
+            for w in &sim.sender_window_sizes {
+                if !seen_high && *w >= *from_at_least {
+                    seen_high = true;
+                } else if seen_high && *w <= *to_at_most {
+                    seen_drop = true;
+                    break;
                 }
-                if let Some(max) = max
-                    && sim.sender_packet_count > *max
-                {
-                    return Err(anyhow!(
-                        "Assertion Failed: Sender sent {} packets, expected max {}",
-                        sim.sender_packet_count,
-                        max
-                    ));
-                }
-            }
-            TestAssertion::SenderWindowMax { min, max } => {
-                let max_win = sim.sender_window_sizes.iter().copied().max().unwrap_or(0);
-                if max_win < *min {
-                    return Err(anyhow!(
-                        "Assertion Failed: Sender window max {} < expected min {}",
-                        max_win,
-                        min
-                    ));
-                }
-                if let Some(m) = max
-                    && max_win > *m
+            }
+            if !seen_high || !seen_drop {
+                bail!(Msg::WindowNoDrop.failed(&[from_at_least, to_at_most]));
+            }
+            None
+        }
+        // Checked while running; met when the run ended.
+        TestAssertion::MaxDuration { .. } => Some(sim.current_time()),
+        TestAssertion::LogContains { node, pattern } => {
+            let re = Regex::new(pattern)
+                .with_context(|| Msg::InvalidLogPattern.fill(&[&format!("{pattern:?}")]))?;
+            let Some(log) = sim
+                .logs
+                .iter()
+                .find(|log| log.node == *node && re.is_match(&log.message))
+            else {
+                bail!(Msg::NeverLogged.failed(&[&node_name(*node), &format!("{pattern:?}")]));
+            };
+            Some(log.time)
+        }
+        TestAssertion::MaxInFlight { n } => {
+            let Some(limit) = n.or(sim.config().window_size) else {
+                bail!(Msg::MaxInFlightNeedsWindow.text());
+            };
+            if sim.max_in_flight() > limit as usize {
+                bail!(Msg::TooManyInFlight.failed(&[&sim.max_in_flight(), &limit]));
+            }
+            None
+        }
+        TestAssertion::MaxQueueDelay { ms } => {
+            if sim.config().bottleneck.is_none() {
+                bail!(Msg::MaxQueueDelayNeedsBottleneck.text());
+            }
+            let series = sim.metric_series(QUEUE_DELAY_METRIC).unwrap_or_default();
+            if let Some(&(time, delay)) = series.iter().find(|(_, delay)| *delay > *ms as f64) {
+                bail!(Msg::QueueDelayTooHigh.failed(&[&delay, &time, &ms]));
+            }
+            None
+        }
+        TestAssertion::RespectsZeroWindow => {
+            if let Some((time, len)) = first_zero_window_send(&sim.flow_trace) {
+                bail!(Msg::ZeroWindowSend.failed(&[&len, &time]));
+            }
+            None
+        }
+        TestAssertion::ResumesAfterWindowUpdate { within_ms } => {
+            if let Some(time) =
+                first_stalled_reopen(&sim.flow_trace, *within_ms, sim.current_time())
+            {
+                bail!(Msg::NoResumeAfterWindow.failed(&[&time, within_ms]));
+            }
+            None
+        }
+        TestAssertion::ZeroWindowProbes { max_interval_ms } => {
+            if let Some(time) = first_probe_gap(&sim.flow_trace, *max_interval_ms, max_duration) {
+                bail!(Msg::ZeroWindowUnprobed.failed(&[max_interval_ms, &time]));
+            }
+            None
+        }
+        TestAssertion::HandshakeCompleted { within_ms } => {
+            let connection = &sim.connection;
+            let Some(syn_at) = connection.syn_sent_at else {
+                bail!(Msg::NoSyn.failed(&[]));
+            };
+            match connection.established_at {
+                Some(at) if at - syn_at <= *within_ms => Some(at),
+                Some(at) => {
+                    bail!(Msg::HandshakeSlow.failed(&[&(at - syn_at), within_ms]));
+                }
+                None => {
+                    bail!(Msg::HandshakeStuck.failed(&[&format!("{:?}", connection.state)]));
+                }
+            }
+        }
+        TestAssertion::SynRetransmittedAtMost { n } => {
+            let retransmissions = sim.connection.syn_retransmissions();
+            if retransmissions > *n {
+                bail!(Msg::SynRetransmits.failed(&[&retransmissions, n]));
+            }
+            None
+        }
+        TestAssertion::NoDataBeforeEstablished => {
+            if let Some(time) = sim.connection.early_data_at {
+                bail!(Msg::DataBeforeEstablished.failed(&[&time]));
+            }
+            None
+        }
+        TestAssertion::RespectsMss => {
+            if sim.config().mss.is_none() {
+                bail!(Msg::RespectsMssNeedsMss.text());
+            }
+            if let Some(v) = sim.connection.mss_violation {
+                bail!(Msg::MssExceeded.failed(&[&node_name(v.node), &v.len, &v.time, &v.mss]));
+            }
+            None
+        }
+        TestAssertion::GracefulClose => {
+            let mut closed_at = 0;
+            for node in [NodeId::Sender, NodeId::Receiver] {
+                let fin = sim.connection.fin(node);
+                if fin.sent_at.is_none() {
+                    bail!(Msg::NoFin.failed(&[&node_name(node)]));
+                }
+                let Some(acked_at) = fin.acked_at else {
+                    bail!(Msg::FinNotAcked.failed(&[&node_name(node)]));
+                };
+                closed_at = closed_at.max(acked_at);
+            }
+            Some(closed_at)
+        }
+        TestAssertion::NoDataAfterFin => {
+            if let Some((node, time)) = sim.connection.data_after_fin {
+                bail!(Msg::DataAfterFin.failed(&[&node_name(node), &time]));
+            }
+            None
+        }
+        TestAssertion::DeliversAfterHalfClose { min_bytes } => {
+            if sim.half_closes.is_empty() {
+                bail!(Msg::NoHalfClose.text());
+            }
+            for close in &sim.half_closes {
+                let expected = close.peer_written_after.max(*min_bytes);
+                if close.delivered_after < expected {
+                    bail!(Msg::HalfOpenStalled.failed(&[
+                        &node_name(close.node),
+                        &close.time,
+                        &close.delivered_after,
+                        &node_name(close.node.peer()),
+                        &close.peer_written_after,
+                        &expected,
+                    ]));
+                }
+            }
+            None
+        }
+        TestAssertion::NoPendingTimersAtEnd => {
+            let end = sim.last_packet_time;
+            if let Some(timer) = sim.timer_log.iter().find(|timer| timer.armed_at(end)) {
+                bail!(Msg::TimerArmedAtEnd.failed(&[
+                    &node_name(timer.node),
+                    &timer.timer_id,
+                    &timer.started,
+                    &end
+                ]));
+            }
+            None
+        }
+        TestAssertion::TimersClearedAfterFin { grace_ms } => {
+            let grace = grace_ms.unwrap_or(0);
+            let mut checked = false;
+            for node in [NodeId::Sender, NodeId::Receiver] {
+                let Some(acked_at) = sim.connection.fin(node).acked_at else {
+                    continue;
+                };
+                checked = true;
+                let pending = sim.pending_timers_at(node, acked_at + grace);
+                if !pending.is_empty() {
+                    let ids: Vec<String> = pending.iter().map(|t| t.timer_id.to_string()).collect();
+                    bail!(Msg::TimersAfterFin.failed(&[
+                        &node_name(node),
+                        &pending.len(),
+                        &grace,
+                        &acked_at,
+                        &ids.join(", ")
+                    ]));
+                }
+            }
+            if !checked {
+                bail!(Msg::NoFinAcked.text());
+            }
+            None
+        }
+        TestAssertion::NoSilentAppDataLoss => {
+            let losses = sim.silent_app_losses();
+            if let Some(first) = losses.first() {
+                let bytes: u64 = losses.iter().map(|loss| loss.missing).sum();
+                let range = format!("{}..{}", first.range.start, first.range.end);
+                bail!(Msg::SilentAppLoss.failed(&[&losses.len(), &bytes, &first.time, &range]));
+            }
+            let held = sim.app_backpressure().left_sends;
+            if held > 0 {
+                bail!(Msg::AppSendsStillHeld.failed(&[&held]));
+            }
+            None
+        }
+        TestAssertion::TimersBalanced => {
+            if let Some(problem) = unbalanced_timers(sim) {
+                bail!(Msg::TimersUnbalanced.failed(&[&problem]));
+            }
+            None
+        }
+        TestAssertion::NoSendStorms { max_per_callback } => {
+            let Some(limit) = max_per_callback.or(sim.config().send_storm_threshold) else {
+                bail!(Msg::SendStormNeedsLimit.text());
+            };
+            for node in [NodeId::Sender, NodeId::Receiver] {
+                if let Some((packets, time)) = sim.largest_burst(node)
+                    && packets > limit
                 {
-                    return Err(anyhow!(
-                        "Assertion Failed: Sender window max {} > expected max {}",
-                        max_win,
-                        m
-                    ));
+                    bail!(Msg::SendStorm.failed(&[&node_name(node), &packets, &time, &limit]));
                 }
             }
-            TestAssertion::SenderWindowDrop {
-                from_at_least,
-                to_at_most,
-            } => {
-                let mut seen_high = false;
-                let mut seen_drop = false;
-                for w in &sim.sender_window_sizes {
-                    if !seen_high && *w >= *from_at_least {
-                        seen_high = true;
-                    } else if seen_high && *w <= *to_at_most {
-                        seen_drop = true;
-                        break;
+            None
+        }
+        TestAssertion::MinInterSendGap { us } => {
+            if let Some((gap, time)) = sim.min_send_gap(NodeId::Sender)
+                && gap < *us
+            {
+                bail!(Msg::SendGapTooShort.failed(&[&gap, &time, us]));
+            }
+            None
+        }
+        TestAssertion::MaxBurstSize { packets, gap_us } => {
+            if let Some((burst, time)) = sim.largest_data_burst(NodeId::Sender, gap_us.unwrap_or(1))
+                && burst > *packets
+            {
+                bail!(Msg::BurstTooLarge.failed(&[&burst, &time, packets]));
+            }
+            None
+        }
+        TestAssertion::DeliveryLatency { percentile, max_ms } => {
+            if !(1..=100).contains(percentile) {
+                bail!(Msg::PercentileRange.fill(&[&"delivery_latency", percentile]));
+            }
+            let Some(latency) = sim.delivery_latency.percentile(*percentile) else {
+                bail!(Msg::NoDeliveries.failed(&[]));
+            };
+            if latency > *max_ms {
+                bail!(Msg::LatencyTooHigh.failed(&[percentile, &latency, max_ms]));
+            }
+            None
+        }
+        TestAssertion::RoundTripLatency { percentile, max_ms } => {
+            if !(1..=100).contains(percentile) {
+                bail!(Msg::PercentileRange.fill(&[&"round_trip_latency", percentile]));
+            }
+            if sim.config().app_model != AppModel::Echo {
+                bail!(Msg::RoundTripNeedsEcho.text());
+            }
+            let Some(rtt) = sim.round_trip_latency.percentile(*percentile) else {
+                bail!(Msg::NoEchoes.failed(&[]));
+            };
+            if rtt > *max_ms {
+                bail!(Msg::RoundTripTooHigh.failed(&[percentile, &rtt, max_ms]));
+            }
+            None
+        }
+        TestAssertion::RtoWithin {
+            factor_of_rtt,
+            after_ms,
+        } => {
+            if factor_of_rtt.is_nan() || *factor_of_rtt < 1.0 {
+                bail!(Msg::RtoFactorRange.fill(&[&"rto_within", factor_of_rtt]));
+            }
+            let Some(rtt) = sim.true_rtt(*after_ms) else {
+                bail!(Msg::NoRttSamples.failed(&[after_ms]));
+            };
+            let Some(series) = sim.metric_series(RTO_METRIC) else {
+                bail!(Msg::NoRtoMetric.failed(&[]));
+            };
+            // The last value recorded before the warm-up ends is still in effect after it.
+            let first = series
+                .partition_point(|(time, _)| *time <= *after_ms)
+                .saturating_sub(1);
+            let (low, high) = (rtt / factor_of_rtt, rtt * factor_of_rtt);
+            if let Some((time, rto)) = series[first..]
+                .iter()
+                .find(|(_, rto)| !(low..=high).contains(rto))
+            {
+                bail!(Msg::RtoOutOfRange.failed(&[
+                    &format!("{rto:.0}"),
+                    time,
+                    &format!("{low:.0}"),
+                    &format!("{high:.0}"),
+                    &format!("{rtt:.0}"),
+                ]));
+            }
+            None
+        }
+        TestAssertion::RtoAdapts {
+            within_samples,
+            factor_of_rtt,
+        } => {
+            if factor_of_rtt.is_nan() || *factor_of_rtt < 1.0 {
+                bail!(Msg::RtoFactorRange.fill(&[&"rto_adapts", factor_of_rtt]));
+            }
+            if *within_samples == 0 {
+                bail!(Msg::RtoSamplesRange.text());
+            }
+            if sim.latency_changes.is_empty() {
+                bail!(Msg::RtoAdaptsNeedsChange.text());
+            }
+            let Some(series) = sim.metric_series(RTO_METRIC) else {
+                bail!(Msg::NoRtoMetric.failed(&[]));
+            };
+            for (i, change) in sim.latency_changes.iter().enumerate() {
+                let until = sim
+                    .latency_changes
+                    .get(i + 1)
+                    .map_or(u64::MAX, |next| next.time);
+                let Some(rtt) = sim.true_rtt_between(change.time, until) else {
+                    bail!(Msg::NoRttSamples.failed(&[&change.time]));
+                };
+                let (low, high) = (rtt / factor_of_rtt, rtt * factor_of_rtt);
+                let first = series.partition_point(|(time, _)| *time <= change.time);
+                let samples: Vec<f64> = series[first..]
+                    .iter()
+                    .take(*within_samples)
+                    .map(|(_, rto)| *rto)
+                    .collect();
+                if !samples.iter().any(|rto| (low..=high).contains(rto)) {
+                    let values: Vec<String> =
+                        samples.iter().map(|rto| format!("{rto:.0}")).collect();
+                    bail!(Msg::RtoDidNotAdapt.failed(&[
+                        &change.time,
+                        within_samples,
+                        &values.join(", "),
+                        &format!("{low:.0}"),
+                        &format!("{high:.0}"),
+                        &format!("{rtt:.0}"),
+                    ]));
+                }
+            }
+            None
+        }
+        TestAssertion::AckOverheadBelow { ratio } => {
+            if ratio.is_nan() || *ratio <= 0.0 {
+                bail!(Msg::AckRatioRange.fill(&[ratio]));
+            }
+            let Some(overhead) = sim.traffic.ack_overhead() else {
+                bail!(Msg::NoDataSent.failed(&[]));
+            };
+            if overhead >= *ratio {
+                let (sender, receiver) = (&sim.traffic.from_sender, &sim.traffic.from_receiver);
+                bail!(Msg::AckOverheadTooHigh.failed(&[
+                    &(sender.pure_ack_wire_bytes + receiver.pure_ack_wire_bytes),
+                    &(sender.data_wire_bytes + receiver.data_wire_bytes),
+                    &format!("{overhead:.2}"),
+                    ratio,
+                ]));
+            }
+            None
+        }
+        TestAssertion::PiggybackedAcks { min_fraction } => {
+            if !(0.0..=1.0).contains(min_fraction) {
+                bail!(Msg::PiggybackFractionRange.fill(&[min_fraction]));
+            }
+            let Some(fraction) = sim.traffic.piggybacked_fraction() else {
+                bail!(Msg::NoAcksSent.failed(&[]));
+            };
+            if fraction < *min_fraction {
+                let (sender, receiver) = (&sim.traffic.from_sender, &sim.traffic.from_receiver);
+                let piggybacked = sender.piggybacked_acks + receiver.piggybacked_acks;
+                let pure = sender.pure_ack_packets + receiver.pure_ack_packets;
+                bail!(Msg::TooFewPiggybacked.failed(&[
+                    &piggybacked,
+                    &(piggybacked + pure),
+                    &format!("{:.0}", fraction * 100.0),
+                    &format!("{:.0}", min_fraction * 100.0),
+                ]));
+            }
+            None
+        }
+        TestAssertion::AckClocked { min_fraction } => {
+            if !(0.0..=1.0).contains(min_fraction) {
+                bail!(Msg::AckClockedFractionRange.fill(&[min_fraction]));
+            }
+            let clocking = sim.clocking_stats();
+            let Some(fraction) = clocking.ack_clocked_fraction() else {
+                bail!(Msg::NoDataSent.failed(&[]));
+            };
+            if fraction < *min_fraction {
+                bail!(Msg::TooFewAckClocked.failed(&[
+                    &clocking.ack,
+                    &clocking.total(),
+                    &format!("{:.0}", fraction * 100.0),
+                    &clocking.timer,
+                    &format!("{:.0}", min_fraction * 100.0),
+                ]));
+            }
+            None
+        }
+        TestAssertion::FramesOnTime { min_percent } => {
+            let Some(media) = sim.media.report() else {
+                bail!(Msg::NoMediaFrames.text());
+            };
+            if media.on_time_percent() < *min_percent {
+                bail!(Msg::FramesLate.failed(&[
+                    &format!("{:.1}", media.on_time_percent()),
+                    &media.late,
+                    &media.lost,
+                    &media.frames,
+                    min_percent,
+                ]));
+            }
+            None
+        }
+        TestAssertion::StateSequence {
+            node,
+            states,
+            exact,
+        } => {
+            let visited: Vec<&str> = sim
+                .state_log
+                .iter()
+                .filter(|t| t.node == *node)
+                .map(|t| t.to.as_str())
+                .collect();
+            if let Some(missing) = first_missing_state(&visited, states, *exact) {
+                let visited = if visited.is_empty() {
+                    Msg::NoStatesRecorded.text().to_string()
+                } else {
+                    visited.join(" -> ")
+                };
+                if *exact {
+                    bail!(Msg::StatesNotExact.failed(&[
+                        &node_name(*node),
+                        &visited,
+                        &states.join(" -> ")
+                    ]));
+                }
+                bail!(Msg::StateMissing.failed(&[
+                    &node_name(*node),
+                    &states[missing],
+                    &(missing + 1),
+                    &visited
+                ]));
+            }
+            // Met when the node entered the last expected state, matched in order.
+            let mut transitions = sim.state_log.iter().filter(|t| t.node == *node);
+            states
+                .iter()
+                .map(|state| transitions.find(|t| t.to == *state).map(|t| t.time))
+                .last()
+                .flatten()
+        }
+        TestAssertion::PacketSent {
+            node,
+            seq,
+            ack,
+            flags: wanted_flags,
+            after_receiving,
+            within_ms,
+        } => {
+            let wanted = describe_header(*seq, *ack, *wanted_flags);
+            let (start, reference) = match after_receiving {
+                Some(trigger) => {
+                    let received = sim.packet_log.iter().find(|p| {
+                        p.node == *node
+                            && matches!(
+                                p.kind,
+                                PacketEventKind::Arrived | PacketEventKind::Injected
+                            )
+                            && p.flags & trigger == *trigger
+                    });
+                    let Some(received) = received else {
+                        bail!(
+                            Msg::NeverReceivedFlags
+                                .failed(&[&node_name(*node), &flags::names(*trigger)])
+                        );
+                    };
+                    (
+                        received.time,
+                        Msg::ReceivingFlags.fill(&[&flags::names(*trigger)]),
+                    )
+                }
+                None => (0, Msg::RunStart.text().to_string()),
+            };
+            let sent = sim.packet_log.iter().find(|p| {
+                p.kind == PacketEventKind::Sent
+                    && p.node == *node
+                    && p.time >= start
+                    && seq.is_none_or(|seq| p.seq == seq)
+                    && ack.is_none_or(|ack| p.ack == ack)
+                    && wanted_flags.is_none_or(|bits| p.flags & bits == bits)
+            });
+            let Some(sent) = sent else {
+                bail!(Msg::PacketNeverSent.failed(&[&node_name(*node), &wanted]));
+            };
+            if let Some(limit) = within_ms
+                && sent.time - start > *limit
+            {
+                bail!(Msg::PacketSentLate.failed(&[
+                    &node_name(*node),
+                    &wanted,
+                    &(sent.time - start),
+                    &reference,
+                    limit
+                ]));
+            }
+            Some(sent.time)
+        }
+        TestAssertion::PacketSequence { node, expect } => {
+            let steps: Vec<PacketStep> = expect
+                .iter()
+                .cloned()
+                .map(|mut step| {
+                    step.node = step.node.or(*node);
+                    step
+                })
+                .collect();
+            match match_steps(&sim.packet_log, &steps) {
+                Ok(last) => last,
+                Err(miss) => {
+                    let number = miss.step + 1;
+                    let step = &expect[miss.step];
+                    if let Some(gap) = miss.near_gap {
+                        bail!(Msg::PacketStepTiming.failed(&[&number, step, &gap]));
                     }
+                    bail!(Msg::PacketStepMissing.failed(&[&number, step, &miss.after]));
                 }
-                if !seen_high || !seen_drop {
-                    return Err(anyhow!(
-                        "Assertion Failed: Sender window did not drop from >= {} down to <= {}",
-                        from_at_least,
-                        to_at_most
-                    ));
+            }
+        }
+        TestAssertion::NoOptimisticAcks { unit } => {
+            if let Some((time, ack)) = first_optimistic_ack(&sim.ack_trace, *unit) {
+                bail!(Msg::OptimisticAck.failed(&[&ack, &time]));
+            }
+            None
+        }
+    };
+    Ok(satisfied_at)
+}
+
+/// `seq=… ack=… flags=…` for the fields a `packet_sent` assertion constrains.
+fn describe_header(seq: Option<u32>, ack: Option<u32>, bits: Option<u8>) -> String {
+    let fields: Vec<String> = [
+        seq.map(|seq| format!("seq={seq}")),
+        ack.map(|ack| format!("ack={ack}")),
+        bits.map(|bits| format!("flags={}", flags::names(bits))),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if fields.is_empty() {
+        Msg::AnyHeader.text().to_string()
+    } else {
+        fields.join(" ")
+    }
+}
+
+/// Index of the first of `expected` that `visited` does not contain in order, or `None` when
+/// it is a subsequence (`exact`: equal to `visited`).
+fn first_missing_state(visited: &[&str], expected: &[String], exact: bool) -> Option<usize> {
+    if exact {
+        let mismatch = visited.iter().zip(expected).position(|(v, e)| v != e);
+        return mismatch
+            .or((visited.len() != expected.len()).then(|| visited.len().min(expected.len())));
+    }
+    let mut rest = visited.iter();
+    expected.iter().position(|state| !rest.any(|v| v == state))
+}
+
+/// The `packet_sequence` step that found no event, from `match_steps`.
+#[derive(Debug, PartialEq, Eq)]
+struct UnmatchedStep {
+    step: usize,
+    /// Time of the previous step's event (0 for the first step).
+    after: u64,
+    /// Gap to the first later event that matched the step's header but not its timing.
+    near_gap: Option<u64>,
+}
+
+/// Matches `steps` in order against `log`, each against the first later event that fits it.
+/// Returns the time of the last step's event (`None` without steps) or the first step that
+/// found none.
+fn match_steps(log: &[PacketEvent], steps: &[PacketStep]) -> Result<Option<u64>, UnmatchedStep> {
+    let (mut next, mut after) = (0, 0);
+    for (index, step) in steps.iter().enumerate() {
+        let mut near_gap = None;
+        let found = log[next..].iter().position(|p| {
+            if p.kind != step.kind || !step.matches(p.node, p.seq, p.ack, p.flags, p.len) {
+                return false;
+            }
+            let gap = p.time - after;
+            if step.gap_allows(gap) {
+                return true;
+            }
+            near_gap.get_or_insert(gap);
+            false
+        });
+        let Some(found) = found else {
+            return Err(UnmatchedStep {
+                step: index,
+                after,
+                near_gap,
+            });
+        };
+        next += found + 1;
+        after = log[next - 1].time;
+    }
+    Ok((!steps.is_empty()).then_some(after))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AssertionPolicy, AssertionStatus, RunOptions, SUITE_FILE, ScenarioError, ScenarioResult,
+        StudentSeed, TagFilter, first_missing_state, first_optimistic_ack, first_probe_gap,
+        first_stalled_reopen, first_zero_window_send, load_scenario, load_suite_info, merge_range,
+        migrate_scenario, parse_scenario, run_scenario, run_scenario_with, scenario_files,
+    };
+    use crate::checker::{Checker, RunEvent};
+    use crate::engine::AckTraceEntry::{Acked, Arrived};
+    use crate::engine::FlowTraceEntry::{Data, Probe, Window};
+    use crate::engine::{ClockingStats, LinkEventKind, PacketEventKind};
+    use crate::explain::Clue;
+    use crate::test_protocol::FnProtocol;
+    use std::cell::{Cell, RefCell};
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::rc::Rc;
+    use tcp_lab_abstract::{NodeId, Packet, SeqUnit, TestAction, TransportProtocol, flags};
+
+    #[test]
+    fn test_builtin_scenarios() {
+        let scenario = load_scenario("builtin:test_gbn", true).unwrap();
+        let file = load_scenario("../../tests/test_gbn.toml", true).unwrap();
+        assert_eq!(scenario.name, file.name);
+        assert_eq!(scenario.assertions.len(), file.assertions.len());
+
+        let err = load_scenario("builtin:lab3/nope", true).unwrap_err();
+        assert!(err.to_string().contains("test_gbn"), "{err}");
+    }
+
+    #[test]
+    fn test_min_tool_version() {
+        let scenario = |version: &str| {
+            parse_scenario(
+                &format!(
+                    "name = \"v\"\ndescription = \"\"\nmin_tool_version = \"{version}\"\n\
+                     actions = []\nassertions = []\n[config]\n"
+                ),
+                true,
+            )
+        };
+        assert!(scenario("0.1").is_ok());
+        assert!(scenario("0.0.9-beta").is_ok());
+        let err = scenario("99.0.0").unwrap_err();
+        assert!(matches!(err, ScenarioError::Parse { .. }), "{err}");
+        assert!(err.to_string().contains("99.0.0"), "{err}");
+        assert!(scenario("latest").is_err());
+    }
+
+    #[test]
+    fn test_migrate_scenario() {
+        let legacy = "name = \"old\"\nloss_rate = 0.2\nseed = 7\nauthor = \"ta\"\n\
+                      [[assertions]]\ntype = \"max_duration\"\nms = 100\nunit = \"ms\"\n";
+        let migration = migrate_scenario(legacy).unwrap();
+        assert_eq!(migration.changes.len(), 6, "{:?}", migration.changes);
+        let scenario = parse_scenario(&migration.content, true).unwrap();
+        assert_eq!(scenario.config.loss_rate, Some(0.2));
+        assert_eq!(scenario.config.seed, Some(7));
+        assert_eq!(scenario.assertions.len(), 1);
+        assert!(migration.content.starts_with("name = \"old\""));
+
+        let current = include_str!("../../../tests/test_gbn.toml");
+        assert!(migrate_scenario(current).unwrap().changes.is_empty());
+        let broken = "name = \"x\"\n[[assertions]]\ntype = \"max_duration\"\n";
+        assert!(migrate_scenario(broken).is_err());
+    }
+
+    #[test]
+    fn test_optimistic_acks() {
+        // Packet numbering: ACK the seq that arrived or the next one expected.
+        let honest = [
+            Arrived {
+                time: 1,
+                seq: 0,
+                len: 5,
+            },
+            Acked { time: 1, ack: 0 },
+            Acked { time: 1, ack: 1 },
+        ];
+        assert_eq!(first_optimistic_ack(&honest, SeqUnit::Packet), None);
+
+        let ahead = [
+            Arrived {
+                time: 1,
+                seq: 0,
+                len: 5,
+            },
+            Acked { time: 2, ack: 3 },
+        ];
+        assert_eq!(first_optimistic_ack(&ahead, SeqUnit::Packet), Some((2, 3)));
+        assert_eq!(first_optimistic_ack(&ahead, SeqUnit::Byte), None);
+
+        // An ACK sent before the data arrived is optimistic even if the data shows up later.
+        let early = [
+            Acked { time: 1, ack: 100 },
+            Arrived {
+                time: 2,
+                seq: 0,
+                len: 100,
+            },
+        ];
+        assert_eq!(first_optimistic_ack(&early, SeqUnit::Byte), Some((1, 100)));
+
+        // Bytes 100..200 never arrived, so a cumulative ACK of 300 is optimistic although
+        // 300 ends a segment that did; once the gap is filled, it is honest.
+        let arrived = |time, seq| Arrived {
+            time,
+            seq,
+            len: 100,
+        };
+        let gap = [
+            arrived(1, 0),
+            Acked { time: 1, ack: 100 },
+            arrived(2, 200),
+            Acked { time: 2, ack: 100 },
+            Acked { time: 3, ack: 300 },
+        ];
+        assert_eq!(first_optimistic_ack(&gap, SeqUnit::Byte), Some((3, 300)));
+        let filled = [
+            arrived(1, 0),
+            arrived(2, 200),
+            Acked { time: 2, ack: 100 },
+            arrived(3, 100),
+            Acked { time: 3, ack: 300 },
+        ];
+        assert_eq!(first_optimistic_ack(&filled, SeqUnit::Byte), None);
+        // Selective ACKs of packets past a gap are honest.
+        let selective = [
+            arrived(1, 0),
+            arrived(2, 2),
+            Acked { time: 2, ack: 2 },
+            Acked { time: 2, ack: 3 },
+            Acked { time: 2, ack: 4 },
+        ];
+        assert_eq!(
+            first_optimistic_ack(&selective, SeqUnit::Packet),
+            Some((2, 4))
+        );
+    }
+
+    #[test]
+    fn test_merge_range() {
+        let mut ranges = BTreeMap::new();
+        for (start, end) in [(200, 300), (0, 100), (400, 500), (100, 200), (250, 450)] {
+            merge_range(&mut ranges, start, end);
+        }
+        assert_eq!(ranges.into_iter().collect::<Vec<_>>(), [(0, 500)]);
+        let mut ranges = BTreeMap::new();
+        merge_range(&mut ranges, 0, 10);
+        merge_range(&mut ranges, 20, 30);
+        merge_range(&mut ranges, 5, 8);
+        assert_eq!(ranges.into_iter().collect::<Vec<_>>(), [(0, 10), (20, 30)]);
+    }
+
+    fn window(time: u64, window: u16) -> crate::engine::FlowTraceEntry {
+        Window {
+            time,
+            window,
+            app_data_pending: true,
+        }
+    }
+
+    #[test]
+    fn test_zero_window_checks() {
+        // Probing a closed window with one byte is fine; a full segment is not.
+        let probe = [window(1, 0), Data { time: 5, len: 1 }, window(9, 100)];
+        assert_eq!(first_zero_window_send(&probe), None);
+        let overrun = [window(1, 0), Data { time: 5, len: 10 }];
+        assert_eq!(first_zero_window_send(&overrun), Some((5, 10)));
+
+        let prompt = [window(1, 0), window(10, 100), Data { time: 15, len: 10 }];
+        assert_eq!(first_stalled_reopen(&prompt, 10, 100), None);
+        let late = [window(1, 0), window(10, 100), Data { time: 50, len: 10 }];
+        assert_eq!(first_stalled_reopen(&late, 10, 100), Some(10));
+        let silent = [window(1, 0), window(10, 100)];
+        assert_eq!(first_stalled_reopen(&silent, 10, 100), Some(10));
+        let finished = [
+            window(1, 0),
+            Window {
+                time: 10,
+                window: 100,
+                app_data_pending: false,
+            },
+        ];
+        assert_eq!(first_stalled_reopen(&finished, 10, 100), None);
+    }
+
+    #[test]
+    fn test_probe_gaps() {
+        let probed = [
+            window(0, 0),
+            Probe { time: 40 },
+            Probe { time: 80 },
+            window(100, 50),
+        ];
+        assert_eq!(first_probe_gap(&probed, 50, 1000), None);
+        let lapsed = [window(0, 0), Probe { time: 40 }, Probe { time: 120 }];
+        assert_eq!(first_probe_gap(&lapsed, 50, 1000), Some(40));
+        // A sender that never probes fails even if the run ends right after the window closed.
+        assert_eq!(first_probe_gap(&[window(0, 0)], 50, 1000), Some(0));
+        // Repeated zero-window ACKs do not restart the clock.
+        let repeated = [window(0, 0), window(40, 0), window(80, 0), window(90, 50)];
+        assert_eq!(first_probe_gap(&repeated, 50, 1000), Some(0));
+    }
+
+    /// Logs `message` from `init` and does nothing else.
+    fn logger(message: &'static str) -> Box<dyn TransportProtocol> {
+        FnProtocol::new().init(move |ctx| ctx.log(message)).boxed()
+    }
+
+    fn run_with_log_assertion(node: &str, pattern: &str) -> Result<(), ScenarioError> {
+        let scenario = parse_scenario(
+            &format!(
+                "name = \"logs\"\ndescription = \"\"\nactions = []\n\n[config]\n\n[[assertions]]\n\
+                 type = \"log_contains\"\nnode = \"{node}\"\npattern = '{pattern}'\n"
+            ),
+            true,
+        )?;
+        let sender = logger("timeout for seq 3, retransmitting");
+        let receiver = logger("receiver ready");
+        run_scenario(&scenario, sender, receiver, true).map(|_| ())
+    }
+
+    #[test]
+    fn test_run_scenario_with_lists_failures() {
+        let scenario = parse_scenario(
+            r#"
+name = "structured"
+description = ""
+actions = []
+[config]
+[[assertions]]
+type = "log_contains"
+node = "sender"
+pattern = "timeout"
+[[assertions]]
+type = "log_contains"
+node = "receiver"
+pattern = "timeout"
+[[assertions]]
+type = "data_delivered"
+data = "never sent"
+"#,
+            true,
+        )
+        .unwrap();
+        let result = run_scenario_with(
+            &scenario,
+            logger("timeout for seq 3"),
+            logger("receiver ready"),
+            RunOptions::default(),
+        );
+        assert!(!result.passed() && result.report.is_some());
+        let failed: Vec<usize> = result
+            .failures
+            .iter()
+            .map(|failure| match failure {
+                ScenarioError::AssertionFailed { which, .. } => *which,
+                other => panic!("unexpected failure {other}"),
+            })
+            .collect();
+        assert_eq!(failed, [1, 2]);
+        let err = result.into_result().unwrap_err();
+        assert!(err.to_string().contains("never logged"), "{err}");
+
+        let result = run_scenario_with(
+            &scenario,
+            FnProtocol::new().init(|_| panic!("boom")).boxed(),
+            logger("receiver ready"),
+            RunOptions::default(),
+        );
+        assert!(
+            matches!(&result.failures[..], [ScenarioError::StudentRuntime { details }] if details.contains("boom")),
+            "{:?}",
+            result.failures
+        );
+    }
+
+    #[test]
+    fn test_tag_filter() {
+        let scenario = parse_scenario(
+            "name = \"t\"\ndescription = \"\"\ntags = [\"rdt3\", \"bonus\"]\n\
+             actions = []\nassertions = []\n[config]\n",
+            true,
+        )
+        .unwrap();
+        let filter = |tags: &[&str], exclude_tags: &[&str]| TagFilter {
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            exclude_tags: exclude_tags.iter().map(|tag| tag.to_string()).collect(),
+        };
+        assert!(filter(&[], &[]).matches(&scenario));
+        assert!(filter(&["gbn", "rdt3"], &[]).matches(&scenario));
+        assert!(!filter(&["gbn"], &[]).matches(&scenario));
+        assert!(!filter(&["rdt3"], &["bonus"]).matches(&scenario));
+    }
+
+    #[test]
+    fn test_suite_info() {
+        let dir = std::env::temp_dir().join(format!("tcp-lab-suite-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        assert!(load_suite_info(&dir).unwrap().is_empty());
+        fs::write(
+            dir.join(SUITE_FILE),
+            "course = \"CS 305\"\nlab = \"Lab 3\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("test_a.toml"),
+            "name = \"a\"\ndescription = \"\"\nrubric = \"Delivers in order (5 points)\"\n\
+             actions = []\nassertions = []\n[config]\n",
+        )
+        .unwrap();
+        let files = scenario_files(&dir).unwrap();
+        let info = load_suite_info(&dir).unwrap();
+        let scenario = load_scenario(dir.join("test_a.toml"), true).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(files, [dir.join("test_a.toml")]);
+        assert_eq!(info.to_string(), "CS 305 / Lab 3");
+        assert_eq!(
+            scenario.rubric.as_deref(),
+            Some("Delivers in order (5 points)")
+        );
+    }
+
+    #[test]
+    fn test_log_contains() {
+        run_with_log_assertion("sender", r"timeout.*seq \d").unwrap();
+        let err = run_with_log_assertion("receiver", "timeout").unwrap_err();
+        assert!(err.to_string().contains("never logged"), "{err}");
+        let err = run_with_log_assertion("sender", "(unclosed").unwrap_err();
+        assert!(
+            err.to_string().contains("Invalid log_contains pattern"),
+            "{err}"
+        );
+    }
+
+    /// Logs the header fields and payload of every packet it gets.
+    fn packet_logger() -> Box<dyn TransportProtocol> {
+        FnProtocol::new()
+            .on_packet(|ctx, packet| {
+                let h = &packet.header;
+                ctx.log(&format!(
+                    "got seq={} ack={} flags={:#04x} window={} payload={:?}",
+                    h.seq_num,
+                    h.ack_num,
+                    h.flags,
+                    h.window_size,
+                    String::from_utf8_lossy(&packet.payload)
+                ));
+            })
+            .boxed()
+    }
+
+    #[test]
+    fn test_inject_packet() {
+        let inject = |payload: &str| {
+            parse_scenario(
+                &format!(
+                    "name = \"inject\"\ndescription = \"\"\n[config]\nloss_rate = 1.0\n\n\
+                     [[actions]]\ntype = \"inject_packet\"\ntime = 30\nto = \"receiver\"\n\
+                     seq = 7\nack = 3\nflags = [\"syn\", \"ACK\"]\nwindow = 64\npayload = \"{payload}\"\n\n\
+                     [[assertions]]\ntype = \"log_contains\"\nnode = \"receiver\"\n\
+                     pattern = 'got seq=7 ack=3 flags=0x12 window=64 payload=\"hi\"'\n"
+                ),
+                true,
+            )
+        };
+        // The packet skips the channel, so it arrives despite a 100% loss rate.
+        let report = run_scenario(
+            &inject("68 69").unwrap(),
+            packet_logger(),
+            packet_logger(),
+            true,
+        )
+        .unwrap();
+        let event = report
+            .link_events
+            .iter()
+            .find(|e| e.kind == LinkEventKind::Injected)
+            .unwrap();
+        assert_eq!(event.time, 30);
+        let err = inject("686").unwrap_err();
+        assert!(
+            format!("{err:#}").contains("odd number of digits"),
+            "{err:#}"
+        );
+    }
+
+    #[test]
+    fn test_state_sequences() {
+        let visited = ["WAIT_CALL_0", "WAIT_ACK_0", "WAIT_CALL_1", "WAIT_ACK_1"];
+        let expect = |states: &[&str]| states.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let loose = expect(&["WAIT_ACK_0", "WAIT_ACK_1"]);
+        assert_eq!(first_missing_state(&visited, &loose, false), None);
+        assert_eq!(first_missing_state(&visited, &loose, true), Some(0));
+        let out_of_order = expect(&["WAIT_CALL_1", "WAIT_ACK_0"]);
+        assert_eq!(first_missing_state(&visited, &out_of_order, false), Some(1));
+        let all = expect(&visited);
+        assert_eq!(first_missing_state(&visited, &all, true), None);
+        assert_eq!(first_missing_state(&visited[..2], &all, true), Some(2));
+    }
+
+    #[test]
+    fn test_restart_needs_factory() {
+        let scenario = parse_scenario(
+            r#"
+name = "restart"
+description = ""
+assertions = []
+[config]
+[[actions]]
+type = "restart_node"
+node = "receiver"
+time = 10
+"#,
+            true,
+        )
+        .unwrap();
+        let err = run_scenario(&scenario, logger(""), logger(""), false).unwrap_err();
+        assert!(err.to_string().contains("restart_node"), "{err}");
+    }
+
+    #[test]
+    fn test_init_params() {
+        let scenario = parse_scenario(
+            r#"
+name = "params"
+description = ""
+actions = []
+
+[config]
+
+[params.sender]
+window = 4
+rto = 1.5
+mode = "gbn"
+
+[[assertions]]
+type = "log_contains"
+node = "sender"
+pattern = '\{"mode": "gbn", "rto": "1.5", "window": "4"\}'
+
+[[assertions]]
+type = "log_contains"
+node = "receiver"
+pattern = '^\{\}$'
+"#,
+            true,
+        )
+        .unwrap();
+        let param_logger = || {
+            FnProtocol::new()
+                .init(|ctx| {
+                    let params = ctx.init_params();
+                    ctx.log(&format!("{params:?}"));
+                })
+                .boxed()
+        };
+        run_scenario(&scenario, param_logger(), param_logger(), true).unwrap();
+    }
+    /// Three-way handshake client that retransmits its SYN every 100 ms; `eager` also
+    /// sends data straight away.
+    fn handshake_sender(eager: bool) -> Box<dyn TransportProtocol> {
+        let connected = Rc::new(Cell::new(false));
+        let on_syn_ack = connected.clone();
+        FnProtocol::new()
+            .init(move |ctx| {
+                ctx.send_packet(Packet::new_simple(0, 0, flags::SYN, Vec::new()));
+                ctx.start_timer(100, 1);
+                if eager {
+                    ctx.send_packet(Packet::new_simple(1, 0, 0, b"early".to_vec()));
+                }
+            })
+            .on_packet(move |ctx, packet| {
+                if packet.header.is_syn() && !on_syn_ack.replace(true) {
+                    ctx.cancel_timer(1);
+                    ctx.send_packet(Packet::new_simple(1, 1, flags::ACK, Vec::new()));
+                }
+            })
+            .on_timer(move |ctx, _| {
+                if !connected.get() {
+                    ctx.send_packet(Packet::new_simple(0, 0, flags::SYN, Vec::new()));
+                    ctx.start_timer(100, 1);
+                }
+            })
+            .boxed()
+    }
+
+    /// Answers every SYN with a SYN-ACK.
+    fn handshake_receiver() -> Box<dyn TransportProtocol> {
+        FnProtocol::new()
+            .on_packet(|ctx, packet| {
+                if packet.header.is_syn() {
+                    let syn_ack = Packet::new_simple(0, 1, flags::SYN | flags::ACK, Vec::new());
+                    ctx.send_packet(syn_ack);
                 }
+            })
+            .boxed()
+    }
+
+    /// Run the handshake with the first SYN lost, checking one assertion.
+    fn run_handshake(assertion: &str, eager: bool) -> Result<(), ScenarioError> {
+        let scenario = parse_scenario(
+            &format!(
+                "name = \"handshake\"\ndescription = \"\"\n\n[config]\nmin_latency = 10\n\
+                 max_latency = 10\n\n[[actions]]\ntype = \"drop_next_from_sender_seq\"\nseq = 0\n\n\
+                 [[assertions]]\n{assertion}\n"
+            ),
+            true,
+        )?;
+        run_scenario(
+            &scenario,
+            handshake_sender(eager),
+            handshake_receiver(),
+            false,
+        )
+        .map(|_| ())
+    }
+
+    #[test]
+    fn test_handshake_assertions() {
+        // SYN at 0 is lost, the retransmission at 100 ms completes the handshake at 130 ms.
+        run_handshake("type = \"handshake_completed\"\nwithin_ms = 130", false).unwrap();
+        let err =
+            run_handshake("type = \"handshake_completed\"\nwithin_ms = 120", false).unwrap_err();
+        assert!(err.to_string().contains("took 130 ms"), "{err}");
+
+        run_handshake("type = \"syn_retransmitted_at_most\"\nn = 1", false).unwrap();
+        let err = run_handshake("type = \"syn_retransmitted_at_most\"\nn = 0", false).unwrap_err();
+        assert!(err.to_string().contains("retransmitted 1 times"), "{err}");
+
+        run_handshake("type = \"no_data_before_established\"", false).unwrap();
+        let err = run_handshake("type = \"no_data_before_established\"", true).unwrap_err();
+        assert!(err.to_string().contains("at 0 ms"), "{err}");
+    }
+
+    #[test]
+    fn test_max_queue_delay() {
+        // 25 bytes with the header at 100 B/s: the three segments wait 0, 250 and 500 ms.
+        let run = |bottleneck: &str, ms: u64| {
+            let scenario = parse_scenario(
+                &format!(
+                    "name = \"queue\"\ndescription = \"\"\n\n[config]\n{bottleneck}\n\n\
+                     [[actions]]\ntype = \"app_send\"\ntime = 0\ndata = \"msg-1\"\n\n\
+                     [[actions]]\ntype = \"app_send\"\ntime = 0\ndata = \"msg-2\"\n\n\
+                     [[actions]]\ntype = \"app_send\"\ntime = 0\ndata = \"msg-3\"\n\n\
+                     [[assertions]]\ntype = \"max_queue_delay\"\nms = {ms}\n"
+                ),
+                true,
+            )
+            .unwrap();
+            // Sends each chunk of application data as one segment straight away.
+            let blaster = FnProtocol::new().on_app_data(|ctx, data| {
+                ctx.send_packet(Packet::new_simple(0, 0, 0, data.to_vec()))
+            });
+            run_scenario_with(
+                &scenario,
+                blaster.boxed(),
+                packet_logger(),
+                RunOptions::default(),
+            )
+        };
+        let bottleneck = "bottleneck = { rate = 100, buffer_ms = 1000 }";
+        assert!(run(bottleneck, 500).passed());
+        let err = run(bottleneck, 300).failures[0].to_string();
+        assert!(err.contains("waited 500 ms"), "{err}");
+        let err = run("", 500).failures[0].to_string();
+        assert!(err.contains("needs `bottleneck`"), "{err}");
+    }
+
+    /// Handshake client that sends one `size`-byte segment, or one as large as the peer's
+    /// MSS allows, once the SYN-ACK arrives.
+    fn mss_sender(size: Option<usize>) -> Box<dyn TransportProtocol> {
+        FnProtocol::new()
+            .init(|ctx| {
+                assert_eq!(ctx.peer_mss(), None);
+                ctx.send_packet(Packet::new_simple(0, 0, flags::SYN, Vec::new()));
+            })
+            .on_packet(move |ctx, packet| {
+                if packet.header.is_syn() {
+                    let peer_mss = ctx.peer_mss().expect("the SYN-ACK carries an MSS");
+                    let size = size.unwrap_or(usize::from(peer_mss));
+                    ctx.send_packet(Packet::new_simple(1, 1, flags::ACK, vec![b'x'; size]));
+                }
+            })
+            .boxed()
+    }
+
+    #[test]
+    fn test_respects_mss() {
+        let run = |size| {
+            let scenario = parse_scenario(
+                "name = \"mss\"\ndescription = \"\"\nactions = []\n\n[config]\n\
+                 mss = { sender = 1460, receiver = 536 }\n\n\
+                 [[assertions]]\ntype = \"respects_mss\"\n",
+                true,
+            )
+            .unwrap();
+            run_scenario(&scenario, mss_sender(size), handshake_receiver(), false).map(|_| ())
+        };
+        run(None).unwrap();
+        // Within the sender's own MSS, but not the receiver's.
+        let err = run(Some(1000)).unwrap_err();
+        assert!(err.to_string().contains("1000-byte segment"), "{err}");
+        assert!(err.to_string().contains("MSS of 536 bytes"), "{err}");
+    }
+
+    #[test]
+    fn test_satisfied_at() {
+        let scenario = parse_scenario(
+            "name = \"timing\"\ndescription = \"\"\n\n[config]\nmin_latency = 10\n\
+             max_latency = 10\n\n[[actions]]\ntype = \"drop_next_from_sender_seq\"\nseq = 0\n\n\
+             [[assertions]]\ntype = \"handshake_completed\"\nwithin_ms = 200\n\n\
+             [[assertions]]\ntype = \"sender_packet_count\"\nmin = 2\n\n\
+             [[assertions]]\ntype = \"max_in_flight\"\nn = 1\n\n\
+             [[assertions]]\ntype = \"max_duration\"\nms = 1000\n",
+            true,
+        )
+        .unwrap();
+        let report = run_scenario(
+            &scenario,
+            handshake_sender(false),
+            handshake_receiver(),
+            false,
+        )
+        .unwrap();
+        let satisfied: Vec<Option<u64>> =
+            report.assertions.iter().map(|a| a.satisfied_at).collect();
+        // Established at 130 ms by the SYN retransmitted at 100 ms; `max_in_flight` is about
+        // the whole run.
+        assert_eq!(
+            satisfied,
+            [Some(130), Some(100), None, Some(report.duration_ms)]
+        );
+    }
+
+    #[test]
+    fn test_assertion_policy() {
+        let scenario = parse_scenario(
+            "name = \"policy\"\ndescription = \"\"\nactions = []\n\n[config]\n\n\
+             [[assertions]]\ntype = \"sender_packet_count\"\nmin = 100\n\n\
+             [[assertions]]\ntype = \"max_duration\"\nms = 1000\n\n\
+             [[assertions]]\ntype = \"data_delivered\"\ndata = \"never\"\n",
+            true,
+        )
+        .unwrap();
+        let run = |assertion_policy| {
+            let options = RunOptions {
+                assertion_policy,
+                ..Default::default()
+            };
+            run_scenario_with(
+                &scenario,
+                handshake_sender(false),
+                handshake_receiver(),
+                options,
+            )
+        };
+        let statuses = |result: &ScenarioResult| -> Vec<AssertionStatus> {
+            let report = result.report.as_ref().unwrap();
+            report.assertions.iter().map(|a| a.status).collect()
+        };
+
+        let all = run(None);
+        assert_eq!(all.failures.len(), 2);
+        assert_eq!(
+            statuses(&all),
+            [
+                AssertionStatus::Failed,
+                AssertionStatus::Passed,
+                AssertionStatus::Failed
+            ]
+        );
+        assert_eq!(
+            all.assertion_tally().unwrap(),
+            "1 of 3 assertions passed; failed: assertions[0], assertions[2]"
+        );
+
+        let first = run(Some(AssertionPolicy::FirstFailure));
+        assert_eq!(first.failures.len(), 1);
+        assert_eq!(
+            statuses(&first),
+            [
+                AssertionStatus::Failed,
+                AssertionStatus::Skipped,
+                AssertionStatus::Skipped
+            ]
+        );
+    }
+
+    #[test]
+    fn test_student_seed() {
+        let seed = |student: &str, base_seed| {
+            StudentSeed {
+                student: student.into(),
+                base_seed,
             }
-            TestAssertion::MaxDuration { .. } => {} // Already checked
+            .derive("loss")
+            .seed
+        };
+        // Fixed across platforms and releases, and different per student and base seed.
+        assert_eq!(seed("s1", 7), seed("s1", 7));
+        assert_ne!(seed("s1", 7), seed("s2", 7));
+        assert_ne!(seed("s1", 7), seed("s1", 8));
+
+        let scenario = parse_scenario(
+            "name = \"loss\"\ndescription = \"\"\nactions = []\nassertions = []\n\n\
+             [config]\nseed = 1\n",
+            true,
+        )
+        .unwrap();
+        let student_seed = StudentSeed {
+            student: "s1".into(),
+            base_seed: 7,
+        };
+        let options = RunOptions {
+            student_seed: Some(student_seed.clone()),
+            ..Default::default()
+        };
+        let result = run_scenario_with(
+            &scenario,
+            handshake_sender(false),
+            handshake_receiver(),
+            options,
+        );
+        let report = result.report.unwrap();
+        assert_eq!(report.config.seed, seed("s1", 7));
+        assert_eq!(report.seed_derivation, Some(student_seed.derive("loss")));
+        assert_eq!(scenario.config.seed, Some(1));
+    }
+
+    #[test]
+    fn test_checkers() {
+        let scenario = parse_scenario(
+            "name = \"handshake\"\ndescription = \"\"\nassertions = []\n\n[config]\nmin_latency = 10\n\
+             max_latency = 10\n\n[[actions]]\ntype = \"drop_next_from_sender_seq\"\nseq = 0\n",
+            true,
+        )
+        .unwrap();
+        let mut syns = 0;
+        let one_syn = Checker::new("one_syn", move |event| match event {
+            RunEvent::Packet(p)
+                if p.node == NodeId::Sender
+                    && p.kind == PacketEventKind::Sent
+                    && p.flags & flags::SYN != 0 =>
+            {
+                syns += 1;
+                match syns {
+                    1 => Ok(()),
+                    _ => Err(format!("SYN sent again at {} ms", p.time)),
+                }
+            }
+            _ => Ok(()),
+        });
+        let options = RunOptions {
+            checkers: vec![one_syn],
+            ..Default::default()
+        };
+        let result = run_scenario_with(
+            &scenario,
+            handshake_sender(false),
+            handshake_receiver(),
+            options,
+        );
+        match &result.failures[..] {
+            [
+                ScenarioError::CheckFailed {
+                    time: 100,
+                    checker,
+                    details,
+                },
+            ] => {
+                assert_eq!(checker, "one_syn");
+                assert!(details.ends_with("SYN sent again at 100 ms"), "{details}");
+            }
+            other => panic!("unexpected failures {other:?}"),
         }
+        // The run ended there, before the retransmitted SYN arrived.
+        let report = result.report.unwrap();
+        assert!(report.packets.iter().all(|p| p.time <= 100));
     }
 
-    info!("Test Scenario Passed!");
-    Ok(sim.export_report())
+    #[test]
+    fn test_packet_sent() {
+        let packet_sent =
+            |fields: &str| run_handshake(&format!("type = \"packet_sent\"\n{fields}"), false);
+        // The retransmitted SYN reaches the receiver at 110 ms and is answered at once.
+        packet_sent(
+            "node = \"receiver\"\nack = 1\nflags = [\"SYN\", \"ACK\"]\n\
+             after_receiving = [\"SYN\"]\nwithin_ms = 0",
+        )
+        .unwrap();
+        let err = packet_sent("node = \"receiver\"\nack = 2").unwrap_err();
+        assert!(
+            err.to_string().contains("never sent a packet with ack=2"),
+            "{err}"
+        );
+        let err = packet_sent("node = \"sender\"\nflags = [\"ACK\"]\nwithin_ms = 50").unwrap_err();
+        assert!(err.to_string().contains("120 ms after the start"), "{err}");
+        let err = packet_sent("node = \"sender\"\nafter_receiving = [\"FIN\"]").unwrap_err();
+        assert!(err.to_string().contains("never received"), "{err}");
+    }
+
+    #[test]
+    fn test_packet_sequence() {
+        let sequence = |steps: &str| {
+            run_handshake(
+                &format!("type = \"packet_sequence\"\nnode = \"sender\"\nexpect = [{steps}]"),
+                false,
+            )
+        };
+        sequence(r#""SEND seq=0 flags=SYN", "DROP", "SEND seq=0 after>=100ms", "ARRIVE receiver flags=SYN after<=10""#)
+            .unwrap();
+        let err = sequence(r#""DROP", "SEND seq=0 flags=SYN after>=150""#).unwrap_err();
+        assert!(err.to_string().contains("came 100 ms after"), "{err}");
+        let err = sequence(r#""SEND flags=FIN""#).unwrap_err();
+        assert!(err.to_string().contains("step 1 `SEND flags=FIN`"), "{err}");
+        let err = sequence(r#""SEND window=4""#).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("invalid \"window=4\""),
+            "{err:#}"
+        );
+    }
+    /// Sends one data packet, then a FIN once it is acknowledged. `leaky` never cancels
+    /// the retransmission timer; `chatty` sends more data after its FIN.
+    fn closing_sender(leaky: bool, chatty: bool) -> Box<dyn TransportProtocol> {
+        let mut acked = false;
+        FnProtocol::new()
+            .on_packet(move |ctx, packet| {
+                if packet.header.is_fin() {
+                    let ack = packet.header.seq_num;
+                    ctx.send_packet(Packet::new_simple(2, ack, flags::ACK, Vec::new()));
+                } else if packet.header.ack_num == 0 && !acked {
+                    acked = true;
+                    if !leaky {
+                        ctx.cancel_timer(1);
+                    }
+                    ctx.send_packet(Packet::new_simple(1, 0, flags::FIN, Vec::new()));
+                    if chatty {
+                        ctx.send_packet(Packet::new_simple(2, 0, 0, b"more".to_vec()));
+                    }
+                }
+            })
+            .on_app_data(|ctx, data| {
+                ctx.send_packet(Packet::new_simple(0, 0, 0, data.to_vec()));
+                ctx.start_timer(200, 1);
+            })
+            .boxed()
+    }
+
+    /// ACKs data and answers a FIN with an ACK and its own FIN.
+    fn closing_receiver() -> Box<dyn TransportProtocol> {
+        FnProtocol::new()
+            .on_packet(|ctx, packet| {
+                let seq = packet.header.seq_num;
+                if packet.header.is_fin() {
+                    let fin_ack = Packet::new_simple(0, seq, flags::ACK | flags::FIN, Vec::new());
+                    ctx.send_packet(fin_ack);
+                } else if !packet.payload.is_empty() {
+                    ctx.deliver_data(&packet.payload);
+                    ctx.send_packet(Packet::new_simple(0, seq, flags::ACK, Vec::new()));
+                }
+            })
+            .boxed()
+    }
+
+    fn run_teardown(
+        assertion: &str,
+        sender: Box<dyn TransportProtocol>,
+    ) -> Result<(), ScenarioError> {
+        let scenario = parse_scenario(
+            &format!(
+                "name = \"teardown\"\ndescription = \"\"\n\n[config]\nmin_latency = 10\n\
+                 max_latency = 10\n\n[[actions]]\ntype = \"app_send\"\ntime = 0\ndata = \"bye\"\n\n\
+                 [[assertions]]\ntype = \"{assertion}\"\n"
+            ),
+            true,
+        )?;
+        run_scenario(&scenario, sender, closing_receiver(), false).map(|_| ())
+    }
+
+    #[test]
+    fn test_teardown_assertions() {
+        for assertion in [
+            "graceful_close",
+            "no_data_after_fin",
+            "no_pending_timers_at_end",
+        ] {
+            run_teardown(assertion, closing_sender(false, false)).unwrap();
+        }
+        let scenario = parse_scenario(
+            "name = \"t\"\ndescription = \"\"\nactions = []\n\n[config]\n\n\
+             [[assertions]]\ntype = \"graceful_close\"\n",
+            true,
+        )
+        .unwrap();
+        let err = run_scenario(
+            &scenario,
+            closing_sender(false, false),
+            closing_receiver(),
+            false,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Sender never sent a FIN"), "{err}");
+
+        let err = run_teardown("no_data_after_fin", closing_sender(false, true)).unwrap_err();
+        assert!(
+            err.to_string().contains("Sender sent new data at 20 ms"),
+            "{err}"
+        );
+
+        let err =
+            run_teardown("no_pending_timers_at_end", closing_sender(true, false)).unwrap_err();
+        assert!(
+            err.to_string().contains("Sender timer 1 (started at 0 ms)"),
+            "{err}"
+        );
+        run_teardown("timers_cleared_after_fin", closing_sender(false, false)).unwrap();
+        let err =
+            run_teardown("timers_cleared_after_fin", closing_sender(true, false)).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Sender still had 1 timer(s) armed 0 ms after its FIN"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_shutdown_flush() {
+        let scenario = parse_scenario(
+            "name = \"shutdown\"\ndescription = \"\"\nassertions = []\n\n[config]\nmin_latency = 10\n\
+             max_latency = 10\n\n[[actions]]\ntype = \"app_send\"\ntime = 0\ndata = \"abcdef\"\n\n\
+             [[actions]]\ntype = \"shutdown\"\ntime = 100\n",
+            true,
+        )
+        .unwrap();
+        // Sends app data in 4-byte segments, holding back the rest until `on_close` if
+        // `flush_on_close`.
+        let run = |flush_on_close: bool| {
+            let held = Rc::new(RefCell::new(Vec::new()));
+            let sender = FnProtocol::new()
+                .on_app_data({
+                    let held = held.clone();
+                    move |ctx, data| {
+                        let mut held = held.borrow_mut();
+                        held.extend_from_slice(data);
+                        while held.len() >= 4 {
+                            let segment: Vec<u8> = held.drain(..4).collect();
+                            ctx.send_packet(Packet::new_simple(0, 0, 0, segment));
+                        }
+                    }
+                })
+                .on_close(move |ctx| {
+                    if flush_on_close && !held.borrow().is_empty() {
+                        let rest = held.take();
+                        ctx.send_packet(Packet::new_simple(1, 0, 0, rest));
+                    }
+                });
+            run_scenario(&scenario, sender.boxed(), closing_receiver(), false)
+        };
+        let report = run(true).unwrap();
+        assert_eq!(
+            report.shutdown.map(|s| (s.time, s.app_bytes)),
+            Some((100, 6))
+        );
+        assert_eq!(report.callbacks.sender.on_close.calls, 1);
+
+        let err = run(false).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                ScenarioError::NotFlushed {
+                    time: 100,
+                    written: 6,
+                    delivered: 4,
+                    ..
+                }
+            ),
+            "{err}"
+        );
+    }
+
+    /// Sends each write as one segment and a FIN on close, delivering what arrives.
+    /// `fin_closes_all` treats the peer's FIN as the end of the connection and sends
+    /// nothing more once it has arrived.
+    fn half_closer(fin_closes_all: bool) -> Box<dyn TransportProtocol> {
+        let fin_received = Rc::new(Cell::new(false));
+        let on_fin = fin_received.clone();
+        FnProtocol::new()
+            .on_packet(move |ctx, packet| {
+                if packet.header.is_fin() {
+                    on_fin.set(true);
+                } else if !packet.payload.is_empty() {
+                    ctx.deliver_data(&packet.payload);
+                }
+            })
+            .on_app_data(move |ctx, data| {
+                if !(fin_closes_all && fin_received.get()) {
+                    ctx.send_packet(Packet::new_simple(0, 0, 0, data.to_vec()));
+                }
+            })
+            .on_close(|ctx| ctx.send_packet(Packet::new_simple(0, 0, flags::FIN, Vec::new())))
+            .boxed()
+    }
+
+    #[test]
+    fn test_half_close() {
+        // The sender's FIN reaches the receiver at 10 ms right behind "abc", before the
+        // receiver's application echoes it.
+        let scenario = parse_scenario(
+            "name = \"half-close\"\ndescription = \"\"\n\n[config]\nmin_latency = 10\n\
+             max_latency = 10\napp_model = \"echo\"\n\n\
+             [[actions]]\ntype = \"app_send\"\ntime = 0\ndata = \"abc\"\n\n\
+             [[actions]]\ntype = \"half_close\"\ntime = 0\nnode = \"sender\"\n\n\
+             [[actions]]\ntype = \"app_send\"\ntime = 50\ndata = \"late\"\n\n\
+             [[assertions]]\ntype = \"delivers_after_half_close\"\nmin_bytes = 3\n",
+            true,
+        )
+        .unwrap();
+        let run = |fin_closes_all| {
+            let (sender, receiver) = (half_closer(fin_closes_all), half_closer(fin_closes_all));
+            run_scenario(&scenario, sender, receiver, false)
+        };
+        let report = run(false).unwrap();
+        // Only the sender closed, and its write after the close went nowhere.
+        assert_eq!(report.callbacks.sender.on_close.calls, 1);
+        assert_eq!(report.callbacks.receiver.on_close.calls, 0);
+        assert_eq!(
+            report
+                .half_closes
+                .iter()
+                .map(|c| (
+                    c.time,
+                    c.node,
+                    c.app_bytes,
+                    c.peer_written_after,
+                    c.delivered_after
+                ))
+                .collect::<Vec<_>>(),
+            vec![(0, NodeId::Sender, 3, 3, 3)]
+        );
+        assert!(report.connection.sender_fin.received_at.is_some());
+        assert_eq!(report.delivered_data, vec![b"abc".to_vec()]);
+
+        let err = run(true).unwrap_err();
+        assert!(
+            err.to_string().contains(
+                "Sender half-closed at 0 ms, then got only 0 bytes through the direction \
+                 still open (Receiver's application wrote 3 after the close); expected 3"
+            ),
+            "{err}"
+        );
+
+        let mut scenario = scenario.clone();
+        scenario
+            .actions
+            .retain(|action| !matches!(action, TestAction::HalfClose { .. }));
+        let err =
+            run_scenario(&scenario, half_closer(false), closing_receiver(), false).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("delivers_after_half_close needs a half_close action"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_timers_balanced() {
+        let scenario = parse_scenario(
+            "name = \"t\"\ndescription = \"\"\nactions = []\n\n[config]\n\n\
+             [[assertions]]\ntype = \"timers_balanced\"\n\n\
+             [[assertions]]\ntype = \"max_duration\"\nms = 500\n",
+            true,
+        )
+        .unwrap();
+        // Re-arms its timer forever, so the run never drains.
+        let ticker = FnProtocol::new()
+            .init(|ctx| ctx.start_timer(100, 1))
+            .on_timer(|ctx, _| ctx.start_timer(100, 1));
+        let err = run_scenario(&scenario, ticker.boxed(), logger(""), false).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Sender started 7 timers, 6 fired, 0 cancelled, 1 still armed"),
+            "{err}"
+        );
+        run_scenario(&scenario, logger(""), logger(""), false).unwrap();
+    }
+
+    #[test]
+    fn test_no_silent_app_data_loss() {
+        let scenario = parse_scenario(
+            "name = \"busy\"\ndescription = \"\"\n\n[config]\nloss_rate = 0.0\n\
+             min_latency = 10\nmax_latency = 10\n\n\
+             [[actions]]\ntype = \"app_send\"\ntime = 0\ndata = \"ab\"\n\n\
+             [[actions]]\ntype = \"app_send\"\ntime = 5\ndata = \"cd\"\n\n\
+             [[actions]]\ntype = \"app_send\"\ntime = 100\ndata = \"ef\"\n\n\
+             [[assertions]]\ntype = \"no_silent_app_data_loss\"\n",
+            true,
+        )
+        .unwrap();
+        // Sends one app send at a time and ignores or refuses the ones made before its ACK.
+        let run = |refuse: bool| {
+            let busy = Rc::new(Cell::new(false));
+            let sender = FnProtocol::new()
+                .on_packet({
+                    let busy = busy.clone();
+                    move |_, _| busy.set(false)
+                })
+                .on_app_data(move |ctx, data| {
+                    if !busy.replace(true) {
+                        ctx.send_packet(Packet::new_simple(0, 0, 0, data.to_vec()));
+                    } else if refuse {
+                        ctx.refuse_app_data("waiting for an ACK");
+                    }
+                });
+            run_scenario(&scenario, sender.boxed(), closing_receiver(), false)
+        };
+        let report = run(true).unwrap();
+        assert_eq!(report.refused_app_sends.len(), 1);
+        assert_eq!(report.refused_app_sends[0].time, 5);
+        let err = run(false).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("1 app sends (2 bytes) were neither delivered nor refused by the sender, the first made at 5 ms (stream bytes 2..4)"),
+            "{err}"
+        );
+    }
+
+    /// Sends a packet every 100 ms for 500 ms, recording `rto` as its timeout each time.
+    fn rto_sender(rto: f64) -> Box<dyn TransportProtocol> {
+        rto_steps(vec![rto; 5])
+    }
+
+    /// Sends a packet every 100 ms, recording the next of `rtos` as its timeout with each.
+    fn rto_steps(rtos: Vec<f64>) -> Box<dyn TransportProtocol> {
+        let mut sent = 0;
+        FnProtocol::new()
+            .init(|ctx| ctx.start_timer(100, 1))
+            .on_timer(move |ctx, _| {
+                ctx.send_packet(Packet::new_simple(sent as u32, 0, 0, vec![b'x']));
+                ctx.record_metric("rto", rtos[sent]);
+                sent += 1;
+                if sent < rtos.len() {
+                    ctx.start_timer(100, 1);
+                }
+            })
+            .boxed()
+    }
+
+    /// ACKs every packet by its seq.
+    fn acker() -> Box<dyn TransportProtocol> {
+        FnProtocol::new()
+            .on_packet(|ctx, packet| {
+                let seq = packet.header.seq_num;
+                ctx.send_packet(Packet::new_simple(0, seq, flags::ACK, Vec::new()));
+            })
+            .boxed()
+    }
+
+    #[test]
+    fn test_rto_within() {
+        // A fixed 50 ms each way makes the true RTT exactly 100 ms.
+        let scenario = parse_scenario(
+            "name = \"rto\"\ndescription = \"\"\nactions = []\n\n\
+             [config]\nmin_latency = 50\nmax_latency = 50\n\n\
+             [[assertions]]\ntype = \"rto_within\"\nfactor_of_rtt = 2.0\nafter_ms = 200\n",
+            true,
+        )
+        .unwrap();
+        let run = |rto: f64| run_scenario(&scenario, rto_sender(rto), acker(), false);
+        run(150.0).unwrap();
+        let err = run(300.0).unwrap_err();
+        assert!(
+            err.to_string().contains(
+                "rto was 300 ms at 200 ms, outside 50-200 ms around the true RTT of 100 ms"
+            ),
+            "{err}"
+        );
+        let err = run_scenario(&scenario, logger(""), acker(), false).unwrap_err();
+        assert!(err.to_string().contains("No packets crossed"), "{err}");
+    }
+
+    #[test]
+    fn test_rto_adapts() {
+        // The RTT steps from 100 ms to 400 ms at 250 ms; rto samples follow at 300, 400, 500.
+        let scenario = |within_samples: usize| {
+            parse_scenario(
+                &format!(
+                    "name = \"rto step\"\ndescription = \"\"\n\n\
+                     [config]\nmin_latency = 50\nmax_latency = 50\n\n\
+                     [[actions]]\ntype = \"set_latency\"\ntime = 250\nmin = 200\nmax = 200\n\n\
+                     [[assertions]]\ntype = \"rto_adapts\"\nwithin_samples = {within_samples}\n\
+                     factor_of_rtt = 2.0\n"
+                ),
+                true,
+            )
+            .unwrap()
+        };
+        let rtos = [150.0, 150.0, 150.0, 150.0, 500.0];
+        let run = |within_samples| {
+            let sender = rto_steps(rtos.to_vec());
+            run_scenario(&scenario(within_samples), sender, acker(), false)
+        };
+        let report = run(3).unwrap();
+        assert_eq!(report.latency_changes[0].time, 250);
+        let err = run(2).unwrap_err();
+        assert!(
+            err.to_string().contains(
+                "rto did not adapt to the latency change at 250 ms within 2 samples: [150, 150] ms, \
+                 none within 200-800 ms around the new true RTT of 400 ms"
+            ),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_failure_explanation() {
+        let scenario = parse_scenario(
+            "name = \"explain\"\ndescription = \"\"\n\
+             [config]\nmin_latency = 50\nmax_latency = 50\n\n\
+             [[actions]]\ntype = \"app_send\"\ntime = 0\ndata = \"a\"\n\n\
+             [[actions]]\ntype = \"app_send\"\ntime = 10\ndata = \"b\"\n\n\
+             [[actions]]\ntype = \"drop_next_from_sender_seq\"\nseq = 1\n\n\
+             [[assertions]]\ntype = \"data_delivered\"\ndata = \"b\"\n\n\
+             [[assertions]]\ntype = \"sender_packet_count\"\nmin = 3\n",
+            true,
+        )
+        .unwrap();
+        // Sends each app message once under timer 2 and cancels it on any ACK.
+        let mut next = 0;
+        let sender = FnProtocol::new()
+            .on_packet(|ctx, _| ctx.cancel_timer(2))
+            .on_app_data(move |ctx, data| {
+                ctx.send_packet(Packet::new_simple(next, 0, 0, data.to_vec()));
+                ctx.start_timer(200, 2);
+                next += 1;
+            });
+        let result = run_scenario_with(&scenario, sender.boxed(), acker(), RunOptions::default());
+        let explanations: Vec<&[Clue]> = result
+            .failures
+            .iter()
+            .map(|failure| match failure {
+                ScenarioError::AssertionFailed { explanation, .. }
+                | ScenarioError::Deadlock { explanation, .. } => &explanation[..],
+                other => panic!("unexpected failure {other}"),
+            })
+            .collect();
+        // The run went quiet with "b" lost, so a deadlock replaces the failed delivery. The
+        // packet count is not about a loss, so only the deadlock gets clues.
+        assert_eq!(explanations.len(), 2);
+        assert!(
+            matches!(
+                &result.failures[0],
+                ScenarioError::Deadlock { waiting_node: NodeId::Sender, time: 100, details, .. }
+                    if details.contains("only 0 of 2 bytes")
+                        && details.contains("Sender ARRIVE seq=0 ack=0 at 100 ms")
+            ),
+            "{}",
+            result.failures[0]
+        );
+        assert!(explanations[1].is_empty());
+        assert_eq!(
+            explanations[0][0].to_string(),
+            "seq=1 was dropped at t=10ms; your sender never retransmitted it; \
+             timer 2 was cancelled at t=100ms"
+        );
+        assert_eq!(
+            explanations[0][1],
+            Clue::Undelivered {
+                sent: 2,
+                delivered: 0
+            }
+        );
+        assert_eq!(explanations[0][2].to_string(), "bytes 0..2 missing");
+        let err = result.into_result().unwrap_err();
+        assert!(err.to_string().contains("\n  Why: seq=1"), "{err}");
+    }
+
+    #[test]
+    fn test_ack_overhead_below() {
+        let scenario = |ratio: f64| {
+            parse_scenario(
+                &format!(
+                    "name = \"acks\"\ndescription = \"\"\nactions = []\n\n\
+                     [config]\nloss_rate = 0.0\ncorrupt_rate = 0.0\n\n\
+                     [[assertions]]\ntype = \"ack_overhead_below\"\nratio = {ratio:?}\n"
+                ),
+                true,
+            )
+            .unwrap()
+        };
+        let run = |ratio: f64| run_scenario(&scenario(ratio), rto_sender(0.0), acker(), false);
+        // Five 1-byte segments (21 wire bytes each) against five 20-byte ACKs.
+        let report = run(1.0).unwrap();
+        assert_eq!(report.traffic.from_receiver.pure_ack_packets, 5);
+        assert_eq!(report.summary().from_sender.data_wire_bytes, 105);
+        let err = run(0.5).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Pure ACKs took 100 wire bytes against 105 for data (ratio 0.95)"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_warmup_excluded_from_traffic() {
+        let scenario = parse_scenario(
+            "name = \"warmup\"\ndescription = \"\"\nactions = []\nassertions = []\n\n\
+             [config]\nloss_rate = 0.0\ncorrupt_rate = 0.0\nmin_latency = 50\n\
+             max_latency = 50\nwarmup_ms = 250\n",
+            true,
+        )
+        .unwrap();
+        let report = run_scenario(&scenario, rto_sender(0.0), acker(), false).unwrap();
+        // Sends at 100..=500 ms, ACKs 50 ms later: the first two sends and the
+        // first ACK fall inside the warm-up.
+        assert_eq!(report.traffic.from_sender.data_packets, 5);
+        assert_eq!(report.steady_traffic.from_sender.data_packets, 3);
+        assert_eq!(report.steady_traffic.from_receiver.pure_ack_packets, 4);
+        assert_eq!(report.summary().efficiency, 0.0);
+    }
+
+    #[test]
+    fn test_metric_bands_warn_without_failing() {
+        let scenario = parse_scenario(
+            "name = \"bands\"\ndescription = \"\"\nactions = []\nassertions = []\n\
+             metric_bands = [\n\
+             { metric = \"rto\", max = 1000, after_ms = 250 },\n\
+             { metric = \"rto\", min = 1000 },\n\
+             { metric = \"cwnd\", max = 1 },\n]\n\n\
+             [config]\nloss_rate = 0.0\ncorrupt_rate = 0.0\n",
+            true,
+        )
+        .unwrap();
+        let report = run_scenario(&scenario, rto_sender(3000.0), acker(), false).unwrap();
+        // Samples at 100..=500 ms; the three from 250 ms on break the first band.
+        let [warning] = report.metric_band_warnings.as_slice() else {
+            panic!("{:?}", report.metric_band_warnings);
+        };
+        assert_eq!(
+            (warning.first_at, warning.outside, warning.samples),
+            (300, 3, 3)
+        );
+        assert_eq!(
+            warning.message(),
+            "rto left its target band (rto <= 1000) at 300 ms: 3 of 3 samples outside, \
+             furthest at 3000"
+        );
+
+        let err = parse_scenario(
+            "name = \"b\"\ndescription = \"\"\nactions = []\nassertions = []\n\
+             metric_bands = [{ metric = \"rto\", min = 5, max = 1 }]\n[config]\n",
+            true,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("min 5 above max 1"), "{err}");
+    }
+
+    #[test]
+    fn test_piggybacked_acks() {
+        let scenario = parse_scenario(
+            "name = \"piggyback\"\ndescription = \"\"\nactions = []\n\n\
+             [config]\nloss_rate = 0.0\ncorrupt_rate = 0.0\n\n\
+             [[assertions]]\ntype = \"piggybacked_acks\"\nmin_fraction = 0.9\n",
+            true,
+        )
+        .unwrap();
+        // Echoes every data segment back with the ACK flag, so each ACK rides on data.
+        let piggybacker = FnProtocol::new().on_packet(|ctx, packet| {
+            let seq = packet.header.seq_num;
+            ctx.send_packet(Packet::new_simple(0, seq, flags::ACK, packet.payload));
+        });
+        let report = run_scenario(&scenario, rto_sender(0.0), piggybacker.boxed(), false).unwrap();
+        assert_eq!(report.summary().piggybacked_ack_fraction, Some(1.0));
+        let err = run_scenario(&scenario, rto_sender(0.0), acker(), false).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("0 of 5 ACKs were piggybacked on data (0%), at least 90% required"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_ack_clocked() {
+        let scenario = parse_scenario(
+            "name = \"clocking\"\ndescription = \"\"\nactions = []\n\n\
+             [config]\nloss_rate = 0.0\ncorrupt_rate = 0.0\n\n\
+             [[assertions]]\ntype = \"ack_clocked\"\nmin_fraction = 0.75\n",
+            true,
+        )
+        .unwrap();
+        // Sends its first segment from `init` and each of the next four as an ACK arrives.
+        let mut sent = 1;
+        let sender = FnProtocol::new()
+            .init(|ctx| ctx.send_packet(Packet::new_simple(0, 0, 0, vec![b'x'])))
+            .on_packet(move |ctx, _| {
+                if sent < 5 {
+                    ctx.send_packet(Packet::new_simple(sent, 0, 0, vec![b'x']));
+                    sent += 1;
+                }
+            });
+        let report = run_scenario(&scenario, sender.boxed(), acker(), false).unwrap();
+        assert_eq!(
+            report.clocking,
+            ClockingStats {
+                ack: 4,
+                timer: 0,
+                other: 1
+            }
+        );
+        assert_eq!(report.summary().ack_clocked_fraction, Some(0.8));
+        let err = run_scenario(&scenario, rto_sender(0.0), acker(), false).unwrap_err();
+        assert!(
+            err.to_string().contains(
+                "0 of 5 data segments were sent as an ACK arrived (0%; 5 from timers), at least 75% required"
+            ),
+            "{err}"
+        );
+    }
 }
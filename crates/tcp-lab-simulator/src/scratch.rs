@@ -0,0 +1,66 @@
+//! The scratch store: string key-value pairs each node's protocol keeps from one run to the
+//! next through `SystemContext::store` and `load`, e.g. an RTO or window tuned over several
+//! experiments. Protocols cannot write files themselves once sandboxed, so the host loads
+//! the store before a run and saves it afterwards:
+//!
+//! ```json
+//! { "sender": { "best_rto": "240" }, "receiver": {} }
+//! ```
+//!
+//! Only practice runs opt in (`tcp-lab-sim-cli --scratch-store FILE`); graded runs never
+//! give protocols a store, so `load` returns nothing there.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::engine::NodeId;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScratchStore {
+    #[serde(default)]
+    pub sender: BTreeMap<String, String>,
+    #[serde(default)]
+    pub receiver: BTreeMap<String, String>,
+}
+
+impl ScratchStore {
+    /// Read the store at `path`; a missing file is an empty store, as on a first run.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Failed to read scratch store {}", path.display()));
+            }
+        };
+        serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse scratch store {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let text = serde_json::to_string_pretty(self)?;
+        fs::write(path, text + "\n")
+            .with_context(|| format!("Failed to write scratch store {}", path.display()))
+    }
+
+    pub fn node(&self, node: NodeId) -> &BTreeMap<String, String> {
+        match node {
+            NodeId::Sender => &self.sender,
+            NodeId::Receiver => &self.receiver,
+        }
+    }
+
+    pub fn node_mut(&mut self, node: NodeId) -> &mut BTreeMap<String, String> {
+        match node {
+            NodeId::Sender => &mut self.sender,
+            NodeId::Receiver => &mut self.receiver,
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! Regression matrix for engine changes: protocol pairs against every scenario in a
+//! directory across several seeds, compared with the outcomes recorded for each pair.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+use anyhow::Context;
+use serde::Deserialize;
+use tcp_lab_abstract::{NodeId, ProtocolFactory, TransportProtocol};
+
+use crate::scenario_runner::{
+    TagFilter, load_scenario, run_scenario_with_factories, scenario_files,
+};
+
+/// Builds a fresh protocol instance; shared so restarts can build more.
+pub type SharedFactory = Rc<dyn Fn() -> anyhow::Result<Box<dyn TransportProtocol>>>;
+
+/// A sender and receiver graded together under one name, e.g. a builtin like `rdt2`.
+pub struct ProtocolPair {
+    pub name: String,
+    pub sender: SharedFactory,
+    pub receiver: SharedFactory,
+}
+
+/// One pair, scenario and seed of the matrix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GradeOutcome {
+    pub pair: String,
+    /// File stem of the scenario, e.g. `test_rdt3`.
+    pub scenario: String,
+    pub seed: u64,
+    /// The grader's error, `None` when every assertion held.
+    pub error: Option<String>,
+}
+
+impl GradeOutcome {
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+impl fmt::Display for GradeOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} / {} (seed {})", self.pair, self.scenario, self.seed)
+    }
+}
+
+/// Recorded outcomes, read from a TOML file such as the workspace's
+/// `tests/expected/builtins.toml`:
+///
+/// ```toml
+/// [pairs.rdt2]
+/// failing = ["test_gbn", { scenario = "test_rdt20", seeds = [1] }]
+/// ```
+///
+/// A scenario listed by name fails with every seed, one listed with `seeds` only with those.
+/// Everything else (including pairs not listed at all) is expected to pass.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expectations {
+    #[serde(default)]
+    pub pairs: BTreeMap<String, PairExpectations>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PairExpectations {
+    #[serde(default)]
+    pub failing: Vec<ExpectedFailure>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum ExpectedFailure {
+    Always(String),
+    WithSeeds { scenario: String, seeds: Vec<u64> },
+}
+
+impl ExpectedFailure {
+    fn covers(&self, scenario: &str, seed: u64) -> bool {
+        match self {
+            ExpectedFailure::Always(name) => name == scenario,
+            ExpectedFailure::WithSeeds {
+                scenario: name,
+                seeds,
+            } => name == scenario && seeds.contains(&seed),
+        }
+    }
+}
+
+impl Expectations {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read expectations {}", path.display()))?;
+        toml::from_str(&content)
+            .with_context(|| format!("Failed to parse expectations {}", path.display()))
+    }
+
+    pub fn expects_pass(&self, pair: &str, scenario: &str, seed: u64) -> bool {
+        self.pairs.get(pair).is_none_or(|expected| {
+            !expected
+                .failing
+                .iter()
+                .any(|failure| failure.covers(scenario, seed))
+        })
+    }
+
+    /// A line for every outcome that differs from the recorded one.
+    pub fn regressions(&self, outcomes: &[GradeOutcome]) -> Vec<String> {
+        outcomes
+            .iter()
+            .filter_map(|outcome| {
+                match (
+                    self.expects_pass(&outcome.pair, &outcome.scenario, outcome.seed),
+                    &outcome.error,
+                ) {
+                    (true, Some(error)) => Some(format!("{outcome}: expected to pass, {error}")),
+                    (false, None) => Some(format!("{outcome}: expected to fail, but passed")),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Grade every pair against every `*.toml` scenario in `scenario_dir` (in file name order)
+/// that `filter` selects, once per seed, overriding each scenario's own seed. Scenarios are
+/// loaded strictly, and both nodes can be restarted.
+pub fn grade_pairs(
+    pairs: &[ProtocolPair],
+    scenario_dir: impl AsRef<Path>,
+    seeds: &[u64],
+    filter: &TagFilter,
+) -> anyhow::Result<Vec<GradeOutcome>> {
+    let mut outcomes = Vec::new();
+    for path in &scenario_files(scenario_dir)? {
+        let scenario = load_scenario(path, true)?;
+        if !filter.matches(&scenario) {
+            continue;
+        }
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        for pair in pairs {
+            for &seed in seeds {
+                let mut scenario = scenario.clone();
+                scenario.config.seed = Some(seed);
+                let factories: Vec<(NodeId, ProtocolFactory)> = vec![
+                    (NodeId::Sender, restart_factory(&pair.sender)),
+                    (NodeId::Receiver, restart_factory(&pair.receiver)),
+                ];
+                let result = (pair.sender)().and_then(|sender| {
+                    let receiver = (pair.receiver)()?;
+                    Ok(run_scenario_with_factories(
+                        &scenario, sender, receiver, true, factories,
+                    )?)
+                });
+                outcomes.push(GradeOutcome {
+                    pair: pair.name.clone(),
+                    scenario: name.clone(),
+                    seed,
+                    error: result.err().map(|err| format!("{err:#}")),
+                });
+            }
+        }
+    }
+    Ok(outcomes)
+}
+
+fn restart_factory(factory: &SharedFactory) -> ProtocolFactory {
+    let factory = Rc::clone(factory);
+    Box::new(move || factory())
+}
@@ -0,0 +1,172 @@
+//! How the receiver's deliveries line up with the stream the sender's application wrote:
+//! which byte ranges never arrived, which arrived more than once and which came after
+//! later bytes, so a failed delivery reads "bytes 1024..2048 missing" instead of only
+//! "data not delivered".
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::ops::Range;
+
+use serde::Serialize;
+
+use crate::i18n::Msg;
+
+/// Ranges of one kind listed in a message; the rest are only counted.
+const MAX_RANGES: usize = 3;
+
+/// Where each delivery landed in the application stream.
+#[derive(Debug, Clone, Default)]
+pub struct StreamTracker {
+    /// Bytes delivered at least once, as disjoint ranges keyed by their start.
+    covered: BTreeMap<u64, u64>,
+    duplicated: Vec<Range<u64>>,
+    reordered: Vec<Range<u64>>,
+    /// End of the furthest delivery so far.
+    furthest: u64,
+}
+
+impl StreamTracker {
+    /// Note that `len` bytes from `offset` in the app stream were delivered.
+    pub fn record_delivery(&mut self, offset: u64, len: u64) {
+        if len == 0 {
+            return;
+        }
+        let end = offset + len;
+        // Covered ranges are disjoint, so their ends grow with their starts.
+        let overlaps: Vec<Range<u64>> = self
+            .covered
+            .range(..end)
+            .rev()
+            .take_while(|&(_, &to)| to > offset)
+            .map(|(&from, &to)| from.max(offset)..to.min(end))
+            .collect();
+        let repeated: u64 = overlaps.iter().map(|range| range.end - range.start).sum();
+        if repeated < len && offset < self.furthest {
+            self.reordered.push(offset..end);
+        }
+        self.duplicated.extend(overlaps);
+        self.furthest = self.furthest.max(end);
+
+        let touching: Vec<u64> = self
+            .covered
+            .range(..=end)
+            .rev()
+            .take_while(|&(_, &to)| to >= offset)
+            .map(|(&from, _)| from)
+            .collect();
+        let (mut from, mut to) = (offset, end);
+        for start in touching {
+            let stop = self.covered.remove(&start).unwrap_or(start);
+            from = from.min(start);
+            to = to.max(stop);
+        }
+        self.covered.insert(from, to);
+    }
+
+    /// The differences against a stream of `sent` bytes.
+    pub fn report(&self, sent: u64) -> StreamReport {
+        let mut missing = Vec::new();
+        let mut at = 0;
+        for (&from, &to) in &self.covered {
+            if from > at {
+                missing.push(at..from.min(sent));
+            }
+            at = at.max(to);
+        }
+        if at < sent {
+            missing.push(at..sent);
+        }
+        StreamReport {
+            sent,
+            missing,
+            duplicated: merge(self.duplicated.clone()),
+            reordered: self.reordered.clone(),
+        }
+    }
+}
+
+/// The receiver's deliveries against the bytes the sender's application wrote, with
+/// offsets in that stream (watermark tags excluded).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct StreamReport {
+    pub sent: u64,
+    /// Ranges never delivered.
+    pub missing: Vec<Range<u64>>,
+    /// Ranges delivered more than once.
+    pub duplicated: Vec<Range<u64>>,
+    /// Deliveries of new bytes that came after a delivery of later ones, in delivery order.
+    pub reordered: Vec<Range<u64>>,
+}
+
+impl StreamReport {
+    /// Whether the deliveries were exactly the sent stream, in order.
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty() && self.duplicated.is_empty() && self.reordered.is_empty()
+    }
+}
+
+impl Display for StreamReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = [
+            (Msg::StreamMissing, &self.missing),
+            (Msg::StreamDuplicated, &self.duplicated),
+            (Msg::StreamReordered, &self.reordered),
+        ]
+        .into_iter()
+        .filter(|(_, ranges)| !ranges.is_empty())
+        .map(|(msg, ranges)| msg.fill(&[&list(ranges)]))
+        .collect();
+        f.write_str(&parts.join("; "))
+    }
+}
+
+/// `ranges` as `0..512, 1024..2048`, with the ones past `MAX_RANGES` counted.
+fn list(ranges: &[Range<u64>]) -> String {
+    let mut shown: Vec<String> = ranges
+        .iter()
+        .take(MAX_RANGES)
+        .map(|range| format!("{}..{}", range.start, range.end))
+        .collect();
+    if ranges.len() > MAX_RANGES {
+        shown.push(Msg::StreamMore.fill(&[&(ranges.len() - MAX_RANGES)]));
+    }
+    shown.join(", ")
+}
+
+/// Sort `ranges` and join the ones that overlap or touch.
+fn merge(mut ranges: Vec<Range<u64>>) -> Vec<Range<u64>> {
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<Range<u64>> = Vec::new();
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_gaps_duplicates_and_reordering() {
+        let mut tracker = StreamTracker::default();
+        for (offset, len) in [(0, 512), (1024, 512), (512, 512), (0, 512), (2048, 512)] {
+            tracker.record_delivery(offset, len);
+        }
+        let report = tracker.report(3072);
+        assert_eq!(report.missing, [1536..2048, 2560..3072]);
+        assert_eq!(
+            report.to_string(),
+            "bytes 1536..2048, 2560..3072 missing; bytes 0..512 delivered more than once; \
+             bytes 512..1024 delivered out of order"
+        );
+
+        let mut in_order = StreamTracker::default();
+        in_order.record_delivery(0, 10);
+        in_order.record_delivery(10, 10);
+        assert!(in_order.report(20).is_clean());
+    }
+}
@@ -0,0 +1,50 @@
+//! Optional OpenTelemetry export of callback spans.
+//!
+//! The engine opens a `student_callback` span around every protocol callback and
+//! each language bridge opens a nested `bridge_call` span, so the exported traces
+//! show both total callback latency and how much of it is JNI/PyO3/FFI overhead.
+//! The OTLP endpoint follows the standard `OTEL_EXPORTER_OTLP_*` env variables.
+
+use anyhow::{Context, Result};
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::SpanExporter;
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tracing::Subscriber;
+use tracing_subscriber::Layer;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::registry::LookupSpan;
+
+/// Flushes and shuts down the exporter when dropped.
+pub struct OtelGuard {
+    provider: SdkTracerProvider,
+}
+
+impl Drop for OtelGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            eprintln!("Failed to flush OpenTelemetry spans: {e}");
+        }
+    }
+}
+
+/// Build a tracing layer that exports callback spans over OTLP/HTTP.
+/// Keep the returned guard alive until the run finishes so pending spans get flushed.
+pub fn otel_layer<S>(service_name: &'static str) -> Result<(impl Layer<S>, OtelGuard)>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .build()
+        .context("Failed to build OTLP span exporter")?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(service_name).build())
+        .build();
+    let tracer = provider.tracer(service_name);
+    let layer = tracing_opentelemetry::layer()
+        .with_tracer(tracer)
+        .with_filter(LevelFilter::DEBUG);
+    Ok((layer, OtelGuard { provider }))
+}
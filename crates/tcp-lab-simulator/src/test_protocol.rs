@@ -0,0 +1,98 @@
+//! A protocol made of closures, so a test states only the callbacks it exercises.
+
+use tcp_lab_abstract::{Packet, SystemContext, TransportProtocol};
+
+type Callback<A> = Box<dyn FnMut(&mut dyn SystemContext, A)>;
+type DataCallback = Box<dyn FnMut(&mut dyn SystemContext, &[u8])>;
+
+/// A protocol whose callbacks run the closures it was given; the others do nothing.
+/// State shared between callbacks, or read by the test afterwards, lives in an
+/// `Rc<Cell<_>>` or `Rc<RefCell<_>>` the closures capture.
+pub struct FnProtocol {
+    init: Callback<()>,
+    on_packet: Callback<Packet>,
+    on_timer: Callback<u32>,
+    on_app_data: DataCallback,
+    on_close: Callback<()>,
+    language: &'static str,
+}
+
+impl Default for FnProtocol {
+    fn default() -> Self {
+        Self {
+            init: Box::new(|_, ()| {}),
+            on_packet: Box::new(|_, _| {}),
+            on_timer: Box::new(|_, _| {}),
+            on_app_data: Box::new(|_, _| {}),
+            on_close: Box::new(|_, ()| {}),
+            language: "rust",
+        }
+    }
+}
+
+impl FnProtocol {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn init(mut self, mut f: impl FnMut(&mut dyn SystemContext) + 'static) -> Self {
+        self.init = Box::new(move |ctx, ()| f(ctx));
+        self
+    }
+
+    pub fn on_packet(mut self, f: impl FnMut(&mut dyn SystemContext, Packet) + 'static) -> Self {
+        self.on_packet = Box::new(f);
+        self
+    }
+
+    pub fn on_timer(mut self, f: impl FnMut(&mut dyn SystemContext, u32) + 'static) -> Self {
+        self.on_timer = Box::new(f);
+        self
+    }
+
+    pub fn on_app_data(mut self, f: impl FnMut(&mut dyn SystemContext, &[u8]) + 'static) -> Self {
+        self.on_app_data = Box::new(f);
+        self
+    }
+
+    pub fn on_close(mut self, mut f: impl FnMut(&mut dyn SystemContext) + 'static) -> Self {
+        self.on_close = Box::new(move |ctx, ()| f(ctx));
+        self
+    }
+
+    /// Report `language` to the engine, which phrases rejections for it.
+    pub fn language(mut self, language: &'static str) -> Self {
+        self.language = language;
+        self
+    }
+
+    pub fn boxed(self) -> Box<dyn TransportProtocol> {
+        Box::new(self)
+    }
+}
+
+impl TransportProtocol for FnProtocol {
+    fn init(&mut self, ctx: &mut dyn SystemContext) {
+        (self.init)(ctx, ());
+    }
+
+    fn on_packet(&mut self, ctx: &mut dyn SystemContext, packet: Packet) {
+        (self.on_packet)(ctx, packet);
+    }
+
+    fn on_timer(&mut self, ctx: &mut dyn SystemContext, timer_id: u32) {
+        (self.on_timer)(ctx, timer_id);
+    }
+
+    fn on_app_data(&mut self, ctx: &mut dyn SystemContext, data: &[u8]) {
+        (self.on_app_data)(ctx, data);
+    }
+
+    fn on_close(&mut self, ctx: &mut dyn SystemContext) {
+        (self.on_close)(ctx, ());
+    }
+
+    fn language(&self) -> &'static str {
+        self.language
+    }
+}
@@ -1,16 +1,260 @@
+pub mod binary;
+pub mod bundle;
+pub mod dot;
+
+pub use dot::export_dot;
+
 use serde::Serialize;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use tcp_lab_abstract::SimConfig;
 
-use crate::engine::LinkEventSummary;
+use crate::connection::ConnectionTrace;
+use crate::engine::{
+    Annotation, AppBackpressureStats, BridgeWarning, BridgeWarningEvent, CallRecord,
+    CallbackReport, ClockingStats, CorruptedPacket, DebugEvent, DirectionBytes, DropEvent,
+    DropReason, HalfClose, LatencyChange, LinkEventKind, LinkEventSummary, LogEntry, PacingStats,
+    PacketEvent, RefusedAppSend, RejectedAction, SendStorm, Shutdown, StateTransition,
+    TamperedDuplicate, TimerRecord, TimerReport, TrafficStats, TruncatedPacket,
+};
+use crate::hints::Hint;
+use crate::latency::{LatencyPercentiles, LatencyReport};
+use crate::media::MediaReport;
+use crate::scenario_runner::{AssertionOutcome, MetricBandWarning, SeedDerivation};
+use crate::scratch::ScratchStore;
+use crate::stream::StreamReport;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct SimulationReport {
     pub config: SimConfig,
     pub duration_ms: u64,
+    /// The earliest a protocol could have delivered all app data on this link; `None`
+    /// if the application sent nothing.
+    pub optimal_completion_ms: Option<u64>,
     pub delivered_data: Vec<Vec<u8>>,
+    /// Echoes delivered back to the sender's application (`app_model = "echo"`).
+    pub echoed_data: Vec<Vec<u8>>,
+    /// Deadline results of `media_stream` frames, if the run sent any.
+    pub media: Option<MediaReport>,
+    /// Missing, repeated and reordered ranges of the receiver's deliveries.
+    pub stream: StreamReport,
     pub sender_packet_count: u32,
+    /// Sender data packets that repeat the seq and payload of an earlier one; see
+    /// `ReportSummary::retransmissions`.
+    pub sender_retransmissions: u32,
+    /// ACKs that reached the sender covering a retransmitted seq; tagged `ambiguous`
+    /// in `packets`.
+    pub ambiguous_acks: u32,
+    pub max_in_flight: usize,
+    pub connection: ConnectionTrace,
+    pub timers: TimerReport,
+    /// Every timer a protocol started, with how it ended. With `cause` and `arrival` in
+    /// `packets`, its `cause` and `expiry` link what the run did into a causal graph.
+    pub timer_log: Vec<TimerRecord>,
+    pub send_storms: Vec<SendStorm>,
+    /// App sends the sender's protocol turned down with `refuse_app_data`.
+    pub refused_app_sends: Vec<RefusedAppSend>,
+    pub pacing: PacingStats,
+    /// How long the sender's protocol held its application back (`app_pause`).
+    pub app_backpressure: AppBackpressureStats,
+    pub clocking: ClockingStats,
+    pub drops: Vec<DropEvent>,
+    /// Every packet sent, dropped, corrupted, received or injected, in order.
+    pub packets: Vec<PacketEvent>,
+    /// Dropped packets per reason, MTU violations included.
+    pub drop_counts: BTreeMap<DropReason, u64>,
+    pub latency: LatencyReport,
+    pub traffic: TrafficStats,
+    /// `traffic` from `SimConfig::warmup_ms` on, which throughput and efficiency use.
+    pub steady_traffic: TrafficStats,
+    /// How often each node's callbacks ran and how long they took.
+    pub callbacks: CallbackReport,
     pub sender_window_sizes: Vec<u16>,
     pub metrics: HashMap<String, Vec<(u64, f64)>>,
     pub link_events: Vec<LinkEventSummary>,
+    pub link_event_counts: BTreeMap<LinkEventKind, u64>,
+    pub rejected_actions: Vec<RejectedAction>,
+    pub integrity_violations: Vec<RejectedAction>,
+    /// Callbacks that saw `now()` go backwards; see `Simulator::clock_anomalies`.
+    pub clock_anomalies: Vec<RejectedAction>,
+    /// `set_latency` changes the run reached.
+    pub latency_changes: Vec<LatencyChange>,
+    /// Where the channel corrupted each packet, for checking checksum handling.
+    pub corrupted_packets: Vec<CorruptedPacket>,
+    /// Where the channel cut payloads short, for checking length validation.
+    pub truncated_packets: Vec<TruncatedPacket>,
+    /// Altered copies of data packets the channel delivered, for checking that receivers
+    /// verify every arrival.
+    pub tampered_duplicates: Vec<TamperedDuplicate>,
+    /// The scenario's `shutdown`, if the run reached it.
+    pub shutdown: Option<Shutdown>,
+    /// The scenario's `half_close` actions the run reached.
+    pub half_closes: Vec<HalfClose>,
+    /// Problems the language bridges worked around, the first 100 of each kind.
+    pub bridge_warnings: Vec<BridgeWarningEvent>,
+    /// Every bridge warning, counted per kind.
+    pub bridge_warning_counts: BTreeMap<BridgeWarning, u64>,
+    /// Every callback the protocols got, with `call_log = true`; empty otherwise.
+    pub call_log: Vec<CallRecord>,
+    /// Engine decisions, with `--debug-events`; empty otherwise.
+    pub debug_events: Vec<DebugEvent>,
+    /// The scratch store as the run left it, when the host gave the protocols one.
+    pub scratch_store: Option<ScratchStore>,
+    pub logs: Vec<LogEntry>,
+    /// `log` messages past `SimConfig::log_limit`, only counted.
+    pub dropped_logs: u64,
+    /// Values of metrics past `SimConfig::metric_name_limit` distinct names, per name.
+    pub dropped_metrics: BTreeMap<String, u64>,
+    pub states: Vec<StateTransition>,
+    /// Scenario annotations the run reached; each is also inline in `link_events`.
+    pub annotations: Vec<Annotation>,
+    /// Advice for the bug signatures a failed scenario run matched; empty otherwise.
+    pub hints: Vec<Hint>,
+    /// How the seed was derived when the run used a per-student seed.
+    pub seed_derivation: Option<SeedDerivation>,
+    /// How each of the scenario's assertions went, in scenario order; empty if the run
+    /// ended before they were checked, e.g. on a timeout.
+    pub assertions: Vec<AssertionOutcome>,
+    /// The scenario's `metric_bands` the run's metrics left; they do not fail it.
+    pub metric_band_warnings: Vec<MetricBandWarning>,
+}
+
+/// Aggregate counts over a run, derived from the report's counters rather than by
+/// re-parsing `link_events` (which `link_event_limit` may have truncated).
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ReportSummary {
+    /// Packets handed to the channel in both directions.
+    pub sends: u64,
+    pub sender_packets: u64,
+    pub receiver_packets: u64,
+    /// Sender data packets with the same seq and the same payload as the previous packet
+    /// sent with that seq. Zero-window probes, and reused seqs carrying new data
+    /// (alternating bit, wrapped windows), are not retransmissions.
+    pub retransmissions: u64,
+    /// ACKs that cannot be used as RTT samples under Karn's algorithm.
+    pub ambiguous_acks: u64,
+    /// Sender segments classified as zero-window probes (counted in `sends` too).
+    pub zero_window_probes: u64,
+    pub random_drops: u64,
+    pub injected_drops: u64,
+    pub random_corruptions: u64,
+    pub injected_corruptions: u64,
+    /// Payloads the channel cut short, at random or by a scenario action.
+    pub truncations: u64,
+    /// Altered copies of data packets the channel delivered.
+    pub tampered_duplicates: u64,
+    /// Drops per reason, including sends refused for exceeding the MTU (which never
+    /// reached the channel and are not in `sends`).
+    pub drops_by_reason: BTreeMap<DropReason, u64>,
+    /// Accepted `deliver_data` calls.
+    pub deliveries: u64,
+    pub rejected_actions: u64,
+    pub integrity_violations: u64,
+    /// Bridge warnings of every kind.
+    pub bridge_warnings: u64,
+    /// Log messages past `SimConfig::log_limit`.
+    pub dropped_logs: u64,
+    /// Metric values under names past `SimConfig::metric_name_limit`.
+    pub dropped_metric_values: u64,
+    pub sender_payload_bytes: u64,
+    pub sender_wire_bytes: u64,
+    pub receiver_payload_bytes: u64,
+    pub receiver_wire_bytes: u64,
+    pub from_sender: DirectionBytes,
+    pub from_receiver: DirectionBytes,
+    /// Pure-ACK wire bytes per data wire byte; see `TrafficStats::ack_overhead`.
+    pub ack_overhead: Option<f64>,
+    /// Share of ACKs piggybacked on data; see `TrafficStats::piggybacked_fraction`.
+    pub piggybacked_ack_fraction: Option<f64>,
+    /// Share of the sender's data segments sent as an ACK arrived; see
+    /// `ClockingStats::ack_clocked_fraction`.
+    pub ack_clocked_fraction: Option<f64>,
+    pub delivered_bytes: u64,
+    /// See `SimulationReport::goodput_bps`.
+    pub goodput_bps: f64,
+    /// See `SimulationReport::efficiency`.
+    pub efficiency: f64,
+    pub channel_latency: Option<LatencyPercentiles>,
+    pub delivery_latency: Option<LatencyPercentiles>,
+    pub round_trip_latency: Option<LatencyPercentiles>,
+    pub optimal_completion_ms: Option<u64>,
+    /// `optimal_completion_ms` over the run's duration: 1.0 means no protocol could
+    /// have finished sooner.
+    pub efficiency_vs_optimal: Option<f64>,
+}
+
+impl ReportSummary {
+    pub fn drops(&self) -> u64 {
+        self.random_drops + self.injected_drops
+    }
+
+    pub fn corruptions(&self) -> u64 {
+        self.random_corruptions + self.injected_corruptions
+    }
+}
+
+impl SimulationReport {
+    /// Delivered bits per second after the warm-up (`SimConfig::warmup_ms`), so slow
+    /// start does not drag down a protocol compared on steady-state behaviour.
+    pub fn goodput_bps(&self) -> f64 {
+        let warmup = self.config.warmup_ms.min(self.duration_ms);
+        self.steady_traffic.goodput_bps(self.duration_ms - warmup)
+    }
+
+    /// Delivered bytes per wire byte after the warm-up.
+    pub fn efficiency(&self) -> f64 {
+        self.steady_traffic.efficiency()
+    }
+
+    pub fn summary(&self) -> ReportSummary {
+        let count = |kind| self.link_event_counts.get(&kind).copied().unwrap_or(0);
+        // Lost packets are recorded as drops instead of sends.
+        let sends = count(LinkEventKind::Send)
+            + count(LinkEventKind::Probe)
+            + count(LinkEventKind::Drop)
+            + count(LinkEventKind::InjectedDrop);
+        let sender_packets = u64::from(self.sender_packet_count);
+        ReportSummary {
+            sends,
+            sender_packets,
+            receiver_packets: sends.saturating_sub(sender_packets),
+            retransmissions: u64::from(self.sender_retransmissions),
+            ambiguous_acks: u64::from(self.ambiguous_acks),
+            zero_window_probes: count(LinkEventKind::Probe),
+            random_drops: count(LinkEventKind::Drop),
+            injected_drops: count(LinkEventKind::InjectedDrop),
+            random_corruptions: count(LinkEventKind::Corrupt),
+            injected_corruptions: count(LinkEventKind::InjectedCorrupt),
+            truncations: count(LinkEventKind::Truncate),
+            tampered_duplicates: count(LinkEventKind::TamperedDuplicate),
+            drops_by_reason: self.drop_counts.clone(),
+            deliveries: self.delivered_data.len() as u64,
+            rejected_actions: self.rejected_actions.len() as u64,
+            integrity_violations: self.integrity_violations.len() as u64,
+            bridge_warnings: self.bridge_warning_counts.values().sum(),
+            dropped_logs: self.dropped_logs,
+            dropped_metric_values: self.dropped_metrics.values().sum(),
+            sender_payload_bytes: self.traffic.sender_payload_bytes,
+            sender_wire_bytes: self.traffic.sender_wire_bytes,
+            receiver_payload_bytes: self.traffic.receiver_payload_bytes,
+            receiver_wire_bytes: self.traffic.receiver_wire_bytes,
+            from_sender: self.traffic.from_sender,
+            from_receiver: self.traffic.from_receiver,
+            ack_overhead: self.traffic.ack_overhead(),
+            piggybacked_ack_fraction: self.traffic.piggybacked_fraction(),
+            ack_clocked_fraction: self.clocking.ack_clocked_fraction(),
+            delivered_bytes: self.traffic.delivered_bytes,
+            goodput_bps: self.goodput_bps(),
+            efficiency: self.efficiency(),
+            channel_latency: self.latency.channel.as_ref().map(|s| s.percentiles()),
+            delivery_latency: self.latency.delivery.as_ref().map(|s| s.percentiles()),
+            round_trip_latency: self.latency.round_trip.as_ref().map(|s| s.percentiles()),
+            optimal_completion_ms: self.optimal_completion_ms,
+            efficiency_vs_optimal: self.optimal_completion_ms.map(|optimal| {
+                match self.duration_ms {
+                    0 => 1.0,
+                    ms => optimal as f64 / ms as f64,
+                }
+            }),
+        }
+    }
 }
@@ -0,0 +1,512 @@
+//! A compact binary trace that can be read from any point in time, for runs whose JSON
+//! trace is too large to load just to look at a few seconds of it.
+//!
+//! Layout, all integers little-endian:
+//!
+//! ```text
+//! "TLTR" version:u8
+//! record*          len:u32, then `len` bytes: tag:u8 time:u64 fields...
+//! index            count:u64, then count × (time:u64 offset:u64)
+//! footer           index_offset:u64 "TLIX"
+//! ```
+//!
+//! Records are in time order. The index holds the time and file offset of every
+//! `INDEX_STRIDE`th record, so a reader seeks to the nearest entry before a timestamp
+//! and decodes at most one stride of records from there.
+
+use anyhow::{Context, Result, bail, ensure};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::engine::{
+    LinkEventKind, LinkEventSummary, LinkPacket, NodeId, PacketEvent, PacketEventKind,
+};
+use crate::trace::SimulationReport;
+
+const MAGIC: &[u8; 4] = b"TLTR";
+const INDEX_MAGIC: &[u8; 4] = b"TLIX";
+const VERSION: u8 = 1;
+const HEADER_LEN: u64 = 5;
+const FOOTER_LEN: i64 = 12;
+/// Records between two index entries.
+const INDEX_STRIDE: u64 = 256;
+
+/// Stands for `None` in a packet record's event IDs.
+const NO_EVENT: u64 = u64::MAX;
+/// Stands for `None` in a channel record's latency.
+const NO_LATENCY: u64 = u64::MAX;
+
+/// A link event without a packet; traces written before link events carried their packet
+/// hold only these.
+const TAG_LINK: u8 = 0;
+const TAG_PACKET: u8 = 1;
+/// A link event about a packet in the channel.
+const TAG_CHANNEL: u8 = 2;
+
+/// Wire codes of the enums, by position; append only, so old traces stay readable.
+const LINK_KINDS: [LinkEventKind; 24] = [
+    LinkEventKind::Send,
+    LinkEventKind::Probe,
+    LinkEventKind::Drop,
+    LinkEventKind::InjectedDrop,
+    LinkEventKind::Corrupt,
+    LinkEventKind::InjectedCorrupt,
+    LinkEventKind::Deliver,
+    LinkEventKind::Rejected,
+    LinkEventKind::IntegrityViolation,
+    LinkEventKind::Suppressed,
+    LinkEventKind::Restart,
+    LinkEventKind::SendStorm,
+    LinkEventKind::Annotation,
+    LinkEventKind::Injected,
+    LinkEventKind::AckCompressed,
+    LinkEventKind::Rewrite,
+    LinkEventKind::LatencyChange,
+    LinkEventKind::Policed,
+    LinkEventKind::Close,
+    LinkEventKind::QueueDrop,
+    LinkEventKind::Mark,
+    LinkEventKind::Truncate,
+    LinkEventKind::TamperedDuplicate,
+    LinkEventKind::AppFlow,
+];
+const PACKET_KINDS: [PacketEventKind; 5] = [
+    PacketEventKind::Sent,
+    PacketEventKind::Dropped,
+    PacketEventKind::Corrupted,
+    PacketEventKind::Arrived,
+    PacketEventKind::Injected,
+];
+const NODES: [NodeId; 2] = [NodeId::Sender, NodeId::Receiver];
+
+/// One entry of a binary trace.
+#[derive(Debug, Clone)]
+pub enum TraceRecord {
+    Link(LinkEventSummary),
+    Packet(PacketEvent),
+}
+
+impl TraceRecord {
+    pub fn time(&self) -> u64 {
+        match self {
+            TraceRecord::Link(event) => event.time,
+            TraceRecord::Packet(event) => event.time,
+        }
+    }
+}
+
+/// Write `report`'s link events and packet events to `path`, merged in time order.
+pub fn write_report(path: &Path, report: &SimulationReport) -> Result<()> {
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create trace file {}", path.display()))?;
+    encode_report(BufWriter::new(file), report)?
+        .flush()
+        .with_context(|| format!("Failed to write trace file {}", path.display()))
+}
+
+/// Write `report`'s records to `out` as `write_report` does, returning `out`.
+pub fn encode_report<W: Write>(out: W, report: &SimulationReport) -> Result<W> {
+    let mut writer = TraceWriter::new(out)?;
+    let mut links = report.link_events.iter().peekable();
+    let mut packets = report.packets.iter().peekable();
+    loop {
+        let record = match (links.peek(), packets.peek()) {
+            (Some(link), Some(packet)) if link.time <= packet.time => {
+                TraceRecord::Link(links.next().unwrap().clone())
+            }
+            (_, Some(_)) => TraceRecord::Packet(packets.next().unwrap().clone()),
+            (Some(_), None) => TraceRecord::Link(links.next().unwrap().clone()),
+            (None, None) => break,
+        };
+        writer.write(&record)?;
+    }
+    writer.finish()
+}
+
+pub struct TraceWriter<W: Write> {
+    out: W,
+    offset: u64,
+    records: u64,
+    last_time: u64,
+    index: Vec<(u64, u64)>,
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub fn new(mut out: W) -> Result<Self> {
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION])?;
+        Ok(Self {
+            out,
+            offset: HEADER_LEN,
+            records: 0,
+            last_time: 0,
+            index: Vec::new(),
+        })
+    }
+
+    /// Append `record`, which must not be older than the previous one.
+    pub fn write(&mut self, record: &TraceRecord) -> Result<()> {
+        let time = record.time();
+        ensure!(
+            time >= self.last_time,
+            "Trace records out of order: t={time}ms after t={}ms",
+            self.last_time
+        );
+        if self.records.is_multiple_of(INDEX_STRIDE) {
+            self.index.push((time, self.offset));
+        }
+        let payload = encode(record);
+        let len = u32::try_from(payload.len()).context("Trace record too large")?;
+        self.out.write_all(&len.to_le_bytes())?;
+        self.out.write_all(&payload)?;
+        self.offset += 4 + u64::from(len);
+        self.records += 1;
+        self.last_time = time;
+        Ok(())
+    }
+
+    /// Write the index and footer; returns the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        let index_offset = self.offset;
+        self.out
+            .write_all(&(self.index.len() as u64).to_le_bytes())?;
+        for (time, offset) in &self.index {
+            self.out.write_all(&time.to_le_bytes())?;
+            self.out.write_all(&offset.to_le_bytes())?;
+        }
+        self.out.write_all(&index_offset.to_le_bytes())?;
+        self.out.write_all(INDEX_MAGIC)?;
+        Ok(self.out)
+    }
+}
+
+/// Reads records in order, from the start or from any timestamp (`seek_to_time`).
+pub struct TraceReader<R: Read + Seek> {
+    input: R,
+    index: Vec<(u64, u64)>,
+    pos: u64,
+    end: u64,
+}
+
+impl TraceReader<BufReader<File>> {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open trace file {}", path.display()))?;
+        Self::new(BufReader::new(file)).with_context(|| format!("In {}", path.display()))
+    }
+}
+
+impl<R: Read + Seek> TraceReader<R> {
+    /// Check the header and load the index; the reader starts at the first record.
+    pub fn new(mut input: R) -> Result<Self> {
+        let mut header = [0; HEADER_LEN as usize];
+        input
+            .read_exact(&mut header)
+            .context("Not a binary trace")?;
+        ensure!(&header[..4] == MAGIC, "Not a binary trace");
+        ensure!(
+            header[4] == VERSION,
+            "Unsupported binary trace version {}",
+            header[4]
+        );
+
+        input.seek(SeekFrom::End(-FOOTER_LEN))?;
+        let index_offset = read_u64(&mut input).context("Truncated binary trace")?;
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        ensure!(&magic == INDEX_MAGIC, "Truncated binary trace (no index)");
+
+        input.seek(SeekFrom::Start(index_offset))?;
+        let count = read_u64(&mut input)?;
+        let index = (0..count)
+            .map(|_| Ok((read_u64(&mut input)?, read_u64(&mut input)?)))
+            .collect::<Result<Vec<_>>>()
+            .context("Truncated binary trace index")?;
+
+        input.seek(SeekFrom::Start(HEADER_LEN))?;
+        Ok(Self {
+            input,
+            index,
+            pos: HEADER_LEN,
+            end: index_offset,
+        })
+    }
+
+    /// Position the reader at the first record at or after `time`.
+    pub fn seek_to_time(&mut self, time: u64) -> Result<()> {
+        // Entries at `time` itself may not be the first record with that time.
+        let start = match self.index.partition_point(|(t, _)| *t < time) {
+            0 => HEADER_LEN,
+            n => self.index[n - 1].1,
+        };
+        self.jump(start)?;
+        loop {
+            let at = self.pos;
+            match self.next_record()? {
+                Some(record) if record.time() < time => {}
+                Some(_) => return self.jump(at),
+                None => return Ok(()),
+            }
+        }
+    }
+
+    /// The next record, or `None` at the end of the trace.
+    pub fn next_record(&mut self) -> Result<Option<TraceRecord>> {
+        if self.pos >= self.end {
+            return Ok(None);
+        }
+        let mut len = [0; 4];
+        self.input.read_exact(&mut len)?;
+        let len = u32::from_le_bytes(len);
+        let mut payload = vec![0; len as usize];
+        self.input
+            .read_exact(&mut payload)
+            .context("Truncated trace record")?;
+        self.pos += 4 + u64::from(len);
+        decode(&payload).map(Some)
+    }
+
+    fn jump(&mut self, offset: u64) -> Result<()> {
+        self.input.seek(SeekFrom::Start(offset))?;
+        self.pos = offset;
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Iterator for TraceReader<R> {
+    type Item = Result<TraceRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+fn read_u64(input: &mut impl Read) -> Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn code<T: PartialEq>(table: &[T], value: &T) -> u8 {
+    table
+        .iter()
+        .position(|v| v == value)
+        .expect("every variant has a wire code") as u8
+}
+
+fn encode(record: &TraceRecord) -> Vec<u8> {
+    let mut out = Vec::new();
+    match record {
+        TraceRecord::Link(event) => {
+            out.push(if event.packet.is_some() {
+                TAG_CHANNEL
+            } else {
+                TAG_LINK
+            });
+            out.extend(event.time.to_le_bytes());
+            out.push(code(&LINK_KINDS, &event.kind));
+            if let Some(packet) = &event.packet {
+                out.push(code(&NODES, &packet.from));
+                out.extend(packet.seq.to_le_bytes());
+                out.extend(packet.ack.to_le_bytes());
+                out.extend(packet.latency.unwrap_or(NO_LATENCY).to_le_bytes());
+            }
+            out.extend(event.description.as_bytes());
+        }
+        TraceRecord::Packet(event) => {
+            out.push(TAG_PACKET);
+            out.extend(event.time.to_le_bytes());
+            out.push(code(&PACKET_KINDS, &event.kind));
+            out.push(code(&NODES, &event.node));
+            out.extend(event.seq.to_le_bytes());
+            out.extend(event.ack.to_le_bytes());
+            out.push(event.flags);
+            out.extend(event.window.to_le_bytes());
+            out.extend((event.len as u64).to_le_bytes());
+            out.push(event.ambiguous as u8);
+            for id in [event.cause, event.arrival] {
+                out.extend(id.unwrap_or(NO_EVENT).to_le_bytes());
+            }
+            if let Some(note) = &event.note {
+                out.extend(note.as_bytes());
+            }
+        }
+    }
+    out
+}
+
+/// Reads fixed-size fields off the front of a record.
+struct Fields<'a>(&'a [u8]);
+
+impl Fields<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let Some((head, rest)) = self.0.split_first_chunk::<N>() else {
+            bail!("Truncated trace record");
+        };
+        self.0 = rest;
+        Ok(*head)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    /// An optional event ID; absent from records written before causality was recorded.
+    fn event_id(&mut self) -> Result<Option<u64>> {
+        if self.0.is_empty() {
+            return Ok(None);
+        }
+        let id = u64::from_le_bytes(self.take()?);
+        Ok((id != NO_EVENT).then_some(id))
+    }
+
+    /// The rest of the record as an `annotate_packet` note, if there is any.
+    fn note(&mut self) -> Result<Option<String>> {
+        let rest = std::mem::take(&mut self.0);
+        if rest.is_empty() {
+            return Ok(None);
+        }
+        let note = String::from_utf8(rest.to_vec()).context("Packet note is not UTF-8")?;
+        Ok(Some(note))
+    }
+
+    fn lookup<T: Copy>(&mut self, table: &[T], what: &str) -> Result<T> {
+        let code = self.u8()?;
+        table
+            .get(code as usize)
+            .copied()
+            .with_context(|| format!("Unknown {what} code {code} in trace"))
+    }
+}
+
+fn decode(payload: &[u8]) -> Result<TraceRecord> {
+    let mut fields = Fields(payload);
+    let tag = fields.u8()?;
+    let time = u64::from_le_bytes(fields.take()?);
+    match tag {
+        TAG_LINK | TAG_CHANNEL => {
+            let kind = fields.lookup(&LINK_KINDS, "link event")?;
+            let packet = if tag == TAG_CHANNEL {
+                let from = fields.lookup(&NODES, "node")?;
+                let seq = u32::from_le_bytes(fields.take()?);
+                let ack = u32::from_le_bytes(fields.take()?);
+                let latency = u64::from_le_bytes(fields.take()?);
+                Some(LinkPacket {
+                    from,
+                    seq,
+                    ack,
+                    latency: (latency != NO_LATENCY).then_some(latency),
+                })
+            } else {
+                None
+            };
+            let description = String::from_utf8(fields.0.to_vec())
+                .context("Link event description is not UTF-8")?;
+            Ok(TraceRecord::Link(LinkEventSummary {
+                time,
+                kind,
+                description,
+                packet,
+            }))
+        }
+        TAG_PACKET => Ok(TraceRecord::Packet(PacketEvent {
+            time,
+            kind: fields.lookup(&PACKET_KINDS, "packet event")?,
+            node: fields.lookup(&NODES, "node")?,
+            seq: u32::from_le_bytes(fields.take()?),
+            ack: u32::from_le_bytes(fields.take()?),
+            flags: fields.u8()?,
+            window: u16::from_le_bytes(fields.take()?),
+            len: u64::from_le_bytes(fields.take()?) as usize,
+            ambiguous: fields.u8()? != 0,
+            cause: fields.event_id()?,
+            arrival: fields.event_id()?,
+            note: fields.note()?,
+        })),
+        other => bail!("Unknown trace record tag {other}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_seek_to_time() {
+        let mut writer = TraceWriter::new(Vec::new()).unwrap();
+        for i in 0..1000u64 {
+            let record = if i % 2 == 0 {
+                TraceRecord::Link(LinkEventSummary {
+                    time: i / 3,
+                    kind: LinkEventKind::Send,
+                    description: format!("event {i}"),
+                    // Every other link event is about a packet.
+                    packet: (i % 4 == 0).then_some(LinkPacket {
+                        from: NodeId::Sender,
+                        seq: i as u32,
+                        ack: 1,
+                        latency: Some(i),
+                    }),
+                })
+            } else {
+                TraceRecord::Packet(PacketEvent {
+                    time: i / 3,
+                    kind: PacketEventKind::Arrived,
+                    node: NodeId::Receiver,
+                    seq: i as u32,
+                    ack: 0,
+                    flags: 0,
+                    window: 0,
+                    len: 1,
+                    ambiguous: false,
+                    cause: Some(i),
+                    arrival: None,
+                    note: None,
+                })
+            };
+            writer.write(&record).unwrap();
+        }
+        let bytes = writer.finish().unwrap();
+
+        let mut reader = TraceReader::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.by_ref().count(), 1000);
+        // t=200 starts at record 600, past two index strides.
+        reader.seek_to_time(200).unwrap();
+        let rest: Vec<TraceRecord> = reader.by_ref().map(Result::unwrap).collect();
+        assert_eq!(rest.len(), 400);
+        match &rest[0] {
+            TraceRecord::Link(event) => {
+                assert_eq!(event.description, "event 600");
+                let packet = event.packet.unwrap();
+                assert_eq!((packet.seq, packet.latency), (600, Some(600)));
+            }
+            other => panic!("unexpected record {other:?}"),
+        }
+        match &rest[2] {
+            TraceRecord::Link(event) => {
+                assert_eq!(event.description, "event 602");
+                assert!(event.packet.is_none());
+            }
+            other => panic!("unexpected record {other:?}"),
+        }
+        match &rest[1] {
+            TraceRecord::Packet(event) => {
+                assert_eq!((event.cause, event.arrival), (Some(601), None))
+            }
+            other => panic!("unexpected record {other:?}"),
+        }
+        reader.seek_to_time(10_000).unwrap();
+        assert!(reader.next().is_none());
+
+        let mut writer = TraceWriter::new(Vec::new()).unwrap();
+        writer.write(&rest[1]).unwrap();
+        assert!(writer.write(&rest[0].clone()).is_ok());
+        let mut early = rest[0].clone();
+        if let TraceRecord::Link(event) = &mut early {
+            event.time = 0;
+        }
+        assert!(writer.write(&early).is_err());
+    }
+}
@@ -0,0 +1,267 @@
+//! A run bundle (`.tcplabrun`): one zip file holding everything about a run, so it can
+//! be handed in, replayed or compared as a single artifact instead of loose files that
+//! get lost or mixed up between runs.
+//!
+//! Entries:
+//!
+//! ```text
+//! metadata.json        BundleMetadata
+//! report.json          the JSON trace (`--trace-out`)
+//! trace.bin            the binary trace (`--binary-trace-out`)
+//! scenario.toml        the scenario file as run, if the run had one
+//! logs/sender.log      the sender's `SystemContext::log` messages, one per line
+//! logs/receiver.log    the same for the receiver
+//! ```
+
+use anyhow::{Context, Result, bail, ensure};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Cursor, Read, Seek, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zip::result::ZipError;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::engine::NodeId;
+use crate::trace::SimulationReport;
+use crate::trace::binary::{self, TraceReader};
+
+/// Layout version in `metadata.json`; bumped when entries change incompatibly.
+const FORMAT: u32 = 1;
+
+const METADATA: &str = "metadata.json";
+const REPORT: &str = "report.json";
+const TRACE: &str = "trace.bin";
+const SCENARIO: &str = "scenario.toml";
+
+/// What a bundle was made from, to tell runs apart without opening their traces.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleMetadata {
+    pub format: u32,
+    /// The version of the tool that made the bundle.
+    pub version: String,
+    /// Seconds since the Unix epoch.
+    pub created: u64,
+    /// The scenario's name, if the run had one.
+    pub scenario: Option<String>,
+    pub seed: u64,
+    /// Where the sender came from, e.g. `java:Sender` or `builtin:rdt2`.
+    pub sender: Option<String>,
+    pub receiver: Option<String>,
+    pub duration_ms: u64,
+}
+
+impl BundleMetadata {
+    /// Metadata for `report`, stamped with the current time.
+    pub fn new(report: &SimulationReport, scenario: Option<String>) -> Self {
+        Self {
+            format: FORMAT,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            created: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs()),
+            scenario,
+            seed: report.config.seed,
+            sender: None,
+            receiver: None,
+            duration_ms: report.duration_ms,
+        }
+    }
+}
+
+/// Write a bundle of `report` to `path`, with the scenario file's text if there is one.
+pub fn write(
+    path: &Path,
+    metadata: &BundleMetadata,
+    report: &SimulationReport,
+    scenario: Option<&str>,
+) -> Result<()> {
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create bundle {}", path.display()))?;
+    encode(BufWriter::new(file), metadata, report, scenario)?
+        .flush()
+        .with_context(|| format!("Failed to write bundle {}", path.display()))
+}
+
+/// Write a bundle to `out` as `write` does, returning `out`.
+pub fn encode<W: Write + Seek>(
+    out: W,
+    metadata: &BundleMetadata,
+    report: &SimulationReport,
+    scenario: Option<&str>,
+) -> Result<W> {
+    let mut zip = ZipWriter::new(out);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut entry = |name: &str, data: &[u8]| -> Result<()> {
+        zip.start_file(name, options)?;
+        zip.write_all(data)?;
+        Ok(())
+    };
+    entry(METADATA, &serde_json::to_vec_pretty(metadata)?)?;
+    entry(REPORT, &serde_json::to_vec_pretty(report)?)?;
+    entry(TRACE, &binary::encode_report(Vec::new(), report)?)?;
+    if let Some(scenario) = scenario {
+        entry(SCENARIO, scenario.as_bytes())?;
+    }
+    for node in [NodeId::Sender, NodeId::Receiver] {
+        let mut log = String::new();
+        for entry in report.logs.iter().filter(|entry| entry.node == node) {
+            let _ = writeln!(log, "{:>8}  {}", entry.time, entry.message);
+        }
+        entry(&log_name(node), log.as_bytes())?;
+    }
+    Ok(zip.finish()?)
+}
+
+/// `path` starts like a zip file, so it may be a bundle rather than a bare trace.
+pub fn is_bundle(path: &Path) -> bool {
+    let mut magic = [0; 4];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok_and(|()| magic == *b"PK\x03\x04")
+}
+
+/// An open run bundle.
+pub struct Bundle<R: Read + Seek> {
+    archive: ZipArchive<R>,
+    metadata: BundleMetadata,
+}
+
+impl Bundle<BufReader<File>> {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open bundle {}", path.display()))?;
+        Self::new(BufReader::new(file)).with_context(|| format!("In {}", path.display()))
+    }
+}
+
+impl<R: Read + Seek> Bundle<R> {
+    /// Check that `input` is a bundle this version can read.
+    pub fn new(input: R) -> Result<Self> {
+        let mut archive = ZipArchive::new(input).context("Not a run bundle")?;
+        let Some(data) = read_entry(&mut archive, METADATA)? else {
+            bail!("Not a run bundle: no {METADATA}");
+        };
+        let metadata: BundleMetadata =
+            serde_json::from_slice(&data).with_context(|| format!("Invalid {METADATA}"))?;
+        ensure!(
+            metadata.format == FORMAT,
+            "Unsupported run bundle format {}",
+            metadata.format
+        );
+        Ok(Self { archive, metadata })
+    }
+
+    pub fn metadata(&self) -> &BundleMetadata {
+        &self.metadata
+    }
+
+    /// The binary trace, for reading from any point in time.
+    pub fn trace(&mut self) -> Result<TraceReader<Cursor<Vec<u8>>>> {
+        TraceReader::new(Cursor::new(self.required(TRACE)?))
+    }
+
+    /// The JSON trace, as `--trace-out` writes it.
+    pub fn report(&mut self) -> Result<serde_json::Value> {
+        serde_json::from_slice(&self.required(REPORT)?).with_context(|| format!("Invalid {REPORT}"))
+    }
+
+    /// The scenario file as run, or `None` if the run had no scenario.
+    pub fn scenario(&mut self) -> Result<Option<String>> {
+        read_entry(&mut self.archive, SCENARIO)?
+            .map(|data| String::from_utf8(data).context("Scenario is not UTF-8"))
+            .transpose()
+    }
+
+    /// `node`'s log messages, one per line.
+    pub fn log(&mut self, node: NodeId) -> Result<String> {
+        String::from_utf8(self.required(&log_name(node))?).context("Log is not UTF-8")
+    }
+
+    fn required(&mut self, name: &str) -> Result<Vec<u8>> {
+        read_entry(&mut self.archive, name)?.with_context(|| format!("Run bundle has no {name}"))
+    }
+}
+
+fn log_name(node: NodeId) -> String {
+    match node {
+        NodeId::Sender => "logs/sender.log".to_string(),
+        NodeId::Receiver => "logs/receiver.log".to_string(),
+    }
+}
+
+/// The contents of entry `name`, or `None` if there is no such entry.
+fn read_entry<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str) -> Result<Option<Vec<u8>>> {
+    let mut file = match archive.by_name(name) {
+        Ok(file) => file,
+        Err(ZipError::FileNotFound) => return Ok(None),
+        Err(err) => return Err(err).with_context(|| format!("Failed to read {name}")),
+    };
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)
+        .with_context(|| format!("Failed to read {name}"))?;
+    Ok(Some(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Simulator;
+    use tcp_lab_abstract::{Packet, SimConfig, SystemContext, TransportProtocol};
+
+    /// Logs and sends each message; the receiver logs and delivers each packet.
+    struct Chatty;
+
+    impl TransportProtocol for Chatty {
+        fn on_packet(&mut self, ctx: &mut dyn SystemContext, packet: Packet) {
+            ctx.log(&format!("got {} bytes", packet.payload.len()));
+            ctx.deliver_data(&packet.payload);
+        }
+
+        fn on_timer(&mut self, _ctx: &mut dyn SystemContext, _timer_id: u32) {}
+
+        fn on_app_data(&mut self, ctx: &mut dyn SystemContext, data: &[u8]) {
+            ctx.log("sending");
+            ctx.send_packet(Packet::new_simple(0, 0, 0, data.to_vec()));
+        }
+    }
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let mut simulator =
+            Simulator::new(SimConfig::default(), Box::new(Chatty), Box::new(Chatty));
+        simulator.schedule_app_send(0, b"hello".to_vec());
+        simulator.run_until_complete();
+        let report = simulator.export_report();
+        let mut metadata = BundleMetadata::new(&report, Some("hello".into()));
+        metadata.sender = Some("builtin:chatty".into());
+
+        let scenario = "name = \"hello\"\n";
+        let bytes = encode(Cursor::new(Vec::new()), &metadata, &report, Some(scenario)).unwrap();
+        let mut bundle = Bundle::new(bytes).unwrap();
+        assert_eq!(bundle.metadata(), &metadata);
+        assert_eq!(bundle.scenario().unwrap().as_deref(), Some(scenario));
+        assert_eq!(
+            bundle
+                .log(NodeId::Sender)
+                .unwrap()
+                .matches("sending")
+                .count(),
+            1
+        );
+        assert!(
+            bundle
+                .log(NodeId::Receiver)
+                .unwrap()
+                .contains("got 5 bytes")
+        );
+        let records = bundle.trace().unwrap().count();
+        assert_eq!(records, report.link_events.len() + report.packets.len());
+        assert_eq!(bundle.report().unwrap()["duration_ms"], report.duration_ms);
+
+        let bytes = encode(Cursor::new(Vec::new()), &metadata, &report, None).unwrap();
+        assert_eq!(Bundle::new(bytes).unwrap().scenario().unwrap(), None);
+    }
+}
@@ -0,0 +1,264 @@
+//! The causal graph of a run as Graphviz DOT, built from the `cause` and `arrival` links in
+//! the report: which arrival or timer expiry each packet was sent from, and where each
+//! packet arrived. Meant for untangling e.g. a retransmission storm:
+//!
+//! ```text
+//! tcp-lab-sim-cli --dot-out run.dot --dot-from 2000 --dot-until 2500 ...
+//! dot -Tsvg run.dot -o run.svg
+//! ```
+//!
+//! Sends are boxes (bold for retransmissions, red when the channel lost them), arrivals
+//! ellipses and timer expiries diamonds; a dashed edge starts a timer. Events that are
+//! neither an arrival nor a timer, such as the application handing over data, show as
+//! `event N`.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write as _;
+
+use crate::engine::{NodeId, PacketEvent, PacketEventKind};
+use crate::trace::SimulationReport;
+
+/// Render `report`'s causal graph, keeping only what happened between `from` and `until`
+/// (ms, both inclusive). Edges to causes outside the window are left out.
+pub fn export_dot(report: &SimulationReport, from: Option<u64>, until: Option<u64>) -> String {
+    let in_window =
+        |time: u64| from.is_none_or(|from| time >= from) && until.is_none_or(|until| time <= until);
+
+    // Every arrival and timer expiry of the run by event ID, with its time and attributes.
+    let mut events: BTreeMap<u64, (u64, String)> = BTreeMap::new();
+    for packet in &report.packets {
+        let verb = match packet.kind {
+            PacketEventKind::Arrived => "arrived at",
+            PacketEventKind::Injected => "injected at",
+            _ => continue,
+        };
+        if let Some(id) = packet.cause {
+            let label = format!(
+                "{} ms\\n{verb} {:?}\\n{}",
+                packet.time,
+                packet.node,
+                header(packet)
+            );
+            events.insert(
+                id,
+                (packet.time, format!("shape=ellipse label=\"{label}\"")),
+            );
+        }
+    }
+    for timer in &report.timer_log {
+        let fate = match (timer.fired, timer.ended) {
+            (true, _) => "fired",
+            (false, Some(_)) => "cancelled",
+            (false, None) => "still armed",
+        };
+        let label = format!(
+            "{} ms\\n{:?} timer {} {fate}",
+            timer.expires, timer.node, timer.timer_id
+        );
+        let mut attrs = format!("shape=diamond label=\"{label}\"");
+        if !timer.fired {
+            attrs.push_str(" style=dashed");
+        }
+        // A timer belongs in the window while it was running there.
+        let time = if in_window(timer.started) {
+            timer.started
+        } else {
+            timer.expires
+        };
+        events.insert(timer.expiry, (time, attrs));
+    }
+
+    let mut graph = Graph::default();
+    for (id, (time, attrs)) in &events {
+        if in_window(*time) {
+            graph.node(format!("e{id}"), attrs);
+        }
+    }
+    let cause = |graph: &mut Graph, cause: Option<u64>| -> Option<String> {
+        match cause {
+            None => Some(graph.node("init".to_string(), "shape=plaintext")),
+            Some(id) if events.contains_key(&id) => {
+                let name = format!("e{id}");
+                graph.declared.contains(&name).then_some(name)
+            }
+            Some(id) => Some(graph.node(
+                format!("e{id}"),
+                &format!("shape=plaintext label=\"event {id}\""),
+            )),
+        }
+    };
+
+    for timer in &report.timer_log {
+        let expiry = format!("e{}", timer.expiry);
+        if in_window(timer.started)
+            && graph.declared.contains(&expiry)
+            && let Some(cause) = cause(&mut graph, timer.cause)
+        {
+            graph.edge(&cause, &expiry, " [style=dashed label=\"start_timer\"]");
+        }
+    }
+
+    let mut sent_seqs = HashSet::new();
+    for (index, packet) in report.packets.iter().enumerate() {
+        if packet.kind != PacketEventKind::Sent {
+            continue;
+        }
+        let retransmission = packet.len > 0 && !sent_seqs.insert((packet.node, packet.seq));
+        if !in_window(packet.time) {
+            continue;
+        }
+        let mut label = format!(
+            "{} ms\\n{} {}",
+            packet.time,
+            sent_by(packet.node),
+            header(packet)
+        );
+        let mut attrs = String::from("shape=box");
+        if retransmission {
+            label.push_str("\\nretransmission");
+            attrs.push_str(" style=bold");
+        }
+        if packet.arrival.is_none() {
+            label.push_str("\\nlost");
+            attrs.push_str(" color=red");
+        }
+        let name = graph.node(format!("s{index}"), &format!("{attrs} label=\"{label}\""));
+        if let Some(cause) = cause(&mut graph, packet.cause) {
+            graph.edge(&cause, &name, "");
+        }
+        if let Some(arrival) = packet.arrival.map(|id| format!("e{id}"))
+            && graph.declared.contains(&arrival)
+        {
+            graph.edge(&name, &arrival, "");
+        }
+    }
+    graph.finish()
+}
+
+fn sent_by(node: NodeId) -> &'static str {
+    match node {
+        NodeId::Sender => "Sender sent",
+        NodeId::Receiver => "Receiver sent",
+    }
+}
+
+fn header(packet: &PacketEvent) -> String {
+    format!("seq={} ack={} len={}", packet.seq, packet.ack, packet.len)
+}
+
+#[derive(Default)]
+struct Graph {
+    nodes: String,
+    edges: String,
+    declared: HashSet<String>,
+}
+
+impl Graph {
+    /// Declare node `name` unless it already is; returns the name.
+    fn node(&mut self, name: String, attrs: &str) -> String {
+        if self.declared.insert(name.clone()) {
+            let _ = writeln!(self.nodes, "  {name} [{attrs}];");
+        }
+        name
+    }
+
+    fn edge(&mut self, from: &str, to: &str, attrs: &str) {
+        let _ = writeln!(self.edges, "  {from} -> {to}{attrs};");
+    }
+
+    fn finish(self) -> String {
+        format!(
+            "digraph causality {{\n  node [fontname=\"monospace\" fontsize=10];\n{}{}}}\n",
+            self.nodes, self.edges
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Simulator;
+    use tcp_lab_abstract::{Packet, SimConfig, SystemContext, TransportProtocol, flags};
+
+    /// Sends seq 0 from `init` and again when timer 1 expires.
+    struct Retransmitter;
+
+    impl TransportProtocol for Retransmitter {
+        fn init(&mut self, ctx: &mut dyn SystemContext) {
+            ctx.send_packet(Packet::new_simple(0, 0, 0, vec![b'x']));
+            ctx.start_timer(100, 1);
+        }
+
+        fn on_packet(&mut self, _ctx: &mut dyn SystemContext, _packet: Packet) {}
+
+        fn on_timer(&mut self, ctx: &mut dyn SystemContext, _timer_id: u32) {
+            ctx.send_packet(Packet::new_simple(0, 0, 0, vec![b'x']));
+        }
+
+        fn on_app_data(&mut self, _ctx: &mut dyn SystemContext, _data: &[u8]) {}
+    }
+
+    struct Acker;
+
+    impl TransportProtocol for Acker {
+        fn on_packet(&mut self, ctx: &mut dyn SystemContext, packet: Packet) {
+            let seq = packet.header.seq_num;
+            ctx.send_packet(Packet::new_simple(0, seq, flags::ACK, Vec::new()));
+        }
+
+        fn on_timer(&mut self, _ctx: &mut dyn SystemContext, _timer_id: u32) {}
+
+        fn on_app_data(&mut self, _ctx: &mut dyn SystemContext, _data: &[u8]) {}
+    }
+
+    #[test]
+    fn test_export_dot() {
+        let config = SimConfig {
+            min_latency: 10,
+            max_latency: 10,
+            ..Default::default()
+        };
+        let mut simulator = Simulator::new(config, Box::new(Retransmitter), Box::new(Acker));
+        simulator.run_until_complete();
+        let report = simulator.export_report();
+        let expiry = report.timer_log[0].expiry;
+        let sends: Vec<usize> = (0..report.packets.len())
+            .filter(|&i| {
+                let packet = &report.packets[i];
+                packet.kind == PacketEventKind::Sent && packet.node == NodeId::Sender
+            })
+            .collect();
+        let arrival = report.packets[sends[0]].arrival.unwrap();
+
+        let dot = export_dot(&report, None, None);
+        assert!(dot.starts_with("digraph causality {"), "{dot}");
+        assert!(dot.contains(&format!("init -> s{};", sends[0])), "{dot}");
+        assert!(
+            dot.contains(&format!("init -> e{expiry} [style=dashed")),
+            "{dot}"
+        );
+        assert!(
+            dot.contains(&format!("s{} -> e{arrival};", sends[0])),
+            "{dot}"
+        );
+        // The retransmission was sent from the timer's expiry.
+        assert!(
+            dot.contains(&format!("e{expiry} -> s{};", sends[1])),
+            "{dot}"
+        );
+        assert!(dot.contains("Sender timer 1 fired"), "{dot}");
+        assert!(
+            dot.contains(&format!("s{} [shape=box style=bold", sends[1])),
+            "{dot}"
+        );
+
+        // From 50 ms on, only the retransmission and what followed it are left.
+        let late = export_dot(&report, Some(50), None);
+        assert!(!late.contains(&format!("s{} ", sends[0])), "{late}");
+        assert!(!late.contains("init"), "{late}");
+        assert!(
+            late.contains(&format!("e{expiry} -> s{};", sends[1])),
+            "{late}"
+        );
+    }
+}
@@ -4,7 +4,16 @@ use std::{
     time::{Duration, Instant},
 };
 
-use crate::engine::Simulator;
+use crate::capture::Capture;
+use crate::engine::{
+    IN_FLIGHT_METRIC, LinkDirection, LinkEventKind, LinkEventSummary, NodeId, PacketEvent,
+    QUEUE_DROPS_METRIC, QUEUE_LENGTH_METRIC, Simulator,
+};
+use crate::i18n::Msg;
+use crate::live::LiveInput;
+use crate::report::diagram::first_divergence;
+use crate::report::series::downsample;
+use crate::report::time_sequence::TimeSequence;
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
     execute,
@@ -15,6 +24,15 @@ use ratatui::{
     prelude::*,
     widgets::{Axis, Block, Borders, Chart, Dataset, GraphType, List, ListItem, Paragraph},
 };
+use tcp_lab_abstract::MetricBand;
+
+mod style;
+
+pub use style::RenderMode;
+use style::TextCanvas;
+
+/// Builds a run anew, e.g. with the student's implementation reloaded for the `r` key.
+pub type Reload = Box<dyn FnMut() -> anyhow::Result<Simulator>>;
 
 /// A tracing subscriber that writes to a shared buffer for TUI display
 #[derive(Clone)]
@@ -64,6 +82,27 @@ pub struct TuiApp {
     scenario_name: Option<String>,
     /// Vertical scroll offset for link events list
     link_scroll: usize,
+    mode: RenderMode,
+    /// Piped app data, sent as it arrives.
+    live: Option<LiveInput>,
+    /// Whether `i` opens a prompt for typing app data.
+    typing: bool,
+    /// The message being typed, while the prompt is open.
+    draft: Option<String>,
+    /// Whether the time-sequence graph replaces the in-flight chart.
+    time_sequence: bool,
+    /// Restarts the run in place when `r` is pressed.
+    reload: Option<Reload>,
+    /// Why the last reload failed, shown until the next one.
+    reload_error: Option<String>,
+    /// A second run stepped in lockstep with this one and drawn next to it.
+    reference: Option<Simulator>,
+    /// Rebuilds `reference` when `r` restarts the run.
+    build_reference: Option<Reload>,
+    /// A real transfer drawn next to the run, up to the run's current time.
+    capture: Option<Capture>,
+    /// The scenario's `metric_bands`, drawn on the charts of their metrics.
+    metric_bands: Vec<MetricBand>,
 }
 
 impl TuiApp {
@@ -73,9 +112,124 @@ impl TuiApp {
             paused: true, // Start paused
             scenario_name,
             link_scroll: 0,
+            mode: RenderMode::default(),
+            live: None,
+            typing: false,
+            draft: None,
+            time_sequence: false,
+            reload: None,
+            reload_error: None,
+            reference: None,
+            build_reference: None,
+            capture: None,
+            metric_bands: Vec::new(),
+        }
+    }
+
+    pub fn set_render_mode(&mut self, mode: RenderMode) {
+        self.mode = mode;
+    }
+
+    /// Send each message from `input` at the simulated time it arrives.
+    pub fn set_live_input(&mut self, input: LiveInput) {
+        self.live = Some(input);
+    }
+
+    /// Let `i` open a prompt whose lines become app sends, for when stdin is
+    /// the terminal the TUI itself reads keys from.
+    pub fn set_typed_input(&mut self, enabled: bool) {
+        self.typing = enabled;
+    }
+
+    /// Let `r` replace the run with a fresh one from `reload`, e.g. after the student
+    /// rebuilt their protocol. The layout and render settings carry over.
+    pub fn set_reload(&mut self, reload: Reload) {
+        self.reload = Some(reload);
+    }
+
+    /// Run `build`'s simulation next to this one, e.g. the reference implementation on the
+    /// same scenario and seed. Both are stepped in lockstep and their space-time diagrams
+    /// are drawn side by side, marked where they first differ.
+    pub fn set_reference(&mut self, mut build: Reload) -> anyhow::Result<()> {
+        self.reference = Some(build()?);
+        self.build_reference = Some(build);
+        Ok(())
+    }
+
+    /// Draw `capture` next to this run in the space-time diagram and the time-sequence
+    /// graph, revealed as simulated time passes so both show the same moment.
+    pub fn set_capture(&mut self, capture: Capture) {
+        self.capture = Some(capture);
+    }
+
+    /// Draw each band's bounds as guide lines on the chart of its metric (`cwnd`,
+    /// `ssthresh`, `in_flight` or `queue_length`), so students see while the run goes
+    /// what the grader will warn about.
+    pub fn set_metric_bands(&mut self, bands: Vec<MetricBand>) {
+        self.metric_bands = bands;
+    }
+
+    /// The part of `capture` up to the run's current time, or all of it once the run is
+    /// over.
+    fn capture_so_far<'a>(
+        &self,
+        capture: &'a Capture,
+    ) -> (&'a [LinkEventSummary], &'a [PacketEvent]) {
+        if self.simulator.peek_next_event_time().is_none() {
+            return (&capture.link_events, &capture.packets);
+        }
+        let now = self.simulator.current_time();
+        let events = capture.link_events.partition_point(|e| e.time <= now);
+        let packets = capture.packets.partition_point(|p| p.time <= now);
+        (&capture.link_events[..events], &capture.packets[..packets])
+    }
+
+    /// Swap in a fresh run from `reload`, paused at its start. On failure the current run
+    /// stays and the error is shown in the control bar.
+    fn reload(&mut self) {
+        let Some(reload) = &mut self.reload else {
+            return;
+        };
+        let rebuilt = reload().and_then(|simulator| {
+            let reference = self.build_reference.as_mut().map(|build| build());
+            Ok((simulator, reference.transpose()?))
+        });
+        match rebuilt {
+            Ok((mut simulator, reference)) => {
+                simulator.init();
+                self.simulator = simulator;
+                if let Some(mut reference) = reference {
+                    reference.init();
+                    self.reference = Some(reference);
+                }
+                self.paused = true;
+                self.link_scroll = 0;
+                self.reload_error = None;
+            }
+            Err(err) => self.reload_error = Some(format!("{err:#}")),
         }
     }
 
+    /// Advance by one event, and the reference in lockstep: whichever run has the earlier
+    /// next event moves, both when they are due at the same time. False once both are done.
+    fn step(&mut self) -> bool {
+        let Some(reference) = &mut self.reference else {
+            return self.simulator.step();
+        };
+        let ours = self.simulator.peek_next_event_time();
+        let theirs = reference.peek_next_event_time();
+        let Some(next) = ours.into_iter().chain(theirs).min() else {
+            return false;
+        };
+        if ours == Some(next) {
+            self.simulator.step();
+        }
+        if theirs == Some(next) {
+            reference.step();
+        }
+        true
+    }
+
     pub fn run(&mut self) -> anyhow::Result<()> {
         enable_raw_mode()?;
         let mut stdout = io::stdout();
@@ -88,6 +242,9 @@ impl TuiApp {
 
         // Init sim
         self.simulator.init();
+        if let Some(reference) = &mut self.reference {
+            reference.init();
+        }
 
         loop {
             terminal.draw(|f| self.ui(f))?;
@@ -99,32 +256,62 @@ impl TuiApp {
             if crossterm::event::poll(timeout)?
                 && let Event::Key(key) = event::read()?
             {
+                if let Some(draft) = &mut self.draft {
+                    match key.code {
+                        KeyCode::Char(c) => draft.push(c),
+                        KeyCode::Backspace => {
+                            draft.pop();
+                        }
+                        KeyCode::Enter => {
+                            let data = std::mem::take(draft).into_bytes();
+                            self.draft = None;
+                            if !data.is_empty() {
+                                let now = self.simulator.current_time();
+                                self.simulator.schedule_app_send(now, data);
+                                self.paused = false;
+                            }
+                        }
+                        KeyCode::Esc => self.draft = None,
+                        _ => {}
+                    }
+                    continue;
+                }
                 match key.code {
                     KeyCode::Char('q') => break,
                     KeyCode::Char(' ') => self.paused = !self.paused,
+                    KeyCode::Char('i') if self.typing => self.draft = Some(String::new()),
+                    KeyCode::Char('t') => self.time_sequence = !self.time_sequence,
+                    KeyCode::Char('r') => self.reload(),
                     KeyCode::Char('s') => {
                         // Step once
-                        self.simulator.step();
+                        self.step();
                     }
                     // Vertical scroll in link events list
                     KeyCode::Up => {
                         self.link_scroll = self.link_scroll.saturating_add(1);
                     }
                     KeyCode::Down => {
-                        if self.link_scroll > 0 {
-                            self.link_scroll -= 1;
-                        }
+                        self.link_scroll = self.link_scroll.saturating_sub(1);
                     }
                     _ => {}
                 }
             }
 
+            if let Some(live) = &mut self.live {
+                let now = self.simulator.current_time();
+                live.feed(&mut self.simulator, now);
+            }
+
             if last_tick.elapsed() >= tick_rate {
                 if !self.paused {
                     // Advance simulation
                     // We can do multiple steps per frame if needed
-                    if self.simulator.step() {
-                        // Continue
+                    let notes = self.simulator.annotations.len();
+                    if self.step() {
+                        // Stop on each annotation so it can be read before the run moves on
+                        if self.simulator.annotations.len() > notes {
+                            self.paused = true;
+                        }
                     } else {
                         // Simulation finished
                         self.paused = true;
@@ -150,48 +337,146 @@ impl TuiApp {
         self.simulator
     }
 
+    /// A bordered panel, with ASCII borders in ASCII mode.
+    fn block<'a>(&self, title: impl Into<Line<'a>>) -> Block<'a> {
+        Block::default()
+            .borders(Borders::ALL)
+            .border_set(self.mode.border_set())
+            .title(title)
+    }
+
     fn ui(&self, f: &mut Frame) {
         let rows = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
-                Constraint::Length(3),  // Control bar
+                Constraint::Length(3), // Control bar
+                Constraint::Length(if self.simulator.latest_annotation().is_some() {
+                    3
+                } else {
+                    0
+                }), // Scenario annotation
                 Constraint::Length(10), // Link space-time
-                Constraint::Min(0),     // Split dashboard + window
+                Constraint::Min(0),    // Split dashboard + window
                 Constraint::Length(10), // Link events
             ])
             .split(f.area());
 
         self.render_control(f, rows[0]);
-        self.render_link_space_time(f, rows[1]);
+        self.render_annotation(f, rows[1]);
+        match (&self.reference, &self.capture) {
+            (Some(reference), _) => self.render_side_by_side(f, rows[2], reference),
+            (None, Some(capture)) => self.render_beside_capture(f, rows[2], capture),
+            (None, None) => self.render_link_space_time(
+                f,
+                rows[2],
+                &self.simulator.link_events,
+                Msg::SpaceTimeTitle.text(),
+                None,
+                None,
+            ),
+        }
 
         let mid_chunks = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
-            .split(rows[2]);
-        self.render_dashboard_body(f, mid_chunks[0]);
-        self.render_window_history(f, mid_chunks[1]);
+            .split(rows[3]);
+        let left_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(4)])
+            .split(mid_chunks[0]);
+        self.render_dashboard_body(f, left_chunks[0]);
+        self.render_states(f, left_chunks[1]);
+
+        let chart_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(mid_chunks[1]);
+        self.render_window_history(f, chart_chunks[0]);
+        if self.time_sequence {
+            let ours = &self.simulator.packet_log;
+            let now = self.simulator.current_time();
+            match &self.capture {
+                Some(capture) => {
+                    let halves =
+                        Layout::horizontal([Constraint::Percentage(50); 2]).split(chart_chunks[1]);
+                    let (_, theirs) = self.capture_so_far(capture);
+                    let end = theirs.last().map_or(now, |packet| packet.time.max(now));
+                    self.render_time_sequence(f, halves[0], ours, now, Msg::SpaceTimeYours.text());
+                    self.render_time_sequence(f, halves[1], theirs, end, Msg::Capture.text());
+                }
+                None => self.render_time_sequence(
+                    f,
+                    chart_chunks[1],
+                    ours,
+                    now,
+                    Msg::TimeSequenceTitle.text(),
+                ),
+            }
+        } else if self.simulator.config().bottleneck.is_some() {
+            let halves = Layout::horizontal([Constraint::Percentage(50); 2]).split(chart_chunks[1]);
+            self.render_in_flight(f, halves[0]);
+            self.render_queue(f, halves[1]);
+        } else {
+            self.render_in_flight(f, chart_chunks[1]);
+        }
 
-        self.render_link_events(f, rows[3]);
+        self.render_link_events(f, rows[4]);
     }
 
     fn render_control(&self, f: &mut Frame, area: Rect) {
-        let scenario = self.scenario_name.as_deref().unwrap_or("Ad-hoc Simulation");
-        let status_text = format!(
-            "Scenario: {} | Time: {} ms | Status: {} | Events Pending: {} | (q)uit (space)pause/resume (s)tep",
-            scenario,
-            self.simulator.current_time(),
-            if self.paused { "PAUSED" } else { "RUNNING" },
-            self.simulator.remaining_events()
-        );
-        let status_block = Paragraph::new(status_text)
-            .block(Block::default().borders(Borders::ALL).title("Control"));
+        let scenario = self
+            .scenario_name
+            .as_deref()
+            .unwrap_or(Msg::AdHocSimulation.text());
+        let status = if self.paused {
+            Msg::Paused
+        } else {
+            Msg::Running
+        };
+        let mut status_text = Msg::ControlStatus.fill(&[
+            &scenario,
+            &self.simulator.current_time(),
+            &status.text(),
+            &self.simulator.remaining_events(),
+        ]);
+        if let Some(draft) = &self.draft {
+            status_text = Msg::ControlDraft.fill(&[draft]);
+        } else if let Some(err) = &self.reload_error {
+            status_text = Msg::ReloadFailed.fill(&[err]);
+        } else {
+            if self.typing {
+                status_text.push_str(Msg::ControlTypeHint.text());
+            }
+            if self.reload.is_some() {
+                status_text.push_str(Msg::ControlReloadHint.text());
+            }
+        }
+        let status_block = Paragraph::new(status_text).block(self.block(Msg::Control.text()));
         f.render_widget(status_block, area);
     }
 
+    /// The latest scenario annotation the run reached, as a banner under the control bar.
+    fn render_annotation(&self, f: &mut Frame, area: Rect) {
+        let Some(note) = self.simulator.latest_annotation() else {
+            return;
+        };
+        let color = self.mode.palette().note;
+        let banner = Paragraph::new(Line::from(Span::styled(
+            note.text.as_str(),
+            Style::default().fg(color).add_modifier(Modifier::BOLD),
+        )))
+        .block(
+            self.block(Msg::NoteAt.fill(&[&note.time]))
+                .border_style(Style::default().fg(color)),
+        );
+        f.render_widget(banner, area);
+    }
+
     fn render_dashboard_body(&self, f: &mut Frame, area: Rect) {
         // Stats
         let delivered = self.simulator.delivered_data.len();
         let sent_packets = self.simulator.sender_packet_count;
+        let traffic = &self.simulator.traffic;
         let (win_current, win_max) = if self.simulator.sender_window_sizes.is_empty() {
             (0u16, 0u16)
         } else {
@@ -206,34 +491,131 @@ impl TuiApp {
         };
 
         let cfg = self.simulator.config();
-        let stats_text = vec![
-            Line::from("Simulation Stats:"),
-            Line::from(format!("  Delivered messages: {}", delivered)),
-            Line::from(format!("  Sender packets:     {}", sent_packets)),
-            Line::from(format!(
-                "  Sender window:      current={} max={}",
-                win_current, win_max
-            )),
-            Line::from(format!(
-                "  Channel: loss={:.2}, corrupt={:.2}, latency={}..{} ms",
-                cfg.loss_rate, cfg.corrupt_rate, cfg.min_latency, cfg.max_latency
-            )),
+        let mut stats_text = vec![
+            Line::from(Msg::SimulationStats.text()),
+            Line::from(Msg::StatDelivered.fill(&[&delivered])),
+            Line::from(Msg::StatSenderPackets.fill(&[&sent_packets])),
+            Line::from(Msg::StatWireBytes.fill(&[
+                &traffic.wire_bytes(),
+                &format!("{:.1}", traffic.efficiency() * 100.0),
+            ])),
+            Line::from(Msg::StatSenderWindow.fill(&[&win_current, &win_max])),
+            Line::from(Msg::StatTimers.fill(&[
+                &self.pending_timers(NodeId::Sender),
+                &self.pending_timers(NodeId::Receiver),
+            ])),
+            Line::from(Msg::StatChannel.fill(&[
+                &format!("{:.2}", cfg.loss_rate),
+                &format!("{:.2}", cfg.corrupt_rate),
+                &cfg.min_latency,
+                &cfg.max_latency,
+            ])),
             Line::from(""),
-            Line::from("Controls:"),
-            Line::from("  Space: Pause/Resume"),
-            Line::from("  s:     Step one event"),
-            Line::from("  q:     Quit"),
+            Line::from(Msg::ControlsHeading.text()),
+            Line::from(Msg::ControlSpace.text()),
+            Line::from(Msg::ControlStep.text()),
+            Line::from(Msg::ControlTimeSequence.text()),
+            Line::from(Msg::ControlQuit.text()),
         ];
+        if self.typing {
+            stats_text.push(Line::from(Msg::ControlType.text()));
+        }
+        if self.reload.is_some() {
+            stats_text.push(Line::from(Msg::ControlReload.text()));
+        }
 
         // Stats block
-        let stats_block = Paragraph::new(stats_text)
-            .block(Block::default().borders(Borders::ALL).title("Dashboard"));
+        let stats_block = Paragraph::new(stats_text).block(self.block(Msg::Dashboard.text()));
         f.render_widget(stats_block, area);
     }
 
+    /// The timers `node` has armed, as `id@expiry` soonest first, or `-` for none.
+    fn pending_timers(&self, node: NodeId) -> String {
+        let pending = self.simulator.pending_timers(node);
+        if pending.is_empty() {
+            return "-".to_string();
+        }
+        let timers: Vec<String> = pending
+            .iter()
+            .map(|timer| format!("{}@{}", timer.timer_id, timer.expires))
+            .collect();
+        timers.join(" ")
+    }
+
+    /// One line per node with the states it reported through `record_state`, newest last;
+    /// older states scroll off to the left.
+    fn render_states(&self, f: &mut Frame, area: Rect) {
+        let width = area.width.saturating_sub(2) as usize;
+        let palette = self.mode.palette();
+        let lines: Vec<Line> = [NodeId::Sender, NodeId::Receiver]
+            .into_iter()
+            .map(|node| {
+                let label = format!("{:<9}", format!("{:?}:", node));
+                let mut budget = width.saturating_sub(label.len());
+                let mut spans = Vec::new();
+                let transitions = self.simulator.state_log.iter().filter(|t| t.node == node);
+                for (i, t) in transitions.rev().enumerate() {
+                    let text = format!("{}@{} ", t.to, t.time);
+                    if text.len() + 2 > budget {
+                        spans.push(Span::styled(
+                            self.mode.ellipsis(),
+                            Style::default().fg(palette.muted),
+                        ));
+                        break;
+                    }
+                    budget -= text.len() + 2;
+                    let style = if i == 0 {
+                        Style::default()
+                            .fg(palette.current)
+                            .add_modifier(Modifier::BOLD)
+                    } else {
+                        Style::default()
+                    };
+                    if i > 0 {
+                        spans.push(Span::raw(self.mode.arrow()));
+                    }
+                    spans.push(Span::styled(text, style));
+                }
+                if spans.is_empty() {
+                    spans.push(Span::styled(
+                        "no states recorded",
+                        Style::default().fg(palette.muted),
+                    ));
+                }
+                spans.push(Span::raw(label));
+                spans.reverse();
+                Line::from(spans)
+            })
+            .collect();
+        let block = Paragraph::new(lines).block(self.block(Msg::States.text()));
+        f.render_widget(block, area);
+    }
+
+    /// Guide lines at the bounds of the `metric_bands` on `metrics`, each from its band's
+    /// `after_ms` to `x_max`, labelled e.g. `cwnd <= 64`.
+    fn band_lines(&self, metrics: &[&str], x_max: f64) -> Vec<(String, [(f64, f64); 2])> {
+        self.metric_bands
+            .iter()
+            .filter(|band| metrics.contains(&band.metric.as_str()))
+            .flat_map(|band| {
+                let from = (band.after_ms as f64).min(x_max);
+                let min = band
+                    .min
+                    .map(|min| (format!("{} >= {min}", band.metric), min));
+                let max = band
+                    .max
+                    .map(|max| (format!("{} <= {max}", band.metric), max));
+                min.into_iter()
+                    .chain(max)
+                    .map(move |(label, y)| (label, [(from, y), (x_max, y)]))
+            })
+            .collect()
+    }
+
     fn render_window_history(&self, f: &mut Frame, area: Rect) {
         // 构造一张叠加图：前景 cwnd，背景 ssthresh
-        // cwnd 优先来自 metrics("cwnd")，否则退化为 sender_window_sizes（按采样顺序）
+        // cwnd 优先来自 metrics("cwnd")（按时间），否则退化为 sender_window_sizes（按采样顺序）
+        // 长序列先按 chart_max_points 降采样
 
         let mut y_min = f64::MAX;
         let mut y_max = f64::MIN;
@@ -241,15 +623,13 @@ impl TuiApp {
         // 先收集所有序列，避免同时对 Vec 可变+不可变借用
         let mut cwnd_series_vec: Option<Vec<(f64, f64)>> = None;
         let mut ssthresh_series_vec: Option<Vec<(f64, f64)>> = None;
+        let mut by_index = false;
 
         // cwnd 系列
-        if let Some(cwnd_series) = self.simulator.metric_series("cwnd") {
+        if let Some(cwnd_series) = self.simulator.chart_series("cwnd") {
             if !cwnd_series.is_empty() {
-                let pts: Vec<(f64, f64)> = cwnd_series
-                    .iter()
-                    .enumerate()
-                    .map(|(i, (_, v))| (i as f64, *v))
-                    .collect();
+                let pts: Vec<(f64, f64)> =
+                    cwnd_series.iter().map(|(t, v)| (*t as f64, *v)).collect();
                 if !pts.is_empty() {
                     for (_, y) in &pts {
                         if *y < y_min {
@@ -264,13 +644,18 @@ impl TuiApp {
             }
         } else if !self.simulator.sender_window_sizes.is_empty() {
             // 没有 metric 时退化为按索引显示，不支持时间缩放
-            let pts: Vec<(f64, f64)> = self
+            let indexed: Vec<(u64, f64)> = self
                 .simulator
                 .sender_window_sizes
                 .iter()
                 .enumerate()
-                .map(|(i, w)| (i as f64, *w as f64))
+                .map(|(i, w)| (i as u64, *w as f64))
                 .collect();
+            let pts: Vec<(f64, f64)> =
+                downsample(&indexed, self.simulator.config().chart_max_points)
+                    .iter()
+                    .map(|(i, w)| (*i as f64, *w))
+                    .collect();
             for (_, y) in &pts {
                 if *y < y_min {
                     y_min = *y;
@@ -280,17 +665,14 @@ impl TuiApp {
                 }
             }
             cwnd_series_vec = Some(pts);
+            by_index = true;
         }
 
         // ssthresh 系列（只有 Reno/Tahoe 会报）
-        if let Some(series) = self.simulator.metric_series("ssthresh")
+        if let Some(series) = self.simulator.chart_series("ssthresh")
             && !series.is_empty()
         {
-            let pts: Vec<(f64, f64)> = series
-                .iter()
-                .enumerate()
-                .map(|(i, (_, v))| (i as f64, *v))
-                .collect();
+            let pts: Vec<(f64, f64)> = series.iter().map(|(t, v)| (*t as f64, *v)).collect();
             if !pts.is_empty() {
                 for (_, y) in &pts {
                     if *y < y_min {
@@ -304,6 +686,25 @@ impl TuiApp {
             }
         }
 
+        let x_max = cwnd_series_vec
+            .iter()
+            .chain(&ssthresh_series_vec)
+            .filter_map(|v| v.last().map(|(x, _)| *x))
+            .fold(1.0, f64::max);
+        // An index-based cwnd has no time axis to start a band at.
+        let bands = if by_index {
+            Vec::new()
+        } else {
+            self.band_lines(&["cwnd", "ssthresh"], x_max)
+        };
+        if y_min != f64::MAX {
+            for (_, line) in &bands {
+                y_min = y_min.min(line[0].1);
+                y_max = y_max.max(line[0].1);
+            }
+        }
+
+        let palette = self.mode.palette();
         let mut datasets: Vec<Dataset> = Vec::new();
 
         if let Some(ref pts) = cwnd_series_vec {
@@ -311,7 +712,7 @@ impl TuiApp {
                 Dataset::default()
                     .name("cwnd")
                     .marker(symbols::Marker::Dot)
-                    .style(Style::default().fg(Color::Cyan))
+                    .style(Style::default().fg(palette.cwnd))
                     .graph_type(GraphType::Line)
                     .data(pts),
             );
@@ -324,17 +725,20 @@ impl TuiApp {
                     .marker(symbols::Marker::Braille)
                     .style(
                         Style::default()
-                            .fg(Color::Yellow)
+                            .fg(palette.ssthresh)
                             .add_modifier(Modifier::DIM),
                     )
                     .graph_type(GraphType::Line)
                     .data(pts),
             );
         }
+        for (label, line) in &bands {
+            datasets.push(band_dataset(label, line, palette.band));
+        }
 
         if datasets.is_empty() || y_min == f64::MAX {
-            let block = Paragraph::new("No window metrics yet")
-                .block(Block::default().borders(Borders::ALL).title("Window"));
+            let block =
+                Paragraph::new(Msg::NoWindowMetrics.text()).block(self.block(Msg::Window.text()));
             f.render_widget(block, area);
             return;
         }
@@ -343,7 +747,26 @@ impl TuiApp {
             y_max += 1.0;
         }
 
-        let x_labels = vec![Span::raw("0"), Span::raw(""), Span::raw("n")];
+        if self.mode.ascii() {
+            let mut series = vec![
+                ("cwnd", '*', palette.cwnd, cwnd_series_vec.as_deref()),
+                (
+                    "ssthresh",
+                    '+',
+                    palette.ssthresh,
+                    ssthresh_series_vec.as_deref(),
+                ),
+            ];
+            series.extend(band_series(&bands, palette.band));
+            let block = self.block(Msg::WindowChartTitle.text());
+            render_text_chart(f, area, block, &series, [0.0, x_max], [y_min, y_max]);
+            return;
+        }
+        let x_labels = vec![
+            Span::raw("0"),
+            Span::raw(""),
+            Span::raw(format!("{:.0}", x_max)),
+        ];
         let y_labels = vec![
             Span::raw(format!("{:.0}", y_min)),
             Span::raw(""),
@@ -351,26 +774,16 @@ impl TuiApp {
         ];
 
         let chart = Chart::new(datasets)
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .title("Sender Window / ssthresh"),
-            )
+            .block(self.block(Msg::WindowChartTitle.text()))
             .x_axis(
                 Axis::default()
-                    .title("time")
-                    .bounds([
-                        0.0,
-                        cwnd_series_vec
-                            .as_ref()
-                            .map(|v| v.len() as f64)
-                            .unwrap_or(1.0),
-                    ])
+                    .title(Msg::AxisTime.text())
+                    .bounds([0.0, x_max])
                     .labels(x_labels),
             )
             .y_axis(
                 Axis::default()
-                    .title("size")
+                    .title(Msg::AxisSize.text())
                     .bounds([y_min, y_max])
                     .labels(y_labels),
             );
@@ -378,26 +791,380 @@ impl TuiApp {
         f.render_widget(chart, area);
     }
 
-    fn render_link_space_time(&self, f: &mut Frame, area: Rect) {
-        let events = &self.simulator.link_events;
-        if events.is_empty() {
-            let block = Paragraph::new("No link activity yet")
-                .block(Block::default().borders(Borders::ALL).title("Link"));
+    /// Engine-counted unacked segments over time, with the configured window for reference.
+    fn render_in_flight(&self, f: &mut Frame, area: Rect) {
+        let series = match self.simulator.chart_series(IN_FLIGHT_METRIC) {
+            Some(series) if !series.is_empty() => series,
+            _ => {
+                let block = Paragraph::new(Msg::NoDataSegments.text())
+                    .block(self.block(Msg::InFlight.text()));
+                f.render_widget(block, area);
+                return;
+            }
+        };
+
+        let now = self.simulator.current_time() as f64;
+        // Hold each count until the next change so the line reads as a step function.
+        let mut pts: Vec<(f64, f64)> = Vec::with_capacity(series.len() * 2 + 1);
+        for (t, n) in series.iter() {
+            if let Some(&(_, prev)) = pts.last() {
+                pts.push((*t as f64, prev));
+            }
+            pts.push((*t as f64, *n));
+        }
+        if let Some(&(_, last)) = pts.last() {
+            pts.push((now, last));
+        }
+
+        let window = self.simulator.config().window_size.map(f64::from);
+        let window_pts: Vec<(f64, f64)> =
+            window.map(|w| vec![(0.0, w), (now, w)]).unwrap_or_default();
+        let bands = self.band_lines(&[IN_FLIGHT_METRIC], now);
+        let y_max = pts
+            .iter()
+            .map(|(_, n)| *n)
+            .chain(window)
+            .chain(bands.iter().map(|(_, line)| line[0].1))
+            .fold(1.0, f64::max);
+
+        let palette = self.mode.palette();
+        if self.mode.ascii() {
+            let mut series = vec![
+                ("in flight", '#', palette.in_flight, Some(pts.as_slice())),
+                (
+                    "window_size",
+                    '=',
+                    palette.window,
+                    Some(window_pts.as_slice()),
+                ),
+            ];
+            series.extend(band_series(&bands, palette.band));
+            let block = self.block(Msg::InFlight.text());
+            render_text_chart(f, area, block, &series, [0.0, now.max(1.0)], [0.0, y_max]);
+            return;
+        }
+
+        let mut datasets = vec![
+            Dataset::default()
+                .name("in flight")
+                .marker(symbols::Marker::Braille)
+                .style(Style::default().fg(palette.in_flight))
+                .graph_type(GraphType::Line)
+                .data(&pts),
+        ];
+        if !window_pts.is_empty() {
+            datasets.push(
+                Dataset::default()
+                    .name("window_size")
+                    .marker(symbols::Marker::Braille)
+                    .style(
+                        Style::default()
+                            .fg(palette.window)
+                            .add_modifier(Modifier::DIM),
+                    )
+                    .graph_type(GraphType::Line)
+                    .data(&window_pts),
+            );
+        }
+        for (label, line) in &bands {
+            datasets.push(band_dataset(label, line, palette.band));
+        }
+
+        let chart = Chart::new(datasets)
+            .block(self.block(Msg::InFlight.text()))
+            .x_axis(
+                Axis::default()
+                    .title("ms")
+                    .bounds([0.0, now.max(1.0)])
+                    .labels(vec![Span::raw("0"), Span::raw(format!("{:.0}", now))]),
+            )
+            .y_axis(
+                Axis::default()
+                    .title(Msg::AxisSegments.text())
+                    .bounds([0.0, y_max])
+                    .labels(vec![Span::raw("0"), Span::raw(format!("{:.0}", y_max))]),
+            );
+        f.render_widget(chart, area);
+    }
+
+    /// Bytes waiting at the sender's bottleneck over time, with each drop marked where
+    /// it happened; the title counts the drops.
+    fn render_queue(&self, f: &mut Frame, area: Rect) {
+        let (Some(length), Some(drops)) = (
+            self.simulator.chart_series(QUEUE_LENGTH_METRIC),
+            self.simulator.metric_series(QUEUE_DROPS_METRIC),
+        ) else {
+            let block = Paragraph::new(Msg::QueueIdle.text())
+                .block(self.block(Msg::QueueChartTitle.fill(&[&0])));
             f.render_widget(block, area);
             return;
+        };
+        let title = Msg::QueueChartTitle.fill(&[&drops.last().map_or(0.0, |&(_, n)| n)]);
+
+        let now = self.simulator.current_time() as f64;
+        // Hold each length until the next sample so the line reads as a step function.
+        let mut pts: Vec<(f64, f64)> = Vec::with_capacity(length.len() * 2 + 1);
+        for (t, bytes) in length.iter() {
+            if let Some(&(_, prev)) = pts.last() {
+                pts.push((*t as f64, prev));
+            }
+            pts.push((*t as f64, *bytes));
+        }
+        if let Some(&(_, last)) = pts.last() {
+            pts.push((now, last));
+        }
+        // Drops are samples where the count went up; the queue was as long as then.
+        let lengths = self
+            .simulator
+            .metric_series(QUEUE_LENGTH_METRIC)
+            .unwrap_or_default();
+        let drop_pts: Vec<(f64, f64)> = drops
+            .iter()
+            .zip(lengths)
+            .scan(0.0, |count, (&(t, n), &(_, bytes))| {
+                let dropped = n > *count;
+                *count = n;
+                Some(dropped.then_some((t as f64, bytes)))
+            })
+            .flatten()
+            .collect();
+        let bands = self.band_lines(&[QUEUE_LENGTH_METRIC], now);
+        let y_max = pts
+            .iter()
+            .map(|(_, bytes)| *bytes)
+            .chain(bands.iter().map(|(_, line)| line[0].1))
+            .fold(1.0, f64::max);
+
+        let palette = self.mode.palette();
+        if self.mode.ascii() {
+            let mut series = vec![("queued", '#', palette.queue, Some(pts.as_slice()))];
+            series.extend(band_series(&bands, palette.band));
+            let block = self.block(title);
+            render_text_chart(f, area, block, &series, [0.0, now.max(1.0)], [0.0, y_max]);
+            return;
         }
 
-        // 仅展示最近若干个事件，形成简单的“局部时空图”
-        let max_events = (area.width as usize).saturating_sub(4).max(4);
-        let tail = events.iter().rev().take(max_events).collect::<Vec<_>>();
-        let window_events: Vec<_> = tail.into_iter().rev().collect();
+        let mut datasets = vec![
+            Dataset::default()
+                .name("queued")
+                .marker(symbols::Marker::Braille)
+                .style(Style::default().fg(palette.queue))
+                .graph_type(GraphType::Line)
+                .data(&pts),
+            Dataset::default()
+                .name("drop")
+                .marker(symbols::Marker::Dot)
+                .style(Style::default().fg(palette.drop))
+                .graph_type(GraphType::Scatter)
+                .data(&drop_pts),
+        ];
+        for (label, line) in &bands {
+            datasets.push(band_dataset(label, line, palette.band));
+        }
+        let chart = Chart::new(datasets)
+            .block(self.block(title))
+            .x_axis(
+                Axis::default()
+                    .title("ms")
+                    .bounds([0.0, now.max(1.0)])
+                    .labels(vec![Span::raw("0"), Span::raw(format!("{:.0}", now))]),
+            )
+            .y_axis(
+                Axis::default()
+                    .title(Msg::AxisBytes.text())
+                    .bounds([0.0, y_max])
+                    .labels(vec![Span::raw("0"), Span::raw(format!("{:.0}", y_max))]),
+            );
+        f.render_widget(chart, area);
+    }
 
-        let t_min = window_events.first().map(|e| e.time as f64).unwrap_or(0.0);
-        let mut t_max = window_events.last().map(|e| e.time as f64).unwrap_or(1.0);
-        if (t_max - t_min).abs() < f64::EPSILON {
-            t_max += 1.0;
+    /// tcptrace-style graph: a vertical bar per data segment spanning its sequence range,
+    /// coloured as a first send or a retransmission with lost ones marked at the top, and
+    /// the sender's highest ACK as a step line.
+    /// The time-sequence graph of `packets` up to `now` (ms).
+    fn render_time_sequence(
+        &self,
+        f: &mut Frame,
+        area: Rect,
+        packets: &[PacketEvent],
+        now: u64,
+        title: &str,
+    ) {
+        let graph = TimeSequence::from_packets(packets);
+        if graph.is_empty() {
+            let block = Paragraph::new(Msg::NoDataSegments.text()).block(self.block(title));
+            f.render_widget(block, area);
+            return;
         }
 
+        let now = (now as f64).max(1.0);
+        let y_max = f64::from(graph.max_seq()).max(1.0);
+        let palette = self.mode.palette();
+
+        let bars: Vec<CanvasLine> = graph
+            .segments
+            .iter()
+            .map(|s| CanvasLine {
+                x1: s.time as f64,
+                y1: f64::from(s.seq),
+                x2: s.time as f64,
+                y2: f64::from(s.end),
+                color: if s.retransmit {
+                    palette.retransmit
+                } else {
+                    palette.send
+                },
+            })
+            .collect();
+        let lost: Vec<(f64, f64)> = graph
+            .segments
+            .iter()
+            .filter(|s| s.lost)
+            .map(|s| (s.time as f64, f64::from(s.end)))
+            .collect();
+        // ACKs can arrive out of order; the line follows the highest one seen so far.
+        let mut acks: Vec<CanvasLine> = Vec::new();
+        let mut highest: Option<(f64, f64)> = None;
+        for &(time, ack) in &graph.acks {
+            let (t, a) = (time as f64, f64::from(ack));
+            if let Some((t0, a0)) = highest {
+                if a <= a0 {
+                    continue;
+                }
+                acks.push(CanvasLine::new(t0, a0, t, a0, palette.deliver));
+                acks.push(CanvasLine::new(t, a0, t, a, palette.deliver));
+            }
+            highest = Some((t, a));
+        }
+        if let Some((t0, a0)) = highest {
+            acks.push(CanvasLine::new(t0, a0, now, a0, palette.deliver));
+        }
+
+        let block = self.block(title);
+        if self.mode.ascii() {
+            let inner = block.inner(area);
+            f.render_widget(block, area);
+            let [plot, legend] =
+                Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(inner);
+            let mut canvas = TextCanvas::new(plot, [0.0, now], [0.0, y_max]);
+            for line in &acks {
+                canvas.line((line.x1, line.y1), (line.x2, line.y2), '-', line.color);
+            }
+            for (bar, segment) in bars.iter().zip(&graph.segments) {
+                let ch = if segment.retransmit { 'R' } else { '|' };
+                canvas.line((bar.x1, bar.y1), (bar.x2, bar.y2), ch, bar.color);
+            }
+            for &(x, y) in &lost {
+                canvas.point(x, y, 'x', palette.drop);
+            }
+            let legend_line = Line::from(vec![
+                Span::styled("| sent  ", Style::default().fg(palette.send)),
+                Span::styled("R retransmitted  ", Style::default().fg(palette.retransmit)),
+                Span::styled("x lost  ", Style::default().fg(palette.drop)),
+                Span::styled("- ACK  ", Style::default().fg(palette.deliver)),
+                Span::raw(format!("x 0..{:.0}  y 0..{:.0}", now, y_max)),
+            ]);
+            f.render_widget(Paragraph::new(canvas.into_lines()), plot);
+            f.render_widget(Paragraph::new(legend_line), legend);
+            return;
+        }
+
+        let canvas = Canvas::default()
+            .block(block)
+            .x_bounds([0.0, now])
+            .y_bounds([0.0, y_max])
+            .paint(move |ctx| {
+                for line in acks.iter().chain(&bars) {
+                    ctx.draw(line);
+                }
+                if !lost.is_empty() {
+                    ctx.draw(&Points {
+                        coords: &lost,
+                        color: palette.drop,
+                    });
+                }
+                ctx.print(0.0, y_max, format!("{} {:.0}", Msg::AxisSeq.text(), y_max));
+                ctx.print(now, 0.0, format!("{:.0} ms", now));
+            });
+        f.render_widget(canvas, area);
+    }
+
+    /// The run's and the reference's space-time diagrams on one time axis, with the time
+    /// they first differ marked on both.
+    fn render_side_by_side(&self, f: &mut Frame, area: Rect, reference: &Simulator) {
+        let halves = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(area);
+        let (ours, theirs) = (&self.simulator.link_events, &reference.link_events);
+        let width = halves[0].width;
+        let span = match (recent_span(ours, width), recent_span(theirs, width)) {
+            (Some(a), Some(b)) => Some((a.0.min(b.0), a.1.max(b.1))),
+            (a, b) => a.or(b),
+        };
+        // Events before both runs' next event are final in each.
+        let settled = [&self.simulator, reference]
+            .into_iter()
+            .filter_map(|sim| sim.peek_next_event_time())
+            .min()
+            .unwrap_or(u64::MAX);
+        let divergence = first_divergence(ours, theirs, settled);
+        let title = match divergence {
+            Some(time) => Msg::SpaceTimeDiverged.fill(&[&time]),
+            None => Msg::SpaceTimeYours.text().to_string(),
+        };
+        self.render_link_space_time(f, halves[0], ours, &title, span, divergence);
+        self.render_link_space_time(
+            f,
+            halves[1],
+            theirs,
+            Msg::SpaceTimeReference.text(),
+            span,
+            divergence,
+        );
+    }
+
+    /// The run's space-time diagram next to the capture's, on one time axis.
+    fn render_beside_capture(&self, f: &mut Frame, area: Rect, capture: &Capture) {
+        let halves = Layout::horizontal([Constraint::Percentage(50); 2]).split(area);
+        let ours = &self.simulator.link_events;
+        let (theirs, _) = self.capture_so_far(capture);
+        let width = halves[0].width;
+        let span = match (recent_span(ours, width), recent_span(theirs, width)) {
+            (Some(a), Some(b)) => Some((a.0.min(b.0), a.1.max(b.1))),
+            (a, b) => a.or(b),
+        };
+        self.render_link_space_time(f, halves[0], ours, Msg::SpaceTimeYours.text(), span, None);
+        let title = Msg::CaptureTitle.fill(&[&capture.sender, &capture.receiver]);
+        self.render_link_space_time(f, halves[1], theirs, &title, span, None);
+    }
+
+    /// The space-time diagram of the latest `events` that fit, or of those within `span`
+    /// (ms) when given. `marker` draws a vertical line at that time.
+    fn render_link_space_time(
+        &self,
+        f: &mut Frame,
+        area: Rect,
+        events: &[LinkEventSummary],
+        title: &str,
+        span: Option<(f64, f64)>,
+        marker: Option<u64>,
+    ) {
+        let Some((t_min, t_max)) = span.or_else(|| recent_span(events, area.width)) else {
+            let block = Paragraph::new(Msg::NoLinkActivity.text()).block(self.block(title));
+            f.render_widget(block, area);
+            return;
+        };
+        let window_events: Vec<_> = events
+            .iter()
+            .filter(|e| (t_min..=t_max).contains(&(e.time as f64)))
+            .collect();
+        let marker = marker
+            .map(|time| time as f64)
+            .filter(|time| (t_min..=t_max).contains(time));
+
+        let palette = self.mode.palette();
+
         // 构造发送箭头（Sender/Receiver 之间的斜线）
         let mut lines: Vec<CanvasLine> = Vec::new();
         let mut drop_points: Vec<(f64, f64)> = Vec::new();
@@ -407,9 +1174,15 @@ impl TuiApp {
         for e in &window_events {
             let desc = e.description.as_str();
             let t0 = e.time as f64;
-            let direction = detect_direction(desc);
+            let direction = e.direction();
 
-            if desc.contains("SEND") {
+            if matches!(e.kind, LinkEventKind::Send | LinkEventKind::Probe) {
+                // Zero-window probes are drawn like sends, in their own colour.
+                let color = if e.kind == LinkEventKind::Probe {
+                    palette.probe
+                } else {
+                    palette.send
+                };
                 // 方向：Sender->Receiver 或 Receiver->Sender
                 let (y_src, y_dst) = match direction {
                     LinkDirection::SenderToReceiver => (0.0, 2.0),
@@ -418,15 +1191,7 @@ impl TuiApp {
                 };
 
                 // 解析 latency=XXms
-                let mut latency = 0.0;
-                if let Some(idx) = desc.find("latency=") {
-                    let s = &desc[idx + "latency=".len()..];
-                    if let Some(end_idx) = s.find("ms")
-                        && let Ok(v) = s[..end_idx].trim().parse::<f64>()
-                    {
-                        latency = v;
-                    }
-                }
+                let latency = e.latency().unwrap_or(0) as f64;
                 let t1 = if latency > 0.0 {
                     t0 + latency
                 } else {
@@ -441,30 +1206,34 @@ impl TuiApp {
                     y1: y_src,
                     x2: mid_t,
                     y2: mid_y,
-                    color: Color::White,
+                    color,
                 });
                 lines.push(CanvasLine {
                     x1: mid_t,
                     y1: mid_y,
                     x2: t1,
                     y2: y_dst,
-                    color: Color::White,
+                    color,
                 });
+                // The protocol's own label, halfway along the first leg.
+                if let Some(note) = e.note() {
+                    annotations.push((
+                        (t0 + mid_t) / 2.0,
+                        (y_src + mid_y) / 2.0,
+                        note.to_string(),
+                        color,
+                    ));
+                }
             } else if desc.contains("DROP") {
                 drop_points.push((t0, 1.0));
-                annotations.push((
-                    t0,
-                    1.25,
-                    format_link_annotation(desc, "DROP", direction),
-                    Color::Red,
-                ));
+                annotations.push((t0, 1.25, format_link_annotation(e, "DROP"), palette.drop));
             } else if desc.contains("CORRUPT") {
                 corrupt_points.push((t0, 1.0));
                 annotations.push((
                     t0,
                     0.75,
-                    format_link_annotation(desc, "CORRUPT", direction),
-                    Color::Yellow,
+                    format_link_annotation(e, "CORRUPT"),
+                    palette.corrupt,
                 ));
             }
         }
@@ -476,12 +1245,43 @@ impl TuiApp {
         let drop_points = drop_points;
         let corrupt_points = corrupt_points;
 
+        if self.mode.ascii() {
+            let block = self.block(title);
+            let inner = block.inner(area);
+            f.render_widget(block, area);
+            let mut canvas = TextCanvas::new(inner, [t_min, t_max], [y_min, y_max]);
+            for (y, color) in [
+                (0.0, palette.sender_axis),
+                (1.0, palette.channel_axis),
+                (2.0, palette.receiver_axis),
+            ] {
+                canvas.line((t_min, y), (t_max, y), '-', color);
+            }
+            if let Some(x) = marker {
+                canvas.line((x, y_min), (x, y_max), '|', palette.note);
+            }
+            for line in &lines {
+                let ch = if line.y2 > line.y1 { '/' } else { '\\' };
+                canvas.line((line.x1, line.y1), (line.x2, line.y2), ch, line.color);
+            }
+            for &(x, y) in &drop_points {
+                canvas.point(x, y, 'X', palette.drop);
+            }
+            for &(x, y) in &corrupt_points {
+                canvas.point(x, y, '?', palette.corrupt);
+            }
+            for (x, y, label, color) in &annotations {
+                canvas.print(*x, *y, label, *color);
+            }
+            canvas.print(t_min, 0.0, "S", palette.sender_axis);
+            canvas.print(t_min, 1.0, "ch", palette.channel_axis);
+            canvas.print(t_min, 2.0, "R", palette.receiver_axis);
+            f.render_widget(Paragraph::new(canvas.into_lines()), inner);
+            return;
+        }
+
         let canvas = Canvas::default()
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .title("Link Space-Time Diagram"),
-            )
+            .block(self.block(title))
             .x_bounds([t_min, t_max])
             .y_bounds([y_min, y_max])
             .paint(move |ctx| {
@@ -491,23 +1291,33 @@ impl TuiApp {
                     y1: 0.0,
                     x2: t_max,
                     y2: 0.0,
-                    color: Color::Cyan,
+                    color: palette.sender_axis,
                 });
                 ctx.draw(&CanvasLine {
                     x1: t_min,
                     y1: 1.0,
                     x2: t_max,
                     y2: 1.0,
-                    color: Color::Gray,
+                    color: palette.channel_axis,
                 });
                 ctx.draw(&CanvasLine {
                     x1: t_min,
                     y1: 2.0,
                     x2: t_max,
                     y2: 2.0,
-                    color: Color::Yellow,
+                    color: palette.receiver_axis,
                 });
 
+                if let Some(x) = marker {
+                    ctx.draw(&CanvasLine {
+                        x1: x,
+                        y1: y_min,
+                        x2: x,
+                        y2: y_max,
+                        color: palette.note,
+                    });
+                }
+
                 // 标签（简单文本，不带样式）
                 ctx.print(t_min, 0.0, "S");
                 ctx.print(t_min, 1.0, "ch");
@@ -522,13 +1332,13 @@ impl TuiApp {
                 if !drop_points.is_empty() {
                     ctx.draw(&Points {
                         coords: &drop_points,
-                        color: Color::Red,
+                        color: palette.drop,
                     });
                 }
                 if !corrupt_points.is_empty() {
                     ctx.draw(&Points {
                         coords: &corrupt_points,
-                        color: Color::Yellow,
+                        color: palette.corrupt,
                     });
                 }
 
@@ -547,8 +1357,8 @@ impl TuiApp {
     fn render_link_events(&self, f: &mut Frame, area: Rect) {
         let events = &self.simulator.link_events;
         if events.is_empty() {
-            let block = Paragraph::new("No link events yet")
-                .block(Block::default().borders(Borders::ALL).title("Link Events"));
+            let block =
+                Paragraph::new(Msg::NoLinkEvents.text()).block(self.block(Msg::LinkEvents.text()));
             f.render_widget(block, area);
             return;
         }
@@ -560,80 +1370,133 @@ impl TuiApp {
         let scroll = self.link_scroll.min(max_scroll);
         let start = total.saturating_sub(visible + scroll);
         let end = total.saturating_sub(scroll);
-        let start = start.max(0);
         let end = end.max(start);
         let slice = &events[start..end];
+        let palette = self.mode.palette();
 
         let items: Vec<ListItem> = slice
             .iter()
             .map(|e| {
                 let text = format!("[{:>5} ms] {}", e.time, e.description);
-                let style = if e.description.contains("DROP") || e.description.contains("CORRUPT") {
-                    Style::default().fg(Color::Red)
-                } else if e.description.contains("DELIVERED") {
-                    Style::default().fg(Color::Green)
-                } else {
-                    Style::default().fg(Color::White)
+                let style = match e.kind {
+                    LinkEventKind::Drop
+                    | LinkEventKind::InjectedDrop
+                    | LinkEventKind::Corrupt
+                    | LinkEventKind::InjectedCorrupt
+                    | LinkEventKind::Truncate
+                    | LinkEventKind::TamperedDuplicate
+                    | LinkEventKind::AckCompressed
+                    | LinkEventKind::Policed
+                    | LinkEventKind::QueueDrop => Style::default().fg(palette.drop),
+                    LinkEventKind::Deliver => Style::default().fg(palette.deliver),
+                    LinkEventKind::Probe => Style::default().fg(palette.probe),
+                    LinkEventKind::Restart
+                    | LinkEventKind::Injected
+                    | LinkEventKind::Rewrite
+                    | LinkEventKind::LatencyChange
+                    | LinkEventKind::Close
+                    | LinkEventKind::AppFlow
+                    | LinkEventKind::Mark => Style::default().fg(palette.restart),
+                    LinkEventKind::SendStorm => Style::default().fg(palette.storm),
+                    LinkEventKind::Annotation => Style::default()
+                        .fg(palette.note)
+                        .add_modifier(Modifier::BOLD),
+                    _ => Style::default().fg(palette.send),
                 };
                 ListItem::new(Line::from(Span::styled(text, style)))
             })
             .collect();
 
-        let list =
-            List::new(items).block(Block::default().borders(Borders::ALL).title("Link Events"));
+        let list = List::new(items).block(self.block(Msg::LinkEvents.text()));
 
         f.render_widget(list, area);
     }
 }
 
-fn format_link_annotation(desc: &str, fallback: &str, direction: LinkDirection) -> String {
-    const LIMIT: usize = 16;
-    let keys: [&str; 2] = match direction {
-        LinkDirection::SenderToReceiver => ["seq=", "ack="],
-        LinkDirection::ReceiverToSender => ["ack=", "seq="],
-        LinkDirection::Unknown => ["seq=", "ack="],
-    };
+/// Name, plot character, colour and points of one series in a text chart.
+type TextSeries<'a> = (&'a str, char, Color, Option<&'a [(f64, f64)]>);
 
-    if let Some(field) = keys.into_iter().find_map(|key| extract_field(desc, key)) {
-        let label = format!("{} {}", fallback, field);
-        if label.len() > LIMIT {
-            label[..LIMIT].to_string()
-        } else {
-            label
-        }
-    } else {
-        fallback.to_string()
-    }
+/// A metric band's guide line in an ASCII chart.
+fn band_series(bands: &[(String, [(f64, f64); 2])], color: Color) -> Vec<TextSeries<'_>> {
+    bands
+        .iter()
+        .map(|(label, line)| (label.as_str(), '-', color, Some(line.as_slice())))
+        .collect()
 }
 
-fn extract_field(desc: &str, key: &str) -> Option<String> {
-    let idx = desc.find(key)?;
-    let rest = &desc[idx + key.len()..];
-    let token = rest
-        .split([' ', ')', '|'])
-        .next()
-        .unwrap_or("")
-        .trim_matches(',');
-    if token.is_empty() {
-        None
-    } else {
-        Some(format!("{}{}", key, token))
+/// A metric band's guide line in a chart.
+fn band_dataset<'a>(label: &'a str, line: &'a [(f64, f64); 2], color: Color) -> Dataset<'a> {
+    Dataset::default()
+        .name(label)
+        .marker(symbols::Marker::Braille)
+        .style(Style::default().fg(color).add_modifier(Modifier::DIM))
+        .graph_type(GraphType::Line)
+        .data(line)
+}
+
+/// ASCII-mode chart: each series drawn with its own character, with a legend and the axis
+/// ranges on the last line.
+fn render_text_chart(
+    f: &mut Frame,
+    area: Rect,
+    block: Block,
+    series: &[TextSeries],
+    x: [f64; 2],
+    y: [f64; 2],
+) {
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let [plot, legend] = Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(inner);
+
+    let mut canvas = TextCanvas::new(plot, x, y);
+    let mut legend_spans = Vec::new();
+    for &(name, ch, color, pts) in series {
+        let Some(pts) = pts.filter(|pts| !pts.is_empty()) else {
+            continue;
+        };
+        for pair in pts.windows(2) {
+            canvas.line(pair[0], pair[1], ch, color);
+        }
+        canvas.point(pts[0].0, pts[0].1, ch, color);
+        legend_spans.push(Span::styled(
+            format!("{ch} {name}  "),
+            Style::default().fg(color),
+        ));
     }
+    legend_spans.push(Span::raw(format!(
+        "x {:.0}..{:.0}  y {:.0}..{:.0}",
+        x[0], x[1], y[0], y[1]
+    )));
+    f.render_widget(Paragraph::new(canvas.into_lines()), plot);
+    f.render_widget(Paragraph::new(Line::from(legend_spans)), legend);
 }
 
-#[derive(Copy, Clone, Debug)]
-enum LinkDirection {
-    SenderToReceiver,
-    ReceiverToSender,
-    Unknown,
+/// The time span (ms) of the latest events that fit in a diagram `width` columns wide.
+fn recent_span(events: &[LinkEventSummary], width: u16) -> Option<(f64, f64)> {
+    // 仅展示最近若干个事件，形成简单的“局部时空图”
+    let max_events = (width as usize).saturating_sub(4).max(4);
+    let window = &events[events.len().saturating_sub(max_events)..];
+    let t_min = window.first()?.time as f64;
+    let mut t_max = window.last()?.time as f64;
+    if (t_max - t_min).abs() < f64::EPSILON {
+        t_max += 1.0;
+    }
+    Some((t_min, t_max))
 }
 
-fn detect_direction(desc: &str) -> LinkDirection {
-    if desc.contains("[Sender->Receiver]") {
-        LinkDirection::SenderToReceiver
-    } else if desc.contains("[Receiver->Sender]") {
-        LinkDirection::ReceiverToSender
+fn format_link_annotation(event: &LinkEventSummary, fallback: &str) -> String {
+    const LIMIT: usize = 16;
+    // Data packets are told apart by their seq, ACKs by their ack.
+    let Some(packet) = event.packet else {
+        return fallback.to_string();
+    };
+    let label = match packet.from {
+        NodeId::Sender => format!("{} seq={}", fallback, packet.seq),
+        NodeId::Receiver => format!("{} ack={}", fallback, packet.ack),
+    };
+    if label.len() > LIMIT {
+        label[..LIMIT].to_string()
     } else {
-        LinkDirection::Unknown
+        label
     }
 }
@@ -0,0 +1,236 @@
+//! Render modes for terminals and students the default look does not work for: a
+//! colour-blind-safe palette, and an ASCII mode that replaces Braille canvases with text.
+
+use std::str::FromStr;
+
+use ratatui::prelude::*;
+use ratatui::symbols::border;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RenderMode {
+    /// Braille charts with the classic red/green/yellow link colours.
+    #[default]
+    Color,
+    /// Braille charts with the Okabe-Ito palette instead of red/green coding.
+    ColorBlind,
+    /// Text charts, ASCII borders and markers, and the colour-blind-safe palette.
+    Ascii,
+}
+
+impl FromStr for RenderMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('_', "-").as_str() {
+            "color" | "colour" | "default" => Ok(RenderMode::Color),
+            "color-blind" | "colour-blind" | "colorblind" | "cb" => Ok(RenderMode::ColorBlind),
+            "ascii" => Ok(RenderMode::Ascii),
+            other => Err(format!(
+                "unknown render mode '{other}' (use color, color-blind or ascii)"
+            )),
+        }
+    }
+}
+
+impl RenderMode {
+    pub(super) fn palette(self) -> &'static Palette {
+        match self {
+            RenderMode::Color => &CLASSIC,
+            RenderMode::ColorBlind | RenderMode::Ascii => &OKABE_ITO,
+        }
+    }
+
+    pub(super) fn ascii(self) -> bool {
+        self == RenderMode::Ascii
+    }
+
+    pub(super) fn border_set(self) -> border::Set {
+        if self.ascii() {
+            ASCII_BORDER
+        } else {
+            border::PLAIN
+        }
+    }
+
+    pub(super) fn ellipsis(self) -> &'static str {
+        if self.ascii() { "... " } else { "… " }
+    }
+
+    pub(super) fn arrow(self) -> &'static str {
+        if self.ascii() { "-> " } else { "→ " }
+    }
+}
+
+const ASCII_BORDER: border::Set = border::Set {
+    top_left: "+",
+    top_right: "+",
+    bottom_left: "+",
+    bottom_right: "+",
+    vertical_left: "|",
+    vertical_right: "|",
+    horizontal_top: "-",
+    horizontal_bottom: "-",
+};
+
+/// Colours for everything the TUI codes by colour.
+pub(super) struct Palette {
+    pub send: Color,
+    pub retransmit: Color,
+    pub probe: Color,
+    pub drop: Color,
+    pub corrupt: Color,
+    pub deliver: Color,
+    pub restart: Color,
+    pub storm: Color,
+    pub note: Color,
+    pub current: Color,
+    pub muted: Color,
+    pub sender_axis: Color,
+    pub channel_axis: Color,
+    pub receiver_axis: Color,
+    pub cwnd: Color,
+    pub ssthresh: Color,
+    pub in_flight: Color,
+    pub window: Color,
+    pub queue: Color,
+    pub band: Color,
+}
+
+const CLASSIC: Palette = Palette {
+    send: Color::White,
+    retransmit: Color::LightRed,
+    probe: Color::Cyan,
+    drop: Color::Red,
+    corrupt: Color::Yellow,
+    deliver: Color::Green,
+    restart: Color::Magenta,
+    storm: Color::Yellow,
+    note: Color::LightBlue,
+    current: Color::Green,
+    muted: Color::DarkGray,
+    sender_axis: Color::Cyan,
+    channel_axis: Color::Gray,
+    receiver_axis: Color::Yellow,
+    cwnd: Color::Cyan,
+    ssthresh: Color::Yellow,
+    in_flight: Color::Magenta,
+    window: Color::Red,
+    queue: Color::LightBlue,
+    band: Color::LightGreen,
+};
+
+// Okabe-Ito colours, as the nearest xterm-256 entries so 256-colour terminals show them too.
+const BLUE: Color = Color::Indexed(32);
+const ORANGE: Color = Color::Indexed(214);
+const SKY_BLUE: Color = Color::Indexed(117);
+const BLUISH_GREEN: Color = Color::Indexed(36);
+const YELLOW: Color = Color::Indexed(227);
+const VERMILLION: Color = Color::Indexed(166);
+const REDDISH_PURPLE: Color = Color::Indexed(175);
+
+const OKABE_ITO: Palette = Palette {
+    send: Color::White,
+    retransmit: ORANGE,
+    probe: SKY_BLUE,
+    drop: VERMILLION,
+    corrupt: YELLOW,
+    deliver: BLUE,
+    restart: REDDISH_PURPLE,
+    storm: ORANGE,
+    note: SKY_BLUE,
+    current: BLUE,
+    muted: Color::DarkGray,
+    sender_axis: SKY_BLUE,
+    channel_axis: Color::Gray,
+    receiver_axis: ORANGE,
+    cwnd: SKY_BLUE,
+    ssthresh: ORANGE,
+    in_flight: BLUISH_GREEN,
+    window: VERMILLION,
+    queue: BLUE,
+    band: REDDISH_PURPLE,
+};
+
+/// A character grid standing in for a Braille canvas or chart in ASCII mode. Coordinates are
+/// in data units, y pointing up, as on the canvas it replaces.
+pub(super) struct TextCanvas {
+    width: usize,
+    height: usize,
+    x: [f64; 2],
+    y: [f64; 2],
+    cells: Vec<(char, Color)>,
+}
+
+impl TextCanvas {
+    pub fn new(area: Rect, x: [f64; 2], y: [f64; 2]) -> Self {
+        let (width, height) = (area.width.max(1) as usize, area.height.max(1) as usize);
+        Self {
+            width,
+            height,
+            x,
+            y,
+            cells: vec![(' ', Color::Reset); width * height],
+        }
+    }
+
+    fn cell(&self, x: f64, y: f64) -> (f64, f64) {
+        let span = |[lo, hi]: [f64; 2]| if hi > lo { hi - lo } else { 1.0 };
+        let col = (x - self.x[0]) / span(self.x) * (self.width - 1) as f64;
+        let row =
+            (self.height - 1) as f64 - (y - self.y[0]) / span(self.y) * (self.height - 1) as f64;
+        (col, row)
+    }
+
+    fn put_cell(&mut self, col: f64, row: f64, ch: char, color: Color) {
+        let (col, row) = (col.round(), row.round());
+        if col < 0.0 || row < 0.0 || col >= self.width as f64 || row >= self.height as f64 {
+            return;
+        }
+        self.cells[row as usize * self.width + col as usize] = (ch, color);
+    }
+
+    pub fn point(&mut self, x: f64, y: f64, ch: char, color: Color) {
+        let (col, row) = self.cell(x, y);
+        self.put_cell(col, row, ch, color);
+    }
+
+    pub fn line(&mut self, from: (f64, f64), to: (f64, f64), ch: char, color: Color) {
+        let (c1, r1) = self.cell(from.0, from.1);
+        let (c2, r2) = self.cell(to.0, to.1);
+        let steps = (c2 - c1).abs().max((r2 - r1).abs()).ceil().max(1.0);
+        for i in 0..=steps as usize {
+            let f = i as f64 / steps;
+            self.put_cell(c1 + (c2 - c1) * f, r1 + (r2 - r1) * f, ch, color);
+        }
+    }
+
+    pub fn print(&mut self, x: f64, y: f64, text: &str, color: Color) {
+        let (col, row) = self.cell(x, y);
+        for (i, ch) in text.chars().enumerate() {
+            self.put_cell(col.round() + i as f64, row, ch, color);
+        }
+    }
+
+    pub fn into_lines(self) -> Vec<Line<'static>> {
+        self.cells
+            .chunks(self.width)
+            .map(|row| {
+                let mut spans: Vec<Span> = Vec::new();
+                let mut run = String::new();
+                let mut run_color = row[0].1;
+                for &(ch, color) in row {
+                    if color != run_color && !run.is_empty() {
+                        spans.push(Span::styled(
+                            std::mem::take(&mut run),
+                            Style::default().fg(run_color),
+                        ));
+                    }
+                    run_color = color;
+                    run.push(ch);
+                }
+                spans.push(Span::styled(run, Style::default().fg(run_color)));
+                Line::from(spans)
+            })
+            .collect()
+    }
+}
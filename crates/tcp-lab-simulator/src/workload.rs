@@ -0,0 +1,155 @@
+use rand::distr::Alphanumeric;
+use rand::{Rng, SeedableRng};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+use tcp_lab_abstract::SimConfig;
+
+use crate::engine::Simulator;
+
+/// Keeps random payloads independent of the link's loss and latency draws.
+const PAYLOAD_SEED_SALT: u64 = 0x5041_594c_4f41_4453;
+
+/// The link used by ad-hoc runs that have no scenario: 10% loss, 100–500 ms latency.
+pub fn default_config() -> SimConfig {
+    SimConfig {
+        loss_rate: 0.1,
+        min_latency: 100,
+        max_latency: 500,
+        seed: 42,
+        ..Default::default()
+    }
+}
+
+/// What the generated messages contain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PayloadPattern {
+    /// `Packet N` padded with `.` to the message size, or cut off if longer.
+    #[default]
+    Counter,
+    /// Zero bytes.
+    Zeros,
+    /// Repeating `A`..`Z`, continuing across messages so every byte's offset in
+    /// the stream can be read off its letter.
+    Alphabet,
+    /// Random bytes drawn from the simulation seed.
+    Random,
+}
+
+impl FromStr for PayloadPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "counter" => Ok(PayloadPattern::Counter),
+            "zeros" => Ok(PayloadPattern::Zeros),
+            "alphabet" => Ok(PayloadPattern::Alphabet),
+            "random" => Ok(PayloadPattern::Random),
+            other => Err(format!(
+                "unknown payload pattern '{other}' (use counter, zeros, alphabet or random)"
+            )),
+        }
+    }
+}
+
+impl Display for PayloadPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PayloadPattern::Counter => "counter",
+            PayloadPattern::Zeros => "zeros",
+            PayloadPattern::Alphabet => "alphabet",
+            PayloadPattern::Random => "random",
+        })
+    }
+}
+
+/// Application messages for runs without a scenario: `messages` sends of
+/// `size` bytes, the first at `interval` ms and then every `interval` ms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Workload {
+    pub messages: usize,
+    pub size: usize,
+    pub interval: u64,
+    pub pattern: PayloadPattern,
+}
+
+impl Default for Workload {
+    /// Three 16-byte messages one second apart.
+    fn default() -> Self {
+        Self {
+            messages: 3,
+            size: 16,
+            interval: 1000,
+            pattern: PayloadPattern::Counter,
+        }
+    }
+}
+
+impl Workload {
+    /// The payloads in send order.
+    pub fn payloads(&self, seed: u64) -> Vec<Vec<u8>> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed ^ PAYLOAD_SEED_SALT);
+        (0..self.messages)
+            .map(|idx| match self.pattern {
+                PayloadPattern::Counter => {
+                    let mut data = format!("Packet {}", idx + 1).into_bytes();
+                    data.resize(self.size, b'.');
+                    data
+                }
+                PayloadPattern::Zeros => vec![0; self.size],
+                PayloadPattern::Alphabet => (0..self.size)
+                    .map(|offset| b'A' + ((idx * self.size + offset) % 26) as u8)
+                    .collect(),
+                PayloadPattern::Random => (0..self.size).map(|_| rng.random()).collect(),
+            })
+            .collect()
+    }
+
+    /// Queue every message on `sim`, using its seed for random payloads.
+    pub fn schedule(&self, sim: &mut Simulator) {
+        let seed = sim.config().seed;
+        for (idx, data) in self.payloads(seed).into_iter().enumerate() {
+            sim.schedule_app_send((idx as u64 + 1) * self.interval, data);
+        }
+    }
+}
+
+/// Payload of media frame `index`: `frame N:` followed by letters and digits
+/// drawn from the index, cut to `size` bytes. Frames differ from each other, so
+/// a delivery can be traced back to the frame it belongs to.
+pub fn media_frame(index: u32, size: usize) -> Vec<u8> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(u64::from(index) ^ PAYLOAD_SEED_SALT);
+    let mut data = format!("frame {index}:").into_bytes();
+    data.extend((0..size).map(|_| rng.sample(Alphanumeric)));
+    data.truncate(size);
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counter_payloads_are_padded_and_numbered() {
+        let workload = Workload {
+            messages: 2,
+            size: 10,
+            ..Default::default()
+        };
+        assert_eq!(
+            workload.payloads(42),
+            vec![b"Packet 1..".to_vec(), b"Packet 2..".to_vec()]
+        );
+    }
+
+    #[test]
+    fn alphabet_continues_across_messages() {
+        let workload = Workload {
+            messages: 2,
+            size: 20,
+            pattern: PayloadPattern::Alphabet,
+            ..Default::default()
+        };
+        let payloads = workload.payloads(42);
+        assert_eq!(&payloads[1][..8], b"UVWXYZAB");
+    }
+}
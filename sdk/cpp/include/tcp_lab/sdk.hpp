@@ -1,39 +1,85 @@
 #pragma once
 
 #include <cstdint>
+#include <map>
+#include <optional>
 #include <string>
 #include <vector>
 
+// Bumped whenever the exported protocol functions or the host callbacks change signature.
+#define TCP_LAB_ABI_VERSION 2
+// Checked by the grader, which refuses libraries built against another SDK version.
+#define TCP_LAB_SDK_VERSION 2
+
 extern "C" {
-void tcp_lab_send_packet(uint32_t seq, uint32_t ack, uint8_t flags, uint16_t window, uint16_t checksum,
-                         const uint8_t* payload, size_t payload_len);
+// Sent to the host by `protocol_selftest` when the library is loaded, so a build with
+// other struct packing, wchar_t size, pointer width or byte order is refused up front
+// instead of corrupting headers mid-run. Mirrors `SelftestProbe` in tcp-lab-ffi.
+struct tcp_lab_selftest_probe {
+    uint32_t magic;
+    uint32_t struct_size;
+    uint32_t pointer_size;
+    uint32_t wchar_size;
+    uint8_t flags;
+    uint16_t window;
+    uint32_t seq;
+    uint64_t offset;
+    double value;
+    int32_t timer_id;
+    size_t len;
+};
+
+void tcp_lab_send_packet(uint16_t src_port, uint16_t dst_port, uint32_t seq, uint32_t ack, uint8_t flags,
+                         uint16_t window, uint16_t checksum, uint16_t urgent_ptr, const uint8_t* payload,
+                         size_t payload_len);
 void tcp_lab_start_timer(uint64_t delay_ms, int32_t timer_id);
 void tcp_lab_cancel_timer(int32_t timer_id);
 void tcp_lab_deliver_data(const uint8_t* data, size_t len);
+void tcp_lab_deliver_data_at(uint64_t offset, const uint8_t* data, size_t len);
 void tcp_lab_log(const char* msg);
 uint64_t tcp_lab_now();
 void tcp_lab_record_metric(const char* name, double value);
+void tcp_lab_record_state(const char* name);
+size_t tcp_lab_init_params(char* buf, size_t buf_len);
+size_t tcp_lab_config(char* buf, size_t buf_len);
+int64_t tcp_lab_receive_window();
+int32_t tcp_lab_peer_mss();
+void tcp_lab_store(const char* key, const char* value);
+int64_t tcp_lab_load(const char* key, char* buf, size_t buf_len);
+void tcp_lab_selftest(const tcp_lab_selftest_probe* probe);
+void tcp_lab_annotate_packet(const char* note);
+void tcp_lab_app_pause();
+void tcp_lab_app_resume();
+void tcp_lab_refuse_app_data(const char* reason);
 }
 
 namespace tcp_lab::sdk {
 
 struct TcpHeader {
+    uint16_t src_port = 0;
+    uint16_t dst_port = 0;
     uint32_t seq_num = 0;
     uint32_t ack_num = 0;
     uint8_t flags = 0;
     uint16_t window_size = 0;
     uint16_t checksum = 0;
+    uint16_t urgent_ptr = 0;
 };
 
 inline void send_packet(const TcpHeader& header, const std::vector<uint8_t>& payload) {
-    tcp_lab_send_packet(header.seq_num, header.ack_num, header.flags, header.window_size, header.checksum,
-                        payload.data(), payload.size());
+    tcp_lab_send_packet(header.src_port, header.dst_port, header.seq_num, header.ack_num, header.flags,
+                        header.window_size, header.checksum, header.urgent_ptr, payload.data(), payload.size());
 }
 
 inline void deliver_data(const std::vector<uint8_t>& data) {
     tcp_lab_deliver_data(data.data(), data.size());
 }
 
+// Deliver `data` as the bytes at `offset` of the reassembled stream; the simulator checks the claim.
+inline void deliver_data_at(uint64_t offset, const std::vector<uint8_t>& data) {
+    tcp_lab_deliver_data_at(offset, data.data(), data.size());
+}
+
 inline void start_timer(uint64_t delay_ms, int timer_id) {
     tcp_lab_start_timer(delay_ms, timer_id);
 }
@@ -54,6 +100,96 @@ inline void record_metric(const std::string& name, double value) {
     tcp_lab_record_metric(name.c_str(), value);
 }
 
+// Note that the protocol's state machine entered `name`, e.g. "WAIT_ACK_0".
+inline void record_state(const std::string& name) {
+    tcp_lab_record_state(name.c_str());
+}
+
+namespace detail {
+inline std::map<std::string, std::string> read_pairs(size_t (*fetch)(char*, size_t)) {
+    std::vector<char> buffer(fetch(nullptr, 0));
+    fetch(buffer.data(), buffer.size());
+    std::map<std::string, std::string> pairs;
+    for (size_t pos = 0; pos < buffer.size();) {
+        std::string key(buffer.data() + pos);
+        pos += key.size() + 1;
+        std::string value(buffer.data() + pos);
+        pos += value.size() + 1;
+        pairs.emplace(std::move(key), std::move(value));
+    }
+    return pairs;
+}
+} // namespace detail
+
+// Parameters the scenario passes to this node ([params.sender] / [params.receiver]).
+inline std::map<std::string, std::string> init_params() {
+    return detail::read_pairs(tcp_lab_init_params);
+}
+
+// Simulation settings as strings, e.g. config()["window_size"] (unset options are absent).
+inline std::map<std::string, std::string> config() {
+    return detail::read_pairs(tcp_lab_config);
+}
+
+// Free bytes in the receiver's buffer to advertise in ACK windows; empty on the sender
+// or when the scenario models no receive buffer.
+inline std::optional<uint64_t> receive_window() {
+    int64_t window = tcp_lab_receive_window();
+    if (window < 0) {
+        return std::nullopt;
+    }
+    return static_cast<uint64_t>(window);
+}
+
+// MSS the peer advertised in its SYN; empty until that SYN has arrived or when the
+// scenario models no MSS option.
+inline std::optional<uint16_t> peer_mss() {
+    int32_t mss = tcp_lab_peer_mss();
+    if (mss < 0) {
+        return std::nullopt;
+    }
+    return static_cast<uint16_t>(mss);
+}
+
+// Keep `value` under `key` for later runs when the host opted in to a scratch store
+// (tcp-lab-sim-cli --scratch-store FILE); does nothing otherwise, graded runs included.
+inline void store(const std::string& key, const std::string& value) {
+    tcp_lab_store(key.c_str(), value.c_str());
+}
+
+// The value last stored under `key`, in this run or an earlier one; empty without a
+// scratch store.
+inline std::optional<std::string> load(const std::string& key) {
+    int64_t len = tcp_lab_load(key.c_str(), nullptr, 0);
+    if (len < 0) {
+        return std::nullopt;
+    }
+    std::string value(static_cast<size_t>(len), '\0');
+    tcp_lab_load(key.c_str(), value.data(), value.size());
+    return value;
+}
+
+// Label the next packet this node sends (e.g. "fast-retransmit") on its arrow in the
+// space-time diagram and in the trace.
+inline void annotate_packet(const std::string& note) {
+    tcp_lab_annotate_packet(note.c_str());
+}
+
+// Hold the application's sends instead of dropping what on_app_data cannot take (sender
+// only); app_resume hands them over in order.
+inline void app_pause() {
+    tcp_lab_app_pause();
+}
+
+inline void app_resume() {
+    tcp_lab_app_resume();
+}
+
+// Turn down the data on_app_data is handing over instead of dropping it silently.
+inline void refuse_app_data(const std::string& reason) {
+    tcp_lab_refuse_app_data(reason.c_str());
+}
+
 class Protocol {
   public:
     virtual ~Protocol() = default;
@@ -61,9 +197,35 @@ class Protocol {
     virtual void on_packet(const TcpHeader& header, const std::vector<uint8_t>& payload) {}
     virtual void on_timer(int timer_id) {}
     virtual void on_app_data(const std::vector<uint8_t>& data) {}
+    // The application closed the connection; finish sending what is still buffered.
+    virtual void on_close() {}
+    // Diagnostic hook: the checksum this protocol computes over `data`, which
+    // `tcp-lab-sim-cli checksum` compares with the reference test vectors. Never called
+    // during a simulation; empty means the protocol exposes no checksum.
+    virtual std::optional<uint16_t> checksum(const std::vector<uint8_t>& data) { return std::nullopt; }
 };
 
+// Behind `protocol_selftest`; see `tcp_lab_selftest_probe`.
+inline void selftest() {
+    tcp_lab_selftest_probe probe{};
+    probe.magic = 0x01020304;
+    probe.struct_size = sizeof(tcp_lab_selftest_probe);
+    probe.pointer_size = sizeof(void*);
+    probe.wchar_size = sizeof(wchar_t);
+    probe.flags = 0xA5;
+    probe.window = 0xBEEF;
+    probe.seq = 0xDEADBEEF;
+    probe.offset = 0x0123456789ABCDEF;
+    probe.value = 1.5;
+    probe.timer_id = -2;
+    probe.len = 0x5A5A;
+    tcp_lab_selftest(&probe);
+}
+
 #define TCP_LAB_REGISTER_PROTOCOL(CLASS)                                                               \
+    extern "C" uint32_t tcp_lab_abi_version() { return TCP_LAB_ABI_VERSION; }                           \
+    extern "C" void protocol_selftest() { ::tcp_lab::sdk::selftest(); }                                 \
+    extern "C" uint32_t tcp_lab_sdk_version() { return TCP_LAB_SDK_VERSION; }                           \
     extern "C" ::tcp_lab::sdk::Protocol* create_protocol() { return new CLASS(); }                      \
     extern "C" void destroy_protocol(::tcp_lab::sdk::Protocol* ptr) { delete ptr; }                     \
     extern "C" void protocol_init(::tcp_lab::sdk::Protocol* ptr) { ptr->init(); }                       \
@@ -72,22 +234,33 @@ class Protocol {
         std::vector<uint8_t> buffer(data, data + len);                                                  \
         ptr->on_app_data(buffer);                                                                       \
     }                                                                                                   \
-    extern "C" void protocol_on_packet(::tcp_lab::sdk::Protocol* ptr, uint32_t seq, uint32_t ack,       \
-                                       uint8_t flags, uint16_t window, uint16_t checksum,               \
+    extern "C" void protocol_on_packet(::tcp_lab::sdk::Protocol* ptr, uint16_t src_port,                \
+                                       uint16_t dst_port, uint32_t seq, uint32_t ack, uint8_t flags,    \
+                                       uint16_t window, uint16_t checksum, uint16_t urgent_ptr,         \
                                        const uint8_t* payload, size_t len)                              \
     {                                                                                                   \
         TcpHeader header{};                                                                             \
+        header.src_port = src_port;                                                                     \
+        header.dst_port = dst_port;                                                                     \
         header.seq_num = seq;                                                                           \
         header.ack_num = ack;                                                                           \
         header.flags = flags;                                                                           \
         header.window_size = window;                                                                    \
         header.checksum = checksum;                                                                     \
+        header.urgent_ptr = urgent_ptr;                                                                 \
         std::vector<uint8_t> buffer(payload, payload + len);                                            \
         ptr->on_packet(header, buffer);                                                                 \
     }                                                                                                   \
     extern "C" void protocol_on_timer(::tcp_lab::sdk::Protocol* ptr, int timer_id)                      \
     {                                                                                                   \
         ptr->on_timer(timer_id);                                                                        \
-    }
+    }                                                                                                   \
+    extern "C" void protocol_on_close(::tcp_lab::sdk::Protocol* ptr) { ptr->on_close(); }               \
+    extern "C" int32_t protocol_checksum(::tcp_lab::sdk::Protocol* ptr, const uint8_t* data,            \
+                                         size_t len)                                                    \
+    {                                                                                                   \
+        auto checksum = ptr->checksum(std::vector<uint8_t>(data, data + len));                          \
+        return checksum ? *checksum : -1;                                                               \
+    }                                                                                                   \
 
 } // namespace tcp_lab::sdk
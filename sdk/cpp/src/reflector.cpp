@@ -0,0 +1,13 @@
+#include "tcp_lab/sdk.hpp"
+
+using namespace tcp_lab::sdk;
+
+// Sends every packet straight back, for the grader's `round-trip` check of the bridge.
+class Reflector final : public Protocol {
+  public:
+    void on_packet(const TcpHeader& header, const std::vector<uint8_t>& payload) override {
+        send_packet(header, payload);
+    }
+};
+
+TCP_LAB_REGISTER_PROTOCOL(Reflector)
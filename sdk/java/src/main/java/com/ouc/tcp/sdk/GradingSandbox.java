@@ -0,0 +1,138 @@
+package com.ouc.tcp.sdk;
+
+import java.io.FilePermission;
+import java.lang.reflect.ReflectPermission;
+import java.net.SocketPermission;
+import java.security.AccessController;
+import java.security.Permission;
+
+/**
+ * Security manager the host installs for graded runs. Protocol code may still read files (the
+ * JVM loads classes that way) but may not write or delete them, open sockets, start processes,
+ * exit the JVM or replace this manager. Nor may it load native code, create class loaders,
+ * use internal JDK packages, bypass access checks with reflection or redirect the standard
+ * streams, all of which could switch the manager off; the JDK itself still may, inside its own
+ * privileged blocks. Each attempt is reported to the simulator as an integrity violation and
+ * then fails with a {@link SecurityException}.
+ */
+@SuppressWarnings("removal")
+final class GradingSandbox extends SecurityManager {
+    private GradingSandbox() {}
+
+    /** Called by the host through JNI right after the JVM starts. */
+    static void install() {
+        System.setSecurityManager(new GradingSandbox());
+    }
+
+    @Override
+    public void checkPermission(Permission perm) {
+        String forbidden = describe(perm);
+        if (forbidden == null) {
+            String escape = describeEscape(perm);
+            if (escape != null && fromProtocol()) {
+                forbidden = escape;
+            }
+        }
+        if (forbidden != null) {
+            NativeBridge.reportViolation(forbidden);
+            throw new SecurityException(forbidden + " is not allowed in graded runs");
+        }
+    }
+
+    @Override
+    public void checkPermission(Permission perm, Object context) {
+        checkPermission(perm);
+    }
+
+    /** What a forbidden permission would allow, or {@code null} when it is allowed. */
+    private static String describe(Permission perm) {
+        if (perm instanceof FilePermission) {
+            String actions = perm.getActions();
+            if (actions.contains("execute")) {
+                return "running " + perm.getName();
+            }
+            if (actions.contains("write") || actions.contains("delete")) {
+                return "writing " + perm.getName();
+            }
+            return null;
+        }
+        if (perm instanceof SocketPermission) {
+            return "network access to " + perm.getName();
+        }
+        if (perm instanceof RuntimePermission) {
+            String name = perm.getName();
+            if (name.startsWith("exitVM")) {
+                return "System.exit";
+            }
+            if (name.equals("setSecurityManager")) {
+                return "replacing the grading security manager";
+            }
+        }
+        return null;
+    }
+
+    /**
+     * What a permission the JDK needs internally would let protocol code do to get out of the
+     * sandbox, or {@code null} when it is harmless.
+     */
+    private static String describeEscape(Permission perm) {
+        String name = perm.getName();
+        if (perm instanceof ReflectPermission) {
+            if (name.equals("suppressAccessChecks")) {
+                return "bypassing access checks with reflection";
+            }
+            return null;
+        }
+        if (!(perm instanceof RuntimePermission)) {
+            return null;
+        }
+        if (name.startsWith("loadLibrary.")) {
+            return "loading the native library " + name.substring("loadLibrary.".length());
+        }
+        if (name.equals("createClassLoader")) {
+            return "creating a class loader";
+        }
+        if (name.equals("accessDeclaredMembers")) {
+            return "reflecting on the private members of another class";
+        }
+        if (name.startsWith("accessClassInPackage.sun.")
+                || name.startsWith("accessClassInPackage.jdk.internal.")) {
+            return "using the internal package " + name.substring("accessClassInPackage.".length());
+        }
+        if (name.equals("setIO")) {
+            return "replacing System.in, System.out or System.err";
+        }
+        return null;
+    }
+
+    /**
+     * Whether protocol code asked for the permission: a class outside the JDK is on the stack
+     * before any JDK class that made the request inside {@code AccessController.doPrivileged}.
+     * Reflection frames are skipped, so protocol code cannot pass for the JDK by calling
+     * {@code doPrivileged} reflectively.
+     */
+    private boolean fromProtocol() {
+        Class<?>[] stack = getClassContext();
+        for (int i = 0; i < stack.length; i++) {
+            Class<?> cls = stack[i];
+            if (cls == GradingSandbox.class) {
+                continue;
+            }
+            if (cls == AccessController.class) {
+                if (i + 1 < stack.length && isJdk(stack[i + 1])) {
+                    return false;
+                }
+                continue;
+            }
+            if (!isJdk(cls)) {
+                return true;
+            }
+        }
+        return false;
+    }
+
+    private static boolean isJdk(Class<?> cls) {
+        ClassLoader loader = cls.getClassLoader();
+        return loader == null || loader == ClassLoader.getPlatformClassLoader();
+    }
+}
@@ -11,7 +11,9 @@ final class NativeBridge {
 
     private NativeBridge() {}
 
-    static native void sendPacket(long seq, long ack, byte flags, int window, int checksum, int urgentPtr, byte[] payload);
+    static native void sendPacket(
+            int srcPort, int dstPort, long seq, long ack, byte flags, int window, int checksum, int urgentPtr,
+            byte[] payload);
 
     static native void startTimer(long delayMs, int timerId);
 
@@ -19,9 +21,39 @@ final class NativeBridge {
 
     static native void deliverData(byte[] payload);
 
+    static native void deliverDataAt(long offset, byte[] payload);
+
     static native void log(String message);
 
     static native long now();
 
     static native void recordMetric(String name, double value);
+
+    static native void recordState(String name);
+
+    /** Something {@link GradingSandbox} blocked, e.g. "writing /tmp/answers.txt". */
+    static native void reportViolation(String what);
+
+    /** Init params flattened as key, value, key, value, ... */
+    static native String[] initParams();
+
+    static native long receiveWindow();
+
+    static native int peerMss();
+
+    static native void store(String key, String value);
+
+    /** The stored value, or null. */
+    static native String load(String key);
+
+    static native void annotatePacket(String note);
+
+    static native void appPause();
+
+    static native void appResume();
+
+    static native void refuseAppData(String reason);
+
+    /** Simulation settings flattened like {@link #initParams()}. */
+    static native String[] config();
 }
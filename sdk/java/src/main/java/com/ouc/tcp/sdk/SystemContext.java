@@ -1,5 +1,7 @@
 package com.ouc.tcp.sdk;
 
+import java.util.Map;
+
 public interface SystemContext {
     void sendPacket(Packet packet);
 
@@ -9,9 +11,65 @@ public interface SystemContext {
 
     void deliverData(byte[] data);
 
+    /**
+     * Deliver {@code data} as the bytes at {@code offset} of the reassembled stream, so the
+     * simulator can check that they are the ones sent there and that nothing was reordered.
+     */
+    default void deliverDataAt(long offset, byte[] data) {
+        deliverData(data);
+    }
+
     void log(String message);
 
     long now();
 
     void recordMetric(String name, double value);
+
+    /** Note that the protocol's state machine entered {@code name}, e.g. {@code "WAIT_ACK_0"}. */
+    void recordState(String name);
+
+    /** Parameters the scenario passes to this node ({@code [params.sender]} / {@code [params.receiver]}). */
+    Map<String, String> initParams();
+
+    /** Simulation settings as strings, e.g. {@code config().get("window_size")}; unset options are absent. */
+    Map<String, String> config();
+
+    /**
+     * Free bytes in the receiver's buffer, already reduced by data delivered in this callback;
+     * the value to advertise in ACK windows. -1 on the sender or when no buffer is modeled.
+     */
+    long receiveWindow();
+
+    /**
+     * MSS the peer advertised in its SYN; keep data segments within it and your own MSS.
+     * -1 until that SYN has arrived or when the scenario models no MSS option.
+     */
+    int peerMss();
+
+    /**
+     * Keep {@code value} under {@code key} for later runs when the host opted in to a scratch
+     * store ({@code tcp-lab-sim-cli --scratch-store FILE}); does nothing otherwise, graded runs
+     * included.
+     */
+    void store(String key, String value);
+
+    /** The value last stored under {@code key}, in this run or an earlier one; null without a scratch store. */
+    String load(String key);
+
+    /**
+     * Label the next packet this node sends (e.g. {@code "fast-retransmit"}) on its arrow in the
+     * space-time diagram and in the trace.
+     */
+    void annotatePacket(String note);
+
+    /**
+     * Hold the application's sends instead of dropping what {@code onAppData} cannot take
+     * (sender only); {@link #appResume()} hands them over in order.
+     */
+    void appPause();
+
+    void appResume();
+
+    /** Turn down the data {@code onAppData} is handing over instead of dropping it silently. */
+    void refuseAppData(String reason);
 }
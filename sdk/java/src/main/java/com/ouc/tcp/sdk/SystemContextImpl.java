@@ -1,10 +1,15 @@
 package com.ouc.tcp.sdk;
 
+import java.util.LinkedHashMap;
+import java.util.Map;
+
 public final class SystemContextImpl implements SystemContext {
     @Override
     public void sendPacket(Packet packet) {
         var header = packet.getHeader();
         NativeBridge.sendPacket(
+                header.getSrcPort(),
+                header.getDstPort(),
                 header.getSeqNum(),
                 header.getAckNum(),
                 (byte) header.getFlags(),
@@ -29,6 +34,11 @@ public final class SystemContextImpl implements SystemContext {
         NativeBridge.deliverData(data);
     }
 
+    @Override
+    public void deliverDataAt(long offset, byte[] data) {
+        NativeBridge.deliverDataAt(offset, data);
+    }
+
     @Override
     public void log(String message) {
         NativeBridge.log(message);
@@ -43,4 +53,67 @@ public final class SystemContextImpl implements SystemContext {
     public void recordMetric(String name, double value) {
         NativeBridge.recordMetric(name, value);
     }
+
+    @Override
+    public void recordState(String name) {
+        NativeBridge.recordState(name);
+    }
+
+    @Override
+    public Map<String, String> initParams() {
+        return toMap(NativeBridge.initParams());
+    }
+
+    @Override
+    public Map<String, String> config() {
+        return toMap(NativeBridge.config());
+    }
+
+    @Override
+    public long receiveWindow() {
+        return NativeBridge.receiveWindow();
+    }
+
+    @Override
+    public int peerMss() {
+        return NativeBridge.peerMss();
+    }
+
+    @Override
+    public void store(String key, String value) {
+        NativeBridge.store(key, value);
+    }
+
+    @Override
+    public String load(String key) {
+        return NativeBridge.load(key);
+    }
+
+    @Override
+    public void annotatePacket(String note) {
+        NativeBridge.annotatePacket(note);
+    }
+
+    @Override
+    public void appPause() {
+        NativeBridge.appPause();
+    }
+
+    @Override
+    public void appResume() {
+        NativeBridge.appResume();
+    }
+
+    @Override
+    public void refuseAppData(String reason) {
+        NativeBridge.refuseAppData(reason);
+    }
+
+    private static Map<String, String> toMap(String[] flat) {
+        Map<String, String> map = new LinkedHashMap<>();
+        for (int i = 0; i + 1 < flat.length; i += 2) {
+            map.put(flat[i], flat[i + 1]);
+        }
+        return map;
+    }
 }
@@ -1,6 +1,8 @@
 package com.ouc.tcp.sdk;
 
 public final class TcpHeader {
+    private int srcPort;
+    private int dstPort;
     private long seqNum;
     private long ackNum;
     private int flags;
@@ -8,6 +10,22 @@ public final class TcpHeader {
     private int checksum;
     private int urgentPointer;
 
+    public int getSrcPort() {
+        return srcPort;
+    }
+
+    public void setSrcPort(int srcPort) {
+        this.srcPort = srcPort;
+    }
+
+    public int getDstPort() {
+        return dstPort;
+    }
+
+    public void setDstPort(int dstPort) {
+        this.dstPort = dstPort;
+    }
+
     public long getSeqNum() {
         return seqNum;
     }
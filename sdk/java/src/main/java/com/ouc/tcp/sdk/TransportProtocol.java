@@ -1,6 +1,9 @@
 package com.ouc.tcp.sdk;
 
 public interface TransportProtocol {
+    /** Checked by the grader, which refuses an SDK copy of another version. */
+    int SDK_VERSION = 2;
+
     void init(SystemContext ctx);
 
     void onPacket(SystemContext ctx, Packet packet);
@@ -8,4 +11,16 @@ public interface TransportProtocol {
     void onTimer(SystemContext ctx, int timerId);
 
     void onAppData(SystemContext ctx, byte[] data);
+
+    /** The application closed the connection; finish sending what is still buffered. */
+    default void onClose(SystemContext ctx) {}
+
+    /**
+     * Diagnostic hook: return the checksum your protocol computes over {@code data}, from 0
+     * to 65535, so {@code tcp-lab-sim-cli checksum} can compare it with the reference test
+     * vectors. Never called during a simulation; -1 means the protocol exposes no checksum.
+     */
+    default int checksum(SystemContext ctx, byte[] data) {
+        return -1;
+    }
 }
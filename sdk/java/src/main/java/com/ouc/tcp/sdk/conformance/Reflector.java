@@ -0,0 +1,22 @@
+package com.ouc.tcp.sdk.conformance;
+
+import com.ouc.tcp.sdk.Packet;
+import com.ouc.tcp.sdk.SystemContext;
+import com.ouc.tcp.sdk.TransportProtocol;
+
+/** Sends every packet straight back, for the grader's {@code round-trip} check of the bridge. */
+public final class Reflector implements TransportProtocol {
+    @Override
+    public void init(SystemContext ctx) {}
+
+    @Override
+    public void onPacket(SystemContext ctx, Packet packet) {
+        ctx.sendPacket(packet);
+    }
+
+    @Override
+    public void onTimer(SystemContext ctx, int timerId) {}
+
+    @Override
+    public void onAppData(SystemContext ctx, byte[] data) {}
+}
@@ -0,0 +1,28 @@
+package com.ouc.tcp.sdk.kotlin.rdt1
+
+import com.ouc.tcp.sdk.Packet
+import com.ouc.tcp.sdk.SystemContext
+import com.ouc.tcp.sdk.TransportProtocol
+
+/**
+ * An `object`: the simulator uses its `INSTANCE` instead of constructing a new one.
+ * Keep per-run state elsewhere if you load the same object as both sender and receiver.
+ */
+object Rdt1Receiver : TransportProtocol {
+    override fun init(ctx: SystemContext) {
+        ctx.log("Kotlin RDT1 receiver ready")
+    }
+
+    override fun onPacket(ctx: SystemContext, packet: Packet) {
+        ctx.log("RDT1 receiver delivering ${packet.payload.size} bytes")
+        ctx.deliverData(packet.payload)
+    }
+
+    override fun onTimer(ctx: SystemContext, timerId: Int) {
+        ctx.log("RDT1 receiver ignores timer $timerId")
+    }
+
+    override fun onAppData(ctx: SystemContext, data: ByteArray) {
+        ctx.log("RDT1 receiver ignores outbound data of len ${data.size}")
+    }
+}
@@ -0,0 +1,29 @@
+package com.ouc.tcp.sdk.kotlin.rdt1
+
+import com.ouc.tcp.sdk.Packet
+import com.ouc.tcp.sdk.SystemContext
+import com.ouc.tcp.sdk.TcpHeader
+import com.ouc.tcp.sdk.TransportProtocol
+
+/**
+ * A plain class: the simulator calls its no-argument constructor.
+ * Constructor parameters are fine as long as every one has a default value.
+ */
+class Rdt1Sender(private val greeting: String = "Kotlin RDT1 sender ready") : TransportProtocol {
+    override fun init(ctx: SystemContext) {
+        ctx.log(greeting)
+    }
+
+    override fun onPacket(ctx: SystemContext, packet: Packet) {
+        ctx.log("RDT1 sender ignoring inbound packet seq=${packet.header.seqNum}")
+    }
+
+    override fun onTimer(ctx: SystemContext, timerId: Int) {
+        ctx.log("RDT1 sender ignores timer $timerId")
+    }
+
+    override fun onAppData(ctx: SystemContext, data: ByteArray) {
+        ctx.log("RDT1 sender sending ${data.size} bytes")
+        ctx.sendPacket(Packet(TcpHeader(), data))
+    }
+}
@@ -2,4 +2,8 @@
 
 from .structs import Packet, TcpHeader
 
-__all__ = ["Packet", "TcpHeader"]
+# Checked by the grader, which refuses an SDK copy of another version. Bumped together
+# with SDK_VERSION in crates/tcp-lab-loader.
+SDK_VERSION = 2
+
+__all__ = ["Packet", "TcpHeader", "SDK_VERSION"]
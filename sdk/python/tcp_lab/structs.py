@@ -12,6 +12,8 @@ class TcpHeader:
     window_size: int = 0
     checksum: int = 0
     urgent_ptr: int = 0
+    src_port: int = 0
+    dst_port: int = 0
 
     def setSeqNum(self, value: int) -> None:  # JNI expects camelCase setters
         self.seq_num = value
@@ -31,6 +33,12 @@ class TcpHeader:
     def setUrgentPtr(self, value: int) -> None:
         self.urgent_ptr = value
 
+    def setSrcPort(self, value: int) -> None:
+        self.src_port = value
+
+    def setDstPort(self, value: int) -> None:
+        self.dst_port = value
+
 
 @dataclass
 class Packet:
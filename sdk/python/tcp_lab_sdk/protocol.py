@@ -1,7 +1,7 @@
 from __future__ import annotations
 
 from abc import ABC, abstractmethod
-from typing import Protocol
+from typing import Any, Protocol
 
 from tcp_lab.structs import Packet
 
@@ -15,12 +15,36 @@ class SystemContext(Protocol):
 
     def deliver_data(self, data: bytes) -> None: ...
 
+    def deliver_data_at(self, offset: int, data: bytes) -> None: ...
+
     def log(self, message: str) -> None: ...
 
     def now(self) -> int: ...
 
     def record_metric(self, name: str, value: float) -> None: ...
 
+    def record_state(self, name: str) -> None: ...
+
+    def init_params(self) -> dict[str, str]: ...
+
+    def config(self) -> dict[str, Any]: ...
+
+    def receive_window(self) -> int | None: ...
+
+    def peer_mss(self) -> int | None: ...
+
+    def store(self, key: str, value: str) -> None: ...
+
+    def load(self, key: str) -> str | None: ...
+
+    def annotate_packet(self, note: str) -> None: ...
+
+    def app_pause(self) -> None: ...
+
+    def app_resume(self) -> None: ...
+
+    def refuse_app_data(self, reason: str) -> None: ...
+
 
 class BaseTransportProtocol(ABC):
     """Students should inherit from this base class."""
@@ -38,3 +62,15 @@ class BaseTransportProtocol(ABC):
     @abstractmethod
     def on_app_data(self, ctx: SystemContext, data: bytes) -> None:
         ...
+
+    def on_close(self, ctx: SystemContext) -> None:
+        """The application closed the connection; finish sending what is still buffered."""
+        pass
+
+    def checksum(self, ctx: SystemContext, data: bytes) -> int | None:
+        """Diagnostic hook: return the checksum your protocol computes over `data`.
+
+        `tcp-lab-sim-cli checksum` compares it with the reference test vectors; it is never
+        called during a simulation. `None` means the protocol exposes no checksum.
+        """
+        return None
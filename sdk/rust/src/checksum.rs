@@ -20,3 +20,49 @@ pub fn internet_checksum(data: &[u8]) -> u16 {
 
     !(sum as u16)
 }
+
+/// An input and the checksum every SDK's helper must return for it.
+#[derive(Debug, Clone)]
+pub struct TestVector {
+    pub name: &'static str,
+    pub data: Vec<u8>,
+    pub checksum: u16,
+}
+
+/// The canonical checksum test vectors. `tcp-lab-sim-cli checksum` checks a protocol's own
+/// checksum against them, and the Java, Python and C++ helpers agree with every one.
+pub fn test_vectors() -> Vec<TestVector> {
+    let vector = |name, data: &[u8], checksum| TestVector {
+        name,
+        data: data.to_vec(),
+        checksum,
+    };
+    vec![
+        vector("empty", b"", 0xFFFF),
+        // An odd trailing byte is padded with a zero byte on the right, not the left.
+        vector("one_byte", &[0xAB], 0x54FF),
+        vector("odd_length", b"abc", 0x3B9D),
+        // The worked example of RFC 1071, section 3.
+        vector(
+            "rfc1071",
+            &[0x00, 0x01, 0xF2, 0x03, 0xF4, 0xF5, 0xF6, 0xF7],
+            0x220D,
+        ),
+        vector("text", b"Packet 1", 0xC68D),
+        vector("zeros", &[0; 16], 0xFFFF),
+        vector("all_ones", &[0xFF; 16], 0x0000),
+        // 0xFFFF + 0x0001 overflows 16 bits; the carry must wrap around.
+        vector("end_around_carry", &[0xFF, 0xFF, 0x00, 0x01], 0xFFFE),
+        vector("sums_to_ffff", &[0xFF, 0x00, 0x00, 0xFF], 0x0000),
+        TestVector {
+            name: "counter_1500",
+            data: (0..1500).map(|i| (i % 256) as u8).collect(),
+            checksum: 0x394E,
+        },
+        TestVector {
+            name: "payload_64k",
+            data: (0..64 * 1024).map(|i| (i % 251) as u8).collect(),
+            checksum: 0x9303,
+        },
+    ]
+}
@@ -50,9 +50,9 @@ impl TransportProtocol for Rdt1Receiver {
 }
 
 pub fn sender() -> Box<dyn TransportProtocol> {
-    Box::new(Rdt1Sender::default())
+    Box::new(Rdt1Sender)
 }
 
 pub fn receiver() -> Box<dyn TransportProtocol> {
-    Box::new(Rdt1Receiver::default())
+    Box::new(Rdt1Receiver)
 }